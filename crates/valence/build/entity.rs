@@ -120,7 +120,7 @@ impl Value {
             Value::String(_) => quote!(Box<str>),
             Value::TextComponent(_) => quote!(Text),
             Value::OptionalTextComponent(_) => quote!(Option<Text>),
-            Value::ItemStack(_) => quote!(()), // TODO
+            Value::ItemStack(_) => quote!(Option<ItemStack>),
             Value::Boolean(_) => quote!(bool),
             Value::Rotation { .. } => quote!(EulerAngle),
             Value::BlockPos(_) => quote!(BlockPos),
@@ -145,6 +145,7 @@ impl Value {
             Value::String(_) => quote!(&str),
             Value::TextComponent(_) => quote!(&Text),
             Value::OptionalTextComponent(_) => quote!(Option<&Text>),
+            Value::ItemStack(_) => quote!(Option<&ItemStack>),
             Value::NbtCompound(_) => quote!(&valence_nbt::Compound),
             _ => self.field_type(),
         }
@@ -155,7 +156,9 @@ impl Value {
             Value::String(_) | Value::TextComponent(_) | Value::NbtCompound(_) => {
                 quote!(&self.#field_name)
             }
-            Value::OptionalTextComponent(_) => quote!(self.#field_name.as_ref()),
+            Value::OptionalTextComponent(_) | Value::ItemStack(_) => {
+                quote!(self.#field_name.as_ref())
+            }
             _ => quote!(self.#field_name),
         }
     }
@@ -172,7 +175,8 @@ impl Value {
                 assert!(t.is_none());
                 quote!(None)
             }
-            Value::ItemStack(_) => quote!(()), // TODO
+            // The stacks default to one air, which is sent as an empty slot.
+            Value::ItemStack(_) => quote!(None),
             Value::Boolean(b) => quote!(#b),
             Value::Rotation { pitch, yaw, roll } => quote! {
                 EulerAngle {
@@ -420,6 +424,95 @@ pub fn build() -> anyhow::Result<TokenStream> {
         }
     });
 
+    // Accessors for the fields shared by every entity are also exposed directly on
+    // `TrackedData` so they can be used without matching on the entity kind.
+    let base_entity_accessors = entities["Entity"].fields.iter().map(|field| {
+        let field_name = ident(&field.name);
+        let field_type = field.default_value.field_type();
+
+        if !field.bits.is_empty() {
+            field
+                .bits
+                .iter()
+                .map(|bit| {
+                    let bit_name = ident(&bit.name);
+                    let getter_name = ident(format!("get_{}", &bit.name));
+                    let setter_name = ident(format!("set_{}", &bit.name));
+
+                    quote! {
+                        pub fn #getter_name(&self) -> bool {
+                            match self {
+                                #(Self::#concrete_entity_names(e) => e.#getter_name(),)*
+                            }
+                        }
+
+                        pub fn #setter_name(&mut self, #bit_name: bool) {
+                            match self {
+                                #(Self::#concrete_entity_names(e) => e.#setter_name(#bit_name),)*
+                            }
+                        }
+                    }
+                })
+                .collect::<TokenStream>()
+        } else {
+            let getter_name = ident(format!("get_{}", &field.name));
+            let setter_name = ident(format!("set_{}", &field.name));
+            let getter_return_type = field.default_value.getter_return_type();
+
+            quote! {
+                pub fn #getter_name(&self) -> #getter_return_type {
+                    match self {
+                        #(Self::#concrete_entity_names(e) => e.#getter_name(),)*
+                    }
+                }
+
+                pub fn #setter_name(&mut self, #field_name: impl Into<#field_type>) {
+                    let #field_name = #field_name.into();
+                    match self {
+                        #(Self::#concrete_entity_names(e) => e.#setter_name(#field_name),)*
+                    }
+                }
+            }
+        }
+    });
+
+    let living_entity_names = concrete_entities
+        .keys()
+        .filter(|name| inherits_from(name, "Living", &entities))
+        .map(ident)
+        .collect::<Vec<_>>();
+
+    // Accessors for the fields shared by every living entity. The getters return
+    // `None` and the setters do nothing for entities that are not alive.
+    let living_entity_accessors = entities["Living"]
+        .fields
+        .iter()
+        .filter(|field| field.bits.is_empty())
+        .map(|field| {
+            let field_name = ident(&field.name);
+            let field_type = field.default_value.field_type();
+            let getter_name = ident(format!("get_{}", &field.name));
+            let setter_name = ident(format!("set_{}", &field.name));
+            let getter_return_type = field.default_value.getter_return_type();
+
+            quote! {
+                pub fn #getter_name(&self) -> Option<#getter_return_type> {
+                    match self {
+                        #(Self::#living_entity_names(e) => Some(e.#getter_name()),)*
+                        _ => None,
+                    }
+                }
+
+                pub fn #setter_name(&mut self, #field_name: impl Into<#field_type>) {
+                    let #field_name = #field_name.into();
+                    match self {
+                        #(Self::#living_entity_names(e) => e.#setter_name(#field_name),)*
+                        _ => {}
+                    }
+                }
+            }
+        });
+
     let translation_key_arms = concrete_entities.iter().map(|(k, v)| {
         let name = ident(k);
         let key = v
@@ -493,12 +586,36 @@ pub fn build() -> anyhow::Result<TokenStream> {
                     #(Self::#concrete_entity_names(e) => e.clear_modifications(),)*
                 }
             }
+
+            /// Returns `true` if this is the tracked data of a living entity.
+            pub fn is_living(&self) -> bool {
+                matches!(self, #(Self::#living_entity_names(_))|*)
+            }
+
+            #(#base_entity_accessors)*
+
+            #(#living_entity_accessors)*
         }
 
         #(#concrete_entity_structs)*
     })
 }
 
+fn inherits_from(entity_name: &str, ancestor: &str, entities: &Entities) -> bool {
+    let mut name = entity_name;
+
+    loop {
+        if name == ancestor {
+            return true;
+        }
+
+        match &entities[name].parent {
+            Some(parent) => name = parent,
+            None => return false,
+        }
+    }
+}
+
 fn collect_all_fields<'a>(entity_name: &str, entities: &'a Entities) -> Vec<&'a Field> {
     fn rec<'a>(entity_name: &str, entities: &'a Entities, fields: &mut Vec<&'a Field>) {
         let e = &entities[entity_name];
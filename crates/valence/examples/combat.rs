@@ -1,7 +1,7 @@
 use glam::Vec3Swizzles;
 use valence::client::despawn_disconnected_clients;
 use valence::client::event::{
-    default_event_handler, InteractWithEntity, StartSprinting, StopSprinting,
+    default_event_handler, AttackEntityEvent, StartSprinting, StopSprinting,
 };
 use valence::prelude::*;
 
@@ -88,11 +88,10 @@ fn init_clients(
 }
 
 fn handle_combat_events(
-    manager: Res<McEntityManager>,
     server: Res<Server>,
     mut start_sprinting: EventReader<StartSprinting>,
     mut stop_sprinting: EventReader<StopSprinting>,
-    mut interact_with_entity: EventReader<InteractWithEntity>,
+    mut attack_entity: EventReader<AttackEntityEvent>,
     mut clients: Query<(&mut Client, &mut CombatState, &mut McEntity)>,
 ) {
     for &StartSprinting { client } in start_sprinting.iter() {
@@ -107,17 +106,12 @@ fn handle_combat_events(
         }
     }
 
-    for &InteractWithEntity {
+    for &AttackEntityEvent {
         client: attacker_client,
-        entity_id,
+        target: victim_client,
         ..
-    } in interact_with_entity.iter()
+    } in attack_entity.iter()
     {
-        let Some(victim_client) = manager.get_with_protocol_id(entity_id) else {
-            // Attacked entity doesn't exist.
-            continue
-        };
-
         let Ok([(attacker_client, mut attacker_state, _), (mut victim_client, mut victim_state, mut victim_entity)]) =
             clients.get_many_mut([attacker_client, victim_client])
         else {
@@ -1,9 +1,8 @@
 use valence::client::despawn_disconnected_clients;
 use valence::client::event::{
-    default_event_handler, InteractWithEntity, ResourcePackStatus, ResourcePackStatusChange,
+    default_event_handler, AttackEntityEvent, ResourcePackStatus, ResourcePackStatusChange,
 };
 use valence::prelude::*;
-use valence_protocol::types::EntityInteraction;
 
 const SPAWN_Y: i32 = 64;
 
@@ -59,19 +58,17 @@ fn init_clients(
     }
 }
 
-fn prompt_on_punch(mut clients: Query<&mut Client>, mut events: EventReader<InteractWithEntity>) {
+fn prompt_on_punch(mut clients: Query<&mut Client>, mut events: EventReader<AttackEntityEvent>) {
     for event in events.iter() {
         let Ok(mut client) = clients.get_mut(event.client) else {
             continue;
         };
-        if event.interact == EntityInteraction::Attack {
-            client.set_resource_pack(
-                "https://github.com/valence-rs/valence/raw/main/assets/example_pack.zip",
-                "d7c6108849fb190ec2a49f2d38b7f1f897d9ce9f",
-                false,
-                None,
-            );
-        }
+        client.set_resource_pack(
+            "https://github.com/valence-rs/valence/raw/main/assets/example_pack.zip",
+            "d7c6108849fb190ec2a49f2d38b7f1f897d9ce9f",
+            false,
+            None,
+        );
     }
 }
 
@@ -0,0 +1,422 @@
+//! Banners and decorated shields.
+//!
+//! A [`Banner`] is a base color with a list of colored pattern layers drawn on
+//! top of it. It can be turned into a banner item, a shield decorated with the
+//! banner or a banner block, and read back from any of them.
+
+use valence_nbt::{compound, Compound, List, Value};
+use valence_protocol::{BlockKind, BlockState, ItemKind, ItemStack};
+
+use crate::instance::{Block, BlockRef};
+
+/// The sixteen dye colors.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum DyeColor {
+    White,
+    Orange,
+    Magenta,
+    LightBlue,
+    Yellow,
+    Lime,
+    Pink,
+    Gray,
+    LightGray,
+    Cyan,
+    Purple,
+    Blue,
+    Brown,
+    Green,
+    Red,
+    Black,
+}
+
+impl DyeColor {
+    pub const ALL: [Self; 16] = [
+        Self::White,
+        Self::Orange,
+        Self::Magenta,
+        Self::LightBlue,
+        Self::Yellow,
+        Self::Lime,
+        Self::Pink,
+        Self::Gray,
+        Self::LightGray,
+        Self::Cyan,
+        Self::Purple,
+        Self::Blue,
+        Self::Brown,
+        Self::Green,
+        Self::Red,
+        Self::Black,
+    ];
+
+    /// Returns the numeric ID of the color.
+    pub const fn id(self) -> i32 {
+        self as i32
+    }
+
+    pub fn from_id(id: i32) -> Option<Self> {
+        Self::ALL.get(usize::try_from(id).ok()?).copied()
+    }
+
+    /// Returns the name of the color, as used in the names of blocks and
+    /// items.
+    pub const fn to_str(self) -> &'static str {
+        match self {
+            Self::White => "white",
+            Self::Orange => "orange",
+            Self::Magenta => "magenta",
+            Self::LightBlue => "light_blue",
+            Self::Yellow => "yellow",
+            Self::Lime => "lime",
+            Self::Pink => "pink",
+            Self::Gray => "gray",
+            Self::LightGray => "light_gray",
+            Self::Cyan => "cyan",
+            Self::Purple => "purple",
+            Self::Blue => "blue",
+            Self::Brown => "brown",
+            Self::Green => "green",
+            Self::Red => "red",
+            Self::Black => "black",
+        }
+    }
+}
+
+/// A pattern which can be drawn on a banner.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum BannerPattern {
+    Base,
+    SquareBottomLeft,
+    SquareBottomRight,
+    SquareTopLeft,
+    SquareTopRight,
+    StripeBottom,
+    StripeTop,
+    StripeLeft,
+    StripeRight,
+    StripeCenter,
+    StripeMiddle,
+    StripeDownright,
+    StripeDownleft,
+    SmallStripes,
+    Cross,
+    StraightCross,
+    TriangleBottom,
+    TriangleTop,
+    TrianglesBottom,
+    TrianglesTop,
+    DiagonalLeft,
+    DiagonalUpRight,
+    DiagonalUpLeft,
+    DiagonalRight,
+    Circle,
+    Rhombus,
+    HalfVertical,
+    HalfHorizontal,
+    HalfVerticalRight,
+    HalfHorizontalBottom,
+    Border,
+    CurlyBorder,
+    Gradient,
+    GradientUp,
+    Bricks,
+    Globe,
+    Creeper,
+    Skull,
+    Flower,
+    Mojang,
+    Piglin,
+}
+
+impl BannerPattern {
+    pub const ALL: [Self; 41] = [
+        Self::Base,
+        Self::SquareBottomLeft,
+        Self::SquareBottomRight,
+        Self::SquareTopLeft,
+        Self::SquareTopRight,
+        Self::StripeBottom,
+        Self::StripeTop,
+        Self::StripeLeft,
+        Self::StripeRight,
+        Self::StripeCenter,
+        Self::StripeMiddle,
+        Self::StripeDownright,
+        Self::StripeDownleft,
+        Self::SmallStripes,
+        Self::Cross,
+        Self::StraightCross,
+        Self::TriangleBottom,
+        Self::TriangleTop,
+        Self::TrianglesBottom,
+        Self::TrianglesTop,
+        Self::DiagonalLeft,
+        Self::DiagonalUpRight,
+        Self::DiagonalUpLeft,
+        Self::DiagonalRight,
+        Self::Circle,
+        Self::Rhombus,
+        Self::HalfVertical,
+        Self::HalfHorizontal,
+        Self::HalfVerticalRight,
+        Self::HalfHorizontalBottom,
+        Self::Border,
+        Self::CurlyBorder,
+        Self::Gradient,
+        Self::GradientUp,
+        Self::Bricks,
+        Self::Globe,
+        Self::Creeper,
+        Self::Skull,
+        Self::Flower,
+        Self::Mojang,
+        Self::Piglin,
+    ];
+
+    /// Returns the short code identifying the pattern in NBT.
+    pub const fn code(self) -> &'static str {
+        match self {
+            Self::Base => "b",
+            Self::SquareBottomLeft => "bl",
+            Self::SquareBottomRight => "br",
+            Self::SquareTopLeft => "tl",
+            Self::SquareTopRight => "tr",
+            Self::StripeBottom => "bs",
+            Self::StripeTop => "ts",
+            Self::StripeLeft => "ls",
+            Self::StripeRight => "rs",
+            Self::StripeCenter => "cs",
+            Self::StripeMiddle => "ms",
+            Self::StripeDownright => "drs",
+            Self::StripeDownleft => "dls",
+            Self::SmallStripes => "ss",
+            Self::Cross => "cr",
+            Self::StraightCross => "sc",
+            Self::TriangleBottom => "bt",
+            Self::TriangleTop => "tt",
+            Self::TrianglesBottom => "bts",
+            Self::TrianglesTop => "tts",
+            Self::DiagonalLeft => "ld",
+            Self::DiagonalUpRight => "rd",
+            Self::DiagonalUpLeft => "lud",
+            Self::DiagonalRight => "rud",
+            Self::Circle => "mc",
+            Self::Rhombus => "mr",
+            Self::HalfVertical => "vh",
+            Self::HalfHorizontal => "hh",
+            Self::HalfVerticalRight => "vhr",
+            Self::HalfHorizontalBottom => "hhb",
+            Self::Border => "bo",
+            Self::CurlyBorder => "cbo",
+            Self::Gradient => "gra",
+            Self::GradientUp => "gru",
+            Self::Bricks => "bri",
+            Self::Globe => "glb",
+            Self::Creeper => "cre",
+            Self::Skull => "sku",
+            Self::Flower => "flo",
+            Self::Mojang => "moj",
+            Self::Piglin => "pig",
+        }
+    }
+
+    pub fn from_code(code: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|pattern| pattern.code() == code)
+    }
+}
+
+/// A single pattern layer of a [`Banner`].
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub struct BannerLayer {
+    pub pattern: BannerPattern,
+    pub color: DyeColor,
+}
+
+/// The design of a banner.
+///
+/// Layers are drawn in order on top of the base color. Clients display at
+/// most 16 layers, although only 6 can be added in survival.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Banner {
+    pub base: DyeColor,
+    pub layers: Vec<BannerLayer>,
+}
+
+impl Banner {
+    pub fn new(base: DyeColor) -> Self {
+        Self {
+            base,
+            layers: vec![],
+        }
+    }
+
+    /// Adds a pattern layer on top of the existing layers.
+    #[must_use]
+    pub fn with_layer(mut self, pattern: BannerPattern, color: DyeColor) -> Self {
+        self.layers.push(BannerLayer { pattern, color });
+        self
+    }
+
+    /// Returns the kind of the standing banner block with the base color of
+    /// this banner.
+    pub fn block_kind(&self) -> BlockKind {
+        BlockKind::from_str(&format!("{}_banner", self.base.to_str()))
+            .expect("missing banner block")
+    }
+
+    /// Returns the kind of the wall banner block with the base color of this
+    /// banner.
+    pub fn wall_block_kind(&self) -> BlockKind {
+        BlockKind::from_str(&format!("{}_wall_banner", self.base.to_str()))
+            .expect("missing wall banner block")
+    }
+
+    fn patterns_nbt(&self) -> List {
+        List::Compound(
+            self.layers
+                .iter()
+                .map(|layer| {
+                    compound! {
+                        "Pattern" => layer.pattern.code(),
+                        "Color" => layer.color.id(),
+                    }
+                })
+                .collect(),
+        )
+    }
+
+    /// Reads the layers from a block entity compound containing a `Patterns`
+    /// list. Unknown patterns and colors are skipped.
+    fn layers_from_nbt(nbt: &Compound) -> Vec<BannerLayer> {
+        let Some(Value::List(List::Compound(patterns))) = nbt.get("Patterns") else {
+            return vec![]
+        };
+
+        patterns
+            .iter()
+            .filter_map(|layer| {
+                let Some(Value::String(code)) = layer.get("Pattern") else {
+                    return None
+                };
+
+                let Some(Value::Int(color)) = layer.get("Color") else {
+                    return None
+                };
+
+                Some(BannerLayer {
+                    pattern: BannerPattern::from_code(code)?,
+                    color: DyeColor::from_id(*color)?,
+                })
+            })
+            .collect()
+    }
+
+    /// Creates a banner item with this design.
+    pub fn to_item(&self) -> ItemStack {
+        let nbt = (!self.layers.is_empty()).then(|| {
+            compound! {
+                "BlockEntityTag" => compound! {
+                    "Patterns" => self.patterns_nbt(),
+                },
+            }
+        });
+
+        ItemStack::new(self.block_kind().to_item_kind(), 1, nbt)
+    }
+
+    /// Creates a shield decorated with this banner.
+    pub fn to_shield(&self) -> ItemStack {
+        ItemStack::new(
+            ItemKind::Shield,
+            1,
+            Some(compound! {
+                "BlockEntityTag" => compound! {
+                    "Base" => self.base.id(),
+                    "Patterns" => self.patterns_nbt(),
+                },
+            }),
+        )
+    }
+
+    /// Creates a banner block with this design. `state` should be a state of
+    /// [`Banner::block_kind`] or [`Banner::wall_block_kind`], which determines
+    /// the rotation or facing of the banner.
+    pub fn to_block(&self, state: BlockState) -> Block {
+        Block::with_nbt(
+            state,
+            compound! {
+                "Patterns" => self.patterns_nbt(),
+            },
+        )
+    }
+
+    /// Reads the design of a banner item or decorated shield.
+    pub fn from_item(stack: &ItemStack) -> Option<Self> {
+        let tag = match stack.nbt.as_ref().and_then(|nbt| nbt.get("BlockEntityTag")) {
+            Some(Value::Compound(tag)) => Some(tag),
+            _ => None,
+        };
+
+        let base = if stack.item == ItemKind::Shield {
+            match tag?.get("Base") {
+                Some(Value::Int(base)) => DyeColor::from_id(*base)?,
+                _ => return None,
+            }
+        } else {
+            DyeColor::ALL
+                .into_iter()
+                .find(|color| stack.item.to_str().strip_suffix("_banner") == Some(color.to_str()))?
+        };
+
+        Some(Self {
+            base,
+            layers: tag.map(Self::layers_from_nbt).unwrap_or_default(),
+        })
+    }
+
+    /// Reads the design of a banner block.
+    pub fn from_block(block: BlockRef) -> Option<Self> {
+        let name = block.state().to_kind().to_str();
+        let color = name
+            .strip_suffix("_wall_banner")
+            .or_else(|| name.strip_suffix("_banner"))?;
+
+        Some(Self {
+            base: DyeColor::ALL
+                .into_iter()
+                .find(|base| base.to_str() == color)?,
+            layers: block.nbt().map(Self::layers_from_nbt).unwrap_or_default(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use valence_protocol::block::{PropName, PropValue};
+
+    use super::*;
+    use crate::instance::Chunk;
+
+    #[test]
+    fn banner_round_trip() {
+        let banner = Banner::new(DyeColor::LightBlue)
+            .with_layer(BannerPattern::Creeper, DyeColor::Black)
+            .with_layer(BannerPattern::Border, DyeColor::Orange);
+
+        let item = banner.to_item();
+        assert_eq!(item.item, ItemKind::LightBlueBanner);
+        assert_eq!(Banner::from_item(&item), Some(banner.clone()));
+
+        let shield = banner.to_shield();
+        assert_eq!(Banner::from_item(&shield), Some(banner.clone()));
+
+        let state =
+            BlockState::from_kind(banner.wall_block_kind()).set(PropName::Facing, PropValue::South);
+
+        let mut chunk = Chunk::new(1);
+        chunk.set_block(0, 0, 0, banner.to_block(state));
+
+        assert_eq!(Banner::from_block(chunk.block(0, 0, 0)), Some(banner));
+    }
+}
@@ -0,0 +1,239 @@
+//! Keeping the duration of ticks within a budget under load.
+//!
+//! The [`TickBudget`] resource measures how much of every tick is spent in
+//! each [`TickPhase`]. When the average tick recorded in the [`TickStats`]
+//! takes longer than the budget, the server is overloaded and starts shedding
+//! the kinds of [`SheddableWork`] enabled in the budget until it recovers.
+//! Every tick work is shed on, a [`WorkShed`] event is sent for each kind of
+//! work.
+//!
+//! Shedding trades responsiveness for a stable tick rate: generated chunks
+//! arrive more slowly, fire and weather stop changing the world and animals
+//! and villagers stop acting on their own, while movement, chat and everything
+//! else keeps running at full speed.
+
+use std::collections::HashSet;
+use std::mem;
+use std::time::{Duration, Instant};
+
+use bevy_ecs::prelude::*;
+
+use crate::tick_stats::TickStats;
+
+/// The fraction of the budget the average tick duration needs to drop below
+/// before the server stops shedding work.
+const RECOVERY_FRACTION: f64 = 0.8;
+
+/// A part of the tick whose cost is measured separately.
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub enum TickPhase {
+    /// Caching the chunk and entity update packets of instances.
+    Instances,
+    /// Sending packets to clients.
+    Clients,
+    /// Everything else, including user systems.
+    Simulation,
+}
+
+impl TickPhase {
+    pub const ALL: [Self; 3] = [Self::Instances, Self::Clients, Self::Simulation];
+}
+
+/// Work which can be shed while the server is overloaded.
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub enum SheddableWork {
+    /// Fewer generated chunks are inserted into instances every tick, which
+    /// reduces the number of chunks sent to clients.
+    ChunkInsertions,
+    /// Fire does not spread or burn out, and weather does not randomly strike
+    /// lightning, cover the ground in snow or fill cauldrons.
+    RandomTicks,
+    /// Pets do not follow their owners, animals in love do not breed, ridden
+    /// horses do not become tamed and villagers do not restock their trades.
+    EntityAi,
+}
+
+impl SheddableWork {
+    pub const ALL: [Self; 3] = [Self::ChunkInsertions, Self::RandomTicks, Self::EntityAi];
+}
+
+/// An event sent on every tick the server sheds work.
+#[derive(Clone, PartialEq, Debug)]
+pub struct WorkShed {
+    /// The kind of work which is shed.
+    pub work: SheddableWork,
+    /// The average tick duration which caused the work to be shed.
+    pub average_tick_duration: Duration,
+}
+
+/// A [`Resource`] which measures the cost of the phases of ticks and sheds
+/// work when ticks exceed the budget.
+#[derive(Resource, Debug)]
+pub struct TickBudget {
+    /// The duration the average tick should stay below.
+    ///
+    /// # Default Value
+    ///
+    /// The duration of a tick at the configured tick rate.
+    pub budget: Duration,
+    /// The kinds of work which may be shed while the server is overloaded.
+    ///
+    /// # Default Value
+    ///
+    /// All of [`SheddableWork::ALL`].
+    pub sheddable: HashSet<SheddableWork>,
+    /// The costs of the phases of the previous tick.
+    phase_costs: [Duration; 3],
+    /// The costs of the phases of the current tick so far.
+    current_phase_costs: [Duration; 3],
+    /// When the current phase started.
+    phase_start: Option<Instant>,
+    overloaded: bool,
+}
+
+impl TickBudget {
+    pub fn new(budget: Duration) -> Self {
+        Self {
+            budget,
+            sheddable: SheddableWork::ALL.into_iter().collect(),
+            phase_costs: [Duration::ZERO; 3],
+            current_phase_costs: [Duration::ZERO; 3],
+            phase_start: None,
+            overloaded: false,
+        }
+    }
+
+    /// Returns how long the phase took in the previous tick.
+    pub fn phase_cost(&self, phase: TickPhase) -> Duration {
+        self.phase_costs[phase as usize]
+    }
+
+    /// Returns `true` if the average tick exceeds the budget. The server stays
+    /// overloaded until the average tick is well below the budget again.
+    pub fn is_overloaded(&self) -> bool {
+        self.overloaded
+    }
+
+    /// Returns `true` if the work is shed on the current tick.
+    pub fn is_shedding(&self, work: SheddableWork) -> bool {
+        self.overloaded && self.sheddable.contains(&work)
+    }
+
+    /// Completes the phase costs of the previous tick, whose duration was
+    /// the last one recorded in the [`TickStats`], and updates whether the
+    /// server is overloaded.
+    fn finish_tick(&mut self, stats: &TickStats) {
+        let mut costs = mem::take(&mut self.current_phase_costs);
+
+        costs[TickPhase::Simulation as usize] = stats
+            .last_tick_duration()
+            .saturating_sub(costs[TickPhase::Instances as usize])
+            .saturating_sub(costs[TickPhase::Clients as usize]);
+
+        self.phase_costs = costs;
+
+        let average = stats.average_tick_duration();
+
+        if average > self.budget {
+            self.overloaded = true;
+        } else if average < self.budget.mul_f64(RECOVERY_FRACTION) {
+            self.overloaded = false;
+        }
+    }
+
+    pub(crate) fn start_phase(&mut self) {
+        self.phase_start = Some(Instant::now());
+    }
+
+    /// Adds the time since [`Self::start_phase`] to the cost of the phase.
+    pub(crate) fn end_phase(&mut self, phase: TickPhase) {
+        if let Some(start) = self.phase_start.take() {
+            self.current_phase_costs[phase as usize] += start.elapsed();
+        }
+    }
+}
+
+pub(crate) fn start_tick_budget(
+    mut budget: ResMut<TickBudget>,
+    stats: Res<TickStats>,
+    mut shed: EventWriter<WorkShed>,
+) {
+    budget.finish_tick(&stats);
+
+    if budget.overloaded {
+        let average_tick_duration = stats.average_tick_duration();
+
+        let mut work: Vec<_> = budget.sheddable.iter().copied().collect();
+        work.sort_unstable();
+
+        shed.send_batch(work.into_iter().map(|work| WorkShed {
+            work,
+            average_tick_duration,
+        }));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy_app::App;
+
+    use super::*;
+    use crate::unit_test::util::scenario_single_client;
+
+    fn run(stats: &mut TickStats, budget: &mut TickBudget, ticks: u32, mspt: u64) {
+        for _ in 0..ticks {
+            stats.record_tick(Instant::now(), Duration::from_millis(mspt));
+            budget.finish_tick(stats);
+        }
+    }
+
+    #[test]
+    fn overload_sheds_work() {
+        let mut stats = TickStats::new(20);
+        let mut budget = TickBudget::new(Duration::from_millis(50));
+        budget.sheddable.remove(&SheddableWork::EntityAi);
+
+        run(&mut stats, &mut budget, 10, 20);
+        assert!(!budget.is_overloaded());
+
+        run(&mut stats, &mut budget, 20, 200);
+        assert!(budget.is_overloaded());
+        assert!(budget.is_shedding(SheddableWork::RandomTicks));
+        assert!(!budget.is_shedding(SheddableWork::EntityAi));
+        assert_eq!(
+            budget.phase_cost(TickPhase::Simulation),
+            Duration::from_millis(200)
+        );
+
+        // Dropping just below the budget is not enough to recover.
+        run(&mut stats, &mut budget, 100, 45);
+        assert!(stats.average_tick_duration() < budget.budget);
+        assert!(budget.is_overloaded());
+
+        run(&mut stats, &mut budget, 50, 10);
+        assert!(!budget.is_overloaded());
+    }
+
+    #[test]
+    fn shed_work_is_reported() {
+        let mut app = App::new();
+        scenario_single_client(&mut app);
+
+        for _ in 0..5 {
+            app.world
+                .resource_mut::<TickStats>()
+                .record_tick(Instant::now(), Duration::from_secs(1));
+        }
+
+        app.update();
+
+        let events = app.world.resource::<Events<WorkShed>>();
+        let work: Vec<_> = events
+            .get_reader()
+            .iter(events)
+            .map(|event| event.work)
+            .collect();
+
+        assert_eq!(work, SheddableWork::ALL);
+    }
+}
@@ -0,0 +1,266 @@
+//! Bundles and the items inside them.
+//!
+//! Bundles keep their contents in the `Items` list of their NBT, newest item
+//! first. Every item takes up some of the [`BUNDLE_CAPACITY`] of a bundle
+//! depending on how well it stacks: a full stack of anything fills a bundle.
+//!
+//! Valence carries out bundle clicks on the server. Right-clicking an item
+//! with a bundle on the cursor, or right-clicking a bundle with an item on the
+//! cursor, puts as much of the item as fits into the bundle. Right-clicking
+//! with an empty hand or on an empty slot takes the newest item back out.
+//! Using a bundle drops all of its contents in front of the client.
+//!
+//! The contents of a bundle can be read and changed with the functions in
+//! this module:
+//!
+//! ```
+//! use valence::bundle::{bundle_contents, bundle_weight, insert_into_bundle};
+//! use valence::prelude::*;
+//!
+//! let mut bundle = ItemStack::new(ItemKind::Bundle, 1, None);
+//! let pearls = ItemStack::new(ItemKind::EnderPearl, 16, None);
+//!
+//! assert_eq!(insert_into_bundle(&mut bundle, &pearls), 16);
+//! assert_eq!(bundle_weight(&bundle), 64);
+//! assert_eq!(bundle_contents(&bundle), vec![pearls]);
+//! ```
+
+use bevy_ecs::prelude::*;
+use glam::DVec3;
+use valence_nbt::{List, Value};
+use valence_protocol::types::SoundCategory;
+use valence_protocol::{ItemKind, ItemStack, Sound};
+
+use crate::client::event::UseItem;
+use crate::client::Client;
+use crate::crafting::can_stack;
+use crate::ender::look_direction;
+use crate::entity::dropped_item::ItemEntityBundle;
+use crate::entity::equipment::{stack_from_nbt, stack_to_nbt};
+use crate::instance::Instance;
+use crate::inventory::{hand_slot, Inventory};
+
+/// The total weight of the items a bundle can hold.
+pub const BUNDLE_CAPACITY: u32 = 64;
+
+/// The weight an empty bundle has inside another bundle.
+const NESTED_BUNDLE_WEIGHT: u32 = 4;
+
+/// The number of ticks before items dropped from a bundle can be picked up.
+const DROP_PICKUP_DELAY: u32 = 40;
+
+/// Returns the items inside a bundle, newest first. Items which aren't
+/// bundles have no contents.
+pub fn bundle_contents(bundle: &ItemStack) -> Vec<ItemStack> {
+    if bundle.item != ItemKind::Bundle {
+        return vec![];
+    }
+
+    match bundle.nbt.as_ref().and_then(|nbt| nbt.get("Items")) {
+        Some(Value::List(List::Compound(items))) => {
+            items.iter().filter_map(stack_from_nbt).collect()
+        }
+        _ => vec![],
+    }
+}
+
+/// Returns the weight one item of the stack takes up inside a bundle.
+pub fn item_weight(stack: &ItemStack) -> u32 {
+    if stack.item == ItemKind::Bundle {
+        return NESTED_BUNDLE_WEIGHT + bundle_weight(stack);
+    }
+
+    BUNDLE_CAPACITY / stack.item.max_stack().max(1) as u32
+}
+
+/// Returns the total weight of the items inside a bundle.
+pub fn bundle_weight(bundle: &ItemStack) -> u32 {
+    bundle_contents(bundle)
+        .iter()
+        .map(|stack| item_weight(stack) * stack.count() as u32)
+        .sum()
+}
+
+/// Returns `true` if the item can be put into a bundle. Shulker boxes can't
+/// be put into bundles.
+pub fn fits_in_bundle(item: ItemKind) -> bool {
+    item != ItemKind::Air && !item.to_str().ends_with("shulker_box")
+}
+
+/// Puts as many items of the stack into the bundle as fit, and returns the
+/// number of items put in. The items are added to a stack of the same item
+/// in the bundle, which becomes the newest item, or to a new stack.
+pub fn insert_into_bundle(bundle: &mut ItemStack, stack: &ItemStack) -> u8 {
+    if bundle.item != ItemKind::Bundle || !fits_in_bundle(stack.item) {
+        return 0;
+    }
+
+    let room = BUNDLE_CAPACITY.saturating_sub(bundle_weight(bundle));
+    let count = (room / item_weight(stack)).min(stack.count() as u32) as u8;
+
+    if count == 0 {
+        return 0;
+    }
+
+    let mut contents = bundle_contents(bundle);
+
+    // Bundles are never merged with each other.
+    let existing = if stack.item == ItemKind::Bundle {
+        None
+    } else {
+        contents.iter().position(|s| can_stack(s, stack))
+    };
+
+    let added = match existing {
+        Some(idx) => {
+            let mut existing = contents.remove(idx);
+            existing.set_count(existing.count() + count);
+            existing
+        }
+        None => {
+            let mut new = stack.clone();
+            new.set_count(count);
+            new
+        }
+    };
+
+    contents.insert(0, added);
+
+    set_bundle_contents(bundle, &contents);
+    count
+}
+
+/// Takes the newest item out of the bundle.
+pub fn remove_from_bundle(bundle: &mut ItemStack) -> Option<ItemStack> {
+    let mut contents = bundle_contents(bundle);
+
+    if contents.is_empty() {
+        return None;
+    }
+
+    let removed = contents.remove(0);
+    set_bundle_contents(bundle, &contents);
+    Some(removed)
+}
+
+/// Takes all items out of the bundle, newest first.
+pub fn empty_bundle(bundle: &mut ItemStack) -> Vec<ItemStack> {
+    let contents = bundle_contents(bundle);
+    set_bundle_contents(bundle, &[]);
+    contents
+}
+
+fn set_bundle_contents(bundle: &mut ItemStack, contents: &[ItemStack]) {
+    if contents.is_empty() {
+        // Empty bundles have no `Items` list, so they stack with new bundles.
+        if let Some(nbt) = &mut bundle.nbt {
+            nbt.remove("Items");
+
+            if nbt.is_empty() {
+                bundle.nbt = None;
+            }
+        }
+        return;
+    }
+
+    let items = contents.iter().map(|s| stack_to_nbt(Some(s))).collect();
+
+    bundle
+        .nbt
+        .get_or_insert_with(Default::default)
+        .insert("Items", List::Compound(items));
+}
+
+/// Drops the contents of the bundles clients use in front of them.
+pub(crate) fn drop_bundle_contents(
+    mut commands: Commands,
+    mut clients: Query<(&Client, &mut Inventory)>,
+    mut instances: Query<&mut Instance>,
+    mut use_item: EventReader<UseItem>,
+) {
+    for event in use_item.iter() {
+        let Ok((client, mut inventory)) = clients.get_mut(event.client) else {
+            continue;
+        };
+
+        let slot = hand_slot(client, event.hand);
+
+        let Some(mut bundle) = inventory
+            .slot(slot)
+            .filter(|stack| stack.item == ItemKind::Bundle)
+            .cloned()
+        else {
+            continue;
+        };
+
+        let contents = empty_bundle(&mut bundle);
+
+        if contents.is_empty() {
+            continue;
+        }
+
+        inventory.replace_slot(slot, bundle);
+
+        // The items are thrown from just below the eyes of the client.
+        let position = client.position() + DVec3::new(0.0, 1.32, 0.0);
+        let velocity = look_direction(client.yaw(), client.pitch()) * 0.3 + DVec3::Y * 0.1;
+
+        for stack in contents {
+            commands.spawn(
+                ItemEntityBundle::new(client.instance(), position, stack)
+                    .with_velocity(velocity)
+                    .with_pickup_delay(DROP_PICKUP_DELAY),
+            );
+        }
+
+        if let Ok(mut instance) = instances.get_mut(client.instance()) {
+            instance.play_sound(
+                Sound::ItemBundleDropContents,
+                SoundCategory::Player,
+                client.position(),
+                0.8,
+                0.8 + rand::random::<f32>() * 0.4,
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bundles_hold_one_stack_worth_of_items() {
+        let mut bundle = ItemStack::new(ItemKind::Bundle, 1, None);
+
+        let dirt = ItemStack::new(ItemKind::Dirt, 40, None);
+        let pearls = ItemStack::new(ItemKind::EnderPearl, 16, None);
+
+        assert_eq!(insert_into_bundle(&mut bundle, &dirt), 40);
+        // 24 weight is left, which is room for 6 pearls.
+        assert_eq!(insert_into_bundle(&mut bundle, &pearls), 6);
+        assert_eq!(insert_into_bundle(&mut bundle, &dirt), 0);
+        assert_eq!(bundle_weight(&bundle), BUNDLE_CAPACITY);
+
+        let removed = remove_from_bundle(&mut bundle).unwrap();
+        assert_eq!(removed.item, ItemKind::EnderPearl);
+        assert_eq!(removed.count(), 6);
+
+        // The rest of the dirt is added to the dirt already inside.
+        let more_dirt = ItemStack::new(ItemKind::Dirt, 64, None);
+        assert_eq!(insert_into_bundle(&mut bundle, &more_dirt), 24);
+        assert_eq!(
+            bundle_contents(&bundle),
+            vec![ItemStack::new(ItemKind::Dirt, 64, None)]
+        );
+
+        assert_eq!(empty_bundle(&mut bundle).len(), 1);
+        assert_eq!(bundle.nbt, None);
+
+        let mut shulker_box = ItemStack::new(ItemKind::ShulkerBox, 1, None);
+        assert_eq!(insert_into_bundle(&mut bundle, &shulker_box), 0);
+        shulker_box.item = ItemKind::Bundle;
+        assert_eq!(insert_into_bundle(&mut bundle, &shulker_box), 1);
+        assert_eq!(bundle_weight(&bundle), NESTED_BUNDLE_WEIGHT);
+    }
+}
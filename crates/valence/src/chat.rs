@@ -0,0 +1,824 @@
+//! Formatting and broadcasting chat messages.
+//!
+//! Chat messages sent by clients are not broadcast by default. Inserting the
+//! [`ChatFormat`] resource broadcasts every message to all clients after
+//! formatting it:
+//!
+//! 1. Replacements such as emoji shortcodes are applied to the message.
+//! 2. Filtered words are masked.
+//! 3. Links are highlighted and made clickable.
+//! 4. The message is placed in the [template](ChatFormat::template) along with
+//!    the name and [`ChatRank`] of the sender.
+//!
+//! Players in the [`IgnoreList`] of a client are not shown to it.
+//!
+//! The resource also enables the built-in private message commands:
+//!
+//! - `/msg <player> <message>` (or `/tell` and `/w`) sends a private message.
+//! - `/reply <message>` (or `/r`) replies to the last private message partner.
+//! - `/ignore <player>` adds the player to the [`IgnoreList`] of the client,
+//!   or removes them if they are already ignored.
+//!
+//! Private messages are sent with the vanilla private message
+//! [chat types](ChatType), so they are displayed and narrated by the client
+//! like on vanilla servers.
+//!
+//! Applications with their own chat pipeline can use
+//! [`ChatFormat::format_message`] to format messages the same way.
+
+use std::borrow::Cow;
+use std::collections::HashSet;
+
+use bevy_ecs::prelude::*;
+use uuid::Uuid;
+use valence_nbt::{compound, Compound, List};
+use valence_protocol::packets::s2c::play::DisguisedChatMessage;
+use valence_protocol::text::{Color, Text, TextFormat};
+use valence_protocol::VarInt;
+
+use crate::client::event::{ChatCommand, ChatMessage};
+use crate::client::Client;
+use crate::nickname::{display_name, Nickname};
+
+/// A [`Resource`] which enables broadcasting chat messages and describes how
+/// they are formatted.
+#[derive(Resource, Clone, PartialEq, Debug)]
+pub struct ChatFormat {
+    /// The template of broadcast messages. The placeholders `{name}`,
+    /// `{rank}`, `{server}` and `{message}` are replaced by the display name
+    /// of the sender, the [`ChatRank`] of the sender, the
+    /// [server name](Self::server_name) and the message. Other text is kept
+    /// as is.
+    ///
+    /// # Default Value
+    ///
+    /// `{rank}<{name}> {message}`
+    pub template: String,
+    /// The name of the server for the `{server}` placeholder.
+    ///
+    /// # Default Value
+    ///
+    /// `Valence`
+    pub server_name: String,
+    /// The words masked in messages.
+    ///
+    /// # Default Value
+    ///
+    /// An empty filter.
+    pub filter: WordFilter,
+    /// Pairs of text and its replacement applied to messages, such as
+    /// `(":heart:", "❤")`.
+    ///
+    /// # Default Value
+    ///
+    /// No replacements.
+    pub replacements: Vec<(String, String)>,
+    /// If `http://` and `https://` links in messages are underlined and open
+    /// the link when clicked.
+    ///
+    /// # Default Value
+    ///
+    /// `true`
+    pub highlight_links: bool,
+}
+
+impl Default for ChatFormat {
+    fn default() -> Self {
+        Self {
+            template: "{rank}<{name}> {message}".into(),
+            server_name: "Valence".into(),
+            filter: WordFilter::new(),
+            replacements: vec![],
+            highlight_links: true,
+        }
+    }
+}
+
+impl ChatFormat {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// See [`Self::template`].
+    #[must_use]
+    pub fn with_template(mut self, template: impl Into<String>) -> Self {
+        self.template = template.into();
+        self
+    }
+
+    /// See [`Self::server_name`].
+    #[must_use]
+    pub fn with_server_name(mut self, server_name: impl Into<String>) -> Self {
+        self.server_name = server_name.into();
+        self
+    }
+
+    /// See [`Self::filter`].
+    #[must_use]
+    pub fn with_filter(mut self, filter: WordFilter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    /// Adds a replacement. See [`Self::replacements`].
+    #[must_use]
+    pub fn with_replacement(mut self, from: impl Into<String>, to: impl Into<String>) -> Self {
+        self.replacements.push((from.into(), to.into()));
+        self
+    }
+
+    /// See [`Self::highlight_links`].
+    #[must_use]
+    pub fn with_link_highlighting(mut self, highlight_links: bool) -> Self {
+        self.highlight_links = highlight_links;
+        self
+    }
+
+    /// Formats a message sent by a player with the given name and rank.
+    pub fn format_message(&self, name: Text, rank: Option<&ChatRank>, message: &str) -> Text {
+        let message = self.format_content(message);
+
+        let mut res = Text::default();
+        let mut rest = self.template.as_str();
+
+        while let Some(start) = rest.find('{') {
+            let Some(len) = rest[start..].find('}') else {
+                break;
+            };
+
+            let end = start + len + 1;
+
+            let value = match &rest[start + 1..end - 1] {
+                "name" => name.clone(),
+                "rank" => rank.map(|r| r.prefix.clone()).unwrap_or_default(),
+                "server" => self.server_name.clone().into(),
+                "message" => message.clone(),
+                _ => {
+                    res += rest[..end].to_owned();
+                    rest = &rest[end..];
+                    continue;
+                }
+            };
+
+            if start > 0 {
+                res += rest[..start].to_owned();
+            }
+            res += value;
+            rest = &rest[end..];
+        }
+
+        if !rest.is_empty() {
+            res += rest.to_owned();
+        }
+
+        res
+    }
+
+    /// Applies the replacements, the word filter and link highlighting to a
+    /// message without placing it in the template. This is used for private
+    /// messages.
+    pub fn format_content(&self, message: &str) -> Text {
+        let mut message = message.to_owned();
+
+        for (from, to) in &self.replacements {
+            if !from.is_empty() {
+                message = message.replace(from.as_str(), to);
+            }
+        }
+
+        let message = self.filter.filter(&message);
+
+        if self.highlight_links {
+            highlight_links(&message)
+        } else {
+            message.into()
+        }
+    }
+}
+
+/// Returns the message with links underlined and clickable.
+fn highlight_links(message: &str) -> Text {
+    let mut res = Text::default();
+    let mut plain = String::new();
+
+    for segment in message.split_inclusive(char::is_whitespace) {
+        let word = segment.trim_end();
+
+        if word.starts_with("https://") || word.starts_with("http://") {
+            if !plain.is_empty() {
+                res += std::mem::take(&mut plain);
+            }
+
+            res += word
+                .to_owned()
+                .underlined()
+                .color(Color::AQUA)
+                .on_click_open_url(word.to_owned());
+            plain.push_str(&segment[word.len()..]);
+        } else {
+            plain.push_str(segment);
+        }
+    }
+
+    if !plain.is_empty() {
+        res += plain;
+    }
+
+    res
+}
+
+/// A list of words which are masked in chat messages.
+///
+/// Words are matched ignoring case, and only as whole words, so filtering
+/// `ass` does not affect `class`.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct WordFilter {
+    words: HashSet<String>,
+    replacement: char,
+}
+
+impl Default for WordFilter {
+    fn default() -> Self {
+        Self {
+            words: HashSet::new(),
+            replacement: '*',
+        }
+    }
+}
+
+impl WordFilter {
+    /// Creates an empty filter which masks words with `*`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[must_use]
+    pub fn with_word(mut self, word: impl AsRef<str>) -> Self {
+        self.words.insert(word.as_ref().to_lowercase());
+        self
+    }
+
+    #[must_use]
+    pub fn with_words<I>(mut self, words: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: AsRef<str>,
+    {
+        self.words
+            .extend(words.into_iter().map(|w| w.as_ref().to_lowercase()));
+        self
+    }
+
+    /// Sets the character every character of a filtered word is replaced with.
+    #[must_use]
+    pub fn with_replacement(mut self, replacement: char) -> Self {
+        self.replacement = replacement;
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.words.is_empty()
+    }
+
+    /// Returns `true` if the message contains a filtered word.
+    pub fn is_filtered(&self, message: &str) -> bool {
+        message
+            .split(|c: char| !c.is_alphanumeric())
+            .any(|word| self.words.contains(&word.to_lowercase()))
+    }
+
+    /// Returns the message with the filtered words masked.
+    pub fn filter(&self, message: &str) -> String {
+        if self.is_empty() {
+            return message.to_owned();
+        }
+
+        let mut res = String::with_capacity(message.len());
+        let mut word_start = None;
+
+        for (i, c) in message.char_indices() {
+            if c.is_alphanumeric() {
+                word_start.get_or_insert(i);
+            } else {
+                if let Some(start) = word_start.take() {
+                    self.push_word(&mut res, &message[start..i]);
+                }
+                res.push(c);
+            }
+        }
+
+        if let Some(start) = word_start {
+            self.push_word(&mut res, &message[start..]);
+        }
+
+        res
+    }
+
+    fn push_word(&self, res: &mut String, word: &str) {
+        if self.words.contains(&word.to_lowercase()) {
+            res.extend(word.chars().map(|_| self.replacement));
+        } else {
+            res.push_str(word);
+        }
+    }
+}
+
+/// A [`Component`] with the rank of the [`Client`] on the same entity, which
+/// is displayed in place of `{rank}` in the [chat template].
+///
+/// [chat template]: ChatFormat::template
+#[derive(Component, Clone, PartialEq, Debug)]
+pub struct ChatRank {
+    /// The text displayed for the rank, such as `[Admin] `.
+    pub prefix: Text,
+}
+
+impl ChatRank {
+    pub fn new(prefix: impl Into<Text>) -> Self {
+        Self {
+            prefix: prefix.into(),
+        }
+    }
+}
+
+/// A [`Component`] with the players a [`Client`] does not want to see chat
+/// messages and private messages from.
+///
+/// Players are identified by their UUID, so the list stays valid when an
+/// ignored player reconnects. Clients without this component ignore no one.
+#[derive(Component, Clone, PartialEq, Eq, Default, Debug)]
+pub struct IgnoreList {
+    players: HashSet<Uuid>,
+}
+
+impl IgnoreList {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds the player to the list. Returns `false` if the player was already
+    /// ignored.
+    pub fn ignore(&mut self, player: Uuid) -> bool {
+        self.players.insert(player)
+    }
+
+    /// Removes the player from the list. Returns `false` if the player was
+    /// not ignored.
+    pub fn unignore(&mut self, player: Uuid) -> bool {
+        self.players.remove(&player)
+    }
+
+    pub fn is_ignoring(&self, player: Uuid) -> bool {
+        self.players.contains(&player)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = Uuid> + '_ {
+        self.players.iter().copied()
+    }
+}
+
+/// A [`Component`] with the client a [`Client`] last exchanged a private
+/// message with, which is the recipient of `/reply`.
+#[derive(Component, Copy, Clone, PartialEq, Eq, Debug)]
+pub struct ReplyTarget(pub Entity);
+
+/// The chat types in the registry sent to clients, which decide how the
+/// client displays and narrates a chat message.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum ChatType {
+    /// A chat message, displayed as `<sender> message`.
+    Chat,
+    /// A private message received from another player.
+    MsgCommandIncoming,
+    /// A private message sent to another player.
+    MsgCommandOutgoing,
+}
+
+impl ChatType {
+    pub const ALL: [Self; 3] = [
+        Self::Chat,
+        Self::MsgCommandIncoming,
+        Self::MsgCommandOutgoing,
+    ];
+
+    /// The ID of the chat type in the registry.
+    pub const fn id(self) -> i32 {
+        self as i32
+    }
+
+    pub const fn name(self) -> &'static str {
+        match self {
+            ChatType::Chat => "minecraft:chat",
+            ChatType::MsgCommandIncoming => "minecraft:msg_command_incoming",
+            ChatType::MsgCommandOutgoing => "minecraft:msg_command_outgoing",
+        }
+    }
+
+    pub(crate) fn to_chat_type_registry_item(self) -> Compound {
+        let decoration = |translation_key: &str, parameters: [&str; 2], private: bool| {
+            let mut decoration = compound! {
+                "translation_key" => translation_key,
+                "parameters" => List::String(parameters.map(String::from).to_vec()),
+            };
+
+            if private {
+                decoration.insert(
+                    "style",
+                    compound! {
+                        "color" => "gray",
+                        "italic" => true,
+                    },
+                );
+            }
+
+            decoration
+        };
+
+        let chat = match self {
+            ChatType::Chat => decoration("chat.type.text", ["sender", "content"], false),
+            ChatType::MsgCommandIncoming => decoration(
+                "commands.message.display.incoming",
+                ["sender", "content"],
+                true,
+            ),
+            ChatType::MsgCommandOutgoing => decoration(
+                "commands.message.display.outgoing",
+                ["target", "content"],
+                true,
+            ),
+        };
+
+        compound! {
+            "name" => self.name(),
+            "id" => self.id(),
+            "element" => compound! {
+                "chat" => chat,
+                "narration" => decoration("chat.type.text.narrate", ["sender", "content"], false),
+            },
+        }
+    }
+}
+
+/// Sends a chat message with the given chat type which is not signed by the
+/// sender.
+fn send_chat(
+    client: &mut Client,
+    chat_type: ChatType,
+    message: &Text,
+    sender: &Text,
+    target: Option<&Text>,
+) {
+    client.write_packet(&DisguisedChatMessage {
+        message: Cow::Borrowed(message),
+        chat_type: VarInt(chat_type.id()),
+        chat_type_name: Cow::Borrowed(sender),
+        target_name: target.map(Cow::Borrowed),
+    });
+}
+
+pub(crate) fn broadcast_chat_messages(
+    format: Option<Res<ChatFormat>>,
+    mut messages: EventReader<ChatMessage>,
+    mut clients: Query<(
+        &mut Client,
+        Option<&Nickname>,
+        Option<&ChatRank>,
+        Option<&IgnoreList>,
+    )>,
+) {
+    for message in messages.iter() {
+        // Messages sent while broadcasting is disabled are discarded.
+        let Some(format) = &format else {
+            continue;
+        };
+
+        let Ok((sender, nickname, rank, _)) = clients.get(message.client) else {
+            continue;
+        };
+
+        let sender_uuid = sender.uuid();
+        let text = format.format_message(display_name(sender, nickname), rank, &message.message);
+
+        for (mut client, _, _, ignored) in &mut clients {
+            if ignored.map_or(false, |list| list.is_ignoring(sender_uuid)) {
+                continue;
+            }
+
+            client.send_message(text.clone());
+        }
+    }
+}
+
+type MessageQuery<'a> = (
+    Entity,
+    &'a mut Client,
+    Option<&'a Nickname>,
+    Option<&'a mut IgnoreList>,
+    Option<&'a ReplyTarget>,
+);
+
+pub(crate) fn handle_private_message_commands(
+    format: Option<Res<ChatFormat>>,
+    mut commands: Commands,
+    mut chat_commands: EventReader<ChatCommand>,
+    mut clients: Query<MessageQuery>,
+) {
+    for command in chat_commands.iter() {
+        // The commands are only available while chat is enabled.
+        let Some(format) = &format else {
+            continue;
+        };
+
+        let (name, args) = command
+            .command
+            .split_once(' ')
+            .unwrap_or((&command.command, ""));
+        let args = args.trim();
+
+        let (target, message) = match name {
+            "msg" | "tell" | "w" => {
+                let Some((target, message)) = args.split_once(' ') else {
+                    send_error(
+                        &mut clients,
+                        command.client,
+                        "Usage: /msg <player> <message>",
+                    );
+                    continue;
+                };
+
+                let Some(target) = find_player(&clients, target) else {
+                    send_error(&mut clients, command.client, "That player is not online");
+                    continue;
+                };
+
+                (target, message.trim())
+            }
+            "reply" | "r" => {
+                if args.is_empty() {
+                    send_error(&mut clients, command.client, "Usage: /reply <message>");
+                    continue;
+                }
+
+                let target = clients
+                    .get(command.client)
+                    .ok()
+                    .and_then(|(_, _, _, _, reply)| reply)
+                    .map(|reply| reply.0)
+                    .filter(|&target| clients.contains(target));
+
+                let Some(target) = target else {
+                    send_error(&mut clients, command.client, "There is no one to reply to");
+                    continue;
+                };
+
+                (target, args)
+            }
+            "ignore" => {
+                if args.is_empty() || args.contains(' ') {
+                    send_error(&mut clients, command.client, "Usage: /ignore <player>");
+                    continue;
+                }
+
+                let Some(target) = find_player(&clients, args) else {
+                    send_error(&mut clients, command.client, "That player is not online");
+                    continue;
+                };
+
+                if target == command.client {
+                    send_error(&mut clients, command.client, "You can not ignore yourself");
+                    continue;
+                }
+
+                let Ok([(_, mut client, _, ignored, _), (_, target, nickname, _, _)]) =
+                    clients.get_many_mut([command.client, target])
+                else {
+                    continue;
+                };
+
+                let target_name = display_name(&target, nickname);
+                let target_uuid = target.uuid();
+
+                let is_ignored = match ignored {
+                    Some(mut list) => !list.unignore(target_uuid) && list.ignore(target_uuid),
+                    None => {
+                        let mut list = IgnoreList::new();
+                        list.ignore(target_uuid);
+                        commands.entity(command.client).insert(list);
+                        true
+                    }
+                };
+
+                let mut text = Text::from(if is_ignored {
+                    "You are now ignoring "
+                } else {
+                    "You are no longer ignoring "
+                });
+                text += target_name;
+                client.send_message(text.color(Color::GRAY));
+
+                continue;
+            }
+            _ => continue,
+        };
+
+        if target == command.client {
+            send_error(&mut clients, command.client, "You can not message yourself");
+            continue;
+        }
+
+        let Ok(
+            [(_, mut sender, sender_nick, _, _), (_, mut recipient, recipient_nick, ignored, _)],
+        ) = clients.get_many_mut([command.client, target])
+        else {
+            continue;
+        };
+
+        let sender_name = display_name(&sender, sender_nick);
+        let recipient_name = display_name(&recipient, recipient_nick);
+        let text = format.format_content(message);
+
+        send_chat(
+            &mut sender,
+            ChatType::MsgCommandOutgoing,
+            &text,
+            &sender_name,
+            Some(&recipient_name),
+        );
+
+        // The message is not delivered if the recipient ignores the sender,
+        // but the sender is not told about it.
+        if !ignored.map_or(false, |list| list.is_ignoring(sender.uuid())) {
+            send_chat(
+                &mut recipient,
+                ChatType::MsgCommandIncoming,
+                &text,
+                &sender_name,
+                None,
+            );
+
+            commands.entity(target).insert(ReplyTarget(command.client));
+        }
+
+        commands.entity(command.client).insert(ReplyTarget(target));
+    }
+}
+
+/// Returns the client with the given username, ignoring case.
+fn find_player(clients: &Query<MessageQuery>, username: &str) -> Option<Entity> {
+    clients
+        .iter()
+        .find(|(_, client, _, _, _)| client.username().as_str().eq_ignore_ascii_case(username))
+        .map(|(entity, _, _, _, _)| entity)
+}
+
+fn send_error(clients: &mut Query<MessageQuery>, client: Entity, message: &str) {
+    if let Ok((_, mut client, _, _, _)) = clients.get_mut(client) {
+        client.send_message(message.to_owned().color(Color::RED));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy_app::App;
+    use valence_protocol::packets::c2s::play::{
+        ChatCommand as ChatCommandC2s, ChatMessage as ChatMessageC2s,
+    };
+    use valence_protocol::packets::S2cPlayPacket;
+    use valence_protocol::VarInt;
+
+    use super::*;
+    use crate::assert_packet_count;
+    use crate::unit_test::util::{create_mock_client, gen_client_info, scenario_single_client};
+
+    #[test]
+    fn filter_masks_whole_words() {
+        let filter = WordFilter::new().with_words(["darn", "heck"]);
+
+        assert_eq!(filter.filter("Darn it, heck!"), "**** it, ****!");
+        assert_eq!(filter.filter("darned hecking"), "darned hecking");
+        assert!(filter.is_filtered("what the HECK"));
+        assert!(!filter.is_filtered("checkers"));
+    }
+
+    #[test]
+    fn messages_are_formatted() {
+        let format = ChatFormat::new()
+            .with_template("[{server}] {rank}{name}: {message} {unknown}")
+            .with_server_name("Lobby")
+            .with_filter(WordFilter::new().with_word("darn"))
+            .with_replacement(":heart:", "❤");
+
+        let text = format.format_message(
+            "Steve".into(),
+            Some(&ChatRank::new("[Admin] ")),
+            "darn :heart: https://valence.rs",
+        );
+
+        assert_eq!(
+            text.to_string(),
+            "[Lobby] [Admin] Steve: **** ❤ https://valence.rs {unknown}"
+        );
+    }
+
+    #[test]
+    fn chat_is_broadcast_with_format() {
+        let mut app = App::new();
+        let (_, mut client_helper) = scenario_single_client(&mut app);
+
+        app.update();
+        client_helper.clear_sent();
+
+        // Nothing is broadcast without the resource.
+        let message = ChatMessageC2s {
+            message: "hello",
+            timestamp: 0,
+            salt: 0,
+            signature: None,
+            message_count: VarInt(0),
+            acknowledgement: &[0; 3],
+        };
+
+        client_helper.send(&message);
+        app.update();
+
+        let sent_packets = client_helper.collect_sent().unwrap();
+        assert_packet_count!(sent_packets, 0, S2cPlayPacket::SystemChatMessage(_));
+
+        app.insert_resource(ChatFormat::new());
+        client_helper.clear_sent();
+        client_helper.send(&message);
+        app.update();
+
+        let sent_packets = client_helper.collect_sent().unwrap();
+        assert_packet_count!(sent_packets, 1, S2cPlayPacket::SystemChatMessage(_));
+    }
+
+    fn command(command: &str) -> ChatCommandC2s {
+        ChatCommandC2s {
+            command,
+            timestamp: 0,
+            salt: 0,
+            argument_signatures: vec![],
+            message_count: VarInt(0),
+            acknowledgement: &[0; 3],
+        }
+    }
+
+    #[test]
+    fn private_messages_respect_ignore_lists() {
+        let mut app = App::new();
+        let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+        app.insert_resource(ChatFormat::new());
+
+        let instance_ent = app.world.get::<Client>(client_ent).unwrap().instance();
+        let client_uuid = app.world.get::<Client>(client_ent).unwrap().uuid();
+
+        let (mut other, mut other_helper) = create_mock_client(gen_client_info("Other"));
+        other.set_instance(instance_ent);
+        let other_ent = app.world.spawn(other).id();
+
+        app.update();
+        client_helper.clear_sent();
+        other_helper.clear_sent();
+
+        client_helper.send(&command("msg other hi there"));
+        app.update();
+
+        let sent_packets = client_helper.collect_sent().unwrap();
+        assert_packet_count!(sent_packets, 1, S2cPlayPacket::DisguisedChatMessage(_));
+        let sent_packets = other_helper.collect_sent().unwrap();
+        assert_packet_count!(sent_packets, 1, S2cPlayPacket::DisguisedChatMessage(_));
+        assert_eq!(
+            app.world.get::<ReplyTarget>(other_ent),
+            Some(&ReplyTarget(client_ent))
+        );
+
+        other_helper.clear_sent();
+        other_helper.send(&command("ignore test"));
+        app.update();
+
+        let ignored = app.world.get::<IgnoreList>(other_ent).unwrap();
+        assert!(ignored.is_ignoring(client_uuid));
+
+        // Ignored players can neither chat with nor message the client.
+        client_helper.clear_sent();
+        other_helper.clear_sent();
+        client_helper.send(&command("r are you there?"));
+        client_helper.send(&ChatMessageC2s {
+            message: "hello?",
+            timestamp: 0,
+            salt: 0,
+            signature: None,
+            message_count: VarInt(0),
+            acknowledgement: &[0; 3],
+        });
+        app.update();
+
+        let sent_packets = client_helper.collect_sent().unwrap();
+        assert_packet_count!(sent_packets, 1, S2cPlayPacket::DisguisedChatMessage(_));
+        assert_packet_count!(sent_packets, 1, S2cPlayPacket::SystemChatMessage(_));
+        let sent_packets = other_helper.collect_sent().unwrap();
+        assert_packet_count!(sent_packets, 0, S2cPlayPacket::DisguisedChatMessage(_));
+        assert_packet_count!(sent_packets, 0, S2cPlayPacket::SystemChatMessage(_));
+    }
+}
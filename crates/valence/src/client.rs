@@ -7,34 +7,48 @@ use anyhow::{bail, Context};
 use bevy_ecs::prelude::*;
 use bytes::BytesMut;
 use glam::{DVec3, Vec3};
+use rustc_hash::FxHashSet;
 use tracing::warn;
 use uuid::Uuid;
 use valence_protocol::packets::s2c::particle::Particle;
 use valence_protocol::packets::s2c::play::{
     AcknowledgeBlockChange, CombatDeath, DisconnectPlay, EntityEvent, GameEvent, KeepAliveS2c,
-    LoginPlay, ParticleS2c, PluginMessageS2c, RemoveEntitiesEncode, ResourcePackS2c, Respawn,
-    SetActionBarText, SetCenterChunk, SetDefaultSpawnPosition, SetEntityMetadata,
-    SetEntityVelocity, SetRenderDistance, SetSubtitleText, SetTitleAnimationTimes, SetTitleText,
-    SoundEffect, SynchronizePlayerPosition, SystemChatMessage, UnloadChunk,
+    LoginPlay, LookAt, ParticleS2c, PluginMessageS2c, RemoveEntitiesEncode, ResourcePackS2c,
+    Respawn, SetActionBarText, SetCenterChunk, SetCooldown, SetDefaultSpawnPosition,
+    SetEntityMetadata, SetEntityVelocity, SetRenderDistance, SetSubtitleText,
+    SetTitleAnimationTimes, SetTitleText, SoundEffect, SynchronizePlayerPosition,
+    SystemChatMessage, UnloadChunk, UpdateTime,
 };
 use valence_protocol::types::{
-    GameEventKind, GameMode, GlobalPos, Property, SoundCategory, SyncPlayerPosLookFlags,
+    FeetOrEyes, GameEventKind, GameMode, GlobalPos, LookAtEntity, Property, SoundCategory,
+    SyncPlayerPosLookFlags,
 };
 use valence_protocol::{
-    BlockPos, EncodePacket, Ident, ItemStack, PacketDecoder, PacketEncoder, RawBytes, Sound, Text,
-    Username, VarInt,
+    BlockPos, CompressionStats, EncodePacket, Ident, ItemKind, ItemStack, PacketDecoder,
+    PacketEncoder, RawBytes, Sound, Text, Username, VarInt,
 };
 
+use crate::budget::{TickBudget, TickPhase};
+use crate::client::disconnect::DisconnectReason;
 use crate::dimension::DimensionId;
 use crate::entity::data::Player;
+use crate::entity::disguise::Disguise;
+use crate::entity::filter::EntityFilter;
 use crate::entity::{velocity_to_packet_units, EntityStatus, McEntity};
-use crate::instance::Instance;
+use crate::instance::{Instance, PartitionCell};
+use crate::inventory::InventoryDrag;
+use crate::menu::Menu;
 use crate::packet::WritePacket;
 use crate::server::{NewClientInfo, Server};
 use crate::view::{ChunkPos, ChunkView};
+use crate::weather::{write_weather_change, Weather};
+use crate::world_border::{write_world_border_change, WorldBorder};
 use crate::{Despawned, NULL_ENTITY};
 
+pub mod disconnect;
 pub mod event;
+pub mod pose;
+pub mod profile;
 
 /// Represents a client connected to the server. Used to send and receive
 /// packets from the client.
@@ -44,7 +58,14 @@ pub struct Client {
     enc: PacketEncoder,
     dec: PacketDecoder,
     scratch: Vec<u8>,
+    /// The total number of bytes sent to the client.
+    bytes_sent: u64,
     is_disconnected: bool,
+    disconnect_reason: Option<DisconnectReason>,
+    /// If a [`DisconnectEvent`] was sent for this client.
+    ///
+    /// [`DisconnectEvent`]: disconnect::DisconnectEvent
+    pub(crate) disconnect_reported: bool,
     username: Username<String>,
     uuid: Uuid,
     ip: IpAddr,
@@ -58,6 +79,13 @@ pub struct Client {
     yaw_modified: bool,
     pitch: f32,
     pitch_modified: bool,
+    /// The parts of the pending teleport which are relative, if it was made
+    /// with [`Client::teleport_relative`].
+    relative_teleport: Option<RelativeTeleport>,
+    dismount_on_teleport: bool,
+    /// The vehicle the client rides and the passengers of the vehicle last
+    /// sent to the client, with the client itself as the reserved ID 0.
+    pub(crate) seat: Option<(Entity, Vec<VarInt>)>,
     on_ground: bool,
     game_mode: GameMode,
     op_level: u8,
@@ -68,6 +96,9 @@ pub struct Client {
     old_view_distance: u8,
     death_location: Option<(DimensionId, BlockPos)>,
     entities_to_despawn: Vec<VarInt>,
+    /// The entities in the view of the client which are hidden by its
+    /// [`EntityFilter`].
+    hidden_entities: FxHashSet<Entity>,
     got_keepalive: bool,
     last_keepalive_id: u64,
     keepalive_sent_time: Instant,
@@ -95,7 +126,30 @@ pub struct Client {
     /// Tracks what slots have been modified by this client in this tick, so we
     /// don't need to send updates for them.
     pub(crate) inventory_slots_modified: u64,
+    /// The drag the client is in the middle of, if any.
+    pub(crate) inventory_drag: Option<InventoryDrag>,
     pub(crate) held_item_slot: u16,
+    /// The menu opened with [`Self::open_menu`] which is not open yet.
+    pub(crate) pending_menu: Option<Menu>,
+    time_override: Option<i64>,
+    weather_override: Option<Weather>,
+    world_border_override: Option<WorldBorder>,
+    /// The time of day, weather and world border the client is currently
+    /// displaying. `None` if the client's state is unknown. The time of day
+    /// is the last one sent, which is negative if it is frozen.
+    displayed_time_of_day: Option<i64>,
+    displayed_weather: Weather,
+    displayed_world_border: Option<WorldBorder>,
+}
+
+/// The relative parts of a teleport and the position and rotation they are
+/// relative to.
+#[derive(Copy, Clone, Debug)]
+struct RelativeTeleport {
+    flags: SyncPlayerPosLookFlags,
+    position: DVec3,
+    yaw: f32,
+    pitch: f32,
 }
 
 pub trait ClientConnection: Send + Sync + 'static {
@@ -110,25 +164,33 @@ impl Client {
         enc: PacketEncoder,
         dec: PacketDecoder,
     ) -> Self {
+        let spawn = info.spawn;
+
         Self {
             conn,
             enc,
             dec,
             scratch: vec![],
+            bytes_sent: 0,
             is_disconnected: false,
+            disconnect_reason: None,
+            disconnect_reported: false,
             username: info.username,
             uuid: info.uuid,
             ip: info.ip,
             properties: info.properties,
-            instance: NULL_ENTITY,
+            instance: spawn.map_or(NULL_ENTITY, |s| s.instance),
             old_instance: NULL_ENTITY,
-            position: DVec3::ZERO,
+            position: spawn.map_or(DVec3::ZERO, |s| s.position),
             old_position: DVec3::ZERO,
             position_modified: true,
-            yaw: 0.0,
+            yaw: spawn.map_or(0.0, |s| s.yaw),
             yaw_modified: true,
-            pitch: 0.0,
+            pitch: spawn.map_or(0.0, |s| s.pitch),
             pitch_modified: true,
+            relative_teleport: None,
+            dismount_on_teleport: false,
+            seat: None,
             on_ground: false,
             game_mode: GameMode::default(),
             op_level: 0,
@@ -138,6 +200,7 @@ impl Client {
             old_view_distance: 2,
             death_location: None,
             entities_to_despawn: vec![],
+            hidden_entities: FxHashSet::default(),
             is_new: true,
             needs_respawn: false,
             is_hardcore: false,
@@ -154,7 +217,15 @@ impl Client {
             window_id: 0,
             inventory_state_id: Wrapping(0),
             inventory_slots_modified: 0,
-            held_item_slot: 0,
+            inventory_drag: None,
+            held_item_slot: 36,
+            pending_menu: None,
+            time_override: None,
+            weather_override: None,
+            world_border_override: None,
+            displayed_time_of_day: None,
+            displayed_weather: Weather::CLEAR,
+            displayed_world_border: None,
         }
     }
 
@@ -202,7 +273,10 @@ impl Client {
         self.ip
     }
 
-    /// Gets the properties from this client's game profile.
+    /// Gets the properties from the game profile this client logged in with.
+    /// The current profile is in the [`GameProfile`] component.
+    ///
+    /// [`GameProfile`]: profile::GameProfile
     pub fn properties(&self) -> &[Property] {
         &self.properties
     }
@@ -212,10 +286,29 @@ impl Client {
     /// A disconnected client component will never become reconnected. It is
     /// your responsibility to despawn disconnected client entities, since
     /// they will not be automatically despawned by Valence.
+    ///
+    /// A [`DisconnectEvent`] is sent once for every disconnected client.
+    ///
+    /// [`DisconnectEvent`]: disconnect::DisconnectEvent
     pub fn is_disconnected(&self) -> bool {
         self.is_disconnected
     }
 
+    /// Returns why the client was disconnected, or `None` if it is still
+    /// connected.
+    pub fn disconnect_reason(&self) -> Option<&DisconnectReason> {
+        self.disconnect_reason.as_ref()
+    }
+
+    /// Marks the client as disconnected. Only the first reason a client is
+    /// disconnected for is kept.
+    pub(crate) fn disconnect(&mut self, reason: DisconnectReason) {
+        if !self.is_disconnected {
+            self.is_disconnected = true;
+            self.disconnect_reason = Some(reason);
+        }
+    }
+
     /// Gets the [`Instance`] entity this client is located in. The client is
     /// not in any instance when they first join.
     pub fn instance(&self) -> Entity {
@@ -232,6 +325,16 @@ impl Client {
         self.needs_respawn = true;
     }
 
+    /// Returns the instance this client was in at the end of the previous tick.
+    pub(crate) fn old_instance(&self) -> Entity {
+        self.old_instance
+    }
+
+    /// Returns `true` if the client has not been initialized yet.
+    pub(crate) fn is_new(&self) -> bool {
+        self.is_new
+    }
+
     /// Gets the absolute position of this client in the instance it is located
     /// in.
     pub fn position(&self) -> DVec3 {
@@ -241,6 +344,17 @@ impl Client {
     pub fn set_position(&mut self, pos: impl Into<DVec3>) {
         self.position = pos.into();
         self.position_modified = true;
+
+        if let Some(relative) = &mut self.relative_teleport {
+            relative.flags = relative.flags.with_x(false).with_y(false).with_z(false);
+        }
+    }
+
+    /// Sets the position of the client on the server without teleporting the
+    /// client, for movement the client sees on its own, such as riding a
+    /// vehicle.
+    pub(crate) fn set_position_unsynced(&mut self, pos: DVec3) {
+        self.position = pos;
     }
 
     /// Returns the position this client was in at the end of the previous tick.
@@ -276,6 +390,10 @@ impl Client {
     pub fn set_yaw(&mut self, yaw: f32) {
         self.yaw = yaw;
         self.yaw_modified = true;
+
+        if let Some(relative) = &mut self.relative_teleport {
+            relative.flags = relative.flags.with_y_rot(false);
+        }
     }
 
     /// Gets this client's pitch (in degrees).
@@ -287,6 +405,110 @@ impl Client {
     pub fn set_pitch(&mut self, pitch: f32) {
         self.pitch = pitch;
         self.pitch_modified = true;
+
+        if let Some(relative) = &mut self.relative_teleport {
+            relative.flags = relative.flags.with_x_rot(false);
+        }
+    }
+
+    /// Makes the client turn its head so that its eyes look at the position.
+    /// The client sends its new yaw and pitch back to the server.
+    pub fn face_position(&mut self, pos: impl Into<DVec3>) {
+        self.write_packet(&LookAt {
+            feet_eyes: FeetOrEyes::Eyes,
+            target_position: pos.into().to_array(),
+            entity_to_face: None,
+        });
+    }
+
+    /// Makes the client look at the eyes of the entity, like
+    /// [`Self::face_position`] with the [`eye_position`] of the entity.
+    ///
+    /// [`eye_position`]: McEntity::eye_position
+    pub fn face_entity(&mut self, entity: &McEntity) {
+        self.write_packet(&LookAt {
+            feet_eyes: FeetOrEyes::Eyes,
+            target_position: entity.eye_position().to_array(),
+            entity_to_face: Some(LookAtEntity {
+                entity_id: VarInt(entity.protocol_id()),
+                entity_feet_eyes: FeetOrEyes::Eyes,
+            }),
+        });
+    }
+
+    /// Teleports the client to the position with the given yaw and pitch (in
+    /// degrees). Unlike setting the position and rotation separately, this
+    /// also makes the client leave the vehicle it is riding.
+    pub fn teleport(&mut self, pos: impl Into<DVec3>, yaw: f32, pitch: f32) {
+        self.set_position(pos);
+        self.set_yaw(yaw);
+        self.set_pitch(pitch);
+        self.dismount_on_teleport = true;
+    }
+
+    /// Teleports the client like [`Self::teleport`], but the coordinates,
+    /// yaw and pitch which are set in `flags` are added to the current ones
+    /// instead of replacing them.
+    ///
+    /// The relative parts are sent to the client as offsets, so the client
+    /// keeps the movement it made since the server last heard from it.
+    pub fn teleport_relative(
+        &mut self,
+        pos: impl Into<DVec3>,
+        yaw: f32,
+        pitch: f32,
+        flags: SyncPlayerPosLookFlags,
+    ) {
+        let pos = pos.into();
+
+        // Parts set earlier this tick are absolute already.
+        let mut relative = self.relative_teleport.unwrap_or(RelativeTeleport {
+            flags: SyncPlayerPosLookFlags::new()
+                .with_x(!self.position_modified)
+                .with_y(!self.position_modified)
+                .with_z(!self.position_modified)
+                .with_y_rot(!self.yaw_modified)
+                .with_x_rot(!self.pitch_modified),
+            position: self.position,
+            yaw: self.yaw,
+            pitch: self.pitch,
+        });
+
+        relative.flags = relative
+            .flags
+            .with_x(relative.flags.x() && flags.x())
+            .with_y(relative.flags.y() && flags.y())
+            .with_z(relative.flags.z() && flags.z())
+            .with_y_rot(relative.flags.y_rot() && flags.y_rot())
+            .with_x_rot(relative.flags.x_rot() && flags.x_rot());
+
+        let add_if = |relative: bool, current: f64, value: f64| {
+            if relative {
+                current + value
+            } else {
+                value
+            }
+        };
+
+        self.position = DVec3::new(
+            add_if(flags.x(), self.position.x, pos.x),
+            add_if(flags.y(), self.position.y, pos.y),
+            add_if(flags.z(), self.position.z, pos.z),
+        );
+        self.yaw = add_if(flags.y_rot(), self.yaw.into(), yaw.into()) as f32;
+        self.pitch = add_if(flags.x_rot(), self.pitch.into(), pitch.into()) as f32;
+
+        self.position_modified = true;
+        self.yaw_modified = true;
+        self.pitch_modified = true;
+        self.relative_teleport = Some(relative);
+        self.dismount_on_teleport = true;
+    }
+
+    /// Returns `true` if the client has not confirmed every teleport sent to
+    /// it yet. The movement of the client is ignored until it does.
+    pub fn is_teleporting(&self) -> bool {
+        self.pending_teleports != 0
     }
 
     /// Whether or not the client reports that it is currently on the ground.
@@ -431,6 +653,27 @@ impl Client {
         self.ping
     }
 
+    /// Returns statistics about the packets compressed for this client.
+    ///
+    /// Packets shared by many clients, such as chunk data, are compressed once
+    /// for all clients and are only included in [`Self::bytes_sent`].
+    pub fn compression_stats(&self) -> CompressionStats {
+        self.enc.compression_stats()
+    }
+
+    /// Returns the total number of bytes sent to this client since it joined,
+    /// after compression.
+    pub fn bytes_sent(&self) -> u64 {
+        self.bytes_sent
+    }
+
+    /// Sends all packets written so far to the client.
+    fn flush(&mut self) -> anyhow::Result<()> {
+        let bytes = self.enc.take();
+        self.bytes_sent += bytes.len() as u64;
+        self.conn.try_send(bytes)
+    }
+
     /// The item that the client thinks it's holding under the mouse
     /// cursor. Only relevant when the client has an open inventory.
     pub fn cursor_item(&self) -> Option<&ItemStack> {
@@ -470,6 +713,12 @@ impl Client {
         });
     }
 
+    /// Opens the menu for the client on the next tick, replacing any open
+    /// inventory. See the [`menu`](crate::menu) module for details.
+    pub fn open_menu(&mut self, menu: Menu) {
+        self.pending_menu = Some(menu);
+    }
+
     /// Get the slot id in the player's inventory that the client says it's
     /// holding.
     pub fn held_item_slot(&self) -> u16 {
@@ -477,11 +726,66 @@ impl Client {
     }
 
     /// Kick the client with the given reason.
+    ///
+    /// The disconnect packet is sent immediately, since disconnected clients
+    /// are not sent any more packets.
     pub fn kick(&mut self, reason: impl Into<Text>) {
+        if self.is_disconnected {
+            return;
+        }
+
+        let reason = reason.into();
+
         self.write_packet(&DisconnectPlay {
-            reason: reason.into().into(),
+            reason: Cow::Borrowed(&reason),
         });
-        self.is_disconnected = true;
+        let _ = self.flush();
+
+        self.disconnect(DisconnectReason::Kicked(reason));
+    }
+
+    /// Returns the time of day displayed to this client instead of the time of
+    /// day in its instance, if any.
+    pub fn time_override(&self) -> Option<i64> {
+        self.time_override
+    }
+
+    /// Overrides the time of day displayed to this client independent of the
+    /// time of day in its instance. The client does not advance an overridden
+    /// time on its own. Passing `None` removes the override and the time of
+    /// day of the instance is displayed again. If the instance has no time of
+    /// day, the client continues its day-night cycle from the overridden time.
+    pub fn set_time_override(&mut self, time_of_day: impl Into<Option<i64>>) {
+        self.time_override = time_of_day.into();
+    }
+
+    /// Returns the weather displayed to this client instead of the weather in
+    /// its instance, if any.
+    pub fn weather_override(&self) -> Option<Weather> {
+        self.weather_override
+    }
+
+    /// Overrides the weather displayed to this client independent of the
+    /// weather in its instance. Passing `None` removes the override and the
+    /// weather of the instance is displayed again.
+    ///
+    /// Only the rendering of the weather is affected. Lightning and other
+    /// weather effects still follow the weather of the instance.
+    pub fn set_weather_override(&mut self, weather: impl Into<Option<Weather>>) {
+        self.weather_override = weather.into();
+    }
+
+    /// Returns the world border displayed to this client instead of the world
+    /// border of its instance, if any.
+    pub fn world_border_override(&self) -> Option<&WorldBorder> {
+        self.world_border_override.as_ref()
+    }
+
+    /// Overrides the world border displayed to this client independent of the
+    /// world border of its instance. Passing `None` removes the override and
+    /// the world border of the instance is displayed again.
+    pub fn set_world_border_override(&mut self, world_border: impl Into<Option<WorldBorder>>) {
+        self.world_border_override = world_border.into();
     }
 
     /// Requests that the client download and enable a resource pack.
@@ -547,6 +851,20 @@ impl Client {
         });
     }
 
+    /// Shows the cooldown overlay on every stack of the item in the inventory
+    /// of the client, which also stops the client from using the item for the
+    /// given number of ticks. Zero ticks removes the cooldown.
+    ///
+    /// The cooldown is not tracked by the server. Use the
+    /// [`ItemCooldowns`](crate::item_cooldown::ItemCooldowns) component to
+    /// check the cooldown before allowing the item to be used.
+    pub fn set_item_cooldown(&mut self, item: ItemKind, ticks: u32) {
+        self.write_packet(&SetCooldown {
+            item_id: VarInt(item.to_raw() as i32),
+            cooldown_ticks: VarInt(ticks as i32),
+        });
+    }
+
     /// Puts a particle effect at the given position, only for this client.
     ///
     /// If you want to show a particle effect to all players, use
@@ -621,27 +939,48 @@ pub fn despawn_disconnected_clients(mut commands: Commands, clients: Query<(Enti
     }
 }
 
+type UpdatedClient<'a> = (
+    Entity,
+    &'a mut Client,
+    Option<&'a McEntity>,
+    Option<(&'a EntityFilter, ChangeTrackers<EntityFilter>)>,
+);
+
 pub(crate) fn update_clients(
     server: Res<Server>,
-    mut clients: Query<(Entity, &mut Client, Option<&McEntity>)>,
+    mut clients: Query<UpdatedClient>,
     instances: Query<&Instance>,
     entities: Query<&McEntity>,
+    disguises: Query<&Disguise, Without<Despawned>>,
+    mut budget: ResMut<TickBudget>,
 ) {
+    budget.start_phase();
+
     // TODO: what batch size to use?
-    clients.par_for_each_mut(16, |(entity_id, mut client, self_entity)| {
+    clients.par_for_each_mut(16, |(entity_id, mut client, self_entity, filter)| {
         if !client.is_disconnected() {
+            // The visibility of the entities in view is checked again when the filter
+            // changes or is removed.
+            let filter_changed = match &filter {
+                Some((_, trackers)) => trackers.is_changed(),
+                None => !client.hidden_entities.is_empty(),
+            };
+
             if let Err(e) = update_one_client(
                 &mut client,
                 self_entity,
                 entity_id,
+                filter.map(|(filter, _)| filter),
+                filter_changed,
                 &instances,
                 &entities,
+                &disguises,
                 &server,
             ) {
                 client.write_packet(&DisconnectPlay {
                     reason: Text::from("").into(),
                 });
-                client.is_disconnected = true;
+                client.disconnect(DisconnectReason::ServerError(format!("{e:#}")));
                 warn!(
                     username = %client.username,
                     uuid = %client.uuid,
@@ -653,21 +992,214 @@ pub(crate) fn update_clients(
 
         client.is_new = false;
     });
+
+    budget.end_phase(TickPhase::Clients);
+}
+
+/// Returns if the client `viewer` sees `entity` through its filter. Clients
+/// always see their own entity.
+fn is_entity_visible(
+    filter: Option<&EntityFilter>,
+    id: Entity,
+    entity: &McEntity,
+    viewer: Entity,
+) -> bool {
+    id == viewer || filter.map_or(true, |filter| filter.is_visible(id, entity))
+}
+
+/// Spawns an entity coming into the view of the client, unless it is hidden
+/// from the client.
+fn spawn_entity_in_view(
+    client: &mut Client,
+    filter: Option<&EntityFilter>,
+    id: Entity,
+    entity: &McEntity,
+    disguise: Option<&Disguise>,
+    position: DVec3,
+    viewer: Entity,
+) {
+    if is_entity_visible(filter, id, entity, viewer) {
+        write_entity_init_packets(client, entity, disguise, position);
+    } else {
+        client.hidden_entities.insert(id);
+    }
+}
+
+/// Despawns an entity leaving the view of the client, unless it was hidden
+/// from the client.
+fn despawn_entity_in_view(client: &mut Client, id: Entity, entity: &McEntity) {
+    if !client.hidden_entities.remove(&id) {
+        client
+            .entities_to_despawn
+            .push(VarInt(entity.protocol_id()));
+    }
+}
+
+/// Returns the disguise of `entity` if the client `viewer` can see it.
+fn visible_disguise<'a>(
+    disguises: &'a Query<&Disguise, Without<Despawned>>,
+    entity: Entity,
+    viewer: Entity,
+) -> Option<&'a Disguise> {
+    disguises
+        .get(entity)
+        .ok()
+        .filter(|disguise| disguise.is_visible_to(viewer))
+}
+
+fn write_entity_init_packets(
+    client: &mut Client,
+    entity: &McEntity,
+    disguise: Option<&Disguise>,
+    position: DVec3,
+) {
+    match disguise {
+        Some(disguise) => entity.write_init_packets_with_data(
+            disguise.data(),
+            &mut client.enc,
+            position,
+            &mut client.scratch,
+        ),
+        None => entity.write_init_packets(&mut client.enc, position, &mut client.scratch),
+    }
+}
+
+/// Appends the packet buffer of a partition cell to the client, replacing the
+/// update packets of entities whose disguise is visible to the client and
+/// leaving out the update packets of entities hidden from the client.
+fn append_cell_packets(
+    client: &mut Client,
+    cell: &PartitionCell,
+    entities: &Query<&McEntity>,
+    disguises: &Query<&Disguise, Without<Despawned>>,
+    self_id: Entity,
+) {
+    // The update packets of the entities in the cell are written in order, so the
+    // update ranges are sorted.
+    let mut written = 0;
+
+    for &id in &cell.entities {
+        let hidden = client.hidden_entities.contains(&id);
+        let disguise = visible_disguise(disguises, id, self_id).filter(|_| !hidden);
+
+        if !hidden && disguise.is_none() {
+            continue;
+        }
+
+        let Ok(entity) = entities.get(id) else {
+            continue
+        };
+
+        let range = entity.self_update_range.clone();
+
+        if range.start < written || range.end > cell.packet_buf.len() {
+            continue;
+        }
+
+        client
+            .enc
+            .append_bytes(&cell.packet_buf[written..range.start]);
+
+        if let Some(disguise) = disguise {
+            entity.write_update_packets_with_data(
+                disguise.data(),
+                &mut client.enc,
+                &mut client.scratch,
+            );
+        }
+
+        written = range.end;
+    }
+
+    client.enc.append_bytes(&cell.packet_buf[written..]);
+}
+
+/// Sends the time of day, weather and world border of the client's instance or
+/// the client's overrides, if they differ from what the client is displaying.
+fn write_environment_changes(
+    client: &mut Client,
+    instance: &Instance,
+    respawned: bool,
+    current_tick: i64,
+) {
+    // The client resets these when it spawns into a world.
+    if respawned {
+        client.displayed_time_of_day = None;
+        client.displayed_weather = Weather::CLEAR;
+        client.displayed_world_border = None;
+    }
+
+    // Clients run their own day-night cycle until a time of day is set.
+    let time_of_day = match (client.time_override, instance.time_of_day()) {
+        // A negative time stops the client from advancing the time on its own.
+        // Zero cannot be negated, so a full day is used instead.
+        (Some(time), _) => match time.rem_euclid(24000) {
+            0 => Some(-24000),
+            time => Some(-time),
+        },
+        // Keep the moon phase, which changes every day, but never freeze the
+        // time of the instance.
+        (None, Some(time)) => Some(time.rem_euclid(24000 * 8)),
+        // Let the client continue from a removed override.
+        (None, None) => client.displayed_time_of_day.map(i64::abs),
+    };
+
+    // Setting the same time again resets the time clients advanced since.
+    let time_of_day_set = client.time_override.is_none()
+        && instance.time_of_day().is_some()
+        && instance.time_of_day_modified();
+
+    if let Some(time_of_day) = time_of_day {
+        if time_of_day_set || client.displayed_time_of_day != Some(time_of_day) {
+            client.enc.write_packet(&UpdateTime {
+                world_age: current_tick,
+                time_of_day,
+            });
+
+            client.displayed_time_of_day = Some(time_of_day);
+        }
+    }
+
+    let weather = client.weather_override.unwrap_or(instance.weather());
+
+    if client.displayed_weather != weather {
+        write_weather_change(&mut client.enc, client.displayed_weather, weather);
+        client.displayed_weather = weather;
+    }
+
+    let world_border = client
+        .world_border_override
+        .unwrap_or(*instance.world_border());
+
+    if client.displayed_world_border != Some(world_border) {
+        write_world_border_change(
+            &mut client.enc,
+            client.displayed_world_border.as_ref(),
+            &world_border,
+        );
+        client.displayed_world_border = Some(world_border);
+    }
 }
 
 #[inline]
+#[allow(clippy::too_many_arguments)]
 fn update_one_client(
     client: &mut Client,
     _self_entity: Option<&McEntity>,
     _self_id: Entity,
+    filter: Option<&EntityFilter>,
+    filter_changed: bool,
     instances: &Query<&Instance>,
     entities: &Query<&McEntity>,
+    disguises: &Query<&Disguise, Without<Despawned>>,
     server: &Server,
 ) -> anyhow::Result<()> {
     let Ok(instance) = instances.get(client.instance) else {
         bail!("client is in a nonexistent instance");
     };
 
+    let respawned = client.is_new || client.needs_respawn;
+
     // Send the login (play) packet and other initial packets. We defer this until
     // now so that the user can set the client's initial location, game
     // mode, etc.
@@ -757,6 +1289,7 @@ fn update_one_client(
             client.last_keepalive_id = id;
             client.keepalive_sent_time = Instant::now();
         } else {
+            client.disconnect(DisconnectReason::TimedOut);
             bail!("timed out (no keepalive response)");
         }
     }
@@ -764,6 +1297,8 @@ fn update_one_client(
     // Send instance-wide packet data.
     client.enc.append_bytes(&instance.packet_buf);
 
+    write_environment_changes(client, instance, respawned, server.current_tick());
+
     let old_view = client.old_view();
     let view = client.view();
 
@@ -800,10 +1335,14 @@ fn update_one_client(
                         if let Ok(entity) = entities.get(id) {
                             // Spawn the entity at the old position so that later relative entity
                             // movement packets will not set the entity to the wrong position.
-                            entity.write_init_packets(
-                                &mut client.enc,
+                            spawn_entity_in_view(
+                                client,
+                                filter,
+                                id,
+                                entity,
+                                visible_disguise(disguises, id, _self_id),
                                 entity.old_position(),
-                                &mut client.scratch,
+                                _self_id,
                             );
                         }
                     }
@@ -815,9 +1354,7 @@ fn update_one_client(
                         // The outgoing entity moved outside the view distance, so it must be
                         // despawned.
                         if let Ok(entity) = entities.get(id) {
-                            client
-                                .entities_to_despawn
-                                .push(VarInt(entity.protocol_id()));
+                            despawn_entity_in_view(client, id, entity);
                         }
                     }
                 }
@@ -825,7 +1362,11 @@ fn update_one_client(
                 // Send all data in the chunk's packet buffer to this client. This will update
                 // entities in the cell, spawn or update the chunk in the cell, or send any
                 // other packet data that was added here by users.
-                client.enc.append_bytes(&cell.packet_buf);
+                if disguises.is_empty() && client.hidden_entities.is_empty() {
+                    client.enc.append_bytes(&cell.packet_buf);
+                } else {
+                    append_cell_packets(client, cell, entities, disguises, _self_id);
+                }
             }
         });
     }
@@ -850,9 +1391,7 @@ fn update_one_client(
                     // Unload all the entities in the cell.
                     for &id in &cell.entities {
                         if let Ok(entity) = entities.get(id) {
-                            client
-                                .entities_to_despawn
-                                .push(VarInt(entity.protocol_id()));
+                            despawn_entity_in_view(client, id, entity);
                         }
                     }
                 }
@@ -877,10 +1416,14 @@ fn update_one_client(
                 // Load all the entities in this cell.
                 for &id in &cell.entities {
                     if let Ok(entity) = entities.get(id) {
-                        entity.write_init_packets(
-                            &mut client.enc,
+                        spawn_entity_in_view(
+                            client,
+                            filter,
+                            id,
+                            entity,
+                            visible_disguise(disguises, id, _self_id),
                             entity.position(),
-                            &mut client.scratch,
+                            _self_id,
                         );
                     }
                 }
@@ -905,9 +1448,7 @@ fn update_one_client(
                 // Unload all the entities in the cell.
                 for &id in &cell.entities {
                     if let Ok(entity) = entities.get(id) {
-                        client
-                            .entities_to_despawn
-                            .push(VarInt(entity.protocol_id()));
+                        despawn_entity_in_view(client, id, entity);
                     }
                 }
             }
@@ -930,10 +1471,14 @@ fn update_one_client(
                 // Load all the entities in this cell.
                 for &id in &cell.entities {
                     if let Ok(entity) = entities.get(id) {
-                        entity.write_init_packets(
-                            &mut client.enc,
+                        spawn_entity_in_view(
+                            client,
+                            filter,
+                            id,
+                            entity,
+                            visible_disguise(disguises, id, _self_id),
                             entity.position(),
-                            &mut client.scratch,
+                            _self_id,
                         );
                     }
                 }
@@ -941,6 +1486,37 @@ fn update_one_client(
         });
     }
 
+    if filter_changed {
+        // Spawn the entities in view which are no longer hidden and despawn the
+        // entities which are now hidden.
+        view.for_each(|pos| {
+            let Some(cell) = instance.partition.get(&pos) else {
+                return;
+            };
+
+            for &id in &cell.entities {
+                let Ok(entity) = entities.get(id) else {
+                    continue;
+                };
+
+                let visible = is_entity_visible(filter, id, entity, _self_id);
+
+                if visible && client.hidden_entities.remove(&id) {
+                    write_entity_init_packets(
+                        client,
+                        entity,
+                        visible_disguise(disguises, id, _self_id),
+                        entity.position(),
+                    );
+                } else if !visible && client.hidden_entities.insert(id) {
+                    client
+                        .entities_to_despawn
+                        .push(VarInt(entity.protocol_id()));
+                }
+            }
+        });
+    }
+
     // Despawn all the entities that are queued to be despawned.
     if !client.entities_to_despawn.is_empty() {
         client.enc.append_packet(&RemoveEntitiesEncode {
@@ -953,28 +1529,60 @@ fn update_one_client(
     // Teleport the client. Do this after chunk packets are sent so the client does
     // not accidentally pass through blocks.
     if client.position_modified || client.yaw_modified || client.pitch_modified {
+        let relative = client.relative_teleport.take();
+        let relative_flags = relative.map_or(SyncPlayerPosLookFlags::new(), |r| r.flags);
+
+        // Parts which are not modified are sent as relative with an offset of zero.
         let flags = SyncPlayerPosLookFlags::new()
-            .with_x(!client.position_modified)
-            .with_y(!client.position_modified)
-            .with_z(!client.position_modified)
-            .with_y_rot(!client.yaw_modified)
-            .with_x_rot(!client.pitch_modified);
+            .with_x(!client.position_modified || relative_flags.x())
+            .with_y(!client.position_modified || relative_flags.y())
+            .with_z(!client.position_modified || relative_flags.z())
+            .with_y_rot(!client.yaw_modified || relative_flags.y_rot())
+            .with_x_rot(!client.pitch_modified || relative_flags.x_rot());
+
+        let value =
+            |is_relative: bool, modified: bool, current: f64, origin: Option<f64>| match origin {
+                Some(origin) if is_relative => current - origin,
+                _ if modified && !is_relative => current,
+                _ => 0.0,
+            };
 
         client.enc.write_packet(&SynchronizePlayerPosition {
-            position: if client.position_modified {
-                client.position.to_array()
-            } else {
-                [0.0; 3]
-            },
-            yaw: if client.yaw_modified { client.yaw } else { 0.0 },
-            pitch: if client.pitch_modified {
-                client.pitch
-            } else {
-                0.0
-            },
+            position: [
+                value(
+                    flags.x(),
+                    client.position_modified,
+                    client.position.x,
+                    relative.map(|r| r.position.x),
+                ),
+                value(
+                    flags.y(),
+                    client.position_modified,
+                    client.position.y,
+                    relative.map(|r| r.position.y),
+                ),
+                value(
+                    flags.z(),
+                    client.position_modified,
+                    client.position.z,
+                    relative.map(|r| r.position.z),
+                ),
+            ],
+            yaw: value(
+                flags.y_rot(),
+                client.yaw_modified,
+                client.yaw.into(),
+                relative.map(|r| r.yaw.into()),
+            ) as f32,
+            pitch: value(
+                flags.x_rot(),
+                client.pitch_modified,
+                client.pitch.into(),
+                relative.map(|r| r.pitch.into()),
+            ) as f32,
             flags,
             teleport_id: VarInt(client.teleport_id_counter as i32),
-            dismount_vehicle: false,
+            dismount_vehicle: client.dismount_on_teleport,
         });
 
         client.pending_teleports = client.pending_teleports.wrapping_add(1);
@@ -983,6 +1591,7 @@ fn update_one_client(
         client.position_modified = false;
         client.yaw_modified = false;
         client.pitch_modified = false;
+        client.dismount_on_teleport = false;
     }
 
     // This closes the "downloading terrain" screen.
@@ -1021,10 +1630,7 @@ fn update_one_client(
     client.old_position = client.position;
     client.old_view_distance = client.view_distance;
 
-    client
-        .conn
-        .try_send(client.enc.take())
-        .context("failed to flush packet queue")?;
+    client.flush().context("failed to flush packet queue")?;
 
     Ok(())
 }
@@ -1038,8 +1644,45 @@ mod tests {
     use valence_protocol::packets::S2cPlayPacket;
 
     use super::*;
+    use crate::assert_packet_count;
+    use crate::entity::{EntityKind, PLAYER_EYE_HEIGHT};
     use crate::instance::Chunk;
-    use crate::unit_test::util::scenario_single_client;
+    use crate::server::SpawnPoint;
+    use crate::unit_test::util::{create_mock_client, gen_client_info, scenario_single_client};
+
+    #[test]
+    fn bytes_sent_are_counted() {
+        let mut app = App::new();
+        let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+
+        app.update();
+
+        let client = app.world.get::<Client>(client_ent).unwrap();
+
+        assert!(client.bytes_sent() > 0);
+        assert!(!client_helper.collect_sent().unwrap().is_empty());
+
+        // Compression is disabled in tests.
+        assert_eq!(client.compression_stats(), CompressionStats::default());
+    }
+
+    #[test]
+    fn clients_start_at_spawn_point() {
+        let instance = Entity::from_raw(42);
+
+        let mut info = gen_client_info("test");
+        info.spawn = Some(SpawnPoint {
+            yaw: 90.0,
+            ..SpawnPoint::new(instance, [8.5, 64.0, -3.5])
+        });
+
+        let (client, _) = create_mock_client(info);
+
+        assert_eq!(client.instance(), instance);
+        assert_eq!(client.position(), DVec3::new(8.5, 64.0, -3.5));
+        assert_eq!(client.yaw(), 90.0);
+        assert_eq!(client.pitch(), 0.0);
+    }
 
     #[test]
     fn client_chunk_view_change() {
@@ -1122,4 +1765,241 @@ mod tests {
             assert!(loaded_chunks.contains(&pos), "{pos:?}");
         }
     }
+
+    #[test]
+    fn relative_teleports_send_offsets() -> anyhow::Result<()> {
+        let mut app = App::new();
+        let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+
+        let mut client = app.world.get_mut::<Client>(client_ent).unwrap();
+        client.set_position([0.0, 64.0, 0.0]);
+
+        app.update();
+        client_helper.clear_sent();
+
+        let mut client = app.world.get_mut::<Client>(client_ent).unwrap();
+        let flags = SyncPlayerPosLookFlags::new()
+            .with_x(true)
+            .with_y(true)
+            .with_z(true);
+        client.teleport_relative([1.0, 0.0, 2.0], 90.0, 0.0, flags);
+        client.teleport_relative([1.0, 0.0, 0.0], 0.0, 0.0, flags.with_y_rot(true));
+
+        assert_eq!(client.position(), DVec3::new(2.0, 64.0, 2.0));
+        assert_eq!(client.yaw(), 90.0);
+
+        app.update();
+
+        let teleports: Vec<_> = client_helper
+            .collect_sent()?
+            .into_iter()
+            .filter_map(|pkt| match pkt {
+                S2cPlayPacket::SynchronizePlayerPosition(pkt) => Some(pkt),
+                _ => None,
+            })
+            .collect();
+
+        assert_eq!(teleports.len(), 1);
+        assert_eq!(teleports[0].position, [2.0, 0.0, 2.0]);
+        assert_eq!(teleports[0].yaw, 90.0);
+        // The yaw was set absolutely by the first teleport.
+        assert_eq!(teleports[0].flags, flags);
+        assert!(teleports[0].dismount_vehicle);
+
+        Ok(())
+    }
+
+    #[test]
+    fn entities_and_clients_look_at_positions() -> anyhow::Result<()> {
+        let mut app = App::new();
+        let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+
+        app.update();
+        client_helper.clear_sent();
+
+        let mut npc = McEntity::new(EntityKind::Player, Entity::from_raw(0));
+        npc.set_position([0.0, 64.0, 0.0]);
+        npc.look_at([-5.0, 64.0 + PLAYER_EYE_HEIGHT, 0.0]);
+
+        assert_eq!(npc.yaw(), 90.0);
+        assert_eq!(npc.pitch(), 0.0);
+        assert_eq!(npc.head_yaw(), 90.0);
+
+        app.world
+            .get_mut::<Client>(client_ent)
+            .unwrap()
+            .face_entity(&npc);
+
+        app.update();
+
+        let look_ats: Vec<_> = client_helper
+            .collect_sent()?
+            .into_iter()
+            .filter_map(|pkt| match pkt {
+                S2cPlayPacket::LookAt(pkt) => Some(pkt),
+                _ => None,
+            })
+            .collect();
+
+        assert_eq!(look_ats.len(), 1);
+        assert_eq!(
+            look_ats[0].target_position,
+            [0.0, 64.0 + PLAYER_EYE_HEIGHT, 0.0]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn instance_environment_restored_after_override() -> anyhow::Result<()> {
+        let mut app = App::new();
+        let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+        let instance_ent = app.world.get::<Client>(client_ent).unwrap().instance();
+
+        app.update();
+
+        let sent_packets = client_helper.collect_sent()?;
+        assert_packet_count!(sent_packets, 0, S2cPlayPacket::UpdateTime(_));
+        assert_packet_count!(sent_packets, 1, S2cPlayPacket::WorldBorderInitialize(_));
+
+        client_helper.clear_sent();
+
+        let mut client = app.world.get_mut::<Client>(client_ent).unwrap();
+        client.set_time_override(18000);
+        client.set_weather_override(Weather::THUNDER);
+
+        app.update();
+
+        let sent_packets = client_helper.collect_sent()?;
+        assert_packet_count!(sent_packets, 1, S2cPlayPacket::UpdateTime(_));
+        // Begin raining, rain level and thunder level.
+        assert_packet_count!(sent_packets, 3, S2cPlayPacket::GameEvent(_));
+
+        client_helper.clear_sent();
+
+        // Changes to the instance are hidden by the override.
+        app.world
+            .get_mut::<Instance>(instance_ent)
+            .unwrap()
+            .set_weather(Weather::RAIN);
+
+        app.update();
+
+        let sent_packets = client_helper.collect_sent()?;
+        assert_packet_count!(sent_packets, 0, S2cPlayPacket::GameEvent(_));
+
+        client_helper.clear_sent();
+
+        let mut client = app.world.get_mut::<Client>(client_ent).unwrap();
+        client.set_time_override(None);
+        client.set_weather_override(None);
+
+        app.update();
+
+        let sent_packets = client_helper.collect_sent()?;
+        assert_packet_count!(sent_packets, 1, S2cPlayPacket::UpdateTime(_));
+        // Only the thunder level changes.
+        assert_packet_count!(sent_packets, 1, S2cPlayPacket::GameEvent(_));
+
+        Ok(())
+    }
+
+    #[test]
+    fn time_of_day_is_only_sent_once_set() -> anyhow::Result<()> {
+        let mut app = App::new();
+        let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+        let instance_ent = app.world.get::<Client>(client_ent).unwrap().instance();
+
+        // Clients in a plain instance run their own day-night cycle.
+        app.update();
+        app.update();
+
+        let sent_packets = client_helper.collect_sent()?;
+        assert_packet_count!(sent_packets, 0, S2cPlayPacket::UpdateTime(_));
+
+        client_helper.clear_sent();
+
+        app.world
+            .get_mut::<Instance>(instance_ent)
+            .unwrap()
+            .set_time_of_day(18000);
+
+        app.update();
+
+        // The time is positive so that clients keep advancing it.
+        let sent_packets = client_helper.collect_sent()?;
+        let times: Vec<_> = sent_packets
+            .iter()
+            .filter_map(|pkt| match pkt {
+                S2cPlayPacket::UpdateTime(pkt) => Some(pkt.time_of_day),
+                _ => None,
+            })
+            .collect();
+
+        assert_eq!(times, [18000]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn interactions_are_resolved_to_entities() {
+        use valence_protocol::packets::c2s::play::Interact;
+        use valence_protocol::types::{EntityInteraction, Hand};
+
+        use crate::client::event::{AttackEntityEvent, InteractEntityEvent};
+        use crate::entity::McEntity;
+
+        let mut app = App::new();
+        let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+        let instance_ent = app.world.get::<Client>(client_ent).unwrap().instance();
+
+        let zombie_ent = app
+            .world
+            .spawn(McEntity::new(EntityKind::Zombie, instance_ent))
+            .id();
+
+        app.update();
+
+        let zombie_id = app.world.get::<McEntity>(zombie_ent).unwrap().protocol_id();
+
+        client_helper.send(&Interact {
+            entity_id: VarInt(zombie_id),
+            interact: EntityInteraction::Attack,
+            sneaking: true,
+        });
+        client_helper.send(&Interact {
+            entity_id: VarInt(zombie_id),
+            interact: EntityInteraction::InteractAt {
+                target: [0.0, 1.0, 0.0],
+                hand: Hand::Main,
+            },
+            sneaking: false,
+        });
+        // Entities which don't exist are only reported in raw events.
+        client_helper.send(&Interact {
+            entity_id: VarInt(zombie_id + 100),
+            interact: EntityInteraction::Attack,
+            sneaking: false,
+        });
+
+        app.update();
+
+        let attacks = app.world.resource::<Events<AttackEntityEvent>>();
+        let attacks: Vec<_> = attacks.get_reader().iter(attacks).cloned().collect();
+        assert_eq!(attacks.len(), 1);
+        assert_eq!(attacks[0].client, client_ent);
+        assert_eq!(attacks[0].target, zombie_ent);
+        assert!(attacks[0].sneaking);
+
+        let interactions = app.world.resource::<Events<InteractEntityEvent>>();
+        let interactions: Vec<_> = interactions
+            .get_reader()
+            .iter(interactions)
+            .cloned()
+            .collect();
+        assert_eq!(interactions.len(), 1);
+        assert_eq!(interactions[0].target, zombie_ent);
+        assert_eq!(interactions[0].hand, Hand::Main);
+        assert_eq!(interactions[0].position, Some(Vec3::new(0.0, 1.0, 0.0)));
+    }
 }
@@ -0,0 +1,127 @@
+//! Reasons clients are disconnected and kicking clients.
+
+use bevy_ecs::prelude::*;
+use uuid::Uuid;
+use valence_protocol::{Text, Username};
+
+use crate::client::Client;
+
+/// Why a [`Client`] was disconnected.
+#[derive(Clone, PartialEq, Debug)]
+pub enum DisconnectReason {
+    /// The client closed the connection.
+    ClientQuit,
+    /// The client did not respond to keepalives in time.
+    TimedOut,
+    /// The client was kicked by the server.
+    Kicked(Text),
+    /// The client sent a packet which could not be decoded or was not valid
+    /// at the time it was received.
+    ProtocolError {
+        /// The name of the packet, or `None` if the packet could not be
+        /// decoded.
+        packet: Option<&'static str>,
+        message: String,
+    },
+    /// The server failed to update the client.
+    ServerError(String),
+}
+
+/// An event sent once for every [`Client`] which was disconnected.
+///
+/// The client entity still exists while the event is sent, but is usually
+/// despawned shortly after.
+#[derive(Clone, PartialEq, Debug)]
+pub struct DisconnectEvent {
+    pub client: Entity,
+    pub username: Username<String>,
+    pub uuid: Uuid,
+    pub reason: DisconnectReason,
+}
+
+/// A [`Component`] which kicks the [`Client`] on the same entity with the
+/// given reason at the end of the tick.
+///
+/// This is the same as calling [`Client::kick`], but can be inserted with
+/// [`Commands`] from systems which do not have access to the client.
+#[derive(Component, Clone, PartialEq, Debug)]
+pub struct Kick {
+    /// The message displayed on the disconnect screen of the client.
+    pub reason: Text,
+}
+
+impl Kick {
+    pub fn new(reason: impl Into<Text>) -> Self {
+        Self {
+            reason: reason.into(),
+        }
+    }
+}
+
+pub(crate) fn kick_clients(mut clients: Query<(&mut Client, &Kick), Added<Kick>>) {
+    for (mut client, kick) in &mut clients {
+        client.kick(kick.reason.clone());
+    }
+}
+
+pub(crate) fn send_disconnect_events(
+    mut clients: Query<(Entity, &mut Client)>,
+    mut events: EventWriter<DisconnectEvent>,
+) {
+    for (entity, mut client) in &mut clients {
+        if !client.is_disconnected() || client.disconnect_reported {
+            continue;
+        }
+
+        let client = client.bypass_change_detection();
+        client.disconnect_reported = true;
+
+        events.send(DisconnectEvent {
+            client: entity,
+            username: client.username().to_owned_username(),
+            uuid: client.uuid(),
+            reason: client
+                .disconnect_reason()
+                .cloned()
+                .unwrap_or(DisconnectReason::ClientQuit),
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy_app::App;
+
+    use super::*;
+    use crate::unit_test::util::scenario_single_client;
+
+    #[test]
+    fn kicked_clients_are_reported_once() {
+        let mut app = App::new();
+        let (client_ent, _) = scenario_single_client(&mut app);
+
+        app.update();
+
+        app.world
+            .entity_mut(client_ent)
+            .insert(Kick::new("Server closed"));
+
+        app.update();
+        app.update();
+
+        let client = app.world.get::<Client>(client_ent).unwrap();
+        assert!(client.is_disconnected());
+
+        let events = app.world.resource::<Events<DisconnectEvent>>();
+        let reasons: Vec<_> = events
+            .get_reader()
+            .iter(events)
+            .map(|event| event.reason.clone())
+            .collect();
+
+        assert_eq!(
+            reasons,
+            [DisconnectReason::Kicked(Text::from("Server closed"))]
+        );
+    }
+}
@@ -8,7 +8,6 @@ use glam::{DVec3, Vec3};
 use paste::paste;
 use tracing::warn;
 use uuid::Uuid;
-use valence_protocol::entity_meta::Pose;
 use valence_protocol::packets::c2s::play::{
     ClientCommand, PlayerAbilitiesC2s, ResourcePackC2s, SeenAdvancements,
 };
@@ -20,8 +19,9 @@ use valence_protocol::types::{
 };
 use valence_protocol::{BlockFace, BlockPos, Ident, ItemStack};
 
+use crate::client::disconnect::DisconnectReason;
 use crate::client::Client;
-use crate::entity::{EntityAnimation, EntityKind, McEntity, TrackedData};
+use crate::entity::{McEntity, McEntityManager, TrackedData};
 
 #[derive(Clone, Debug)]
 pub struct QueryBlockEntity {
@@ -154,6 +154,35 @@ pub struct InteractWithEntity {
     pub interact: EntityInteraction,
 }
 
+/// A client attacking an [`McEntity`], usually by left-clicking it. Sent
+/// alongside [`InteractWithEntity`] when the attacked entity exists.
+#[derive(Clone, Debug)]
+pub struct AttackEntityEvent {
+    pub client: Entity,
+    /// The entity of the attacked [`McEntity`].
+    pub target: Entity,
+    /// If the client was sneaking during the attack.
+    pub sneaking: bool,
+}
+
+/// A client right-clicking an [`McEntity`]. Sent alongside
+/// [`InteractWithEntity`] when the entity exists.
+///
+/// The client sends two interactions for every right click: one with the
+/// position on the hitbox of the entity that was clicked, and one without.
+#[derive(Clone, Debug)]
+pub struct InteractEntityEvent {
+    pub client: Entity,
+    /// The entity of the clicked [`McEntity`].
+    pub target: Entity,
+    pub hand: Hand,
+    /// The position on the hitbox that was clicked, relative to the position
+    /// of the entity.
+    pub position: Option<Vec3>,
+    /// If the client was sneaking during the interaction.
+    pub sneaking: bool,
+}
+
 #[derive(Clone, Debug)]
 pub struct JigsawGenerate {
     pub client: Entity,
@@ -521,6 +550,18 @@ pub struct SwingArm {
     pub hand: Hand,
 }
 
+/// A client swinging one of its arms, which it does for every left click,
+/// including clicks on nothing, and when it uses or places items. Sent
+/// alongside [`SwingArm`].
+///
+/// The swing is shown to other players by the
+/// [`hand_swing`](crate::hand_swing) module.
+#[derive(Clone, Debug)]
+pub struct HandSwingEvent {
+    pub client: Entity,
+    pub hand: Hand,
+}
+
 #[derive(Clone, Debug)]
 pub struct TeleportToEntity {
     pub client: Entity,
@@ -677,6 +718,9 @@ events! {
         TeleportToEntity
         UseItemOnBlock
         UseItem
+        AttackEntityEvent
+        InteractEntityEvent
+        HandSwingEvent
     }
 }
 
@@ -684,7 +728,10 @@ pub(crate) fn event_loop_run_criteria(
     mut clients: Query<(Entity, &mut Client)>,
     mut clients_to_check: Local<Vec<Entity>>,
     mut events: ClientEvents,
+    manager: Res<McEntityManager>,
 ) -> ShouldRun {
+    let mut packet = None;
+
     if clients_to_check.is_empty() {
         // First run of the criteria. Prepare packets.
 
@@ -695,7 +742,7 @@ pub(crate) fn event_loop_run_criteria(
 
             let Ok(bytes) = client.conn.try_recv() else {
                 // Client is disconnected.
-                client.is_disconnected = true;
+                client.disconnect(DisconnectReason::ClientQuit);
                 continue;
             };
 
@@ -706,7 +753,7 @@ pub(crate) fn event_loop_run_criteria(
 
             client.dec.queue_bytes(bytes);
 
-            match handle_one_packet(client, entity, &mut events) {
+            match handle_one_packet(client, entity, &mut events, &manager, &mut packet) {
                 Ok(had_packet) => {
                     if had_packet {
                         // We decoded one packet, but there might be more.
@@ -721,7 +768,10 @@ pub(crate) fn event_loop_run_criteria(
                         ip = %client.ip,
                         "failed to dispatch events: {e:#}"
                     );
-                    client.is_disconnected = true;
+                    client.disconnect(DisconnectReason::ProtocolError {
+                        packet,
+                        message: format!("{e:#}"),
+                    });
                 }
             }
         }
@@ -734,7 +784,7 @@ pub(crate) fn event_loop_run_criteria(
                 return false;
             };
 
-            match handle_one_packet(&mut client, entity, &mut events) {
+            match handle_one_packet(&mut client, entity, &mut events, &manager, &mut packet) {
                 Ok(had_packet) => had_packet,
                 Err(e) => {
                     // TODO: validate packets in separate systems.
@@ -744,7 +794,10 @@ pub(crate) fn event_loop_run_criteria(
                         ip = %client.ip,
                         "failed to dispatch events: {e:#}"
                     );
-                    client.is_disconnected = true;
+                    client.disconnect(DisconnectReason::ProtocolError {
+                        packet,
+                        message: format!("{e:#}"),
+                    });
 
                     false
                 }
@@ -759,16 +812,25 @@ pub(crate) fn event_loop_run_criteria(
     }
 }
 
+/// Decodes and dispatches the next packet of the client. The name of the
+/// packet is written to `packet` so that errors can be attributed to it. The
+/// entities referenced by protocol ID are resolved with `manager`.
 fn handle_one_packet(
     client: &mut Client,
     entity: Entity,
     events: &mut ClientEvents,
+    manager: &McEntityManager,
+    packet: &mut Option<&'static str>,
 ) -> anyhow::Result<bool> {
+    *packet = None;
+
     let Some(pkt) = client.dec.try_next_packet::<C2sPlayPacket>()? else {
         // No packets to decode.
         return Ok(false);
     };
 
+    *packet = Some(pkt.packet_name());
+
     match pkt {
         C2sPlayPacket::ConfirmTeleport(p) => {
             if client.pending_teleports == 0 {
@@ -904,6 +966,36 @@ fn handle_one_packet(
                 sneaking: p.sneaking,
                 interact: p.interact,
             });
+
+            if let Some(target) = manager.get_with_protocol_id(p.entity_id.0) {
+                match p.interact {
+                    EntityInteraction::Attack => {
+                        events.4.attack_entity_event.send(AttackEntityEvent {
+                            client: entity,
+                            target,
+                            sneaking: p.sneaking,
+                        });
+                    }
+                    EntityInteraction::Interact(hand) => {
+                        events.4.interact_entity_event.send(InteractEntityEvent {
+                            client: entity,
+                            target,
+                            hand,
+                            position: None,
+                            sneaking: p.sneaking,
+                        });
+                    }
+                    EntityInteraction::InteractAt { target: pos, hand } => {
+                        events.4.interact_entity_event.send(InteractEntityEvent {
+                            client: entity,
+                            target,
+                            hand,
+                            position: Some(Vec3::from(pos)),
+                            sneaking: p.sneaking,
+                        });
+                    }
+                }
+            }
         }
         C2sPlayPacket::JigsawGenerate(p) => {
             events.1.jigsaw_generate.send(JigsawGenerate {
@@ -953,7 +1045,7 @@ fn handle_one_packet(
                 old_pitch: client.pitch,
                 pitch: client.pitch,
                 old_on_ground: client.on_ground,
-                on_ground: client.on_ground,
+                on_ground: p.on_ground,
             });
 
             client.position = p.position.into();
@@ -1329,6 +1421,11 @@ fn handle_one_packet(
                 client: entity,
                 hand: p.hand,
             });
+
+            events.4.hand_swing_event.send(HandSwingEvent {
+                client: entity,
+                hand: p.hand,
+            });
         }
         C2sPlayPacket::TeleportToEntity(p) => {
             events.4.teleport_to_entity.send(TeleportToEntity {
@@ -1371,8 +1468,10 @@ fn handle_one_packet(
 /// reasonable default way.
 ///
 /// For instance, movement events are handled by changing the entity's
-/// position/rotation to match the received movement, crouching makes the
-/// entity crouch, etc.
+/// position/rotation to match the received movement, etc. Crouching and
+/// sprinting are handled by the [`PoseState`](crate::client::pose::PoseState)
+/// component, and swinging arms by the [`hand_swing`](crate::hand_swing)
+/// module.
 ///
 /// This system's primary purpose is to reduce boilerplate code in the
 /// examples, but it can be used as a quick way to get started in your own
@@ -1382,16 +1481,10 @@ fn handle_one_packet(
 /// This system must be scheduled to run in the
 /// [`EventLoop`](crate::server::EventLoop) stage. Otherwise, it may not
 /// function correctly.
-#[allow(clippy::too_many_arguments)]
 pub fn default_event_handler(
     mut clients: Query<(&mut Client, Option<&mut McEntity>)>,
     mut update_settings: EventReader<UpdateSettings>,
     mut move_player: EventReader<MovePlayer>,
-    mut start_sneaking: EventReader<StartSneaking>,
-    mut stop_sneaking: EventReader<StopSneaking>,
-    mut start_sprinting: EventReader<StartSprinting>,
-    mut stop_sprinting: EventReader<StopSprinting>,
-    mut swing_arm: EventReader<SwingArm>,
 ) {
     for UpdateSettings {
         client,
@@ -1451,57 +1544,4 @@ pub fn default_event_handler(
         entity.set_pitch(*pitch);
         entity.set_on_ground(*on_ground);
     }
-
-    for StartSneaking { client } in start_sneaking.iter() {
-        let Ok((_, Some(mut entity))) = clients.get_mut(*client) else {
-            continue
-        };
-
-        if let TrackedData::Player(player) = entity.data_mut() {
-            player.set_pose(Pose::Sneaking);
-        }
-    }
-
-    for StopSneaking { client } in stop_sneaking.iter() {
-        let Ok((_, Some(mut entity))) = clients.get_mut(*client) else {
-            continue
-        };
-
-        if let TrackedData::Player(player) = entity.data_mut() {
-            player.set_pose(Pose::Standing);
-        }
-    }
-
-    for StartSprinting { client } in start_sprinting.iter() {
-        let Ok((_, Some(mut entity))) = clients.get_mut(*client) else {
-            continue
-        };
-
-        if let TrackedData::Player(player) = entity.data_mut() {
-            player.set_sprinting(true);
-        }
-    }
-
-    for StopSprinting { client } in stop_sprinting.iter() {
-        let Ok((_, Some(mut entity))) = clients.get_mut(*client) else {
-            continue
-        };
-
-        if let TrackedData::Player(player) = entity.data_mut() {
-            player.set_sprinting(false);
-        }
-    }
-
-    for SwingArm { client, hand } in swing_arm.iter() {
-        let Ok((_, Some(mut entity))) = clients.get_mut(*client) else {
-            continue
-        };
-
-        if entity.kind() == EntityKind::Player {
-            entity.trigger_animation(match hand {
-                Hand::Main => EntityAnimation::SwingMainHand,
-                Hand::Off => EntityAnimation::SwingOffHand,
-            });
-        }
-    }
 }
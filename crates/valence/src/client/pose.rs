@@ -0,0 +1,235 @@
+//! Tracking the sneaking, sprinting, swimming and gliding state of clients.
+
+use bevy_ecs::prelude::*;
+use rustc_hash::FxHashMap;
+use valence_protocol::entity_meta::Pose;
+use valence_protocol::BlockPos;
+
+use crate::client::event::{
+    MovePlayer, StartFlyingWithElytra, StartSneaking, StartSprinting, StopSneaking, StopSprinting,
+};
+use crate::client::Client;
+use crate::entity::{McEntity, TrackedData, PLAYER_EYE_HEIGHT};
+use crate::instance::Instance;
+
+/// A [`Component`] with the movement state of the [`Client`] on the same
+/// entity. Every client has this component.
+///
+/// The state is updated from the packets sent by the client. Swimming is
+/// determined by the server from the blocks around the client. If the client
+/// also has a player [`McEntity`], its pose and flags are updated to match
+/// so that other clients see the movement state. A [`PoseChanged`] event is
+/// sent whenever the state changes.
+#[derive(Component, Copy, Clone, PartialEq, Eq, Default, Debug)]
+pub struct PoseState {
+    sneaking: bool,
+    sprinting: bool,
+    swimming: bool,
+    gliding: bool,
+}
+
+impl PoseState {
+    pub fn is_sneaking(&self) -> bool {
+        self.sneaking
+    }
+
+    pub fn is_sprinting(&self) -> bool {
+        self.sprinting
+    }
+
+    pub fn is_swimming(&self) -> bool {
+        self.swimming
+    }
+
+    /// If the client is flying with an elytra.
+    pub fn is_gliding(&self) -> bool {
+        self.gliding
+    }
+
+    /// Returns the height of the eyes of the client above its feet in this
+    /// state.
+    pub fn eye_height(&self) -> f64 {
+        if self.swimming || self.gliding {
+            0.4
+        } else if self.sneaking {
+            1.27
+        } else {
+            PLAYER_EYE_HEIGHT
+        }
+    }
+
+    /// Returns the pose of the player entity in this state.
+    pub fn pose(&self) -> Pose {
+        if self.gliding {
+            Pose::FallFlying
+        } else if self.swimming {
+            Pose::Swimming
+        } else if self.sneaking {
+            Pose::Sneaking
+        } else {
+            Pose::Standing
+        }
+    }
+}
+
+/// An event sent when the [`PoseState`] of a client has changed.
+#[derive(Clone, Debug)]
+pub struct PoseChanged {
+    pub client: Entity,
+    pub old: PoseState,
+    pub new: PoseState,
+}
+
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn update_pose_states(
+    mut clients: Query<(Entity, &Client, &mut PoseState, Option<&mut McEntity>)>,
+    instances: Query<&Instance>,
+    mut start_sneaking: EventReader<StartSneaking>,
+    mut stop_sneaking: EventReader<StopSneaking>,
+    mut start_sprinting: EventReader<StartSprinting>,
+    mut stop_sprinting: EventReader<StopSprinting>,
+    mut start_gliding: EventReader<StartFlyingWithElytra>,
+    mut move_player: EventReader<MovePlayer>,
+    mut pose_changed: EventWriter<PoseChanged>,
+) {
+    // The states of the clients before the events of this tick.
+    let mut old_states = FxHashMap::default();
+
+    let mut update = |client: Entity, f: &dyn Fn(&mut PoseState)| {
+        if let Ok((_, _, mut state, _)) = clients.get_mut(client) {
+            old_states.entry(client).or_insert(*state);
+            f(&mut state);
+        }
+    };
+
+    for event in start_sneaking.iter() {
+        update(event.client, &|state| state.sneaking = true);
+    }
+
+    for event in stop_sneaking.iter() {
+        update(event.client, &|state| state.sneaking = false);
+    }
+
+    for event in start_sprinting.iter() {
+        update(event.client, &|state| state.sprinting = true);
+    }
+
+    for event in stop_sprinting.iter() {
+        update(event.client, &|state| state.sprinting = false);
+    }
+
+    for event in start_gliding.iter() {
+        update(event.client, &|state| state.gliding = true);
+    }
+
+    for event in move_player.iter() {
+        if event.on_ground {
+            update(event.client, &|state| state.gliding = false);
+        }
+    }
+
+    for (client_id, client, mut state, entity) in &mut clients {
+        let old = old_states.get(&client_id).copied().unwrap_or(*state);
+
+        if let Ok(instance) = instances.get(client.instance()) {
+            let water_at = |y: f64| {
+                let mut pos = client.position();
+                pos.y += y;
+
+                instance.is_water_at(BlockPos::at(pos))
+            };
+
+            let in_water = water_at(0.0);
+
+            // Clients start swimming when their eyes are in water and keep
+            // swimming while they are touching water.
+            state.swimming = state.sprinting
+                && if state.swimming {
+                    in_water
+                } else {
+                    water_at(PLAYER_EYE_HEIGHT)
+                };
+
+            if state.gliding && in_water {
+                state.gliding = false;
+            }
+        }
+
+        if *state == old {
+            continue;
+        }
+
+        if let Some(mut entity) = entity {
+            if let TrackedData::Player(player) = entity.data_mut() {
+                player.set_sneaking(state.sneaking);
+                player.set_sprinting(state.sprinting);
+                player.set_swimming(state.swimming);
+                player.set_fall_flying(state.gliding);
+                player.set_pose(state.pose());
+            }
+        }
+
+        pose_changed.send(PoseChanged {
+            client: client_id,
+            old,
+            new: *state,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy_app::App;
+    use valence_protocol::block::BlockState;
+    use valence_protocol::packets::c2s::play::PlayerCommand;
+    use valence_protocol::types::Action;
+    use valence_protocol::VarInt;
+
+    use super::*;
+    use crate::entity::EntityKind;
+    use crate::instance::Chunk;
+    use crate::unit_test::util::scenario_single_client;
+
+    #[test]
+    fn sprinting_in_water_starts_swimming() {
+        let mut app = App::new();
+        let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+        let instance_ent = app.world.get::<Client>(client_ent).unwrap().instance();
+
+        let mut instance = app.world.get_mut::<Instance>(instance_ent).unwrap();
+        instance.insert_chunk([0, 0], Chunk::default());
+        instance.set_block([0, 0, 0], BlockState::WATER);
+        instance.set_block([0, 1, 0], BlockState::WATER);
+
+        app.world
+            .entity_mut(client_ent)
+            .insert(McEntity::new(EntityKind::Player, instance_ent));
+
+        app.update();
+
+        client_helper.send(&PlayerCommand {
+            entity_id: VarInt(0),
+            action_id: Action::StartSprinting,
+            jump_boost: VarInt(0),
+        });
+
+        app.update();
+
+        let state = app.world.get::<PoseState>(client_ent).unwrap();
+        assert!(state.is_sprinting());
+        assert!(state.is_swimming());
+        assert_eq!(state.pose(), Pose::Swimming);
+
+        let entity = app.world.get::<McEntity>(client_ent).unwrap();
+        let TrackedData::Player(player) = entity.data() else {
+            panic!("entity is not a player")
+        };
+        assert_eq!(player.get_pose(), Pose::Swimming);
+
+        let changes = app.world.resource::<Events<PoseChanged>>();
+        let mut reader = changes.get_reader();
+        assert!(reader
+            .iter(changes)
+            .any(|e| e.client == client_ent && !e.old.is_swimming() && e.new.is_swimming()));
+    }
+}
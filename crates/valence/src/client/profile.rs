@@ -0,0 +1,115 @@
+//! The game profiles of clients.
+
+use bevy_ecs::prelude::*;
+use uuid::Uuid;
+use valence_protocol::types::Property;
+use valence_protocol::Username;
+
+/// A [`Component`] containing the game profile of the [`Client`] on the same
+/// entity. Every client has this component, which initially contains the
+/// profile the client logged in with.
+///
+/// The properties of the profile can be modified to change the skin and cape
+/// of the player, which are part of the `textures` property. Profiles
+/// modified on the tick the client is spawned are sent to other players in
+/// place of the original profile. The [`PlayerList`] default systems update
+/// the player list entry of the client whenever the profile changes, but
+/// players who can already see the player entity of the client only see the
+/// new skin once the entity is spawned for them again.
+///
+/// [`Client`]: crate::client::Client
+/// [`PlayerList`]: crate::player_list::PlayerList
+#[derive(Component, Clone, PartialEq, Eq, Debug)]
+pub struct GameProfile {
+    username: Username<String>,
+    uuid: Uuid,
+    properties: Vec<Property>,
+}
+
+impl GameProfile {
+    pub fn new(username: Username<String>, uuid: Uuid, properties: Vec<Property>) -> Self {
+        Self {
+            username,
+            uuid,
+            properties,
+        }
+    }
+
+    pub fn username(&self) -> Username<&str> {
+        self.username.as_str_username()
+    }
+
+    pub fn uuid(&self) -> Uuid {
+        self.uuid
+    }
+
+    pub fn properties(&self) -> &[Property] {
+        &self.properties
+    }
+
+    /// Returns the property with the given name, such as `textures`.
+    pub fn property(&self, name: &str) -> Option<&Property> {
+        self.properties.iter().find(|prop| prop.name == name)
+    }
+
+    /// Adds a property, replacing any property with the same name. The
+    /// replaced property is returned.
+    pub fn set_property(&mut self, property: Property) -> Option<Property> {
+        match self.properties.iter_mut().find(|p| p.name == property.name) {
+            Some(old) => Some(std::mem::replace(old, property)),
+            None => {
+                self.properties.push(property);
+                None
+            }
+        }
+    }
+
+    /// Removes the property with the given name and returns it.
+    pub fn remove_property(&mut self, name: &str) -> Option<Property> {
+        let idx = self.properties.iter().position(|prop| prop.name == name)?;
+        Some(self.properties.remove(idx))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy_app::App;
+
+    use super::*;
+    use crate::player_list::PlayerList;
+    use crate::unit_test::util::scenario_single_client;
+
+    #[test]
+    fn modified_profiles_update_the_player_list() {
+        let mut app = App::new();
+        let (client_ent, _) = scenario_single_client(&mut app);
+        app.add_system_set(PlayerList::default_system_set());
+
+        let cape = Property {
+            name: "textures".into(),
+            value: "e30=".into(),
+            signature: None,
+        };
+
+        app.world
+            .get_mut::<GameProfile>(client_ent)
+            .unwrap()
+            .set_property(cape.clone());
+
+        app.update();
+
+        let uuid = app.world.get::<GameProfile>(client_ent).unwrap().uuid();
+        let player_list = app.world.resource::<PlayerList>();
+        assert_eq!(player_list.get(uuid).unwrap().properties(), [cape]);
+
+        app.world
+            .get_mut::<GameProfile>(client_ent)
+            .unwrap()
+            .remove_property("textures");
+
+        app.update();
+
+        let player_list = app.world.resource::<PlayerList>();
+        assert!(player_list.get(uuid).unwrap().properties().is_empty());
+    }
+}
@@ -0,0 +1,774 @@
+//! Registering commands and dispatching them to events.
+//!
+//! Commands added to the [`CommandRegistry`] resource are sent to clients in
+//! the `Commands` packet, so they are suggested and tab-completed by the
+//! client. When a client runs a registered command, a [`CommandExecuted`]
+//! event is sent with the name the command was registered with, even if the
+//! client used one of its aliases:
+//!
+//! ```
+//! use valence::command::{CommandInfo, CommandRegistry};
+//!
+//! let mut registry = CommandRegistry::new();
+//!
+//! registry.register(
+//!     CommandInfo::new("spawn")
+//!         .with_alias("hub")
+//!         .with_permission("lobby.spawn")
+//!         .with_cooldown(100),
+//! );
+//!
+//! assert_eq!(registry.get("hub").unwrap().name(), "spawn");
+//! ```
+//!
+//! Commands which require a permission node are only sent to clients with
+//! the node in their [`Permissions`], so clients can not tab-complete
+//! commands they are not allowed to run. Commands which are not registered
+//! are not handled and are only sent as [`ChatCommand`] events.
+//!
+//! # Help
+//!
+//! [`CommandRegistry::register_help`] adds a `/help` command generated from
+//! the descriptions and usages of the registered commands. `/help [page]`
+//! lists the commands the client may run, and `/help <command>` shows a
+//! single command with its aliases. Clicking a command suggests it in the
+//! chat box. The page header, the navigation buttons and the errors use
+//! translation keys, so they are shown in the language of the client.
+
+use std::collections::{HashMap, HashSet};
+
+use bevy_ecs::prelude::*;
+use tracing::warn;
+use valence_protocol::packets::s2c::commands::{Node, NodeData, Parser, StringArg};
+use valence_protocol::packets::s2c::play::Commands as CommandsS2c;
+use valence_protocol::text::{Color, TextFormat};
+use valence_protocol::translation_key::{
+    BOOK_PAGE_INDICATOR, COMMANDS_HELP_FAILED, SPECTATOR_MENU_NEXT_PAGE,
+    SPECTATOR_MENU_PREVIOUS_PAGE,
+};
+use valence_protocol::{Text, VarInt};
+
+use crate::client::event::ChatCommand;
+use crate::client::Client;
+use crate::server::Server;
+
+/// The name, aliases and restrictions of a registered command.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct CommandInfo {
+    name: String,
+    aliases: Vec<String>,
+    description: String,
+    usage: String,
+    permission: Option<String>,
+    cooldown: u32,
+}
+
+impl CommandInfo {
+    /// Creates a command with the given name, which must not contain spaces
+    /// or the leading slash.
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            aliases: vec![],
+            description: String::new(),
+            usage: String::new(),
+            permission: None,
+            cooldown: 0,
+        }
+    }
+
+    /// Adds another name the command can be run with.
+    #[must_use]
+    pub fn with_alias(mut self, alias: impl Into<String>) -> Self {
+        self.aliases.push(alias.into());
+        self
+    }
+
+    #[must_use]
+    pub fn with_description(mut self, description: impl Into<String>) -> Self {
+        self.description = description.into();
+        self
+    }
+
+    /// Sets the arguments of the command, such as `<player> [reason]`.
+    #[must_use]
+    pub fn with_usage(mut self, usage: impl Into<String>) -> Self {
+        self.usage = usage.into();
+        self
+    }
+
+    /// Sets the permission node clients need to run the command.
+    #[must_use]
+    pub fn with_permission(mut self, permission: impl Into<String>) -> Self {
+        self.permission = Some(permission.into());
+        self
+    }
+
+    /// Sets the number of ticks a client has to wait before running the
+    /// command again.
+    #[must_use]
+    pub fn with_cooldown(mut self, ticks: u32) -> Self {
+        self.cooldown = ticks;
+        self
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn aliases(&self) -> &[String] {
+        &self.aliases
+    }
+
+    pub fn description(&self) -> &str {
+        &self.description
+    }
+
+    pub fn usage(&self) -> &str {
+        &self.usage
+    }
+
+    pub fn permission(&self) -> Option<&str> {
+        self.permission.as_deref()
+    }
+
+    pub fn cooldown(&self) -> u32 {
+        self.cooldown
+    }
+
+    /// Returns `true` if a client with the given permissions may run the
+    /// command.
+    pub fn is_allowed(&self, permissions: Option<&Permissions>) -> bool {
+        match &self.permission {
+            Some(node) => permissions.map_or(false, |p| p.has(node)),
+            None => true,
+        }
+    }
+}
+
+/// A [`Resource`] containing the registered commands.
+#[derive(Resource, Clone, Default, Debug)]
+pub struct CommandRegistry {
+    commands: Vec<CommandInfo>,
+    /// Maps names and aliases to indices into `commands`.
+    names: HashMap<String, usize>,
+    /// The number of commands per page of `/help`, if it is registered.
+    help_page_size: Option<usize>,
+}
+
+const HELP_COMMAND: &str = "help";
+
+impl CommandRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers a command, replacing any command with the same name. Aliases
+    /// which are already used by another command are skipped.
+    pub fn register(&mut self, command: CommandInfo) -> &mut Self {
+        self.unregister(&command.name);
+
+        let idx = self.commands.len();
+        self.names.insert(command.name.clone(), idx);
+
+        for alias in &command.aliases {
+            if self.names.contains_key(alias) {
+                warn!(
+                    "alias \"{alias}\" of command \"{}\" is already in use",
+                    command.name
+                );
+            } else {
+                self.names.insert(alias.clone(), idx);
+            }
+        }
+
+        self.commands.push(command);
+        self
+    }
+
+    /// Removes the command with the given name and returns it.
+    pub fn unregister(&mut self, name: &str) -> Option<CommandInfo> {
+        let idx = *self.names.get(name)?;
+
+        if self.commands[idx].name != name {
+            // Aliases can not be used to unregister commands.
+            return None;
+        }
+
+        let command = self.commands.remove(idx);
+
+        if name == HELP_COMMAND {
+            self.help_page_size = None;
+        }
+
+        self.names.retain(|_, i| *i != idx);
+        for i in self.names.values_mut() {
+            if *i > idx {
+                *i -= 1;
+            }
+        }
+
+        Some(command)
+    }
+
+    /// Returns the command with the given name or alias.
+    pub fn get(&self, name: &str) -> Option<&CommandInfo> {
+        self.names.get(name).map(|&idx| &self.commands[idx])
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &CommandInfo> + '_ {
+        self.commands.iter()
+    }
+
+    /// Registers the `/help` command, which lists the given number of
+    /// commands per page. Registering another command named `help` replaces
+    /// it.
+    pub fn register_help(&mut self, commands_per_page: usize) -> &mut Self {
+        self.register(
+            CommandInfo::new(HELP_COMMAND)
+                .with_alias("?")
+                .with_description("Shows the commands you can use")
+                .with_usage("[page|command]"),
+        );
+        self.help_page_size = Some(commands_per_page.max(1));
+        self
+    }
+
+    /// Returns the lines of a page of `/help`, starting from page 1, or `None`
+    /// if the page does not exist.
+    fn help_page(&self, permissions: Option<&Permissions>, page: usize) -> Option<Vec<Text>> {
+        let page_size = self.help_page_size?;
+
+        let mut commands: Vec<_> = self.iter().filter(|c| c.is_allowed(permissions)).collect();
+        commands.sort_unstable_by(|a, b| a.name.cmp(&b.name));
+
+        let page_count = ((commands.len() + page_size - 1) / page_size).max(1);
+
+        if page == 0 || page > page_count {
+            return None;
+        }
+
+        let mut lines = vec![("--- ".into_text()
+            + Text::translate(
+                BOOK_PAGE_INDICATOR,
+                vec![(page as i64).into(), (page_count as i64).into()],
+            )
+            + " ---")
+            .color(Color::GOLD)];
+
+        lines.extend(
+            commands
+                .iter()
+                .skip((page - 1) * page_size)
+                .take(page_size)
+                .copied()
+                .map(help_entry),
+        );
+
+        if page_count > 1 {
+            let mut navigation = Text::text("");
+
+            if page > 1 {
+                navigation += ("« ".into_text()
+                    + Text::translate(SPECTATOR_MENU_PREVIOUS_PAGE, vec![]))
+                .color(Color::GREEN)
+                .on_click_run_command(format!("/{HELP_COMMAND} {}", page - 1));
+            }
+
+            if page > 1 && page < page_count {
+                navigation += " | ".color(Color::GRAY);
+            }
+
+            if page < page_count {
+                navigation += (Text::translate(SPECTATOR_MENU_NEXT_PAGE, vec![]) + " »")
+                    .color(Color::GREEN)
+                    .on_click_run_command(format!("/{HELP_COMMAND} {}", page + 1));
+            }
+
+            lines.push(navigation);
+        }
+
+        Some(lines)
+    }
+
+    /// Returns the lines of `/help <command>`, or `None` if the command does
+    /// not exist or the client may not run it.
+    fn help_command(&self, name: &str, permissions: Option<&Permissions>) -> Option<Vec<Text>> {
+        let command = self.get(name).filter(|c| c.is_allowed(permissions))?;
+
+        let mut lines = vec![help_entry(command)];
+
+        let aliases: Vec<_> = command
+            .aliases
+            .iter()
+            .filter(|alias| self.get(alias).map(|c| &c.name) == Some(&command.name))
+            .collect();
+
+        if !aliases.is_empty() {
+            let mut line = Text::text("");
+
+            for (i, alias) in aliases.into_iter().enumerate() {
+                if i > 0 {
+                    line += ", ".color(Color::GRAY);
+                }
+
+                line += format!("/{alias}")
+                    .color(Color::YELLOW)
+                    .on_click_suggest_command(format!("/{alias} "));
+            }
+
+            lines.push(line);
+        }
+
+        Some(lines)
+    }
+
+    /// Builds the command tree of the `Commands` packet containing the
+    /// commands a client with the given permissions may run.
+    fn command_tree(&self, permissions: Option<&Permissions>) -> Vec<Node> {
+        let mut nodes = vec![Node {
+            children: vec![],
+            data: NodeData::Root,
+            executable: false,
+            redirect_node: None,
+        }];
+
+        for command in self.iter().filter(|c| c.is_allowed(permissions)) {
+            let literal = nodes.len();
+
+            nodes.push(Node {
+                children: vec![VarInt(literal as i32 + 1)],
+                data: NodeData::Literal {
+                    name: &command.name,
+                },
+                executable: true,
+                redirect_node: None,
+            });
+
+            // The arguments of commands are not parsed by the server, so
+            // they accept any text.
+            nodes.push(Node {
+                children: vec![],
+                data: NodeData::Argument {
+                    name: "args",
+                    parser: Parser::String(StringArg::GreedyPhrase),
+                    suggestion: None,
+                },
+                executable: true,
+                redirect_node: None,
+            });
+
+            nodes[0].children.push(VarInt(literal as i32));
+
+            for alias in &command.aliases {
+                if self.names.get(alias) != self.names.get(&command.name) {
+                    continue;
+                }
+
+                nodes[0].children.push(VarInt(nodes.len() as i32));
+                nodes.push(Node {
+                    children: vec![],
+                    data: NodeData::Literal { name: alias },
+                    executable: true,
+                    redirect_node: Some(VarInt(literal as i32)),
+                });
+            }
+        }
+
+        nodes
+    }
+}
+
+/// A line of `/help` with the usage and the description of the command.
+/// Clicking the usage suggests the command in the chat box.
+fn help_entry(command: &CommandInfo) -> Text {
+    let mut usage = format!("/{}", command.name);
+
+    if !command.usage.is_empty() {
+        usage.push(' ');
+        usage.push_str(&command.usage);
+    }
+
+    let mut line = Text::text("")
+        + usage
+            .color(Color::YELLOW)
+            .on_click_suggest_command(format!("/{} ", command.name));
+
+    if !command.description.is_empty() {
+        line += format!(" - {}", command.description).color(Color::GRAY);
+    }
+
+    line
+}
+
+/// A [`Component`] with the permission nodes granted to the [`Client`] on the
+/// same entity. Clients without this component have no permissions.
+///
+/// Nodes are dot separated names such as `lobby.spawn`. Granting a node
+/// ending in `*` grants every node starting with the text before it, so
+/// `lobby.*` grants `lobby.spawn` and `*` grants every node.
+#[derive(Component, Clone, PartialEq, Eq, Default, Debug)]
+pub struct Permissions {
+    nodes: HashSet<String>,
+}
+
+impl Permissions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[must_use]
+    pub fn with(mut self, node: impl Into<String>) -> Self {
+        self.grant(node);
+        self
+    }
+
+    /// Grants the node. Returns `false` if it was already granted.
+    pub fn grant(&mut self, node: impl Into<String>) -> bool {
+        self.nodes.insert(node.into())
+    }
+
+    /// Revokes the node. Returns `false` if it was not granted. Nodes
+    /// granted by a wildcard can not be revoked individually.
+    pub fn revoke(&mut self, node: &str) -> bool {
+        self.nodes.remove(node)
+    }
+
+    /// Returns `true` if the node is granted, either directly or by a
+    /// wildcard.
+    pub fn has(&self, node: &str) -> bool {
+        if self.nodes.contains(node) || self.nodes.contains("*") {
+            return true;
+        }
+
+        node.match_indices('.')
+            .any(|(i, _)| self.nodes.contains(&format!("{}*", &node[..=i])))
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &str> + '_ {
+        self.nodes.iter().map(String::as_str)
+    }
+}
+
+/// A [`Component`] with the ticks at which the [`Client`] on the same entity
+/// may run commands with a cooldown again. This is added to clients the first
+/// time they run such a command.
+#[derive(Component, Clone, PartialEq, Eq, Default, Debug)]
+pub struct CommandCooldowns {
+    ready_at: HashMap<String, i64>,
+}
+
+impl CommandCooldowns {
+    /// Returns the number of ticks until the command can be run again at the
+    /// given tick, or zero if it can be run.
+    pub fn remaining(&self, command: &str, current_tick: i64) -> i64 {
+        self.ready_at
+            .get(command)
+            .map_or(0, |ready_at| (ready_at - current_tick).max(0))
+    }
+
+    /// Lets the command be run again immediately.
+    pub fn reset(&mut self, command: &str) {
+        self.ready_at.remove(command);
+    }
+
+    pub fn clear(&mut self) {
+        self.ready_at.clear();
+    }
+}
+
+/// An event sent when a client runs a registered command it is allowed to
+/// run.
+#[derive(Clone, Debug)]
+pub struct CommandExecuted {
+    pub client: Entity,
+    /// The name the command was registered with.
+    pub command: String,
+    /// The text following the command name, without leading whitespace.
+    pub args: String,
+}
+
+pub(crate) fn send_command_trees(
+    registry: Option<Res<CommandRegistry>>,
+    mut clients: Query<(
+        Entity,
+        &mut Client,
+        Option<&Permissions>,
+        Option<ChangeTrackers<Permissions>>,
+    )>,
+    removed_permissions: RemovedComponents<Permissions>,
+) {
+    let Some(registry) = registry else {
+        return;
+    };
+
+    let removed: HashSet<_> = removed_permissions.iter().collect();
+
+    for (entity, mut client, permissions, changes) in &mut clients {
+        if registry.is_changed()
+            || client.is_new()
+            || changes.map_or(false, |c| c.is_changed())
+            || removed.contains(&entity)
+        {
+            client.write_packet(&CommandsS2c {
+                commands: registry.command_tree(permissions),
+                root_index: VarInt(0),
+            });
+        }
+    }
+}
+
+/// Answers the `/help` command registered with
+/// [`CommandRegistry::register_help`].
+pub(crate) fn send_help(
+    registry: Option<Res<CommandRegistry>>,
+    mut executed: EventReader<CommandExecuted>,
+    mut clients: Query<(&mut Client, Option<&Permissions>)>,
+) {
+    for event in executed.iter() {
+        let Some(registry) = &registry else {
+            continue;
+        };
+
+        if event.command != HELP_COMMAND || registry.help_page_size.is_none() {
+            continue;
+        }
+
+        let Ok((mut client, permissions)) = clients.get_mut(event.client) else {
+            continue;
+        };
+
+        let lines = match event.args.split_whitespace().next() {
+            None => registry.help_page(permissions, 1),
+            Some(arg) => match arg.parse() {
+                Ok(page) => registry.help_page(permissions, page),
+                Err(_) => registry.help_command(arg.trim_start_matches('/'), permissions),
+            },
+        };
+
+        match lines {
+            Some(lines) => {
+                for line in lines {
+                    client.send_message(line);
+                }
+            }
+            None => {
+                client.send_message(Text::translate(COMMANDS_HELP_FAILED, vec![]).color(Color::RED))
+            }
+        }
+    }
+}
+
+pub(crate) fn dispatch_commands(
+    registry: Option<Res<CommandRegistry>>,
+    server: Res<Server>,
+    mut commands: Commands,
+    mut chat_commands: EventReader<ChatCommand>,
+    mut clients: Query<(
+        &mut Client,
+        Option<&Permissions>,
+        Option<&mut CommandCooldowns>,
+    )>,
+    mut executed: EventWriter<CommandExecuted>,
+) {
+    let mut new_cooldowns: HashMap<Entity, CommandCooldowns> = HashMap::new();
+
+    for chat_command in chat_commands.iter() {
+        let Some(registry) = &registry else {
+            continue;
+        };
+
+        let (name, args) = chat_command
+            .command
+            .split_once(' ')
+            .unwrap_or((&chat_command.command, ""));
+
+        let Some(command) = registry.get(name) else {
+            continue;
+        };
+
+        let Ok((mut client, permissions, cooldowns)) = clients.get_mut(chat_command.client) else {
+            continue;
+        };
+
+        if !command.is_allowed(permissions) {
+            client.send_message("You do not have permission to use this command".color(Color::RED));
+            continue;
+        }
+
+        if command.cooldown > 0 {
+            // Cooldowns of clients without the component are kept until the
+            // end of the tick so commands run on the same tick see them.
+            let cooldowns = match cooldowns {
+                Some(cooldowns) => cooldowns.into_inner(),
+                None => new_cooldowns.entry(chat_command.client).or_default(),
+            };
+
+            let current_tick = server.current_tick();
+            let remaining = cooldowns.remaining(&command.name, current_tick);
+
+            if remaining > 0 {
+                let tps = server.shared().tps();
+                let seconds = (remaining + tps - 1) / tps;
+
+                client.send_message(
+                    format!("You must wait {seconds}s before using this command again")
+                        .color(Color::RED),
+                );
+                continue;
+            }
+
+            cooldowns
+                .ready_at
+                .insert(command.name.clone(), current_tick + command.cooldown as i64);
+        }
+
+        executed.send(CommandExecuted {
+            client: chat_command.client,
+            command: command.name.clone(),
+            args: args.trim_start().to_owned(),
+        });
+    }
+
+    for (client, cooldowns) in new_cooldowns {
+        commands.entity(client).insert(cooldowns);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy_app::App;
+    use valence_protocol::packets::c2s::play::ChatCommand as ChatCommandC2s;
+    use valence_protocol::packets::S2cPlayPacket;
+
+    use super::*;
+    use crate::assert_packet_count;
+    use crate::unit_test::util::scenario_single_client;
+
+    #[test]
+    fn permission_wildcards() {
+        let permissions = Permissions::new().with("lobby.*").with("kits.starter");
+
+        assert!(permissions.has("lobby.spawn"));
+        assert!(permissions.has("lobby.admin.kick"));
+        assert!(permissions.has("kits.starter"));
+        assert!(!permissions.has("kits.vip"));
+        assert!(!permissions.has("lobby"));
+        assert!(Permissions::new().with("*").has("anything"));
+    }
+
+    #[test]
+    fn aliases_resolve_to_commands() {
+        let mut registry = CommandRegistry::new();
+
+        registry
+            .register(CommandInfo::new("spawn").with_alias("hub"))
+            .register(CommandInfo::new("lobby").with_alias("hub").with_alias("l"));
+
+        assert_eq!(registry.get("hub").unwrap().name(), "spawn");
+        assert_eq!(registry.get("l").unwrap().name(), "lobby");
+
+        assert!(registry.unregister("hub").is_none());
+        assert!(registry.unregister("spawn").is_some());
+
+        assert!(registry.get("hub").is_none());
+        assert_eq!(registry.get("l").unwrap().name(), "lobby");
+    }
+
+    #[test]
+    fn help_is_paginated_and_filtered() {
+        let mut registry = CommandRegistry::new();
+
+        registry
+            .register_help(2)
+            .register(CommandInfo::new("spawn").with_alias("hub"))
+            .register(CommandInfo::new("warp").with_usage("<name>"))
+            .register(CommandInfo::new("ban").with_permission("admin.ban"));
+
+        // Header, two commands and the navigation.
+        assert_eq!(registry.help_page(None, 1).unwrap().len(), 4);
+        assert_eq!(registry.help_page(None, 2).unwrap().len(), 3);
+        assert!(registry.help_page(None, 3).is_none());
+        assert!(registry.help_page(None, 0).is_none());
+
+        let admin = Permissions::new().with("*");
+        assert_eq!(registry.help_page(Some(&admin), 2).unwrap().len(), 4);
+
+        // The command and its aliases.
+        assert_eq!(registry.help_command("hub", None).unwrap().len(), 2);
+        assert_eq!(registry.help_command("warp", None).unwrap().len(), 1);
+        assert!(registry.help_command("ban", None).is_none());
+
+        registry.unregister("help");
+        assert!(registry.help_page(None, 1).is_none());
+    }
+
+    #[test]
+    fn commands_are_dispatched_with_restrictions() {
+        let mut app = App::new();
+        let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+
+        let mut registry = CommandRegistry::new();
+        registry
+            .register(
+                CommandInfo::new("spawn")
+                    .with_alias("hub")
+                    .with_cooldown(100),
+            )
+            .register(CommandInfo::new("ban").with_permission("admin.ban"));
+        app.insert_resource(registry);
+
+        app.update();
+
+        // The tree contains the root, the spawn command with its argument and
+        // alias, but not the ban command.
+        let sent_packets = client_helper.collect_sent().unwrap();
+        let trees: Vec<_> = sent_packets
+            .iter()
+            .filter_map(|pkt| match pkt {
+                S2cPlayPacket::Commands(pkt) => Some(pkt.commands.len()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(trees, [4]);
+
+        client_helper.clear_sent();
+
+        let mut run = |command: &str| {
+            client_helper.send(&ChatCommandC2s {
+                command,
+                timestamp: 0,
+                salt: 0,
+                argument_signatures: vec![],
+                message_count: VarInt(0),
+                acknowledgement: &[0; 3],
+            });
+        };
+
+        run("hub  now");
+        run("spawn");
+        run("ban Steve");
+        app.update();
+
+        let events = app.world.resource::<Events<CommandExecuted>>();
+        let executed: Vec<_> = events
+            .get_reader()
+            .iter(events)
+            .map(|e| (e.command.as_str(), e.args.as_str()))
+            .collect();
+        assert_eq!(executed, [("spawn", "now")]);
+
+        let sent_packets = client_helper.collect_sent().unwrap();
+        assert_packet_count!(sent_packets, 2, S2cPlayPacket::SystemChatMessage(_));
+
+        // Granting the permission sends the new tree.
+        client_helper.clear_sent();
+        app.world
+            .entity_mut(client_ent)
+            .insert(Permissions::new().with("admin.*"));
+        app.update();
+
+        let sent_packets = client_helper.collect_sent().unwrap();
+        assert_packet_count!(sent_packets, 1, S2cPlayPacket::Commands(_));
+    }
+}
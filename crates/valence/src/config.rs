@@ -3,15 +3,18 @@ use std::sync::Arc;
 
 use async_trait::async_trait;
 use bevy_app::{App, Plugin};
+use md5::Md5;
 use serde::Serialize;
+use sha2::{Digest, Sha256};
 use tokio::runtime::Handle;
 use tracing::error;
-use uuid::Uuid;
-use valence_protocol::{Text, Username};
+use uuid::{Builder, Uuid};
+use valence_protocol::{Text, Username, DEFAULT_COMPRESSION_LEVEL};
 
 use crate::biome::Biome;
 use crate::dimension::Dimension;
-use crate::server::{NewClientInfo, SharedServer};
+use crate::ip_filter::IpFilter;
+use crate::server::{NewClientInfo, SharedServer, SpawnPoint};
 
 #[derive(Clone)]
 #[non_exhaustive]
@@ -65,18 +68,60 @@ pub struct ServerPlugin<A> {
     ///
     /// [`ConnectionMode::Online`]
     pub connection_mode: ConnectionMode,
+    /// If connections are encrypted in [offline mode]. This protects the
+    /// connection on untrusted networks without authenticating players with
+    /// the session server. Connections are always encrypted in online mode,
+    /// and never when the server is behind a proxy.
+    ///
+    /// Note that vanilla clients still contact the session server before
+    /// enabling encryption, so players need a valid account to join.
+    ///
+    /// # Default Value
+    ///
+    /// `false`
+    ///
+    /// [offline mode]: ConnectionMode::Offline
+    pub offline_encryption: bool,
+    /// How the UUIDs of players are derived in [offline mode]. The UUID of a
+    /// player can also be looked up elsewhere with
+    /// [`AsyncCallbacks::offline_uuid`].
+    ///
+    /// # Default Value
+    ///
+    /// [`OfflineUuid::UsernameHash`]
+    ///
+    /// [offline mode]: ConnectionMode::Offline
+    pub offline_uuid: OfflineUuid,
+    /// The IP addresses connections are accepted from. Connections from other
+    /// addresses are closed before the handshake is read.
+    ///
+    /// # Default Value
+    ///
+    /// Connections from every address are accepted.
+    pub ip_filter: IpFilter,
     /// The compression threshold to use for compressing packets. For a
     /// compression threshold of `Some(N)`, packets with encoded lengths >= `N`
     /// are compressed while all others are not. `None` disables compression
     /// completely.
     ///
-    /// If the server is used behind a proxy on the same machine, you will
-    /// likely want to disable compression.
+    /// If the server is used behind a proxy on the same machine or only
+    /// reachable over a LAN, you will likely want to disable compression,
+    /// since bandwidth is cheaper than the CPU time spent compressing.
     ///
     /// # Default Value
     ///
     /// Compression is enabled with an unspecified threshold.
     pub compression_threshold: Option<u32>,
+    /// The zlib compression level from 0 (fastest) to 9 (smallest) used for
+    /// packets above the [compression threshold]. Has no effect if
+    /// compression is disabled.
+    ///
+    /// # Default Value
+    ///
+    /// [`DEFAULT_COMPRESSION_LEVEL`]
+    ///
+    /// [compression threshold]: Self::compression_threshold
+    pub compression_level: u32,
     /// The maximum capacity (in bytes) of the buffer used to hold incoming
     /// packet data.
     ///
@@ -145,7 +190,11 @@ impl<A: AsyncCallbacks> ServerPlugin<A> {
                 // Note: Some people have problems using valence when this is enabled by default.
                 prevent_proxy_connections: false,
             },
+            offline_encryption: false,
+            offline_uuid: OfflineUuid::default(),
+            ip_filter: IpFilter::new(),
             compression_threshold: Some(256),
+            compression_level: DEFAULT_COMPRESSION_LEVEL,
             incoming_capacity: 2097152, // 2 MiB
             outgoing_capacity: 8388608, // 8 MiB
             dimensions: [Dimension::default()].as_slice().into(),
@@ -188,6 +237,27 @@ impl<A: AsyncCallbacks> ServerPlugin<A> {
         self
     }
 
+    /// See [`Self::offline_encryption`].
+    #[must_use]
+    pub fn with_offline_encryption(mut self, offline_encryption: bool) -> Self {
+        self.offline_encryption = offline_encryption;
+        self
+    }
+
+    /// See [`Self::offline_uuid`].
+    #[must_use]
+    pub fn with_offline_uuid(mut self, offline_uuid: OfflineUuid) -> Self {
+        self.offline_uuid = offline_uuid;
+        self
+    }
+
+    /// See [`Self::ip_filter`].
+    #[must_use]
+    pub fn with_ip_filter(mut self, ip_filter: IpFilter) -> Self {
+        self.ip_filter = ip_filter;
+        self
+    }
+
     /// See [`Self::compression_threshold`].
     #[must_use]
     pub fn with_compression_threshold(mut self, compression_threshold: Option<u32>) -> Self {
@@ -195,6 +265,13 @@ impl<A: AsyncCallbacks> ServerPlugin<A> {
         self
     }
 
+    /// See [`Self::compression_level`].
+    #[must_use]
+    pub fn with_compression_level(mut self, compression_level: u32) -> Self {
+        self.compression_level = compression_level;
+        self
+    }
+
     /// See [`Self::incoming_capacity`].
     #[must_use]
     pub fn with_incoming_capacity(mut self, incoming_capacity: usize) -> Self {
@@ -264,11 +341,70 @@ pub trait AsyncCallbacks: Send + Sync + 'static {
         }
     }
 
+    /// Called for every new connection which passes the
+    /// [IP filter](ServerPlugin::ip_filter), before the handshake is read.
+    /// If this method returns `false`, the connection is closed without
+    /// sending anything to the peer.
+    ///
+    /// This method is called from within a tokio runtime.
+    ///
+    /// # Default Implementation
+    ///
+    /// Every connection is accepted.
+    async fn filter_connection(&self, shared: &SharedServer, remote_addr: SocketAddr) -> bool {
+        #![allow(unused_variables)]
+        true
+    }
+
+    /// Called for each client after [`LoginStart`] is received but before the
+    /// client is authenticated. The username has not been verified yet, so
+    /// this is only suitable for cheap checks such as IP bans. If this method
+    /// returns with `Err(reason)`, then the client is immediately disconnected
+    /// with `reason` as the displayed message.
+    ///
+    /// This method is called from within a tokio runtime.
+    ///
+    /// # Default Implementation
+    ///
+    /// The client is allowed to continue unconditionally.
+    ///
+    /// [`LoginStart`]: valence_protocol::packets::c2s::login::LoginStart
+    async fn pre_auth(
+        &self,
+        shared: &SharedServer,
+        remote_addr: SocketAddr,
+        username: Username<&str>,
+    ) -> Result<(), Text> {
+        #![allow(unused_variables)]
+        Ok(())
+    }
+
+    /// Called for each client in [offline mode] to determine their UUID, for
+    /// example by looking it up in a database of players. If this method
+    /// returns with an error, the client is disconnected.
+    ///
+    /// This method is called from within a tokio runtime.
+    ///
+    /// # Default Implementation
+    ///
+    /// The UUID is derived from the username as configured by
+    /// [`ServerPlugin::offline_uuid`].
+    ///
+    /// [offline mode]: ConnectionMode::Offline
+    async fn offline_uuid(
+        &self,
+        shared: &SharedServer,
+        username: Username<&str>,
+    ) -> anyhow::Result<Uuid> {
+        Ok(shared.offline_uuid().uuid(username.as_str()))
+    }
+
     /// Called for each client after successful authentication (if online mode
-    /// is enabled) to determine if they can join the server. On success, a
-    /// new entity is spawned with the [`Client`] component. If this method
-    /// returns with `Err(reason)`, then the client is immediately
-    /// disconnected with `reason` as the displayed message.
+    /// is enabled) to determine if they can join the server. On success,
+    /// [`Self::pre_spawn`] is called and a new entity is spawned with the
+    /// [`Client`] component. If this method returns with `Err(reason)`, then
+    /// the client is immediately disconnected with `reason` as the displayed
+    /// message.
     ///
     /// This method is the appropriate place to perform asynchronous
     /// operations such as database queries which may take some time to
@@ -286,6 +422,27 @@ pub trait AsyncCallbacks: Send + Sync + 'static {
         Ok(())
     }
 
+    /// Called for each client after [`Self::login`] succeeds to determine
+    /// where the client is spawned. This is the place to load the location of
+    /// the player from a database. If this method returns with `Ok(None)`,
+    /// the client is spawned without an instance and must be moved into one
+    /// by a system. If it returns with `Err(reason)`, then the client is
+    /// immediately disconnected with `reason` as the displayed message.
+    ///
+    /// This method is called from within a tokio runtime.
+    ///
+    /// # Default Implementation
+    ///
+    /// The client is spawned without an instance.
+    async fn pre_spawn(
+        &self,
+        shared: &SharedServer,
+        info: &NewClientInfo,
+    ) -> Result<Option<SpawnPoint>, Text> {
+        #![allow(unused_variables)]
+        Ok(None)
+    }
+
     /// Called upon every client login to obtain the full URL to use for session
     /// server requests. This is done to authenticate player accounts. This
     /// method is not called unless [online mode] is enabled.
@@ -352,7 +509,8 @@ pub enum ConnectionMode {
     /// Disables client authentication with the configured session server.
     /// Clients can join with any username and UUID they choose, potentially
     /// gaining privileges they would not otherwise have. Additionally,
-    /// encryption is disabled and Minecraft's default skins will be used.
+    /// encryption is disabled unless [`ServerPlugin::offline_encryption`] is
+    /// set and Minecraft's default skins will be used.
     ///
     /// This mode should be used for development purposes only and not for
     /// publicly exposed servers.
@@ -391,6 +549,36 @@ pub enum ConnectionMode {
     },
 }
 
+/// How the UUIDs of players are derived from their usernames in
+/// [offline mode](ConnectionMode::Offline).
+#[derive(Copy, Clone, PartialEq, Eq, Default, Debug)]
+#[non_exhaustive]
+pub enum OfflineUuid {
+    /// A SHA-256 hash of the username. This is what Valence has always used.
+    #[default]
+    UsernameHash,
+    /// The UUID the vanilla server and most other server software give to
+    /// offline players, which is derived from `OfflinePlayer:<username>`.
+    /// Players keep their identities when a server is migrated to Valence.
+    Vanilla,
+    /// A new random UUID every time a player joins.
+    Random,
+}
+
+impl OfflineUuid {
+    /// Returns the UUID of the player with the given username.
+    pub fn uuid(self, username: &str) -> Uuid {
+        match self {
+            OfflineUuid::UsernameHash => Uuid::from_slice(&Sha256::digest(username)[..16]).unwrap(),
+            OfflineUuid::Vanilla => {
+                let hash = Md5::digest(format!("OfflinePlayer:{username}"));
+                Builder::from_md5_bytes(hash.as_slice().try_into().unwrap()).into_uuid()
+            }
+            OfflineUuid::Random => Builder::from_random_bytes(rand::random()).into_uuid(),
+        }
+    }
+}
+
 /// Minecraft's standard ticks per second (TPS).
 pub const DEFAULT_TPS: i64 = 20;
 
@@ -434,3 +622,24 @@ pub struct PlayerSampleEntry {
     /// The player UUID.
     pub id: Uuid,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn offline_uuids() {
+        assert_eq!(
+            OfflineUuid::Vanilla.uuid("Notch").to_string(),
+            "b50ad385-829d-3141-a216-7e7d7539ba7f"
+        );
+        assert_eq!(
+            OfflineUuid::UsernameHash.uuid("Notch"),
+            OfflineUuid::UsernameHash.uuid("Notch")
+        );
+        assert_ne!(
+            OfflineUuid::Random.uuid("Notch"),
+            OfflineUuid::Random.uuid("Notch")
+        );
+    }
+}
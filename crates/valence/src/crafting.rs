@@ -0,0 +1,854 @@
+//! Recipes and crafting.
+//!
+//! Inserting the [`RecipeRegistry`] resource declares its recipes to clients
+//! and makes the server responsible for crafting: the result slot of the
+//! crafting grid in the player inventory and in crafting tables is computed
+//! from the recipes, and taking the result consumes the ingredients on the
+//! server. Without the resource, crafting grids are ordinary slots.
+//!
+//! ```
+//! use valence::crafting::{Ingredient, Recipe, RecipeRegistry};
+//! use valence::protocol::{ident, ItemKind, ItemStack};
+//!
+//! let mut registry = RecipeRegistry::new();
+//!
+//! registry.insert(
+//!     ident!("valence:torch"),
+//!     Recipe::shaped(
+//!         &["c", "s"],
+//!         &[
+//!             ('c', Ingredient::new([ItemKind::Coal, ItemKind::Charcoal])),
+//!             ('s', ItemKind::Stick.into()),
+//!         ],
+//!         ItemStack::new(ItemKind::Torch, 4, None),
+//!     ),
+//! );
+//! ```
+//!
+//! Only the recipes unlocked in the [`RecipeBook`] of a client are shown in
+//! its recipe book, but every recipe can be crafted.
+
+use std::collections::{HashMap, HashSet};
+use std::ops::Range;
+
+use bevy_ecs::prelude::*;
+use valence_protocol::packets::s2c::declare_recipes::{
+    CraftingCategory, DeclaredRecipe, Ingredient as DeclaredIngredient, SmeltCategory,
+};
+use valence_protocol::packets::s2c::play::{DeclareRecipes, UpdateRecipeBook};
+use valence_protocol::packets::s2c::update_recipe_book::UpdateRecipeBookAction;
+use valence_protocol::types::ClickContainerMode;
+use valence_protocol::{Ident, ItemKind, ItemStack, VarInt};
+
+use crate::client::Client;
+use crate::inventory::{Inventory, InventoryKind};
+
+/// The items accepted in one slot of a recipe.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Ingredient {
+    items: Vec<ItemKind>,
+}
+
+impl Ingredient {
+    /// An ingredient accepting any of the items.
+    pub fn new(items: impl IntoIterator<Item = ItemKind>) -> Self {
+        Self {
+            items: items.into_iter().collect(),
+        }
+    }
+
+    pub fn items(&self) -> &[ItemKind] {
+        &self.items
+    }
+
+    pub fn matches(&self, stack: &ItemStack) -> bool {
+        self.items.contains(&stack.item)
+    }
+
+    fn to_declared(&self) -> DeclaredIngredient {
+        self.items
+            .iter()
+            .map(|&item| Some(ItemStack::new(item, 1, None)))
+            .collect()
+    }
+}
+
+impl From<ItemKind> for Ingredient {
+    fn from(item: ItemKind) -> Self {
+        Self::new([item])
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum Recipe {
+    /// A crafting recipe with its ingredients arranged in a pattern. The
+    /// pattern can be placed anywhere in the grid and mirrored horizontally.
+    Shaped {
+        width: u8,
+        height: u8,
+        /// The ingredients in the pattern, row by row. `None` is an empty
+        /// slot.
+        ingredients: Vec<Option<Ingredient>>,
+        result: ItemStack,
+    },
+    /// A crafting recipe with ingredients which can be placed in any slot.
+    Shapeless {
+        ingredients: Vec<Ingredient>,
+        result: ItemStack,
+    },
+    /// A furnace recipe.
+    Smelting {
+        ingredient: Ingredient,
+        result: ItemStack,
+        experience: f32,
+        /// The number of ticks smelting takes.
+        cooking_time: u32,
+    },
+    /// A smithing table recipe, which upgrades the base item with the
+    /// addition.
+    Smithing {
+        base: Ingredient,
+        addition: Ingredient,
+        result: ItemStack,
+    },
+}
+
+impl Recipe {
+    /// Creates a shaped recipe from rows of characters, which are mapped to
+    /// ingredients by the key. Spaces are empty slots, and empty rows and
+    /// columns around the pattern are removed.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the pattern is empty or larger than 3 by 3, or if a
+    /// character is not in the key.
+    #[track_caller]
+    pub fn shaped(pattern: &[&str], key: &[(char, Ingredient)], result: ItemStack) -> Self {
+        let rows: Vec<Vec<char>> = pattern.iter().map(|row| row.chars().collect()).collect();
+        let is_filled = |x: usize, y: usize| rows[y].get(x).map_or(false, |&c| c != ' ');
+
+        let max_width = rows.iter().map(Vec::len).max().unwrap_or(0);
+        let xs: Vec<_> = (0..max_width)
+            .filter(|&x| (0..rows.len()).any(|y| is_filled(x, y)))
+            .collect();
+        let ys: Vec<_> = (0..rows.len())
+            .filter(|&y| (0..max_width).any(|x| is_filled(x, y)))
+            .collect();
+
+        let (Some(&min_x), Some(&max_x), Some(&min_y), Some(&max_y)) =
+            (xs.first(), xs.last(), ys.first(), ys.last())
+        else {
+            panic!("recipe pattern is empty");
+        };
+
+        let width = max_x - min_x + 1;
+        let height = max_y - min_y + 1;
+
+        assert!(
+            width <= 3 && height <= 3,
+            "recipe pattern is larger than 3 by 3"
+        );
+
+        let mut ingredients = vec![];
+
+        for row in &rows[min_y..=max_y] {
+            for x in min_x..=max_x {
+                ingredients.push(match row.get(x) {
+                    None | Some(' ') => None,
+                    Some(c) => Some(
+                        key.iter()
+                            .find(|(k, _)| k == c)
+                            .unwrap_or_else(|| panic!("character '{c}' is not in the key"))
+                            .1
+                            .clone(),
+                    ),
+                });
+            }
+        }
+
+        Self::Shaped {
+            width: width as u8,
+            height: height as u8,
+            ingredients,
+            result,
+        }
+    }
+
+    pub fn shapeless<I>(ingredients: I, result: ItemStack) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Into<Ingredient>,
+    {
+        Self::Shapeless {
+            ingredients: ingredients.into_iter().map(Into::into).collect(),
+            result,
+        }
+    }
+
+    pub fn result(&self) -> &ItemStack {
+        match self {
+            Recipe::Shaped { result, .. }
+            | Recipe::Shapeless { result, .. }
+            | Recipe::Smelting { result, .. }
+            | Recipe::Smithing { result, .. } => result,
+        }
+    }
+
+    /// Returns `true` if the items in a crafting grid match this recipe. The
+    /// grid is given row by row with the given width.
+    pub fn matches_grid(&self, grid: &[Option<&ItemStack>], grid_width: usize) -> bool {
+        match self {
+            Recipe::Shaped {
+                width,
+                height,
+                ingredients,
+                ..
+            } => {
+                let (width, height) = (*width as usize, *height as usize);
+
+                if grid_width == 0 || ingredients.len() != width * height {
+                    return false;
+                }
+
+                let filled: Vec<_> = grid
+                    .iter()
+                    .enumerate()
+                    .filter_map(|(i, stack)| stack.map(|_| i))
+                    .collect();
+
+                let (Some(min_x), Some(max_x), Some(min_y), Some(max_y)) = (
+                    filled.iter().map(|i| i % grid_width).min(),
+                    filled.iter().map(|i| i % grid_width).max(),
+                    filled.iter().map(|i| i / grid_width).min(),
+                    filled.iter().map(|i| i / grid_width).max(),
+                ) else {
+                    return false;
+                };
+
+                if max_x - min_x + 1 != width || max_y - min_y + 1 != height {
+                    return false;
+                }
+
+                let matches = |mirrored: bool| {
+                    (0..height).all(|y| {
+                        (0..width).all(|x| {
+                            let ingredient = if mirrored {
+                                &ingredients[y * width + width - 1 - x]
+                            } else {
+                                &ingredients[y * width + x]
+                            };
+
+                            match (ingredient, grid[(min_y + y) * grid_width + min_x + x]) {
+                                (None, None) => true,
+                                (Some(ingredient), Some(stack)) => ingredient.matches(stack),
+                                _ => false,
+                            }
+                        })
+                    })
+                };
+
+                matches(false) || matches(true)
+            }
+            Recipe::Shapeless { ingredients, .. } => {
+                let stacks: Vec<_> = grid.iter().flatten().copied().collect();
+
+                stacks.len() == ingredients.len()
+                    && assign_ingredients(&stacks, ingredients, &mut vec![false; stacks.len()])
+            }
+            Recipe::Smelting { .. } | Recipe::Smithing { .. } => false,
+        }
+    }
+
+    fn to_declared<'a>(&'a self, recipe_id: Ident<&'a str>) -> DeclaredRecipe<'a> {
+        match self {
+            Recipe::Shaped {
+                width,
+                height,
+                ingredients,
+                result,
+            } => DeclaredRecipe::CraftingShaped {
+                recipe_id,
+                width: VarInt(*width as i32),
+                height: VarInt(*height as i32),
+                group: "",
+                category: CraftingCategory::Misc,
+                ingredients: ingredients
+                    .iter()
+                    .map(|i| i.as_ref().map_or(vec![], Ingredient::to_declared))
+                    .collect(),
+                result: Some(result.clone()),
+            },
+            Recipe::Shapeless {
+                ingredients,
+                result,
+            } => DeclaredRecipe::CraftingShapeless {
+                recipe_id,
+                group: "",
+                category: CraftingCategory::Misc,
+                ingredients: ingredients.iter().map(Ingredient::to_declared).collect(),
+                result: Some(result.clone()),
+            },
+            Recipe::Smelting {
+                ingredient,
+                result,
+                experience,
+                cooking_time,
+            } => DeclaredRecipe::Smelting {
+                recipe_id,
+                group: "",
+                category: SmeltCategory::Misc,
+                ingredient: ingredient.to_declared(),
+                result: Some(result.clone()),
+                experience: *experience,
+                cooking_time: VarInt(*cooking_time as i32),
+            },
+            Recipe::Smithing {
+                base,
+                addition,
+                result,
+            } => DeclaredRecipe::Smithing {
+                recipe_id,
+                base: base.to_declared(),
+                addition: addition.to_declared(),
+                result: Some(result.clone()),
+            },
+        }
+    }
+}
+
+/// Returns `true` if every stack can be matched with a different ingredient.
+fn assign_ingredients(
+    stacks: &[&ItemStack],
+    ingredients: &[Ingredient],
+    used: &mut [bool],
+) -> bool {
+    let Some((stack, rest)) = stacks.split_first() else {
+        return true;
+    };
+
+    for (i, ingredient) in ingredients.iter().enumerate() {
+        if !used[i] && ingredient.matches(stack) {
+            used[i] = true;
+
+            if assign_ingredients(rest, ingredients, used) {
+                return true;
+            }
+
+            used[i] = false;
+        }
+    }
+
+    false
+}
+
+/// A [`Resource`] containing the recipes known to the server.
+#[derive(Resource, Clone, Default, Debug)]
+pub struct RecipeRegistry {
+    recipes: HashMap<Ident<String>, Recipe>,
+}
+
+impl RecipeRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a recipe, returning the recipe it replaced.
+    pub fn insert(&mut self, id: impl Into<Ident<String>>, recipe: Recipe) -> Option<Recipe> {
+        self.recipes.insert(id.into(), recipe)
+    }
+
+    pub fn remove(&mut self, id: Ident<&str>) -> Option<Recipe> {
+        self.recipes.remove(&id.to_owned_ident())
+    }
+
+    pub fn get(&self, id: Ident<&str>) -> Option<&Recipe> {
+        self.recipes.get(&id.to_owned_ident())
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (Ident<&str>, &Recipe)> + '_ {
+        self.recipes.iter().map(|(id, r)| (id.as_str_ident(), r))
+    }
+
+    /// Returns a crafting recipe matching the items in the grid. If several
+    /// recipes match, which one is returned is unspecified.
+    pub fn find_crafting(
+        &self,
+        grid: &[Option<&ItemStack>],
+        grid_width: usize,
+    ) -> Option<(Ident<&str>, &Recipe)> {
+        self.iter()
+            .find(|(_, recipe)| recipe.matches_grid(grid, grid_width))
+    }
+}
+
+/// A [`Component`] with the recipes unlocked in the recipe book of the
+/// [`Client`] on the same entity.
+#[derive(Component, Clone, Default, Debug)]
+pub struct RecipeBook {
+    unlocked: HashSet<Ident<String>>,
+    added: Vec<Ident<String>>,
+    removed: Vec<Ident<String>>,
+}
+
+impl RecipeBook {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Unlocks the recipe, which shows a notification to the client. Returns
+    /// `false` if the recipe was already unlocked.
+    pub fn unlock(&mut self, id: impl Into<Ident<String>>) -> bool {
+        let id = id.into();
+
+        if self.unlocked.insert(id.clone()) {
+            self.removed.retain(|r| *r != id);
+            self.added.push(id);
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Removes the recipe from the recipe book. Returns `false` if the recipe
+    /// was not unlocked.
+    pub fn lock(&mut self, id: Ident<&str>) -> bool {
+        let id = id.to_owned_ident();
+
+        if self.unlocked.remove(&id) {
+            self.added.retain(|r| *r != id);
+            self.removed.push(id);
+            true
+        } else {
+            false
+        }
+    }
+
+    pub fn is_unlocked(&self, id: Ident<&str>) -> bool {
+        self.unlocked.contains(&id.to_owned_ident())
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = Ident<&str>> + '_ {
+        self.unlocked.iter().map(|id| id.as_str_ident())
+    }
+}
+
+fn update_recipe_book<'a>(
+    action: UpdateRecipeBookAction<'a>,
+    recipe_ids: Vec<Ident<&'a str>>,
+) -> UpdateRecipeBook<'a> {
+    UpdateRecipeBook {
+        action,
+        crafting_recipe_book_open: false,
+        crafting_recipe_book_filter_active: false,
+        smelting_recipe_book_open: false,
+        smelting_recipe_book_filter_active: false,
+        blast_furnace_recipe_book_open: false,
+        blast_furnace_recipe_book_filter_active: false,
+        smoker_recipe_book_open: false,
+        smoker_recipe_book_filter_active: false,
+        recipe_ids,
+    }
+}
+
+/// Declares the recipes to clients and sends changes to their recipe books.
+pub(crate) fn send_recipes(
+    registry: Option<Res<RecipeRegistry>>,
+    mut clients: Query<(&mut Client, Option<&mut RecipeBook>)>,
+) {
+    let Some(registry) = registry else {
+        return;
+    };
+
+    for (mut client, book) in &mut clients {
+        let is_new = client.is_new();
+
+        if is_new || registry.is_changed() {
+            client.write_packet(&DeclareRecipes {
+                recipes: registry
+                    .iter()
+                    .map(|(id, recipe)| recipe.to_declared(id))
+                    .collect(),
+            });
+        }
+
+        let Some(mut book) = book else {
+            continue;
+        };
+
+        if is_new || book.is_added() {
+            client.write_packet(&update_recipe_book(
+                UpdateRecipeBookAction::Init { recipe_ids: vec![] },
+                book.iter().collect(),
+            ));
+        } else {
+            if !book.added.is_empty() {
+                client.write_packet(&update_recipe_book(
+                    UpdateRecipeBookAction::Add,
+                    book.added.iter().map(|id| id.as_str_ident()).collect(),
+                ));
+            }
+
+            if !book.removed.is_empty() {
+                client.write_packet(&update_recipe_book(
+                    UpdateRecipeBookAction::Remove,
+                    book.removed.iter().map(|id| id.as_str_ident()).collect(),
+                ));
+            }
+        }
+
+        if !book.added.is_empty() || !book.removed.is_empty() {
+            book.added.clear();
+            book.removed.clear();
+        }
+    }
+}
+
+/// The width of the crafting grid of inventories of the kind. The grid
+/// follows the result in slot 0.
+fn grid_width(kind: InventoryKind) -> Option<usize> {
+    match kind {
+        InventoryKind::Player => Some(2),
+        InventoryKind::Crafting => Some(3),
+        _ => None,
+    }
+}
+
+fn grid_slots(width: usize) -> Range<u16> {
+    1..1 + (width * width) as u16
+}
+
+/// Returns `true` if the inventory has a crafting grid.
+pub(crate) fn has_crafting_grid(inventory: &Inventory) -> bool {
+    grid_width(inventory.kind()).is_some()
+}
+
+/// Returns the result of the crafting grid of the inventory.
+fn crafting_result(registry: &RecipeRegistry, inventory: &Inventory) -> Option<ItemStack> {
+    let width = grid_width(inventory.kind())?;
+    let grid: Vec<_> = grid_slots(width).map(|slot| inventory.slot(slot)).collect();
+
+    registry
+        .find_crafting(&grid, width)
+        .map(|(_, recipe)| recipe.result().clone())
+}
+
+/// Removes one item from every slot of the crafting grid, leaving behind
+/// containers such as buckets.
+fn consume_ingredients(inventory: &mut Inventory, width: usize) {
+    for slot in grid_slots(width) {
+        let Some(stack) = inventory.slot(slot) else {
+            continue;
+        };
+
+        let new = if stack.count() > 1 {
+            let mut stack = stack.clone();
+            stack.set_count(stack.count() - 1);
+            Some(stack)
+        } else {
+            remainder(stack.item).map(|item| ItemStack::new(item, 1, None))
+        };
+
+        inventory.replace_slot(slot, new);
+    }
+}
+
+/// The item left in the crafting grid after the item is used in a recipe.
+fn remainder(item: ItemKind) -> Option<ItemKind> {
+    match item {
+        ItemKind::WaterBucket
+        | ItemKind::LavaBucket
+        | ItemKind::MilkBucket
+        | ItemKind::PowderSnowBucket => Some(ItemKind::Bucket),
+        ItemKind::HoneyBottle | ItemKind::DragonBreath => Some(ItemKind::GlassBottle),
+        _ => None,
+    }
+}
+
+pub(crate) fn can_stack(a: &ItemStack, b: &ItemStack) -> bool {
+    a.item == b.item && a.nbt == b.nbt
+}
+
+/// Puts the stack in the main inventory and hotbar of a player inventory,
+/// starting from the end of the hotbar. Nothing is moved and `false` is
+/// returned if the stack does not fit completely.
+pub(crate) fn deposit(inventory: &mut Inventory, stack: &ItemStack) -> bool {
+    let max = stack.item.max_stack();
+    let slots = (9..45).rev();
+
+    let room: u32 = slots
+        .clone()
+        .map(|slot| match inventory.slot(slot) {
+            None => max as u32,
+            Some(s) if can_stack(s, stack) => max.saturating_sub(s.count()) as u32,
+            Some(_) => 0,
+        })
+        .sum();
+
+    if room < stack.count() as u32 {
+        return false;
+    }
+
+    let mut remaining = stack.count();
+
+    for slot in slots.clone() {
+        match inventory.slot(slot) {
+            Some(s) if can_stack(s, stack) && s.count() < max => {
+                let mut s = s.clone();
+                let n = remaining.min(max - s.count());
+                s.set_count(s.count() + n);
+                inventory.replace_slot(slot, s);
+                remaining -= n;
+            }
+            _ => {}
+        }
+
+        if remaining == 0 {
+            return true;
+        }
+    }
+
+    for slot in slots {
+        if inventory.slot(slot).is_none() {
+            let mut s = stack.clone();
+            let n = remaining.min(max);
+            s.set_count(n);
+            inventory.replace_slot(slot, s);
+            remaining -= n;
+        }
+
+        if remaining == 0 {
+            return true;
+        }
+    }
+
+    true
+}
+
+/// Handles a click on the result slot of the crafting grid in `grid`.
+/// Clicking puts the result on the cursor, and shift-clicking crafts as many
+/// items as possible into the inventory of the player, which is `player` or
+/// `grid` itself for the crafting grid of the player inventory.
+pub(crate) fn take_crafting_result(
+    registry: &RecipeRegistry,
+    client: &mut Client,
+    grid: &mut Inventory,
+    mut player: Option<&mut Inventory>,
+    mode: ClickContainerMode,
+) {
+    let Some(width) = grid_width(grid.kind()) else {
+        return;
+    };
+
+    match mode {
+        ClickContainerMode::Click => {
+            let Some(result) = crafting_result(registry, grid) else {
+                return;
+            };
+
+            let cursor = match client.cursor_item() {
+                None => result,
+                Some(cursor)
+                    if can_stack(cursor, &result)
+                        && cursor.count() as u16 + result.count() as u16
+                            <= cursor.item.max_stack() as u16 =>
+                {
+                    let mut cursor = cursor.clone();
+                    cursor.set_count(cursor.count() + result.count());
+                    cursor
+                }
+                Some(_) => return,
+            };
+
+            consume_ingredients(grid, width);
+            client.replace_cursor_item(cursor);
+        }
+        ClickContainerMode::ShiftClick => {
+            // A stack of the result is at most 64 crafts.
+            for _ in 0..64 {
+                let Some(result) = crafting_result(registry, grid) else {
+                    break;
+                };
+
+                let target = match player.as_deref_mut() {
+                    Some(player) => player,
+                    None => &mut *grid,
+                };
+
+                if !deposit(target, &result) {
+                    break;
+                }
+
+                consume_ingredients(grid, width);
+            }
+        }
+        _ => {}
+    }
+
+    grid.replace_slot(0, crafting_result(registry, grid));
+}
+
+/// Updates the result slot of changed crafting grids.
+pub(crate) fn update_crafting_results(
+    registry: Option<Res<RecipeRegistry>>,
+    mut inventories: Query<&mut Inventory, Changed<Inventory>>,
+) {
+    let Some(registry) = registry else {
+        return;
+    };
+
+    for mut inventory in &mut inventories {
+        if has_crafting_grid(&inventory) {
+            let result = crafting_result(&registry, &inventory);
+
+            // Avoid marking the inventory as changed again.
+            if inventory.slot(0) != result.as_ref() {
+                inventory.replace_slot(0, result);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy_app::App;
+    use valence_protocol::ident;
+    use valence_protocol::packets::c2s::play::ClickContainer as ClickContainerC2s;
+    use valence_protocol::packets::S2cPlayPacket;
+
+    use super::*;
+    use crate::assert_packet_count;
+    use crate::unit_test::util::scenario_single_client;
+
+    fn stack(item: ItemKind, count: u8) -> ItemStack {
+        ItemStack::new(item, count, None)
+    }
+
+    #[test]
+    fn shaped_recipes_match_anywhere_and_mirrored() {
+        let recipe = Recipe::shaped(
+            &["   ", "## ", " # "],
+            &[('#', ItemKind::Stick.into())],
+            stack(ItemKind::Torch, 1),
+        );
+
+        assert!(matches!(
+            recipe,
+            Recipe::Shaped {
+                width: 2,
+                height: 2,
+                ..
+            }
+        ));
+
+        let stick = stack(ItemKind::Stick, 1);
+        let s = Some(&stick);
+
+        assert!(recipe.matches_grid(&[None, None, None, None, s, s, None, None, s], 3));
+        assert!(recipe.matches_grid(&[s, s, s, None], 2));
+        assert!(!recipe.matches_grid(&[s, s, None, s], 2));
+        assert!(!recipe.matches_grid(&[s, s, s, s], 2));
+    }
+
+    #[test]
+    fn shapeless_recipes_match_any_order() {
+        let recipe = Recipe::shapeless(
+            [
+                Ingredient::new([ItemKind::Sugar]),
+                Ingredient::new([ItemKind::Sugar, ItemKind::Egg]),
+            ],
+            stack(ItemKind::Cake, 1),
+        );
+
+        let sugar = stack(ItemKind::Sugar, 1);
+        let egg = stack(ItemKind::Egg, 1);
+
+        assert!(recipe.matches_grid(&[Some(&egg), None, None, Some(&sugar)], 2));
+        assert!(recipe.matches_grid(&[Some(&sugar), Some(&sugar), None, None], 2));
+        assert!(!recipe.matches_grid(&[Some(&egg), Some(&egg), None, None], 2));
+        assert!(!recipe.matches_grid(&[Some(&sugar), None, None, None], 2));
+    }
+
+    #[test]
+    fn crafting_in_player_inventory() -> anyhow::Result<()> {
+        let mut app = App::new();
+        let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+
+        let mut registry = RecipeRegistry::new();
+        registry.insert(
+            ident!("valence:planks"),
+            Recipe::shapeless([ItemKind::OakLog], stack(ItemKind::OakPlanks, 4)),
+        );
+        app.insert_resource(registry);
+
+        let mut book = RecipeBook::new();
+        book.unlock(ident!("valence:planks"));
+        app.world.entity_mut(client_ent).insert(book);
+
+        app.update();
+
+        let sent_packets = client_helper.collect_sent()?;
+        assert_packet_count!(sent_packets, 1, S2cPlayPacket::DeclareRecipes(_));
+        assert_packet_count!(sent_packets, 1, S2cPlayPacket::UpdateRecipeBook(_));
+
+        // Putting logs in the grid shows the result.
+        app.world
+            .get_mut::<Inventory>(client_ent)
+            .unwrap()
+            .replace_slot(1, stack(ItemKind::OakLog, 2));
+
+        app.update();
+
+        let inventory = app.world.get::<Inventory>(client_ent).unwrap();
+        assert_eq!(inventory.slot(0), Some(&stack(ItemKind::OakPlanks, 4)));
+
+        // Taking the result consumes a log, even if the client claims it
+        // didn't.
+        let state_id = app
+            .world
+            .get::<Client>(client_ent)
+            .unwrap()
+            .inventory_state_id
+            .0;
+
+        client_helper.send(&ClickContainerC2s {
+            window_id: 0,
+            state_id: VarInt(state_id),
+            slot_idx: 0,
+            button: 0,
+            mode: ClickContainerMode::Click,
+            slots: vec![(0, None)],
+            carried_item: Some(stack(ItemKind::OakPlanks, 64)),
+        });
+
+        app.update();
+
+        let client = app.world.get::<Client>(client_ent).unwrap();
+        assert_eq!(client.cursor_item(), Some(&stack(ItemKind::OakPlanks, 4)));
+
+        let inventory = app.world.get::<Inventory>(client_ent).unwrap();
+        assert_eq!(inventory.slot(1), Some(&stack(ItemKind::OakLog, 1)));
+        assert_eq!(inventory.slot(0), Some(&stack(ItemKind::OakPlanks, 4)));
+
+        // Shift-clicking crafts the remaining log into the hotbar.
+        let state_id = client.inventory_state_id.0;
+
+        client_helper.send(&ClickContainerC2s {
+            window_id: 0,
+            state_id: VarInt(state_id),
+            slot_idx: 0,
+            button: 0,
+            mode: ClickContainerMode::ShiftClick,
+            slots: vec![],
+            carried_item: None,
+        });
+
+        app.update();
+
+        let inventory = app.world.get::<Inventory>(client_ent).unwrap();
+        assert_eq!(inventory.slot(1), None);
+        assert_eq!(inventory.slot(0), None);
+        assert_eq!(inventory.slot(44), Some(&stack(ItemKind::OakPlanks, 4)));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,257 @@
+//! Validation of the items creative mode clients put in their inventory.
+//!
+//! Clients in creative mode can put any item in their inventory, including
+//! oversized stacks and arbitrary NBT data. Inserting the [`CreativeRules`]
+//! resource makes the server check these items first:
+//!
+//! ```
+//! use valence::creative::CreativeRules;
+//! use valence::protocol::ItemKind;
+//!
+//! let rules = CreativeRules::new()
+//!     .with_denied_items([ItemKind::CommandBlock, ItemKind::StructureBlock])
+//!     .with_max_nbt_size(1024);
+//! # let _ = rules;
+//! ```
+//!
+//! Items which are changed or rejected by the rules are reported with a
+//! [`CreativeItemFiltered`] event. A rejected item is not put in the
+//! inventory, and the client is sent the item in the slot instead.
+
+use std::collections::HashSet;
+use std::fmt;
+
+use bevy_ecs::prelude::*;
+use valence_protocol::{ItemKind, ItemStack};
+
+/// The top level NBT tags of items kept by [`CreativeRules::new`].
+pub const DEFAULT_ALLOWED_NBT_KEYS: &[&str] = &[
+    "display",
+    "Enchantments",
+    "StoredEnchantments",
+    "Damage",
+    "Unbreakable",
+    "RepairCost",
+    "HideFlags",
+    "CustomModelData",
+    "Potion",
+    "CustomPotionEffects",
+    "CustomPotionColor",
+];
+
+/// The default limit of the size of the NBT data of items in bytes.
+pub const DEFAULT_MAX_NBT_SIZE: usize = 8192;
+
+type Validator = Box<dyn Fn(Entity, &mut ItemStack) -> bool + Send + Sync>;
+
+/// A [`Resource`] with the rules items put in inventories by creative mode
+/// clients must follow.
+#[derive(Resource)]
+pub struct CreativeRules {
+    allowed_items: Option<HashSet<ItemKind>>,
+    denied_items: HashSet<ItemKind>,
+    max_stack_size: Option<u8>,
+    allowed_nbt_keys: Option<HashSet<String>>,
+    max_nbt_size: Option<usize>,
+    validator: Option<Validator>,
+}
+
+impl CreativeRules {
+    /// Creates rules which allow every item up to its usual stack size and
+    /// keep only the [`DEFAULT_ALLOWED_NBT_KEYS`] of at most
+    /// [`DEFAULT_MAX_NBT_SIZE`] bytes.
+    pub fn new() -> Self {
+        Self {
+            allowed_items: None,
+            denied_items: HashSet::new(),
+            max_stack_size: None,
+            allowed_nbt_keys: Some(
+                DEFAULT_ALLOWED_NBT_KEYS
+                    .iter()
+                    .map(|&key| key.to_owned())
+                    .collect(),
+            ),
+            max_nbt_size: Some(DEFAULT_MAX_NBT_SIZE),
+            validator: None,
+        }
+    }
+
+    /// Only allows the given items.
+    #[must_use]
+    pub fn with_allowed_items(mut self, items: impl IntoIterator<Item = ItemKind>) -> Self {
+        self.allowed_items = Some(items.into_iter().collect());
+        self
+    }
+
+    /// Rejects the given items.
+    #[must_use]
+    pub fn with_denied_items(mut self, items: impl IntoIterator<Item = ItemKind>) -> Self {
+        self.denied_items.extend(items);
+        self
+    }
+
+    /// Limits the size of stacks to the given number of items instead of the
+    /// usual stack size of each item.
+    #[must_use]
+    pub fn with_max_stack_size(mut self, max_stack_size: u8) -> Self {
+        self.max_stack_size = Some(max_stack_size);
+        self
+    }
+
+    /// Keeps only the given top level tags of the NBT data of items.
+    #[must_use]
+    pub fn with_allowed_nbt_keys<I>(mut self, keys: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Into<String>,
+    {
+        self.allowed_nbt_keys = Some(keys.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Keeps every tag of the NBT data of items.
+    #[must_use]
+    pub fn with_any_nbt_keys(mut self) -> Self {
+        self.allowed_nbt_keys = None;
+        self
+    }
+
+    /// Removes the NBT data of items if it is larger than the given number of
+    /// bytes.
+    #[must_use]
+    pub fn with_max_nbt_size(mut self, bytes: usize) -> Self {
+        self.max_nbt_size = Some(bytes);
+        self
+    }
+
+    /// Sets a function run after the other rules, which is given the client
+    /// and the item. The function can change the item, or return `false` to
+    /// reject it.
+    #[must_use]
+    pub fn with_validator(
+        mut self,
+        validator: impl Fn(Entity, &mut ItemStack) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        self.validator = Some(Box::new(validator));
+        self
+    }
+
+    /// Returns the item the client is given instead of the item it put in its
+    /// inventory, or `None` if the item is rejected.
+    pub fn apply(&self, client: Entity, stack: &ItemStack) -> Option<ItemStack> {
+        if self.denied_items.contains(&stack.item)
+            || self
+                .allowed_items
+                .as_ref()
+                .map_or(false, |items| !items.contains(&stack.item))
+        {
+            return None;
+        }
+
+        let mut stack = stack.clone();
+
+        let max_stack_size = self
+            .max_stack_size
+            .unwrap_or_else(|| stack.item.max_stack());
+        if stack.count() > max_stack_size {
+            stack.set_count(max_stack_size);
+        }
+
+        if let Some(nbt) = &mut stack.nbt {
+            if let Some(keys) = &self.allowed_nbt_keys {
+                nbt.retain(|key, _| keys.contains(key));
+            }
+
+            let too_large = self
+                .max_nbt_size
+                .map_or(false, |max| nbt.written_size("") > max);
+
+            if too_large || nbt.is_empty() {
+                stack.nbt = None;
+            }
+        }
+
+        if let Some(validator) = &self.validator {
+            if !validator(client, &mut stack) {
+                return None;
+            }
+        }
+
+        Some(stack)
+    }
+}
+
+impl Default for CreativeRules {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for CreativeRules {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CreativeRules")
+            .field("allowed_items", &self.allowed_items)
+            .field("denied_items", &self.denied_items)
+            .field("max_stack_size", &self.max_stack_size)
+            .field("allowed_nbt_keys", &self.allowed_nbt_keys)
+            .field("max_nbt_size", &self.max_nbt_size)
+            .field("validator", &self.validator.is_some())
+            .finish()
+    }
+}
+
+/// An event sent when the [`CreativeRules`] change or reject an item put in
+/// the inventory of a creative mode client.
+#[derive(Clone, Debug)]
+pub struct CreativeItemFiltered {
+    pub client: Entity,
+    pub slot: u16,
+    /// The item sent by the client.
+    pub original: ItemStack,
+    /// The item put in the slot instead, or `None` if the item was rejected.
+    pub filtered: Option<ItemStack>,
+}
+
+#[cfg(test)]
+mod tests {
+    use valence_nbt::compound;
+
+    use super::*;
+
+    #[test]
+    fn rules_filter_items() {
+        let rules = CreativeRules::new()
+            .with_denied_items([ItemKind::CommandBlock])
+            .with_validator(|_, stack| stack.item != ItemKind::Barrier);
+
+        let client = Entity::from_raw(0);
+
+        assert_eq!(
+            rules.apply(client, &ItemStack::new(ItemKind::CommandBlock, 1, None)),
+            None
+        );
+        assert_eq!(
+            rules.apply(client, &ItemStack::new(ItemKind::Barrier, 1, None)),
+            None
+        );
+
+        // Oversized stacks are shrunk and unknown tags are removed.
+        let stack = ItemStack::new(
+            ItemKind::EnderPearl,
+            64,
+            Some(compound! {
+                "Damage" => 1,
+                "BlockEntityTag" => compound! {},
+            }),
+        );
+
+        assert_eq!(
+            rules.apply(client, &stack),
+            Some(ItemStack::new(
+                ItemKind::EnderPearl,
+                16,
+                Some(compound! { "Damage" => 1 })
+            ))
+        );
+    }
+}
@@ -0,0 +1,485 @@
+//! Items with custom behavior, told apart by their custom model data.
+//!
+//! Resource packs give items new models through the `CustomModelData` tag.
+//! The [`CustomItems`] resource maps an item kind and a custom model data
+//! number to a [`CustomItem`], which describes the NBT data of the item and
+//! what happens when clients use it:
+//!
+//! ```
+//! use valence::custom_item::{CustomItem, CustomItems};
+//! use valence::item::ItemBuilder;
+//! use valence::prelude::*;
+//!
+//! fn setup(mut items: ResMut<CustomItems>) {
+//!     items.insert(
+//!         CustomItem::new(ItemKind::Stick, 1)
+//!             .with_template(ItemBuilder::new(ItemKind::Stick).with_name("Magic Wand"))
+//!             .with_use_handler(|event, commands| {
+//!                 let client = event.client;
+//!                 commands.add(move |world: &mut World| {
+//!                     if let Some(mut client) = world.get_mut::<Client>(client) {
+//!                         client.send_message("Abracadabra!");
+//!                     }
+//!                 });
+//!             }),
+//!     );
+//! }
+//! ```
+//!
+//! Handlers run when a client uses the item, attacks an entity with it, uses
+//! it on a block or finishes consuming it. Every one of these actions is also
+//! reported with a [`CustomItemEvent`], whether the item has a handler for it
+//! or not. Consuming an item takes the number of ticks given to
+//! [`CustomItem::with_consume_handler`], and is only started by clients if the
+//! underlying item kind can be eaten or drunk.
+//!
+//! Stacks of a custom item are created with [`CustomItems::create`]. Stacks
+//! which only have the custom model data, like those given by commands, are
+//! stamped with the rest of the NBT data of the custom item once they are in
+//! an [`Inventory`].
+
+use std::collections::HashMap;
+
+use bevy_ecs::prelude::*;
+use valence_nbt::Value;
+use valence_protocol::types::Hand;
+use valence_protocol::{BlockFace, BlockPos, ItemKind, ItemStack};
+
+use crate::client::event::{AttackEntityEvent, UpdateHeldItemState, UseItem, UseItemOnBlock};
+use crate::client::Client;
+use crate::inventory::{consume_held_item, held_item, Inventory};
+use crate::item::ItemBuilder;
+
+/// Identifies a custom item.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub struct CustomItemId {
+    pub item: ItemKind,
+    pub custom_model_data: i32,
+}
+
+impl CustomItemId {
+    pub fn new(item: ItemKind, custom_model_data: i32) -> Self {
+        Self {
+            item,
+            custom_model_data,
+        }
+    }
+
+    /// Returns the ID of the custom item a stack is, or `None` if the stack
+    /// has no custom model data.
+    pub fn of(stack: &ItemStack) -> Option<Self> {
+        match stack.nbt.as_ref()?.get("CustomModelData") {
+            Some(&Value::Int(custom_model_data)) => Some(Self::new(stack.item, custom_model_data)),
+            _ => None,
+        }
+    }
+}
+
+/// Something a client did with a custom item.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum CustomItemAction {
+    /// The client used the item without targeting a block or entity.
+    Use,
+    /// The client attacked an entity with the item in its main hand.
+    Attack { target: Entity },
+    /// The client used the item on a block, which places block items.
+    Place { position: BlockPos, face: BlockFace },
+    /// The client finished eating or drinking the item. One item of the
+    /// stack was used up.
+    Consume,
+}
+
+/// An event sent when a client does something with a custom item registered
+/// in [`CustomItems`].
+#[derive(Clone, PartialEq, Debug)]
+pub struct CustomItemEvent {
+    pub client: Entity,
+    pub item: CustomItemId,
+    pub hand: Hand,
+    pub action: CustomItemAction,
+}
+
+type Handler = Box<dyn Fn(&CustomItemEvent, &mut Commands) + Send + Sync>;
+
+/// An item kind with a custom model data number, NBT data and handlers.
+pub struct CustomItem {
+    id: CustomItemId,
+    stack: ItemStack,
+    use_handler: Option<Handler>,
+    attack_handler: Option<Handler>,
+    place_handler: Option<Handler>,
+    consume_handler: Option<(u32, Handler)>,
+}
+
+impl CustomItem {
+    /// Creates a custom item with no handlers and no NBT data besides the
+    /// custom model data.
+    pub fn new(item: ItemKind, custom_model_data: i32) -> Self {
+        Self {
+            id: CustomItemId::new(item, custom_model_data),
+            stack: ItemBuilder::new(item)
+                .with_custom_model_data(custom_model_data)
+                .build(),
+            use_handler: None,
+            attack_handler: None,
+            place_handler: None,
+            consume_handler: None,
+        }
+    }
+
+    /// Sets the NBT data of the item, like its name and lore, to that of the
+    /// built stack. The item kind, count and custom model data of the custom
+    /// item are kept.
+    #[must_use]
+    pub fn with_template(mut self, template: ItemBuilder) -> Self {
+        let mut stack = template
+            .with_custom_model_data(self.id.custom_model_data)
+            .with_count(1)
+            .build();
+        stack.item = self.id.item;
+        self.stack = stack;
+        self
+    }
+
+    #[must_use]
+    pub fn with_use_handler(
+        mut self,
+        handler: impl Fn(&CustomItemEvent, &mut Commands) + Send + Sync + 'static,
+    ) -> Self {
+        self.use_handler = Some(Box::new(handler));
+        self
+    }
+
+    #[must_use]
+    pub fn with_attack_handler(
+        mut self,
+        handler: impl Fn(&CustomItemEvent, &mut Commands) + Send + Sync + 'static,
+    ) -> Self {
+        self.attack_handler = Some(Box::new(handler));
+        self
+    }
+
+    #[must_use]
+    pub fn with_place_handler(
+        mut self,
+        handler: impl Fn(&CustomItemEvent, &mut Commands) + Send + Sync + 'static,
+    ) -> Self {
+        self.place_handler = Some(Box::new(handler));
+        self
+    }
+
+    /// Makes the item consumable. Clients consume the item by using it for
+    /// the given number of ticks, which is 32 for most vanilla food.
+    #[must_use]
+    pub fn with_consume_handler(
+        mut self,
+        ticks: u32,
+        handler: impl Fn(&CustomItemEvent, &mut Commands) + Send + Sync + 'static,
+    ) -> Self {
+        self.consume_handler = Some((ticks, Box::new(handler)));
+        self
+    }
+
+    pub fn id(&self) -> CustomItemId {
+        self.id
+    }
+
+    /// Returns a stack of the item with all its NBT data.
+    pub fn create(&self, count: u8) -> ItemStack {
+        let mut stack = self.stack.clone();
+        stack.set_count(count);
+        stack
+    }
+
+    fn handler(&self, action: &CustomItemAction) -> Option<&Handler> {
+        match action {
+            CustomItemAction::Use => self.use_handler.as_ref(),
+            CustomItemAction::Attack { .. } => self.attack_handler.as_ref(),
+            CustomItemAction::Place { .. } => self.place_handler.as_ref(),
+            CustomItemAction::Consume => self.consume_handler.as_ref().map(|(_, h)| h),
+        }
+    }
+
+    /// Adds the tags of the item missing from the stack. Returns `false` if
+    /// nothing was missing.
+    fn stamp(&self, stack: &mut ItemStack) -> bool {
+        let Some(template) = &self.stack.nbt else {
+            return false;
+        };
+
+        let nbt = stack.nbt.get_or_insert_with(Default::default);
+        let mut stamped = false;
+
+        for (key, value) in template.iter() {
+            if !nbt.contains_key(key.as_str()) {
+                nbt.insert(key.clone(), value.clone());
+                stamped = true;
+            }
+        }
+
+        stamped
+    }
+}
+
+/// A [`Resource`] with the registered custom items.
+#[derive(Resource, Default)]
+pub struct CustomItems {
+    items: HashMap<CustomItemId, CustomItem>,
+}
+
+impl CustomItems {
+    /// Registers a custom item, replacing the item with the same ID.
+    pub fn insert(&mut self, item: CustomItem) -> Option<CustomItem> {
+        self.items.insert(item.id, item)
+    }
+
+    pub fn remove(&mut self, id: CustomItemId) -> Option<CustomItem> {
+        self.items.remove(&id)
+    }
+
+    pub fn get(&self, id: CustomItemId) -> Option<&CustomItem> {
+        self.items.get(&id)
+    }
+
+    /// Returns the custom item a stack is, if it is registered.
+    pub fn get_stack(&self, stack: &ItemStack) -> Option<&CustomItem> {
+        self.get(CustomItemId::of(stack)?)
+    }
+
+    pub fn contains(&self, id: CustomItemId) -> bool {
+        self.items.contains_key(&id)
+    }
+
+    /// Returns a stack of a registered custom item with all its NBT data.
+    pub fn create(&self, id: CustomItemId, count: u8) -> Option<ItemStack> {
+        Some(self.get(id)?.create(count))
+    }
+
+    /// Runs the handler of the event's action and sends the event.
+    fn dispatch(
+        &self,
+        event: CustomItemEvent,
+        commands: &mut Commands,
+        events: &mut EventWriter<CustomItemEvent>,
+    ) {
+        if let Some(handler) = self.get(event.item).and_then(|i| i.handler(&event.action)) {
+            handler(&event, commands);
+        }
+
+        events.send(event);
+    }
+}
+
+/// A [`Component`] for clients which are consuming a custom item.
+#[derive(Component, Copy, Clone, Debug)]
+pub(crate) struct ConsumingCustomItem {
+    item: CustomItemId,
+    hand: Hand,
+    ticks: u32,
+}
+
+pub(crate) fn use_custom_items(
+    mut commands: Commands,
+    items: Res<CustomItems>,
+    clients: Query<(&Client, &Inventory)>,
+    mut use_item: EventReader<UseItem>,
+    mut use_item_on_block: EventReader<UseItemOnBlock>,
+    mut attack: EventReader<AttackEntityEvent>,
+    mut stop_using: EventReader<UpdateHeldItemState>,
+    mut events: EventWriter<CustomItemEvent>,
+) {
+    let custom_item = |client: Entity, hand: Hand| {
+        let (client, inventory) = clients.get(client).ok()?;
+        items.get_stack(held_item(client, inventory, hand)?)
+    };
+
+    for event in stop_using.iter() {
+        if let Some(mut entity) = commands.get_entity(event.client) {
+            entity.remove::<ConsumingCustomItem>();
+        }
+    }
+
+    for event in use_item.iter() {
+        let Some(item) = custom_item(event.client, event.hand) else {
+            continue;
+        };
+
+        if let Some((ticks, _)) = &item.consume_handler {
+            commands.entity(event.client).insert(ConsumingCustomItem {
+                item: item.id,
+                hand: event.hand,
+                ticks: *ticks,
+            });
+        }
+
+        let event = CustomItemEvent {
+            client: event.client,
+            item: item.id,
+            hand: event.hand,
+            action: CustomItemAction::Use,
+        };
+
+        items.dispatch(event, &mut commands, &mut events);
+    }
+
+    for event in use_item_on_block.iter() {
+        let Some(item) = custom_item(event.client, event.hand) else {
+            continue;
+        };
+
+        let event = CustomItemEvent {
+            client: event.client,
+            item: item.id,
+            hand: event.hand,
+            action: CustomItemAction::Place {
+                position: event.position,
+                face: event.face,
+            },
+        };
+
+        items.dispatch(event, &mut commands, &mut events);
+    }
+
+    for event in attack.iter() {
+        let Some(item) = custom_item(event.client, Hand::Main) else {
+            continue;
+        };
+
+        let event = CustomItemEvent {
+            client: event.client,
+            item: item.id,
+            hand: Hand::Main,
+            action: CustomItemAction::Attack {
+                target: event.target,
+            },
+        };
+
+        items.dispatch(event, &mut commands, &mut events);
+    }
+}
+
+pub(crate) fn consume_custom_items(
+    mut commands: Commands,
+    items: Res<CustomItems>,
+    mut clients: Query<(Entity, &Client, &mut Inventory, &mut ConsumingCustomItem)>,
+    mut events: EventWriter<CustomItemEvent>,
+) {
+    for (entity, client, mut inventory, mut consuming) in &mut clients {
+        consuming.ticks = consuming.ticks.saturating_sub(1);
+
+        if consuming.ticks > 0 {
+            continue;
+        }
+
+        commands.entity(entity).remove::<ConsumingCustomItem>();
+
+        // The client may have switched to another item in the meantime.
+        if held_item(client, &inventory, consuming.hand).and_then(CustomItemId::of)
+            != Some(consuming.item)
+        {
+            continue;
+        }
+
+        consume_held_item(client, &mut inventory, consuming.hand);
+
+        let event = CustomItemEvent {
+            client: entity,
+            item: consuming.item,
+            hand: consuming.hand,
+            action: CustomItemAction::Consume,
+        };
+
+        items.dispatch(event, &mut commands, &mut events);
+    }
+}
+
+/// Adds the NBT data of custom items to stacks in inventories which only
+/// have the custom model data.
+pub(crate) fn stamp_custom_items(
+    items: Res<CustomItems>,
+    mut inventories: Query<&mut Inventory, Changed<Inventory>>,
+) {
+    if items.items.is_empty() {
+        return;
+    }
+
+    for mut inventory in &mut inventories {
+        for idx in 0..inventory.slot_count() {
+            let Some(stack) = inventory.slot(idx) else {
+                continue;
+            };
+
+            let Some(item) = items.get_stack(stack) else {
+                continue;
+            };
+
+            let mut stack = stack.clone();
+
+            if item.stamp(&mut stack) {
+                inventory.replace_slot(idx, stack);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy_app::App;
+    use valence_protocol::packets::c2s::play::UseItem as UseItemPacket;
+    use valence_protocol::VarInt;
+
+    use super::*;
+    use crate::unit_test::util::scenario_single_client;
+
+    #[derive(Component)]
+    struct UsedWand;
+
+    #[test]
+    fn custom_items_are_stamped_and_used() {
+        let mut app = App::new();
+        let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+
+        let wand = CustomItem::new(ItemKind::Stick, 7)
+            .with_template(ItemBuilder::new(ItemKind::Stick).with_name("Wand"))
+            .with_use_handler(|event, commands| {
+                commands.entity(event.client).insert(UsedWand);
+            });
+
+        let stamped = wand.create(1);
+        let id = wand.id();
+        app.world.resource_mut::<CustomItems>().insert(wand);
+
+        // A stack with only the custom model data, like one from a command.
+        let held_slot = app
+            .world
+            .get::<Client>(client_ent)
+            .unwrap()
+            .held_item_slot();
+        let given = ItemBuilder::new(ItemKind::Stick)
+            .with_custom_model_data(7)
+            .build();
+        app.world
+            .get_mut::<Inventory>(client_ent)
+            .unwrap()
+            .replace_slot(held_slot, given);
+
+        app.update();
+
+        let inventory = app.world.get::<Inventory>(client_ent).unwrap();
+        assert_eq!(inventory.slot(held_slot), Some(&stamped));
+
+        client_helper.send(&UseItemPacket {
+            hand: Hand::Main,
+            sequence: VarInt(0),
+        });
+
+        app.update();
+
+        assert!(app.world.get::<UsedWand>(client_ent).is_some());
+
+        let events = app.world.resource::<Events<CustomItemEvent>>();
+        let event = events.get_reader().iter(events).next().cloned().unwrap();
+        assert_eq!(event.item, id);
+        assert_eq!(event.action, CustomItemAction::Use);
+    }
+}
@@ -0,0 +1,439 @@
+//! Camera paths for cutscenes.
+//!
+//! Inserting a [`Cutscene`] on a client entity puts the client in spectator
+//! mode and attaches its camera to an invisible armor stand. The armor stand is
+//! moved along the keyframes of the cutscene, interpolating between them. Once
+//! the last keyframe is reached, the client's game mode, position and
+//! rotation are restored, the [`Cutscene`] component is removed and a
+//! [`CutsceneFinished`] event is sent.
+
+use bevy_ecs::prelude::*;
+use glam::DVec3;
+use valence_protocol::packets::s2c::play::{PlayerAbilitiesS2c, SetCamera};
+use valence_protocol::types::{GameMode, PlayerAbilitiesFlags};
+use valence_protocol::VarInt;
+
+use crate::client::event::StartSneaking;
+use crate::client::Client;
+use crate::entity::{EntityKind, McEntity, TrackedData};
+use crate::Despawned;
+
+/// The FOV modifier used by clients that are not in a cutscene.
+pub const DEFAULT_FOV_MODIFIER: f32 = 0.1;
+
+/// The flying speed of clients in spectator mode.
+const SPECTATOR_FLYING_SPEED: f32 = 0.05;
+
+/// A point on the path of a [`Cutscene`] camera.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct CameraKeyframe {
+    /// The number of ticks since the start of the cutscene at which the camera
+    /// reaches this keyframe.
+    pub tick: u32,
+    /// The position of the camera.
+    pub position: DVec3,
+    /// The yaw of the camera (in degrees).
+    pub yaw: f32,
+    /// The pitch of the camera (in degrees).
+    pub pitch: f32,
+    /// The FOV modifier sent to the client in the player abilities packet.
+    /// Larger values widen the field of view.
+    pub fov_modifier: f32,
+}
+
+impl CameraKeyframe {
+    pub fn new(tick: u32, position: impl Into<DVec3>) -> Self {
+        Self {
+            tick,
+            position: position.into(),
+            yaw: 0.0,
+            pitch: 0.0,
+            fov_modifier: DEFAULT_FOV_MODIFIER,
+        }
+    }
+
+    #[must_use]
+    pub fn with_look(mut self, yaw: f32, pitch: f32) -> Self {
+        self.yaw = yaw;
+        self.pitch = pitch;
+        self
+    }
+
+    #[must_use]
+    pub fn with_fov_modifier(mut self, fov_modifier: f32) -> Self {
+        self.fov_modifier = fov_modifier;
+        self
+    }
+
+    /// Linearly interpolates between two keyframes. Angles are interpolated
+    /// along the shortest path.
+    fn lerp(&self, other: &Self, t: f32) -> Self {
+        let lerp_angle = |a: f32, b: f32| a + ((b - a + 180.0).rem_euclid(360.0) - 180.0) * t;
+
+        Self {
+            tick: self.tick + ((other.tick - self.tick) as f32 * t) as u32,
+            position: self.position.lerp(other.position, t as f64),
+            yaw: lerp_angle(self.yaw, other.yaw),
+            pitch: self.pitch + (other.pitch - self.pitch) * t,
+            fov_modifier: self.fov_modifier + (other.fov_modifier - self.fov_modifier) * t,
+        }
+    }
+}
+
+/// A [`Component`] that plays a cutscene for the [`Client`] on the same
+/// entity.
+///
+/// Playback starts when the component is inserted. While the cutscene is
+/// playing, the client is in spectator mode and cannot move on its own.
+/// Removing the component directly leaves the client in spectator mode; use
+/// [`Cutscene::stop`] to end the cutscene early instead.
+#[derive(Component, Clone, Debug)]
+pub struct Cutscene {
+    keyframes: Vec<CameraKeyframe>,
+    /// If `true`, the client can skip the cutscene by sneaking. Otherwise, the
+    /// camera is reattached when the client tries to leave it.
+    pub skippable: bool,
+    tick: u32,
+    stopped: bool,
+    state: Option<PlaybackState>,
+}
+
+#[derive(Clone, Debug)]
+struct PlaybackState {
+    camera: Entity,
+    camera_spawned: bool,
+    camera_attached: bool,
+    fov_modifier: Option<f32>,
+    game_mode: GameMode,
+    position: DVec3,
+    yaw: f32,
+    pitch: f32,
+}
+
+impl Cutscene {
+    /// Creates a new cutscene from the given keyframes. The order of the
+    /// keyframes does not matter.
+    pub fn new(keyframes: impl IntoIterator<Item = CameraKeyframe>) -> Self {
+        let mut keyframes: Vec<_> = keyframes.into_iter().collect();
+        keyframes.sort_by_key(|k| k.tick);
+
+        Self {
+            keyframes,
+            skippable: false,
+            tick: 0,
+            stopped: false,
+            state: None,
+        }
+    }
+
+    #[must_use]
+    pub fn with_skippable(mut self, skippable: bool) -> Self {
+        self.skippable = skippable;
+        self
+    }
+
+    pub fn keyframes(&self) -> &[CameraKeyframe] {
+        &self.keyframes
+    }
+
+    /// The length of the cutscene in ticks.
+    pub fn duration(&self) -> u32 {
+        self.keyframes.last().map_or(0, |k| k.tick)
+    }
+
+    /// The number of ticks the cutscene has been playing for.
+    pub fn elapsed(&self) -> u32 {
+        self.tick
+    }
+
+    /// Ends the cutscene at the end of the tick as if it had finished.
+    pub fn stop(&mut self) {
+        self.stopped = true;
+    }
+
+    /// Returns the interpolated camera keyframe at the given tick, or `None`
+    /// if the cutscene has no keyframes.
+    pub fn sample(&self, tick: u32) -> Option<CameraKeyframe> {
+        let next = self.keyframes.partition_point(|k| k.tick <= tick);
+
+        if next == 0 {
+            return self.keyframes.first().copied();
+        }
+
+        let prev = &self.keyframes[next - 1];
+
+        match self.keyframes.get(next) {
+            Some(next) => {
+                let t = (tick - prev.tick) as f32 / (next.tick - prev.tick) as f32;
+                Some(prev.lerp(next, t))
+            }
+            None => Some(*prev),
+        }
+    }
+}
+
+/// An event sent when a [`Cutscene`] has finished playing for a client.
+#[derive(Clone, Debug)]
+pub struct CutsceneFinished {
+    pub client: Entity,
+    /// If the cutscene was skipped by the client or stopped with
+    /// [`Cutscene::stop`].
+    pub stopped: bool,
+}
+
+/// A [`Component`] on the armor stands used as the camera of a [`Cutscene`].
+#[derive(Component, Copy, Clone, PartialEq, Eq, Debug)]
+pub struct CutsceneCamera {
+    /// The client viewing the cutscene.
+    pub client: Entity,
+}
+
+fn write_abilities(client: &mut Client, game_mode: GameMode, fov_modifier: f32) {
+    let flags = PlayerAbilitiesFlags::new()
+        .with_invulnerable(matches!(
+            game_mode,
+            GameMode::Creative | GameMode::Spectator
+        ))
+        .with_flying(game_mode == GameMode::Spectator)
+        .with_allow_flying(matches!(
+            game_mode,
+            GameMode::Creative | GameMode::Spectator
+        ))
+        .with_instant_break(game_mode == GameMode::Creative);
+
+    client.write_packet(&PlayerAbilitiesS2c {
+        flags,
+        flying_speed: SPECTATOR_FLYING_SPEED,
+        fov_modifier,
+    });
+}
+
+pub(crate) fn start_cutscenes(
+    mut commands: Commands,
+    mut clients: Query<(Entity, &mut Client, &mut Cutscene), Added<Cutscene>>,
+    mut finished: EventWriter<CutsceneFinished>,
+) {
+    for (client_id, mut client, mut cutscene) in &mut clients {
+        let Some(first) = cutscene.sample(0) else {
+            commands.entity(client_id).remove::<Cutscene>();
+            finished.send(CutsceneFinished {
+                client: client_id,
+                stopped: false,
+            });
+            continue;
+        };
+
+        let mut camera = McEntity::new(EntityKind::ArmorStand, client.instance());
+        camera.set_position(first.position);
+        camera.set_yaw(first.yaw);
+        camera.set_head_yaw(first.yaw);
+        camera.set_pitch(first.pitch);
+
+        if let TrackedData::ArmorStand(data) = camera.data_mut() {
+            data.set_invisible(true);
+            data.set_marker(true);
+            data.set_no_gravity(true);
+        }
+
+        let camera = commands
+            .spawn((camera, CutsceneCamera { client: client_id }))
+            .id();
+
+        cutscene.state = Some(PlaybackState {
+            camera,
+            camera_spawned: false,
+            camera_attached: false,
+            fov_modifier: None,
+            game_mode: client.game_mode(),
+            position: client.position(),
+            yaw: client.yaw(),
+            pitch: client.pitch(),
+        });
+
+        client.set_game_mode(GameMode::Spectator);
+        client.set_position(first.position);
+    }
+}
+
+/// Sneaking in spectator mode detaches the camera, so the cutscene is either
+/// skipped or the camera is reattached.
+pub(crate) fn handle_cutscene_sneaking(
+    mut cutscenes: Query<&mut Cutscene>,
+    mut events: EventReader<StartSneaking>,
+) {
+    for event in events.iter() {
+        let Ok(mut cutscene) = cutscenes.get_mut(event.client) else {
+            continue
+        };
+
+        if cutscene.skippable {
+            cutscene.stop();
+        } else if let Some(state) = &mut cutscene.state {
+            state.camera_attached = false;
+        }
+    }
+}
+
+pub(crate) fn play_cutscenes(
+    mut commands: Commands,
+    mut clients: Query<(Entity, &mut Client, &mut Cutscene)>,
+    mut cameras: Query<&mut McEntity, With<CutsceneCamera>>,
+    mut finished: EventWriter<CutsceneFinished>,
+) {
+    for (client_id, mut client, mut cutscene) in &mut clients {
+        let tick = cutscene.tick;
+        let stopped = cutscene.stopped;
+        let done = stopped || tick > cutscene.duration();
+        let frame = cutscene.sample(tick);
+
+        let Some(state) = &mut cutscene.state else {
+            continue
+        };
+
+        let Ok(mut camera) = cameras.get_mut(state.camera) else {
+            continue
+        };
+
+        if !state.camera_spawned {
+            // The camera needs to be spawned for the client before it can be
+            // attached.
+            state.camera_spawned = true;
+            continue;
+        }
+
+        if done {
+            client.write_packet(&SetCamera {
+                entity_id: VarInt(0),
+            });
+            client.set_game_mode(state.game_mode);
+            write_abilities(&mut client, state.game_mode, DEFAULT_FOV_MODIFIER);
+            client.set_position(state.position);
+            client.set_yaw(state.yaw);
+            client.set_pitch(state.pitch);
+
+            commands.entity(state.camera).insert(Despawned);
+            commands.entity(client_id).remove::<Cutscene>();
+            finished.send(CutsceneFinished {
+                client: client_id,
+                stopped,
+            });
+            continue;
+        }
+
+        if !state.camera_attached {
+            client.write_packet(&SetCamera {
+                entity_id: VarInt(camera.protocol_id()),
+            });
+            state.camera_attached = true;
+        }
+
+        let Some(frame) = frame else { continue };
+
+        if camera.instance() != client.instance() {
+            camera.set_instance(client.instance());
+        }
+
+        camera.set_position(frame.position);
+        camera.set_yaw(frame.yaw);
+        camera.set_head_yaw(frame.yaw);
+        camera.set_pitch(frame.pitch);
+
+        // Keep the client in place so that the chunks around the camera stay
+        // loaded and movement input has no effect.
+        client.set_position(frame.position);
+
+        if state.fov_modifier != Some(frame.fov_modifier) {
+            state.fov_modifier = Some(frame.fov_modifier);
+            write_abilities(&mut client, GameMode::Spectator, frame.fov_modifier);
+        }
+
+        cutscene.tick += 1;
+    }
+}
+
+/// Despawns the cameras of cutscenes that were removed or whose client
+/// disconnected.
+pub(crate) fn despawn_orphaned_cutscene_cameras(
+    mut commands: Commands,
+    cameras: Query<(Entity, &CutsceneCamera), Without<Despawned>>,
+    cutscenes: Query<&Cutscene, Without<Despawned>>,
+) {
+    for (entity, camera) in &cameras {
+        if cutscenes.get(camera.client).map_or(true, |cutscene| {
+            cutscene.state.as_ref().map(|s| s.camera) != Some(entity)
+        }) {
+            commands.entity(entity).insert(Despawned);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy_app::App;
+    use valence_protocol::packets::S2cPlayPacket;
+
+    use super::*;
+    use crate::assert_packet_count;
+    use crate::unit_test::util::scenario_single_client;
+
+    #[test]
+    fn keyframes_are_interpolated() {
+        let cutscene = Cutscene::new([
+            CameraKeyframe::new(10, [10.0, 0.0, 0.0]).with_look(-170.0, 0.0),
+            CameraKeyframe::new(0, [0.0, 0.0, 0.0]).with_look(170.0, 0.0),
+        ]);
+
+        assert_eq!(cutscene.duration(), 10);
+
+        let frame = cutscene.sample(5).unwrap();
+        assert_eq!(frame.position, DVec3::new(5.0, 0.0, 0.0));
+        // Yaw wraps around instead of turning the long way.
+        assert_eq!(frame.yaw, 180.0);
+
+        assert_eq!(cutscene.sample(20).unwrap().position.x, 10.0);
+        assert!(Cutscene::new([]).sample(0).is_none());
+    }
+
+    #[test]
+    fn cutscene_restores_client() -> anyhow::Result<()> {
+        let mut app = App::new();
+        let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+
+        app.update();
+        client_helper.clear_sent();
+
+        let start_pos = app.world.get::<Client>(client_ent).unwrap().position();
+
+        app.world.entity_mut(client_ent).insert(Cutscene::new([
+            CameraKeyframe::new(0, [0.0, 10.0, 0.0]),
+            CameraKeyframe::new(2, [0.0, 20.0, 0.0]),
+        ]));
+
+        app.update();
+
+        let client = app.world.get::<Client>(client_ent).unwrap();
+        assert_eq!(client.game_mode(), GameMode::Spectator);
+
+        app.update();
+        app.update();
+
+        let sent_packets = client_helper.collect_sent()?;
+        assert_packet_count!(sent_packets, 1, S2cPlayPacket::SetCamera(_));
+
+        for _ in 0..3 {
+            app.update();
+        }
+
+        assert!(app.world.get::<Cutscene>(client_ent).is_none());
+        let client = app.world.get::<Client>(client_ent).unwrap();
+        assert_eq!(client.game_mode(), GameMode::Survival);
+        assert_eq!(client.position(), start_pos);
+
+        let mut cameras = app
+            .world
+            .query_filtered::<&CutsceneCamera, Without<Despawned>>();
+        assert_eq!(cameras.iter(&app.world).count(), 0);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,47 @@
+//! Environmental damage reported by Valence's built-in gameplay systems.
+//!
+//! Valence does not keep track of entity health. Systems such as explosions
+//! and fire instead report the damage they deal with the [`EntityDamage`]
+//! event, leaving it up to the combat logic of the server to apply it. The
+//! functions in [`enchantment`](crate::enchantment) can be used to reduce the
+//! damage by the protection enchantments on the armor of the entity.
+
+use bevy_ecs::prelude::*;
+
+/// An event sent when an entity takes damage from one of Valence's built-in
+/// gameplay systems.
+#[derive(Clone, Debug)]
+pub struct EntityDamage {
+    /// The entity that was damaged.
+    pub entity: Entity,
+    /// The entity responsible for the damage, if any.
+    pub source: Option<Entity>,
+    /// What caused the damage.
+    pub kind: DamageKind,
+    /// The amount of damage dealt in half hearts, before armor and enchantments
+    /// are taken into account.
+    pub amount: f32,
+}
+
+/// The cause of an [`EntityDamage`] event.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum DamageKind {
+    /// Caught in the blast of an explosion.
+    Explosion,
+    /// Standing inside of a fire block.
+    InFire,
+    /// Burning after being set on fire.
+    OnFire,
+    /// Standing inside of lava.
+    Lava,
+    /// Struck by lightning.
+    Lightning,
+    /// Ran out of air under water.
+    Drown,
+    /// Hit the ground after falling.
+    Fall,
+    /// Fell into the void below the world.
+    Void,
+    /// Outside of the world border.
+    OutsideBorder,
+}
@@ -0,0 +1,367 @@
+//! Durability of tools, weapons, and armor.
+//!
+//! Items with durability store the damage they have taken in the `Damage` tag
+//! of their NBT. Valence damages the tools and weapons clients attack and dig
+//! with and the armor of clients that take damage. Other systems can damage
+//! items in the inventory of a client by sending the [`DamageItem`] event.
+//!
+//! Unbreaking and the `Unbreakable` tag are taken into account. Once an item
+//! runs out of durability it is removed, the break animation and sound are
+//! played, and an [`ItemBreakEvent`] is sent.
+
+use bevy_ecs::prelude::*;
+use valence_nbt::{Compound, Value};
+use valence_protocol::types::{EntityInteraction, GameMode, Hand, SoundCategory};
+use valence_protocol::{ItemStack, Sound};
+
+use crate::client::event::{FinishDigging, InteractWithEntity};
+use crate::client::Client;
+use crate::damage::{DamageKind, EntityDamage};
+use crate::enchantment::durability_loss_chance;
+use crate::entity::equipment::{EquipmentSlot, Equipments};
+use crate::entity::{EntityStatus, McEntity};
+use crate::instance::Instance;
+use crate::inventory::{hand_slot, Inventory, BOOTS_SLOT, HELMET_SLOT, OFF_HAND_SLOT};
+
+/// An event which damages the item in a slot of a client's player inventory.
+#[derive(Clone, Debug)]
+pub struct DamageItem {
+    pub client: Entity,
+    /// The slot in the player inventory.
+    pub slot: u16,
+    /// The amount of durability to remove before Unbreaking is taken into
+    /// account.
+    pub amount: i32,
+}
+
+/// An event sent when an item in a client's player inventory runs out of
+/// durability and breaks.
+#[derive(Clone, Debug)]
+pub struct ItemBreakEvent {
+    pub client: Entity,
+    /// The slot in the player inventory the item was in.
+    pub slot: u16,
+    /// The item that broke.
+    pub item: ItemStack,
+}
+
+/// Returns the damage the item stack has taken.
+pub fn item_damage(stack: &ItemStack) -> i32 {
+    match stack.nbt.as_ref().and_then(|nbt| nbt.get("Damage")) {
+        Some(Value::Int(damage)) => *damage,
+        _ => 0,
+    }
+}
+
+/// Returns `true` if the item stack has the `Unbreakable` tag set.
+pub fn is_unbreakable(stack: &ItemStack) -> bool {
+    matches!(
+        stack.nbt.as_ref().and_then(|nbt| nbt.get("Unbreakable")),
+        Some(Value::Byte(b)) if *b != 0
+    )
+}
+
+/// Returns `true` if the item is worn in an armor slot.
+fn is_armor(stack: &ItemStack) -> bool {
+    let name = stack.item.to_str();
+
+    ["_helmet", "_chestplate", "_leggings", "_boots"]
+        .iter()
+        .any(|suffix| name.ends_with(suffix))
+        || name == "elytra"
+}
+
+/// Returns the durability a tool or weapon loses when attacking with it and
+/// when breaking a block with it.
+fn tool_wear(stack: &ItemStack) -> Option<(i32, i32)> {
+    let name = stack.item.to_str();
+
+    if name.ends_with("_sword") || name == "trident" {
+        Some((1, 2))
+    } else if ["_axe", "_pickaxe", "_shovel", "_hoe"]
+        .iter()
+        .any(|suffix| name.ends_with(suffix))
+    {
+        Some((2, 1))
+    } else if name == "shears" {
+        Some((0, 1))
+    } else {
+        None
+    }
+}
+
+/// Damages the item stack, returning `true` if it ran out of durability.
+fn damage_stack(stack: &mut ItemStack, amount: i32) -> bool {
+    let max_durability = stack.item.max_durability() as i32;
+
+    if max_durability == 0 || amount <= 0 || is_unbreakable(stack) {
+        return false;
+    }
+
+    let chance = durability_loss_chance(stack, is_armor(stack));
+    let amount = (0..amount).filter(|_| rand::random::<f64>() < chance).count() as i32;
+
+    if amount == 0 {
+        return false;
+    }
+
+    let damage = item_damage(stack) + amount;
+
+    stack
+        .nbt
+        .get_or_insert_with(Compound::new)
+        .insert("Damage", damage);
+
+    damage >= max_durability
+}
+
+/// Returns the status played when the item in a slot of a player inventory
+/// breaks, if the slot is visible to other players.
+fn break_status(client: &Client, slot: u16) -> Option<EntityStatus> {
+    if slot == client.held_item_slot() {
+        return Some(EntityStatus::BreakMainhand);
+    }
+
+    match slot {
+        OFF_HAND_SLOT => Some(EntityStatus::BreakOffhand),
+        _ => match EquipmentSlot::from_armor_slot(slot)? {
+            EquipmentSlot::Head => Some(EntityStatus::BreakHead),
+            EquipmentSlot::Chest => Some(EntityStatus::BreakChest),
+            EquipmentSlot::Legs => Some(EntityStatus::BreakLegs),
+            _ => Some(EntityStatus::BreakFeet),
+        },
+    }
+}
+
+pub(crate) fn wear_attacking_weapons(
+    clients: Query<(&Client, &Inventory)>,
+    mut interact_with_entity: EventReader<InteractWithEntity>,
+    mut damage_item: EventWriter<DamageItem>,
+) {
+    for event in interact_with_entity.iter() {
+        if event.interact != EntityInteraction::Attack {
+            continue;
+        }
+
+        let Ok((client, inventory)) = clients.get(event.client) else {
+            continue
+        };
+
+        let slot = hand_slot(client, Hand::Main);
+
+        if let Some((amount, _)) = inventory.slot(slot).and_then(tool_wear) {
+            damage_item.send(DamageItem {
+                client: event.client,
+                slot,
+                amount,
+            });
+        }
+    }
+}
+
+pub(crate) fn wear_digging_tools(
+    clients: Query<(&Client, &Inventory)>,
+    mut finish_digging: EventReader<FinishDigging>,
+    mut damage_item: EventWriter<DamageItem>,
+) {
+    for event in finish_digging.iter() {
+        let Ok((client, inventory)) = clients.get(event.client) else {
+            continue
+        };
+
+        let slot = hand_slot(client, Hand::Main);
+
+        if let Some((_, amount)) = inventory.slot(slot).and_then(tool_wear) {
+            damage_item.send(DamageItem {
+                client: event.client,
+                slot,
+                amount,
+            });
+        }
+    }
+}
+
+pub(crate) fn wear_damaged_armor(
+    clients: Query<&Inventory, With<Client>>,
+    mut damage: EventReader<EntityDamage>,
+    mut damage_item: EventWriter<DamageItem>,
+) {
+    for event in damage.iter() {
+        // Like in vanilla, damage that bypasses armor does not wear it down.
+        if matches!(
+            event.kind,
+            DamageKind::OnFire
+                | DamageKind::Drown
+                | DamageKind::Fall
+                | DamageKind::Void
+                | DamageKind::OutsideBorder
+        ) {
+            continue;
+        }
+
+        let Ok(inventory) = clients.get(event.entity) else {
+            continue
+        };
+
+        let amount = ((event.amount / 4.0) as i32).max(1);
+
+        for slot in HELMET_SLOT..=BOOTS_SLOT {
+            if inventory.slot(slot).map_or(false, is_armor) {
+                damage_item.send(DamageItem {
+                    client: event.entity,
+                    slot,
+                    amount,
+                });
+            }
+        }
+    }
+}
+
+type DurabilityClient<'a> = (
+    &'a mut Client,
+    &'a mut Inventory,
+    Option<&'a mut McEntity>,
+    Option<&'a mut Equipments>,
+);
+
+pub(crate) fn damage_items(
+    mut clients: Query<DurabilityClient>,
+    mut instances: Query<&mut Instance>,
+    mut damage_item: EventReader<DamageItem>,
+    mut item_break: EventWriter<ItemBreakEvent>,
+) {
+    for event in damage_item.iter() {
+        let Ok((mut client, mut inventory, mc_entity, equipments)) = clients.get_mut(event.client) else {
+            continue
+        };
+
+        if client.game_mode() == GameMode::Creative {
+            continue;
+        }
+
+        let Some(mut stack) = inventory.slot(event.slot).cloned() else {
+            continue
+        };
+
+        if !damage_stack(&mut stack, event.amount) {
+            inventory.replace_slot(event.slot, stack);
+            continue;
+        }
+
+        inventory.replace_slot(event.slot, None);
+
+        if let Some(status) = break_status(&client, event.slot) {
+            client.trigger_status(status);
+
+            if let Some(mut mc_entity) = mc_entity {
+                mc_entity.trigger_status(status);
+            }
+        }
+
+        // Hide the broken item from viewers right away instead of waiting for
+        // the equipment to be synchronized with the inventory.
+        if let Some(mut equipments) = equipments {
+            let slot = if event.slot == client.held_item_slot() {
+                Some(EquipmentSlot::MainHand)
+            } else if event.slot == OFF_HAND_SLOT {
+                Some(EquipmentSlot::OffHand)
+            } else {
+                EquipmentSlot::from_armor_slot(event.slot)
+            };
+
+            if let Some(slot) = slot {
+                equipments.set(slot, None);
+            }
+        }
+
+        if let Ok(mut instance) = instances.get_mut(client.instance()) {
+            instance.play_sound(
+                Sound::EntityItemBreak,
+                SoundCategory::Player,
+                client.position(),
+                0.8,
+                0.8 + rand::random::<f32>() * 0.4,
+            );
+        }
+
+        item_break.send(ItemBreakEvent {
+            client: event.client,
+            slot: event.slot,
+            item: stack,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy_app::App;
+    use valence_nbt::compound;
+    use valence_protocol::ItemKind;
+
+    use super::*;
+    use crate::unit_test::util::scenario_single_client;
+
+    #[test]
+    fn unbreakable_items_keep_durability() {
+        let mut stack = ItemStack::new(
+            ItemKind::IronPickaxe,
+            1,
+            Some(compound! { "Unbreakable" => 1_i8 }),
+        );
+
+        assert!(!damage_stack(&mut stack, 1000));
+        assert_eq!(item_damage(&stack), 0);
+
+        let mut stack = ItemStack::new(ItemKind::IronPickaxe, 1, None);
+        assert!(!damage_stack(&mut stack, 3));
+        assert_eq!(item_damage(&stack), 3);
+
+        let mut stick = ItemStack::new(ItemKind::Stick, 1, None);
+        assert!(!damage_stack(&mut stick, 3));
+        assert_eq!(stick.nbt, None);
+    }
+
+    #[test]
+    fn broken_armor_is_removed() {
+        let mut app = App::new();
+        let (client_ent, _) = scenario_single_client(&mut app);
+
+        app.world
+            .get_mut::<Client>(client_ent)
+            .unwrap()
+            .set_game_mode(GameMode::Survival);
+
+        let max = ItemKind::LeatherBoots.max_durability() as i32;
+        let boots = ItemStack::new(
+            ItemKind::LeatherBoots,
+            1,
+            Some(compound! { "Damage" => max - 1 }),
+        );
+
+        app.world
+            .get_mut::<Inventory>(client_ent)
+            .unwrap()
+            .replace_slot(BOOTS_SLOT, boots);
+
+        app.world.send_event(EntityDamage {
+            entity: client_ent,
+            source: None,
+            kind: DamageKind::Explosion,
+            amount: 4.0,
+        });
+
+        app.update();
+        app.update();
+
+        assert_eq!(
+            app.world.get::<Inventory>(client_ent).unwrap().slot(BOOTS_SLOT),
+            None
+        );
+
+        let events = app.world.resource::<Events<ItemBreakEvent>>();
+        let mut reader = events.get_reader();
+        assert!(reader
+            .iter(events)
+            .any(|e| e.client == client_ent && e.slot == BOOTS_SLOT));
+    }
+}
@@ -0,0 +1,520 @@
+//! Status effects and the particles they emit.
+//!
+//! The active effects of an entity or client are stored in the
+//! [`StatusEffects`] component. Valence counts down their durations and keeps
+//! the potion swirl color and ambient flag in the tracked data of the entity
+//! up to date, so viewers see the particles of the effects without any manual
+//! metadata changes. Clients are also sent the effects that apply to them so
+//! they show up in the inventory and HUD.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use bevy_ecs::prelude::*;
+use valence_protocol::packets::s2c::play::{EntityEffect, RemoveEntityEffect};
+use valence_protocol::types::EntityEffectFlags;
+use valence_protocol::VarInt;
+
+use crate::client::Client;
+use crate::entity::McEntity;
+use crate::tick_rate::SkippedInstances;
+
+/// A status effect which can be applied to living entities.
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub enum StatusEffect {
+    Speed = 1,
+    Slowness,
+    Haste,
+    MiningFatigue,
+    Strength,
+    InstantHealth,
+    InstantDamage,
+    JumpBoost,
+    Nausea,
+    Regeneration,
+    Resistance,
+    FireResistance,
+    WaterBreathing,
+    Invisibility,
+    Blindness,
+    NightVision,
+    Hunger,
+    Weakness,
+    Poison,
+    Wither,
+    HealthBoost,
+    Absorption,
+    Saturation,
+    Glowing,
+    Levitation,
+    Luck,
+    Unluck,
+    SlowFalling,
+    ConduitPower,
+    DolphinsGrace,
+    BadOmen,
+    HeroOfTheVillage,
+    Darkness,
+}
+
+impl StatusEffect {
+    pub const ALL: [Self; 33] = [
+        Self::Speed,
+        Self::Slowness,
+        Self::Haste,
+        Self::MiningFatigue,
+        Self::Strength,
+        Self::InstantHealth,
+        Self::InstantDamage,
+        Self::JumpBoost,
+        Self::Nausea,
+        Self::Regeneration,
+        Self::Resistance,
+        Self::FireResistance,
+        Self::WaterBreathing,
+        Self::Invisibility,
+        Self::Blindness,
+        Self::NightVision,
+        Self::Hunger,
+        Self::Weakness,
+        Self::Poison,
+        Self::Wither,
+        Self::HealthBoost,
+        Self::Absorption,
+        Self::Saturation,
+        Self::Glowing,
+        Self::Levitation,
+        Self::Luck,
+        Self::Unluck,
+        Self::SlowFalling,
+        Self::ConduitPower,
+        Self::DolphinsGrace,
+        Self::BadOmen,
+        Self::HeroOfTheVillage,
+        Self::Darkness,
+    ];
+
+    /// Returns the protocol ID of this effect.
+    pub const fn id(self) -> i32 {
+        self as i32
+    }
+
+    /// Returns the effect with the given protocol ID, if any.
+    pub fn from_id(id: i32) -> Option<Self> {
+        Self::ALL.get(usize::try_from(id - 1).ok()?).copied()
+    }
+
+    /// Returns the color of the particles emitted by this effect as an RGB
+    /// integer.
+    pub const fn color(self) -> u32 {
+        match self {
+            Self::Speed => 0x7cafc6,
+            Self::Slowness => 0x5a6c81,
+            Self::Haste => 0xd9c043,
+            Self::MiningFatigue => 0x4a4217,
+            Self::Strength => 0x932423,
+            Self::InstantHealth => 0xf82423,
+            Self::InstantDamage => 0x430a09,
+            Self::JumpBoost => 0x22ff4c,
+            Self::Nausea => 0x551d4a,
+            Self::Regeneration => 0xcd5cab,
+            Self::Resistance => 0x99453a,
+            Self::FireResistance => 0xe49a3a,
+            Self::WaterBreathing => 0x2e5299,
+            Self::Invisibility => 0x7f8392,
+            Self::Blindness => 0x1f1f23,
+            Self::NightVision => 0x1f1fa1,
+            Self::Hunger => 0x587653,
+            Self::Weakness => 0x484d48,
+            Self::Poison => 0x4e9331,
+            Self::Wither => 0x352a27,
+            Self::HealthBoost => 0xf87d23,
+            Self::Absorption => 0x2552a5,
+            Self::Saturation => 0xf82423,
+            Self::Glowing => 0x94a061,
+            Self::Levitation => 0xceffff,
+            Self::Luck => 0x339900,
+            Self::Unluck => 0xc0a44d,
+            Self::SlowFalling => 0xffefd1,
+            Self::ConduitPower => 0x1dc2d1,
+            Self::DolphinsGrace => 0x88a3be,
+            Self::BadOmen => 0x0b6138,
+            Self::HeroOfTheVillage => 0x44ff44,
+            Self::Darkness => 0x292721,
+        }
+    }
+}
+
+/// A single effect in a [`StatusEffects`] component.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct ActiveEffect {
+    /// The level of the effect minus one.
+    pub amplifier: u8,
+    /// The remaining duration of the effect in ticks.
+    pub duration: i32,
+    /// If the effect was given by a beacon or conduit. Ambient effects emit
+    /// fewer and more translucent particles.
+    pub ambient: bool,
+    /// If the effect emits particles and contributes to the potion swirl
+    /// color.
+    pub show_particles: bool,
+    /// If clients display an icon for the effect in their HUD.
+    pub show_icon: bool,
+}
+
+impl ActiveEffect {
+    /// Creates a new effect which shows particles and an icon.
+    pub fn new(amplifier: u8, duration: i32) -> Self {
+        Self {
+            amplifier,
+            duration,
+            ambient: false,
+            show_particles: true,
+            show_icon: true,
+        }
+    }
+
+    #[must_use]
+    pub fn with_ambient(mut self, ambient: bool) -> Self {
+        self.ambient = ambient;
+        self
+    }
+
+    #[must_use]
+    pub fn with_show_particles(mut self, show_particles: bool) -> Self {
+        self.show_particles = show_particles;
+        self
+    }
+
+    #[must_use]
+    pub fn with_show_icon(mut self, show_icon: bool) -> Self {
+        self.show_icon = show_icon;
+        self
+    }
+}
+
+/// A [`Component`] containing the active status effects of the [`McEntity`]
+/// or [`Client`] on the same entity.
+///
+/// Durations are counted down every tick and effects are removed once they
+/// run out, which is reported with the [`StatusEffectExpired`] event.
+/// Removing the component removes all effects.
+#[derive(Component, Clone, Default, Debug)]
+pub struct StatusEffects {
+    effects: BTreeMap<StatusEffect, ActiveEffect>,
+    /// Effects which were added, changed, or removed since the owning client
+    /// was last updated.
+    modified: BTreeSet<StatusEffect>,
+}
+
+impl StatusEffects {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds an effect, returning the effect of the same kind it replaced.
+    pub fn insert(&mut self, effect: StatusEffect, active: ActiveEffect) -> Option<ActiveEffect> {
+        self.modified.insert(effect);
+        self.effects.insert(effect, active)
+    }
+
+    /// Removes an effect, returning it if it was active.
+    pub fn remove(&mut self, effect: StatusEffect) -> Option<ActiveEffect> {
+        let removed = self.effects.remove(&effect);
+        if removed.is_some() {
+            self.modified.insert(effect);
+        }
+        removed
+    }
+
+    pub fn get(&self, effect: StatusEffect) -> Option<&ActiveEffect> {
+        self.effects.get(&effect)
+    }
+
+    /// Returns the effect so it can be modified in place.
+    pub fn get_mut(&mut self, effect: StatusEffect) -> Option<&mut ActiveEffect> {
+        let active = self.effects.get_mut(&effect)?;
+        self.modified.insert(effect);
+        Some(active)
+    }
+
+    pub fn contains(&self, effect: StatusEffect) -> bool {
+        self.effects.contains_key(&effect)
+    }
+
+    /// Shows or hides the particles of an active effect. Returns `false` if
+    /// the effect is not active.
+    pub fn set_show_particles(&mut self, effect: StatusEffect, show_particles: bool) -> bool {
+        match self.get_mut(effect) {
+            Some(active) => {
+                active.show_particles = show_particles;
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (StatusEffect, &ActiveEffect)> + '_ {
+        self.effects.iter().map(|(effect, active)| (*effect, active))
+    }
+
+    /// Removes all effects.
+    pub fn clear(&mut self) {
+        self.modified.extend(self.effects.keys());
+        self.effects.clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.effects.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.effects.is_empty()
+    }
+
+    /// Returns the color of the potion swirls emitted by the effects, or
+    /// `None` if no effect shows particles.
+    ///
+    /// Like in vanilla, the colors of the effects are averaged with higher
+    /// levels having more weight.
+    pub fn particle_color(&self) -> Option<u32> {
+        let mut rgb = [0_u32; 3];
+        let mut total_weight = 0;
+
+        for (effect, active) in self.iter().filter(|(_, a)| a.show_particles) {
+            let color = effect.color();
+            let weight = active.amplifier as u32 + 1;
+
+            for (i, c) in rgb.iter_mut().enumerate() {
+                *c += ((color >> (16 - i * 8)) & 0xff) * weight;
+            }
+
+            total_weight += weight;
+        }
+
+        if total_weight == 0 {
+            return None;
+        }
+
+        Some(rgb.iter().fold(0, |acc, c| (acc << 8) | (c / total_weight)))
+    }
+
+    /// Returns `true` if there is at least one effect and all of them are
+    /// ambient.
+    pub fn is_ambient(&self) -> bool {
+        !self.is_empty() && self.effects.values().all(|active| active.ambient)
+    }
+}
+
+/// An event sent when a status effect runs out.
+#[derive(Clone, Debug)]
+pub struct StatusEffectExpired {
+    pub entity: Entity,
+    pub effect: StatusEffect,
+}
+
+type AffectedEntity<'a> = (
+    Entity,
+    &'a mut StatusEffects,
+    Option<&'a McEntity>,
+    Option<&'a Client>,
+);
+
+pub(crate) fn tick_status_effects(
+    mut entities: Query<AffectedEntity>,
+    mut expired: EventWriter<StatusEffectExpired>,
+    skipped: Res<SkippedInstances>,
+) {
+    for (entity, mut effects, mc_entity, client) in &mut entities {
+        let instance = mc_entity
+            .map(|mc_entity| mc_entity.instance())
+            .or_else(|| client.map(|client| client.instance()));
+
+        if instance.map_or(false, |instance| skipped.contains(instance)) {
+            continue;
+        }
+
+        // Counting down does not need to trigger change detection since the
+        // duration is only sent to clients when an effect is first added.
+        let mut ran_out = vec![];
+
+        for (effect, active) in effects.bypass_change_detection().effects.iter_mut() {
+            active.duration -= 1;
+            if active.duration <= 0 {
+                ran_out.push(*effect);
+            }
+        }
+
+        for effect in ran_out {
+            effects.remove(effect);
+            expired.send(StatusEffectExpired { entity, effect });
+        }
+    }
+}
+
+type ChangedEffects<'a> = (
+    &'a StatusEffects,
+    Option<&'a mut McEntity>,
+    Option<&'a mut Client>,
+);
+
+pub(crate) fn update_effect_particles(
+    mut entities: Query<ChangedEffects, Changed<StatusEffects>>,
+) {
+    for (effects, mc_entity, client) in &mut entities {
+        let color = effects.particle_color().unwrap_or(0) as i32;
+        let ambient = effects.is_ambient();
+
+        if let Some(mut mc_entity) = mc_entity {
+            let data = mc_entity.data_mut();
+            data.set_potion_swirls_color(color);
+            data.set_potion_swirls_ambient(ambient);
+        }
+
+        if let Some(mut client) = client {
+            let player = client.player_mut();
+            player.set_potion_swirls_color(color);
+            player.set_potion_swirls_ambient(ambient);
+        }
+    }
+}
+
+pub(crate) fn remove_effect_particles(
+    removed: RemovedComponents<StatusEffects>,
+    mut entities: Query<(Option<&mut McEntity>, Option<&mut Client>), Without<StatusEffects>>,
+) {
+    for entity in removed.iter() {
+        let Ok((mc_entity, client)) = entities.get_mut(entity) else {
+            continue
+        };
+
+        if let Some(mut mc_entity) = mc_entity {
+            let data = mc_entity.data_mut();
+            data.set_potion_swirls_color(0);
+            data.set_potion_swirls_ambient(false);
+        }
+
+        if let Some(mut client) = client {
+            let player = client.player_mut();
+            player.set_potion_swirls_color(0);
+            player.set_potion_swirls_ambient(false);
+
+            // The client does not know the component is gone, so tell it to
+            // forget every effect it might still display.
+            for effect in StatusEffect::ALL {
+                client.write_packet(&RemoveEntityEffect {
+                    entity_id: VarInt(0),
+                    effect_id: VarInt(effect.id()),
+                });
+            }
+        }
+    }
+}
+
+pub(crate) fn send_effects_to_clients(
+    mut clients: Query<(&mut Client, &mut StatusEffects), Changed<StatusEffects>>,
+) {
+    for (mut client, mut effects) in &mut clients {
+        let effects = effects.bypass_change_detection();
+
+        for effect in std::mem::take(&mut effects.modified) {
+            match effects.effects.get(&effect) {
+                Some(active) => client.write_packet(&EntityEffect {
+                    entity_id: VarInt(0),
+                    effect_id: VarInt(effect.id()),
+                    amplifier: active.amplifier,
+                    duration: VarInt(active.duration),
+                    flags: EntityEffectFlags::new()
+                        .with_is_ambient(active.ambient)
+                        .with_show_particles(active.show_particles)
+                        .with_show_icon(active.show_icon),
+                    factor_codec: None,
+                }),
+                None => client.write_packet(&RemoveEntityEffect {
+                    entity_id: VarInt(0),
+                    effect_id: VarInt(effect.id()),
+                }),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy_app::App;
+    use valence_protocol::packets::S2cPlayPacket;
+
+    use super::*;
+    use crate::assert_packet_count;
+    use crate::unit_test::util::scenario_single_client;
+
+    #[test]
+    fn particle_color_mixing() {
+        let mut effects = StatusEffects::new();
+        assert_eq!(effects.particle_color(), None);
+        assert!(!effects.is_ambient());
+
+        effects.insert(StatusEffect::Speed, ActiveEffect::new(0, 100));
+        assert_eq!(effects.particle_color(), Some(0x7cafc6));
+
+        effects.insert(
+            StatusEffect::Luck,
+            ActiveEffect::new(0, 100).with_show_particles(false),
+        );
+        assert_eq!(effects.particle_color(), Some(0x7cafc6));
+
+        effects.insert(StatusEffect::JumpBoost, ActiveEffect::new(1, 100));
+        // (0x7c + 0x22 * 2) / 3 = 0x40, (0xaf + 0xff * 2) / 3 = 0xe4,
+        // (0xc6 + 0x4c * 2) / 3 = 0x74
+        assert_eq!(effects.particle_color(), Some(0x40e474));
+
+        assert_eq!(StatusEffect::from_id(1), Some(StatusEffect::Speed));
+        assert_eq!(StatusEffect::from_id(33), Some(StatusEffect::Darkness));
+        assert_eq!(StatusEffect::from_id(0), None);
+        assert_eq!(StatusEffect::from_id(34), None);
+    }
+
+    #[test]
+    fn effects_expire_and_sync() -> anyhow::Result<()> {
+        let mut app = App::new();
+        let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+
+        let mut effects = StatusEffects::new();
+        effects.insert(
+            StatusEffect::Regeneration,
+            ActiveEffect::new(0, 3).with_ambient(true),
+        );
+        app.world.entity_mut(client_ent).insert(effects);
+
+        app.update();
+
+        let player = app.world.get::<Client>(client_ent).unwrap().player();
+        assert_eq!(player.get_potion_swirls_color(), 0xcd5cab);
+        assert!(player.get_potion_swirls_ambient());
+
+        let sent_packets = client_helper.collect_sent()?;
+        assert_packet_count!(sent_packets, 1, S2cPlayPacket::EntityEffect(_));
+
+        for _ in 0..2 {
+            app.update();
+        }
+
+        assert!(app
+            .world
+            .get::<StatusEffects>(client_ent)
+            .unwrap()
+            .is_empty());
+        let player = app.world.get::<Client>(client_ent).unwrap().player();
+        assert_eq!(player.get_potion_swirls_color(), 0);
+
+        let sent_packets = client_helper.collect_sent()?;
+        assert_packet_count!(sent_packets, 1, S2cPlayPacket::RemoveEntityEffect(_));
+
+        let expired = app.world.resource::<Events<StatusEffectExpired>>();
+        let mut reader = expired.get_reader();
+        assert!(reader
+            .iter(expired)
+            .any(|e| e.entity == client_ent && e.effect == StatusEffect::Regeneration));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,380 @@
+//! Resolving the gameplay effects of enchanted items.
+//!
+//! Enchantments are stored in the NBT of item stacks. The functions in this
+//! module read them and compute how they affect combat, digging, damage
+//! protection, fishing, and durability so that Valence's built-in systems and
+//! the gameplay logic of the server agree on what enchanted gear does.
+
+use valence_nbt::{compound, Compound, List, Value};
+use valence_protocol::enchant::EnchantmentKind;
+use valence_protocol::{ItemKind, ItemStack};
+
+use crate::damage::DamageKind;
+use crate::entity::EntityKind;
+
+/// The highest enchantment protection factor that is taken into account.
+/// Each point reduces damage by 4%, so damage is reduced by at most 80%.
+pub const MAX_PROTECTION_FACTOR: i32 = 20;
+
+/// Returns an iterator over the enchantments on an item stack and their
+/// levels. Unknown enchantments are skipped.
+pub fn enchantments(stack: &ItemStack) -> impl Iterator<Item = (EnchantmentKind, i16)> + '_ {
+    enchantment_list(stack, "Enchantments")
+}
+
+/// Returns an iterator over the enchantments stored in an enchanted book and
+/// their levels. Unlike [`enchantments`], these have no effect on the book
+/// itself and are applied to other items in an anvil.
+pub fn stored_enchantments(stack: &ItemStack) -> impl Iterator<Item = (EnchantmentKind, i16)> + '_ {
+    enchantment_list(stack, "StoredEnchantments")
+}
+
+fn enchantment_list<'a>(
+    stack: &'a ItemStack,
+    key: &str,
+) -> impl Iterator<Item = (EnchantmentKind, i16)> + 'a {
+    let list = match stack.nbt.as_ref().and_then(|nbt| nbt.get(key)) {
+        Some(Value::List(List::Compound(list))) => list.as_slice(),
+        _ => &[],
+    };
+
+    list.iter().filter_map(|ench| {
+        let Some(Value::String(id)) = ench.get("id") else {
+            return None
+        };
+
+        let name = id.strip_prefix("minecraft:").unwrap_or(id);
+        let kind = (0..)
+            .map_while(EnchantmentKind::from_raw)
+            .find(|kind| kind.name() == name)?;
+
+        let level = match ench.get("lvl") {
+            Some(Value::Short(lvl)) => *lvl,
+            Some(Value::Int(lvl)) => *lvl as i16,
+            Some(Value::Byte(lvl)) => *lvl as i16,
+            _ => return None,
+        };
+
+        Some((kind, level))
+    })
+}
+
+/// Sets the level of an enchantment on the item stack, replacing its current
+/// level. Enchanted books store the enchantment in `StoredEnchantments`
+/// instead of `Enchantments`.
+pub fn set_enchantment(stack: &mut ItemStack, kind: EnchantmentKind, level: i16) {
+    let key = if stack.item == ItemKind::EnchantedBook {
+        "StoredEnchantments"
+    } else {
+        "Enchantments"
+    };
+
+    let id = format!("minecraft:{}", kind.name());
+    let enchantment = compound! {
+        "id" => id.clone(),
+        "lvl" => level,
+    };
+
+    let nbt = stack.nbt.get_or_insert_with(Compound::new);
+
+    match nbt.get_mut(key) {
+        Some(Value::List(List::Compound(list))) => {
+            let existing = list.iter_mut().find(|ench| {
+                matches!(
+                    ench.get("id"),
+                    Some(Value::String(other))
+                        if other.strip_prefix("minecraft:").unwrap_or(other) == kind.name()
+                )
+            });
+
+            match existing {
+                Some(existing) => *existing = enchantment,
+                None => list.push(enchantment),
+            }
+        }
+        _ => {
+            nbt.insert(key, List::Compound(vec![enchantment]));
+        }
+    }
+}
+
+/// Returns `true` if the enchantment can be put on the item in an enchanting
+/// table or an anvil. Books accept every enchantment.
+pub fn can_enchant(kind: EnchantmentKind, item: ItemKind) -> bool {
+    use EnchantmentKind::*;
+
+    let name = item.to_str();
+    let is = |suffixes: &[&str]| suffixes.iter().any(|suffix| name.ends_with(suffix));
+
+    if matches!(item, ItemKind::Book | ItemKind::EnchantedBook) {
+        return true;
+    }
+
+    let is_helmet = is(&["_helmet"]);
+    let is_boots = is(&["_boots"]);
+    let is_armor = is_helmet || is_boots || is(&["_chestplate", "_leggings"]);
+
+    match kind {
+        Protection | FireProtection | BlastProtection | ProjectileProtection | Thorns => is_armor,
+        FeatherFalling | DepthStrider | FrostWalker | SoulSpeed => is_boots,
+        Respiration | AquaAffinity => is_helmet,
+        SwiftSneak => is(&["_leggings"]),
+        BindingCurse => is_armor || item == ItemKind::Elytra,
+        Sharpness | Smite | BaneOfArthropods => is(&["_sword", "_axe"]),
+        Knockback | FireAspect | Looting | Sweeping => is(&["_sword"]),
+        Efficiency => is(&["_pickaxe", "_axe", "_shovel", "_hoe"]) || item == ItemKind::Shears,
+        SilkTouch | Fortune => is(&["_pickaxe", "_axe", "_shovel", "_hoe"]),
+        Power | Punch | Flame | Infinity => item == ItemKind::Bow,
+        Loyalty | Impaling | Riptide | Channeling => item == ItemKind::Trident,
+        Multishot | QuickCharge | Piercing => item == ItemKind::Crossbow,
+        LuckOfTheSea | Lure => item == ItemKind::FishingRod,
+        Unbreaking | Mending | VanishingCurse => item.max_durability() > 0,
+    }
+}
+
+/// Returns the level of the enchantment on the item stack, or zero if the item
+/// is not enchanted with it.
+pub fn enchantment_level(stack: &ItemStack, kind: EnchantmentKind) -> i16 {
+    enchantments(stack)
+        .find(|(k, _)| *k == kind)
+        .map_or(0, |(_, level)| level.max(0))
+}
+
+/// Returns the level of the enchantment on an optional item stack.
+fn level_of(stack: Option<&ItemStack>, kind: EnchantmentKind) -> i16 {
+    stack.map_or(0, |stack| enchantment_level(stack, kind))
+}
+
+fn is_undead(kind: EntityKind) -> bool {
+    matches!(
+        kind,
+        EntityKind::Zombie
+            | EntityKind::ZombieVillager
+            | EntityKind::Husk
+            | EntityKind::Drowned
+            | EntityKind::Skeleton
+            | EntityKind::Stray
+            | EntityKind::WitherSkeleton
+            | EntityKind::Wither
+            | EntityKind::Phantom
+            | EntityKind::ZombifiedPiglin
+            | EntityKind::Zoglin
+            | EntityKind::SkeletonHorse
+            | EntityKind::ZombieHorse
+    )
+}
+
+fn is_arthropod(kind: EntityKind) -> bool {
+    matches!(
+        kind,
+        EntityKind::Spider
+            | EntityKind::CaveSpider
+            | EntityKind::Silverfish
+            | EntityKind::Endermite
+            | EntityKind::Bee
+    )
+}
+
+/// Returns the extra damage in half hearts dealt by an attack with `weapon`
+/// against an entity of the kind `target`.
+///
+/// Sharpness adds damage against every target while Smite and Bane of
+/// Arthropods only add damage against undead and arthropods respectively.
+pub fn attack_damage_bonus(weapon: Option<&ItemStack>, target: EntityKind) -> f32 {
+    let Some(weapon) = weapon else {
+        return 0.0
+    };
+
+    enchantments(weapon)
+        .map(|(kind, level)| {
+            let level = level.max(0) as f32;
+
+            match kind {
+                EnchantmentKind::Sharpness if level > 0.0 => 0.5 * level + 0.5,
+                EnchantmentKind::Smite if is_undead(target) => 2.5 * level,
+                EnchantmentKind::BaneOfArthropods if is_arthropod(target) => 2.5 * level,
+                _ => 0.0,
+            }
+        })
+        .sum()
+}
+
+/// Returns the enchantment protection factor of a set of armor pieces against
+/// damage of the given kind, capped at [`MAX_PROTECTION_FACTOR`].
+pub fn protection_factor<'a>(
+    armor: impl IntoIterator<Item = &'a ItemStack>,
+    damage: DamageKind,
+) -> i32 {
+    let is_fire = matches!(
+        damage,
+        DamageKind::InFire | DamageKind::OnFire | DamageKind::Lava
+    );
+    // Protection does not apply to damage that bypasses armor.
+    let bypasses_armor = matches!(
+        damage,
+        DamageKind::Drown | DamageKind::Void | DamageKind::OutsideBorder
+    );
+
+    let factor: i32 = armor
+        .into_iter()
+        .flat_map(enchantments)
+        .map(|(kind, level)| {
+            let level = level.max(0) as i32;
+
+            match kind {
+                EnchantmentKind::Protection if !bypasses_armor => level,
+                EnchantmentKind::FireProtection if is_fire => level * 2,
+                EnchantmentKind::BlastProtection if damage == DamageKind::Explosion => level * 2,
+                EnchantmentKind::FeatherFalling if damage == DamageKind::Fall => level * 3,
+                _ => 0,
+            }
+        })
+        .sum();
+
+    factor.min(MAX_PROTECTION_FACTOR)
+}
+
+/// Reduces `amount` of damage by the given enchantment protection factor.
+pub fn apply_protection(amount: f32, protection_factor: i32) -> f32 {
+    let factor = protection_factor.clamp(0, MAX_PROTECTION_FACTOR) as f32;
+    amount * (1.0 - factor / 25.0)
+}
+
+/// Returns the speed at which `tool` breaks a block, given the speed of the
+/// tool without enchantments.
+///
+/// Efficiency only has an effect if the tool is suited for the block, i.e. its
+/// base speed is greater than one.
+pub fn dig_speed(base_speed: f32, tool: Option<&ItemStack>) -> f32 {
+    let efficiency = level_of(tool, EnchantmentKind::Efficiency) as f32;
+
+    if base_speed > 1.0 && efficiency > 0.0 {
+        base_speed + efficiency * efficiency + 1.0
+    } else {
+        base_speed
+    }
+}
+
+/// The effects of the enchantments on a fishing rod.
+#[derive(Copy, Clone, PartialEq, Eq, Default, Debug)]
+pub struct FishingEnchantments {
+    /// The level of Luck of the Sea, which increases the chance of catching
+    /// treasure instead of junk.
+    pub luck: i16,
+    /// The number of ticks Lure removes from the time it takes for a fish to
+    /// bite.
+    pub lure_ticks: i32,
+}
+
+/// Returns the effects of the enchantments on a fishing rod.
+pub fn fishing_enchantments(rod: Option<&ItemStack>) -> FishingEnchantments {
+    FishingEnchantments {
+        luck: level_of(rod, EnchantmentKind::LuckOfTheSea),
+        // Every level of Lure makes fish bite five seconds sooner.
+        lure_ticks: level_of(rod, EnchantmentKind::Lure) as i32 * 100,
+    }
+}
+
+/// Returns the chance in the range `0.0..=1.0` that using the item costs
+/// durability, taking Unbreaking into account.
+///
+/// Unbreaking is less effective on armor than on tools and weapons.
+pub fn durability_loss_chance(stack: &ItemStack, is_armor: bool) -> f64 {
+    let unbreaking = enchantment_level(stack, EnchantmentKind::Unbreaking) as f64;
+
+    if is_armor {
+        0.6 + 0.4 / (unbreaking + 1.0)
+    } else {
+        1.0 / (unbreaking + 1.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn enchanted(kind: ItemKind, enchantments: &[(&str, i16)]) -> ItemStack {
+        ItemStack::new(
+            kind,
+            1,
+            Some(compound! {
+                "Enchantments" => List::Compound(
+                    enchantments
+                        .iter()
+                        .map(|(id, lvl)| compound! {
+                            "id" => *id,
+                            "lvl" => *lvl,
+                        })
+                        .collect(),
+                ),
+            }),
+        )
+    }
+
+    #[test]
+    fn enchantment_levels() {
+        let boots = enchanted(
+            ItemKind::DiamondBoots,
+            &[("minecraft:feather_falling", 4), ("unbreaking", 2)],
+        );
+
+        assert_eq!(
+            enchantment_level(&boots, EnchantmentKind::FeatherFalling),
+            4
+        );
+        assert_eq!(enchantment_level(&boots, EnchantmentKind::Unbreaking), 2);
+        assert_eq!(enchantment_level(&boots, EnchantmentKind::Protection), 0);
+        assert_eq!(
+            enchantment_level(
+                &ItemStack::new(ItemKind::Stick, 1, None),
+                EnchantmentKind::Protection
+            ),
+            0
+        );
+    }
+
+    #[test]
+    fn resolved_effects() {
+        let sword = enchanted(
+            ItemKind::DiamondSword,
+            &[("minecraft:sharpness", 5), ("minecraft:smite", 2)],
+        );
+
+        assert_eq!(attack_damage_bonus(Some(&sword), EntityKind::Cow), 3.0);
+        assert_eq!(attack_damage_bonus(Some(&sword), EntityKind::Zombie), 8.0);
+        assert_eq!(attack_damage_bonus(None, EntityKind::Zombie), 0.0);
+
+        let armor = [
+            enchanted(ItemKind::DiamondBoots, &[("minecraft:feather_falling", 4)]),
+            enchanted(ItemKind::DiamondHelmet, &[("minecraft:protection", 4)]),
+            enchanted(
+                ItemKind::DiamondChestplate,
+                &[("minecraft:fire_protection", 4)],
+            ),
+        ];
+
+        assert_eq!(protection_factor(&armor, DamageKind::Fall), 16);
+        assert_eq!(protection_factor(&armor, DamageKind::Lava), 12);
+        assert_eq!(protection_factor(&armor, DamageKind::Void), 0);
+        assert_eq!(apply_protection(10.0, 20), 2.0);
+
+        let pickaxe = enchanted(ItemKind::DiamondPickaxe, &[("minecraft:efficiency", 3)]);
+        assert_eq!(dig_speed(8.0, Some(&pickaxe)), 18.0);
+        assert_eq!(dig_speed(1.0, Some(&pickaxe)), 1.0);
+
+        let rod = enchanted(
+            ItemKind::FishingRod,
+            &[("minecraft:luck_of_the_sea", 3), ("minecraft:lure", 2)],
+        );
+        assert_eq!(
+            fishing_enchantments(Some(&rod)),
+            FishingEnchantments {
+                luck: 3,
+                lure_ticks: 200,
+            }
+        );
+
+        let pickaxe = enchanted(ItemKind::DiamondPickaxe, &[("minecraft:unbreaking", 3)]);
+        assert_eq!(durability_loss_chance(&pickaxe, false), 0.25);
+    }
+}
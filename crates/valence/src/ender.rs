@@ -0,0 +1,411 @@
+//! Ender pearls, chorus fruit and end crystals.
+//!
+//! Clients using an ender pearl throw a [`ThrownEnderPearl`] entity which
+//! teleports them to where it lands. Eating chorus fruit teleports clients to
+//! a random safe position nearby. Both teleports are reported with the
+//! [`ItemTeleport`] event. End crystals explode when attacked.
+
+use bevy_ecs::prelude::*;
+use glam::DVec3;
+use rand::Rng;
+use valence_protocol::types::{EntityInteraction, Hand, SoundCategory};
+use valence_protocol::{BlockPos, ItemKind, Sound};
+
+use crate::client::event::{InteractWithEntity, UpdateHeldItemState, UseItem};
+use crate::client::Client;
+use crate::damage::{DamageKind, EntityDamage};
+use crate::entity::{EntityKind, McEntity, McEntityManager};
+use crate::explosion::{Explosion, PendingExplosions};
+use crate::fall::FallDistance;
+use crate::instance::Instance;
+use crate::inventory::{consume_held_item, held_item, Inventory};
+use crate::tick_rate::SkippedInstances;
+use crate::Despawned;
+
+/// The speed of a thrown ender pearl in blocks per tick.
+const PEARL_SPEED: f64 = 1.5;
+
+/// The damage dealt to a client teleported by an ender pearl.
+const PEARL_DAMAGE: f32 = 5.0;
+
+/// The number of ticks it takes to eat a chorus fruit.
+const CHORUS_FRUIT_EAT_TICKS: u32 = 32;
+
+/// The power of the explosion of an end crystal.
+pub const END_CRYSTAL_POWER: f32 = 6.0;
+
+/// A [`Component`] for ender pearls in flight. The entity must also have an
+/// [`McEntity`] component.
+///
+/// The pearl moves every tick until it hits a block, at which point it is
+/// despawned and its owner is teleported to it.
+#[derive(Component, Clone, PartialEq, Debug)]
+pub struct ThrownEnderPearl {
+    /// The client which threw the pearl.
+    pub owner: Entity,
+    /// The velocity of the pearl in blocks per tick.
+    pub velocity: DVec3,
+}
+
+impl ThrownEnderPearl {
+    pub fn new(owner: Entity, velocity: impl Into<DVec3>) -> Self {
+        Self {
+            owner,
+            velocity: velocity.into(),
+        }
+    }
+}
+
+/// A [`Component`] for clients which are eating a chorus fruit.
+#[derive(Component, Copy, Clone, Debug)]
+pub(crate) struct EatingChorusFruit {
+    hand: Hand,
+    ticks: u32,
+}
+
+/// An event sent when a client is teleported by an item.
+#[derive(Clone, Debug)]
+pub struct ItemTeleport {
+    pub client: Entity,
+    pub item: ItemKind,
+    /// The position of the client before the teleport.
+    pub from: DVec3,
+    /// The position of the client after the teleport.
+    pub to: DVec3,
+}
+
+/// Returns the unit vector a client with the given yaw and pitch is looking
+/// along.
+pub(crate) fn look_direction(yaw: f32, pitch: f32) -> DVec3 {
+    let (yaw, pitch) = ((yaw as f64).to_radians(), (pitch as f64).to_radians());
+
+    DVec3::new(
+        -yaw.sin() * pitch.cos(),
+        -pitch.sin(),
+        yaw.cos() * pitch.cos(),
+    )
+}
+
+pub(crate) fn throw_ender_pearls(
+    mut commands: Commands,
+    mut clients: Query<(&Client, &mut Inventory)>,
+    mut use_item: EventReader<UseItem>,
+) {
+    for event in use_item.iter() {
+        let Ok((client, mut inventory)) = clients.get_mut(event.client) else {
+            continue
+        };
+
+        if held_item(client, &inventory, event.hand)
+            .map_or(true, |stack| stack.item != ItemKind::EnderPearl)
+        {
+            continue;
+        }
+
+        consume_held_item(client, &mut inventory, event.hand);
+
+        let velocity = look_direction(client.yaw(), client.pitch()) * PEARL_SPEED;
+
+        let mut pearl = McEntity::new(EntityKind::EnderPearl, client.instance());
+        pearl.set_position(client.position() + DVec3::new(0.0, 1.52, 0.0));
+
+        commands.spawn((pearl, ThrownEnderPearl::new(event.client, velocity)));
+    }
+}
+
+pub(crate) fn tick_ender_pearls(
+    mut commands: Commands,
+    instances: Query<&Instance>,
+    mut pearls: Query<(Entity, &mut McEntity, &mut ThrownEnderPearl), Without<Despawned>>,
+    mut clients: Query<(&mut Client, Option<&mut FallDistance>)>,
+    mut teleports: EventWriter<ItemTeleport>,
+    mut damage: EventWriter<EntityDamage>,
+    skipped: Res<SkippedInstances>,
+) {
+    for (entity, mut mc_entity, mut pearl) in &mut pearls {
+        if skipped.contains(mc_entity.instance()) {
+            continue;
+        }
+
+        let Ok(instance) = instances.get(mc_entity.instance()) else {
+            commands.entity(entity).insert(Despawned);
+            continue;
+        };
+
+        let Some(collision) = instance.collide_aabb(mc_entity.hitbox(), pearl.velocity) else {
+            let position = mc_entity.position() + pearl.velocity;
+
+            if position.y < instance.min_y() as f64 - 64.0 {
+                commands.entity(entity).insert(Despawned);
+                continue;
+            }
+
+            pearl.velocity = pearl.velocity * 0.99 - DVec3::new(0.0, 0.03, 0.0);
+
+            mc_entity.set_position(position);
+            mc_entity.set_velocity((pearl.velocity * 20.0).as_vec3());
+            continue;
+        };
+
+        commands.entity(entity).insert(Despawned);
+
+        let landing = mc_entity.position() + pearl.velocity * collision.t;
+
+        let Ok((mut client, fall)) = clients.get_mut(pearl.owner) else {
+            continue
+        };
+
+        if client.instance() != mc_entity.instance() {
+            continue;
+        }
+
+        let from = client.position();
+        client.set_position(landing);
+
+        if let Some(mut fall) = fall {
+            fall.distance = 0.0;
+        }
+
+        damage.send(EntityDamage {
+            entity: pearl.owner,
+            source: Some(entity),
+            kind: DamageKind::Fall,
+            amount: PEARL_DAMAGE,
+        });
+
+        teleports.send(ItemTeleport {
+            client: pearl.owner,
+            item: ItemKind::EnderPearl,
+            from,
+            to: landing,
+        });
+    }
+}
+
+/// Returns a random position within 8 blocks of `origin` where a client can
+/// stand, or `None` if none was found.
+fn find_chorus_destination(instance: &Instance, origin: DVec3) -> Option<DVec3> {
+    let mut rng = rand::thread_rng();
+
+    let is_free = |pos: BlockPos| {
+        instance.block_collision_shapes(pos).next().is_none() && !instance.is_water_at(pos)
+    };
+
+    for _ in 0..16 {
+        let x = origin.x + rng.gen_range(-8.0..8.0);
+        let z = origin.z + rng.gen_range(-8.0..8.0);
+        let mut pos = BlockPos::at([x, origin.y + rng.gen_range(-8.0..8.0), z]);
+
+        // Move down until the block below can be stood on.
+        while pos.y > instance.min_y()
+            && instance
+                .block_collision_shapes([pos.x, pos.y - 1, pos.z])
+                .next()
+                .is_none()
+        {
+            pos.y -= 1;
+        }
+
+        if pos.y <= instance.min_y() || !is_free(pos) || !is_free([pos.x, pos.y + 1, pos.z].into())
+        {
+            continue;
+        }
+
+        let ground = instance
+            .block_collision_shapes([pos.x, pos.y - 1, pos.z])
+            .map(|shape| shape.max.y)
+            .fold(f64::NEG_INFINITY, f64::max);
+
+        return Some(DVec3::new(x, ground, z));
+    }
+
+    None
+}
+
+pub(crate) fn start_eating_chorus_fruit(
+    mut commands: Commands,
+    clients: Query<(&Client, &Inventory)>,
+    mut use_item: EventReader<UseItem>,
+    mut stop_using: EventReader<UpdateHeldItemState>,
+) {
+    for event in use_item.iter() {
+        let Ok((client, inventory)) = clients.get(event.client) else {
+            continue
+        };
+
+        if held_item(client, inventory, event.hand)
+            .map_or(false, |stack| stack.item == ItemKind::ChorusFruit)
+        {
+            commands.entity(event.client).insert(EatingChorusFruit {
+                hand: event.hand,
+                ticks: CHORUS_FRUIT_EAT_TICKS,
+            });
+        }
+    }
+
+    for event in stop_using.iter() {
+        if let Some(mut entity) = commands.get_entity(event.client) {
+            entity.remove::<EatingChorusFruit>();
+        }
+    }
+}
+
+pub(crate) fn eat_chorus_fruit(
+    mut commands: Commands,
+    mut instances: Query<&mut Instance>,
+    mut clients: Query<(
+        Entity,
+        &mut Client,
+        &mut Inventory,
+        &mut EatingChorusFruit,
+        Option<&mut FallDistance>,
+    )>,
+    mut teleports: EventWriter<ItemTeleport>,
+) {
+    for (entity, mut client, mut inventory, mut eating, fall) in &mut clients {
+        eating.ticks = eating.ticks.saturating_sub(1);
+
+        if eating.ticks > 0 {
+            continue;
+        }
+
+        commands.entity(entity).remove::<EatingChorusFruit>();
+
+        if held_item(&client, &inventory, eating.hand)
+            .map_or(true, |stack| stack.item != ItemKind::ChorusFruit)
+        {
+            continue;
+        }
+
+        consume_held_item(&client, &mut inventory, eating.hand);
+
+        let Ok(mut instance) = instances.get_mut(client.instance()) else {
+            continue
+        };
+
+        let from = client.position();
+
+        let Some(to) = find_chorus_destination(&instance, from) else {
+            continue
+        };
+
+        client.set_position(to);
+        instance.play_sound(
+            Sound::ItemChorusFruitTeleport,
+            SoundCategory::Player,
+            to,
+            1.0,
+            1.0,
+        );
+
+        if let Some(mut fall) = fall {
+            fall.distance = 0.0;
+        }
+
+        teleports.send(ItemTeleport {
+            client: entity,
+            item: ItemKind::ChorusFruit,
+            from,
+            to,
+        });
+    }
+}
+
+pub(crate) fn explode_end_crystals(
+    mut commands: Commands,
+    manager: Res<McEntityManager>,
+    crystals: Query<&McEntity, Without<Despawned>>,
+    mut pending: ResMut<PendingExplosions>,
+    mut interactions: EventReader<InteractWithEntity>,
+) {
+    for event in interactions.iter() {
+        if event.interact != EntityInteraction::Attack {
+            continue;
+        }
+
+        let Some(entity) = manager.get_with_protocol_id(event.entity_id) else {
+            continue
+        };
+
+        let Ok(crystal) = crystals.get(entity) else {
+            continue
+        };
+
+        if crystal.kind() != EntityKind::EndCrystal {
+            continue;
+        }
+
+        commands.entity(entity).insert(Despawned);
+
+        pending.push(
+            Explosion::new(crystal.instance(), crystal.position(), END_CRYSTAL_POWER)
+                .with_source(event.client),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy_app::App;
+    use valence_protocol::BlockState;
+
+    use super::*;
+    use crate::instance::Chunk;
+    use crate::unit_test::util::scenario_single_client;
+
+    #[test]
+    fn ender_pearl_teleports_owner() {
+        let mut app = App::new();
+        let (client_ent, _) = scenario_single_client(&mut app);
+        let instance_ent = app.world.get::<Client>(client_ent).unwrap().instance();
+
+        let mut instance = app.world.get_mut::<Instance>(instance_ent).unwrap();
+        instance.insert_chunk([0, 0], Chunk::default());
+        instance.set_block([4, 0, 4], BlockState::STONE);
+
+        let mut pearl = McEntity::new(EntityKind::EnderPearl, instance_ent);
+        pearl.set_position([4.5, 5.0, 4.5]);
+
+        app.world
+            .spawn((pearl, ThrownEnderPearl::new(client_ent, [0.0, -1.0, 0.0])));
+
+        for _ in 0..5 {
+            app.update();
+        }
+
+        let client = app.world.get::<Client>(client_ent).unwrap();
+        assert!(client.position().distance(DVec3::new(4.5, 1.0, 4.5)) < 1e-9);
+
+        let teleports = app.world.resource::<Events<ItemTeleport>>();
+        let mut reader = teleports.get_reader();
+        assert!(reader
+            .iter(teleports)
+            .any(|e| e.client == client_ent && e.item == ItemKind::EnderPearl));
+    }
+
+    #[test]
+    fn chorus_fruit_destination_is_safe() {
+        let mut app = App::new();
+        let (client_ent, _) = scenario_single_client(&mut app);
+        let instance_ent = app.world.get::<Client>(client_ent).unwrap().instance();
+
+        let mut instance = app.world.get_mut::<Instance>(instance_ent).unwrap();
+
+        for z in -1..=1 {
+            for x in -1..=1 {
+                instance.insert_chunk([x, z], Chunk::default());
+            }
+        }
+
+        for z in -16..16 {
+            for x in -16..16 {
+                instance.set_block([x, 0, z], BlockState::STONE_SLAB);
+            }
+        }
+
+        let to = find_chorus_destination(&instance, DVec3::new(0.0, 3.0, 0.0)).unwrap();
+
+        assert_eq!(to.y, 0.5);
+        assert!(to.x.abs() <= 8.0 && to.z.abs() <= 8.0);
+    }
+}
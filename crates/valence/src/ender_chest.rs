@@ -0,0 +1,252 @@
+//! Private ender chest inventories.
+//!
+//! The items of the ender chest of a client are kept in an ordinary
+//! [`Inventory`] on its own entity, which the [`EnderChest`] component on the
+//! entity of the client refers to. Clicking an ender chest block opens it, and
+//! every ender chest shows the same items to the same client. Other ways of
+//! opening it, such as a command, open the inventory like any other:
+//!
+//! ```
+//! use valence::command::CommandExecuted;
+//! use valence::ender_chest::EnderChest;
+//! use valence::prelude::*;
+//!
+//! fn open_ender_chest_command(
+//!     mut commands: Commands,
+//!     clients: Query<&EnderChest>,
+//!     mut executed: EventReader<CommandExecuted>,
+//! ) {
+//!     for event in executed.iter().filter(|e| e.command == "enderchest") {
+//!         if let Ok(chest) = clients.get(event.client) {
+//!             commands
+//!                 .entity(event.client)
+//!                 .insert(OpenInventory::new(chest.inventory()));
+//!         }
+//!     }
+//! }
+//! ```
+//!
+//! The items are read from and written to the `EnderItems` list of player
+//! data with [`read_ender_items`] and [`write_ender_items`]. The inventory is
+//! despawned along with the client.
+
+use bevy_ecs::prelude::*;
+use glam::DVec3;
+use valence_nbt::{Compound, List, Value};
+use valence_protocol::translation_key::CONTAINER_ENDERCHEST;
+use valence_protocol::types::{GameMode, SoundCategory};
+use valence_protocol::{BlockFace, BlockKind, Sound, Text};
+
+use crate::client::event::UseItemOnBlock;
+use crate::client::pose::PoseState;
+use crate::client::Client;
+use crate::entity::equipment::{stack_from_nbt, stack_to_nbt};
+use crate::instance::Instance;
+use crate::inventory::{held_item, Inventory, InventoryKind, OpenInventory};
+use crate::Despawned;
+
+/// The number of slots of an ender chest.
+pub const ENDER_CHEST_SLOTS: u16 = 27;
+
+/// A [`Component`] with the entity of the ender chest inventory of the
+/// [`Client`] on the same entity.
+#[derive(Component, Copy, Clone, PartialEq, Eq, Debug)]
+pub struct EnderChest {
+    inventory: Entity,
+}
+
+impl EnderChest {
+    /// Uses the [`Inventory`] on the given entity as the ender chest. The
+    /// inventory is usually created with [`ender_chest_inventory`] or
+    /// [`read_ender_items`].
+    pub fn new(inventory: Entity) -> Self {
+        Self { inventory }
+    }
+
+    pub fn inventory(&self) -> Entity {
+        self.inventory
+    }
+}
+
+/// Creates an empty ender chest inventory.
+pub fn ender_chest_inventory() -> Inventory {
+    Inventory::with_title(
+        InventoryKind::Generic9x3,
+        Text::translate(CONTAINER_ENDERCHEST, vec![]),
+    )
+}
+
+/// Creates an ender chest inventory with the items of the `EnderItems` list
+/// of player data. Items in slots outside of the ender chest are ignored.
+pub fn read_ender_items(nbt: &Compound) -> Inventory {
+    let mut inventory = ender_chest_inventory();
+
+    if let Some(Value::List(List::Compound(items))) = nbt.get("EnderItems") {
+        for item in items {
+            let Some(Value::Byte(slot)) = item.get("Slot") else {
+                continue;
+            };
+
+            if !(0..ENDER_CHEST_SLOTS as i8).contains(slot) {
+                continue;
+            }
+
+            inventory.replace_slot(*slot as u16, stack_from_nbt(item));
+        }
+    }
+
+    inventory
+}
+
+/// Writes the items of an ender chest inventory to the `EnderItems` list of
+/// player data. Empty slots are left out like in vanilla.
+pub fn write_ender_items(inventory: &Inventory, nbt: &mut Compound) {
+    let items = (0..inventory.slot_count().min(ENDER_CHEST_SLOTS))
+        .filter_map(|slot| {
+            let stack = inventory.slot(slot)?;
+            let mut item = stack_to_nbt(Some(stack));
+            item.insert("Slot", slot as i8);
+            Some(item)
+        })
+        .collect();
+
+    nbt.insert("EnderItems", List::Compound(items));
+}
+
+/// Opens the ender chests of clients clicking ender chest blocks.
+pub(crate) fn open_ender_chests(
+    mut commands: Commands,
+    clients: Query<(&Client, &Inventory, &EnderChest, Option<&PoseState>)>,
+    mut instances: Query<&mut Instance>,
+    mut use_item_on_block: EventReader<UseItemOnBlock>,
+) {
+    for event in use_item_on_block.iter() {
+        let Ok((client, inventory, chest, pose)) = clients.get(event.client) else {
+            continue;
+        };
+
+        if client.game_mode() == GameMode::Spectator {
+            continue;
+        }
+
+        // Sneaking clients use their item on the ender chest instead.
+        if pose.map_or(false, |pose| pose.is_sneaking())
+            && held_item(client, inventory, event.hand).is_some()
+        {
+            continue;
+        }
+
+        let Ok(mut instance) = instances.get_mut(client.instance()) else {
+            continue;
+        };
+
+        if instance
+            .block(event.position)
+            .map_or(true, |b| b.state().to_kind() != BlockKind::EnderChest)
+        {
+            continue;
+        }
+
+        // Like chests, ender chests can't be opened below solid blocks.
+        if instance
+            .block(event.position.get_in_direction(BlockFace::Top))
+            .map_or(false, |b| b.state().is_opaque())
+        {
+            continue;
+        }
+
+        let pos = event.position;
+        instance.play_sound(
+            Sound::BlockEnderChestOpen,
+            SoundCategory::Block,
+            DVec3::new(pos.x as f64 + 0.5, pos.y as f64 + 0.5, pos.z as f64 + 0.5),
+            0.5,
+            rand::random::<f32>() * 0.1 + 0.9,
+        );
+
+        commands
+            .entity(event.client)
+            .insert(OpenInventory::new(chest.inventory));
+    }
+}
+
+/// Despawns the ender chest inventories of despawned clients.
+pub(crate) fn despawn_ender_chests(
+    mut commands: Commands,
+    clients: Query<&EnderChest, Added<Despawned>>,
+) {
+    for chest in &clients {
+        commands.entity(chest.inventory).insert(Despawned);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy_app::App;
+    use valence_protocol::block::BlockState;
+    use valence_protocol::packets::c2s::play::UseItemOn;
+    use valence_protocol::packets::S2cPlayPacket;
+    use valence_protocol::types::Hand;
+    use valence_protocol::{BlockPos, ItemKind, ItemStack};
+
+    use super::*;
+    use crate::assert_packet_count;
+    use crate::instance::Chunk;
+    use crate::unit_test::util::scenario_single_client;
+
+    #[test]
+    fn ender_items_round_trip() {
+        let mut inventory = ender_chest_inventory();
+        inventory.replace_slot(3, ItemStack::new(ItemKind::Diamond, 7, None));
+        inventory.replace_slot(26, ItemStack::new(ItemKind::Stick, 1, None));
+
+        let mut nbt = Compound::new();
+        write_ender_items(&inventory, &mut nbt);
+
+        let read = read_ender_items(&nbt);
+        assert_eq!(read.slot(3), inventory.slot(3));
+        assert_eq!(read.slot(26), inventory.slot(26));
+        assert_eq!(read.slot(0), None);
+    }
+
+    #[test]
+    fn clicking_an_ender_chest_opens_it() -> anyhow::Result<()> {
+        let mut app = App::new();
+        let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+        let instance_ent = app.world.get::<Client>(client_ent).unwrap().instance();
+
+        let mut instance = app.world.get_mut::<Instance>(instance_ent).unwrap();
+        instance.insert_chunk([0, 0], Chunk::default());
+        instance.set_block([0, 1, 0], BlockState::ENDER_CHEST);
+
+        let chest_ent = app.world.spawn(ender_chest_inventory()).id();
+        app.world
+            .entity_mut(client_ent)
+            .insert(EnderChest::new(chest_ent));
+
+        app.update();
+        client_helper.clear_sent();
+
+        client_helper.send(&UseItemOn {
+            hand: Hand::Main,
+            position: BlockPos::new(0, 1, 0),
+            face: BlockFace::Top,
+            cursor_pos: [0.5, 1.0, 0.5],
+            head_inside_block: false,
+            sequence: 0.into(),
+        });
+
+        app.update();
+
+        // The inventory is opened on the tick after the click.
+        app.update();
+
+        let open = app.world.get::<OpenInventory>(client_ent).unwrap();
+        assert_eq!(open.entity(), chest_ent);
+
+        let sent_packets = client_helper.collect_sent()?;
+        assert_packet_count!(sent_packets, 1, S2cPlayPacket::OpenScreen(_));
+
+        Ok(())
+    }
+}
@@ -1,4 +1,5 @@
-use std::collections::HashMap;
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, VecDeque};
 use std::fmt;
 use std::fmt::Formatter;
 use std::ops::Range;
@@ -11,34 +12,75 @@ use tracing::warn;
 use uuid::Uuid;
 use valence_protocol::entity_meta::{Facing, PaintingKind, Pose};
 use valence_protocol::packets::s2c::play::{
-    EntityAnimationS2c, EntityEvent as EntityEventS2c, SetEntityMetadata, SetEntityVelocity,
-    SetHeadRotation, SpawnEntity, SpawnExperienceOrb, SpawnPlayer, TeleportEntity,
-    UpdateEntityPosition, UpdateEntityPositionAndRotation, UpdateEntityRotation,
+    EntityAnimationS2c, EntityEvent as EntityEventS2c, RemoveEntitiesEncode, SetEntityMetadata,
+    SetEntityVelocity, SetEquipment, SetHeadRotation, SetPassengers, SpawnEntity,
+    SpawnExperienceOrb, SpawnPlayer, TeleportEntity, UpdateEntityPosition,
+    UpdateEntityPositionAndRotation, UpdateEntityRotation,
 };
-use valence_protocol::{ByteAngle, RawBytes, VarInt};
+use valence_protocol::packets::s2c::set_equipment::EquipmentEntry;
+use valence_protocol::{ByteAngle, ItemStack, RawBytes, VarInt};
 
 use crate::config::DEFAULT_TPS;
-use crate::math::Aabb;
+use crate::entity::equipment::EquipmentSlot;
+use crate::math::{yaw_and_pitch_toward, Aabb};
 use crate::packet::WritePacket;
+use crate::server::Server;
 use crate::{Despawned, NULL_ENTITY};
 
+pub mod armor_stand;
+pub mod attributes;
+pub mod breeding;
 pub mod data;
+pub mod disguise;
+pub mod dropped_item;
+pub mod equipment;
+pub mod filter;
+pub mod horse;
+pub mod item_frame;
+pub mod item_interaction;
+pub mod loadout;
+pub mod name_tag;
+pub mod passengers;
+pub mod pet;
+pub mod pushing;
+pub mod villager;
 
 include!(concat!(env!("OUT_DIR"), "/entity_event.rs"));
 
+/// The height of the eyes of a standing player above its feet.
+pub const PLAYER_EYE_HEIGHT: f64 = 1.62;
+
+/// The number of ticks before the protocol ID of a despawned entity is given
+/// to a new entity. Packets sent by clients before they saw the entity
+/// despawn would otherwise refer to the new entity.
+pub const PROTOCOL_ID_REUSE_DELAY: i64 = 40;
+
 /// A [`Resource`] which maintains information about all the [`McEntity`]
 /// components on the server.
+///
+/// The manager hands out the protocol IDs of entities, which the packets of
+/// clients use to refer to them, and keeps track of the entities by protocol
+/// ID and by UUID so both can be resolved without going through all entities.
+/// The protocol IDs of despawned entities are given to new entities again,
+/// oldest first, once [`PROTOCOL_ID_REUSE_DELAY`] ticks have passed.
 #[derive(Resource)]
 pub struct McEntityManager {
     protocol_id_to_entity: FxHashMap<i32, Entity>,
+    /// The entities with each UUID, in the order they were spawned.
+    uuid_to_entity: FxHashMap<Uuid, Vec<Entity>>,
     next_protocol_id: i32,
+    /// The tick the protocol IDs of despawned entities were freed in and the
+    /// IDs, in the order they were freed.
+    free_protocol_ids: VecDeque<(i64, i32)>,
 }
 
 impl McEntityManager {
     pub(crate) fn new() -> Self {
         Self {
             protocol_id_to_entity: HashMap::default(),
+            uuid_to_entity: HashMap::default(),
             next_protocol_id: 1,
+            free_protocol_ids: VecDeque::new(),
         }
     }
 
@@ -46,36 +88,98 @@ impl McEntityManager {
     pub fn get_with_protocol_id(&self, id: i32) -> Option<Entity> {
         self.protocol_id_to_entity.get(&id).cloned()
     }
+
+    /// Gets the [`Entity`] of the [`McEntity`] with the given UUID. If several
+    /// entities share the UUID, the one spawned last is returned.
+    pub fn get_with_uuid(&self, uuid: Uuid) -> Option<Entity> {
+        self.uuid_to_entity
+            .get(&uuid)
+            .and_then(|entities| entities.last())
+            .cloned()
+    }
+
+    /// Returns the number of entities with a protocol ID.
+    pub fn len(&self) -> usize {
+        self.protocol_id_to_entity.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.protocol_id_to_entity.is_empty()
+    }
+
+    fn allocate_protocol_id(&mut self, current_tick: i64) -> i32 {
+        if let Some(&(free_tick, id)) = self.free_protocol_ids.front() {
+            if current_tick - free_tick >= PROTOCOL_ID_REUSE_DELAY {
+                self.free_protocol_ids.pop_front();
+                return id;
+            }
+        }
+
+        loop {
+            let id = self.next_protocol_id;
+            self.next_protocol_id = self.next_protocol_id.wrapping_add(1);
+
+            if id == 0 {
+                warn!("entity protocol ID overflow");
+                // ID 0 is reserved for clients so we skip over it.
+                continue;
+            }
+
+            // IDs handed out before an overflow may still be in use.
+            if !self.protocol_id_to_entity.contains_key(&id) {
+                return id;
+            }
+        }
+    }
 }
 
 /// Sets the protocol ID of new entities.
 pub(crate) fn init_entities(
     mut entities: Query<(Entity, &mut McEntity), Added<McEntity>>,
     mut manager: ResMut<McEntityManager>,
+    server: Res<Server>,
 ) {
     for (entity, mut mc_entity) in &mut entities {
-        if manager.next_protocol_id == 0 {
-            warn!("entity protocol ID overflow");
-            // ID 0 is reserved for clients so we skip over it.
-            manager.next_protocol_id = 1;
-        }
-
-        mc_entity.protocol_id = manager.next_protocol_id;
-        manager.next_protocol_id = manager.next_protocol_id.wrapping_add(1);
+        mc_entity.protocol_id = manager.allocate_protocol_id(server.current_tick());
 
         manager
             .protocol_id_to_entity
             .insert(mc_entity.protocol_id, entity);
+        manager
+            .uuid_to_entity
+            .entry(mc_entity.uuid)
+            .or_default()
+            .push(entity);
     }
 }
 
-/// Removes despawned entities from the entity manager.
+/// Removes despawned entities from the entity manager and frees their protocol
+/// IDs. The packets removing the entities from clients have been written by
+/// then, but the IDs are only given to new entities after
+/// [`PROTOCOL_ID_REUSE_DELAY`] ticks.
 pub(crate) fn deinit_despawned_entities(
-    entities: Query<&mut McEntity, With<Despawned>>,
+    entities: Query<(Entity, &McEntity), With<Despawned>>,
     mut manager: ResMut<McEntityManager>,
+    server: Res<Server>,
 ) {
-    for entity in &entities {
-        manager.protocol_id_to_entity.remove(&entity.protocol_id);
+    for (entity, mc_entity) in &entities {
+        if manager
+            .protocol_id_to_entity
+            .remove(&mc_entity.protocol_id)
+            .is_some()
+        {
+            manager
+                .free_protocol_ids
+                .push_back((server.current_tick(), mc_entity.protocol_id));
+        }
+
+        if let Entry::Occupied(mut entry) = manager.uuid_to_entity.entry(mc_entity.uuid) {
+            entry.get_mut().retain(|&e| e != entity);
+
+            if entry.get().is_empty() {
+                entry.remove();
+            }
+        }
     }
 }
 
@@ -86,9 +190,13 @@ pub(crate) fn update_entities(mut entities: Query<&mut McEntity, Changed<McEntit
         entity.old_instance = entity.instance;
         entity.statuses = 0;
         entity.animations = 0;
+        entity.equipment_modified = 0;
+        entity.passenger_ids_modified = false;
         entity.yaw_or_pitch_modified = false;
+        entity.teleported = false;
         entity.head_yaw_modified = false;
         entity.velocity_modified = false;
+        entity.needs_respawn = false;
     }
 }
 
@@ -124,6 +232,16 @@ pub struct McEntity {
     statuses: u64,
     /// Contains a set bit for every animation triggered this tick.
     animations: u8,
+    /// The items visible in the equipment slots of this entity.
+    equipment: [Option<ItemStack>; 7],
+    /// Contains a set bit for every equipment slot modified this tick.
+    equipment_modified: u8,
+    /// The protocol IDs of the entities riding this entity.
+    passenger_ids: Vec<VarInt>,
+    passenger_ids_modified: bool,
+    /// The protocol ID of the vehicle this entity rides, and the protocol IDs
+    /// of the passengers of the vehicle.
+    vehicle_ids: Option<(VarInt, Vec<VarInt>)>,
     instance: Entity,
     old_instance: Entity,
     position: DVec3,
@@ -131,11 +249,14 @@ pub struct McEntity {
     yaw: f32,
     pitch: f32,
     yaw_or_pitch_modified: bool,
+    /// If the entity was moved with [`McEntity::teleport`] this tick.
+    teleported: bool,
     head_yaw: f32,
     head_yaw_modified: bool,
     velocity: Vec3,
     velocity_modified: bool,
     on_ground: bool,
+    needs_respawn: bool,
 }
 
 impl McEntity {
@@ -157,6 +278,11 @@ impl McEntity {
             self_update_range: 0..0,
             statuses: 0,
             animations: 0,
+            equipment: Default::default(),
+            equipment_modified: 0,
+            passenger_ids: vec![],
+            passenger_ids_modified: false,
+            vehicle_ids: None,
             instance,
             old_instance: NULL_ENTITY,
             position: DVec3::ZERO,
@@ -164,6 +290,7 @@ impl McEntity {
             yaw: 0.0,
             pitch: 0.0,
             yaw_or_pitch_modified: false,
+            teleported: false,
             head_yaw: 0.0,
             head_yaw_modified: false,
             velocity: Vec3::ZERO,
@@ -171,6 +298,7 @@ impl McEntity {
             protocol_id: 0,
             uuid,
             on_ground: false,
+            needs_respawn: false,
         }
     }
 
@@ -184,6 +312,55 @@ impl McEntity {
         &mut self.data
     }
 
+    /// Despawns and spawns this entity again for all clients in view of it at
+    /// the end of the tick. This is needed for changes that clients only read
+    /// when the entity is spawned, such as the skin of a player entity.
+    pub fn respawn(&mut self) {
+        self.needs_respawn = true;
+    }
+
+    /// Returns the item visible in an equipment slot of this entity.
+    ///
+    /// The equipment is set through the [`Equipments`] component.
+    ///
+    /// [`Equipments`]: equipment::Equipments
+    pub fn equipment(&self, slot: EquipmentSlot) -> Option<&ItemStack> {
+        self.equipment[slot as usize].as_ref()
+    }
+
+    pub(crate) fn set_equipment(&mut self, slot: EquipmentSlot, item: Option<ItemStack>) {
+        if self.equipment[slot as usize] != item {
+            self.equipment[slot as usize] = item;
+            self.equipment_modified |= 1 << slot as u8;
+        }
+    }
+
+    /// Sets the passengers sent to clients in view of this entity. They are
+    /// set through the [`Passengers`] component.
+    ///
+    /// [`Passengers`]: passengers::Passengers
+    pub(crate) fn set_passenger_ids(&mut self, ids: Vec<VarInt>) {
+        if self.passenger_ids != ids {
+            self.passenger_ids = ids;
+            self.passenger_ids_modified = true;
+        }
+    }
+
+    pub(crate) fn passenger_ids(&self) -> &[VarInt] {
+        &self.passenger_ids
+    }
+
+    /// Sets the vehicle and its passengers sent to clients this entity becomes
+    /// visible to, so that it is seated even if the vehicle became visible
+    /// first.
+    pub(crate) fn set_vehicle_ids(&mut self, ids: Option<(VarInt, Vec<VarInt>)>) {
+        self.vehicle_ids = ids;
+    }
+
+    pub(crate) fn vehicle_ids(&self) -> Option<&(VarInt, Vec<VarInt>)> {
+        self.vehicle_ids.as_ref()
+    }
+
     /// Gets the [`EntityKind`] of this entity.
     pub fn kind(&self) -> EntityKind {
         self.data.kind()
@@ -266,6 +443,19 @@ impl McEntity {
         }
     }
 
+    /// Moves the entity to the position with the given yaw and pitch (in
+    /// degrees).
+    ///
+    /// Clients are sent the exact position instead of the movement from the
+    /// previous position, which is used for small movements and can
+    /// accumulate rounding errors.
+    pub fn teleport(&mut self, pos: impl Into<DVec3>, yaw: f32, pitch: f32) {
+        self.set_position(pos);
+        self.set_yaw(yaw);
+        self.set_pitch(pitch);
+        self.teleported = true;
+    }
+
     /// Gets the head yaw of this entity in degrees.
     pub fn head_yaw(&self) -> f32 {
         self.head_yaw
@@ -279,6 +469,36 @@ impl McEntity {
         }
     }
 
+    /// Returns the height of the eyes of this entity above its position.
+    pub fn eye_height(&self) -> f64 {
+        match self.kind {
+            EntityKind::Player => PLAYER_EYE_HEIGHT,
+            // Vanilla places the eyes of most entities at 85% of their height.
+            _ => {
+                let hitbox = self.hitbox();
+                (hitbox.max.y - hitbox.min.y) * 0.85
+            }
+        }
+    }
+
+    /// Returns the position of the eyes of this entity.
+    pub fn eye_position(&self) -> DVec3 {
+        self.position + DVec3::new(0.0, self.eye_height(), 0.0)
+    }
+
+    /// Turns the body and the head of this entity so that its eyes look at
+    /// the target position. Pass the [`eye_position`] of another entity to
+    /// make this entity look it in the eyes.
+    ///
+    /// [`eye_position`]: Self::eye_position
+    pub fn look_at(&mut self, target: impl Into<DVec3>) {
+        if let Some((yaw, pitch)) = yaw_and_pitch_toward(self.eye_position(), target.into()) {
+            self.set_yaw(yaw);
+            self.set_pitch(pitch);
+            self.set_head_yaw(yaw);
+        }
+    }
+
     /// Gets the velocity of this entity in meters per second.
     pub fn velocity(&self) -> Vec3 {
         self.velocity
@@ -323,6 +543,12 @@ impl McEntity {
     ///
     /// [interact event]: crate::client::event::InteractWithEntity
     pub fn hitbox(&self) -> Aabb {
+        self.hitbox_with_data(&self.data)
+    }
+
+    /// Returns the hitbox this entity would have if its tracked data was
+    /// `data`.
+    pub(crate) fn hitbox_with_data(&self, data: &TrackedData) -> Aabb {
         fn baby(is_baby: bool, adult_hitbox: [f64; 3]) -> [f64; 3] {
             if is_baby {
                 adult_hitbox.map(|a| a / 2.0)
@@ -331,10 +557,10 @@ impl McEntity {
             }
         }
 
-        fn item_frame(pos: DVec3, rotation: i32) -> Aabb {
+        fn item_frame(pos: DVec3, facing: i32) -> Aabb {
             let mut center_pos = pos + 0.5;
 
-            match rotation {
+            match facing {
                 0 => center_pos.y += 0.46875,
                 1 => center_pos.y -= 0.46875,
                 2 => center_pos.z += 0.46875,
@@ -344,7 +570,7 @@ impl McEntity {
                 _ => center_pos.y -= 0.46875,
             };
 
-            let bounds = DVec3::from(match rotation {
+            let bounds = DVec3::from(match facing {
                 0 | 1 => [0.75, 0.0625, 0.75],
                 2 | 3 => [0.75, 0.75, 0.0625],
                 4 | 5 => [0.0625, 0.75, 0.75],
@@ -357,7 +583,7 @@ impl McEntity {
             }
         }
 
-        let dimensions = match &self.data {
+        let dimensions = match data {
             TrackedData::Allay(_) => [0.6, 0.35, 0.6],
             TrackedData::ChestBoat(_) => [1.375, 0.5625, 1.375],
             TrackedData::Frog(_) => [0.5, 0.5, 0.5],
@@ -381,13 +607,13 @@ impl McEntity {
                 }
             }
             TrackedData::Arrow(_) => [0.5, 0.5, 0.5],
-            TrackedData::Axolotl(_) => [1.3, 0.6, 1.3],
+            TrackedData::Axolotl(e) => baby(e.get_child(), [1.3, 0.6, 1.3]),
             TrackedData::Bat(_) => [0.5, 0.9, 0.5],
             TrackedData::Bee(e) => baby(e.get_child(), [0.7, 0.6, 0.7]),
             TrackedData::Blaze(_) => [0.6, 1.8, 0.6],
             TrackedData::Boat(_) => [1.375, 0.5625, 1.375],
             TrackedData::Camel(e) => baby(e.get_child(), [1.7, 2.375, 1.7]),
-            TrackedData::Cat(_) => [0.6, 0.7, 0.6],
+            TrackedData::Cat(e) => baby(e.get_child(), [0.6, 0.7, 0.6]),
             TrackedData::CaveSpider(_) => [0.7, 0.5, 0.7],
             TrackedData::Chicken(e) => baby(e.get_child(), [0.4, 0.7, 0.4]),
             TrackedData::Cod(_) => [0.5, 0.3, 0.5],
@@ -411,7 +637,9 @@ impl McEntity {
             TrackedData::Fox(e) => baby(e.get_child(), [0.6, 0.7, 0.6]),
             TrackedData::Ghast(_) => [4.0, 4.0, 4.0],
             TrackedData::Giant(_) => [3.6, 12.0, 3.6],
-            TrackedData::GlowItemFrame(e) => return item_frame(self.position, e.get_rotation()),
+            TrackedData::GlowItemFrame(_) => {
+                return item_frame(self.position, item_frame_facing(self.pitch, self.yaw))
+            }
             TrackedData::GlowSquid(_) => [0.8, 0.8, 0.8],
             TrackedData::Goat(e) => {
                 if e.get_pose() == Pose::LongJumping {
@@ -427,7 +655,9 @@ impl McEntity {
             TrackedData::Illusioner(_) => [0.6, 1.95, 0.6],
             TrackedData::IronGolem(_) => [1.4, 2.7, 1.4],
             TrackedData::Item(_) => [0.25, 0.25, 0.25],
-            TrackedData::ItemFrame(e) => return item_frame(self.position, e.get_rotation()),
+            TrackedData::ItemFrame(_) => {
+                return item_frame(self.position, item_frame_facing(self.pitch, self.yaw))
+            }
             TrackedData::Fireball(_) => [1.0, 1.0, 1.0],
             TrackedData::LeashKnot(_) => [0.375, 0.5, 0.375],
             TrackedData::Lightning(_) => [0.0, 0.0, 0.0],
@@ -565,7 +795,7 @@ impl McEntity {
             TrackedData::ExperienceBottle(_) => [0.25, 0.25, 0.25],
             TrackedData::Potion(_) => [0.25, 0.25, 0.25],
             TrackedData::Trident(_) => [0.5, 0.5, 0.5],
-            TrackedData::TraderLlama(_) => [0.9, 1.87, 0.9],
+            TrackedData::TraderLlama(e) => baby(e.get_child(), [0.9, 1.87, 0.9]),
             TrackedData::TropicalFish(_) => [0.5, 0.4, 0.5],
             TrackedData::Turtle(e) => {
                 if e.get_child() {
@@ -608,23 +838,35 @@ impl McEntity {
     /// the entity and initialize tracked data.
     pub(crate) fn write_init_packets(
         &self,
+        writer: impl WritePacket,
+        position: DVec3,
+        scratch: &mut Vec<u8>,
+    ) {
+        self.write_init_packets_with_data(&self.data, writer, position, scratch)
+    }
+
+    /// Like [`Self::write_init_packets`], but spawns the entity as if its
+    /// tracked data was `data`.
+    pub(crate) fn write_init_packets_with_data(
+        &self,
+        data: &TrackedData,
         mut writer: impl WritePacket,
         position: DVec3,
         scratch: &mut Vec<u8>,
     ) {
-        let with_object_data = |data| SpawnEntity {
+        let with_object_data = |object_data| SpawnEntity {
             entity_id: VarInt(self.protocol_id),
             object_uuid: self.uuid,
-            kind: VarInt(self.kind() as i32),
+            kind: VarInt(data.kind() as i32),
             position: position.to_array(),
             pitch: ByteAngle::from_degrees(self.pitch),
             yaw: ByteAngle::from_degrees(self.yaw),
             head_yaw: ByteAngle::from_degrees(self.head_yaw),
-            data: VarInt(data),
+            data: VarInt(object_data),
             velocity: velocity_to_packet_units(self.velocity),
         };
 
-        match &self.data {
+        match data {
             TrackedData::Marker(_) => {}
             TrackedData::ExperienceOrb(_) => writer.write_packet(&SpawnExperienceOrb {
                 entity_id: VarInt(self.protocol_id),
@@ -646,9 +888,8 @@ impl McEntity {
                     head_yaw: ByteAngle::from_degrees(self.head_yaw),
                 });
             }
-            TrackedData::ItemFrame(e) => writer.write_packet(&with_object_data(e.get_rotation())),
-            TrackedData::GlowItemFrame(e) => {
-                writer.write_packet(&with_object_data(e.get_rotation()))
+            TrackedData::ItemFrame(_) | TrackedData::GlowItemFrame(_) => {
+                writer.write_packet(&with_object_data(item_frame_facing(self.pitch, self.yaw)))
             }
 
             TrackedData::Painting(_) => writer.write_packet(&with_object_data(
@@ -671,22 +912,91 @@ impl McEntity {
         }
 
         scratch.clear();
-        self.data.write_initial_tracked_data(scratch);
+        data.write_initial_tracked_data(scratch);
         if !scratch.is_empty() {
             writer.write_packet(&SetEntityMetadata {
                 entity_id: VarInt(self.protocol_id),
                 metadata: RawBytes(scratch),
             });
         }
+
+        let equipment = self.equipment_entries(|slot| self.equipment(slot).is_some());
+        if !equipment.is_empty() {
+            writer.write_packet(&SetEquipment {
+                entity_id: VarInt(self.protocol_id),
+                equipment,
+            });
+        }
+
+        if !self.passenger_ids.is_empty() {
+            writer.write_packet(&SetPassengers {
+                entity_id: VarInt(self.protocol_id),
+                passengers: self.passenger_ids.clone(),
+            });
+        }
+
+        if let Some((vehicle_id, passenger_ids)) = &self.vehicle_ids {
+            writer.write_packet(&SetPassengers {
+                entity_id: *vehicle_id,
+                passengers: passenger_ids.clone(),
+            });
+        }
+    }
+
+    /// Returns the equipment entries for the slots matching the predicate.
+    /// Slots with the same protocol ID are sent once with the item of the last
+    /// non-empty slot.
+    fn equipment_entries(&self, f: impl Fn(EquipmentSlot) -> bool) -> Vec<EquipmentEntry> {
+        let mut entries: Vec<EquipmentEntry> = vec![];
+
+        for slot in EquipmentSlot::ALL.into_iter().filter(|&slot| f(slot)) {
+            let id = slot.protocol_id();
+
+            if entries.iter().any(|entry| entry.slot == id) {
+                continue;
+            }
+
+            let item = EquipmentSlot::ALL
+                .into_iter()
+                .filter(|s| s.protocol_id() == id)
+                .filter_map(|s| self.equipment(s))
+                .last()
+                .cloned();
+
+            entries.push(EquipmentEntry { slot: id, item });
+        }
+
+        entries
     }
 
     /// Writes the appropriate packets to update the entity (Position, tracked
     /// data, events, animations).
-    pub(crate) fn write_update_packets(&self, mut writer: impl WritePacket, scratch: &mut Vec<u8>) {
+    pub(crate) fn write_update_packets(&self, writer: impl WritePacket, scratch: &mut Vec<u8>) {
+        self.write_update_packets_with_data(&self.data, writer, scratch)
+    }
+
+    /// Like [`Self::write_update_packets`], but uses `data` as the tracked data
+    /// of the entity.
+    pub(crate) fn write_update_packets_with_data(
+        &self,
+        data: &TrackedData,
+        mut writer: impl WritePacket,
+        scratch: &mut Vec<u8>,
+    ) {
         let entity_id = VarInt(self.protocol_id);
 
+        if self.needs_respawn {
+            writer.write_packet(&RemoveEntitiesEncode {
+                entity_ids: &[entity_id],
+            });
+
+            // The spawn packets already contain the current state of the entity.
+            self.write_init_packets_with_data(data, writer, self.position, scratch);
+            return;
+        }
+
         let position_delta = self.position - self.old_position;
-        let needs_teleport = position_delta.abs().max_element() >= 8.0;
+        let needs_teleport = self.teleported || position_delta.abs().max_element() >= 8.0;
         let changed_position = self.position != self.old_position;
 
         if changed_position && !needs_teleport && self.yaw_or_pitch_modified {
@@ -706,7 +1016,8 @@ impl McEntity {
                 });
             }
 
-            if self.yaw_or_pitch_modified {
+            // Teleports include the rotation.
+            if self.yaw_or_pitch_modified && !needs_teleport {
                 writer.write_packet(&UpdateEntityRotation {
                     entity_id,
                     yaw: ByteAngle::from_degrees(self.yaw),
@@ -741,7 +1052,7 @@ impl McEntity {
         }
 
         scratch.clear();
-        self.data.write_updated_tracked_data(scratch);
+        data.write_updated_tracked_data(scratch);
         if !scratch.is_empty() {
             writer.write_packet(&SetEntityMetadata {
                 entity_id,
@@ -749,6 +1060,21 @@ impl McEntity {
             });
         }
 
+        if self.equipment_modified != 0 {
+            writer.write_packet(&SetEquipment {
+                entity_id,
+                equipment: self
+                    .equipment_entries(|slot| (self.equipment_modified >> slot as u8) & 1 == 1),
+            });
+        }
+
+        if self.passenger_ids_modified {
+            writer.write_packet(&SetPassengers {
+                entity_id,
+                passengers: self.passenger_ids.clone(),
+            });
+        }
+
         if self.statuses != 0 {
             for i in 0..std::mem::size_of_val(&self.statuses) {
                 if (self.statuses >> i) & 1 == 1 {
@@ -781,6 +1107,24 @@ pub(crate) fn velocity_to_packet_units(vel: Vec3) -> [i16; 3] {
         .map(|v| v as i16)
 }
 
+/// Returns the direction an item frame with the given pitch and yaw faces, as
+/// sent in the object data of its spawn packet. Item frames face up or down
+/// when they are pitched that way, like vanilla item frames.
+fn item_frame_facing(pitch: f32, yaw: f32) -> i32 {
+    if pitch <= -45.0 {
+        1
+    } else if pitch >= 45.0 {
+        0
+    } else {
+        match ((yaw + 45.0).rem_euclid(360.0) / 90.0) as u8 {
+            0 => 3,
+            1 => 4,
+            2 => 2,
+            _ => 5,
+        }
+    }
+}
+
 impl fmt::Debug for McEntity {
     fn fmt(&self, f: &mut Formatter) -> fmt::Result {
         f.debug_struct("McEntity")
@@ -791,3 +1135,127 @@ impl fmt::Debug for McEntity {
             .finish_non_exhaustive()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use bevy_app::App;
+
+    use super::*;
+    use crate::client::Client;
+    use crate::unit_test::util::scenario_single_client;
+
+    #[test]
+    fn protocol_ids_of_despawned_entities_are_reused() {
+        let mut app = App::new();
+        let (client_ent, _client_helper) = scenario_single_client(&mut app);
+        let instance_ent = app.world.get::<Client>(client_ent).unwrap().instance();
+
+        let zombie = McEntity::new(EntityKind::Zombie, instance_ent);
+        let zombie_uuid = zombie.uuid();
+        let zombie_ent = app.world.spawn(zombie).id();
+
+        app.update();
+
+        let zombie_id = app.world.get::<McEntity>(zombie_ent).unwrap().protocol_id();
+        let manager = app.world.resource::<McEntityManager>();
+        assert_eq!(manager.get_with_protocol_id(zombie_id), Some(zombie_ent));
+        assert_eq!(manager.get_with_uuid(zombie_uuid), Some(zombie_ent));
+
+        app.world.entity_mut(zombie_ent).insert(Despawned);
+        app.update();
+
+        let manager = app.world.resource::<McEntityManager>();
+        assert_eq!(manager.get_with_protocol_id(zombie_id), None);
+        assert_eq!(manager.get_with_uuid(zombie_uuid), None);
+
+        for _ in 0..PROTOCOL_ID_REUSE_DELAY {
+            app.update();
+        }
+
+        let skeleton_ent = app
+            .world
+            .spawn(McEntity::new(EntityKind::Skeleton, instance_ent))
+            .id();
+
+        app.update();
+
+        let skeleton = app.world.get::<McEntity>(skeleton_ent).unwrap();
+        assert_eq!(skeleton.protocol_id(), zombie_id);
+        assert_eq!(
+            app.world
+                .resource::<McEntityManager>()
+                .get_with_protocol_id(zombie_id),
+            Some(skeleton_ent)
+        );
+    }
+
+    #[test]
+    fn protocol_ids_are_not_reused_right_away() {
+        let mut app = App::new();
+        let (client_ent, _client_helper) = scenario_single_client(&mut app);
+        let instance_ent = app.world.get::<Client>(client_ent).unwrap().instance();
+
+        let zombie_ent = app
+            .world
+            .spawn(McEntity::new(EntityKind::Zombie, instance_ent))
+            .id();
+
+        app.update();
+
+        let zombie_id = app.world.get::<McEntity>(zombie_ent).unwrap().protocol_id();
+
+        app.world.entity_mut(zombie_ent).insert(Despawned);
+        app.update();
+
+        for _ in 1..PROTOCOL_ID_REUSE_DELAY {
+            let skeleton_ent = app
+                .world
+                .spawn(McEntity::new(EntityKind::Skeleton, instance_ent))
+                .id();
+
+            app.update();
+
+            let skeleton = app.world.get::<McEntity>(skeleton_ent).unwrap();
+            assert_ne!(skeleton.protocol_id(), zombie_id);
+        }
+    }
+
+    #[test]
+    fn entities_sharing_a_uuid_are_tracked_separately() {
+        let mut app = App::new();
+        let (client_ent, _client_helper) = scenario_single_client(&mut app);
+        let instance_ent = app.world.get::<Client>(client_ent).unwrap().instance();
+
+        let uuid = Uuid::from_u128(1234);
+
+        let older_ent = app
+            .world
+            .spawn(McEntity::with_uuid(EntityKind::Zombie, instance_ent, uuid))
+            .id();
+
+        app.update();
+
+        let newer_ent = app
+            .world
+            .spawn(McEntity::with_uuid(EntityKind::Zombie, instance_ent, uuid))
+            .id();
+
+        app.update();
+
+        let manager = app.world.resource::<McEntityManager>();
+        assert_eq!(manager.get_with_uuid(uuid), Some(newer_ent));
+
+        // The older entity is found again once the newer one despawns.
+        app.world.entity_mut(newer_ent).insert(Despawned);
+        app.update();
+
+        let manager = app.world.resource::<McEntityManager>();
+        assert_eq!(manager.get_with_uuid(uuid), Some(older_ent));
+
+        app.world.entity_mut(older_ent).insert(Despawned);
+        app.update();
+
+        let manager = app.world.resource::<McEntityManager>();
+        assert_eq!(manager.get_with_uuid(uuid), None);
+    }
+}
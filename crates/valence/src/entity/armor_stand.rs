@@ -0,0 +1,504 @@
+//! Posing and styling armor stands.
+//!
+//! Armor stands are equipped like any other entity through
+//! [`Equipments`](super::equipment::Equipments). The components in this module
+//! control the rest of their appearance, which makes it possible to build
+//! statues and holograms without touching the tracked data directly.
+//!
+//! Like in vanilla, clients swap the item in their hand with an item of an
+//! armor stand by using the item on the stand, or take an item by clicking on
+//! it with an empty hand. Every swap is reported with an
+//! [`ArmorStandManipulateEvent`], which makes it possible to build gear-swap
+//! stations. Armor stands with the [`LockedArmorStand`] component keep their
+//! items, and clients in adventure or spectator mode can't change any stand.
+
+use bevy_ecs::prelude::*;
+use valence_protocol::entity_meta::EulerAngle;
+use valence_protocol::types::GameMode;
+use valence_protocol::{ItemKind, ItemStack};
+
+use crate::client::event::InteractEntityEvent;
+use crate::client::Client;
+use crate::entity::equipment::{EquipmentSlot, Equipments};
+use crate::entity::{McEntity, TrackedData};
+use crate::inventory::{armor_slot, hand_slot, Inventory};
+use crate::Despawned;
+
+/// A [`Component`] containing the rotations of the body parts of the armor
+/// stand [`McEntity`] on the same entity, in degrees.
+///
+/// Removing the component resets the armor stand to [`Self::DEFAULT`]. The
+/// component has no effect on other kinds of entities.
+#[derive(Component, Copy, Clone, PartialEq, Debug)]
+pub struct ArmorStandPose {
+    pub head: EulerAngle,
+    pub body: EulerAngle,
+    pub left_arm: EulerAngle,
+    pub right_arm: EulerAngle,
+    pub left_leg: EulerAngle,
+    pub right_leg: EulerAngle,
+}
+
+const fn angle(pitch: f32, yaw: f32, roll: f32) -> EulerAngle {
+    EulerAngle { pitch, yaw, roll }
+}
+
+impl ArmorStandPose {
+    /// The pose armor stands are spawned with.
+    pub const DEFAULT: Self = Self {
+        head: angle(0.0, 0.0, 0.0),
+        body: angle(0.0, 0.0, 0.0),
+        left_arm: angle(-10.0, 0.0, -10.0),
+        right_arm: angle(-15.0, 0.0, 10.0),
+        left_leg: angle(-1.0, 0.0, -1.0),
+        right_leg: angle(1.0, 0.0, 1.0),
+    };
+
+    /// Every body part pointing straight down.
+    pub const ZERO: Self = Self {
+        head: angle(0.0, 0.0, 0.0),
+        body: angle(0.0, 0.0, 0.0),
+        left_arm: angle(0.0, 0.0, 0.0),
+        right_arm: angle(0.0, 0.0, 0.0),
+        left_leg: angle(0.0, 0.0, 0.0),
+        right_leg: angle(0.0, 0.0, 0.0),
+    };
+
+    #[must_use]
+    pub fn with_head(mut self, pitch: f32, yaw: f32, roll: f32) -> Self {
+        self.head = angle(pitch, yaw, roll);
+        self
+    }
+
+    #[must_use]
+    pub fn with_body(mut self, pitch: f32, yaw: f32, roll: f32) -> Self {
+        self.body = angle(pitch, yaw, roll);
+        self
+    }
+
+    #[must_use]
+    pub fn with_left_arm(mut self, pitch: f32, yaw: f32, roll: f32) -> Self {
+        self.left_arm = angle(pitch, yaw, roll);
+        self
+    }
+
+    #[must_use]
+    pub fn with_right_arm(mut self, pitch: f32, yaw: f32, roll: f32) -> Self {
+        self.right_arm = angle(pitch, yaw, roll);
+        self
+    }
+
+    #[must_use]
+    pub fn with_left_leg(mut self, pitch: f32, yaw: f32, roll: f32) -> Self {
+        self.left_leg = angle(pitch, yaw, roll);
+        self
+    }
+
+    #[must_use]
+    pub fn with_right_leg(mut self, pitch: f32, yaw: f32, roll: f32) -> Self {
+        self.right_leg = angle(pitch, yaw, roll);
+        self
+    }
+
+    fn apply(&self, mc_entity: &mut McEntity) {
+        let TrackedData::ArmorStand(data) = mc_entity.data_mut() else {
+            return;
+        };
+
+        data.set_tracker_head_rotation(self.head);
+        data.set_tracker_body_rotation(self.body);
+        data.set_tracker_left_arm_rotation(self.left_arm);
+        data.set_tracker_right_arm_rotation(self.right_arm);
+        data.set_tracker_left_leg_rotation(self.left_leg);
+        data.set_tracker_right_leg_rotation(self.right_leg);
+    }
+}
+
+impl Default for ArmorStandPose {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+/// A [`Component`] containing the style of the armor stand [`McEntity`] on
+/// the same entity.
+///
+/// Removing the component resets the armor stand to the default style. The
+/// component has no effect on other kinds of entities.
+#[derive(Component, Copy, Clone, PartialEq, Eq, Default, Debug)]
+pub struct ArmorStandStyle {
+    /// If the armor stand is half as large.
+    pub small: bool,
+    /// If the arms of the armor stand are visible. Arms are required for
+    /// items in the hand slots to be visible.
+    pub show_arms: bool,
+    /// If the stone plate under the armor stand is hidden.
+    pub hide_base_plate: bool,
+    /// If the armor stand has no hitbox. Markers cannot be interacted with,
+    /// which is useful for holograms.
+    pub marker: bool,
+}
+
+impl ArmorStandStyle {
+    /// The style of a statue holding items.
+    pub const STATUE: Self = Self {
+        small: false,
+        show_arms: true,
+        hide_base_plate: true,
+        marker: false,
+    };
+
+    fn apply(&self, mc_entity: &mut McEntity) {
+        let TrackedData::ArmorStand(data) = mc_entity.data_mut() else {
+            return;
+        };
+
+        data.set_small(self.small);
+        data.set_show_arms(self.show_arms);
+        data.set_hide_base_plate(self.hide_base_plate);
+        data.set_marker(self.marker);
+    }
+}
+
+/// A [`Component`] which keeps clients from taking or placing items on the
+/// armor stand [`McEntity`] on the same entity, like the `DisabledSlots` tag
+/// of vanilla armor stands with every slot disabled.
+#[derive(Component, Copy, Clone, PartialEq, Eq, Default, Debug)]
+pub struct LockedArmorStand;
+
+/// An event sent when a client swaps the item in its hand with an item of an
+/// armor stand.
+#[derive(Clone, PartialEq, Debug)]
+pub struct ArmorStandManipulateEvent {
+    pub client: Entity,
+    pub armor_stand: Entity,
+    pub slot: EquipmentSlot,
+    /// The item the client put on the armor stand.
+    pub placed: Option<ItemStack>,
+    /// The item the client took from the armor stand.
+    pub taken: Option<ItemStack>,
+}
+
+/// Returns the slot an item is put in when it is used on an armor stand.
+fn preferred_slot(item: ItemKind) -> EquipmentSlot {
+    if let Some(slot) = armor_slot(item).and_then(EquipmentSlot::from_armor_slot) {
+        slot
+    } else if item == ItemKind::Shield {
+        EquipmentSlot::OffHand
+    } else {
+        EquipmentSlot::MainHand
+    }
+}
+
+/// Returns the slot of an armor stand clicked with an empty hand, `y` blocks
+/// above the feet of the stand.
+fn clicked_slot(equipments: &Equipments, y: f32, small: bool) -> EquipmentSlot {
+    let has = |slot| equipments.get(slot).is_some();
+
+    let (y, feet_top, chest_bottom, chest_top, legs_top) = if small {
+        (y * 2.0, 0.9, 1.2, 1.9, 1.4)
+    } else {
+        (y, 0.55, 0.9, 1.6, 1.2)
+    };
+
+    if (0.1..feet_top).contains(&y) && has(EquipmentSlot::Feet) {
+        EquipmentSlot::Feet
+    } else if (chest_bottom..chest_top).contains(&y) && has(EquipmentSlot::Chest) {
+        EquipmentSlot::Chest
+    } else if (0.4..legs_top).contains(&y) && has(EquipmentSlot::Legs) {
+        EquipmentSlot::Legs
+    } else if y >= 1.6 && has(EquipmentSlot::Head) {
+        EquipmentSlot::Head
+    } else if !has(EquipmentSlot::MainHand) && has(EquipmentSlot::OffHand) {
+        EquipmentSlot::OffHand
+    } else {
+        EquipmentSlot::MainHand
+    }
+}
+
+pub(crate) fn manipulate_armor_stands(
+    mut commands: Commands,
+    mut clients: Query<(&Client, &mut Inventory)>,
+    mut stands: Query<
+        (&McEntity, Option<&mut Equipments>),
+        (
+            Without<Client>,
+            Without<LockedArmorStand>,
+            Without<Despawned>,
+        ),
+    >,
+    mut interact: EventReader<InteractEntityEvent>,
+    mut manipulate: EventWriter<ArmorStandManipulateEvent>,
+) {
+    for event in interact.iter() {
+        // Vanilla armor stands only respond to interactions with the clicked
+        // position.
+        let Some(position) = event.position else {
+            continue;
+        };
+
+        let Ok((client, mut inventory)) = clients.get_mut(event.client) else {
+            continue;
+        };
+
+        let Ok((mc_entity, equipments)) = stands.get_mut(event.target) else {
+            continue;
+        };
+
+        let TrackedData::ArmorStand(data) = mc_entity.data() else {
+            continue;
+        };
+
+        if data.get_marker()
+            || matches!(
+                client.game_mode(),
+                GameMode::Adventure | GameMode::Spectator
+            )
+        {
+            continue;
+        }
+
+        let empty = Equipments::new();
+        let current = equipments.as_deref().unwrap_or(&empty);
+
+        let held_slot = hand_slot(client, event.hand);
+        let held = inventory.slot(held_slot).cloned();
+
+        let slot = match &held {
+            Some(stack) if stack.item == ItemKind::NameTag => continue,
+            Some(stack) => {
+                let slot = preferred_slot(stack.item);
+
+                if matches!(slot, EquipmentSlot::MainHand | EquipmentSlot::OffHand)
+                    && !data.get_show_arms()
+                {
+                    continue;
+                }
+
+                slot
+            }
+            None => clicked_slot(current, position.y, data.get_small()),
+        };
+
+        let on_stand = current.get(slot).cloned();
+
+        let (placed, taken) = match held {
+            None if on_stand.is_none() => continue,
+            Some(mut stack) if client.game_mode() == GameMode::Creative && on_stand.is_none() => {
+                stack.set_count(1);
+                (Some(stack), None)
+            }
+            Some(mut stack) if stack.count() > 1 => {
+                if on_stand.is_some() {
+                    continue;
+                }
+
+                let mut rest = stack.clone();
+                rest.set_count(stack.count() - 1);
+                inventory.replace_slot(held_slot, rest);
+
+                stack.set_count(1);
+                (Some(stack), None)
+            }
+            held => {
+                inventory.replace_slot(held_slot, on_stand.clone());
+                (held, on_stand)
+            }
+        };
+
+        match equipments {
+            Some(mut equipments) => equipments.set(slot, placed.clone()),
+            None => {
+                commands
+                    .entity(event.target)
+                    .insert(Equipments::new().with(slot, placed.clone()));
+            }
+        }
+
+        manipulate.send(ArmorStandManipulateEvent {
+            client: event.client,
+            armor_stand: event.target,
+            slot,
+            placed,
+            taken,
+        });
+    }
+}
+
+pub(crate) fn update_armor_stand_poses(
+    mut stands: Query<
+        (&ArmorStandPose, &mut McEntity),
+        Or<(Changed<ArmorStandPose>, Added<McEntity>)>,
+    >,
+) {
+    for (pose, mut mc_entity) in &mut stands {
+        pose.apply(&mut mc_entity);
+    }
+}
+
+pub(crate) fn update_armor_stand_styles(
+    mut stands: Query<
+        (&ArmorStandStyle, &mut McEntity),
+        Or<(Changed<ArmorStandStyle>, Added<McEntity>)>,
+    >,
+) {
+    for (style, mut mc_entity) in &mut stands {
+        style.apply(&mut mc_entity);
+    }
+}
+
+pub(crate) fn reset_armor_stand_poses(
+    removed: RemovedComponents<ArmorStandPose>,
+    mut stands: Query<&mut McEntity, Without<ArmorStandPose>>,
+) {
+    for entity in removed.iter() {
+        if let Ok(mut mc_entity) = stands.get_mut(entity) {
+            ArmorStandPose::DEFAULT.apply(&mut mc_entity);
+        }
+    }
+}
+
+pub(crate) fn reset_armor_stand_styles(
+    removed: RemovedComponents<ArmorStandStyle>,
+    mut stands: Query<&mut McEntity, Without<ArmorStandStyle>>,
+) {
+    for entity in removed.iter() {
+        if let Ok(mut mc_entity) = stands.get_mut(entity) {
+            ArmorStandStyle::default().apply(&mut mc_entity);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy_app::App;
+    use valence_protocol::packets::c2s::play::Interact;
+    use valence_protocol::types::{EntityInteraction, Hand};
+    use valence_protocol::VarInt;
+
+    use super::*;
+    use crate::entity::EntityKind;
+    use crate::unit_test::util::scenario_single_client;
+
+    #[test]
+    fn statues_are_posed_and_equipped() {
+        let mut app = App::new();
+        let (client_ent, _) = scenario_single_client(&mut app);
+        let instance_ent = app.world.get::<Client>(client_ent).unwrap().instance();
+
+        let sword = ItemStack::new(ItemKind::DiamondSword, 1, None);
+
+        let stand = app
+            .world
+            .spawn((
+                McEntity::new(EntityKind::ArmorStand, instance_ent),
+                ArmorStandPose::DEFAULT.with_right_arm(-90.0, 0.0, 0.0),
+                ArmorStandStyle::STATUE,
+                Equipments::new().with(EquipmentSlot::MainHand, sword.clone()),
+            ))
+            .id();
+
+        app.update();
+
+        let mc_entity = app.world.get::<McEntity>(stand).unwrap();
+        let TrackedData::ArmorStand(data) = mc_entity.data() else {
+            panic!("not an armor stand")
+        };
+        assert_eq!(
+            data.get_tracker_right_arm_rotation(),
+            angle(-90.0, 0.0, 0.0)
+        );
+        assert!(data.get_show_arms());
+        assert_eq!(mc_entity.equipment(EquipmentSlot::MainHand), Some(&sword));
+
+        app.world
+            .entity_mut(stand)
+            .remove::<ArmorStandPose>()
+            .remove::<ArmorStandStyle>();
+        app.update();
+
+        let mc_entity = app.world.get::<McEntity>(stand).unwrap();
+        let TrackedData::ArmorStand(data) = mc_entity.data() else {
+            panic!("not an armor stand")
+        };
+        assert_eq!(
+            data.get_tracker_right_arm_rotation(),
+            ArmorStandPose::DEFAULT.right_arm
+        );
+        assert!(!data.get_show_arms());
+    }
+
+    #[test]
+    fn armor_is_swapped_with_stands() {
+        let mut app = App::new();
+        let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+        let instance_ent = app.world.get::<Client>(client_ent).unwrap().instance();
+
+        let held_slot = app
+            .world
+            .get::<Client>(client_ent)
+            .unwrap()
+            .held_item_slot();
+
+        let helmet = ItemStack::new(ItemKind::IronHelmet, 1, None);
+
+        app.world
+            .get_mut::<Inventory>(client_ent)
+            .unwrap()
+            .replace_slot(held_slot, helmet.clone());
+
+        let stand = app
+            .world
+            .spawn(McEntity::new(EntityKind::ArmorStand, instance_ent))
+            .id();
+
+        app.update();
+
+        let entity_id = app.world.get::<McEntity>(stand).unwrap().protocol_id();
+        let interact_at = |y: f32| Interact {
+            entity_id: VarInt(entity_id),
+            interact: EntityInteraction::InteractAt {
+                target: [0.0, y, 0.0],
+                hand: Hand::Main,
+            },
+            sneaking: false,
+        };
+
+        client_helper.send(&interact_at(0.2));
+        app.update();
+
+        let equipments = app.world.get::<Equipments>(stand).unwrap();
+        assert_eq!(equipments.get(EquipmentSlot::Head), Some(&helmet));
+
+        let inventory = app.world.get::<Inventory>(client_ent).unwrap();
+        assert_eq!(inventory.slot(held_slot), None);
+
+        // Clicking on the feet with an empty hand takes nothing.
+        client_helper.send(&interact_at(0.2));
+        app.update();
+
+        assert!(app
+            .world
+            .get::<Inventory>(client_ent)
+            .unwrap()
+            .slot(held_slot)
+            .is_none());
+
+        client_helper.send(&interact_at(1.8));
+        app.update();
+
+        let equipments = app.world.get::<Equipments>(stand).unwrap();
+        assert_eq!(equipments.get(EquipmentSlot::Head), None);
+
+        let inventory = app.world.get::<Inventory>(client_ent).unwrap();
+        assert_eq!(inventory.slot(held_slot), Some(&helmet));
+
+        let events = app.world.resource::<Events<ArmorStandManipulateEvent>>();
+        let taken: Vec<_> = events
+            .get_reader()
+            .iter(events)
+            .map(|event| event.taken.clone())
+            .collect();
+        assert_eq!(taken, [Some(helmet)]);
+    }
+}
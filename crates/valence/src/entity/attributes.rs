@@ -0,0 +1,242 @@
+//! Attributes of entities and armor attributes derived from equipment.
+//!
+//! The [`EntityAttributes`] component contains the attribute values of an
+//! entity. Clients are sent their own attributes whenever they change.
+//!
+//! Equipment is purely cosmetic by default. Inserting [`ArmorAttributes`]
+//! opts an entity in to having its armor, armor toughness and knockback
+//! resistance computed from the armor in its
+//! [`Equipments`](super::equipment::Equipments), like in vanilla.
+
+use bevy_ecs::prelude::*;
+use valence_protocol::packets::s2c::play::UpdateAttributes;
+use valence_protocol::types::AttributeProperty;
+use valence_protocol::{Ident, ItemKind, VarInt};
+
+use crate::client::Client;
+use crate::entity::equipment::Equipments;
+
+/// An attribute of an entity.
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub enum Attribute {
+    /// The number of armor points, displayed as half chestplates above the
+    /// hotbar.
+    Armor,
+    /// Reduces the damage absorbed by armor against strong attacks.
+    ArmorToughness,
+    /// The chance from 0 to 1 of resisting knockback.
+    KnockbackResistance,
+}
+
+impl Attribute {
+    pub const ALL: [Self; 3] = [Self::Armor, Self::ArmorToughness, Self::KnockbackResistance];
+
+    /// Returns the resource location of the attribute.
+    pub fn key(self) -> &'static str {
+        match self {
+            Attribute::Armor => "minecraft:generic.armor",
+            Attribute::ArmorToughness => "minecraft:generic.armor_toughness",
+            Attribute::KnockbackResistance => "minecraft:generic.knockback_resistance",
+        }
+    }
+
+    /// Returns the largest value of the attribute accepted by the client.
+    pub fn max_value(self) -> f64 {
+        match self {
+            Attribute::Armor => 30.0,
+            Attribute::ArmorToughness => 20.0,
+            Attribute::KnockbackResistance => 1.0,
+        }
+    }
+}
+
+/// A [`Component`] containing the attribute values of an entity. Every
+/// attribute is zero by default.
+///
+/// If there is a [`Client`] on the same entity, the client is sent the
+/// attributes whenever they change.
+#[derive(Component, Copy, Clone, PartialEq, Default, Debug)]
+pub struct EntityAttributes {
+    values: [f64; 3],
+}
+
+impl EntityAttributes {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn get(&self, attr: Attribute) -> f64 {
+        self.values[attr as usize]
+    }
+
+    /// Sets the value of the attribute, which is clamped between zero and
+    /// [`Attribute::max_value`].
+    pub fn set(&mut self, attr: Attribute, value: f64) {
+        self.values[attr as usize] = value.clamp(0.0, attr.max_value());
+    }
+
+    #[must_use]
+    pub fn with(mut self, attr: Attribute, value: f64) -> Self {
+        self.set(attr, value);
+        self
+    }
+}
+
+/// A [`Component`] which sets the armor attributes in the
+/// [`EntityAttributes`] on the same entity from the armor in its
+/// [`Equipments`]. The [`EntityAttributes`] are inserted if missing.
+#[derive(Component, Copy, Clone, PartialEq, Eq, Default, Debug)]
+pub struct ArmorAttributes;
+
+/// The attribute values provided by a piece of armor.
+#[derive(Copy, Clone, PartialEq, Default, Debug)]
+pub struct ArmorValues {
+    pub armor: f64,
+    pub toughness: f64,
+    pub knockback_resistance: f64,
+}
+
+impl ArmorValues {
+    const fn new(armor: f64, toughness: f64, knockback_resistance: f64) -> Self {
+        Self {
+            armor,
+            toughness,
+            knockback_resistance,
+        }
+    }
+
+    /// Returns the values of the armor item, or `None` if the item is not
+    /// armor.
+    pub fn of(kind: ItemKind) -> Option<Self> {
+        Some(match kind {
+            ItemKind::LeatherHelmet => Self::new(1.0, 0.0, 0.0),
+            ItemKind::LeatherChestplate => Self::new(3.0, 0.0, 0.0),
+            ItemKind::LeatherLeggings => Self::new(2.0, 0.0, 0.0),
+            ItemKind::LeatherBoots => Self::new(1.0, 0.0, 0.0),
+            ItemKind::ChainmailHelmet => Self::new(2.0, 0.0, 0.0),
+            ItemKind::ChainmailChestplate => Self::new(5.0, 0.0, 0.0),
+            ItemKind::ChainmailLeggings => Self::new(4.0, 0.0, 0.0),
+            ItemKind::ChainmailBoots => Self::new(1.0, 0.0, 0.0),
+            ItemKind::IronHelmet => Self::new(2.0, 0.0, 0.0),
+            ItemKind::IronChestplate => Self::new(6.0, 0.0, 0.0),
+            ItemKind::IronLeggings => Self::new(5.0, 0.0, 0.0),
+            ItemKind::IronBoots => Self::new(2.0, 0.0, 0.0),
+            ItemKind::GoldenHelmet => Self::new(2.0, 0.0, 0.0),
+            ItemKind::GoldenChestplate => Self::new(5.0, 0.0, 0.0),
+            ItemKind::GoldenLeggings => Self::new(3.0, 0.0, 0.0),
+            ItemKind::GoldenBoots => Self::new(1.0, 0.0, 0.0),
+            ItemKind::DiamondHelmet => Self::new(3.0, 2.0, 0.0),
+            ItemKind::DiamondChestplate => Self::new(8.0, 2.0, 0.0),
+            ItemKind::DiamondLeggings => Self::new(6.0, 2.0, 0.0),
+            ItemKind::DiamondBoots => Self::new(3.0, 2.0, 0.0),
+            ItemKind::NetheriteHelmet => Self::new(3.0, 3.0, 0.1),
+            ItemKind::NetheriteChestplate => Self::new(8.0, 3.0, 0.1),
+            ItemKind::NetheriteLeggings => Self::new(6.0, 3.0, 0.1),
+            ItemKind::NetheriteBoots => Self::new(3.0, 3.0, 0.1),
+            ItemKind::TurtleHelmet => Self::new(2.0, 0.0, 0.0),
+            _ => return None,
+        })
+    }
+}
+
+/// Returns the sum of the values of the armor in the armor slots.
+fn total_armor_values(equipments: &Equipments) -> ArmorValues {
+    let mut total = ArmorValues::default();
+
+    for (_, stack) in equipments.iter().filter(|(slot, _)| slot.is_armor()) {
+        if let Some(values) = ArmorValues::of(stack.item) {
+            total.armor += values.armor;
+            total.toughness += values.toughness;
+            total.knockback_resistance += values.knockback_resistance;
+        }
+    }
+
+    total
+}
+
+pub(crate) fn update_armor_attributes(
+    mut commands: Commands,
+    mut entities: Query<
+        (Entity, &Equipments, Option<&mut EntityAttributes>),
+        (
+            With<ArmorAttributes>,
+            Or<(Changed<Equipments>, Added<ArmorAttributes>)>,
+        ),
+    >,
+) {
+    for (entity, equipments, attrs) in &mut entities {
+        let total = total_armor_values(equipments);
+
+        let mut new_attrs = attrs.as_deref().copied().unwrap_or_default();
+        new_attrs.set(Attribute::Armor, total.armor);
+        new_attrs.set(Attribute::ArmorToughness, total.toughness);
+        new_attrs.set(Attribute::KnockbackResistance, total.knockback_resistance);
+
+        match attrs {
+            Some(mut attrs) => {
+                if *attrs != new_attrs {
+                    *attrs = new_attrs;
+                }
+            }
+            None => {
+                commands.entity(entity).insert(new_attrs);
+            }
+        }
+    }
+}
+
+pub(crate) fn send_attributes_to_clients(
+    mut clients: Query<(&mut Client, &EntityAttributes), Changed<EntityAttributes>>,
+) {
+    for (mut client, attrs) in &mut clients {
+        client.write_packet(&UpdateAttributes {
+            entity_id: VarInt(0),
+            properties: Attribute::ALL
+                .into_iter()
+                .map(|attr| AttributeProperty {
+                    key: Ident::new(attr.key()).unwrap(),
+                    value: attrs.get(attr),
+                    modifiers: vec![],
+                })
+                .collect(),
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy_app::App;
+    use valence_protocol::packets::S2cPlayPacket;
+    use valence_protocol::ItemStack;
+
+    use super::*;
+    use crate::assert_packet_count;
+    use crate::inventory::{Inventory, CHESTPLATE_SLOT};
+    use crate::unit_test::util::scenario_single_client;
+
+    #[test]
+    fn armor_sets_attributes() {
+        let mut app = App::new();
+        let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+
+        app.world.entity_mut(client_ent).insert(ArmorAttributes);
+        app.world
+            .get_mut::<Inventory>(client_ent)
+            .unwrap()
+            .replace_slot(
+                CHESTPLATE_SLOT,
+                ItemStack::new(ItemKind::NetheriteChestplate, 1, None),
+            );
+
+        app.update();
+        app.update();
+
+        let attrs = app.world.get::<EntityAttributes>(client_ent).unwrap();
+        assert_eq!(attrs.get(Attribute::Armor), 8.0);
+        assert_eq!(attrs.get(Attribute::ArmorToughness), 3.0);
+        assert_eq!(attrs.get(Attribute::KnockbackResistance), 0.1);
+
+        let sent_packets = client_helper.collect_sent().unwrap();
+        assert_packet_count!(sent_packets, 1, S2cPlayPacket::UpdateAttributes(_));
+    }
+}
@@ -0,0 +1,517 @@
+//! Breeding animals and growing up babies.
+//!
+//! Entities with a [`Breedable`] component enter love mode when a client
+//! feeds them their breeding item, such as wheat for cows. Two animals of the
+//! same kind in love near each other breed and spawn a baby, after which both
+//! parents have to wait for a cooldown before they can breed again. Babies
+//! grow up over time, and feeding them makes them grow up faster.
+//!
+//! Animals look for a partner on the tick after they are fed. Servers can
+//! restrict breeding by handling the [`EnterLoveMode`] event and calling
+//! [`Breedable::stop_love`] before then, and reward players with the
+//! [`AnimalBred`] event.
+
+use bevy_ecs::prelude::*;
+use valence_protocol::types::{EntityInteraction, GameMode};
+use valence_protocol::ItemKind;
+
+use crate::budget::{SheddableWork, TickBudget};
+use crate::client::event::InteractWithEntity;
+use crate::client::Client;
+use crate::entity::horse::Horse;
+use crate::entity::pet::Owner;
+use crate::entity::{EntityKind, EntityStatus, McEntity, McEntityManager, TrackedData};
+use crate::inventory::{consume_held_item, held_item, Inventory};
+use crate::tick_rate::SkippedInstances;
+use crate::Despawned;
+
+/// The age of a newborn baby. Babies grow up once their age reaches zero.
+pub const BABY_AGE: i32 = -24000;
+
+/// The number of ticks parents have to wait before breeding again.
+pub const BREEDING_COOLDOWN: i32 = 6000;
+
+/// The number of ticks an animal stays in love after being fed.
+const LOVE_TICKS: i32 = 600;
+
+/// The maximum distance between two animals that breed.
+const BREEDING_RANGE: f64 = 8.0;
+
+/// A [`Component`] for animals that can breed. The [`McEntity`] on the same
+/// entity should be an animal with a baby variant.
+///
+/// The age of an animal works like in vanilla: negative ages count up to zero
+/// while the animal is a baby, and positive ages count down to zero while
+/// the animal can't breed.
+#[derive(Component, Clone, PartialEq, Eq, Debug)]
+pub struct Breedable {
+    age: i32,
+    love_ticks: i32,
+    /// The client that fed the animal.
+    lover: Option<Entity>,
+}
+
+impl Breedable {
+    /// Returns a new adult animal that is ready to breed.
+    pub fn new() -> Self {
+        Self {
+            age: 0,
+            love_ticks: 0,
+            lover: None,
+        }
+    }
+
+    /// Returns a newborn baby.
+    pub fn baby() -> Self {
+        Self {
+            age: BABY_AGE,
+            ..Self::new()
+        }
+    }
+
+    pub fn age(&self) -> i32 {
+        self.age
+    }
+
+    pub fn set_age(&mut self, age: i32) {
+        self.age = age;
+
+        if self.is_baby() {
+            self.stop_love();
+        }
+    }
+
+    pub fn is_baby(&self) -> bool {
+        self.age < 0
+    }
+
+    /// Returns if the animal is an adult that isn't waiting for its breeding
+    /// cooldown.
+    pub fn can_breed(&self) -> bool {
+        self.age == 0
+    }
+
+    pub fn in_love(&self) -> bool {
+        self.love_ticks > 0
+    }
+
+    /// Returns the client that made the animal fall in love by feeding it.
+    pub fn lover(&self) -> Option<Entity> {
+        self.lover
+    }
+
+    /// Makes the animal fall in love, as if it was fed by `lover`.
+    pub fn start_love(&mut self, lover: Option<Entity>) {
+        self.love_ticks = LOVE_TICKS;
+        self.lover = lover;
+    }
+
+    pub fn stop_love(&mut self) {
+        self.love_ticks = 0;
+        self.lover = None;
+    }
+
+    /// Makes a baby grow up by the given number of ticks.
+    pub fn age_up(&mut self, ticks: i32) {
+        if self.is_baby() {
+            self.age = (self.age + ticks).min(0);
+        }
+    }
+}
+
+impl Default for Breedable {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// An event sent when a client feeds an animal and makes it fall in love.
+#[derive(Clone, Debug)]
+pub struct EnterLoveMode {
+    pub entity: Entity,
+    pub client: Entity,
+}
+
+/// An event sent when two animals breed.
+#[derive(Clone, Debug)]
+pub struct AnimalBred {
+    pub parents: [Entity; 2],
+    pub baby: Entity,
+    /// The client that fed one of the parents, if any.
+    pub client: Option<Entity>,
+}
+
+/// Returns if animals of the given kind are fed the item to breed.
+pub fn is_breeding_item(kind: EntityKind, item: ItemKind) -> bool {
+    match kind {
+        EntityKind::Cow | EntityKind::Mooshroom | EntityKind::Sheep | EntityKind::Goat => {
+            item == ItemKind::Wheat
+        }
+        EntityKind::Pig => matches!(
+            item,
+            ItemKind::Carrot | ItemKind::Potato | ItemKind::Beetroot
+        ),
+        EntityKind::Chicken => matches!(
+            item,
+            ItemKind::WheatSeeds
+                | ItemKind::MelonSeeds
+                | ItemKind::PumpkinSeeds
+                | ItemKind::BeetrootSeeds
+        ),
+        EntityKind::Rabbit => matches!(
+            item,
+            ItemKind::Carrot | ItemKind::GoldenCarrot | ItemKind::Dandelion
+        ),
+        EntityKind::Wolf => matches!(
+            item,
+            ItemKind::Beef
+                | ItemKind::CookedBeef
+                | ItemKind::Porkchop
+                | ItemKind::CookedPorkchop
+                | ItemKind::Chicken
+                | ItemKind::CookedChicken
+                | ItemKind::Mutton
+                | ItemKind::CookedMutton
+                | ItemKind::Rabbit
+                | ItemKind::CookedRabbit
+                | ItemKind::RottenFlesh
+        ),
+        EntityKind::Cat | EntityKind::Ocelot => matches!(item, ItemKind::Cod | ItemKind::Salmon),
+        EntityKind::Horse | EntityKind::Donkey => matches!(
+            item,
+            ItemKind::GoldenApple | ItemKind::EnchantedGoldenApple | ItemKind::GoldenCarrot
+        ),
+        EntityKind::Llama | EntityKind::TraderLlama => item == ItemKind::HayBlock,
+        EntityKind::Fox => matches!(item, ItemKind::SweetBerries | ItemKind::GlowBerries),
+        EntityKind::Panda => item == ItemKind::Bamboo,
+        EntityKind::Turtle => item == ItemKind::Seagrass,
+        EntityKind::Hoglin => item == ItemKind::CrimsonFungus,
+        EntityKind::Strider => item == ItemKind::WarpedFungus,
+        EntityKind::Axolotl => item == ItemKind::TropicalFishBucket,
+        EntityKind::Frog => item == ItemKind::SlimeBall,
+        EntityKind::Camel => item == ItemKind::Cactus,
+        _ => false,
+    }
+}
+
+/// Returns if animals of the given kind must be tamed to breed.
+fn needs_taming(kind: EntityKind) -> bool {
+    matches!(
+        kind,
+        EntityKind::Wolf
+            | EntityKind::Cat
+            | EntityKind::Horse
+            | EntityKind::Donkey
+            | EntityKind::Llama
+            | EntityKind::TraderLlama
+    )
+}
+
+type FedAnimal<'a> = (
+    &'a mut Breedable,
+    &'a mut McEntity,
+    Option<&'a Owner>,
+    Option<&'a Horse>,
+);
+
+pub(crate) fn feed_breedables(
+    manager: Res<McEntityManager>,
+    mut clients: Query<(&Client, &mut Inventory)>,
+    mut animals: Query<FedAnimal, Without<Despawned>>,
+    mut interactions: EventReader<InteractWithEntity>,
+    mut love: EventWriter<EnterLoveMode>,
+) {
+    for event in interactions.iter() {
+        let EntityInteraction::Interact(hand) = event.interact else {
+            continue
+        };
+
+        let Some(entity) = manager.get_with_protocol_id(event.entity_id) else {
+            continue
+        };
+
+        let Ok((mut breedable, mut mc_entity, owner, horse)) = animals.get_mut(entity) else {
+            continue
+        };
+
+        let Ok((client, mut inventory)) = clients.get_mut(event.client) else {
+            continue
+        };
+
+        if client.game_mode() == GameMode::Spectator {
+            continue;
+        }
+
+        let kind = mc_entity.kind();
+
+        if !held_item(client, &inventory, hand)
+            .map_or(false, |stack| is_breeding_item(kind, stack.item))
+        {
+            continue;
+        }
+
+        if breedable.is_baby() {
+            // Feeding babies makes them grow up by a tenth of the time they
+            // have left.
+            consume_held_item(client, &mut inventory, hand);
+            let ticks = -breedable.age / 10;
+            breedable.age_up(ticks);
+            continue;
+        }
+
+        let tamed = owner.is_some() || horse.map_or(false, |horse| horse.is_tamed());
+
+        if !breedable.can_breed() || breedable.in_love() || (needs_taming(kind) && !tamed) {
+            continue;
+        }
+
+        consume_held_item(client, &mut inventory, hand);
+        breedable.start_love(Some(event.client));
+        mc_entity.trigger_status(EntityStatus::AddBreedingParticles);
+        love.send(EnterLoveMode {
+            entity,
+            client: event.client,
+        });
+    }
+}
+
+pub(crate) fn breed_animals(
+    mut commands: Commands,
+    mut animals: Query<(Entity, &mut Breedable, &McEntity), Without<Despawned>>,
+    mut bred: EventWriter<AnimalBred>,
+    budget: Res<TickBudget>,
+) {
+    if budget.is_shedding(SheddableWork::EntityAi) {
+        return;
+    }
+
+    let mut in_love: Vec<_> = animals
+        .iter()
+        .filter(|(_, breedable, _)| breedable.in_love() && breedable.can_breed())
+        .map(|(entity, _, mc_entity)| {
+            (
+                entity,
+                mc_entity.kind(),
+                mc_entity.instance(),
+                mc_entity.position(),
+            )
+        })
+        .collect();
+
+    while let Some((entity, kind, instance, position)) = in_love.pop() {
+        let Some(idx) = in_love
+            .iter()
+            .position(|&(_, other_kind, other_instance, other_pos)| {
+                other_kind == kind
+                    && other_instance == instance
+                    && other_pos.distance(position) <= BREEDING_RANGE
+            })
+        else {
+            continue
+        };
+
+        let (partner, _, _, partner_pos) = in_love.swap_remove(idx);
+
+        let mut client = None;
+
+        for parent in [entity, partner] {
+            if let Ok((_, mut breedable, _)) = animals.get_mut(parent) {
+                client = client.or(breedable.lover);
+                breedable.stop_love();
+                breedable.age = BREEDING_COOLDOWN;
+            }
+        }
+
+        let mut mc_entity = McEntity::new(kind, instance);
+        mc_entity.set_position(position.lerp(partner_pos, 0.5));
+        set_baby_data(mc_entity.data_mut(), true);
+
+        let baby = commands.spawn((mc_entity, Breedable::baby())).id();
+
+        bred.send(AnimalBred {
+            parents: [entity, partner],
+            baby,
+            client,
+        });
+    }
+}
+
+pub(crate) fn tick_breedables(
+    mut animals: Query<(&mut Breedable, &McEntity)>,
+    skipped: Res<SkippedInstances>,
+) {
+    for (mut breedable, mc_entity) in &mut animals {
+        if skipped.contains(mc_entity.instance()) {
+            continue;
+        }
+
+        let was_baby = breedable.is_baby();
+
+        // Counting down is done without change detection, so that only babies
+        // growing up update the tracked data.
+        let ticked = breedable.bypass_change_detection();
+
+        if ticked.love_ticks > 0 {
+            ticked.love_ticks -= 1;
+
+            if ticked.love_ticks == 0 {
+                ticked.lover = None;
+            }
+        }
+
+        ticked.age -= ticked.age.signum();
+
+        if was_baby && !breedable.is_baby() {
+            breedable.set_changed();
+        }
+    }
+}
+
+pub(crate) fn update_baby_data(
+    mut animals: Query<(&Breedable, &mut McEntity), Changed<Breedable>>,
+) {
+    for (breedable, mut mc_entity) in &mut animals {
+        set_baby_data(mc_entity.data_mut(), breedable.is_baby());
+    }
+}
+
+/// Sets the baby flag of animals with a baby variant.
+fn set_baby_data(data: &mut TrackedData, baby: bool) {
+    macro_rules! set_child {
+        ($($kind:ident),* $(,)?) => {
+            match data {
+                $(TrackedData::$kind(data) => data.set_child(baby),)*
+                _ => {}
+            }
+        };
+    }
+
+    set_child!(
+        Axolotl,
+        Bee,
+        Camel,
+        Cat,
+        Chicken,
+        Cow,
+        Donkey,
+        Fox,
+        Frog,
+        Goat,
+        Hoglin,
+        Horse,
+        Llama,
+        Mooshroom,
+        Mule,
+        Ocelot,
+        Panda,
+        Parrot,
+        Pig,
+        PolarBear,
+        Rabbit,
+        Sheep,
+        SkeletonHorse,
+        Strider,
+        TraderLlama,
+        Turtle,
+        Villager,
+        WanderingTrader,
+        Wolf,
+        ZombieHorse,
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy_app::App;
+    use valence_protocol::packets::c2s::play::{Interact, SetHeldItemC2s};
+    use valence_protocol::types::Hand;
+    use valence_protocol::ItemStack;
+
+    use super::*;
+    use crate::unit_test::util::scenario_single_client;
+
+    #[test]
+    fn fed_animals_breed() {
+        let mut app = App::new();
+        let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+        let instance_ent = app.world.get::<Client>(client_ent).unwrap().instance();
+
+        let cows: Vec<_> = (0..2)
+            .map(|_| {
+                app.world
+                    .spawn((
+                        McEntity::new(EntityKind::Cow, instance_ent),
+                        Breedable::new(),
+                    ))
+                    .id()
+            })
+            .collect();
+
+        let mut inventory = app.world.get_mut::<Inventory>(client_ent).unwrap();
+        inventory.replace_slot(36, ItemStack::new(ItemKind::Wheat, 3, None));
+
+        client_helper.send(&SetHeldItemC2s { slot: 0 });
+
+        app.update();
+
+        for &cow in &cows {
+            let cow_id = app.world.get::<McEntity>(cow).unwrap().protocol_id();
+
+            client_helper.send(&Interact {
+                entity_id: cow_id.into(),
+                interact: EntityInteraction::Interact(Hand::Main),
+                sneaking: false,
+            });
+        }
+
+        app.update();
+
+        for &cow in &cows {
+            assert!(app.world.get::<Breedable>(cow).unwrap().in_love());
+        }
+
+        // The cows find each other on the next tick.
+        app.update();
+
+        for &cow in &cows {
+            let breedable = app.world.get::<Breedable>(cow).unwrap();
+            assert!(!breedable.in_love());
+            assert!(!breedable.can_breed());
+        }
+
+        let bred = app.world.resource::<Events<AnimalBred>>();
+        let event = bred.iter_current_update_events().next().unwrap();
+        assert_eq!(event.client, Some(client_ent));
+
+        let baby = app.world.get::<McEntity>(event.baby).unwrap();
+        assert!(app.world.get::<Breedable>(event.baby).unwrap().is_baby());
+
+        match baby.data() {
+            TrackedData::Cow(data) => assert!(data.get_child()),
+            _ => unreachable!(),
+        }
+
+        assert_eq!(
+            app.world
+                .get::<Inventory>(client_ent)
+                .unwrap()
+                .slot(36)
+                .map(|stack| stack.count()),
+            Some(1)
+        );
+    }
+
+    #[test]
+    fn babies_grow_up() {
+        let mut breedable = Breedable::baby();
+        assert!(breedable.is_baby());
+        assert!(!breedable.can_breed());
+
+        breedable.age_up(-BABY_AGE / 10);
+        assert_eq!(breedable.age(), BABY_AGE * 9 / 10);
+
+        breedable.age_up(i32::MAX);
+        assert!(!breedable.is_baby());
+        assert!(breedable.can_breed());
+    }
+}
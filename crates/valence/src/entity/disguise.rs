@@ -0,0 +1,251 @@
+//! Showing entities as a different kind of entity.
+
+use bevy_ecs::prelude::*;
+use rustc_hash::FxHashSet;
+use valence_protocol::types::EntityInteraction;
+
+use crate::client::event::InteractWithEntity;
+use crate::damage::EntityDamage;
+use crate::entity::{EntityKind, McEntity, McEntityManager, TrackedData};
+use crate::math::Aabb;
+
+/// A [`Component`] that makes the [`McEntity`] on the same entity appear as a
+/// different kind of entity to some or all clients.
+///
+/// The real entity is unaffected. Only the spawn and tracked data packets sent
+/// to the viewers of the disguise are rewritten. Movement, events, and
+/// animations are shared with the real entity.
+///
+/// Disguising an entity as a player requires a
+/// [`PlayerListEntry`](crate::player_list::PlayerListEntry) with the UUID of
+/// the entity to exist. Otherwise, clients will not display it.
+#[derive(Component)]
+pub struct Disguise {
+    data: TrackedData,
+    viewers: DisguiseViewers,
+    /// Remove the disguise when the disguised entity attacks another entity.
+    pub remove_on_attack: bool,
+    /// Remove the disguise when the disguised entity takes damage.
+    pub remove_on_damage: bool,
+    needs_respawn: bool,
+}
+
+/// Determines which clients see a [`Disguise`].
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum DisguiseViewers {
+    /// Every client sees the disguise.
+    All,
+    /// Only the given clients see the disguise.
+    Only(FxHashSet<Entity>),
+    /// Every client except the given clients see the disguise.
+    AllExcept(FxHashSet<Entity>),
+}
+
+impl DisguiseViewers {
+    pub fn contains(&self, client: Entity) -> bool {
+        match self {
+            DisguiseViewers::All => true,
+            DisguiseViewers::Only(clients) => clients.contains(&client),
+            DisguiseViewers::AllExcept(clients) => !clients.contains(&client),
+        }
+    }
+}
+
+impl Disguise {
+    /// Creates a disguise of the given kind that is visible to every client.
+    pub fn new(kind: EntityKind) -> Self {
+        Self {
+            data: TrackedData::new(kind),
+            viewers: DisguiseViewers::All,
+            remove_on_attack: false,
+            remove_on_damage: false,
+            needs_respawn: true,
+        }
+    }
+
+    #[must_use]
+    pub fn with_viewers(mut self, viewers: DisguiseViewers) -> Self {
+        self.viewers = viewers;
+        self
+    }
+
+    #[must_use]
+    pub fn with_remove_on_attack(mut self, remove_on_attack: bool) -> Self {
+        self.remove_on_attack = remove_on_attack;
+        self
+    }
+
+    #[must_use]
+    pub fn with_remove_on_damage(mut self, remove_on_damage: bool) -> Self {
+        self.remove_on_damage = remove_on_damage;
+        self
+    }
+
+    /// Gets the kind of entity viewers see.
+    pub fn kind(&self) -> EntityKind {
+        self.data.kind()
+    }
+
+    /// Returns a reference to the tracked data viewers see.
+    pub fn data(&self) -> &TrackedData {
+        &self.data
+    }
+
+    /// Returns a mutable reference to the tracked data viewers see. Changes are
+    /// sent to the viewers at the end of the tick.
+    pub fn data_mut(&mut self) -> &mut TrackedData {
+        &mut self.data
+    }
+
+    pub fn viewers(&self) -> &DisguiseViewers {
+        &self.viewers
+    }
+
+    /// Changes which clients see the disguise. The entity is respawned for its
+    /// viewers to apply the change.
+    pub fn set_viewers(&mut self, viewers: DisguiseViewers) {
+        if self.viewers != viewers {
+            self.viewers = viewers;
+            self.needs_respawn = true;
+        }
+    }
+
+    /// Returns if the client with the given [`Entity`] sees the disguise.
+    pub fn is_visible_to(&self, client: Entity) -> bool {
+        self.viewers.contains(client)
+    }
+
+    /// Returns the hitbox of the disguised entity as seen by the viewers of
+    /// the disguise. Viewers interact with this hitbox instead of the hitbox of
+    /// the real entity.
+    pub fn hitbox(&self, entity: &McEntity) -> Aabb {
+        entity.hitbox_with_data(&self.data)
+    }
+}
+
+/// Respawns entities for their viewers when their disguise is added, removed,
+/// or has its viewers changed.
+pub(crate) fn respawn_disguised_entities(
+    mut disguised: Query<(&mut Disguise, &mut McEntity)>,
+    removed: RemovedComponents<Disguise>,
+    mut entities: Query<&mut McEntity, Without<Disguise>>,
+) {
+    for (mut disguise, mut entity) in &mut disguised {
+        if disguise.needs_respawn {
+            disguise.needs_respawn = false;
+            entity.respawn();
+        }
+    }
+
+    for entity in removed.iter() {
+        if let Ok(mut entity) = entities.get_mut(entity) {
+            entity.respawn();
+        }
+    }
+}
+
+/// Removes disguises that are configured to be removed by certain actions.
+pub(crate) fn remove_disguises_on_action(
+    mut commands: Commands,
+    disguises: Query<&Disguise>,
+    manager: Res<McEntityManager>,
+    mut interactions: EventReader<InteractWithEntity>,
+    mut damage: EventReader<EntityDamage>,
+) {
+    for event in interactions.iter() {
+        if event.interact != EntityInteraction::Attack
+            || manager.get_with_protocol_id(event.entity_id).is_none()
+        {
+            continue;
+        }
+
+        if disguises
+            .get(event.client)
+            .map_or(false, |disguise| disguise.remove_on_attack)
+        {
+            commands.entity(event.client).remove::<Disguise>();
+        }
+    }
+
+    for event in damage.iter() {
+        if disguises
+            .get(event.entity)
+            .map_or(false, |disguise| disguise.remove_on_damage)
+        {
+            commands.entity(event.entity).remove::<Disguise>();
+        }
+    }
+}
+
+/// Clears the modifications to the tracked data of disguises after they were
+/// sent to clients.
+pub(crate) fn clear_disguise_modifications(mut disguises: Query<&mut Disguise>) {
+    for mut disguise in &mut disguises {
+        disguise.data.clear_modifications();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy_app::App;
+    use valence_protocol::packets::S2cPlayPacket;
+
+    use super::*;
+    use crate::assert_packet_count;
+    use crate::client::Client;
+    use crate::instance::{Chunk, Instance};
+    use crate::unit_test::util::scenario_single_client;
+
+    fn spawned_kinds(packets: &[S2cPlayPacket]) -> Vec<i32> {
+        packets
+            .iter()
+            .filter_map(|packet| match packet {
+                S2cPlayPacket::SpawnEntity(p) => Some(p.kind.0),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn disguise_is_only_sent_to_viewers() -> anyhow::Result<()> {
+        let mut app = App::new();
+        let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+        let instance_ent = app.world.get::<Client>(client_ent).unwrap().instance();
+
+        app.world
+            .get_mut::<Instance>(instance_ent)
+            .unwrap()
+            .insert_chunk([0, 0], Chunk::default());
+
+        let pig = app
+            .world
+            .spawn((
+                McEntity::new(EntityKind::Pig, instance_ent),
+                Disguise::new(EntityKind::Cow),
+            ))
+            .id();
+
+        app.update();
+
+        let sent_packets = client_helper.collect_sent()?;
+        assert_eq!(spawned_kinds(&sent_packets), [EntityKind::Cow as i32]);
+
+        client_helper.clear_sent();
+
+        // Hide the disguise from the client.
+        app.world
+            .get_mut::<Disguise>(pig)
+            .unwrap()
+            .set_viewers(DisguiseViewers::AllExcept(
+                [client_ent].into_iter().collect(),
+            ));
+
+        app.update();
+
+        let sent_packets = client_helper.collect_sent()?;
+        assert_packet_count!(sent_packets, 1, S2cPlayPacket::RemoveEntities(_));
+        assert_eq!(spawned_kinds(&sent_packets), [EntityKind::Pig as i32]);
+
+        Ok(())
+    }
+}
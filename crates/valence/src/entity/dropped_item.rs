@@ -0,0 +1,564 @@
+//! Items lying in the world.
+//!
+//! Spawning an [`ItemEntityBundle`] drops a stack of items into an instance.
+//! Like in vanilla, the items fall and slide on the ground, merge with nearby
+//! items of the same kind, and are picked up by clients walking into them
+//! once their pickup delay is over. Every pickup plays the collect animation
+//! and sends an [`ItemPickupEvent`]. Items which lie around for
+//! [`DESPAWN_TICKS`] ticks are despawned.
+
+use bevy_ecs::prelude::*;
+use glam::DVec3;
+use valence_protocol::packets::s2c::play::PickupItem;
+use valence_protocol::types::GameMode;
+use valence_protocol::{ItemStack, VarInt};
+
+use crate::client::Client;
+use crate::crafting::can_stack;
+use crate::entity::data::TrackedData;
+use crate::entity::{EntityKind, McEntity};
+use crate::instance::Instance;
+use crate::inventory::Inventory;
+use crate::math::Aabb;
+use crate::tick_rate::SkippedInstances;
+use crate::Despawned;
+
+/// The number of ticks after which items are despawned by default, which is
+/// five minutes.
+pub const DESPAWN_TICKS: u32 = 6000;
+
+/// The number of ticks before new items can be picked up by default.
+pub const DEFAULT_PICKUP_DELAY: u32 = 10;
+
+/// The speed items gain downwards every tick, in blocks per tick.
+const GRAVITY: f64 = 0.04;
+
+/// The fraction of the velocity items keep every tick.
+const DRAG: f64 = 0.98;
+
+/// The fraction of the horizontal velocity items on the ground keep every
+/// tick, on top of the drag.
+const GROUND_FRICTION: f64 = 0.6;
+
+/// How far the hitbox of an item reaches to merge with other items.
+const MERGE_RANGE: DVec3 = DVec3::new(0.5, 0.0, 0.5);
+
+/// How far the hitbox of a client reaches to pick up items.
+const PICKUP_RANGE: DVec3 = DVec3::new(1.0, 0.5, 1.0);
+
+/// A [`Component`] for items lying in the world. The entity must also have an
+/// [`McEntity`] of the kind [`EntityKind::Item`], which is shown to clients
+/// holding the stack of this component.
+#[derive(Component, Clone, PartialEq, Debug)]
+pub struct ItemEntity {
+    /// The items of the entity. The entity is despawned when the stack is
+    /// empty.
+    pub stack: ItemStack,
+    /// The velocity of the items in blocks per tick.
+    pub velocity: DVec3,
+    /// The number of ticks until the items can be picked up.
+    pub pickup_delay: u32,
+    /// The number of ticks the items have existed.
+    pub age: u32,
+    /// The age at which the items are despawned, or `None` if they are never
+    /// despawned.
+    pub lifetime: Option<u32>,
+}
+
+impl ItemEntity {
+    pub fn new(stack: ItemStack) -> Self {
+        Self {
+            stack,
+            velocity: DVec3::ZERO,
+            pickup_delay: DEFAULT_PICKUP_DELAY,
+            age: 0,
+            lifetime: Some(DESPAWN_TICKS),
+        }
+    }
+
+    #[must_use]
+    pub fn with_velocity(mut self, velocity: impl Into<DVec3>) -> Self {
+        self.velocity = velocity.into();
+        self
+    }
+
+    #[must_use]
+    pub fn with_pickup_delay(mut self, ticks: u32) -> Self {
+        self.pickup_delay = ticks;
+        self
+    }
+
+    #[must_use]
+    pub fn with_lifetime(mut self, ticks: Option<u32>) -> Self {
+        self.lifetime = ticks;
+        self
+    }
+
+    /// Returns if clients can pick up the items.
+    pub fn can_pick_up(&self) -> bool {
+        self.pickup_delay == 0 && self.stack.count() > 0
+    }
+}
+
+/// The components of an item entity.
+#[derive(Bundle)]
+pub struct ItemEntityBundle {
+    pub mc_entity: McEntity,
+    pub item: ItemEntity,
+}
+
+impl ItemEntityBundle {
+    /// Creates items at rest at the given position in the instance.
+    pub fn new(instance: Entity, position: impl Into<DVec3>, stack: ItemStack) -> Self {
+        let mut mc_entity = McEntity::new(EntityKind::Item, instance);
+        mc_entity.set_position(position);
+
+        if let TrackedData::Item(data) = mc_entity.data_mut() {
+            data.set_stack(stack.clone());
+        }
+
+        Self {
+            mc_entity,
+            item: ItemEntity::new(stack),
+        }
+    }
+
+    /// Sets the velocity of the items in blocks per tick.
+    #[must_use]
+    pub fn with_velocity(mut self, velocity: impl Into<DVec3>) -> Self {
+        let velocity = velocity.into();
+        self.mc_entity.set_velocity((velocity * 20.0).as_vec3());
+        self.item.velocity = velocity;
+        self
+    }
+
+    #[must_use]
+    pub fn with_pickup_delay(mut self, ticks: u32) -> Self {
+        self.item.pickup_delay = ticks;
+        self
+    }
+
+    #[must_use]
+    pub fn with_lifetime(mut self, ticks: Option<u32>) -> Self {
+        self.item.lifetime = ticks;
+        self
+    }
+}
+
+/// An event sent when a client picks up items.
+#[derive(Clone, Debug)]
+pub struct ItemPickupEvent {
+    pub client: Entity,
+    /// The item entity the items were picked up from. It is despawned if all
+    /// of its items were picked up.
+    pub item: Entity,
+    /// The items which were put in the inventory of the client, which are
+    /// fewer than the items of the entity if the inventory is full.
+    pub stack: ItemStack,
+}
+
+fn expand(aabb: Aabb, by: DVec3) -> Aabb {
+    Aabb {
+        min: aabb.min - by,
+        max: aabb.max + by,
+    }
+}
+
+fn translate(aabb: Aabb, by: DVec3) -> Aabb {
+    Aabb {
+        min: aabb.min + by,
+        max: aabb.max + by,
+    }
+}
+
+/// Ages item entities and moves them along their velocity.
+pub(crate) fn tick_item_entities(
+    mut commands: Commands,
+    instances: Query<&Instance>,
+    mut items: Query<(Entity, &mut McEntity, &mut ItemEntity), Without<Despawned>>,
+    skipped: Res<SkippedInstances>,
+) {
+    for (entity, mut mc_entity, mut item) in &mut items {
+        if skipped.contains(mc_entity.instance()) {
+            continue;
+        }
+
+        item.age = item.age.saturating_add(1);
+        item.pickup_delay = item.pickup_delay.saturating_sub(1);
+
+        if item.stack.count() == 0 || item.lifetime.map_or(false, |ticks| item.age >= ticks) {
+            commands.entity(entity).insert(Despawned);
+            continue;
+        }
+
+        let Ok(instance) = instances.get(mc_entity.instance()) else {
+            commands.entity(entity).insert(Despawned);
+            continue;
+        };
+
+        item.velocity.y -= GRAVITY;
+
+        let mut hitbox = mc_entity.hitbox();
+        let mut motion = item.velocity;
+        let mut offset = DVec3::ZERO;
+        let mut on_ground = false;
+
+        // Every collision stops the motion along one axis, so the items slide
+        // along at most three faces.
+        for _ in 0..3 {
+            let Some(collision) = instance.collide_aabb(hitbox, motion) else {
+                offset += motion;
+                break;
+            };
+
+            let step = motion * collision.t;
+            let normal = collision.normal;
+
+            offset += step;
+            hitbox = translate(hitbox, step);
+            motion -= step;
+            motion -= normal * motion.dot(normal);
+            item.velocity -= normal * item.velocity.dot(normal);
+
+            if normal.y > 0.0 {
+                on_ground = true;
+            }
+        }
+
+        let position = mc_entity.position() + offset;
+
+        if position.y < instance.min_y() as f64 - 64.0 {
+            commands.entity(entity).insert(Despawned);
+            continue;
+        }
+
+        item.velocity *= DRAG;
+
+        if on_ground {
+            item.velocity.x *= GROUND_FRICTION;
+            item.velocity.z *= GROUND_FRICTION;
+        }
+
+        if offset != DVec3::ZERO {
+            mc_entity.set_position(position);
+        }
+
+        mc_entity.set_velocity((item.velocity * 20.0).as_vec3());
+        mc_entity.set_on_ground(on_ground);
+    }
+}
+
+/// Moves the items of item entities into nearby item entities of the same
+/// kind. The larger stack takes the items of the smaller one.
+pub(crate) fn merge_item_entities(
+    mut commands: Commands,
+    mut items: Query<(Entity, &McEntity, &mut ItemEntity), Without<Despawned>>,
+    skipped: Res<SkippedInstances>,
+) {
+    let candidates: Vec<_> = items
+        .iter()
+        .filter(|(_, mc_entity, item)| {
+            !skipped.contains(mc_entity.instance())
+                && item.stack.count() > 0
+                && item.stack.count() < item.stack.item.max_stack()
+        })
+        .map(|(entity, mc_entity, _)| {
+            (
+                entity,
+                mc_entity.instance(),
+                expand(mc_entity.hitbox(), MERGE_RANGE),
+            )
+        })
+        .collect();
+
+    for (i, &(a, instance_a, range)) in candidates.iter().enumerate() {
+        for &(b, instance_b, _) in &candidates[i + 1..] {
+            if instance_a != instance_b {
+                continue;
+            }
+
+            let Ok([(_, _, item_a), (_, mc_entity_b, item_b)]) = items.get_many_mut([a, b]) else {
+                continue;
+            };
+
+            if !range.intersects(&mc_entity_b.hitbox())
+                || !can_stack(&item_a.stack, &item_b.stack)
+                || item_a.stack.count() == 0
+                || item_b.stack.count() == 0
+            {
+                continue;
+            }
+
+            let ((from_entity, mut from), mut into) =
+                if item_a.stack.count() >= item_b.stack.count() {
+                    ((b, item_b), item_a)
+                } else {
+                    ((a, item_a), item_b)
+                };
+
+            let max = into.stack.item.max_stack();
+            let n = from
+                .stack
+                .count()
+                .min(max.saturating_sub(into.stack.count()));
+
+            if n == 0 {
+                continue;
+            }
+
+            let count = into.stack.count() + n;
+            into.stack.set_count(count);
+            into.pickup_delay = into.pickup_delay.max(from.pickup_delay);
+            into.age = into.age.min(from.age);
+
+            let count = from.stack.count() - n;
+            from.stack.set_count(count);
+
+            if count == 0 {
+                commands.entity(from_entity).insert(Despawned);
+            }
+        }
+    }
+}
+
+/// Puts as many items of the stack as possible in the main inventory and
+/// hotbar of a player inventory, filling stacks of the same item before
+/// empty slots. Returns the number of items which were put in the inventory.
+fn insert_stack(inventory: &mut Inventory, stack: &ItemStack) -> u8 {
+    let max = stack.item.max_stack();
+    let slots = (36..45).chain(9..36);
+    let mut remaining = stack.count();
+
+    for slot in slots.clone() {
+        match inventory.slot(slot) {
+            Some(s) if can_stack(s, stack) && s.count() < max => {
+                let mut s = s.clone();
+                let n = remaining.min(max - s.count());
+                s.set_count(s.count() + n);
+                inventory.replace_slot(slot, s);
+                remaining -= n;
+            }
+            _ => {}
+        }
+
+        if remaining == 0 {
+            return stack.count();
+        }
+    }
+
+    for slot in slots {
+        if inventory.slot(slot).is_none() {
+            let mut s = stack.clone();
+            let n = remaining.min(max);
+            s.set_count(n);
+            inventory.replace_slot(slot, s);
+            remaining -= n;
+        }
+
+        if remaining == 0 {
+            break;
+        }
+    }
+
+    stack.count() - remaining
+}
+
+/// Puts the items near clients in their inventories and shows the collect
+/// animation to the clients in the same instance.
+pub(crate) fn pick_up_items(
+    mut commands: Commands,
+    mut items: Query<(Entity, &McEntity, &mut ItemEntity), Without<Despawned>>,
+    mut clients: Query<(
+        Entity,
+        &mut Client,
+        Option<&mut Inventory>,
+        Option<&McEntity>,
+    )>,
+    mut events: EventWriter<ItemPickupEvent>,
+) {
+    let mut pickups = vec![];
+
+    for (client_entity, client, inventory, collector) in &mut clients {
+        let Some(mut inventory) = inventory else {
+            continue;
+        };
+
+        if client.is_disconnected() || client.game_mode() == GameMode::Spectator {
+            continue;
+        }
+
+        let pos = client.position();
+        let reach = expand(
+            Aabb::new(
+                pos - DVec3::new(0.3, 0.0, 0.3),
+                pos + DVec3::new(0.3, 1.8, 0.3),
+            ),
+            PICKUP_RANGE,
+        );
+
+        for (item_entity, mc_entity, mut item) in &mut items {
+            if mc_entity.instance() != client.instance()
+                || !item.can_pick_up()
+                || !reach.intersects(&mc_entity.hitbox())
+            {
+                continue;
+            }
+
+            let n = insert_stack(&mut inventory, &item.stack);
+
+            if n == 0 {
+                continue;
+            }
+
+            let mut stack = item.stack.clone();
+            stack.set_count(n);
+
+            let count = item.stack.count() - n;
+            item.stack.set_count(count);
+
+            if count == 0 {
+                commands.entity(item_entity).insert(Despawned);
+            }
+
+            pickups.push((
+                client.instance(),
+                client_entity,
+                collector.map(|collector| collector.protocol_id()),
+                mc_entity.protocol_id(),
+                n,
+            ));
+
+            events.send(ItemPickupEvent {
+                client: client_entity,
+                item: item_entity,
+                stack,
+            });
+        }
+    }
+
+    for (instance, collector, collector_id, item_id, count) in pickups {
+        for (client_entity, mut client, _, _) in &mut clients {
+            if client.instance() != instance {
+                continue;
+            }
+
+            // Clients see themselves with the reserved entity ID 0.
+            let collector_id = if client_entity == collector {
+                0
+            } else if let Some(id) = collector_id {
+                id
+            } else {
+                continue;
+            };
+
+            client.write_packet(&PickupItem {
+                collected_entity_id: VarInt(item_id),
+                collector_entity_id: VarInt(collector_id),
+                pickup_item_count: VarInt(count.into()),
+            });
+        }
+    }
+}
+
+/// Shows clients the stacks of item entities whose items changed.
+pub(crate) fn update_item_entity_data(mut items: Query<(&mut McEntity, &ItemEntity)>) {
+    for (mut mc_entity, item) in &mut items {
+        let TrackedData::Item(data) = mc_entity.data() else {
+            continue;
+        };
+
+        if data.get_stack() == Some(&item.stack) || item.stack.count() == 0 {
+            continue;
+        }
+
+        if let TrackedData::Item(data) = mc_entity.data_mut() {
+            data.set_stack(item.stack.clone());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy_app::App;
+    use valence_protocol::block::BlockState;
+    use valence_protocol::packets::S2cPlayPacket;
+    use valence_protocol::ItemKind;
+
+    use super::*;
+    use crate::assert_packet_count;
+    use crate::instance::Chunk;
+    use crate::unit_test::util::scenario_single_client;
+
+    #[test]
+    fn items_merge_and_are_picked_up() -> anyhow::Result<()> {
+        let mut app = App::new();
+        let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+        let instance_ent = app.world.get::<Client>(client_ent).unwrap().instance();
+
+        let mut instance = app.world.get_mut::<Instance>(instance_ent).unwrap();
+        instance.insert_chunk([0, 0], Chunk::default());
+        for x in 0..16 {
+            for z in 0..16 {
+                instance.set_block([x, 0, z], BlockState::STONE);
+            }
+        }
+
+        app.world
+            .get_mut::<Client>(client_ent)
+            .unwrap()
+            .set_position([8.5, 1.0, 8.5]);
+
+        // Two stacks far from the client which merge into one.
+        let a = app
+            .world
+            .spawn(ItemEntityBundle::new(
+                instance_ent,
+                [1.5, 1.0, 1.5],
+                ItemStack::new(ItemKind::Diamond, 5, None),
+            ))
+            .id();
+        let b = app
+            .world
+            .spawn(ItemEntityBundle::new(
+                instance_ent,
+                [1.8, 1.0, 1.5],
+                ItemStack::new(ItemKind::Diamond, 3, None),
+            ))
+            .id();
+
+        app.update();
+
+        assert_eq!(app.world.get::<ItemEntity>(a).unwrap().stack.count(), 8);
+        assert!(app.world.get::<Despawned>(b).is_some());
+
+        // The items rest on the stone.
+        let pos = app.world.get::<McEntity>(a).unwrap().position();
+        assert_eq!(pos.y, 1.0);
+
+        // Items near the client are picked up once the delay is over.
+        app.world
+            .get_mut::<McEntity>(a)
+            .unwrap()
+            .set_position([8.5, 1.0, 9.0]);
+
+        for _ in 0..DEFAULT_PICKUP_DELAY {
+            app.update();
+        }
+
+        assert!(app.world.get::<Despawned>(a).is_some());
+
+        let inventory = app.world.get::<Inventory>(client_ent).unwrap();
+        assert_eq!(
+            inventory.slot(36),
+            Some(&ItemStack::new(ItemKind::Diamond, 8, None))
+        );
+
+        let events = app.world.resource::<Events<ItemPickupEvent>>();
+        assert_eq!(events.get_reader().iter(events).count(), 1);
+
+        let sent_packets = client_helper.collect_sent()?;
+        assert_packet_count!(sent_packets, 1, S2cPlayPacket::PickupItem(_));
+
+        Ok(())
+    }
+}
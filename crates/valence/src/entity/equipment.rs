@@ -0,0 +1,836 @@
+//! Items held and worn by entities.
+
+use std::ops::{Index, IndexMut};
+
+use bevy_ecs::prelude::*;
+use valence_nbt::{compound, Compound, List, Value};
+use valence_protocol::packets::s2c::play::SetHeldItemS2c;
+use valence_protocol::{ItemKind, ItemStack, PROTOCOL_VERSION};
+
+use crate::client::Client;
+use crate::entity::McEntity;
+use crate::inventory::{
+    Inventory, InventoryKind, BOOTS_SLOT, CHESTPLATE_SLOT, HELMET_SLOT, LEGGINGS_SLOT,
+    OFF_HAND_SLOT,
+};
+
+/// A slot of an entity which can hold a visible item.
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub enum EquipmentSlot {
+    MainHand,
+    OffHand,
+    Feet,
+    Legs,
+    Chest,
+    Head,
+    /// The armor of animals such as horses.
+    Body,
+}
+
+impl EquipmentSlot {
+    pub const ALL: [Self; 7] = [
+        Self::MainHand,
+        Self::OffHand,
+        Self::Feet,
+        Self::Legs,
+        Self::Chest,
+        Self::Head,
+        Self::Body,
+    ];
+
+    /// Returns the ID of the slot in the protocol.
+    ///
+    /// The body slot was added in protocol version 766 (1.20.5). Before that,
+    /// animals display their armor from the chest slot, so the body slot is
+    /// sent as the chest slot. Entities should not use both slots on those
+    /// versions.
+    pub const fn protocol_id(self) -> i8 {
+        match self {
+            Self::MainHand => 0,
+            Self::OffHand => 1,
+            Self::Feet => 2,
+            Self::Legs => 3,
+            Self::Chest => 4,
+            Self::Head => 5,
+            Self::Body if PROTOCOL_VERSION >= 766 => 6,
+            Self::Body => 4,
+        }
+    }
+
+    /// Returns `true` if this is one of the four armor slots.
+    pub fn is_armor(self) -> bool {
+        matches!(self, Self::Feet | Self::Legs | Self::Chest | Self::Head)
+    }
+
+    /// Returns the armor slot at the given slot of a player inventory, if any.
+    pub(crate) fn from_armor_slot(slot: u16) -> Option<Self> {
+        match slot {
+            HELMET_SLOT => Some(Self::Head),
+            CHESTPLATE_SLOT => Some(Self::Chest),
+            LEGGINGS_SLOT => Some(Self::Legs),
+            BOOTS_SLOT => Some(Self::Feet),
+            _ => None,
+        }
+    }
+}
+
+/// A [`Component`] containing the items visible in the equipment slots of the
+/// [`McEntity`] on the same entity.
+///
+/// Viewers of the entity are sent the items whenever they change, and clients
+/// the entity becomes visible to are sent the items along with the spawn
+/// packets. Removing the component empties all slots.
+///
+/// Every [`Client`] has this component, which is kept in sync with the armor
+/// and held items in the client's player [`Inventory`]. Once the client's
+/// player entity is spawned on the same entity, the items are visible to
+/// other clients. Other entities with a player [`Inventory`] and a
+/// [`HeldItem`] are kept in sync the same way.
+///
+/// Armor stands only display items in their hands when their arms are shown,
+/// see [`ArmorStandStyle`](super::armor_stand::ArmorStandStyle).
+#[derive(Component, Clone, PartialEq, Default, Debug)]
+pub struct Equipments {
+    items: [Option<ItemStack>; 7],
+}
+
+impl Equipments {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn get(&self, slot: EquipmentSlot) -> Option<&ItemStack> {
+        self.items[slot as usize].as_ref()
+    }
+
+    pub fn set(&mut self, slot: EquipmentSlot, item: impl Into<Option<ItemStack>>) {
+        self[slot] = item.into();
+    }
+
+    /// Sets the item in a slot, returning the previous item.
+    pub fn replace(
+        &mut self,
+        slot: EquipmentSlot,
+        item: impl Into<Option<ItemStack>>,
+    ) -> Option<ItemStack> {
+        std::mem::replace(&mut self[slot], item.into())
+    }
+
+    /// Swaps the items in two slots.
+    pub fn swap(&mut self, a: EquipmentSlot, b: EquipmentSlot) {
+        self.items.swap(a as usize, b as usize);
+    }
+
+    #[must_use]
+    pub fn with(mut self, slot: EquipmentSlot, item: impl Into<Option<ItemStack>>) -> Self {
+        self.set(slot, item);
+        self
+    }
+
+    /// Returns an iterator over the non-empty slots.
+    pub fn iter(&self) -> impl Iterator<Item = (EquipmentSlot, &ItemStack)> + '_ {
+        EquipmentSlot::ALL
+            .into_iter()
+            .filter_map(|slot| Some((slot, self.get(slot)?)))
+    }
+
+    /// Returns an iterator over all slots, including empty ones.
+    pub fn slots(&self) -> impl Iterator<Item = (EquipmentSlot, Option<&ItemStack>)> + '_ {
+        EquipmentSlot::ALL
+            .into_iter()
+            .map(|slot| (slot, self.get(slot)))
+    }
+
+    /// Empties all slots.
+    pub fn clear(&mut self) {
+        self.items = Default::default();
+    }
+
+    /// Writes the items to the `HandItems` and `ArmorItems` lists of an
+    /// entity compound, like vanilla saves the equipment of mobs and armor
+    /// stands. The body slot is written to `ArmorItem` like the armor of
+    /// horses.
+    pub fn write_nbt(&self, nbt: &mut Compound) {
+        let list = |slots: &[EquipmentSlot]| {
+            List::Compound(
+                slots
+                    .iter()
+                    .map(|&slot| stack_to_nbt(self.get(slot)))
+                    .collect(),
+            )
+        };
+
+        nbt.insert("HandItems", list(&EquipmentSlot::ALL[..2]));
+        nbt.insert("ArmorItems", list(&EquipmentSlot::ALL[2..6]));
+
+        if let Some(body) = self.get(EquipmentSlot::Body) {
+            nbt.insert("ArmorItem", stack_to_nbt(Some(body)));
+        }
+    }
+
+    /// Reads the items from the `HandItems` and `ArmorItems` lists and the
+    /// `ArmorItem` of an entity compound. Missing lists and unknown items
+    /// leave the slots empty.
+    pub fn from_nbt(nbt: &Compound) -> Self {
+        let mut equipments = Self::new();
+
+        for (key, slots) in [
+            ("HandItems", &EquipmentSlot::ALL[..2]),
+            ("ArmorItems", &EquipmentSlot::ALL[2..6]),
+        ] {
+            if let Some(Value::List(List::Compound(items))) = nbt.get(key) {
+                for (&slot, item) in slots.iter().zip(items) {
+                    equipments.set(slot, stack_from_nbt(item));
+                }
+            }
+        }
+
+        if let Some(Value::Compound(body)) = nbt.get("ArmorItem") {
+            equipments.set(EquipmentSlot::Body, stack_from_nbt(body));
+        }
+
+        equipments
+    }
+}
+
+/// Converts an item stack to the compound vanilla saves it as. Empty slots are
+/// saved as empty compounds.
+pub(crate) fn stack_to_nbt(stack: Option<&ItemStack>) -> Compound {
+    let Some(stack) = stack else {
+        return Compound::new();
+    };
+
+    let mut nbt = compound! {
+        "id" => format!("minecraft:{}", stack.item.to_str()),
+        "Count" => stack.count() as i8,
+    };
+
+    if let Some(tag) = &stack.nbt {
+        nbt.insert("tag", tag.clone());
+    }
+
+    nbt
+}
+
+/// Reads an item stack from the compound vanilla saves it as. Returns `None`
+/// for empty compounds and unknown items.
+pub(crate) fn stack_from_nbt(nbt: &Compound) -> Option<ItemStack> {
+    let Some(Value::String(id)) = nbt.get("id") else {
+        return None;
+    };
+
+    let item = ItemKind::from_str(id.strip_prefix("minecraft:").unwrap_or(id))?;
+
+    let count = match nbt.get("Count") {
+        Some(Value::Byte(count)) => *count as u8,
+        Some(Value::Int(count)) => (*count).clamp(0, 127) as u8,
+        _ => 1,
+    };
+
+    if count == 0 || item == ItemKind::Air {
+        return None;
+    }
+
+    let tag = match nbt.get("tag") {
+        Some(Value::Compound(tag)) => Some(tag.clone()),
+        _ => None,
+    };
+
+    Some(ItemStack::new(item, count, tag))
+}
+
+impl Index<EquipmentSlot> for Equipments {
+    type Output = Option<ItemStack>;
+
+    fn index(&self, slot: EquipmentSlot) -> &Self::Output {
+        &self.items[slot as usize]
+    }
+}
+
+impl IndexMut<EquipmentSlot> for Equipments {
+    fn index_mut(&mut self, slot: EquipmentSlot) -> &mut Self::Output {
+        &mut self.items[slot as usize]
+    }
+}
+
+/// A [`Component`] containing the selected hotbar slot of an entity with a
+/// player [`Inventory`]. The item in the selected slot is held in the
+/// [`EquipmentSlot::MainHand`] of the entity's [`Equipments`].
+///
+/// Every [`Client`] has this component, which is updated when the client
+/// selects a different hotbar slot. Changing it selects the slot on the
+/// client. Non-player entities with a player inventory and [`Equipments`] can
+/// be given this component to hold items the same way.
+#[derive(Component, Copy, Clone, PartialEq, Eq, Default, Debug)]
+pub struct HeldItem {
+    hotbar_slot: u8,
+}
+
+impl HeldItem {
+    /// The slot of the player inventory the first hotbar slot is in.
+    const HOTBAR_START: u16 = 36;
+
+    /// Creates a component selecting the hotbar slot, which is clamped to
+    /// `0..=8`.
+    pub fn new(hotbar_slot: u8) -> Self {
+        Self {
+            hotbar_slot: hotbar_slot.min(8),
+        }
+    }
+
+    /// Returns the selected hotbar slot in `0..=8`.
+    pub fn hotbar_slot(&self) -> u8 {
+        self.hotbar_slot
+    }
+
+    /// Selects the hotbar slot, which is clamped to `0..=8`.
+    pub fn set_hotbar_slot(&mut self, hotbar_slot: u8) {
+        self.hotbar_slot = hotbar_slot.min(8);
+    }
+
+    /// Returns the slot of the player inventory which is held.
+    pub fn inventory_slot(&self) -> u16 {
+        Self::HOTBAR_START + self.hotbar_slot as u16
+    }
+}
+
+/// An event sent when the equipment of a [`Client`] changes because of
+/// something the client did, such as putting on armor or selecting a
+/// different hotbar slot. The event is sent while the packets of the client
+/// are handled, so the [`Equipments`] of the client are only updated later in
+/// the tick.
+///
+/// Changes made by the server, including the initial equipment of a client,
+/// do not send this event.
+#[derive(Clone, PartialEq, Debug)]
+pub struct EquipmentChangeEvent {
+    pub client: Entity,
+    pub slot: EquipmentSlot,
+    /// The slot of the client's player [`Inventory`] the new item is in.
+    pub inventory_slot: u16,
+    /// If the change was caused by the client selecting a different hotbar
+    /// slot rather than by the contents of a slot changing.
+    pub selection_changed: bool,
+    pub old: Option<ItemStack>,
+    pub new: Option<ItemStack>,
+}
+
+impl EquipmentChangeEvent {
+    /// Undoes the change by putting the old item back into the inventory slot.
+    /// The new item is returned to the caller, who is responsible for putting
+    /// it somewhere else if it should not be lost.
+    ///
+    /// Returns `None` without doing anything if the change cannot be undone,
+    /// because it was a selection change or the slot has changed again since.
+    pub fn rollback(&self, inventory: &mut Inventory) -> Option<Option<ItemStack>> {
+        if self.selection_changed || inventory.slot(self.inventory_slot) != self.new.as_ref() {
+            return None;
+        }
+
+        Some(inventory.replace_slot(self.inventory_slot, self.old.clone()))
+    }
+}
+
+type EquipmentsChanged = Or<(Changed<Equipments>, Added<McEntity>)>;
+
+pub(crate) fn update_equipments(
+    mut entities: Query<(&Equipments, &mut McEntity), EquipmentsChanged>,
+) {
+    // The modified slots are sent together in the cached update packets of the
+    // entity's partition cell, so only clients in view of the entity receive
+    // them.
+    for (equipments, mut mc_entity) in &mut entities {
+        for slot in EquipmentSlot::ALL {
+            if mc_entity.equipment(slot) != equipments.get(slot) {
+                mc_entity.set_equipment(slot, equipments.get(slot).cloned());
+            }
+        }
+    }
+}
+
+pub(crate) fn remove_equipments(
+    removed: RemovedComponents<Equipments>,
+    mut entities: Query<&mut McEntity, Without<Equipments>>,
+) {
+    for entity in removed.iter() {
+        if let Ok(mut mc_entity) = entities.get_mut(entity) {
+            for slot in EquipmentSlot::ALL {
+                mc_entity.set_equipment(slot, None);
+            }
+        }
+    }
+}
+
+pub(crate) fn send_held_items(mut clients: Query<(&mut Client, &HeldItem), Changed<HeldItem>>) {
+    for (mut client, held) in &mut clients {
+        // Selections made by the client are already known to it.
+        if client.held_item_slot != held.inventory_slot() {
+            client.held_item_slot = held.inventory_slot();
+            client.write_packet(&SetHeldItemS2c {
+                slot: held.hotbar_slot,
+            });
+        }
+    }
+}
+
+/// Returns the equipment slots of a player and the slots of its player
+/// inventory they show, with the main hand showing `held_slot`.
+fn player_equipment_slots(held_slot: u16) -> impl Iterator<Item = (EquipmentSlot, u16)> {
+    [
+        (EquipmentSlot::MainHand, held_slot),
+        (EquipmentSlot::OffHand, OFF_HAND_SLOT),
+    ]
+    .into_iter()
+    .chain(
+        (HELMET_SLOT..=BOOTS_SLOT)
+            .filter_map(|inv_slot| Some((EquipmentSlot::from_armor_slot(inv_slot)?, inv_slot))),
+    )
+}
+
+/// The items in the equipment slots of the player inventory of a client,
+/// taken before handling a packet of the client which may change them.
+pub(crate) struct EquipmentSnapshot {
+    client: Entity,
+    items: Vec<(EquipmentSlot, u16, Option<ItemStack>)>,
+}
+
+impl EquipmentSnapshot {
+    /// Takes a snapshot of the equipment of the client, with the main hand
+    /// showing the inventory slot `held_slot`.
+    pub(crate) fn take(client: Entity, inventory: &Inventory, held_slot: u16) -> Self {
+        Self {
+            client,
+            items: player_equipment_slots(held_slot)
+                .map(|(slot, inv_slot)| (slot, inv_slot, inventory.slot(inv_slot).cloned()))
+                .collect(),
+        }
+    }
+
+    /// Sends an [`EquipmentChangeEvent`] for every equipment slot whose item
+    /// changed since the snapshot was taken.
+    pub(crate) fn send_changes(
+        self,
+        inventory: &Inventory,
+        held_slot: u16,
+        events: &mut EventWriter<EquipmentChangeEvent>,
+    ) {
+        for ((slot, old_slot, old), (_, inv_slot)) in self
+            .items
+            .into_iter()
+            .zip(player_equipment_slots(held_slot))
+        {
+            let new = inventory.slot(inv_slot);
+
+            if old.as_ref() == new {
+                continue;
+            }
+
+            events.send(EquipmentChangeEvent {
+                client: self.client,
+                slot,
+                inventory_slot: inv_slot,
+                selection_changed: inv_slot != old_slot,
+                old,
+                new: new.cloned(),
+            });
+        }
+    }
+}
+
+type PlayerEquipmentsChanged = Or<(Changed<Inventory>, Changed<HeldItem>, Added<Equipments>)>;
+
+/// Shows the items in the hands and armor slots of player inventories in the
+/// [`Equipments`] of the same entity.
+pub(crate) fn update_player_equipments(
+    mut entities: Query<(&Inventory, &HeldItem, &mut Equipments), PlayerEquipmentsChanged>,
+) {
+    for (inventory, held, mut equipments) in &mut entities {
+        if inventory.kind() != InventoryKind::Player {
+            continue;
+        }
+
+        for (slot, inv_slot) in player_equipment_slots(held.inventory_slot()) {
+            let item = inventory.slot(inv_slot);
+
+            // Avoid triggering change detection when nothing changed.
+            if equipments.get(slot) != item {
+                equipments.replace(slot, item.cloned());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy_app::App;
+    use valence_protocol::packets::c2s::play::{ClickContainer, SetHeldItemC2s};
+    use valence_protocol::packets::S2cPlayPacket;
+    use valence_protocol::types::ClickContainerMode;
+    use valence_protocol::VarInt;
+
+    use super::*;
+    use crate::assert_packet_count;
+    use crate::entity::EntityKind;
+    use crate::unit_test::util::scenario_single_client;
+
+    #[test]
+    fn player_armor_is_equipped() {
+        let mut app = App::new();
+        let (client_ent, _) = scenario_single_client(&mut app);
+        let instance_ent = app.world.get::<Client>(client_ent).unwrap().instance();
+        let uuid = app.world.get::<Client>(client_ent).unwrap().uuid();
+
+        app.world.entity_mut(client_ent).insert((
+            McEntity::with_uuid(EntityKind::Player, instance_ent, uuid),
+            Equipments::new(),
+        ));
+
+        let helmet = ItemStack::new(ItemKind::IronHelmet, 1, None);
+        app.world
+            .get_mut::<Inventory>(client_ent)
+            .unwrap()
+            .replace_slot(HELMET_SLOT, helmet.clone());
+
+        app.update();
+
+        assert_eq!(
+            app.world
+                .get::<Equipments>(client_ent)
+                .unwrap()
+                .get(EquipmentSlot::Head),
+            Some(&helmet)
+        );
+        assert_eq!(
+            app.world
+                .get::<McEntity>(client_ent)
+                .unwrap()
+                .equipment(EquipmentSlot::Head),
+            Some(&helmet)
+        );
+
+        app.world.entity_mut(client_ent).remove::<Equipments>();
+        app.update();
+
+        assert_eq!(
+            app.world
+                .get::<McEntity>(client_ent)
+                .unwrap()
+                .equipment(EquipmentSlot::Head),
+            None
+        );
+    }
+
+    #[test]
+    fn equipment_is_sent_when_entities_enter_view() {
+        let mut app = App::new();
+        let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+        let instance_ent = app.world.get::<Client>(client_ent).unwrap().instance();
+
+        let mut zombie = McEntity::new(EntityKind::Zombie, instance_ent);
+        zombie.set_position([1000.0, 0.0, 1000.0]);
+
+        let zombie_ent = app
+            .world
+            .spawn((
+                zombie,
+                Equipments::new().with(
+                    EquipmentSlot::Head,
+                    ItemStack::new(ItemKind::CarvedPumpkin, 1, None),
+                ),
+            ))
+            .id();
+
+        app.update();
+        client_helper.clear_sent();
+
+        // The equipment is unchanged, but the zombie is now visible.
+        app.world
+            .get_mut::<McEntity>(zombie_ent)
+            .unwrap()
+            .set_position([0.0, 0.0, 0.0]);
+
+        app.update();
+
+        let sent_packets = client_helper.collect_sent().unwrap();
+        assert_packet_count!(sent_packets, 1, S2cPlayPacket::SpawnEntity(_));
+        assert_packet_count!(sent_packets, 1, S2cPlayPacket::SetEquipment(_));
+    }
+
+    #[test]
+    fn equipment_changes_are_only_sent_to_viewers() {
+        let mut app = App::new();
+        let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+        let instance_ent = app.world.get::<Client>(client_ent).unwrap().instance();
+
+        let mut far_zombie = McEntity::new(EntityKind::Zombie, instance_ent);
+        far_zombie.set_position([1000.0, 0.0, 1000.0]);
+
+        let near = app
+            .world
+            .spawn((
+                McEntity::new(EntityKind::Zombie, instance_ent),
+                Equipments::new(),
+            ))
+            .id();
+        let far = app.world.spawn((far_zombie, Equipments::new())).id();
+
+        app.update();
+        client_helper.clear_sent();
+
+        for zombie in [near, far] {
+            let mut equipments = app.world.get_mut::<Equipments>(zombie).unwrap();
+            equipments.set(
+                EquipmentSlot::MainHand,
+                ItemStack::new(ItemKind::IronSword, 1, None),
+            );
+            equipments.set(
+                EquipmentSlot::Head,
+                ItemStack::new(ItemKind::IronHelmet, 1, None),
+            );
+        }
+
+        app.update();
+
+        // Both slots of the near zombie are sent in a single packet, and
+        // nothing is sent for the zombie out of view.
+        let sent_packets = client_helper.collect_sent().unwrap();
+        assert_packet_count!(sent_packets, 1, S2cPlayPacket::SetEquipment(_));
+    }
+
+    #[test]
+    fn held_item_follows_hotbar_selection() {
+        let mut app = App::new();
+        let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+
+        let sword = ItemStack::new(ItemKind::IronSword, 1, None);
+        app.world
+            .get_mut::<Inventory>(client_ent)
+            .unwrap()
+            .replace_slot(38, sword.clone());
+
+        app.update();
+        client_helper.clear_sent();
+
+        // Selected by the client.
+        client_helper.send(&SetHeldItemC2s { slot: 2 });
+        app.update();
+
+        assert_eq!(
+            app.world.get::<HeldItem>(client_ent).unwrap().hotbar_slot(),
+            2
+        );
+        assert_eq!(
+            app.world
+                .get::<Equipments>(client_ent)
+                .unwrap()
+                .get(EquipmentSlot::MainHand),
+            Some(&sword)
+        );
+
+        let sent_packets = client_helper.collect_sent().unwrap();
+        assert_packet_count!(sent_packets, 0, S2cPlayPacket::SetHeldItemS2c(_));
+
+        // Selected by the server.
+        app.world
+            .get_mut::<HeldItem>(client_ent)
+            .unwrap()
+            .set_hotbar_slot(0);
+        app.update();
+
+        assert_eq!(
+            app.world
+                .get::<Equipments>(client_ent)
+                .unwrap()
+                .get(EquipmentSlot::MainHand),
+            None
+        );
+
+        let sent_packets = client_helper.collect_sent().unwrap();
+        assert_packet_count!(sent_packets, 1, S2cPlayPacket::SetHeldItemS2c(_));
+    }
+
+    #[test]
+    fn npcs_hold_items_from_their_hotbar() {
+        let mut app = App::new();
+        let (client_ent, _) = scenario_single_client(&mut app);
+        let instance_ent = app.world.get::<Client>(client_ent).unwrap().instance();
+
+        let axe = ItemStack::new(ItemKind::IronAxe, 1, None);
+        let mut inventory = Inventory::new(InventoryKind::Player);
+        inventory.replace_slot(HeldItem::new(4).inventory_slot(), axe.clone());
+
+        let npc = app
+            .world
+            .spawn((
+                McEntity::new(EntityKind::Player, instance_ent),
+                inventory,
+                HeldItem::new(4),
+                Equipments::new(),
+            ))
+            .id();
+
+        app.update();
+
+        assert_eq!(
+            app.world
+                .get::<Equipments>(npc)
+                .unwrap()
+                .get(EquipmentSlot::MainHand),
+            Some(&axe)
+        );
+
+        // NPC changes are not reported as client changes.
+        let events = app.world.resource::<Events<EquipmentChangeEvent>>();
+        assert_eq!(events.get_reader().iter(events).count(), 0);
+    }
+
+    #[test]
+    fn slot_accessors() {
+        let sword = ItemStack::new(ItemKind::IronSword, 1, None);
+        let shield = ItemStack::new(ItemKind::Shield, 1, None);
+
+        let mut equipments = Equipments::new().with(EquipmentSlot::MainHand, sword.clone());
+        assert_eq!(equipments[EquipmentSlot::MainHand], Some(sword.clone()));
+
+        equipments.swap(EquipmentSlot::MainHand, EquipmentSlot::OffHand);
+        assert_eq!(equipments.get(EquipmentSlot::MainHand), None);
+        assert_eq!(equipments.get(EquipmentSlot::OffHand), Some(&sword));
+
+        assert_eq!(
+            equipments.replace(EquipmentSlot::OffHand, shield),
+            Some(sword)
+        );
+
+        if let Some(stack) = &mut equipments[EquipmentSlot::OffHand] {
+            stack.set_count(2);
+        }
+
+        let slots: Vec<_> = equipments.slots().collect();
+        assert_eq!(slots.len(), EquipmentSlot::ALL.len());
+        assert_eq!(
+            slots[1],
+            (
+                EquipmentSlot::OffHand,
+                Some(&ItemStack::new(ItemKind::Shield, 2, None))
+            )
+        );
+        assert_eq!(slots[0], (EquipmentSlot::MainHand, None));
+    }
+
+    #[test]
+    fn equipments_round_trip_through_nbt() {
+        let mut enchanted = ItemStack::new(ItemKind::DiamondSword, 1, None);
+        enchanted.nbt = Some(compound! { "Damage" => 12 });
+
+        let equipments = Equipments::new()
+            .with(EquipmentSlot::MainHand, enchanted)
+            .with(
+                EquipmentSlot::Head,
+                ItemStack::new(ItemKind::CarvedPumpkin, 1, None),
+            )
+            .with(
+                EquipmentSlot::Feet,
+                ItemStack::new(ItemKind::LeatherBoots, 1, None),
+            );
+
+        let mut nbt = compound! { "id" => "minecraft:zombie" };
+        equipments.write_nbt(&mut nbt);
+
+        let Some(Value::List(List::Compound(armor))) = nbt.get("ArmorItems") else {
+            panic!("missing armor items");
+        };
+        assert_eq!(armor.len(), 4);
+        assert_eq!(
+            armor[0].get("id"),
+            Some(&Value::String("minecraft:leather_boots".into()))
+        );
+        assert!(armor[1].is_empty());
+
+        assert_eq!(Equipments::from_nbt(&nbt), equipments);
+        assert_eq!(Equipments::from_nbt(&Compound::new()), Equipments::new());
+    }
+
+    #[test]
+    fn clients_have_equipments() {
+        let mut app = App::new();
+        let (client_ent, _) = scenario_single_client(&mut app);
+
+        let shield = ItemStack::new(ItemKind::Shield, 1, None);
+        app.world
+            .get_mut::<Inventory>(client_ent)
+            .unwrap()
+            .replace_slot(OFF_HAND_SLOT, shield.clone());
+
+        app.update();
+
+        assert_eq!(
+            app.world
+                .get::<Equipments>(client_ent)
+                .unwrap()
+                .get(EquipmentSlot::OffHand),
+            Some(&shield)
+        );
+    }
+
+    #[test]
+    fn changes_can_be_rolled_back() {
+        let mut app = App::new();
+        let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+
+        // The client puts on the helmet it carries with the cursor.
+        let helmet = ItemStack::new(ItemKind::DiamondHelmet, 1, None);
+        app.world
+            .get_mut::<Client>(client_ent)
+            .unwrap()
+            .replace_cursor_item(helmet.clone());
+
+        app.update();
+
+        let state_id = app
+            .world
+            .get::<Client>(client_ent)
+            .unwrap()
+            .inventory_state_id;
+
+        client_helper.send(&ClickContainer {
+            window_id: 0,
+            button: 0,
+            mode: ClickContainerMode::Click,
+            state_id: VarInt(state_id.0),
+            slot_idx: HELMET_SLOT as i16,
+            slots: vec![(HELMET_SLOT as i16, Some(helmet.clone()))],
+            carried_item: None,
+        });
+
+        app.update();
+
+        let events = app.world.resource::<Events<EquipmentChangeEvent>>();
+        let changes: Vec<_> = events.get_reader().iter(events).cloned().collect();
+
+        assert_eq!(
+            changes,
+            [EquipmentChangeEvent {
+                client: client_ent,
+                slot: EquipmentSlot::Head,
+                inventory_slot: HELMET_SLOT,
+                selection_changed: false,
+                old: None,
+                new: Some(helmet.clone()),
+            }]
+        );
+
+        let mut inventory = app.world.get_mut::<Inventory>(client_ent).unwrap();
+        assert_eq!(changes[0].rollback(&mut inventory), Some(Some(helmet)));
+        assert_eq!(inventory.slot(HELMET_SLOT), None);
+        assert_eq!(changes[0].rollback(&mut inventory), None);
+
+        // Changes made by the server are not reported.
+        inventory.replace_slot(OFF_HAND_SLOT, ItemStack::new(ItemKind::Shield, 1, None));
+
+        app.update();
+        app.update();
+
+        let events = app.world.resource::<Events<EquipmentChangeEvent>>();
+        assert_eq!(events.get_reader().iter(events).count(), 0);
+    }
+}
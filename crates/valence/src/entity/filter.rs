@@ -0,0 +1,204 @@
+//! Hiding entities from some clients.
+
+use std::fmt;
+
+use bevy_ecs::prelude::*;
+use rustc_hash::FxHashSet;
+
+use crate::entity::{EntityKind, McEntity};
+
+type Predicate = Box<dyn Fn(Entity, &McEntity) -> bool + Send + Sync>;
+
+/// A [`Component`] which hides entities from the [`Client`] on the same
+/// entity.
+///
+/// Hidden entities are not spawned for the client, and are despawned if the
+/// client can already see them. An entity is hidden if it was hidden with
+/// [`hide`](Self::hide), or if the predicate of the filter returns `false`
+/// for it and it was not shown with [`show`](Self::show).
+///
+/// The predicate is evaluated when entities come into the view of the client
+/// and every time the filter is changed. Hiding every player from a client,
+/// for instance for a `/hideplayers` command in a lobby, looks like this:
+///
+/// ```
+/// use valence::entity::filter::EntityFilter;
+/// use valence::entity::EntityKind;
+///
+/// let filter = EntityFilter::new().with_hidden_kinds([EntityKind::Player]);
+/// # let _ = filter;
+/// ```
+///
+/// [`Client`]: crate::client::Client
+#[derive(Component, Default)]
+pub struct EntityFilter {
+    hidden: FxHashSet<Entity>,
+    shown: FxHashSet<Entity>,
+    hidden_kinds: FxHashSet<EntityKind>,
+    predicate: Option<Predicate>,
+}
+
+impl EntityFilter {
+    /// Creates a filter which hides nothing.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Hides every entity of the given kinds.
+    #[must_use]
+    pub fn with_hidden_kinds(mut self, kinds: impl IntoIterator<Item = EntityKind>) -> Self {
+        self.hidden_kinds.extend(kinds);
+        self
+    }
+
+    /// Sets a function which is given an entity and returns `false` if the
+    /// entity is hidden.
+    #[must_use]
+    pub fn with_predicate(
+        mut self,
+        predicate: impl Fn(Entity, &McEntity) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        self.predicate = Some(Box::new(predicate));
+        self
+    }
+
+    /// Hides the entity, regardless of its kind and the predicate.
+    pub fn hide(&mut self, entity: Entity) {
+        self.shown.remove(&entity);
+        self.hidden.insert(entity);
+    }
+
+    /// Shows the entity, regardless of its kind and the predicate.
+    pub fn show(&mut self, entity: Entity) {
+        self.hidden.remove(&entity);
+        self.shown.insert(entity);
+    }
+
+    /// Removes the entity from the entities hidden or shown with
+    /// [`hide`](Self::hide) and [`show`](Self::show), so its kind and the
+    /// predicate decide if it is hidden again.
+    pub fn reset(&mut self, entity: Entity) {
+        self.hidden.remove(&entity);
+        self.shown.remove(&entity);
+    }
+
+    pub fn hide_kind(&mut self, kind: EntityKind) {
+        self.hidden_kinds.insert(kind);
+    }
+
+    pub fn show_kind(&mut self, kind: EntityKind) {
+        self.hidden_kinds.remove(&kind);
+    }
+
+    pub fn set_predicate(
+        &mut self,
+        predicate: impl Fn(Entity, &McEntity) -> bool + Send + Sync + 'static,
+    ) {
+        self.predicate = Some(Box::new(predicate));
+    }
+
+    pub fn clear_predicate(&mut self) {
+        self.predicate = None;
+    }
+
+    /// Returns if the client sees the given entity.
+    pub fn is_visible(&self, id: Entity, entity: &McEntity) -> bool {
+        if self.hidden.contains(&id) {
+            return false;
+        }
+
+        if self.shown.contains(&id) {
+            return true;
+        }
+
+        !self.hidden_kinds.contains(&entity.kind())
+            && self
+                .predicate
+                .as_ref()
+                .map_or(true, |predicate| predicate(id, entity))
+    }
+}
+
+impl fmt::Debug for EntityFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EntityFilter")
+            .field("hidden", &self.hidden)
+            .field("shown", &self.shown)
+            .field("hidden_kinds", &self.hidden_kinds)
+            .field("predicate", &self.predicate.is_some())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy_app::App;
+    use valence_protocol::packets::S2cPlayPacket;
+
+    use super::*;
+    use crate::assert_packet_count;
+    use crate::client::Client;
+    use crate::instance::{Chunk, Instance};
+    use crate::unit_test::util::scenario_single_client;
+
+    #[test]
+    fn hidden_entities_are_not_spawned() -> anyhow::Result<()> {
+        let mut app = App::new();
+        let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+        let instance_ent = app.world.get::<Client>(client_ent).unwrap().instance();
+
+        app.world
+            .get_mut::<Instance>(instance_ent)
+            .unwrap()
+            .insert_chunk([0, 0], Chunk::default());
+
+        app.world
+            .entity_mut(client_ent)
+            .insert(EntityFilter::new().with_hidden_kinds([EntityKind::Pig]));
+
+        let pig = app
+            .world
+            .spawn(McEntity::new(EntityKind::Pig, instance_ent))
+            .id();
+        app.world
+            .spawn(McEntity::new(EntityKind::Cow, instance_ent));
+
+        app.update();
+
+        let sent_packets = client_helper.collect_sent()?;
+        assert_packet_count!(sent_packets, 1, S2cPlayPacket::SpawnEntity(_));
+
+        // Showing the pig spawns it.
+        app.world
+            .get_mut::<EntityFilter>(client_ent)
+            .unwrap()
+            .show(pig);
+
+        app.update();
+
+        let sent_packets = client_helper.collect_sent()?;
+        assert_packet_count!(sent_packets, 1, S2cPlayPacket::SpawnEntity(_));
+
+        // Hiding it again despawns it.
+        app.world
+            .get_mut::<EntityFilter>(client_ent)
+            .unwrap()
+            .hide(pig);
+
+        app.update();
+
+        let sent_packets = client_helper.collect_sent()?;
+        assert_packet_count!(sent_packets, 1, S2cPlayPacket::RemoveEntities(_));
+        assert_packet_count!(sent_packets, 0, S2cPlayPacket::SpawnEntity(_));
+
+        // Removing the filter shows every entity.
+        app.world.entity_mut(client_ent).remove::<EntityFilter>();
+
+        app.update();
+
+        let sent_packets = client_helper.collect_sent()?;
+        assert_packet_count!(sent_packets, 1, S2cPlayPacket::SpawnEntity(_));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,726 @@
+//! Taming, saddling and riding horses, donkeys and mules.
+//!
+//! Entities with a [`Horse`] component can be mounted by clients. Untamed
+//! horses try to throw off their rider until they are tamed, which is more
+//! likely the more they were fed. Tamed horses carry a saddle, armor and
+//! possibly a chest in an [`Inventory`] which clients open with the horse
+//! screen. Clients steer saddled horses they ride, and jumping with the jump
+//! bar is reported with the [`HorseJump`] event.
+//!
+//! Horse armor is kept in the inventory and shown in the body slot of the
+//! horse's [`Equipments`], which is inserted if missing.
+
+use bevy_ecs::prelude::*;
+use rand::Rng;
+use uuid::Uuid;
+use valence_protocol::types::{EntityInteraction, GameMode, SoundCategory};
+use valence_protocol::{ItemKind, ItemStack, Sound};
+
+use crate::budget::{SheddableWork, TickBudget};
+use crate::client::event::{
+    InteractWithEntity, MoveVehicle, OpenHorseInventory, PlayerInput, StartJumpWithHorse,
+};
+use crate::client::Client;
+use crate::entity::breeding::{is_breeding_item, Breedable};
+use crate::entity::equipment::{EquipmentSlot, Equipments};
+use crate::entity::passengers::Passengers;
+use crate::entity::{EntityKind, EntityStatus, McEntity, McEntityManager, TrackedData};
+use crate::instance::Instance;
+use crate::inventory::{consume_held_item, held_item, Inventory, InventoryKind, OpenInventory};
+use crate::tick_rate::SkippedInstances;
+use crate::Despawned;
+
+/// The slot of a horse [`Inventory`] containing the saddle.
+pub const SADDLE_SLOT: u16 = 0;
+
+/// The slot of a horse [`Inventory`] containing the horse armor.
+pub const HORSE_ARMOR_SLOT: u16 = 1;
+
+/// The number of ticks a horse rears after throwing off its rider.
+const REARING_TICKS: u32 = 20;
+
+/// The chance per tick that an untamed horse tries to throw off its rider.
+const TAMING_ATTEMPT_CHANCE: f64 = 1.0 / 50.0;
+
+/// A [`Component`] for horses, donkeys and mules. The [`McEntity`] on the same
+/// entity must be one of the horse family.
+///
+/// If the entity has no [`Inventory`], one without a chest is added.
+#[derive(Component, Clone, PartialEq, Debug)]
+pub struct Horse {
+    owner: Option<Uuid>,
+    /// How close the horse is to accepting a rider. Feeding the horse and
+    /// failed taming attempts raise the temper.
+    ///
+    /// # Default Value
+    ///
+    /// `0`
+    pub temper: i32,
+    /// The temper at which taming always succeeds.
+    ///
+    /// # Default Value
+    ///
+    /// `100`
+    pub max_temper: i32,
+    /// The jump strength of the horse. Vanilla horses have a jump strength
+    /// between `0.4` and `1.0`.
+    ///
+    /// # Default Value
+    ///
+    /// `0.7`
+    pub jump_strength: f64,
+    rider: Option<Entity>,
+    /// The rider last sent to clients.
+    sent_rider: Option<Entity>,
+    rearing_ticks: u32,
+}
+
+impl Horse {
+    pub fn new() -> Self {
+        Self {
+            owner: None,
+            temper: 0,
+            max_temper: 100,
+            jump_strength: 0.7,
+            rider: None,
+            sent_rider: None,
+            rearing_ticks: 0,
+        }
+    }
+
+    /// Makes the horse tamed by the player with the given UUID.
+    #[must_use]
+    pub fn with_owner(mut self, owner: Uuid) -> Self {
+        self.owner = Some(owner);
+        self
+    }
+
+    /// Returns the UUID of the player which tamed the horse.
+    pub fn owner(&self) -> Option<Uuid> {
+        self.owner
+    }
+
+    /// Sets the owner of the horse. Setting this to `None` makes the horse
+    /// untamed.
+    pub fn set_owner(&mut self, owner: Option<Uuid>) {
+        self.owner = owner;
+    }
+
+    pub fn is_tamed(&self) -> bool {
+        self.owner.is_some()
+    }
+
+    /// Returns the client riding the horse.
+    pub fn rider(&self) -> Option<Entity> {
+        self.rider
+    }
+
+    /// Sets the client riding the horse. The rider must be an entity with a
+    /// [`Client`] in the same instance as the horse.
+    pub fn set_rider(&mut self, rider: Option<Entity>) {
+        self.rider = rider;
+    }
+
+    /// Returns if the horse is rearing after throwing off its rider.
+    pub fn is_rearing(&self) -> bool {
+        self.rearing_ticks > 0
+    }
+
+    /// Returns the vertical velocity in blocks per tick of a jump with the
+    /// given power from the jump bar, which ranges from `0` to `100`.
+    pub fn jump_velocity(&self, jump_boost: u8) -> f64 {
+        let scale = if jump_boost >= 90 {
+            1.0
+        } else {
+            0.4 + 0.4 * jump_boost as f64 / 90.0
+        };
+
+        self.jump_strength * scale
+    }
+}
+
+impl Default for Horse {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// An event sent when a client riding a horse jumps with it.
+#[derive(Clone, Debug)]
+pub struct HorseJump {
+    pub horse: Entity,
+    pub client: Entity,
+    /// The power of the jump from the jump bar in `0..=100`.
+    pub jump_boost: u8,
+    /// The vertical velocity of the jump in blocks per tick.
+    pub velocity: f64,
+}
+
+/// An event sent when a client tames a horse by riding it.
+#[derive(Clone, Debug)]
+pub struct HorseTamed {
+    pub horse: Entity,
+    pub client: Entity,
+}
+
+/// Returns the temper gained by feeding an untamed horse the item, or `None`
+/// if horses don't eat it.
+fn food_temper(item: ItemKind) -> Option<i32> {
+    match item {
+        ItemKind::Sugar | ItemKind::Wheat | ItemKind::Apple => Some(3),
+        ItemKind::GoldenCarrot => Some(5),
+        ItemKind::GoldenApple | ItemKind::EnchantedGoldenApple => Some(10),
+        _ => None,
+    }
+}
+
+fn is_horse_armor(item: ItemKind) -> bool {
+    matches!(
+        item,
+        ItemKind::LeatherHorseArmor
+            | ItemKind::IronHorseArmor
+            | ItemKind::GoldenHorseArmor
+            | ItemKind::DiamondHorseArmor
+    )
+}
+
+/// Returns if the inventory of a horse holds a saddle.
+pub fn is_saddled(inventory: &Inventory) -> bool {
+    inventory.slot_count() > SADDLE_SLOT
+        && inventory
+            .slot(SADDLE_SLOT)
+            .map_or(false, |stack| stack.item == ItemKind::Saddle)
+}
+
+/// Returns if the horse has a chest.
+fn has_chest(inventory: &Inventory) -> bool {
+    inventory.kind() == InventoryKind::Horse { chest: true }
+}
+
+/// Returns a copy of a horse inventory with a chest.
+fn with_chest(inventory: &Inventory) -> Inventory {
+    let mut new = Inventory::with_title(
+        InventoryKind::Horse { chest: true },
+        inventory.title().clone(),
+    );
+
+    for (idx, stack) in inventory.slots().enumerate() {
+        new.replace_slot(idx as u16, stack.cloned());
+    }
+
+    new
+}
+
+pub(crate) fn init_horse_inventories(
+    mut commands: Commands,
+    horses: Query<Entity, (With<Horse>, Without<Inventory>)>,
+) {
+    for entity in &horses {
+        commands
+            .entity(entity)
+            .insert(Inventory::new(InventoryKind::Horse { chest: false }));
+    }
+}
+
+type HorseQuery<'a> = (
+    &'a mut Horse,
+    &'a McEntity,
+    &'a mut Inventory,
+    Option<&'a Breedable>,
+);
+
+pub(crate) fn interact_with_horses(
+    mut commands: Commands,
+    manager: Res<McEntityManager>,
+    mut clients: Query<(&Client, &mut Inventory), Without<Horse>>,
+    mut horses: Query<HorseQuery, (Without<Client>, Without<Despawned>)>,
+    mut interactions: EventReader<InteractWithEntity>,
+    mut instances: Query<&mut Instance>,
+) {
+    for event in interactions.iter() {
+        let EntityInteraction::Interact(hand) = event.interact else {
+            continue
+        };
+
+        let Some(horse_entity) = manager.get_with_protocol_id(event.entity_id) else {
+            continue
+        };
+
+        let Ok((mut horse, mc_entity, mut horse_inventory, breedable)) =
+            horses.get_mut(horse_entity)
+        else {
+            continue
+        };
+
+        let Ok((client, mut inventory)) = clients.get_mut(event.client) else {
+            continue
+        };
+
+        if client.game_mode() == GameMode::Spectator || horse.rider.is_some() {
+            continue;
+        }
+
+        if event.sneaking && horse.is_tamed() {
+            commands
+                .entity(event.client)
+                .insert(OpenInventory::new(horse_entity));
+            continue;
+        }
+
+        let kind = mc_entity.kind();
+        let held = held_item(client, &inventory, hand).map(|stack| stack.item);
+
+        let sound = match held {
+            Some(item) if !horse.is_tamed() && food_temper(item).is_some() => {
+                if horse.temper >= horse.max_temper {
+                    continue;
+                }
+
+                horse.temper = (horse.temper + food_temper(item).unwrap()).min(horse.max_temper);
+                Some(Sound::EntityHorseEat)
+            }
+            Some(ItemKind::Saddle) if horse.is_tamed() && !is_saddled(&horse_inventory) => {
+                horse_inventory
+                    .replace_slot(SADDLE_SLOT, ItemStack::new(ItemKind::Saddle, 1, None));
+                Some(Sound::EntityHorseSaddle)
+            }
+            Some(item)
+                if is_horse_armor(item)
+                    && kind == EntityKind::Horse
+                    && horse.is_tamed()
+                    && horse_inventory.slot(HORSE_ARMOR_SLOT).is_none() =>
+            {
+                let stack = held_item(client, &inventory, hand)
+                    .cloned()
+                    .map(|mut stack| {
+                        stack.set_count(1);
+                        stack
+                    });
+
+                horse_inventory.replace_slot(HORSE_ARMOR_SLOT, stack);
+                Some(Sound::EntityHorseArmor)
+            }
+            Some(ItemKind::Chest)
+                if matches!(kind, EntityKind::Donkey | EntityKind::Mule)
+                    && horse.is_tamed()
+                    && !has_chest(&horse_inventory) =>
+            {
+                *horse_inventory = with_chest(&horse_inventory);
+                Some(Sound::EntityDonkeyChest)
+            }
+            _ => None,
+        };
+
+        if let Some(sound) = sound {
+            consume_held_item(client, &mut inventory, hand);
+
+            if let Ok(mut instance) = instances.get_mut(mc_entity.instance()) {
+                instance.play_sound(
+                    sound,
+                    SoundCategory::Neutral,
+                    mc_entity.position(),
+                    1.0,
+                    1.0,
+                );
+            }
+
+            continue;
+        }
+
+        // Tamed horses that can breed are fed instead of mounted.
+        if horse.is_tamed()
+            && breedable.is_some()
+            && held.map_or(false, |item| is_breeding_item(kind, item))
+        {
+            continue;
+        }
+
+        // Undead horses can only be ridden once tamed.
+        if !horse.is_tamed() && matches!(kind, EntityKind::SkeletonHorse | EntityKind::ZombieHorse)
+        {
+            continue;
+        }
+
+        horse.rider = Some(event.client);
+    }
+}
+
+pub(crate) fn steer_horses(
+    mut horses: Query<(&Horse, &mut McEntity, &Inventory)>,
+    mut move_vehicle: EventReader<MoveVehicle>,
+) {
+    for event in move_vehicle.iter() {
+        let Some((_, mut mc_entity, _)) = horses.iter_mut().find(|(horse, _, inventory)| {
+            horse.rider == Some(event.client) && horse.is_tamed() && is_saddled(inventory)
+        }) else {
+            continue
+        };
+
+        mc_entity.set_position(event.position);
+        mc_entity.set_yaw(event.yaw);
+        mc_entity.set_head_yaw(event.yaw);
+        mc_entity.set_pitch(event.pitch);
+    }
+}
+
+pub(crate) fn handle_horse_jumps(
+    mut horses: Query<(Entity, &Horse, &Inventory)>,
+    mut start_jump: EventReader<StartJumpWithHorse>,
+    mut jumps: EventWriter<HorseJump>,
+) {
+    for event in start_jump.iter() {
+        let Some((entity, horse, _)) = horses.iter_mut().find(|(_, horse, inventory)| {
+            horse.rider == Some(event.client) && horse.is_tamed() && is_saddled(inventory)
+        }) else {
+            continue
+        };
+
+        jumps.send(HorseJump {
+            horse: entity,
+            client: event.client,
+            jump_boost: event.jump_boost,
+            velocity: horse.jump_velocity(event.jump_boost),
+        });
+    }
+}
+
+pub(crate) fn open_horse_inventories(
+    mut commands: Commands,
+    horses: Query<(Entity, &Horse)>,
+    mut open_horse_inventory: EventReader<OpenHorseInventory>,
+) {
+    for event in open_horse_inventory.iter() {
+        if let Some((entity, _)) = horses
+            .iter()
+            .find(|(_, horse)| horse.rider == Some(event.client) && horse.is_tamed())
+        {
+            commands
+                .entity(event.client)
+                .insert(OpenInventory::new(entity));
+        }
+    }
+}
+
+pub(crate) fn dismount_horses(
+    mut horses: Query<&mut Horse>,
+    mut player_input: EventReader<PlayerInput>,
+) {
+    for event in player_input.iter() {
+        if !event.unmount {
+            continue;
+        }
+
+        for mut horse in &mut horses {
+            if horse.rider == Some(event.client) {
+                horse.rider = None;
+            }
+        }
+    }
+}
+
+pub(crate) fn tick_horses(
+    mut horses: Query<(Entity, &mut Horse, &mut McEntity)>,
+    clients: Query<&Client>,
+    mut instances: Query<&mut Instance>,
+    mut tamed: EventWriter<HorseTamed>,
+    skipped: Res<SkippedInstances>,
+    budget: Res<TickBudget>,
+) {
+    let mut rng = rand::thread_rng();
+    let shedding_ai = budget.is_shedding(SheddableWork::EntityAi);
+
+    for (entity, mut horse, mut mc_entity) in &mut horses {
+        if skipped.contains(mc_entity.instance()) {
+            continue;
+        }
+
+        if horse.rearing_ticks > 0 {
+            horse.rearing_ticks -= 1;
+        }
+
+        let Some(rider) = horse.rider else { continue };
+
+        let Ok(client) = clients.get(rider) else {
+            horse.rider = None;
+            continue;
+        };
+
+        if client.instance() != mc_entity.instance() {
+            horse.rider = None;
+            continue;
+        }
+
+        if horse.is_tamed() || shedding_ai || !rng.gen_bool(TAMING_ATTEMPT_CHANCE) {
+            continue;
+        }
+
+        let Ok(mut instance) = instances.get_mut(mc_entity.instance()) else {
+            continue
+        };
+
+        if horse.max_temper > 0 && rng.gen_range(0..horse.max_temper) < horse.temper {
+            horse.owner = Some(client.uuid());
+            mc_entity.trigger_status(EntityStatus::AddPositivePlayerReactionParticles);
+            tamed.send(HorseTamed {
+                horse: entity,
+                client: rider,
+            });
+        } else {
+            horse.temper = (horse.temper + 5).min(horse.max_temper);
+            horse.rider = None;
+            horse.rearing_ticks = REARING_TICKS;
+            mc_entity.trigger_status(EntityStatus::AddNegativePlayerReactionParticles);
+            instance.play_sound(
+                Sound::EntityHorseAngry,
+                SoundCategory::Neutral,
+                mc_entity.position(),
+                1.0,
+                1.0,
+            );
+        }
+    }
+}
+
+/// Seats the riders of horses in the [`Passengers`] of the horses, which
+/// moves the riders with the horses and shows them to clients.
+pub(crate) fn update_horse_passengers(
+    mut commands: Commands,
+    mut horses: Query<(Entity, &mut Horse, Option<&mut Passengers>), Changed<Horse>>,
+) {
+    for (entity, mut horse, passengers) in &mut horses {
+        if horse.rider == horse.sent_rider {
+            continue;
+        }
+
+        let old_rider = horse.sent_rider;
+        horse.sent_rider = horse.rider;
+
+        match passengers {
+            Some(mut passengers) => {
+                if let Some(old_rider) = old_rider {
+                    passengers.remove(old_rider);
+                }
+
+                if let Some(rider) = horse.rider {
+                    passengers.add(rider);
+                }
+            }
+            None => {
+                if let Some(rider) = horse.rider {
+                    commands
+                        .entity(entity)
+                        .insert(Passengers::new().with(rider));
+                }
+            }
+        }
+    }
+}
+
+pub(crate) fn show_horse_armor(
+    mut commands: Commands,
+    mut horses: Query<
+        (Entity, &Inventory, Option<&mut Equipments>),
+        (With<Horse>, Changed<Inventory>),
+    >,
+) {
+    for (entity, inventory, equipments) in &mut horses {
+        let armor = inventory.slot(HORSE_ARMOR_SLOT).cloned();
+
+        match equipments {
+            Some(mut equipments) => {
+                if equipments.get(EquipmentSlot::Body) != armor.as_ref() {
+                    equipments.set(EquipmentSlot::Body, armor);
+                }
+            }
+            None => {
+                commands
+                    .entity(entity)
+                    .insert(Equipments::new().with(EquipmentSlot::Body, armor));
+            }
+        }
+    }
+}
+
+type ChangedHorse = Or<(Changed<Horse>, Changed<Inventory>)>;
+
+pub(crate) fn update_horse_data(
+    mut horses: Query<(&Horse, &mut McEntity, &Inventory), ChangedHorse>,
+) {
+    for (horse, mut mc_entity, inventory) in &mut horses {
+        let saddled = is_saddled(inventory);
+        let chest = has_chest(inventory);
+
+        macro_rules! set_horse_data {
+            ($data:expr) => {{
+                $data.set_tamed(horse.is_tamed());
+                $data.set_saddled(saddled);
+                // The angry flag makes the horse rear.
+                $data.set_angry(horse.is_rearing());
+                $data.set_owner_uuid(horse.owner);
+            }};
+        }
+
+        match mc_entity.data_mut() {
+            TrackedData::Horse(data) => set_horse_data!(data),
+            TrackedData::Donkey(data) => {
+                set_horse_data!(data);
+                data.set_chest(chest);
+            }
+            TrackedData::Mule(data) => {
+                set_horse_data!(data);
+                data.set_chest(chest);
+            }
+            TrackedData::SkeletonHorse(data) => set_horse_data!(data),
+            TrackedData::ZombieHorse(data) => set_horse_data!(data),
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy_app::App;
+    use valence_protocol::packets::c2s::play::{
+        ConfirmTeleport, Interact, MoveVehicleC2s, SetHeldItemC2s,
+    };
+    use valence_protocol::packets::S2cPlayPacket;
+    use valence_protocol::types::Hand;
+
+    use super::*;
+    use crate::unit_test::util::scenario_single_client;
+
+    #[test]
+    fn jump_velocity() {
+        let horse = Horse {
+            jump_strength: 1.0,
+            ..Horse::new()
+        };
+
+        assert_eq!(horse.jump_velocity(0), 0.4);
+        assert_eq!(horse.jump_velocity(45), 0.6000000000000001);
+        assert_eq!(horse.jump_velocity(90), 1.0);
+        assert_eq!(horse.jump_velocity(100), 1.0);
+    }
+
+    #[test]
+    fn saddle_and_ride_tamed_horse() {
+        let mut app = App::new();
+        let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+        let client = app.world.get::<Client>(client_ent).unwrap();
+        let instance_ent = client.instance();
+        let uuid = client.uuid();
+
+        let horse_ent = app
+            .world
+            .spawn((
+                McEntity::new(EntityKind::Horse, instance_ent),
+                Horse::new().with_owner(uuid),
+            ))
+            .id();
+
+        let mut inventory = app.world.get_mut::<Inventory>(client_ent).unwrap();
+        inventory.replace_slot(36, ItemStack::new(ItemKind::Saddle, 1, None));
+
+        app.update();
+
+        let horse_id = app.world.get::<McEntity>(horse_ent).unwrap().protocol_id();
+
+        // Vehicle movement is ignored until the initial teleport is confirmed.
+        client_helper.send(&ConfirmTeleport {
+            teleport_id: 0.into(),
+        });
+        client_helper.send(&SetHeldItemC2s { slot: 0 });
+
+        app.update();
+
+        // Saddle the horse, then mount it and ride it.
+        for _ in 0..2 {
+            client_helper.send(&Interact {
+                entity_id: horse_id.into(),
+                interact: EntityInteraction::Interact(Hand::Main),
+                sneaking: false,
+            });
+        }
+
+        client_helper.send(&MoveVehicleC2s {
+            position: [5.0, 0.0, 5.0],
+            yaw: 90.0,
+            pitch: 0.0,
+        });
+
+        app.update();
+
+        let horse_inventory = app.world.get::<Inventory>(horse_ent).unwrap();
+        assert!(is_saddled(horse_inventory));
+        assert_eq!(
+            app.world.get::<Horse>(horse_ent).unwrap().rider(),
+            Some(client_ent)
+        );
+        assert_eq!(
+            app.world.get::<McEntity>(horse_ent).unwrap().position(),
+            [5.0, 0.0, 5.0].into()
+        );
+        assert!(app
+            .world
+            .get::<Inventory>(client_ent)
+            .unwrap()
+            .slot(36)
+            .is_none());
+
+        match app.world.get::<McEntity>(horse_ent).unwrap().data() {
+            TrackedData::Horse(data) => {
+                assert!(data.get_tamed());
+                assert!(data.get_saddled());
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn horse_armor_is_shown_in_body_slot() {
+        let mut app = App::new();
+        let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+        let instance_ent = app.world.get::<Client>(client_ent).unwrap().instance();
+
+        let horse_ent = app
+            .world
+            .spawn((
+                McEntity::new(EntityKind::Horse, instance_ent),
+                Horse::new(),
+                Inventory::new(InventoryKind::Horse { chest: false }),
+            ))
+            .id();
+
+        app.update();
+        client_helper.clear_sent();
+
+        let armor = ItemStack::new(ItemKind::DiamondHorseArmor, 1, None);
+        app.world
+            .get_mut::<Inventory>(horse_ent)
+            .unwrap()
+            .replace_slot(HORSE_ARMOR_SLOT, armor.clone());
+
+        app.update();
+
+        assert_eq!(
+            app.world
+                .get::<McEntity>(horse_ent)
+                .unwrap()
+                .equipment(EquipmentSlot::Body),
+            Some(&armor)
+        );
+
+        let sent_packets = client_helper.collect_sent().unwrap();
+        let slots: Vec<_> = sent_packets
+            .iter()
+            .filter_map(|pkt| match pkt {
+                S2cPlayPacket::SetEquipment(pkt) => Some(pkt.equipment.clone()),
+                _ => None,
+            })
+            .flatten()
+            .map(|entry| entry.slot)
+            .collect();
+
+        assert_eq!(slots, [EquipmentSlot::Body.protocol_id()]);
+    }
+}
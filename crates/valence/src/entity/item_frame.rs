@@ -0,0 +1,300 @@
+//! Placing and rotating items in item frames.
+//!
+//! Clients put the item in their hand into an empty item frame by using it on
+//! the frame, rotate the item by using the frame again, and knock the item out
+//! of the frame by attacking it. Every change is reported with an
+//! [`ItemFrameEvent`], so map walls and displays can be built without
+//! handling packets:
+//!
+//! ```
+//! use valence::entity::item_frame::{ItemFrameAction, ItemFrameEvent};
+//! use valence::prelude::*;
+//!
+//! fn announce_maps(mut clients: Query<&mut Client>, mut events: EventReader<ItemFrameEvent>) {
+//!     for event in events.iter() {
+//!         if let ItemFrameAction::Place(stack) = &event.action {
+//!             if stack.item == ItemKind::FilledMap {
+//!                 if let Ok(mut client) = clients.get_mut(event.client) {
+//!                     client.send_message("Map added to the wall!");
+//!                 }
+//!             }
+//!         }
+//!     }
+//! }
+//! ```
+//!
+//! The item of a frame is kept in its [`TrackedData`], and the `rotation` of
+//! the tracked data is the rotation of the item in eighths of a full turn. The
+//! frame itself faces the direction of the pitch and yaw of its [`McEntity`].
+//!
+//! Frames with the [`FixedItemFrame`] component can't be changed by clients,
+//! and clients in adventure or spectator mode can't change any frame.
+
+use bevy_ecs::prelude::*;
+use valence_protocol::types::{GameMode, SoundCategory};
+use valence_protocol::{ItemStack, Sound};
+
+use crate::client::event::{AttackEntityEvent, InteractEntityEvent};
+use crate::client::Client;
+use crate::entity::dropped_item::ItemEntityBundle;
+use crate::entity::{EntityKind, McEntity, TrackedData};
+use crate::instance::Instance;
+use crate::inventory::{consume_held_item, held_item, Inventory};
+use crate::Despawned;
+
+/// A [`Component`] which keeps clients from changing the item frame
+/// [`McEntity`] on the same entity, like the `Fixed` tag of vanilla item
+/// frames.
+#[derive(Component, Copy, Clone, PartialEq, Eq, Default, Debug)]
+pub struct FixedItemFrame;
+
+/// An event sent when a client changes the item in an item frame.
+#[derive(Clone, PartialEq, Debug)]
+pub struct ItemFrameEvent {
+    pub client: Entity,
+    pub item_frame: Entity,
+    pub action: ItemFrameAction,
+}
+
+/// A change made to an item frame.
+#[derive(Clone, PartialEq, Debug)]
+pub enum ItemFrameAction {
+    /// An item was put into the empty frame.
+    Place(ItemStack),
+    /// The item in the frame was rotated. Contains the new rotation, from 0
+    /// to 7.
+    Rotate(i32),
+    /// The item was knocked out of the frame. It is dropped at the frame,
+    /// unless the client is in creative mode.
+    Remove(ItemStack),
+}
+
+/// Returns the item in the item frame and its rotation, or `None` if the
+/// entity is not an item frame.
+fn frame_contents(mc_entity: &McEntity) -> Option<(Option<ItemStack>, i32)> {
+    match mc_entity.data() {
+        TrackedData::ItemFrame(frame) => {
+            Some((frame.get_item_stack().cloned(), frame.get_rotation()))
+        }
+        TrackedData::GlowItemFrame(frame) => {
+            Some((frame.get_item_stack().cloned(), frame.get_rotation()))
+        }
+        _ => None,
+    }
+}
+
+fn set_frame_contents(mc_entity: &mut McEntity, item: Option<ItemStack>, rotation: i32) {
+    match mc_entity.data_mut() {
+        TrackedData::ItemFrame(frame) => {
+            frame.set_item_stack(item);
+            frame.set_rotation(rotation);
+        }
+        TrackedData::GlowItemFrame(frame) => {
+            frame.set_item_stack(item);
+            frame.set_rotation(rotation);
+        }
+        _ => {}
+    }
+}
+
+fn frame_sound(mc_entity: &McEntity, action: &ItemFrameAction) -> Sound {
+    let glowing = mc_entity.kind() == EntityKind::GlowItemFrame;
+
+    match (action, glowing) {
+        (ItemFrameAction::Place(_), false) => Sound::EntityItemFrameAddItem,
+        (ItemFrameAction::Place(_), true) => Sound::EntityGlowItemFrameAddItem,
+        (ItemFrameAction::Rotate(_), false) => Sound::EntityItemFrameRotateItem,
+        (ItemFrameAction::Rotate(_), true) => Sound::EntityGlowItemFrameRotateItem,
+        (ItemFrameAction::Remove(_), false) => Sound::EntityItemFrameRemoveItem,
+        (ItemFrameAction::Remove(_), true) => Sound::EntityGlowItemFrameRemoveItem,
+    }
+}
+
+/// Returns `true` if the client is allowed to change item frames.
+fn can_change_frames(client: &Client) -> bool {
+    !matches!(
+        client.game_mode(),
+        GameMode::Adventure | GameMode::Spectator
+    )
+}
+
+pub(crate) fn interact_with_item_frames(
+    mut commands: Commands,
+    mut clients: Query<(&Client, &mut Inventory)>,
+    mut frames: Query<
+        &mut McEntity,
+        (Without<Client>, Without<FixedItemFrame>, Without<Despawned>),
+    >,
+    mut instances: Query<&mut Instance>,
+    mut interact: EventReader<InteractEntityEvent>,
+    mut attack: EventReader<AttackEntityEvent>,
+    mut item_frame_events: EventWriter<ItemFrameEvent>,
+) {
+    let mut changes = vec![];
+
+    // Clients send an interaction with and without the clicked position.
+    // Vanilla item frames only respond to the latter.
+    for event in interact.iter().filter(|event| event.position.is_none()) {
+        let Ok((client, mut inventory)) = clients.get_mut(event.client) else {
+            continue;
+        };
+
+        let Ok(mut frame) = frames.get_mut(event.target) else {
+            continue;
+        };
+
+        let Some((item, rotation)) = frame_contents(&frame) else {
+            continue;
+        };
+
+        if !can_change_frames(client) {
+            continue;
+        }
+
+        let action = match item {
+            Some(item) => {
+                let rotation = (rotation + 1).rem_euclid(8);
+                set_frame_contents(&mut frame, Some(item), rotation);
+                ItemFrameAction::Rotate(rotation)
+            }
+            None => {
+                let Some(mut stack) = held_item(client, &inventory, event.hand).cloned() else {
+                    continue;
+                };
+
+                stack.set_count(1);
+                consume_held_item(client, &mut inventory, event.hand);
+                set_frame_contents(&mut frame, Some(stack.clone()), 0);
+                ItemFrameAction::Place(stack)
+            }
+        };
+
+        changes.push((event.client, event.target, action));
+    }
+
+    for event in attack.iter() {
+        let Ok((client, _)) = clients.get(event.client) else {
+            continue;
+        };
+
+        let Ok(mut frame) = frames.get_mut(event.target) else {
+            continue;
+        };
+
+        let Some((Some(item), _)) = frame_contents(&frame) else {
+            continue;
+        };
+
+        if !can_change_frames(client) {
+            continue;
+        }
+
+        set_frame_contents(&mut frame, None, 0);
+
+        if client.game_mode() != GameMode::Creative {
+            commands.spawn(ItemEntityBundle::new(
+                frame.instance(),
+                frame.position(),
+                item.clone(),
+            ));
+        }
+
+        changes.push((event.client, event.target, ItemFrameAction::Remove(item)));
+    }
+
+    for (client, item_frame, action) in changes {
+        if let Ok(frame) = frames.get(item_frame) {
+            if let Ok(mut instance) = instances.get_mut(frame.instance()) {
+                instance.play_sound(
+                    frame_sound(frame, &action),
+                    SoundCategory::Block,
+                    frame.position(),
+                    1.0,
+                    1.0,
+                );
+            }
+        }
+
+        item_frame_events.send(ItemFrameEvent {
+            client,
+            item_frame,
+            action,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy_app::App;
+    use valence_protocol::packets::c2s::play::Interact;
+    use valence_protocol::types::{EntityInteraction, Hand};
+    use valence_protocol::{ItemKind, VarInt};
+
+    use super::*;
+    use crate::unit_test::util::scenario_single_client;
+
+    #[test]
+    fn items_are_placed_rotated_and_removed() {
+        let mut app = App::new();
+        let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+        let instance_ent = app.world.get::<Client>(client_ent).unwrap().instance();
+
+        let held_slot = app
+            .world
+            .get::<Client>(client_ent)
+            .unwrap()
+            .held_item_slot();
+
+        app.world
+            .get_mut::<Inventory>(client_ent)
+            .unwrap()
+            .replace_slot(held_slot, ItemStack::new(ItemKind::FilledMap, 2, None));
+
+        let frame_ent = app
+            .world
+            .spawn(McEntity::new(EntityKind::ItemFrame, instance_ent))
+            .id();
+
+        app.update();
+
+        let entity_id = app.world.get::<McEntity>(frame_ent).unwrap().protocol_id();
+        let interact = |interact: EntityInteraction| Interact {
+            entity_id: VarInt(entity_id),
+            interact,
+            sneaking: false,
+        };
+
+        client_helper.send(&interact(EntityInteraction::Interact(Hand::Main)));
+        app.update();
+
+        let frame = app.world.get::<McEntity>(frame_ent).unwrap();
+        let map = ItemStack::new(ItemKind::FilledMap, 1, None);
+        assert_eq!(frame_contents(frame), Some((Some(map.clone()), 0)));
+
+        let inventory = app.world.get::<Inventory>(client_ent).unwrap();
+        assert_eq!(inventory.slot(held_slot), Some(&map));
+
+        client_helper.send(&interact(EntityInteraction::Interact(Hand::Main)));
+        app.update();
+
+        let frame = app.world.get::<McEntity>(frame_ent).unwrap();
+        assert_eq!(frame_contents(frame), Some((Some(map.clone()), 1)));
+
+        client_helper.send(&interact(EntityInteraction::Attack));
+        app.update();
+
+        let frame = app.world.get::<McEntity>(frame_ent).unwrap();
+        assert_eq!(frame_contents(frame), Some((None, 0)));
+
+        let events = app.world.resource::<Events<ItemFrameEvent>>();
+        let actions: Vec<_> = events
+            .get_reader()
+            .iter(events)
+            .map(|event| event.action.clone())
+            .collect();
+        assert_eq!(
+            actions,
+            [ItemFrameAction::Rotate(1), ItemFrameAction::Remove(map)]
+        );
+    }
+}
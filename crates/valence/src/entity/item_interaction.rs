@@ -0,0 +1,410 @@
+//! Using items on entities, like shearing sheep and milking cows.
+//!
+//! The [`ItemInteractions`] resource maps pairs of entity kinds and items to
+//! handlers that run when a client uses the item on the entity. A handler
+//! changes the state of the entity and returns an [`ItemInteractionResult`]
+//! describing what happens to the item and what the entity drops. Vanilla
+//! interactions are registered by default and can be replaced or removed.
+//!
+//! Valence can't spawn item entities with an item yet, so drops are reported
+//! with the [`ItemUsedOnEntity`] event for the server to hand out.
+
+use std::collections::HashMap;
+
+use bevy_ecs::prelude::*;
+use rand::Rng;
+use valence_protocol::types::{EntityInteraction, GameMode, SoundCategory};
+use valence_protocol::{ItemKind, ItemStack, Sound};
+
+use crate::banner::DyeColor;
+use crate::client::event::InteractWithEntity;
+use crate::client::Client;
+use crate::entity::{EntityKind, McEntity, McEntityManager, TrackedData};
+use crate::instance::Instance;
+use crate::inventory::{consume_held_item, hand_slot, held_item, Inventory};
+use crate::Despawned;
+
+/// The bit of the sheep color byte which is set when the sheep is sheared.
+const SHEARED_BIT: u8 = 0x10;
+
+/// What happens when an item is used on an entity.
+#[derive(Clone, PartialEq, Default, Debug)]
+pub struct ItemInteractionResult {
+    /// If one of the used items is used up. Clients in creative mode never
+    /// use up their items.
+    pub consume: bool,
+    /// The item given to the client in return, such as a milk bucket. It
+    /// replaces the used item if that was used up, and is dropped if the
+    /// inventory of the client is full.
+    pub returned: Option<ItemStack>,
+    /// The items dropped by the entity.
+    pub drops: Vec<ItemStack>,
+    /// The sound played at the entity.
+    pub sound: Option<Sound>,
+}
+
+impl ItemInteractionResult {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[must_use]
+    pub fn with_consume(mut self, consume: bool) -> Self {
+        self.consume = consume;
+        self
+    }
+
+    #[must_use]
+    pub fn with_returned(mut self, returned: ItemStack) -> Self {
+        self.returned = Some(returned);
+        self
+    }
+
+    #[must_use]
+    pub fn with_drop(mut self, drop: ItemStack) -> Self {
+        self.drops.push(drop);
+        self
+    }
+
+    #[must_use]
+    pub fn with_sound(mut self, sound: Sound) -> Self {
+        self.sound = Some(sound);
+        self
+    }
+}
+
+type Handler =
+    Box<dyn Fn(&mut McEntity, &ItemStack) -> Option<ItemInteractionResult> + Send + Sync>;
+
+/// A [`Resource`] with the handlers for items used on entities.
+///
+/// A handler returns `None` when the item can't be used on the entity in its
+/// current state, like a sheep that was already sheared. The client then
+/// keeps its item.
+#[derive(Resource)]
+pub struct ItemInteractions {
+    handlers: HashMap<(EntityKind, ItemKind), Handler>,
+}
+
+impl Default for ItemInteractions {
+    fn default() -> Self {
+        let mut interactions = Self {
+            handlers: HashMap::new(),
+        };
+
+        interactions.insert(EntityKind::Sheep, ItemKind::Shears, shear_sheep);
+        interactions.insert(EntityKind::SnowGolem, ItemKind::Shears, shear_snow_golem);
+        interactions.insert(EntityKind::Cow, ItemKind::Bucket, |entity, _| {
+            milk(entity, Sound::EntityCowMilk, ItemKind::MilkBucket)
+        });
+        interactions.insert(EntityKind::Mooshroom, ItemKind::Bucket, |entity, _| {
+            milk(entity, Sound::EntityCowMilk, ItemKind::MilkBucket)
+        });
+        interactions.insert(EntityKind::Goat, ItemKind::Bucket, |entity, _| {
+            milk(entity, Sound::EntityGoatMilk, ItemKind::MilkBucket)
+        });
+        interactions.insert(EntityKind::Mooshroom, ItemKind::Bowl, |entity, _| {
+            milk(entity, Sound::EntityMooshroomMilk, ItemKind::MushroomStew)
+        });
+
+        for color in DyeColor::ALL {
+            let Some(dye) = ItemKind::from_str(&format!("{}_dye", color.to_str())) else {
+                continue
+            };
+
+            interactions.insert(EntityKind::Sheep, dye, move |entity, _| {
+                dye_sheep(entity, color)
+            });
+        }
+
+        interactions
+    }
+}
+
+impl ItemInteractions {
+    /// Sets the handler for using `item` on entities of the given kind. An
+    /// existing handler for the pair is replaced.
+    pub fn insert(
+        &mut self,
+        kind: EntityKind,
+        item: ItemKind,
+        handler: impl Fn(&mut McEntity, &ItemStack) -> Option<ItemInteractionResult>
+            + Send
+            + Sync
+            + 'static,
+    ) {
+        self.handlers.insert((kind, item), Box::new(handler));
+    }
+
+    pub fn remove(&mut self, kind: EntityKind, item: ItemKind) -> bool {
+        self.handlers.remove(&(kind, item)).is_some()
+    }
+
+    pub fn contains(&self, kind: EntityKind, item: ItemKind) -> bool {
+        self.handlers.contains_key(&(kind, item))
+    }
+}
+
+/// An event sent when a client uses an item on an entity.
+#[derive(Clone, Debug)]
+pub struct ItemUsedOnEntity {
+    pub client: Entity,
+    pub entity: Entity,
+    pub item: ItemKind,
+    /// The items dropped by the entity, and the returned item if it didn't
+    /// fit in the inventory of the client.
+    pub drops: Vec<ItemStack>,
+}
+
+fn shear_sheep(entity: &mut McEntity, _: &ItemStack) -> Option<ItemInteractionResult> {
+    let TrackedData::Sheep(sheep) = entity.data_mut() else {
+        return None
+    };
+
+    let color = sheep.get_color();
+
+    if sheep.get_child() || color & SHEARED_BIT != 0 {
+        return None;
+    }
+
+    sheep.set_color(color | SHEARED_BIT);
+
+    let wool = DyeColor::from_id((color & 0xf) as i32)
+        .and_then(|color| ItemKind::from_str(&format!("{}_wool", color.to_str())))
+        .unwrap_or(ItemKind::WhiteWool);
+
+    Some(
+        ItemInteractionResult::new()
+            .with_drop(ItemStack::new(
+                wool,
+                rand::thread_rng().gen_range(1..=3),
+                None,
+            ))
+            .with_sound(Sound::EntitySheepShear),
+    )
+}
+
+fn shear_snow_golem(entity: &mut McEntity, _: &ItemStack) -> Option<ItemInteractionResult> {
+    let TrackedData::SnowGolem(golem) = entity.data_mut() else {
+        return None
+    };
+
+    if !golem.get_has_pumpkin() {
+        return None;
+    }
+
+    golem.set_has_pumpkin(false);
+
+    Some(
+        ItemInteractionResult::new()
+            .with_drop(ItemStack::new(ItemKind::CarvedPumpkin, 1, None))
+            .with_sound(Sound::EntitySnowGolemShear),
+    )
+}
+
+fn dye_sheep(entity: &mut McEntity, color: DyeColor) -> Option<ItemInteractionResult> {
+    let TrackedData::Sheep(sheep) = entity.data_mut() else {
+        return None
+    };
+
+    if sheep.get_color() & SHEARED_BIT != 0 || sheep.get_color() == color.id() as u8 {
+        return None;
+    }
+
+    sheep.set_color(color.id() as u8);
+
+    Some(
+        ItemInteractionResult::new()
+            .with_consume(true)
+            .with_sound(Sound::ItemDyeUse),
+    )
+}
+
+/// Fills the used item from an adult cow, goat or mooshroom.
+fn milk(entity: &mut McEntity, sound: Sound, filled: ItemKind) -> Option<ItemInteractionResult> {
+    let baby = match entity.data() {
+        TrackedData::Cow(cow) => cow.get_child(),
+        TrackedData::Mooshroom(mooshroom) => mooshroom.get_child(),
+        TrackedData::Goat(goat) => goat.get_child(),
+        _ => return None,
+    };
+
+    if baby {
+        return None;
+    }
+
+    Some(
+        ItemInteractionResult::new()
+            .with_consume(true)
+            .with_returned(ItemStack::new(filled, 1, None))
+            .with_sound(sound),
+    )
+}
+
+/// Puts `stack` in the held slot if it's empty, or else in the first free
+/// slot of the hotbar or main inventory. Returns the stack if there is no
+/// room for it.
+fn give_item(inventory: &mut Inventory, held_slot: u16, stack: ItemStack) -> Option<ItemStack> {
+    let slot = [held_slot]
+        .into_iter()
+        .chain(36..45)
+        .chain(9..36)
+        .find(|&slot| inventory.slot(slot).is_none());
+
+    match slot {
+        Some(slot) => {
+            inventory.replace_slot(slot, stack);
+            None
+        }
+        None => Some(stack),
+    }
+}
+
+pub(crate) fn use_items_on_entities(
+    manager: Res<McEntityManager>,
+    interactions: Res<ItemInteractions>,
+    mut clients: Query<(&Client, &mut Inventory)>,
+    mut entities: Query<&mut McEntity, (Without<Client>, Without<Despawned>)>,
+    mut instances: Query<&mut Instance>,
+    mut interact: EventReader<InteractWithEntity>,
+    mut used: EventWriter<ItemUsedOnEntity>,
+) {
+    for event in interact.iter() {
+        let EntityInteraction::Interact(hand) = event.interact else {
+            continue
+        };
+
+        let Some(entity) = manager.get_with_protocol_id(event.entity_id) else {
+            continue
+        };
+
+        let Ok(mut mc_entity) = entities.get_mut(entity) else {
+            continue
+        };
+
+        let Ok((client, mut inventory)) = clients.get_mut(event.client) else {
+            continue
+        };
+
+        if client.game_mode() == GameMode::Spectator {
+            continue;
+        }
+
+        let Some(stack) = held_item(client, &inventory, hand).cloned() else {
+            continue
+        };
+
+        let Some(handler) = interactions.handlers.get(&(mc_entity.kind(), stack.item)) else {
+            continue
+        };
+
+        let Some(result) = handler(&mut mc_entity, &stack) else {
+            continue
+        };
+
+        if result.consume {
+            consume_held_item(client, &mut inventory, hand);
+        }
+
+        let mut drops = result.drops;
+
+        if let Some(returned) = result.returned {
+            drops.extend(give_item(&mut inventory, hand_slot(client, hand), returned));
+        }
+
+        if let Some(sound) = result.sound {
+            if let Ok(mut instance) = instances.get_mut(mc_entity.instance()) {
+                instance.play_sound(sound, SoundCategory::Player, mc_entity.position(), 1.0, 1.0);
+            }
+        }
+
+        used.send(ItemUsedOnEntity {
+            client: event.client,
+            entity,
+            item: stack.item,
+            drops,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy_app::App;
+    use valence_protocol::packets::c2s::play::{Interact, SetHeldItemC2s};
+    use valence_protocol::types::Hand;
+
+    use super::*;
+    use crate::unit_test::util::scenario_single_client;
+
+    #[test]
+    fn shear_and_milk() {
+        let mut app = App::new();
+        let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+        let instance_ent = app.world.get::<Client>(client_ent).unwrap().instance();
+
+        let mut sheep = McEntity::new(EntityKind::Sheep, instance_ent);
+        if let TrackedData::Sheep(data) = sheep.data_mut() {
+            data.set_color(DyeColor::Lime.id() as u8);
+        }
+
+        let sheep_ent = app.world.spawn(sheep).id();
+        let cow_ent = app
+            .world
+            .spawn(McEntity::new(EntityKind::Cow, instance_ent))
+            .id();
+
+        let mut inventory = app.world.get_mut::<Inventory>(client_ent).unwrap();
+        inventory.replace_slot(36, ItemStack::new(ItemKind::Shears, 1, None));
+        inventory.replace_slot(37, ItemStack::new(ItemKind::Bucket, 2, None));
+
+        client_helper.send(&SetHeldItemC2s { slot: 0 });
+
+        app.update();
+
+        let sheep_id = app.world.get::<McEntity>(sheep_ent).unwrap().protocol_id();
+        let cow_id = app.world.get::<McEntity>(cow_ent).unwrap().protocol_id();
+
+        // Shearing twice only works once.
+        for _ in 0..2 {
+            client_helper.send(&Interact {
+                entity_id: sheep_id.into(),
+                interact: EntityInteraction::Interact(Hand::Main),
+                sneaking: false,
+            });
+        }
+
+        app.update();
+
+        match app.world.get::<McEntity>(sheep_ent).unwrap().data() {
+            TrackedData::Sheep(data) => {
+                assert_eq!(data.get_color(), DyeColor::Lime.id() as u8 | SHEARED_BIT)
+            }
+            _ => unreachable!(),
+        }
+
+        let used = app.world.resource::<Events<ItemUsedOnEntity>>();
+        let events: Vec<_> = used.iter_current_update_events().collect();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].entity, sheep_ent);
+        assert_eq!(events[0].drops[0].item, ItemKind::LimeWool);
+
+        client_helper.send(&SetHeldItemC2s { slot: 1 });
+
+        app.update();
+
+        client_helper.send(&Interact {
+            entity_id: cow_id.into(),
+            interact: EntityInteraction::Interact(Hand::Main),
+            sneaking: false,
+        });
+
+        app.update();
+
+        let inventory = app.world.get::<Inventory>(client_ent).unwrap();
+        assert_eq!(inventory.slot(37).map(|stack| stack.count()), Some(1));
+        assert_eq!(
+            inventory.slot(38).map(|stack| stack.item),
+            Some(ItemKind::MilkBucket)
+        );
+    }
+}
@@ -0,0 +1,313 @@
+//! Randomized equipment for mobs.
+//!
+//! An [`EquipmentLoadout`] describes which items an entity may spawn with.
+//! Every slot of the loadout has a chance of being filled with an item rolled
+//! from a weighted [`ItemPool`], and rolled items may be enchanted. Mob
+//! spawning systems roll a loadout into the [`Equipments`] of new mobs:
+//!
+//! ```
+//! use valence::entity::equipment::EquipmentSlot;
+//! use valence::entity::loadout::EquipmentLoadout;
+//!
+//! let equipments = EquipmentLoadout::skeleton().roll(&mut rand::thread_rng());
+//! assert!(equipments.get(EquipmentSlot::MainHand).is_some());
+//! ```
+
+use rand::Rng;
+use valence_nbt::{compound, Compound, List, Value};
+use valence_protocol::enchant::EnchantmentKind;
+use valence_protocol::{ItemKind, ItemStack};
+
+use crate::entity::equipment::{EquipmentSlot, Equipments};
+
+/// A weighted choice of items, which can be enchanted.
+#[derive(Clone, PartialEq, Default, Debug)]
+pub struct ItemPool {
+    items: Vec<(Option<ItemStack>, u32)>,
+    enchant_chance: f32,
+    enchantments: Vec<EnchantmentKind>,
+}
+
+impl ItemPool {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a single item with the given weight.
+    #[must_use]
+    pub fn with_item(self, item: ItemKind, weight: u32) -> Self {
+        self.with_stack(ItemStack::new(item, 1, None), weight)
+    }
+
+    #[must_use]
+    pub fn with_stack(mut self, stack: ItemStack, weight: u32) -> Self {
+        self.items.push((Some(stack), weight));
+        self
+    }
+
+    /// Adds the chance of rolling no item with the given weight.
+    #[must_use]
+    pub fn with_nothing(mut self, weight: u32) -> Self {
+        self.items.push((None, weight));
+        self
+    }
+
+    /// Sets the chance from 0 to 1 of a rolled item being enchanted with one
+    /// of the given enchantments at a random level.
+    #[must_use]
+    pub fn with_enchantments(
+        mut self,
+        chance: f32,
+        enchantments: impl IntoIterator<Item = EnchantmentKind>,
+    ) -> Self {
+        self.enchant_chance = chance;
+        self.enchantments = enchantments.into_iter().collect();
+        self
+    }
+
+    /// Rolls an item from the pool. Returns `None` if the pool is empty or
+    /// nothing was rolled.
+    pub fn roll(&self, rng: &mut impl Rng) -> Option<ItemStack> {
+        let total: u32 = self.items.iter().map(|(_, weight)| weight).sum();
+
+        if total == 0 {
+            return None;
+        }
+
+        let mut n = rng.gen_range(0..total);
+
+        let mut stack = self
+            .items
+            .iter()
+            .find(|(_, weight)| match n.checked_sub(*weight) {
+                Some(rest) => {
+                    n = rest;
+                    false
+                }
+                None => true,
+            })
+            .and_then(|(stack, _)| stack.clone())?;
+
+        if !self.enchantments.is_empty() && rng.gen::<f32>() < self.enchant_chance {
+            let kind = self.enchantments[rng.gen_range(0..self.enchantments.len())];
+            let level = rng.gen_range(kind.min_level()..=kind.max_level().max(kind.min_level()));
+            add_enchantment(&mut stack, kind, level);
+        }
+
+        Some(stack)
+    }
+}
+
+/// Adds an enchantment to the `Enchantments` list of the item stack.
+fn add_enchantment(stack: &mut ItemStack, kind: EnchantmentKind, level: i16) {
+    let enchantment = compound! {
+        "id" => format!("minecraft:{}", kind.name()),
+        "lvl" => level,
+    };
+
+    let nbt = stack.nbt.get_or_insert_with(Compound::new);
+
+    match nbt.get_mut("Enchantments") {
+        Some(Value::List(List::Compound(list))) => list.push(enchantment),
+        _ => {
+            nbt.insert("Enchantments", List::Compound(vec![enchantment]));
+        }
+    }
+}
+
+/// Gear which is randomly rolled for the equipment slots of an entity.
+#[derive(Clone, PartialEq, Default, Debug)]
+pub struct EquipmentLoadout {
+    slots: Vec<(EquipmentSlot, f32, ItemPool)>,
+}
+
+impl EquipmentLoadout {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Fills the slot with an item from the pool with the given chance from 0
+    /// to 1. Adding the same slot again replaces it.
+    #[must_use]
+    pub fn with_slot(mut self, slot: EquipmentSlot, chance: f32, pool: ItemPool) -> Self {
+        self.slots.retain(|(s, _, _)| *s != slot);
+        self.slots.push((slot, chance, pool));
+        self
+    }
+
+    /// The gear of zombies, which approximates vanilla on normal difficulty.
+    /// Zombies rarely spawn with armor and even more rarely with an iron
+    /// sword or shovel.
+    pub fn zombie() -> Self {
+        Self::armored().with_slot(
+            EquipmentSlot::MainHand,
+            0.01,
+            ItemPool::new()
+                .with_item(ItemKind::IronSword, 1)
+                .with_item(ItemKind::IronShovel, 2)
+                .with_enchantments(
+                    0.25,
+                    [EnchantmentKind::Sharpness, EnchantmentKind::Unbreaking],
+                ),
+        )
+    }
+
+    /// The gear of skeletons, which approximates vanilla on normal
+    /// difficulty. Skeletons always hold a bow and rarely spawn with armor.
+    pub fn skeleton() -> Self {
+        Self::armored().with_slot(
+            EquipmentSlot::MainHand,
+            1.0,
+            ItemPool::new()
+                .with_item(ItemKind::Bow, 1)
+                .with_enchantments(0.1, [EnchantmentKind::Power, EnchantmentKind::Punch]),
+        )
+    }
+
+    /// Armor of a random material in each armor slot. The lower slots are
+    /// less likely to be filled, like vanilla fills the slots from the helmet
+    /// down and may stop at every slot.
+    fn armored() -> Self {
+        let pool = |kinds: [ItemKind; 5]| {
+            // The rarity of the materials from leather to diamond.
+            [370, 487, 128, 12, 1].into_iter().zip(kinds).fold(
+                ItemPool::new().with_enchantments(
+                    0.1,
+                    [EnchantmentKind::Protection, EnchantmentKind::Unbreaking],
+                ),
+                |pool, (weight, kind)| pool.with_item(kind, weight),
+            )
+        };
+
+        Self::new()
+            .with_slot(
+                EquipmentSlot::Head,
+                0.15,
+                pool([
+                    ItemKind::LeatherHelmet,
+                    ItemKind::GoldenHelmet,
+                    ItemKind::ChainmailHelmet,
+                    ItemKind::IronHelmet,
+                    ItemKind::DiamondHelmet,
+                ]),
+            )
+            .with_slot(
+                EquipmentSlot::Chest,
+                0.135,
+                pool([
+                    ItemKind::LeatherChestplate,
+                    ItemKind::GoldenChestplate,
+                    ItemKind::ChainmailChestplate,
+                    ItemKind::IronChestplate,
+                    ItemKind::DiamondChestplate,
+                ]),
+            )
+            .with_slot(
+                EquipmentSlot::Legs,
+                0.12,
+                pool([
+                    ItemKind::LeatherLeggings,
+                    ItemKind::GoldenLeggings,
+                    ItemKind::ChainmailLeggings,
+                    ItemKind::IronLeggings,
+                    ItemKind::DiamondLeggings,
+                ]),
+            )
+            .with_slot(
+                EquipmentSlot::Feet,
+                0.11,
+                pool([
+                    ItemKind::LeatherBoots,
+                    ItemKind::GoldenBoots,
+                    ItemKind::ChainmailBoots,
+                    ItemKind::IronBoots,
+                    ItemKind::DiamondBoots,
+                ]),
+            )
+    }
+
+    /// Rolls new equipment from the loadout.
+    pub fn roll(&self, rng: &mut impl Rng) -> Equipments {
+        let mut equipments = Equipments::new();
+        self.apply(&mut equipments, rng);
+        equipments
+    }
+
+    /// Rolls the loadout into existing equipment. Slots which are not filled
+    /// by the roll keep their items.
+    pub fn apply(&self, equipments: &mut Equipments, rng: &mut impl Rng) {
+        for (slot, chance, pool) in &self.slots {
+            if rng.gen::<f32>() < *chance {
+                if let Some(stack) = pool.roll(rng) {
+                    equipments.set(*slot, stack);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    use super::*;
+    use crate::enchantment::enchantment_level;
+
+    #[test]
+    fn pools_respect_weights() {
+        let mut rng = StdRng::seed_from_u64(0);
+
+        let pool = ItemPool::new()
+            .with_item(ItemKind::Stick, 0)
+            .with_item(ItemKind::Bone, 3)
+            .with_nothing(1);
+
+        let rolls: Vec<_> = (0..1000).map(|_| pool.roll(&mut rng)).collect();
+        let bones = rolls.iter().filter(|r| r.is_some()).count();
+
+        assert!(rolls
+            .iter()
+            .flatten()
+            .all(|stack| stack.item == ItemKind::Bone));
+        assert!((650..850).contains(&bones), "{bones}");
+        assert_eq!(ItemPool::new().roll(&mut rng), None);
+    }
+
+    #[test]
+    fn rolled_items_are_enchanted() {
+        let mut rng = StdRng::seed_from_u64(1);
+
+        let loadout = EquipmentLoadout::new().with_slot(
+            EquipmentSlot::MainHand,
+            1.0,
+            ItemPool::new()
+                .with_item(ItemKind::DiamondSword, 1)
+                .with_enchantments(1.0, [EnchantmentKind::Sharpness]),
+        );
+
+        let mut equipments = Equipments::new().with(
+            EquipmentSlot::Head,
+            ItemStack::new(ItemKind::CarvedPumpkin, 1, None),
+        );
+        loadout.apply(&mut equipments, &mut rng);
+
+        let sword = equipments.get(EquipmentSlot::MainHand).unwrap();
+        let level = enchantment_level(sword, EnchantmentKind::Sharpness);
+        assert!((1..=5).contains(&level), "{level}");
+        assert!(equipments.get(EquipmentSlot::Head).is_some());
+    }
+
+    #[test]
+    fn skeletons_hold_bows() {
+        let mut rng = StdRng::seed_from_u64(2);
+
+        for _ in 0..100 {
+            let equipments = EquipmentLoadout::skeleton().roll(&mut rng);
+            assert_eq!(
+                equipments.get(EquipmentSlot::MainHand).map(|s| s.item),
+                Some(ItemKind::Bow)
+            );
+        }
+    }
+}
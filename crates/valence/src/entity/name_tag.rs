@@ -0,0 +1,256 @@
+//! Names displayed above entities.
+
+use bevy_ecs::prelude::*;
+use glam::DVec3;
+use valence_protocol::Text;
+
+use crate::entity::{EntityKind, McEntity, TrackedData};
+use crate::Despawned;
+
+/// The vertical distance between the lines of a [`NameTag`].
+pub const NAME_TAG_LINE_SPACING: f64 = 0.25;
+
+/// A [`Component`] that sets the custom name of the [`McEntity`] on the same
+/// entity.
+///
+/// The name is part of the entity's tracked data, so clients that start
+/// viewing the entity later on will see the name as well. Removing the
+/// component removes the custom name.
+#[derive(Component, Clone, PartialEq, Debug)]
+pub struct CustomName {
+    /// The name of the entity.
+    pub name: Text,
+    /// If `true`, the name is displayed at all times. Otherwise, the name is
+    /// only displayed while a player is looking directly at the entity, which
+    /// only works for living entities.
+    pub always_visible: bool,
+}
+
+impl CustomName {
+    /// Creates a new custom name which is always visible.
+    pub fn new(name: impl Into<Text>) -> Self {
+        Self {
+            name: name.into(),
+            always_visible: true,
+        }
+    }
+
+    #[must_use]
+    pub fn with_always_visible(mut self, always_visible: bool) -> Self {
+        self.always_visible = always_visible;
+        self
+    }
+}
+
+/// A [`Component`] that displays multiple lines of text above the
+/// [`McEntity`] on the same entity.
+///
+/// Minecraft only supports a single line of text in a custom name. Each line
+/// of a name tag is instead displayed by an invisible marker armor stand
+/// which follows the entity around. The armor stands are despawned together
+/// with the entity or when this component is removed.
+#[derive(Component, Clone, PartialEq, Debug)]
+pub struct NameTag {
+    /// The lines of the name tag from top to bottom.
+    pub lines: Vec<Text>,
+    /// The armor stands displaying the lines, in the same order as `lines`.
+    stands: Vec<Entity>,
+}
+
+impl NameTag {
+    pub fn new<T: Into<Text>>(lines: impl IntoIterator<Item = T>) -> Self {
+        Self {
+            lines: lines.into_iter().map(Into::into).collect(),
+            stands: vec![],
+        }
+    }
+}
+
+/// A [`Component`] on the armor stands spawned for a [`NameTag`].
+#[derive(Component, Copy, Clone, PartialEq, Eq, Debug)]
+pub struct NameTagLine {
+    /// The entity with the [`NameTag`] this line belongs to.
+    pub owner: Entity,
+}
+
+type CustomNameChanged = Or<(Changed<CustomName>, Added<McEntity>)>;
+
+pub(crate) fn update_custom_names(
+    mut entities: Query<(&CustomName, &mut McEntity), CustomNameChanged>,
+) {
+    for (custom_name, mut entity) in &mut entities {
+        let data = entity.data_mut();
+
+        data.set_custom_name(custom_name.name.clone());
+        data.set_name_visible(custom_name.always_visible);
+    }
+}
+
+pub(crate) fn remove_custom_names(
+    removed: RemovedComponents<CustomName>,
+    mut entities: Query<&mut McEntity, Without<CustomName>>,
+) {
+    for entity in removed.iter() {
+        if let Ok(mut entity) = entities.get_mut(entity) {
+            let data = entity.data_mut();
+
+            data.set_custom_name(None);
+            data.set_name_visible(false);
+        }
+    }
+}
+
+fn line_position(owner: &McEntity, line: usize, line_count: usize) -> DVec3 {
+    let mut pos = owner.position();
+    pos.y = owner.hitbox().max.y + (line_count - 1 - line) as f64 * NAME_TAG_LINE_SPACING;
+    pos
+}
+
+pub(crate) fn update_name_tags(
+    mut commands: Commands,
+    mut owners: Query<(Entity, &McEntity, &mut NameTag), Without<Despawned>>,
+    mut stands: Query<&mut McEntity, (With<NameTagLine>, Without<NameTag>)>,
+) {
+    for (owner_id, owner, mut name_tag) in &mut owners {
+        let name_tag = &mut *name_tag;
+        let line_count = name_tag.lines.len();
+
+        for stand in name_tag
+            .stands
+            .drain(line_count.min(name_tag.stands.len())..)
+        {
+            commands.entity(stand).insert(Despawned);
+        }
+
+        for (i, line) in name_tag.lines.iter().enumerate() {
+            let pos = line_position(owner, i, line_count);
+
+            if let Some(&stand_id) = name_tag.stands.get(i) {
+                let Ok(mut stand) = stands.get_mut(stand_id) else {
+                    // The armor stand was spawned this tick.
+                    continue
+                };
+
+                if stand.instance() != owner.instance() {
+                    stand.set_instance(owner.instance());
+                }
+
+                if stand.position() != pos {
+                    stand.set_position(pos);
+                }
+
+                if stand.data().get_custom_name() != Some(line) {
+                    stand.data_mut().set_custom_name(line.clone());
+                }
+            } else {
+                let mut stand = McEntity::new(EntityKind::ArmorStand, owner.instance());
+                stand.set_position(pos);
+
+                if let TrackedData::ArmorStand(data) = stand.data_mut() {
+                    data.set_invisible(true);
+                    data.set_marker(true);
+                    data.set_no_gravity(true);
+                    data.set_custom_name(line.clone());
+                    data.set_name_visible(true);
+                }
+
+                let stand_id = commands
+                    .spawn((stand, NameTagLine { owner: owner_id }))
+                    .id();
+
+                name_tag.stands.push(stand_id);
+            }
+        }
+    }
+}
+
+/// Despawns the armor stands of name tags that were removed or whose owner was
+/// despawned.
+pub(crate) fn despawn_orphaned_name_tag_lines(
+    mut commands: Commands,
+    lines: Query<(Entity, &NameTagLine), Without<Despawned>>,
+    owners: Query<&NameTag, Without<Despawned>>,
+) {
+    for (entity, line) in &lines {
+        if owners
+            .get(line.owner)
+            .map_or(true, |name_tag| !name_tag.stands.contains(&entity))
+        {
+            commands.entity(entity).insert(Despawned);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy_app::App;
+
+    use super::*;
+    use crate::client::Client;
+    use crate::unit_test::util::scenario_single_client;
+
+    #[test]
+    fn name_tag_lines_follow_owner() {
+        let mut app = App::new();
+        let (client_ent, _) = scenario_single_client(&mut app);
+        let instance_ent = app.world.get::<Client>(client_ent).unwrap().instance();
+
+        let owner = app
+            .world
+            .spawn((
+                McEntity::new(EntityKind::Zombie, instance_ent),
+                NameTag::new(["first", "second"]),
+            ))
+            .id();
+
+        app.update();
+
+        let mut lines = app.world.query::<(&McEntity, &NameTagLine)>();
+        assert_eq!(lines.iter(&app.world).count(), 2);
+
+        app.world
+            .get_mut::<NameTag>(owner)
+            .unwrap()
+            .lines
+            .truncate(1);
+
+        app.update();
+
+        let mut lines = app
+            .world
+            .query_filtered::<&NameTagLine, Without<Despawned>>();
+        assert_eq!(lines.iter(&app.world).count(), 1);
+
+        app.world.entity_mut(owner).remove::<NameTag>();
+        app.update();
+
+        assert_eq!(lines.iter(&app.world).count(), 0);
+    }
+
+    #[test]
+    fn custom_name_sets_tracked_data() {
+        let mut app = App::new();
+        let (client_ent, _) = scenario_single_client(&mut app);
+        let instance_ent = app.world.get::<Client>(client_ent).unwrap().instance();
+
+        let entity = app
+            .world
+            .spawn((
+                McEntity::new(EntityKind::Pig, instance_ent),
+                CustomName::new("Bacon"),
+            ))
+            .id();
+
+        app.update();
+
+        let data = app.world.get::<McEntity>(entity).unwrap().data();
+        assert_eq!(data.get_custom_name(), Some(&Text::from("Bacon")));
+        assert!(data.get_name_visible());
+
+        app.world.entity_mut(entity).remove::<CustomName>();
+        app.update();
+
+        let data = app.world.get::<McEntity>(entity).unwrap().data();
+        assert_eq!(data.get_custom_name(), None);
+    }
+}
@@ -0,0 +1,497 @@
+//! Entities riding other entities.
+//!
+//! The entities in the [`Passengers`] component of an entity ride its
+//! [`McEntity`]. Every tick, passengers are moved to the seat of the vehicle
+//! they ride, and the passengers riding them are moved along. Clients riding
+//! an entity are moved on the server without being teleported, so their view
+//! follows the vehicle and the chunks around it are loaded.
+//!
+//! Clients dismount by sneaking, which removes them from the [`Passengers`]
+//! of their vehicle, moves them on top of it and sends a [`DismountEvent`].
+//! Passengers which are despawned are removed from their vehicle as well.
+
+use bevy_ecs::prelude::*;
+use glam::DVec3;
+use rustc_hash::{FxHashMap, FxHashSet};
+use valence_protocol::packets::s2c::play::SetPassengers;
+use valence_protocol::VarInt;
+
+use crate::client::event::PlayerInput;
+use crate::client::Client;
+use crate::entity::{EntityKind, McEntity};
+use crate::Despawned;
+
+/// A [`Component`] with the entities riding the [`McEntity`] on the same
+/// entity.
+///
+/// Passengers can be entities with an [`McEntity`], clients, or both.
+/// Clients in view of the vehicle are shown the passengers whenever they
+/// change.
+#[derive(Component, Clone, PartialEq, Eq, Default, Debug)]
+pub struct Passengers {
+    entities: Vec<Entity>,
+}
+
+impl Passengers {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[must_use]
+    pub fn with(mut self, passenger: Entity) -> Self {
+        self.add(passenger);
+        self
+    }
+
+    /// Adds a passenger. Returns `false` if the entity is already a passenger.
+    pub fn add(&mut self, passenger: Entity) -> bool {
+        if self.entities.contains(&passenger) {
+            return false;
+        }
+
+        self.entities.push(passenger);
+        true
+    }
+
+    /// Removes a passenger. Returns `false` if the entity is not a passenger.
+    pub fn remove(&mut self, passenger: Entity) -> bool {
+        let len = self.entities.len();
+        self.entities.retain(|&e| e != passenger);
+        self.entities.len() != len
+    }
+
+    pub fn clear(&mut self) {
+        self.entities.clear();
+    }
+
+    pub fn contains(&self, passenger: Entity) -> bool {
+        self.entities.contains(&passenger)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entities.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entities.len()
+    }
+
+    /// Returns the passengers in the order they mounted.
+    pub fn iter(&self) -> impl ExactSizeIterator<Item = Entity> + '_ {
+        self.entities.iter().copied()
+    }
+}
+
+/// An event sent when a client dismounts the vehicle it rides.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct DismountEvent {
+    pub client: Entity,
+    pub vehicle: Entity,
+}
+
+/// Returns the height above the feet of the vehicle at which its passengers
+/// sit.
+pub fn mount_height(vehicle: &McEntity) -> f64 {
+    match vehicle.kind() {
+        EntityKind::Boat | EntityKind::ChestBoat => -0.1,
+        EntityKind::Minecart
+        | EntityKind::ChestMinecart
+        | EntityKind::CommandBlockMinecart
+        | EntityKind::FurnaceMinecart
+        | EntityKind::HopperMinecart
+        | EntityKind::SpawnerMinecart
+        | EntityKind::TntMinecart => 0.0,
+        _ => {
+            let hitbox = vehicle.hitbox();
+            (hitbox.max.y - hitbox.min.y) * 0.75
+        }
+    }
+}
+
+/// Returns how far below the seat of a vehicle the feet of a passenger of the
+/// given kind are.
+fn seat_offset(passenger: EntityKind) -> f64 {
+    match passenger {
+        EntityKind::Player => 0.35,
+        _ => 0.0,
+    }
+}
+
+/// Removes clients which asked to dismount from the passengers of their
+/// vehicles, and places them on top of the vehicles like vanilla does for most
+/// entities.
+pub(crate) fn dismount_passengers(
+    mut vehicles: Query<(Entity, &mut Passengers, Option<&McEntity>)>,
+    mut clients: Query<&mut Client>,
+    mut player_input: EventReader<PlayerInput>,
+    mut dismounts: EventWriter<DismountEvent>,
+) {
+    for event in player_input.iter() {
+        if !event.unmount {
+            continue;
+        }
+
+        for (vehicle, mut passengers, mc_entity) in &mut vehicles {
+            if !passengers.contains(event.client) {
+                continue;
+            }
+
+            passengers.remove(event.client);
+
+            if let (Ok(mut client), Some(mc_entity)) = (clients.get_mut(event.client), mc_entity) {
+                let position = mc_entity.position();
+                client.set_position([position.x, mc_entity.hitbox().max.y, position.z]);
+            }
+
+            dismounts.send(DismountEvent {
+                client: event.client,
+                vehicle,
+            });
+        }
+    }
+}
+
+/// Removes despawned entities from the passengers of vehicles.
+pub(crate) fn remove_despawned_passengers(
+    mut vehicles: Query<&mut Passengers>,
+    entities: Query<(), Without<Despawned>>,
+) {
+    for mut passengers in &mut vehicles {
+        if passengers.iter().any(|p| !entities.contains(p)) {
+            passengers.entities.retain(|&p| entities.contains(p));
+        }
+    }
+}
+
+/// Moves passengers to the vehicles they ride, starting with the vehicles
+/// which are not passengers themselves.
+pub(crate) fn move_passengers(
+    vehicles: Query<(Entity, &Passengers), Without<Despawned>>,
+    mut entities: Query<(Option<&mut McEntity>, Option<&mut Client>), Without<Despawned>>,
+) {
+    let riding: FxHashSet<Entity> = vehicles
+        .iter()
+        .flat_map(|(_, passengers)| passengers.iter())
+        .collect();
+
+    let mut moved = FxHashSet::default();
+    let mut stack = vec![];
+
+    for (root, _) in vehicles.iter().filter(|(e, _)| !riding.contains(e)) {
+        stack.push(root);
+
+        while let Some(vehicle) = stack.pop() {
+            let Ok((Some(mc_entity), _)) = entities.get(vehicle) else {
+                continue;
+            };
+
+            let Ok((_, passengers)) = vehicles.get(vehicle) else {
+                continue;
+            };
+
+            let instance = mc_entity.instance();
+            let seat = mc_entity.position() + DVec3::new(0.0, mount_height(mc_entity), 0.0);
+
+            for passenger in passengers.iter() {
+                // A passenger can only ride one vehicle.
+                if !moved.insert(passenger) {
+                    continue;
+                }
+
+                let Ok((mc_entity, client)) = entities.get_mut(passenger) else {
+                    continue;
+                };
+
+                // Clients without an entity are seated like players.
+                let kind = mc_entity
+                    .as_ref()
+                    .map_or(EntityKind::Player, |mc_entity| mc_entity.kind());
+
+                let position = seat - DVec3::new(0.0, seat_offset(kind), 0.0);
+
+                if let Some(mut mc_entity) = mc_entity {
+                    if mc_entity.instance() == instance && mc_entity.position() != position {
+                        mc_entity.set_position(position);
+                    }
+                }
+
+                if let Some(mut client) = client {
+                    if client.instance() == instance {
+                        client.set_position_unsynced(position);
+                    }
+                }
+
+                stack.push(passenger);
+            }
+        }
+    }
+}
+
+/// Stores the protocol IDs of vehicles and their passengers in the
+/// [`McEntity`]s of both. Clients in view of a vehicle are sent its passengers
+/// when they change, and clients which see one of them for the first time are
+/// sent the passengers along with the spawn packets.
+pub(crate) fn update_passenger_ids(
+    vehicles: Query<(Entity, &Passengers)>,
+    mut entities: Query<(Entity, &mut McEntity)>,
+) {
+    let mut passenger_ids = FxHashMap::default();
+    let mut vehicle_ids = FxHashMap::default();
+
+    for (vehicle, passengers) in &vehicles {
+        let Ok((_, mc_entity)) = entities.get(vehicle) else {
+            continue;
+        };
+
+        let vehicle_id = VarInt(mc_entity.protocol_id());
+
+        let ids: Vec<_> = passengers
+            .iter()
+            .filter_map(|passenger| entities.get(passenger).ok())
+            .map(|(_, rider)| VarInt(rider.protocol_id()))
+            .collect();
+
+        for passenger in passengers.iter() {
+            vehicle_ids.insert(passenger, (vehicle_id, ids.clone()));
+        }
+
+        passenger_ids.insert(vehicle, ids);
+    }
+
+    for (entity, mut mc_entity) in &mut entities {
+        let ids = passenger_ids.remove(&entity).unwrap_or_default();
+        if mc_entity.passenger_ids() != ids.as_slice() {
+            mc_entity.set_passenger_ids(ids);
+        }
+
+        let vehicle = vehicle_ids.remove(&entity);
+        if mc_entity.vehicle_ids() != vehicle.as_ref() {
+            mc_entity.set_vehicle_ids(vehicle);
+        }
+    }
+}
+
+/// Seats clients in the vehicles they ride. The passengers of vehicles are
+/// sent to the clients in view of them with the update packets of the
+/// vehicles, but clients see themselves with the reserved entity ID 0 instead
+/// of the protocol ID of their own [`McEntity`]. This runs after the clients
+/// are updated, so the seat is sent after the passengers of the vehicle.
+pub(crate) fn seat_clients(
+    vehicles: Query<(Entity, &McEntity, &Passengers), Without<Despawned>>,
+    entities: Query<&McEntity, Without<Despawned>>,
+    mut clients: Query<(Entity, &mut Client, Option<&McEntity>)>,
+) {
+    let mut seats = FxHashMap::default();
+
+    for (vehicle, mc_entity, passengers) in &vehicles {
+        for passenger in passengers.iter() {
+            let Ok((_, _, self_entity)) = clients.get(passenger) else {
+                continue;
+            };
+
+            let mut ids = mc_entity.passenger_ids().to_vec();
+
+            match self_entity.and_then(|e| ids.iter_mut().find(|id| id.0 == e.protocol_id())) {
+                Some(id) => *id = VarInt(0),
+                None => ids.push(VarInt(0)),
+            }
+
+            seats.insert(passenger, (vehicle, ids));
+        }
+    }
+
+    for (entity, mut client, _) in &mut clients {
+        let seat = seats.remove(&entity);
+
+        if client.seat == seat {
+            continue;
+        }
+
+        // Clients without an `McEntity` are not among the passengers sent to
+        // clients in view of the vehicle, so they are shown the vehicle they
+        // left again.
+        if let Some((old_vehicle, _)) = &client.seat {
+            if seat
+                .as_ref()
+                .map_or(true, |(vehicle, _)| vehicle != old_vehicle)
+            {
+                if let Ok(mc_entity) = entities.get(*old_vehicle) {
+                    client.write_packet(&SetPassengers {
+                        entity_id: VarInt(mc_entity.protocol_id()),
+                        passengers: mc_entity.passenger_ids().to_vec(),
+                    });
+                }
+            }
+        }
+
+        if let Some((vehicle, ids)) = &seat {
+            if let Ok(mc_entity) = entities.get(*vehicle) {
+                client.write_packet(&SetPassengers {
+                    entity_id: VarInt(mc_entity.protocol_id()),
+                    passengers: ids.clone(),
+                });
+            }
+        }
+
+        client.seat = seat;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy_app::App;
+    use valence_protocol::packets::c2s::play::PlayerInput as PlayerInputPacket;
+    use valence_protocol::packets::S2cPlayPacket;
+    use valence_protocol::types::PlayerInputFlags;
+
+    use super::*;
+    use crate::assert_packet_count;
+    use crate::unit_test::util::{create_mock_client, gen_client_info, scenario_single_client};
+
+    /// Returns the last passengers sent for the vehicle with the protocol ID.
+    fn last_passengers(packets: &[S2cPlayPacket], vehicle: i32) -> Option<Vec<VarInt>> {
+        packets
+            .iter()
+            .filter_map(|pkt| match pkt {
+                S2cPlayPacket::SetPassengers(pkt) if pkt.entity_id.0 == vehicle => {
+                    Some(pkt.passengers.clone())
+                }
+                _ => None,
+            })
+            .last()
+    }
+
+    #[test]
+    fn passengers_follow_their_vehicle() -> anyhow::Result<()> {
+        let mut app = App::new();
+        let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+        let instance_ent = app.world.get::<Client>(client_ent).unwrap().instance();
+
+        // A client riding a zombie riding a pig.
+        let zombie = app
+            .world
+            .spawn((
+                McEntity::new(EntityKind::Zombie, instance_ent),
+                Passengers::new().with(client_ent),
+            ))
+            .id();
+
+        let mut pig = McEntity::new(EntityKind::Pig, instance_ent);
+        pig.set_position([100.0, 0.0, 0.0]);
+        let pig = app.world.spawn((pig, Passengers::new().with(zombie))).id();
+
+        app.update();
+        app.update();
+
+        let zombie_id = app.world.get::<McEntity>(zombie).unwrap().protocol_id();
+        let pig_id = app.world.get::<McEntity>(pig).unwrap().protocol_id();
+
+        // The client sees itself riding the zombie with the reserved ID 0.
+        let sent_packets = client_helper.collect_sent()?;
+        assert_eq!(
+            last_passengers(&sent_packets, pig_id),
+            Some(vec![VarInt(zombie_id)])
+        );
+        assert_eq!(
+            last_passengers(&sent_packets, zombie_id),
+            Some(vec![VarInt(0)])
+        );
+
+        // Nothing is sent again while the passengers stay the same.
+        app.update();
+
+        let sent_packets = client_helper.collect_sent()?;
+        assert_packet_count!(sent_packets, 0, S2cPlayPacket::SetPassengers(_));
+
+        let zombie_pos = app.world.get::<McEntity>(zombie).unwrap().position();
+        assert_eq!(zombie_pos.x, 100.0);
+        assert!(zombie_pos.y > 0.0);
+
+        // The view of the client follows the vehicle without a teleport.
+        let client = app.world.get::<Client>(client_ent).unwrap();
+        assert_eq!(client.position().x, 100.0);
+        assert!(client.position().y > zombie_pos.y);
+
+        Ok(())
+    }
+
+    #[test]
+    fn clients_dismount_by_sneaking() -> anyhow::Result<()> {
+        let mut app = App::new();
+        let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+        let instance_ent = app.world.get::<Client>(client_ent).unwrap().instance();
+
+        let uuid = app.world.get::<Client>(client_ent).unwrap().uuid();
+        app.world.entity_mut(client_ent).insert(McEntity::with_uuid(
+            EntityKind::Player,
+            instance_ent,
+            uuid,
+        ));
+
+        let chair = app
+            .world
+            .spawn((
+                McEntity::new(EntityKind::ArmorStand, instance_ent),
+                Passengers::new().with(client_ent),
+            ))
+            .id();
+
+        app.update();
+
+        // Clients seeing the vehicle and the passenger for the first time are
+        // sent the passengers along with both.
+        let (mut other, mut other_helper) = create_mock_client(gen_client_info("other"));
+        other.set_instance(instance_ent);
+        app.world.spawn(other);
+
+        app.update();
+
+        let sent_packets = other_helper.collect_sent()?;
+        assert_packet_count!(sent_packets, 2, S2cPlayPacket::SetPassengers(_));
+
+        let chair_id = app.world.get::<McEntity>(chair).unwrap().protocol_id();
+        let player_id = app.world.get::<McEntity>(client_ent).unwrap().protocol_id();
+        assert_eq!(
+            last_passengers(&sent_packets, chair_id),
+            Some(vec![VarInt(player_id)])
+        );
+
+        // The riding client sees itself with the reserved ID 0 instead.
+        let sent_packets = client_helper.collect_sent()?;
+        assert_eq!(
+            last_passengers(&sent_packets, chair_id),
+            Some(vec![VarInt(0)])
+        );
+
+        client_helper.send(&PlayerInputPacket {
+            sideways: 0.0,
+            forward: 0.0,
+            flags: PlayerInputFlags::new().with_unmount(true),
+        });
+
+        app.update();
+
+        assert!(app.world.get::<Passengers>(chair).unwrap().is_empty());
+
+        // Both clients are shown that the chair is empty.
+        for helper in [&mut client_helper, &mut other_helper] {
+            let sent_packets = helper.collect_sent()?;
+            assert_eq!(last_passengers(&sent_packets, chair_id), Some(vec![]));
+        }
+
+        let chair_top = app.world.get::<McEntity>(chair).unwrap().hitbox().max.y;
+        let client = app.world.get::<Client>(client_ent).unwrap();
+        assert_eq!(client.position().y, chair_top);
+
+        let dismounts = app.world.resource::<Events<DismountEvent>>();
+        assert_eq!(
+            dismounts.get_reader().iter(dismounts).collect::<Vec<_>>(),
+            [&DismountEvent {
+                client: client_ent,
+                vehicle: chair,
+            }]
+        );
+
+        Ok(())
+    }
+}
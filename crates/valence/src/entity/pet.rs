@@ -0,0 +1,464 @@
+//! Taming and ownership of wolves, cats and parrots.
+//!
+//! Entities with a [`Pet`] component can be tamed by clients feeding them the
+//! right item: bones for wolves, raw cod or salmon for cats and seeds for
+//! parrots. Tamed pets get an [`Owner`], which can also be inserted directly to
+//! spawn pets that are already tamed.
+//!
+//! The owner sits and stands their pet by interacting with it. Standing pets
+//! follow their owner around, and teleport to them once they fall too far
+//! behind. Valence does not simulate the movement of entities, so pets walk
+//! straight towards their owner without pathfinding.
+//!
+//! Valence does not keep track of entity health either. Combat logic should
+//! check [`Owner::is_protected_from`] before damaging a pet.
+
+use std::collections::HashMap;
+
+use bevy_ecs::prelude::*;
+use rand::Rng;
+use uuid::Uuid;
+use valence_protocol::types::{EntityInteraction, GameMode, Hand};
+use valence_protocol::ItemKind;
+
+use crate::budget::{SheddableWork, TickBudget};
+use crate::client::event::InteractWithEntity;
+use crate::client::Client;
+use crate::damage::EntityDamage;
+use crate::entity::breeding::{is_breeding_item, Breedable};
+use crate::entity::{EntityKind, EntityStatus, McEntity, McEntityManager, TrackedData};
+use crate::inventory::{consume_held_item, held_item, Inventory};
+use crate::math::to_yaw_and_pitch;
+use crate::tick_rate::SkippedInstances;
+use crate::Despawned;
+
+/// A [`Component`] for tameable animals. The [`McEntity`] on the same entity
+/// must be a wolf, cat or parrot.
+///
+/// Pets without an [`Owner`] are untamed.
+#[derive(Component, Clone, PartialEq, Debug)]
+pub struct Pet {
+    /// If the pet is sitting. Sitting pets don't follow their owner.
+    ///
+    /// # Default Value
+    ///
+    /// `false`
+    pub sitting: bool,
+    /// The distance to the owner at which the pet starts following them.
+    ///
+    /// # Default Value
+    ///
+    /// `10.0`
+    pub follow_distance: f64,
+    /// The distance to the owner at which the pet stops following them.
+    ///
+    /// # Default Value
+    ///
+    /// `2.0`
+    pub stop_distance: f64,
+    /// The distance to the owner at which the pet teleports to them instead
+    /// of walking.
+    ///
+    /// # Default Value
+    ///
+    /// `12.0`
+    pub teleport_distance: f64,
+    /// The distance in blocks the pet walks per tick while following.
+    ///
+    /// # Default Value
+    ///
+    /// `0.3`
+    pub speed: f64,
+    following: bool,
+}
+
+impl Pet {
+    pub fn new() -> Self {
+        Self {
+            sitting: false,
+            follow_distance: 10.0,
+            stop_distance: 2.0,
+            teleport_distance: 12.0,
+            speed: 0.3,
+            following: false,
+        }
+    }
+
+    #[must_use]
+    pub fn with_sitting(mut self, sitting: bool) -> Self {
+        self.sitting = sitting;
+        self
+    }
+
+    /// Returns if the pet is currently following its owner.
+    pub fn is_following(&self) -> bool {
+        self.following
+    }
+}
+
+impl Default for Pet {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A [`Component`] for the owner of a tamed [`Pet`].
+#[derive(Component, Copy, Clone, PartialEq, Eq, Debug)]
+pub struct Owner {
+    /// The UUID of the player that tamed the pet.
+    pub uuid: Uuid,
+}
+
+impl Owner {
+    pub fn new(uuid: Uuid) -> Self {
+        Self { uuid }
+    }
+
+    /// Returns if the pet is protected from damage dealt by the player with
+    /// the given UUID. Pets can't be hurt by their owner.
+    pub fn is_protected_from(&self, attacker: Uuid) -> bool {
+        self.uuid == attacker
+    }
+}
+
+/// An event sent when a client tames a [`Pet`].
+#[derive(Clone, Debug)]
+pub struct PetTamed {
+    pub pet: Entity,
+    pub client: Entity,
+}
+
+/// Returns if a pet of the given kind can be tamed with the item.
+fn is_taming_item(kind: EntityKind, item: ItemKind) -> bool {
+    match kind {
+        EntityKind::Wolf => item == ItemKind::Bone,
+        EntityKind::Cat => matches!(item, ItemKind::Cod | ItemKind::Salmon),
+        EntityKind::Parrot => matches!(
+            item,
+            ItemKind::WheatSeeds
+                | ItemKind::MelonSeeds
+                | ItemKind::PumpkinSeeds
+                | ItemKind::BeetrootSeeds
+        ),
+        _ => false,
+    }
+}
+
+/// Returns the chance that feeding a pet of the given kind its taming item
+/// tames it.
+fn taming_chance(kind: EntityKind) -> f64 {
+    match kind {
+        EntityKind::Parrot => 1.0 / 10.0,
+        _ => 1.0 / 3.0,
+    }
+}
+
+type PetQuery<'a> = (
+    &'a mut Pet,
+    Option<&'a Owner>,
+    &'a mut McEntity,
+    Option<&'a Breedable>,
+);
+
+pub(crate) fn interact_with_pets(
+    mut commands: Commands,
+    manager: Res<McEntityManager>,
+    mut clients: Query<(&Client, &mut Inventory)>,
+    mut pets: Query<PetQuery, (Without<Client>, Without<Despawned>)>,
+    mut interactions: EventReader<InteractWithEntity>,
+    mut tamed: EventWriter<PetTamed>,
+) {
+    let mut rng = rand::thread_rng();
+
+    for event in interactions.iter() {
+        let EntityInteraction::Interact(hand) = event.interact else {
+            continue
+        };
+
+        let Some(pet_entity) = manager.get_with_protocol_id(event.entity_id) else {
+            continue
+        };
+
+        let Ok((mut pet, owner, mut mc_entity, breedable)) = pets.get_mut(pet_entity) else {
+            continue
+        };
+
+        let Ok((client, mut inventory)) = clients.get_mut(event.client) else {
+            continue
+        };
+
+        if client.game_mode() == GameMode::Spectator {
+            continue;
+        }
+
+        let kind = mc_entity.kind();
+        let held = held_item(client, &inventory, hand).map(|stack| stack.item);
+
+        if let Some(owner) = owner {
+            // Tamed pets that can breed are fed instead.
+            let feeding =
+                breedable.is_some() && held.map_or(false, |item| is_breeding_item(kind, item));
+
+            // Clients may send an interaction for each hand, so only the main
+            // hand toggles sitting.
+            if owner.uuid == client.uuid() && hand == Hand::Main && !feeding {
+                pet.sitting = !pet.sitting;
+                pet.following = false;
+            }
+
+            continue;
+        }
+
+        if !held.map_or(false, |item| is_taming_item(kind, item)) {
+            continue;
+        }
+
+        consume_held_item(client, &mut inventory, hand);
+
+        if rng.gen_bool(taming_chance(kind)) {
+            pet.sitting = true;
+            pet.following = false;
+            commands
+                .entity(pet_entity)
+                .insert(Owner::new(client.uuid()));
+            mc_entity.trigger_status(EntityStatus::AddPositivePlayerReactionParticles);
+            tamed.send(PetTamed {
+                pet: pet_entity,
+                client: event.client,
+            });
+        } else {
+            mc_entity.trigger_status(EntityStatus::AddNegativePlayerReactionParticles);
+        }
+    }
+}
+
+/// Makes sitting pets stand up when they are hurt by anyone except their
+/// owner.
+pub(crate) fn stand_up_hurt_pets(
+    manager: Res<McEntityManager>,
+    clients: Query<&Client>,
+    mut pets: Query<(&mut Pet, &Owner)>,
+    mut interactions: EventReader<InteractWithEntity>,
+    mut damage: EventReader<EntityDamage>,
+) {
+    let attacks = interactions
+        .iter()
+        .filter(|event| event.interact == EntityInteraction::Attack)
+        .filter_map(|event| {
+            Some((
+                manager.get_with_protocol_id(event.entity_id)?,
+                Some(event.client),
+            ))
+        });
+
+    let damage = damage.iter().map(|event| (event.entity, event.source));
+
+    for (entity, source) in attacks.chain(damage) {
+        let Ok((mut pet, owner)) = pets.get_mut(entity) else {
+            continue
+        };
+
+        let attacker = source.and_then(|source| clients.get(source).ok());
+
+        if pet.sitting && !attacker.map_or(false, |client| owner.is_protected_from(client.uuid())) {
+            pet.sitting = false;
+        }
+    }
+}
+
+pub(crate) fn follow_owners(
+    mut pets: Query<(&mut Pet, &Owner, &mut McEntity), Without<Client>>,
+    clients: Query<&Client>,
+    budget: Res<TickBudget>,
+    skipped: Res<SkippedInstances>,
+) {
+    if budget.is_shedding(SheddableWork::EntityAi) {
+        return;
+    }
+
+    let owners: HashMap<_, _> = clients
+        .iter()
+        .map(|client| (client.uuid(), client))
+        .collect();
+
+    for (mut pet, owner, mut mc_entity) in &mut pets {
+        if pet.sitting || skipped.contains(mc_entity.instance()) {
+            continue;
+        }
+
+        let Some(client) = owners.get(&owner.uuid) else {
+            continue
+        };
+
+        if client.instance() != mc_entity.instance() {
+            pet.following = false;
+            continue;
+        }
+
+        let offset = client.position() - mc_entity.position();
+        let distance = offset.length();
+
+        if distance >= pet.teleport_distance {
+            mc_entity.set_position(client.position());
+            pet.following = false;
+            continue;
+        }
+
+        if distance > pet.follow_distance {
+            pet.following = true;
+        } else if distance <= pet.stop_distance {
+            pet.following = false;
+        }
+
+        if !pet.following {
+            continue;
+        }
+
+        let step = pet.speed.min(distance - pet.stop_distance);
+        let (yaw, _) = to_yaw_and_pitch(offset.normalize().as_vec3());
+
+        let position = mc_entity.position() + offset / distance * step;
+
+        mc_entity.set_position(position);
+        mc_entity.set_yaw(yaw);
+        mc_entity.set_head_yaw(yaw);
+    }
+}
+
+type PetData<'a> = (&'a Pet, Option<&'a Owner>, &'a mut McEntity);
+
+type ChangedPet = Or<(Changed<Pet>, Changed<Owner>)>;
+
+type UntamedPet<'a> = (&'a Pet, &'a mut McEntity);
+
+type PetDataQueries<'w, 's> = ParamSet<
+    'w,
+    's,
+    (
+        Query<'w, 's, PetData<'static>, ChangedPet>,
+        Query<'w, 's, UntamedPet<'static>, Without<Owner>>,
+    ),
+>;
+
+pub(crate) fn update_pet_data(mut pets: PetDataQueries, removed_owners: RemovedComponents<Owner>) {
+    fn set_pet_data(mc_entity: &mut McEntity, sitting: bool, owner: Option<Uuid>) {
+        match mc_entity.data_mut() {
+            TrackedData::Wolf(data) => {
+                data.set_sitting_pose(sitting);
+                data.set_tamed(owner.is_some());
+                data.set_owner_uuid(owner);
+            }
+            TrackedData::Cat(data) => {
+                data.set_sitting_pose(sitting);
+                data.set_tamed(owner.is_some());
+                data.set_owner_uuid(owner);
+            }
+            TrackedData::Parrot(data) => {
+                data.set_sitting_pose(sitting);
+                data.set_tamed(owner.is_some());
+                data.set_owner_uuid(owner);
+            }
+            _ => {}
+        }
+    }
+
+    for (pet, owner, mut mc_entity) in &mut pets.p0() {
+        set_pet_data(&mut mc_entity, pet.sitting, owner.map(|owner| owner.uuid));
+    }
+
+    for entity in removed_owners.iter() {
+        if let Ok((pet, mut mc_entity)) = pets.p1().get_mut(entity) {
+            set_pet_data(&mut mc_entity, pet.sitting, None);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy_app::App;
+    use glam::DVec3;
+    use valence_protocol::packets::c2s::play::Interact;
+
+    use super::*;
+    use crate::unit_test::util::scenario_single_client;
+
+    #[test]
+    fn pets_follow_and_sit() {
+        let mut app = App::new();
+        let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+        let client = app.world.get::<Client>(client_ent).unwrap();
+        let instance_ent = client.instance();
+        let owner_pos = client.position();
+        let uuid = client.uuid();
+
+        let mut wolf = McEntity::new(EntityKind::Wolf, instance_ent);
+        wolf.set_position(owner_pos + DVec3::new(11.0, 0.0, 0.0));
+
+        let wolf_ent = app.world.spawn((wolf, Pet::new(), Owner::new(uuid))).id();
+
+        app.update();
+
+        // The wolf is too far away and walks towards its owner.
+        let wolf = app.world.get::<McEntity>(wolf_ent).unwrap();
+        assert!(app.world.get::<Pet>(wolf_ent).unwrap().is_following());
+        assert!((wolf.position().distance(owner_pos) - 10.7).abs() < 1e-9);
+
+        match wolf.data() {
+            TrackedData::Wolf(data) => {
+                assert!(data.get_tamed());
+                assert_eq!(data.get_owner_uuid(), Some(uuid));
+            }
+            _ => unreachable!(),
+        }
+
+        let wolf_id = wolf.protocol_id();
+
+        client_helper.send(&Interact {
+            entity_id: wolf_id.into(),
+            interact: EntityInteraction::Interact(Hand::Main),
+            sneaking: false,
+        });
+
+        app.update();
+
+        assert!(app.world.get::<Pet>(wolf_ent).unwrap().sitting);
+
+        // Sitting pets stay put, even when their owner is far away.
+        app.world
+            .get_mut::<McEntity>(wolf_ent)
+            .unwrap()
+            .set_position(owner_pos + DVec3::new(20.0, 0.0, 0.0));
+
+        app.update();
+
+        assert_eq!(
+            app.world.get::<McEntity>(wolf_ent).unwrap().position(),
+            owner_pos + DVec3::new(20.0, 0.0, 0.0)
+        );
+
+        // Being hit by the owner doesn't make the wolf stand up.
+        client_helper.send(&Interact {
+            entity_id: wolf_id.into(),
+            interact: EntityInteraction::Attack,
+            sneaking: false,
+        });
+
+        app.update();
+
+        assert!(app.world.get::<Pet>(wolf_ent).unwrap().sitting);
+
+        client_helper.send(&Interact {
+            entity_id: wolf_id.into(),
+            interact: EntityInteraction::Interact(Hand::Main),
+            sneaking: false,
+        });
+
+        app.update();
+
+        // Standing pets teleport to their owner.
+        assert_eq!(
+            app.world.get::<McEntity>(wolf_ent).unwrap().position(),
+            owner_pos
+        );
+    }
+}
@@ -0,0 +1,170 @@
+//! Pushing apart entities with overlapping hitboxes.
+
+use bevy_ecs::prelude::*;
+use glam::DVec3;
+pub use valence_protocol::packets::s2c::update_teams::CollisionRule;
+
+use crate::client::Client;
+use crate::entity::McEntity;
+use crate::tick_rate::SkippedInstances;
+use crate::Despawned;
+
+/// The distance an entity is pushed per tick when another entity is at the
+/// edge of its hitbox.
+const PUSH_STRENGTH: f64 = 0.05;
+
+/// A [`Component`] that makes the [`McEntity`] on the same entity push and be
+/// pushed by other pushable entities in the same instance.
+///
+/// While the hitboxes of two entities overlap, they are pushed horizontally
+/// away from each other like in vanilla. Valence does not simulate the
+/// movement of entities, so pushed entities are moved directly.
+///
+/// Clients push themselves away from the entities they can see, so entities
+/// with a [`Client`] are never moved by this component but still push other
+/// entities. The team and collision rule should match what the clients were
+/// sent for this to be consistent.
+///
+/// Every pair of pushable entities is checked each tick, so this is not
+/// suited for large numbers of entities.
+#[derive(Component, Clone, PartialEq, Eq, Debug)]
+pub struct Pushable {
+    /// The name of the team the entity is in.
+    pub team: Option<String>,
+    /// Determines which entities this entity collides with, depending on
+    /// their team.
+    ///
+    /// # Default Value
+    ///
+    /// [`CollisionRule::Always`]
+    pub collision_rule: CollisionRule,
+}
+
+impl Pushable {
+    pub fn new() -> Self {
+        Self {
+            team: None,
+            collision_rule: CollisionRule::Always,
+        }
+    }
+
+    #[must_use]
+    pub fn with_team(mut self, team: impl Into<String>, collision_rule: CollisionRule) -> Self {
+        self.team = Some(team.into());
+        self.collision_rule = collision_rule;
+        self
+    }
+
+    /// Returns if this entity and `other` push each other according to their
+    /// teams and collision rules.
+    pub fn collides_with(&self, other: &Pushable) -> bool {
+        let same_team = self.team.is_some() && self.team == other.team;
+
+        [self.collision_rule, other.collision_rule]
+            .into_iter()
+            .all(|rule| match rule {
+                CollisionRule::Always => true,
+                CollisionRule::Never => false,
+                CollisionRule::PushOtherTeams => !same_team,
+                CollisionRule::PushOwnTeam => same_team,
+            })
+    }
+}
+
+impl Default for Pushable {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+type PushableEntity<'a> = (&'a mut McEntity, &'a Pushable, Option<&'a Client>);
+
+pub(crate) fn push_entities(
+    mut entities: Query<PushableEntity, Without<Despawned>>,
+    skipped: Res<SkippedInstances>,
+) {
+    let mut combinations = entities.iter_combinations_mut();
+
+    while let Some([(mut a, a_pushable, a_client), (mut b, b_pushable, b_client)]) =
+        combinations.fetch_next()
+    {
+        if a.instance() != b.instance()
+            || skipped.contains(a.instance())
+            || !a_pushable.collides_with(b_pushable)
+            || !a.hitbox().intersects(&b.hitbox())
+        {
+            continue;
+        }
+
+        let offset = b.position() - a.position();
+        let dist = offset.x.abs().max(offset.z.abs());
+
+        // Entities at the same position can't be pushed apart.
+        if dist < 0.01 {
+            continue;
+        }
+
+        let dist = dist.sqrt();
+        let push =
+            DVec3::new(offset.x, 0.0, offset.z) / dist * (1.0 / dist).min(1.0) * PUSH_STRENGTH;
+
+        if a_client.is_none() {
+            let pos = a.position() - push;
+            a.set_position(pos);
+        }
+
+        if b_client.is_none() {
+            let pos = b.position() + push;
+            b.set_position(pos);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy_app::App;
+
+    use super::*;
+    use crate::entity::EntityKind;
+    use crate::unit_test::util::scenario_single_client;
+
+    #[test]
+    fn overlapping_entities_are_pushed_apart() {
+        let mut app = App::new();
+        let (client_ent, _) = scenario_single_client(&mut app);
+        let instance_ent = app.world.get::<Client>(client_ent).unwrap().instance();
+
+        let mut spawn_pig = |x: f64, pushable: Pushable| {
+            let mut pig = McEntity::new(EntityKind::Pig, instance_ent);
+            pig.set_position([x, 0.0, 0.0]);
+            app.world.spawn((pig, pushable)).id()
+        };
+
+        let a = spawn_pig(0.0, Pushable::new());
+        let b = spawn_pig(0.5, Pushable::new());
+        let c = spawn_pig(10.0, Pushable::new().with_team("c", CollisionRule::Never));
+        let d = spawn_pig(10.5, Pushable::new());
+
+        app.update();
+
+        let x = |app: &App, entity| app.world.get::<McEntity>(entity).unwrap().position().x;
+
+        assert!(x(&app, a) < 0.0);
+        assert!(x(&app, b) > 0.5);
+        assert_eq!(x(&app, c), 10.0);
+        assert_eq!(x(&app, d), 10.5);
+    }
+
+    #[test]
+    fn team_collision_rules() {
+        let own = Pushable::new().with_team("red", CollisionRule::PushOwnTeam);
+        let other = Pushable::new().with_team("red", CollisionRule::PushOtherTeams);
+        let blue = Pushable::new().with_team("blue", CollisionRule::Always);
+
+        assert!(own.collides_with(&own));
+        assert!(!own.collides_with(&blue));
+        assert!(!other.collides_with(&other));
+        assert!(other.collides_with(&blue));
+        assert!(blue.collides_with(&Pushable::new()));
+    }
+}
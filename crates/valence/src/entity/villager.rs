@@ -0,0 +1,401 @@
+//! Villager professions, levels and trade offers.
+//!
+//! The [`Villager`] component holds the profession and experience of a
+//! villager and keeps the villager data seen by clients in sync. The offers a
+//! villager makes are stored in [`VillagerOffers`], which takes the level of
+//! the villager and the reputation of the trading player into account, and
+//! restocks the offers periodically.
+
+use std::collections::HashMap;
+
+use bevy_ecs::prelude::*;
+use uuid::Uuid;
+pub use valence_protocol::entity_meta::{VillagerData, VillagerKind, VillagerProfession};
+use valence_protocol::types::MerchantTrade;
+use valence_protocol::ItemStack;
+
+use crate::budget::{SheddableWork, TickBudget};
+use crate::entity::{McEntity, TrackedData};
+use crate::server::Server;
+use crate::tick_rate::SkippedInstances;
+
+/// The experience needed to reach each villager level, starting at level 1.
+const LEVEL_XP: [i32; 5] = [0, 10, 70, 150, 250];
+
+/// The highest level a villager can reach.
+pub const MAX_VILLAGER_LEVEL: i32 = 5;
+
+/// A [`Component`] for villagers. The [`McEntity`] on the same entity must be
+/// a villager or a zombie villager.
+///
+/// The level of the villager follows from its experience, and the profession,
+/// type and level are shown to clients.
+#[derive(Component, Copy, Clone, PartialEq, Eq, Debug)]
+pub struct Villager {
+    /// The biome type of the villager, which determines its clothing.
+    pub kind: VillagerKind,
+    pub profession: VillagerProfession,
+    xp: i32,
+}
+
+impl Villager {
+    pub fn new(profession: VillagerProfession) -> Self {
+        Self {
+            kind: VillagerKind::Plains,
+            profession,
+            xp: 0,
+        }
+    }
+
+    #[must_use]
+    pub fn with_kind(mut self, kind: VillagerKind) -> Self {
+        self.kind = kind;
+        self
+    }
+
+    /// Returns the trading experience of the villager.
+    pub fn xp(&self) -> i32 {
+        self.xp
+    }
+
+    /// Sets the trading experience of the villager. Villagers without a
+    /// profession, or with the nitwit profession, never gain experience.
+    pub fn set_xp(&mut self, xp: i32) {
+        if self.can_trade() {
+            self.xp = xp.max(0);
+        }
+    }
+
+    /// Adds trading experience and returns if the villager leveled up.
+    pub fn add_xp(&mut self, xp: i32) -> bool {
+        let level = self.level();
+        self.set_xp(self.xp.saturating_add(xp));
+        self.level() > level
+    }
+
+    /// Returns the level of the villager from 1 (novice) to 5 (master).
+    pub fn level(&self) -> i32 {
+        LEVEL_XP.iter().filter(|&&xp| self.xp >= xp).count() as i32
+    }
+
+    /// Returns the experience needed to reach the next level, or `None` if
+    /// the villager is a master.
+    pub fn next_level_xp(&self) -> Option<i32> {
+        LEVEL_XP.get(self.level() as usize).copied()
+    }
+
+    /// Returns if the villager has a profession it can trade with.
+    pub fn can_trade(&self) -> bool {
+        !matches!(
+            self.profession,
+            VillagerProfession::None | VillagerProfession::Nitwit
+        )
+    }
+
+    pub fn to_villager_data(&self) -> VillagerData {
+        VillagerData::new(self.kind, self.profession, self.level())
+    }
+}
+
+/// A [`Component`] storing how players are regarded by a villager.
+///
+/// Reputation ranges from `-700` to `700` and changes the prices of the
+/// villager's offers: every point of reputation lowers the price of an offer
+/// by its price multiplier.
+#[derive(Component, Clone, PartialEq, Eq, Default, Debug)]
+pub struct VillagerReputation {
+    reputation: HashMap<Uuid, i32>,
+}
+
+impl VillagerReputation {
+    pub const MIN: i32 = -700;
+    pub const MAX: i32 = 700;
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the reputation of the player with the given UUID.
+    pub fn get(&self, player: Uuid) -> i32 {
+        self.reputation.get(&player).copied().unwrap_or(0)
+    }
+
+    pub fn set(&mut self, player: Uuid, reputation: i32) {
+        let reputation = reputation.clamp(Self::MIN, Self::MAX);
+
+        if reputation == 0 {
+            self.reputation.remove(&player);
+        } else {
+            self.reputation.insert(player, reputation);
+        }
+    }
+
+    /// Adds to the reputation of the player and returns the new reputation.
+    pub fn add(&mut self, player: Uuid, amount: i32) -> i32 {
+        self.set(player, self.get(player).saturating_add(amount));
+        self.get(player)
+    }
+}
+
+/// A trade offered by a villager.
+#[derive(Clone, PartialEq, Debug)]
+pub struct VillagerOffer {
+    pub input_one: ItemStack,
+    pub input_two: Option<ItemStack>,
+    pub output: ItemStack,
+    /// The villager level at which the offer becomes available.
+    pub min_level: i32,
+    /// The number of times the offer was used since it was last restocked.
+    pub uses: i32,
+    /// The number of times the offer can be used before it must be restocked.
+    pub max_uses: i32,
+    /// The experience the villager gains when the offer is used.
+    pub xp: i32,
+    /// How strongly demand and reputation affect the price of the first input.
+    pub price_multiplier: f32,
+    /// How much more the offer was used than it was restocked. Positive
+    /// demand raises the price of the first input.
+    pub demand: i32,
+}
+
+impl VillagerOffer {
+    pub fn new(input_one: ItemStack, output: ItemStack) -> Self {
+        Self {
+            input_one,
+            input_two: None,
+            output,
+            min_level: 1,
+            uses: 0,
+            max_uses: 12,
+            xp: 2,
+            price_multiplier: 0.05,
+            demand: 0,
+        }
+    }
+
+    #[must_use]
+    pub fn with_input_two(mut self, input_two: ItemStack) -> Self {
+        self.input_two = Some(input_two);
+        self
+    }
+
+    #[must_use]
+    pub fn with_min_level(mut self, min_level: i32) -> Self {
+        self.min_level = min_level;
+        self
+    }
+
+    #[must_use]
+    pub fn with_max_uses(mut self, max_uses: i32) -> Self {
+        self.max_uses = max_uses;
+        self
+    }
+
+    #[must_use]
+    pub fn with_xp(mut self, xp: i32) -> Self {
+        self.xp = xp;
+        self
+    }
+
+    #[must_use]
+    pub fn with_price_multiplier(mut self, price_multiplier: f32) -> Self {
+        self.price_multiplier = price_multiplier;
+        self
+    }
+
+    pub fn is_out_of_stock(&self) -> bool {
+        self.uses >= self.max_uses
+    }
+
+    /// Returns the change in the price of the first input due to reputation.
+    /// Good reputation makes the offer cheaper.
+    pub fn reputation_discount(&self, reputation: i32) -> i32 {
+        -(reputation as f32 * self.price_multiplier).floor() as i32
+    }
+
+    /// Returns the number of items of the first input the offer costs with the
+    /// given reputation.
+    pub fn price(&self, reputation: i32) -> i32 {
+        let base = self.input_one.count() as i32;
+        let demand = (base as f32 * self.demand as f32 * self.price_multiplier).floor() as i32;
+
+        (base + demand.max(0) + self.reputation_discount(reputation))
+            .clamp(1, self.input_one.item.max_stack() as i32)
+    }
+
+    /// Restocks the offer, updating the demand from how much it was used.
+    pub fn restock(&mut self) {
+        self.demand = self.demand + self.uses - (self.max_uses - self.uses);
+        self.uses = 0;
+    }
+
+    /// Converts the offer into the trade sent to clients. The client applies
+    /// the demand and special price to the price it displays.
+    pub fn to_merchant_trade(&self, reputation: i32) -> MerchantTrade {
+        MerchantTrade {
+            input_one: Some(self.input_one.clone()),
+            output_item: Some(self.output.clone()),
+            input_two: self.input_two.clone(),
+            trade_disabled: self.is_out_of_stock(),
+            number_of_trade_uses: self.uses,
+            max_trade_uses: self.max_uses,
+            xp: self.xp,
+            special_price: self.reputation_discount(reputation),
+            price_multiplier: self.price_multiplier,
+            demand: self.demand,
+        }
+    }
+}
+
+/// A [`Component`] containing the offers of a [`Villager`].
+///
+/// Offers are only available once the villager reaches their minimum level,
+/// and villagers which can't trade never offer anything. Offers are restocked
+/// at most twice per [`VillagerOffers::restock_interval`].
+#[derive(Component, Clone, PartialEq, Debug)]
+pub struct VillagerOffers {
+    pub offers: Vec<VillagerOffer>,
+    /// The number of ticks between the times restocks are allowed.
+    ///
+    /// # Default Value
+    ///
+    /// `12000`, half of a Minecraft day.
+    pub restock_interval: i64,
+    last_restock: i64,
+}
+
+impl VillagerOffers {
+    pub fn new(offers: Vec<VillagerOffer>) -> Self {
+        Self {
+            offers,
+            restock_interval: 12000,
+            last_restock: 0,
+        }
+    }
+
+    /// Returns the offers available from a villager.
+    pub fn available<'a>(
+        &'a self,
+        villager: &Villager,
+    ) -> impl Iterator<Item = &'a VillagerOffer> + 'a {
+        let level = villager.level();
+        let can_trade = villager.can_trade();
+
+        self.offers
+            .iter()
+            .filter(move |offer| can_trade && offer.min_level <= level)
+    }
+
+    /// Returns the trades sent to the player with the given reputation when
+    /// it opens the trading screen of a villager.
+    pub fn merchant_trades(&self, villager: &Villager, reputation: i32) -> Vec<MerchantTrade> {
+        self.available(villager)
+            .map(|offer| offer.to_merchant_trade(reputation))
+            .collect()
+    }
+
+    /// Returns if any available offer was used since the last restock.
+    pub fn needs_restock(&self, villager: &Villager) -> bool {
+        self.available(villager).any(|offer| offer.uses > 0)
+    }
+
+    /// Restocks every offer.
+    pub fn restock(&mut self) {
+        for offer in &mut self.offers {
+            offer.restock();
+        }
+    }
+}
+
+pub(crate) fn update_villager_data(
+    mut villagers: Query<(&Villager, &mut McEntity), Changed<Villager>>,
+) {
+    for (villager, mut entity) in &mut villagers {
+        match entity.data_mut() {
+            TrackedData::Villager(data) => data.set_villager_data(villager.to_villager_data()),
+            TrackedData::ZombieVillager(data) => {
+                data.set_villager_data(villager.to_villager_data())
+            }
+            _ => {}
+        }
+    }
+}
+
+pub(crate) fn restock_villagers(
+    server: Res<Server>,
+    budget: Res<TickBudget>,
+    skipped: Res<SkippedInstances>,
+    mut villagers: Query<(&Villager, &mut VillagerOffers, Option<&McEntity>)>,
+) {
+    if budget.is_shedding(SheddableWork::EntityAi) {
+        return;
+    }
+
+    let tick = server.current_tick();
+
+    for (villager, mut offers, mc_entity) in &mut villagers {
+        if mc_entity.map_or(false, |mc_entity| skipped.contains(mc_entity.instance())) {
+            continue;
+        }
+
+        // Vanilla villagers restock up to twice per day at their workstation.
+        if tick - offers.last_restock < offers.restock_interval / 2
+            || !offers.needs_restock(villager)
+        {
+            continue;
+        }
+
+        offers.restock();
+        offers.last_restock = tick;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use valence_protocol::ItemKind;
+
+    use super::*;
+
+    #[test]
+    fn villager_levels_and_prices() {
+        let mut villager = Villager::new(VillagerProfession::Librarian);
+        assert_eq!(villager.level(), 1);
+        assert!(!villager.add_xp(9));
+        assert!(villager.add_xp(1));
+        assert_eq!(villager.level(), 2);
+        assert_eq!(villager.next_level_xp(), Some(70));
+        villager.set_xp(1000);
+        assert_eq!(villager.level(), MAX_VILLAGER_LEVEL);
+        assert_eq!(villager.next_level_xp(), None);
+
+        let mut nitwit = Villager::new(VillagerProfession::Nitwit);
+        assert!(!nitwit.add_xp(100));
+        assert_eq!(nitwit.level(), 1);
+
+        let mut offer = VillagerOffer::new(
+            ItemStack::new(ItemKind::Emerald, 10, None),
+            ItemStack::new(ItemKind::Bookshelf, 1, None),
+        );
+
+        assert_eq!(offer.price(0), 10);
+        assert_eq!(offer.price(100), 5);
+        assert_eq!(offer.price(-700), 45);
+
+        offer.uses = 12;
+        assert!(offer.is_out_of_stock());
+        offer.restock();
+        assert_eq!(offer.uses, 0);
+        assert_eq!(offer.demand, 12);
+        assert_eq!(offer.price(0), 16);
+
+        let offers = VillagerOffers::new(vec![offer.clone(), offer.with_min_level(3)]);
+        assert_eq!(offers.merchant_trades(&villager, 0).len(), 2);
+        assert_eq!(
+            offers
+                .merchant_trades(&Villager::new(VillagerProfession::Librarian), 0)
+                .len(),
+            1
+        );
+        assert!(offers.merchant_trades(&nitwit, 0).is_empty());
+    }
+}
@@ -0,0 +1,606 @@
+//! Explosions and primed TNT.
+//!
+//! Explosions are queued in the [`PendingExplosions`] resource and detonated
+//! during [`CoreStage::PostUpdate`]. Systems that run before then may inspect,
+//! modify, or cancel the explosions that are about to go off. Primed TNT
+//! entities queue their explosion when the fuse runs out, so TNT explosions are
+//! always observable for at least one tick before detonating.
+//!
+//! [`CoreStage::PostUpdate`]: bevy_app::CoreStage::PostUpdate
+
+use bevy_ecs::prelude::*;
+use glam::{DVec3, Vec3};
+use rand::Rng;
+use valence_protocol::packets::s2c::particle::Particle;
+use valence_protocol::types::SoundCategory;
+use valence_protocol::{BlockKind, BlockPos, BlockState, ItemKind, Sound};
+
+use crate::client::event::UseItemOnBlock;
+use crate::client::Client;
+use crate::damage::{DamageKind, EntityDamage};
+use crate::entity::{EntityKind, McEntity, TrackedData};
+use crate::instance::Instance;
+use crate::inventory::{held_item, Inventory};
+use crate::tick_rate::SkippedInstances;
+use crate::Despawned;
+
+/// The number of ticks a TNT block burns for after being ignited by a player,
+/// fire, or redstone.
+pub const DEFAULT_TNT_FUSE: u32 = 80;
+
+/// The power of an explosion caused by TNT.
+pub const TNT_POWER: f32 = 4.0;
+
+/// A [`Resource`] containing global settings for explosions.
+#[derive(Resource, Clone, Debug)]
+pub struct ExplosionSettings {
+    /// Whether or not explosions are allowed to destroy blocks. When `false`,
+    /// this overrides [`Explosion::destroy_blocks`].
+    ///
+    /// # Default Value
+    ///
+    /// `true`
+    pub destroy_blocks: bool,
+    /// Whether or not TNT blocks caught in an explosion are primed, causing a
+    /// chain reaction. When `false`, TNT blocks are destroyed like any other
+    /// block.
+    ///
+    /// # Default Value
+    ///
+    /// `true`
+    pub chain_reactions: bool,
+    /// Whether or not players are allowed to prime TNT by using flint and steel
+    /// on it.
+    ///
+    /// # Default Value
+    ///
+    /// `true`
+    pub flint_and_steel_ignition: bool,
+}
+
+impl Default for ExplosionSettings {
+    fn default() -> Self {
+        Self {
+            destroy_blocks: true,
+            chain_reactions: true,
+            flint_and_steel_ignition: true,
+        }
+    }
+}
+
+/// An explosion waiting to be detonated.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Explosion {
+    /// The [`Entity`] with the [`Instance`] component the explosion occurs in.
+    pub instance: Entity,
+    /// The center of the explosion.
+    pub position: DVec3,
+    /// The strength of the explosion. TNT has a power of [`TNT_POWER`].
+    pub power: f32,
+    /// Whether or not the explosion destroys the blocks around it.
+    pub destroy_blocks: bool,
+    /// The entity responsible for the explosion, if any.
+    pub source: Option<Entity>,
+}
+
+impl Explosion {
+    /// Creates a new explosion which destroys blocks and has no source.
+    pub fn new(instance: Entity, position: impl Into<DVec3>, power: f32) -> Self {
+        Self {
+            instance,
+            position: position.into(),
+            power,
+            destroy_blocks: true,
+            source: None,
+        }
+    }
+
+    #[must_use]
+    pub fn with_destroy_blocks(mut self, destroy_blocks: bool) -> Self {
+        self.destroy_blocks = destroy_blocks;
+        self
+    }
+
+    #[must_use]
+    pub fn with_source(mut self, source: impl Into<Option<Entity>>) -> Self {
+        self.source = source.into();
+        self
+    }
+}
+
+/// A [`Resource`] containing the explosions that will be detonated at the end
+/// of the current tick.
+///
+/// Removing an explosion from this queue cancels it.
+#[derive(Resource, Default, Debug)]
+pub struct PendingExplosions {
+    explosions: Vec<Explosion>,
+}
+
+impl PendingExplosions {
+    /// Queues an explosion to be detonated at the end of the tick.
+    pub fn push(&mut self, explosion: Explosion) {
+        self.explosions.push(explosion);
+    }
+
+    pub fn iter(&self) -> impl ExactSizeIterator<Item = &Explosion> + '_ {
+        self.explosions.iter()
+    }
+
+    /// Returns an iterator over the pending explosions, allowing them to be
+    /// modified before they are detonated.
+    pub fn iter_mut(&mut self) -> impl ExactSizeIterator<Item = &mut Explosion> + '_ {
+        self.explosions.iter_mut()
+    }
+
+    /// Retains only the explosions for which the given predicate returns
+    /// `true`. The other explosions are cancelled.
+    pub fn retain<F>(&mut self, f: F)
+    where
+        F: FnMut(&Explosion) -> bool,
+    {
+        self.explosions.retain(f);
+    }
+
+    /// Cancels all pending explosions.
+    pub fn clear(&mut self) {
+        self.explosions.clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.explosions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.explosions.is_empty()
+    }
+}
+
+/// A [`Component`] for TNT entities that are counting down to an explosion.
+///
+/// The entity must also have an [`McEntity`] component. Once the fuse reaches
+/// zero, the entity is marked as [`Despawned`] and an explosion is queued at
+/// its position.
+#[derive(Component, Clone, Debug)]
+pub struct PrimedTnt {
+    /// The number of ticks remaining until the TNT explodes.
+    pub fuse: u32,
+    /// The power of the resulting explosion.
+    pub power: f32,
+    /// The entity which ignited the TNT, if any.
+    pub igniter: Option<Entity>,
+}
+
+impl PrimedTnt {
+    pub fn new(fuse: u32) -> Self {
+        Self {
+            fuse,
+            power: TNT_POWER,
+            igniter: None,
+        }
+    }
+}
+
+/// What caused a TNT block to be ignited.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum TntIgnitionCause {
+    FlintAndSteel,
+    Redstone,
+    Fire,
+    Explosion,
+    Other,
+}
+
+/// An event which turns the TNT block at a position into a [`PrimedTnt`]
+/// entity. Has no effect if the block at the position is not TNT.
+#[derive(Clone, Debug)]
+pub struct IgniteTnt {
+    pub instance: Entity,
+    pub position: BlockPos,
+    pub cause: TntIgnitionCause,
+    /// The entity responsible for the ignition, if any.
+    pub igniter: Option<Entity>,
+    /// The length of the fuse. [`DEFAULT_TNT_FUSE`] is used if `None`.
+    pub fuse: Option<u32>,
+}
+
+/// Returns an approximation of the explosion resistance of a block.
+fn blast_resistance(state: BlockState) -> f32 {
+    if state.is_air() {
+        return 0.0;
+    }
+
+    if state.is_liquid() {
+        return 100.0;
+    }
+
+    match state.to_kind() {
+        BlockKind::Bedrock
+        | BlockKind::Barrier
+        | BlockKind::CommandBlock
+        | BlockKind::ChainCommandBlock
+        | BlockKind::RepeatingCommandBlock
+        | BlockKind::StructureBlock
+        | BlockKind::Jigsaw
+        | BlockKind::EndPortal
+        | BlockKind::EndPortalFrame
+        | BlockKind::EndGateway
+        | BlockKind::NetherPortal
+        | BlockKind::Light => 3_600_000.0,
+        BlockKind::Obsidian
+        | BlockKind::CryingObsidian
+        | BlockKind::AncientDebris
+        | BlockKind::NetheriteBlock
+        | BlockKind::RespawnAnchor
+        | BlockKind::EnchantingTable
+        | BlockKind::EnderChest
+        | BlockKind::ReinforcedDeepslate => 1200.0,
+        BlockKind::Anvil | BlockKind::ChippedAnvil | BlockKind::DamagedAnvil => 1200.0,
+        BlockKind::Tnt => 0.0,
+        _ if state.is_opaque() => 6.0,
+        _ => 1.0,
+    }
+}
+
+/// Collects the positions of the blocks destroyed by an explosion using the
+/// same ray casting approach as the vanilla server.
+fn collect_destroyed_blocks(instance: &Instance, center: DVec3, power: f32) -> Vec<BlockPos> {
+    let mut rng = rand::thread_rng();
+    let mut destroyed = vec![];
+
+    for x in 0..16 {
+        for y in 0..16 {
+            for z in 0..16 {
+                // Only cast rays from the surface of the 16x16x16 grid.
+                if !(x == 0 || x == 15 || y == 0 || y == 15 || z == 0 || z == 15) {
+                    continue;
+                }
+
+                let dir = DVec3::new(x as f64, y as f64, z as f64) / 15.0 * 2.0 - 1.0;
+                let dir = dir.normalize() * 0.3;
+
+                let mut intensity = power * (0.7 + rng.gen::<f32>() * 0.6);
+                let mut pos = center;
+
+                while intensity > 0.0 {
+                    let block_pos = BlockPos::at(pos);
+
+                    let Some(block) = instance.block(block_pos) else {
+                        break
+                    };
+
+                    let resistance = blast_resistance(block.state());
+
+                    intensity -= (resistance + 0.3) * 0.3;
+
+                    if intensity > 0.0 && !block.state().is_air() && !destroyed.contains(&block_pos)
+                    {
+                        destroyed.push(block_pos);
+                    }
+
+                    pos += dir;
+                    intensity -= 0.225;
+                }
+            }
+        }
+    }
+
+    destroyed
+}
+
+pub(crate) fn handle_flint_and_steel(
+    clients: Query<(&Client, &Inventory)>,
+    settings: Res<ExplosionSettings>,
+    instances: Query<&Instance>,
+    mut use_item_on_block: EventReader<UseItemOnBlock>,
+    mut ignite_tnt: EventWriter<IgniteTnt>,
+) {
+    for event in use_item_on_block.iter() {
+        if !settings.flint_and_steel_ignition {
+            continue;
+        }
+
+        let Ok((client, inventory)) = clients.get(event.client) else {
+            continue
+        };
+
+        if held_item(client, inventory, event.hand)
+            .map_or(true, |stack| stack.item != ItemKind::FlintAndSteel)
+        {
+            continue;
+        }
+
+        let Ok(instance) = instances.get(client.instance()) else {
+            continue
+        };
+
+        if instance
+            .block(event.position)
+            .map_or(false, |b| b.state().to_kind() == BlockKind::Tnt)
+        {
+            ignite_tnt.send(IgniteTnt {
+                instance: client.instance(),
+                position: event.position,
+                cause: TntIgnitionCause::FlintAndSteel,
+                igniter: Some(event.client),
+                fuse: None,
+            });
+        }
+    }
+}
+
+pub(crate) fn ignite_tnt(
+    mut commands: Commands,
+    mut instances: Query<&mut Instance>,
+    mut events: EventReader<IgniteTnt>,
+) {
+    for event in events.iter() {
+        let Ok(mut instance) = instances.get_mut(event.instance) else {
+            continue
+        };
+
+        if instance
+            .block(event.position)
+            .map_or(true, |b| b.state().to_kind() != BlockKind::Tnt)
+        {
+            continue;
+        }
+
+        instance.set_block(event.position, BlockState::AIR);
+
+        let fuse = event.fuse.unwrap_or(DEFAULT_TNT_FUSE);
+
+        let position = DVec3::new(
+            event.position.x as f64 + 0.5,
+            event.position.y as f64,
+            event.position.z as f64 + 0.5,
+        );
+
+        let mut entity = McEntity::new(EntityKind::Tnt, event.instance);
+        entity.set_position(position);
+
+        // Give the TNT a small random nudge like in vanilla.
+        let angle = rand::random::<f32>() * std::f32::consts::TAU;
+        entity.set_velocity(Vec3::new(-angle.sin() * 0.4, 4.0, -angle.cos() * 0.4));
+
+        if let TrackedData::Tnt(tnt) = entity.data_mut() {
+            tnt.set_fuse(fuse as i32);
+        }
+
+        instance.play_sound(
+            Sound::EntityTntPrimed,
+            SoundCategory::Block,
+            position,
+            1.0,
+            1.0,
+        );
+
+        commands.spawn((
+            entity,
+            PrimedTnt {
+                fuse,
+                power: TNT_POWER,
+                igniter: event.igniter,
+            },
+        ));
+    }
+}
+
+pub(crate) fn tick_primed_tnt(
+    mut commands: Commands,
+    mut tnt: Query<(Entity, &McEntity, &mut PrimedTnt), Without<Despawned>>,
+    mut pending: ResMut<PendingExplosions>,
+    skipped: Res<SkippedInstances>,
+) {
+    for (entity, mc_entity, mut primed) in &mut tnt {
+        if skipped.contains(mc_entity.instance()) {
+            continue;
+        }
+
+        primed.fuse = primed.fuse.saturating_sub(1);
+
+        if primed.fuse == 0 {
+            commands.entity(entity).insert(Despawned);
+
+            pending.push(Explosion {
+                instance: mc_entity.instance(),
+                // Vanilla explodes TNT slightly above its base.
+                position: mc_entity.position() + DVec3::new(0.0, 0.0625, 0.0),
+                power: primed.power,
+                destroy_blocks: true,
+                source: primed.igniter,
+            });
+        }
+    }
+}
+
+pub(crate) fn detonate_explosions(
+    mut pending: ResMut<PendingExplosions>,
+    settings: Res<ExplosionSettings>,
+    mut instances: Query<&mut Instance>,
+    mut entities: Query<(Entity, &mut McEntity, Option<&mut Client>), Without<Despawned>>,
+    mut clients: Query<(Entity, &mut Client), Without<McEntity>>,
+    mut ignite_tnt: EventWriter<IgniteTnt>,
+    mut damage: EventWriter<EntityDamage>,
+) {
+    let mut rng = rand::thread_rng();
+
+    for explosion in pending.explosions.drain(..) {
+        let Ok(mut instance) = instances.get_mut(explosion.instance) else {
+            continue
+        };
+
+        if explosion.destroy_blocks && settings.destroy_blocks {
+            for pos in collect_destroyed_blocks(&instance, explosion.position, explosion.power) {
+                let is_tnt = instance
+                    .block(pos)
+                    .map_or(false, |b| b.state().to_kind() == BlockKind::Tnt);
+
+                if is_tnt && settings.chain_reactions {
+                    ignite_tnt.send(IgniteTnt {
+                        instance: explosion.instance,
+                        position: pos,
+                        cause: TntIgnitionCause::Explosion,
+                        igniter: explosion.source,
+                        fuse: Some(rng.gen_range(DEFAULT_TNT_FUSE / 8..DEFAULT_TNT_FUSE * 3 / 8)),
+                    });
+                } else {
+                    instance.set_block(pos, BlockState::AIR);
+                }
+            }
+        }
+
+        // Entities within twice the explosion power are pushed away and damaged.
+        let radius = explosion.power as f64 * 2.0;
+
+        let mut apply = |entity: Entity, position: DVec3| -> Option<Vec3> {
+            let offset = position - explosion.position;
+            let dist = offset.length();
+
+            if dist > radius || dist == 0.0 {
+                return None;
+            }
+
+            let impact = 1.0 - dist / radius;
+
+            damage.send(EntityDamage {
+                entity,
+                source: explosion.source,
+                kind: DamageKind::Explosion,
+                amount: ((impact * impact + impact) / 2.0 * 7.0 * radius + 1.0) as f32,
+            });
+
+            // Vanilla knockback is in meters per tick.
+            Some((offset / dist * impact * 20.0).as_vec3())
+        };
+
+        for (entity, mut mc_entity, client) in &mut entities {
+            if mc_entity.instance() != explosion.instance {
+                continue;
+            }
+
+            if let Some(knockback) = apply(entity, mc_entity.position()) {
+                let velocity = mc_entity.velocity() + knockback;
+
+                if let Some(mut client) = client {
+                    client.set_velocity(velocity);
+                }
+
+                mc_entity.set_velocity(velocity);
+            }
+        }
+
+        for (entity, mut client) in &mut clients {
+            if client.instance() != explosion.instance {
+                continue;
+            }
+
+            if let Some(knockback) = apply(entity, client.position()) {
+                client.set_velocity(knockback);
+            }
+        }
+
+        let particle = if explosion.power >= 2.0 && explosion.destroy_blocks {
+            Particle::ExplosionEmitter
+        } else {
+            Particle::Explosion
+        };
+
+        instance.play_particle(&particle, true, explosion.position, Vec3::ZERO, 1.0, 1);
+
+        instance.play_sound(
+            Sound::EntityGenericExplode,
+            SoundCategory::Block,
+            explosion.position,
+            4.0,
+            (1.0 + (rng.gen::<f32>() - rng.gen::<f32>()) * 0.2) * 0.7,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy_app::App;
+
+    use super::*;
+    use crate::instance::Chunk;
+    use crate::unit_test::util::scenario_single_client;
+
+    #[test]
+    fn tnt_chain_reaction() {
+        let mut app = App::new();
+        let (client_ent, _client_helper) = scenario_single_client(&mut app);
+        let instance_ent = app
+            .world
+            .get::<Client>(client_ent)
+            .expect("could not find client")
+            .instance();
+
+        let mut instance = app
+            .world
+            .get_mut::<Instance>(instance_ent)
+            .expect("could not find instance");
+
+        instance.insert_chunk([0, 0], Chunk::default());
+        instance.set_block([2, 0, 2], BlockState::TNT);
+        instance.set_block([4, 0, 2], BlockState::TNT);
+
+        app.world.send_event(IgniteTnt {
+            instance: instance_ent,
+            position: BlockPos::new(2, 0, 2),
+            cause: TntIgnitionCause::Other,
+            igniter: None,
+            fuse: Some(1),
+        });
+
+        app.update();
+
+        let instance = app.world.get::<Instance>(instance_ent).unwrap();
+        assert!(instance.block([2, 0, 2]).unwrap().state().is_air());
+        assert_eq!(instance.block([4, 0, 2]).unwrap().state(), BlockState::TNT);
+
+        // The fuse runs out and the explosion is queued.
+        app.update();
+
+        // The explosion is detonated, which should prime the second TNT block.
+        app.update();
+
+        let instance = app.world.get::<Instance>(instance_ent).unwrap();
+        assert!(instance.block([4, 0, 2]).unwrap().state().is_air());
+
+        let primed_count = app.world.query::<&PrimedTnt>().iter(&app.world).count();
+        assert_eq!(primed_count, 1);
+    }
+
+    #[test]
+    fn explosion_block_damage_toggle() {
+        let mut app = App::new();
+        let (client_ent, _client_helper) = scenario_single_client(&mut app);
+        let instance_ent = app.world.get::<Client>(client_ent).unwrap().instance();
+
+        let mut instance = app.world.get_mut::<Instance>(instance_ent).unwrap();
+        instance.insert_chunk([0, 0], Chunk::default());
+        instance.set_block([8, 0, 8], BlockState::STONE);
+
+        app.world.resource_mut::<PendingExplosions>().push(
+            Explosion::new(instance_ent, [8.5, 1.0, 8.5], TNT_POWER).with_destroy_blocks(false),
+        );
+
+        app.update();
+
+        let instance = app.world.get::<Instance>(instance_ent).unwrap();
+        assert_eq!(
+            instance.block([8, 0, 8]).unwrap().state(),
+            BlockState::STONE
+        );
+
+        app.world
+            .resource_mut::<PendingExplosions>()
+            .push(Explosion::new(instance_ent, [8.5, 1.0, 8.5], TNT_POWER));
+
+        app.update();
+
+        let instance = app.world.get::<Instance>(instance_ent).unwrap();
+        assert!(instance.block([8, 0, 8]).unwrap().state().is_air());
+    }
+}
@@ -0,0 +1,278 @@
+//! Fall distance tracking and fall damage.
+//!
+//! The distance every client has fallen is tracked from the movement packets
+//! it sends. When the client lands, the fall damage it takes is queued in the
+//! [`PendingFallDamage`] resource and dealt at the end of the following tick
+//! by sending an [`EntityDamage`] event. Systems that run before then may
+//! inspect, modify, or cancel the damage.
+
+use bevy_ecs::prelude::*;
+use valence_protocol::types::GameMode;
+use valence_protocol::{BlockKind, BlockPos, BlockState};
+
+use crate::client::event::MovePlayer;
+use crate::client::pose::PoseState;
+use crate::client::Client;
+use crate::damage::{DamageKind, EntityDamage};
+use crate::enchantment::{apply_protection, protection_factor};
+use crate::game_rules::GameRules;
+use crate::instance::Instance;
+use crate::inventory::{Inventory, BOOTS_SLOT, HELMET_SLOT};
+use crate::water::is_water;
+
+/// The distance a client can fall without taking damage.
+pub const SAFE_FALL_DISTANCE: f32 = 3.0;
+
+/// A [`Component`] containing the distance the [`Client`] on the same entity
+/// has fallen since it was last on the ground. Every client has this
+/// component.
+///
+/// The distance is reset when the client lands, enters water, or climbs a
+/// ladder or vine. It is not reset when the client is teleported, so set it
+/// to zero manually when teleporting a falling client.
+#[derive(Component, Copy, Clone, PartialEq, Default, Debug)]
+pub struct FallDistance {
+    pub distance: f32,
+}
+
+/// Fall damage that will be dealt to a client at the end of the tick.
+#[derive(Clone, PartialEq, Debug)]
+pub struct FallDamage {
+    pub client: Entity,
+    /// The distance the client fell.
+    pub distance: f32,
+    /// The amount of damage in half hearts.
+    pub amount: f32,
+}
+
+/// A [`Resource`] containing the fall damage that will be dealt at the end of
+/// the current tick.
+///
+/// Removing an entry from this queue cancels the damage.
+#[derive(Resource, Default, Debug)]
+pub struct PendingFallDamage {
+    damage: Vec<FallDamage>,
+}
+
+impl PendingFallDamage {
+    /// Queues fall damage to be dealt at the end of the tick.
+    pub fn push(&mut self, damage: FallDamage) {
+        self.damage.push(damage);
+    }
+
+    pub fn iter(&self) -> impl ExactSizeIterator<Item = &FallDamage> + '_ {
+        self.damage.iter()
+    }
+
+    /// Returns an iterator over the pending fall damage, allowing the amount
+    /// to be modified before it is dealt.
+    pub fn iter_mut(&mut self) -> impl ExactSizeIterator<Item = &mut FallDamage> + '_ {
+        self.damage.iter_mut()
+    }
+
+    /// Retains only the fall damage for which the given predicate returns
+    /// `true`. The rest is cancelled.
+    pub fn retain<F>(&mut self, f: F)
+    where
+        F: FnMut(&FallDamage) -> bool,
+    {
+        self.damage.retain(f);
+    }
+
+    /// Cancels all pending fall damage.
+    pub fn clear(&mut self) {
+        self.damage.clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.damage.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.damage.is_empty()
+    }
+}
+
+/// Returns the fall damage dealt when falling `distance` blocks onto the block
+/// `landed_on` while wearing armor with the given enchantment protection
+/// factor.
+fn fall_damage(distance: f32, landed_on: BlockKind, sneaking: bool, protection: i32) -> f32 {
+    let multiplier = match landed_on {
+        // Slime blocks bounce the client back up unless it is sneaking.
+        BlockKind::SlimeBlock if !sneaking => 0.0,
+        BlockKind::HayBlock => 0.2,
+        kind if kind.to_str().ends_with("_bed") => 0.5,
+        _ => 1.0,
+    };
+
+    let damage = ((distance - SAFE_FALL_DISTANCE) * multiplier)
+        .ceil()
+        .max(0.0);
+
+    apply_protection(damage, protection)
+}
+
+pub(crate) fn apply_fall_damage(
+    mut pending: ResMut<PendingFallDamage>,
+    mut damage: EventWriter<EntityDamage>,
+) {
+    for fall in pending.damage.drain(..) {
+        if fall.amount > 0.0 {
+            damage.send(EntityDamage {
+                entity: fall.client,
+                source: None,
+                kind: DamageKind::Fall,
+                amount: fall.amount,
+            });
+        }
+    }
+}
+
+pub(crate) fn update_fall_distance(
+    mut clients: Query<(
+        &Client,
+        &mut FallDistance,
+        Option<&PoseState>,
+        Option<&Inventory>,
+    )>,
+    instances: Query<(&Instance, Option<&GameRules>)>,
+    mut pending: ResMut<PendingFallDamage>,
+    mut move_player: EventReader<MovePlayer>,
+) {
+    for event in move_player.iter() {
+        let Ok((client, mut fall, pose, inventory)) = clients.get_mut(event.client) else {
+            continue
+        };
+
+        let Ok((instance, rules)) = instances.get(client.instance()) else {
+            continue
+        };
+
+        let block_at = |pos| {
+            instance
+                .block(BlockPos::at(pos))
+                .map_or(BlockState::AIR, |block| block.state())
+        };
+
+        let state = block_at(event.position);
+
+        if is_water(state)
+            || matches!(
+                state.to_kind(),
+                BlockKind::Ladder | BlockKind::Vine | BlockKind::Scaffolding
+            )
+            || matches!(client.game_mode(), GameMode::Creative | GameMode::Spectator)
+        {
+            fall.distance = 0.0;
+            continue;
+        }
+
+        let dy = event.position.y - event.old_position.y;
+
+        if !event.on_ground {
+            if dy < 0.0 {
+                fall.distance -= dy as f32;
+            }
+            continue;
+        }
+
+        let distance = std::mem::take(&mut fall.distance);
+
+        if distance <= SAFE_FALL_DISTANCE || !rules.map_or(true, |rules| rules.fall_damage) {
+            continue;
+        }
+
+        // The block the client landed on is slightly below its feet.
+        let mut below = event.position;
+        below.y -= 0.2;
+
+        let protection = inventory.map_or(0, |inv| {
+            protection_factor(
+                (HELMET_SLOT..=BOOTS_SLOT).filter_map(|slot| inv.slot(slot)),
+                DamageKind::Fall,
+            )
+        });
+
+        pending.push(FallDamage {
+            client: event.client,
+            distance,
+            amount: fall_damage(
+                distance,
+                block_at(below).to_kind(),
+                pose.map_or(false, |pose| pose.is_sneaking()),
+                protection,
+            ),
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy_app::App;
+    use valence_protocol::packets::c2s::play::SetPlayerPosition;
+
+    use super::*;
+    use crate::instance::Chunk;
+    use crate::unit_test::util::scenario_single_client;
+
+    #[test]
+    fn fall_damage_values() {
+        assert_eq!(fall_damage(3.0, BlockKind::Stone, false, 0), 0.0);
+        assert_eq!(fall_damage(10.0, BlockKind::Stone, false, 0), 7.0);
+        assert_eq!(fall_damage(10.0, BlockKind::SlimeBlock, false, 0), 0.0);
+        assert_eq!(fall_damage(10.0, BlockKind::SlimeBlock, true, 0), 7.0);
+        assert_eq!(fall_damage(13.0, BlockKind::WhiteBed, false, 0), 5.0);
+        // Feather Falling IV gives a protection factor of 12.
+        assert_eq!(fall_damage(28.0, BlockKind::Stone, false, 12), 13.0);
+    }
+
+    #[test]
+    fn landing_queues_fall_damage() {
+        let mut app = App::new();
+        let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+        let instance_ent = app.world.get::<Client>(client_ent).unwrap().instance();
+
+        let mut instance = app.world.get_mut::<Instance>(instance_ent).unwrap();
+        instance.insert_chunk([0, 0], Chunk::default());
+        instance.set_block([0, 0, 0], BlockState::STONE);
+
+        app.world
+            .get_mut::<Client>(client_ent)
+            .unwrap()
+            .set_game_mode(GameMode::Survival);
+
+        // The distance moved in the packet with `on_ground` set is not counted.
+        for (y, on_ground) in [(11.0, false), (1.0, false), (1.0, true)] {
+            client_helper.send(&SetPlayerPosition {
+                position: [0.5, y, 0.5],
+                on_ground,
+            });
+        }
+
+        app.update();
+
+        let pending = app.world.resource::<PendingFallDamage>();
+        assert_eq!(
+            pending.iter().collect::<Vec<_>>(),
+            [&FallDamage {
+                client: client_ent,
+                distance: 10.0,
+                amount: 7.0,
+            }]
+        );
+
+        app.update();
+
+        assert!(app.world.resource::<PendingFallDamage>().is_empty());
+
+        let damage = app.world.resource::<Events<EntityDamage>>();
+        let mut reader = damage.get_reader();
+        assert!(reader
+            .iter(damage)
+            .any(|e| e.entity == client_ent && e.kind == DamageKind::Fall && e.amount == 7.0));
+        assert_eq!(
+            app.world.get::<FallDistance>(client_ent).unwrap().distance,
+            0.0
+        );
+    }
+}
@@ -0,0 +1,785 @@
+//! Fire blocks and burning entities.
+//!
+//! Fire blocks are placed with flint and steel, by sending the
+//! [`IgniteBlock`] event or by setting them in an [`Instance`] directly. Fire
+//! in newly inserted chunks is picked up as well. Once placed, fire ticks at a
+//! random interval where it spreads to and burns away flammable blocks nearby
+//! before eventually burning out. Fire only ticks in simulated chunks (chunks
+//! in view of at least one client) and can be disabled per instance with
+//! [`GameRules::do_fire_tick`].
+//!
+//! Entities standing in fire or lava are set on fire with the [`OnFire`]
+//! component. Burning entities take damage periodically, which is reported
+//! with the [`EntityDamage`] event.
+
+use bevy_ecs::prelude::*;
+use rand::rngs::ThreadRng;
+use rand::Rng;
+use rustc_hash::FxHashMap;
+use valence_protocol::block::{PropName, PropValue};
+use valence_protocol::types::SoundCategory;
+use valence_protocol::{BlockFace, BlockKind, BlockPos, BlockState, ItemKind, Sound};
+
+use crate::budget::{SheddableWork, TickBudget};
+use crate::client::event::{StartDigging, UseItemOnBlock};
+use crate::client::Client;
+use crate::damage::{DamageKind, EntityDamage};
+use crate::entity::McEntity;
+use crate::explosion::{IgniteTnt, TntIgnitionCause};
+use crate::game_rules::GameRules;
+use crate::instance::{client_views_by_instance, Instance};
+use crate::inventory::{held_item, Inventory};
+use crate::server::Server;
+use crate::tick_rate::SkippedInstances;
+use crate::view::ChunkPos;
+use crate::Despawned;
+
+/// The number of ticks an entity burns for after leaving a fire block.
+pub const FIRE_BURN_TICKS: u32 = 160;
+
+/// The number of ticks an entity burns for after leaving lava.
+pub const LAVA_BURN_TICKS: u32 = 300;
+
+const MAX_FIRE_AGE: u16 = 15;
+
+/// An event that places a fire block in an instance.
+///
+/// The fire is only placed if the target block is air and the fire has
+/// something to burn on, i.e. an opaque block below it or a flammable block
+/// next to it. Targeting a TNT block primes it instead.
+#[derive(Clone, Debug)]
+pub struct IgniteBlock {
+    /// The [`Entity`] with the [`Instance`] component to place the fire in.
+    pub instance: Entity,
+    /// The position of the fire.
+    pub position: BlockPos,
+    /// The entity that started the fire, if any.
+    pub igniter: Option<Entity>,
+}
+
+/// A [`Component`] for entities that are currently burning.
+///
+/// While this component is present, the entity is displayed as on fire and
+/// takes [`DamageKind::OnFire`] damage once per second. The component is
+/// removed once [`OnFire::ticks`] reaches zero or when the entity enters
+/// water.
+#[derive(Component, Copy, Clone, PartialEq, Eq, Debug)]
+pub struct OnFire {
+    /// The number of ticks remaining until the fire goes out.
+    pub ticks: u32,
+}
+
+impl OnFire {
+    pub fn new(ticks: u32) -> Self {
+        Self { ticks }
+    }
+}
+
+/// A [`Resource`] containing the fire blocks waiting to be ticked.
+#[derive(Resource, Default, Debug)]
+pub(crate) struct ScheduledFireTicks {
+    /// Maps an instance and a fire block position to the tick the fire block
+    /// is next updated on.
+    ticks: FxHashMap<(Entity, BlockPos), i64>,
+}
+
+impl ScheduledFireTicks {
+    fn schedule(
+        &mut self,
+        instance: Entity,
+        pos: BlockPos,
+        current_tick: i64,
+        rng: &mut ThreadRng,
+    ) {
+        self.ticks
+            .insert((instance, pos), current_tick + 30 + rng.gen_range(0..10));
+    }
+
+    /// Schedules a fire block unless it is already waiting to be ticked.
+    fn schedule_new(
+        &mut self,
+        instance: Entity,
+        pos: BlockPos,
+        current_tick: i64,
+        rng: &mut ThreadRng,
+    ) {
+        self.ticks
+            .entry((instance, pos))
+            .or_insert_with(|| current_tick + 30 + rng.gen_range(0..10));
+    }
+}
+
+fn is_fire(state: BlockState) -> bool {
+    matches!(state.to_kind(), BlockKind::Fire | BlockKind::SoulFire)
+}
+
+/// Returns the chance of the block catching fire from a nearby fire and the
+/// chance of the block burning away, in that order. Returns `None` if the block
+/// is not flammable.
+///
+/// The values follow the ones vanilla assigns in `FireBlock::bootStrap`.
+fn flammability(state: BlockState) -> Option<(u32, u32)> {
+    let kind = state.to_kind();
+    let name = kind.to_str();
+
+    match kind {
+        BlockKind::Tnt | BlockKind::Vine => return Some((15, 100)),
+        BlockKind::Bookshelf | BlockKind::Lectern => return Some((30, 20)),
+        BlockKind::HayBlock => return Some((60, 20)),
+        BlockKind::DriedKelpBlock => return Some((30, 60)),
+        BlockKind::CoalBlock => return Some((5, 5)),
+        BlockKind::Target => return Some((15, 20)),
+        BlockKind::Composter => return Some((5, 20)),
+        BlockKind::Scaffolding | BlockKind::Bamboo => return Some((60, 60)),
+        BlockKind::Grass
+        | BlockKind::TallGrass
+        | BlockKind::Fern
+        | BlockKind::LargeFern
+        | BlockKind::DeadBush
+        | BlockKind::SweetBerryBush => return Some((60, 100)),
+        _ => {}
+    }
+
+    if name.starts_with("crimson_") || name.starts_with("warped_") {
+        // Nether wood does not burn.
+        None
+    } else if name.ends_with("_log") || name.ends_with("_wood") {
+        Some((5, 5))
+    } else if name.ends_with("_planks")
+        || (name.contains("_fence") || name.ends_with("_slab") || name.ends_with("_stairs"))
+            && (name.starts_with("oak_")
+                || name.starts_with("spruce_")
+                || name.starts_with("birch_")
+                || name.starts_with("jungle_")
+                || name.starts_with("acacia_")
+                || name.starts_with("dark_oak_")
+                || name.starts_with("mangrove_"))
+    {
+        Some((5, 20))
+    } else if name.ends_with("_leaves") || name.ends_with("_wool") {
+        Some((30, 60))
+    } else if name.ends_with("_carpet") && kind != BlockKind::MossCarpet {
+        Some((60, 20))
+    } else if matches!(
+        kind,
+        BlockKind::Dandelion
+            | BlockKind::Poppy
+            | BlockKind::BlueOrchid
+            | BlockKind::Allium
+            | BlockKind::AzureBluet
+            | BlockKind::RedTulip
+            | BlockKind::OrangeTulip
+            | BlockKind::WhiteTulip
+            | BlockKind::PinkTulip
+            | BlockKind::OxeyeDaisy
+            | BlockKind::Cornflower
+            | BlockKind::LilyOfTheValley
+            | BlockKind::WitherRose
+            | BlockKind::Sunflower
+            | BlockKind::Lilac
+            | BlockKind::RoseBush
+            | BlockKind::Peony
+    ) {
+        Some((60, 100))
+    } else {
+        None
+    }
+}
+
+fn block_state(instance: &Instance, pos: BlockPos) -> BlockState {
+    instance
+        .block(pos)
+        .map_or(BlockState::AIR, |block| block.state())
+}
+
+const FACES: [BlockFace; 6] = [
+    BlockFace::Bottom,
+    BlockFace::Top,
+    BlockFace::North,
+    BlockFace::South,
+    BlockFace::West,
+    BlockFace::East,
+];
+
+fn has_flammable_neighbor(instance: &Instance, pos: BlockPos) -> bool {
+    FACES
+        .into_iter()
+        .any(|face| flammability(block_state(instance, pos.get_in_direction(face))).is_some())
+}
+
+/// Returns the highest chance of any block next to `pos` catching fire.
+fn ignite_odds(instance: &Instance, pos: BlockPos) -> u32 {
+    FACES
+        .into_iter()
+        .filter_map(|face| flammability(block_state(instance, pos.get_in_direction(face))))
+        .map(|(ignite, _)| ignite)
+        .max()
+        .unwrap_or(0)
+}
+
+/// Returns the fire block state to place at `pos`, or `None` if there is
+/// nothing there for the fire to burn on.
+fn fire_state(instance: &Instance, pos: BlockPos, age: u16) -> Option<BlockState> {
+    let below = block_state(instance, pos.get_in_direction(BlockFace::Bottom));
+
+    if below.to_kind() == BlockKind::SoulSand || below.to_kind() == BlockKind::SoulSoil {
+        return Some(BlockState::SOUL_FIRE);
+    }
+
+    let state = BlockState::FIRE.set(PropName::Age, PropValue::from_u16(age)?);
+
+    if below.is_opaque() || flammability(below).is_some() {
+        return Some(state);
+    }
+
+    // Without a floor, the fire clings to the flammable blocks around it.
+    let mut state = state;
+    let mut supported = false;
+
+    for (face, prop) in [
+        (BlockFace::North, PropName::North),
+        (BlockFace::South, PropName::South),
+        (BlockFace::West, PropName::West),
+        (BlockFace::East, PropName::East),
+        (BlockFace::Top, PropName::Up),
+    ] {
+        if flammability(block_state(instance, pos.get_in_direction(face))).is_some() {
+            state = state.set(prop, PropValue::True);
+            supported = true;
+        }
+    }
+
+    supported.then_some(state)
+}
+
+pub(crate) fn place_fire_with_flint_and_steel(
+    clients: Query<(&Client, &Inventory)>,
+    instances: Query<&Instance>,
+    mut use_item_on_block: EventReader<UseItemOnBlock>,
+    mut ignite_block: EventWriter<IgniteBlock>,
+) {
+    for event in use_item_on_block.iter() {
+        let Ok((client, inventory)) = clients.get(event.client) else {
+            continue
+        };
+
+        if held_item(client, inventory, event.hand)
+            .map_or(true, |stack| stack.item != ItemKind::FlintAndSteel)
+        {
+            continue;
+        }
+
+        let Ok(instance) = instances.get(client.instance()) else {
+            continue
+        };
+
+        // Priming TNT is handled by the explosion systems.
+        if block_state(instance, event.position).to_kind() == BlockKind::Tnt {
+            continue;
+        }
+
+        ignite_block.send(IgniteBlock {
+            instance: client.instance(),
+            position: event.position.get_in_direction(event.face),
+            igniter: Some(event.client),
+        });
+    }
+}
+
+pub(crate) fn ignite_blocks(
+    server: Res<Server>,
+    mut instances: Query<&mut Instance>,
+    mut scheduled: ResMut<ScheduledFireTicks>,
+    mut events: EventReader<IgniteBlock>,
+    mut ignite_tnt: EventWriter<IgniteTnt>,
+) {
+    let mut rng = rand::thread_rng();
+
+    for event in events.iter() {
+        let Ok(mut instance) = instances.get_mut(event.instance) else {
+            continue
+        };
+
+        let Some(block) = instance.block(event.position) else {
+            continue
+        };
+
+        let kind = block.state().to_kind();
+
+        if kind == BlockKind::Tnt {
+            ignite_tnt.send(IgniteTnt {
+                instance: event.instance,
+                position: event.position,
+                cause: TntIgnitionCause::Fire,
+                igniter: event.igniter,
+                fuse: None,
+            });
+            continue;
+        }
+
+        if kind != BlockKind::Air {
+            continue;
+        }
+
+        let Some(state) = fire_state(&instance, event.position, 0) else {
+            continue
+        };
+
+        instance.set_block(event.position, state);
+
+        instance.play_sound(
+            Sound::ItemFlintandsteelUse,
+            SoundCategory::Block,
+            [
+                event.position.x as f64 + 0.5,
+                event.position.y as f64 + 0.5,
+                event.position.z as f64 + 0.5,
+            ],
+            1.0,
+            rng.gen_range(0.8..1.2),
+        );
+
+        scheduled.schedule(
+            event.instance,
+            event.position,
+            server.current_tick(),
+            &mut rng,
+        );
+    }
+}
+
+/// Schedules the fire blocks which were placed without [`IgniteBlock`], like
+/// fire set with [`Instance::set_block`] or in newly inserted chunks.
+pub(crate) fn schedule_placed_fire(
+    server: Res<Server>,
+    instances: Query<(Entity, &Instance)>,
+    mut scheduled: ResMut<ScheduledFireTicks>,
+) {
+    let current_tick = server.current_tick();
+    let mut rng = rand::thread_rng();
+
+    for (instance_entity, instance) in &instances {
+        let min_y = instance.min_y();
+
+        for (chunk_pos, chunk) in instance.mutated_chunks() {
+            chunk.for_each_changed_block(
+                |state| state.to_kind() == BlockKind::Fire,
+                |x, y, z| {
+                    let pos = BlockPos::new(
+                        chunk_pos.x * 16 + x as i32,
+                        min_y + y as i32,
+                        chunk_pos.z * 16 + z as i32,
+                    );
+
+                    scheduled.schedule_new(instance_entity, pos, current_tick, &mut rng);
+                },
+            );
+        }
+    }
+}
+
+/// Extinguishes fire when a player punches it.
+pub(crate) fn extinguish_fire(
+    clients: Query<&Client>,
+    mut instances: Query<&mut Instance>,
+    mut start_digging: EventReader<StartDigging>,
+) {
+    for event in start_digging.iter() {
+        let Ok(client) = clients.get(event.client) else {
+            continue
+        };
+
+        let Ok(mut instance) = instances.get_mut(client.instance()) else {
+            continue
+        };
+
+        let pos = event.position.get_in_direction(event.face);
+
+        if is_fire(block_state(&instance, pos)) {
+            instance.set_block(pos, BlockState::AIR);
+
+            instance.play_sound(
+                Sound::BlockFireExtinguish,
+                SoundCategory::Block,
+                [pos.x as f64 + 0.5, pos.y as f64 + 0.5, pos.z as f64 + 0.5],
+                0.5,
+                2.0,
+            );
+        }
+    }
+}
+
+pub(crate) fn tick_fire(
+    server: Res<Server>,
+    skipped: Res<SkippedInstances>,
+    budget: Res<TickBudget>,
+    mut instances: Query<(&mut Instance, Option<&GameRules>)>,
+    clients: Query<&Client>,
+    mut scheduled: ResMut<ScheduledFireTicks>,
+    mut ignite_tnt: EventWriter<IgniteTnt>,
+) {
+    // Fire stays due until the server is no longer overloaded.
+    if budget.is_shedding(SheddableWork::RandomTicks) {
+        return;
+    }
+
+    let current_tick = server.current_tick();
+    let views = client_views_by_instance(&clients);
+    let mut rng = rand::thread_rng();
+
+    // Fire in skipped instances stays due until they are simulated again.
+    let due: Vec<_> = scheduled
+        .ticks
+        .iter()
+        .filter(|(&(instance, _), &tick)| tick <= current_tick && !skipped.contains(instance))
+        .map(|(&key, _)| key)
+        .collect();
+
+    for (instance_entity, pos) in due {
+        let Ok((mut instance, rules)) = instances.get_mut(instance_entity) else {
+            scheduled.ticks.remove(&(instance_entity, pos));
+            continue;
+        };
+
+        let state = block_state(&instance, pos);
+
+        if state.to_kind() != BlockKind::Fire {
+            // Soul fire never spreads or burns out.
+            scheduled.ticks.remove(&(instance_entity, pos));
+            continue;
+        }
+
+        let chunk_pos = ChunkPos::from_block_pos(pos);
+
+        let simulated = views.get(&instance_entity).map_or(false, |views| {
+            views.iter().any(|view| view.contains(chunk_pos))
+        });
+
+        if !simulated || !rules.map_or(true, |rules| rules.do_fire_tick) {
+            scheduled.schedule(instance_entity, pos, current_tick, &mut rng);
+            continue;
+        }
+
+        let age = state
+            .get(PropName::Age)
+            .and_then(|age| age.to_u16())
+            .unwrap_or(0);
+
+        let below = block_state(&instance, pos.get_in_direction(BlockFace::Bottom));
+        let infiniburn = matches!(
+            below.to_kind(),
+            BlockKind::Netherrack | BlockKind::MagmaBlock
+        );
+
+        let new_age = (age + rng.gen_range(0..3) / 2).min(MAX_FIRE_AGE);
+
+        if !infiniburn {
+            let burns_out = if !has_flammable_neighbor(&instance, pos) {
+                !below.is_opaque() || age > 3
+            } else {
+                age == MAX_FIRE_AGE && flammability(below).is_none() && rng.gen_range(0..4) == 0
+            };
+
+            if burns_out {
+                instance.set_block(pos, BlockState::AIR);
+                scheduled.ticks.remove(&(instance_entity, pos));
+                continue;
+            }
+        }
+
+        if new_age != age {
+            if let Some(age) = PropValue::from_u16(new_age) {
+                instance.set_block(pos, state.set(PropName::Age, age));
+            }
+        }
+
+        // Burn away the blocks directly next to the fire.
+        for face in FACES {
+            let target = pos.get_in_direction(face);
+            let chance = if matches!(face, BlockFace::Bottom | BlockFace::Top) {
+                250
+            } else {
+                300
+            };
+
+            let target_state = block_state(&instance, target);
+
+            let Some((_, burn_odds)) = flammability(target_state) else {
+                continue
+            };
+
+            if rng.gen_range(0..chance) >= burn_odds {
+                continue;
+            }
+
+            if target_state.to_kind() == BlockKind::Tnt {
+                ignite_tnt.send(IgniteTnt {
+                    instance: instance_entity,
+                    position: target,
+                    cause: TntIgnitionCause::Fire,
+                    igniter: None,
+                    fuse: None,
+                });
+            } else if rng.gen_range(0..new_age + 10) < 5 {
+                instance.set_block(target, BlockState::AIR);
+
+                let age = (new_age + rng.gen_range(0..5) / 4).min(MAX_FIRE_AGE);
+
+                if let Some(fire) = fire_state(&instance, target, age) {
+                    instance.set_block(target, fire);
+                    scheduled.schedule(instance_entity, target, current_tick, &mut rng);
+                }
+            } else {
+                instance.set_block(target, BlockState::AIR);
+            }
+        }
+
+        // Spread to the air blocks around the fire.
+        for dx in -1..=1 {
+            for dz in -1..=1 {
+                for dy in -1..=4 {
+                    if dx == 0 && dy == 0 && dz == 0 {
+                        continue;
+                    }
+
+                    let target = BlockPos::new(pos.x + dx, pos.y + dy, pos.z + dz);
+
+                    if !block_state(&instance, target).is_air() {
+                        continue;
+                    }
+
+                    let odds = ignite_odds(&instance, target);
+
+                    if odds == 0 {
+                        continue;
+                    }
+
+                    // Spreading upwards is less likely the further up the target is.
+                    let rate = if dy > 1 { 100 + (dy - 1) * 100 } else { 100 };
+
+                    // Vanilla adds 7 for every level of difficulty. Assume normal.
+                    let chance = (odds + 40 + 14) / (new_age as u32 + 30);
+
+                    if chance > 0 && rng.gen_range(0..rate) as u32 <= chance {
+                        let age = (new_age + rng.gen_range(0..5) / 4).min(MAX_FIRE_AGE);
+
+                        if let Some(fire) = fire_state(&instance, target, age) {
+                            instance.set_block(target, fire);
+                            scheduled.schedule(instance_entity, target, current_tick, &mut rng);
+                        }
+                    }
+                }
+            }
+        }
+
+        scheduled.schedule(instance_entity, pos, current_tick, &mut rng);
+    }
+}
+
+/// Sets entities standing in fire or lava on fire and extinguishes entities in
+/// water.
+pub(crate) fn ignite_entities(
+    mut commands: Commands,
+    server: Res<Server>,
+    instances: Query<&Instance>,
+    entities: Query<(Entity, &McEntity, Option<&OnFire>), Without<Despawned>>,
+    clients: Query<(Entity, &Client, Option<&OnFire>), Without<McEntity>>,
+    mut damage: EventWriter<EntityDamage>,
+) {
+    let positions = entities
+        .iter()
+        .map(|(entity, mc_entity, on_fire)| {
+            (entity, mc_entity.instance(), mc_entity.position(), on_fire)
+        })
+        .chain(clients.iter().map(|(entity, client, on_fire)| {
+            (entity, client.instance(), client.position(), on_fire)
+        }));
+
+    for (entity, instance, position, on_fire) in positions {
+        let Ok(instance) = instances.get(instance) else {
+            continue
+        };
+
+        let (burn_ticks, kind, amount) =
+            match block_state(instance, BlockPos::at(position)).to_kind() {
+                BlockKind::Fire | BlockKind::SoulFire => (FIRE_BURN_TICKS, DamageKind::InFire, 1.0),
+                BlockKind::Lava => (LAVA_BURN_TICKS, DamageKind::Lava, 4.0),
+                BlockKind::Water => {
+                    if on_fire.is_some() {
+                        commands.entity(entity).remove::<OnFire>();
+                    }
+                    continue;
+                }
+                _ => continue,
+            };
+
+        if on_fire.map_or(true, |on_fire| on_fire.ticks < burn_ticks) {
+            commands.entity(entity).insert(OnFire::new(burn_ticks));
+        }
+
+        if server.current_tick() % 10 == 0 {
+            damage.send(EntityDamage {
+                entity,
+                source: None,
+                kind,
+                amount,
+            });
+        }
+    }
+}
+
+pub(crate) fn tick_burning_entities(
+    mut commands: Commands,
+    mut burning: Query<(
+        Entity,
+        &mut OnFire,
+        Option<&mut McEntity>,
+        Option<&mut Client>,
+    )>,
+    mut damage: EventWriter<EntityDamage>,
+) {
+    for (entity, mut on_fire, mut mc_entity, mut client) in &mut burning {
+        on_fire.ticks = on_fire.ticks.saturating_sub(1);
+
+        let burning = on_fire.ticks > 0;
+
+        if let Some(mc_entity) = &mut mc_entity {
+            mc_entity.data_mut().set_on_fire(burning);
+        }
+
+        if let Some(client) = &mut client {
+            client.player_mut().set_on_fire(burning);
+        }
+
+        if !burning {
+            commands.entity(entity).remove::<OnFire>();
+        } else if on_fire.ticks % 20 == 0 {
+            damage.send(EntityDamage {
+                entity,
+                source: None,
+                kind: DamageKind::OnFire,
+                amount: 1.0,
+            });
+        }
+    }
+}
+
+/// Clears the on fire flag of entities that had their [`OnFire`] component
+/// removed.
+pub(crate) fn extinguish_entities(
+    removed: RemovedComponents<OnFire>,
+    mut entities: Query<(Option<&mut McEntity>, Option<&mut Client>), Without<OnFire>>,
+) {
+    for entity in removed.iter() {
+        let Ok((mc_entity, client)) = entities.get_mut(entity) else {
+            continue
+        };
+
+        if let Some(mut mc_entity) = mc_entity {
+            mc_entity.data_mut().set_on_fire(false);
+        }
+
+        if let Some(mut client) = client {
+            client.player_mut().set_on_fire(false);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy_app::App;
+
+    use super::*;
+    use crate::instance::Chunk;
+    use crate::unit_test::util::scenario_single_client;
+
+    #[test]
+    fn burning_entity_is_extinguished() {
+        let mut app = App::new();
+        let (client_ent, _) = scenario_single_client(&mut app);
+
+        app.world.entity_mut(client_ent).insert(OnFire::new(3));
+
+        app.update();
+        assert!(app
+            .world
+            .get::<Client>(client_ent)
+            .unwrap()
+            .player()
+            .get_on_fire());
+
+        app.update();
+        app.update();
+        app.update();
+
+        assert!(app.world.get::<OnFire>(client_ent).is_none());
+        assert!(!app
+            .world
+            .get::<Client>(client_ent)
+            .unwrap()
+            .player()
+            .get_on_fire());
+    }
+
+    #[test]
+    fn fire_burns_out_without_fuel() {
+        let mut app = App::new();
+        let (client_ent, _) = scenario_single_client(&mut app);
+        let instance_ent = app.world.get::<Client>(client_ent).unwrap().instance();
+
+        let floor = BlockPos::new(0, 0, 0);
+        let fire = BlockPos::new(0, 1, 0);
+
+        let mut instance = app.world.get_mut::<Instance>(instance_ent).unwrap();
+        instance.insert_chunk([0, 0], Chunk::default());
+        instance.set_block(floor, BlockState::STONE);
+
+        app.world.send_event(IgniteBlock {
+            instance: instance_ent,
+            position: fire,
+            igniter: None,
+        });
+
+        app.update();
+
+        let instance = app.world.get::<Instance>(instance_ent).unwrap();
+        assert!(is_fire(instance.block(fire).unwrap().state()));
+
+        // Fire on stone burns out once it grows old enough.
+        for _ in 0..2000 {
+            app.update();
+        }
+
+        let instance = app.world.get::<Instance>(instance_ent).unwrap();
+        assert!(instance.block(fire).unwrap().state().is_air());
+    }
+
+    #[test]
+    fn fire_set_directly_is_scheduled() {
+        let mut app = App::new();
+        let (client_ent, _) = scenario_single_client(&mut app);
+        let instance_ent = app.world.get::<Client>(client_ent).unwrap().instance();
+
+        // Fire in a chunk as it is inserted.
+        let mut chunk = Chunk::new(1);
+        chunk.set_block_state(0, 1, 0, BlockState::FIRE);
+
+        let mut instance = app.world.get_mut::<Instance>(instance_ent).unwrap();
+        instance.insert_chunk([0, 0], chunk);
+
+        app.update();
+
+        // Fire set in a chunk which was already sent.
+        let mut instance = app.world.get_mut::<Instance>(instance_ent).unwrap();
+        instance.set_block([5, 2, 5], BlockState::FIRE);
+
+        app.update();
+
+        let scheduled = app.world.resource::<ScheduledFireTicks>();
+        let min_y = app.world.get::<Instance>(instance_ent).unwrap().min_y();
+
+        for pos in [BlockPos::new(0, min_y + 1, 0), BlockPos::new(5, 2, 5)] {
+            assert!(scheduled.ticks.contains_key(&(instance_ent, pos)));
+        }
+    }
+}
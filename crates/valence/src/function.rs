@@ -0,0 +1,330 @@
+//! Functions, which are lists of commands run one after another.
+//!
+//! Functions are written in `.mcfunction` files with one command per line.
+//! The leading slash of commands is optional, and empty lines and lines
+//! starting with `#` are skipped. A line `function <name>` runs another
+//! function in its place.
+//!
+//! [`Functions::load_dir`] loads the functions of a data directory laid out
+//! like the `data` directory of data packs, where the file
+//! `<namespace>/functions/<path>.mcfunction` contains the function
+//! `<namespace>:<path>`:
+//!
+//! ```no_run
+//! use valence::function::Functions;
+//! use valence::protocol::ident;
+//!
+//! # fn run(client: valence::prelude::Entity) -> valence::anyhow::Result<()> {
+//! let mut functions = Functions::new();
+//! functions.load_dir("data")?;
+//! functions.run(ident!("lobby:welcome").as_str_ident(), client);
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! A running function sends its commands as [`ChatCommand`] events from the
+//! client it was run for, so they are handled as if the client typed them.
+//! Commands in the [`CommandRegistry`] are dispatched as usual, including the
+//! checks of permissions and cooldowns. At most
+//! [`commands_per_tick`](Functions::commands_per_tick) commands are run per
+//! tick, and the remaining commands are run on the following ticks.
+//!
+//! [`CommandRegistry`]: crate::command::CommandRegistry
+
+use std::collections::{HashMap, VecDeque};
+use std::path::Path;
+
+use anyhow::Context;
+use bevy_ecs::prelude::*;
+use tracing::warn;
+use valence_protocol::Ident;
+
+use crate::client::event::ChatCommand;
+
+/// The default number of commands run per tick.
+pub const DEFAULT_COMMANDS_PER_TICK: usize = 64;
+
+const FUNCTION_COMMAND: &str = "function";
+
+/// A list of commands.
+#[derive(Clone, PartialEq, Eq, Default, Debug)]
+pub struct Function {
+    commands: Vec<String>,
+}
+
+impl Function {
+    pub fn new<I>(commands: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Into<String>,
+    {
+        Self {
+            commands: commands.into_iter().map(Into::into).collect(),
+        }
+    }
+
+    /// Parses the contents of an `.mcfunction` file.
+    pub fn parse(source: &str) -> Self {
+        Self::new(
+            source
+                .lines()
+                .map(str::trim)
+                .filter(|line| !line.is_empty() && !line.starts_with('#'))
+                .map(|line| line.strip_prefix('/').unwrap_or(line)),
+        )
+    }
+
+    /// The commands of the function, without the leading slash.
+    pub fn commands(&self) -> &[String] {
+        &self.commands
+    }
+}
+
+/// A [`Resource`] containing the functions of the server and the commands of
+/// running functions.
+#[derive(Resource, Clone, Debug)]
+pub struct Functions {
+    functions: HashMap<Ident<String>, Function>,
+    /// The commands left to run and the clients they are run for.
+    queue: VecDeque<(Entity, String)>,
+    commands_per_tick: usize,
+}
+
+impl Functions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Loads every `.mcfunction` file in the `functions` directory of every
+    /// namespace in the data directory. Returns the number of functions
+    /// loaded.
+    pub fn load_dir(&mut self, dir: impl AsRef<Path>) -> anyhow::Result<usize> {
+        let dir = dir.as_ref();
+        let mut count = 0;
+
+        let namespaces =
+            std::fs::read_dir(dir).with_context(|| format!("failed to read {}", dir.display()))?;
+
+        for namespace in namespaces {
+            let namespace = namespace?;
+            let functions_dir = namespace.path().join("functions");
+
+            if !functions_dir.is_dir() {
+                continue;
+            }
+
+            let Some(namespace) = namespace.file_name().to_str().map(str::to_owned) else {
+                continue;
+            };
+
+            count += self.load_functions(&namespace, &functions_dir, "")?;
+        }
+
+        Ok(count)
+    }
+
+    /// Loads the functions in the directory, whose path relative to the
+    /// `functions` directory is `prefix`.
+    fn load_functions(
+        &mut self,
+        namespace: &str,
+        dir: &Path,
+        prefix: &str,
+    ) -> anyhow::Result<usize> {
+        let mut count = 0;
+
+        let entries =
+            std::fs::read_dir(dir).with_context(|| format!("failed to read {}", dir.display()))?;
+
+        for entry in entries {
+            let path = entry?.path();
+
+            let Some(name) = path.file_stem().and_then(|s| s.to_str()) else {
+                continue;
+            };
+
+            if path.is_dir() {
+                count += self.load_functions(namespace, &path, &format!("{prefix}{name}/"))?;
+            } else if path.extension().map_or(false, |ext| ext == "mcfunction") {
+                let source = std::fs::read_to_string(&path)
+                    .with_context(|| format!("failed to read {}", path.display()))?;
+
+                let id = Ident::new(format!("{namespace}:{prefix}{name}"))
+                    .with_context(|| format!("invalid function name for {}", path.display()))?;
+
+                self.functions.insert(id, Function::parse(&source));
+                count += 1;
+            }
+        }
+
+        Ok(count)
+    }
+
+    /// Adds a function, returning the function it replaced.
+    pub fn insert(&mut self, id: impl Into<Ident<String>>, function: Function) -> Option<Function> {
+        self.functions.insert(id.into(), function)
+    }
+
+    pub fn remove(&mut self, id: Ident<&str>) -> Option<Function> {
+        self.functions.remove(&id.to_owned_ident())
+    }
+
+    pub fn get(&self, id: Ident<&str>) -> Option<&Function> {
+        self.functions.get(&id.to_owned_ident())
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (Ident<&str>, &Function)> + '_ {
+        self.functions.iter().map(|(id, f)| (id.as_str_ident(), f))
+    }
+
+    /// Runs the function for the client after the commands of the functions
+    /// already running. Returns `false` if the function does not exist.
+    pub fn run(&mut self, id: Ident<&str>, client: Entity) -> bool {
+        let Some(function) = self.functions.get(&id.to_owned_ident()) else {
+            return false;
+        };
+
+        self.queue
+            .extend(function.commands.iter().map(|c| (client, c.clone())));
+
+        true
+    }
+
+    /// The number of commands left to run.
+    pub fn queued(&self) -> usize {
+        self.queue.len()
+    }
+
+    /// Stops all running functions.
+    pub fn cancel_all(&mut self) {
+        self.queue.clear();
+    }
+
+    /// The maximum number of commands run per tick.
+    pub fn commands_per_tick(&self) -> usize {
+        self.commands_per_tick
+    }
+
+    pub fn set_commands_per_tick(&mut self, commands_per_tick: usize) {
+        self.commands_per_tick = commands_per_tick;
+    }
+}
+
+impl Default for Functions {
+    fn default() -> Self {
+        Self {
+            functions: HashMap::new(),
+            queue: VecDeque::new(),
+            commands_per_tick: DEFAULT_COMMANDS_PER_TICK,
+        }
+    }
+}
+
+/// Sends the commands of running functions to the command dispatcher.
+pub(crate) fn run_functions(
+    functions: Option<ResMut<Functions>>,
+    mut chat_commands: EventWriter<ChatCommand>,
+) {
+    let Some(mut functions) = functions else {
+        return;
+    };
+
+    if functions.queue.is_empty() {
+        return;
+    }
+
+    for _ in 0..functions.commands_per_tick {
+        let Some((client, command)) = functions.queue.pop_front() else {
+            break;
+        };
+
+        let called = command
+            .strip_prefix(FUNCTION_COMMAND)
+            .and_then(|rest| rest.strip_prefix(' '))
+            .map(str::trim);
+
+        if let Some(called) = called {
+            // Calling a function counts as one command, and its commands are
+            // run before the rest of the calling function.
+            let function = Ident::new(called)
+                .ok()
+                .and_then(|id| functions.functions.get(&id.to_owned_ident()))
+                .cloned();
+
+            match function {
+                Some(function) => {
+                    for command in function.commands.into_iter().rev() {
+                        functions.queue.push_front((client, command));
+                    }
+                }
+                None => warn!("function \"{called}\" does not exist"),
+            }
+
+            continue;
+        }
+
+        chat_commands.send(ChatCommand {
+            client,
+            command: command.into(),
+            timestamp: 0,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy_app::App;
+    use valence_protocol::ident;
+
+    use super::*;
+    use crate::command::{CommandExecuted, CommandInfo, CommandRegistry};
+    use crate::unit_test::util::scenario_single_client;
+
+    #[test]
+    fn parse_skips_comments_and_slashes() {
+        let function = Function::parse("# Greets the player\n\n/say hello\n  tp 0 64 0  \n");
+
+        assert_eq!(function.commands(), ["say hello", "tp 0 64 0"]);
+    }
+
+    #[test]
+    fn functions_are_run_within_budget() {
+        let mut app = App::new();
+        let (client_ent, _client_helper) = scenario_single_client(&mut app);
+
+        let mut registry = CommandRegistry::new();
+        registry.register(CommandInfo::new("say"));
+        app.insert_resource(registry);
+
+        let mut functions = Functions::new();
+        functions.set_commands_per_tick(2);
+        functions.insert(
+            ident!("test:main"),
+            Function::new(["say 1", "function test:inner", "say 4"]),
+        );
+        functions.insert(ident!("test:inner"), Function::new(["say 2", "say 3"]));
+        assert!(functions.run(ident!("test:main").as_str_ident(), client_ent));
+        assert!(!functions.run(ident!("test:missing").as_str_ident(), client_ent));
+        app.insert_resource(functions);
+
+        let mut reader = app.world.resource::<Events<CommandExecuted>>().get_reader();
+        let mut executed = vec![];
+
+        for _ in 0..3 {
+            app.update();
+
+            let events = app.world.resource::<Events<CommandExecuted>>();
+            executed.push(
+                reader
+                    .iter(events)
+                    .map(|e| e.args.clone())
+                    .collect::<Vec<_>>(),
+            );
+        }
+
+        // The call of the inner function uses up the budget of a command.
+        assert_eq!(executed[0], ["1"]);
+        assert_eq!(executed[1], ["2", "3"]);
+        assert_eq!(executed[2], ["4"]);
+    }
+}
@@ -0,0 +1,36 @@
+//! Per-instance game rules.
+
+use bevy_ecs::prelude::*;
+
+/// A [`Component`] containing the game rules of an
+/// [`Instance`](crate::instance::Instance).
+///
+/// Insert this component on the same entity as the instance to change its
+/// rules. Instances without this component use the default rules.
+#[derive(Component, Clone, PartialEq, Debug)]
+pub struct GameRules {
+    /// Whether or not fire spreads to nearby blocks and burns out. Existing
+    /// fire blocks are left untouched while this is `false`. Equivalent to
+    /// the vanilla `doFireTick` rule.
+    ///
+    /// # Default Value
+    ///
+    /// `true`
+    pub do_fire_tick: bool,
+    /// Whether or not clients take fall damage. Equivalent to the vanilla
+    /// `fallDamage` rule.
+    ///
+    /// # Default Value
+    ///
+    /// `true`
+    pub fall_damage: bool,
+}
+
+impl Default for GameRules {
+    fn default() -> Self {
+        Self {
+            do_fire_tick: true,
+            fall_damage: true,
+        }
+    }
+}
@@ -0,0 +1,127 @@
+//! Arm swings of clients.
+//!
+//! Clients swing their arms when they left-click, even when they click at
+//! nothing, and when they use or place items. Every swing is reported with a
+//! [`HandSwingEvent`], which makes it possible to react to left clicks into
+//! the air, for instance to cast a spell:
+//!
+//! ```
+//! use valence::client::event::HandSwingEvent;
+//! use valence::prelude::*;
+//!
+//! fn cast_spells(mut clients: Query<&mut Client>, mut swings: EventReader<HandSwingEvent>) {
+//!     for swing in swings.iter() {
+//!         if let Ok(mut client) = clients.get_mut(swing.client) {
+//!             client.send_message("Whoosh!");
+//!         }
+//!     }
+//! }
+//! ```
+//!
+//! The swing animation is played by the [`McEntity`] of the client, if it has
+//! one, so that other players see it. This can be turned off with
+//! [`HandSwingSettings`].
+
+use bevy_ecs::prelude::*;
+use valence_protocol::types::Hand;
+
+use crate::client::event::HandSwingEvent;
+use crate::client::Client;
+use crate::entity::{EntityAnimation, EntityKind, McEntity};
+
+/// A [`Resource`] containing global settings for arm swings.
+#[derive(Resource, Clone, Debug)]
+pub struct HandSwingSettings {
+    /// Whether or not the [`McEntity`] of a client plays the swing animation
+    /// when the client swings its arm.
+    ///
+    /// # Default Value
+    ///
+    /// `true`
+    pub broadcast: bool,
+}
+
+impl Default for HandSwingSettings {
+    fn default() -> Self {
+        Self { broadcast: true }
+    }
+}
+
+/// Plays the swing animation of clients swinging their arms.
+pub(crate) fn broadcast_hand_swings(
+    settings: Res<HandSwingSettings>,
+    mut clients: Query<&mut McEntity, With<Client>>,
+    mut swings: EventReader<HandSwingEvent>,
+) {
+    if !settings.broadcast {
+        swings.clear();
+        return;
+    }
+
+    for swing in swings.iter() {
+        let Ok(mut entity) = clients.get_mut(swing.client) else {
+            continue;
+        };
+
+        if entity.kind() == EntityKind::Player {
+            entity.trigger_animation(match swing.hand {
+                Hand::Main => EntityAnimation::SwingMainHand,
+                Hand::Off => EntityAnimation::SwingOffHand,
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy_app::App;
+    use valence_protocol::packets::c2s::play::SwingArm;
+    use valence_protocol::packets::S2cPlayPacket;
+
+    use super::*;
+    use crate::assert_packet_count;
+    use crate::unit_test::util::{create_mock_client, gen_client_info, scenario_single_client};
+
+    #[test]
+    fn swings_are_shown_to_other_players() -> anyhow::Result<()> {
+        let mut app = App::new();
+        let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+        let instance_ent = app.world.get::<Client>(client_ent).unwrap().instance();
+
+        let uuid = app.world.get::<Client>(client_ent).unwrap().uuid();
+        app.world.entity_mut(client_ent).insert(McEntity::with_uuid(
+            EntityKind::Player,
+            instance_ent,
+            uuid,
+        ));
+
+        let (mut other_client, mut other_helper) = create_mock_client(gen_client_info("other"));
+        other_client.set_instance(instance_ent);
+        app.world.spawn(other_client);
+
+        app.update();
+        other_helper.clear_sent();
+
+        client_helper.send(&SwingArm { hand: Hand::Off });
+
+        app.update();
+
+        let sent_packets = other_helper.collect_sent()?;
+        assert_packet_count!(sent_packets, 1, S2cPlayPacket::EntityAnimationS2c(_));
+
+        let swings = app.world.resource::<Events<HandSwingEvent>>();
+        assert_eq!(swings.get_reader().iter(swings).count(), 1);
+
+        app.world.resource_mut::<HandSwingSettings>().broadcast = false;
+        other_helper.clear_sent();
+
+        client_helper.send(&SwingArm { hand: Hand::Main });
+
+        app.update();
+
+        let sent_packets = other_helper.collect_sent()?;
+        assert_packet_count!(sent_packets, 0, S2cPlayPacket::EntityAnimationS2c(_));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,339 @@
+//! Putting idle instances to sleep.
+//!
+//! Servers hosting many arenas or lobbies often have instances which no one
+//! is playing in. Adding a [`Hibernation`] component to the entity of an
+//! [`Instance`] makes it hibernate once it has had no clients for a number of
+//! ticks: its simulation is paused with a [`TickRate`] and, if the component
+//! has a [`HibernationStorage`], its chunks are moved out of the instance and
+//! into the storage.
+//!
+//! Hibernating instances wake up on their own on the tick a client is moved
+//! into them, before the client is sent any chunks, so code routing clients
+//! to instances does not need to know about hibernation. The
+//! [`InstanceHibernated`] and [`InstanceWokeUp`] events are sent when an
+//! instance falls asleep and wakes up.
+
+use std::fmt;
+
+use bevy_ecs::prelude::*;
+use rustc_hash::FxHashSet;
+use tracing::warn;
+
+use crate::client::Client;
+use crate::instance::{Chunk, Instance};
+use crate::tick_rate::TickRate;
+use crate::view::ChunkPos;
+
+/// Stores the chunks of hibernating instances.
+pub trait HibernationStorage: Send + Sync + 'static {
+    /// Stores the chunks moved out of an instance which is falling asleep. If
+    /// storing fails, the chunks are returned with the error and put back in
+    /// the instance.
+    fn store(
+        &mut self,
+        chunks: Vec<(ChunkPos, Chunk)>,
+    ) -> Result<(), (Vec<(ChunkPos, Chunk)>, anyhow::Error)>;
+
+    /// Returns the chunks passed to the last call to [`store`](Self::store)
+    /// when the instance wakes up.
+    fn load(&mut self) -> anyhow::Result<Vec<(ChunkPos, Chunk)>>;
+}
+
+/// A [`HibernationStorage`] which keeps the chunks in memory.
+///
+/// The chunks are stored without the packets cached for clients, so
+/// hibernating instances take up less memory even without writing them
+/// anywhere.
+#[derive(Clone, Default, Debug)]
+pub struct MemoryStorage {
+    chunks: Vec<(ChunkPos, Chunk)>,
+}
+
+impl MemoryStorage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl HibernationStorage for MemoryStorage {
+    fn store(
+        &mut self,
+        chunks: Vec<(ChunkPos, Chunk)>,
+    ) -> Result<(), (Vec<(ChunkPos, Chunk)>, anyhow::Error)> {
+        self.chunks = chunks;
+        Ok(())
+    }
+
+    fn load(&mut self) -> anyhow::Result<Vec<(ChunkPos, Chunk)>> {
+        Ok(std::mem::take(&mut self.chunks))
+    }
+}
+
+/// A [`Component`] which makes the [`Instance`] on the same entity hibernate
+/// when it has had no clients for some time.
+#[derive(Component)]
+pub struct Hibernation {
+    after: u32,
+    storage: Option<Box<dyn HibernationStorage>>,
+    idle_ticks: u32,
+    wake_requested: bool,
+    state: Option<Asleep>,
+}
+
+/// What to restore when a hibernating instance wakes up.
+#[derive(Copy, Clone, Debug)]
+struct Asleep {
+    /// If the [`TickRate`] of the instance was paused before it fell asleep,
+    /// or `None` if it had no tick rate.
+    was_paused: Option<bool>,
+}
+
+impl Hibernation {
+    /// Hibernates the instance after it has had no clients for the given
+    /// number of ticks. Its chunks stay in the instance unless a storage is
+    /// set.
+    pub fn new(after_ticks: u32) -> Self {
+        Self {
+            after: after_ticks,
+            storage: None,
+            idle_ticks: 0,
+            wake_requested: false,
+            state: None,
+        }
+    }
+
+    /// Moves the chunks of the instance into the storage while it hibernates.
+    #[must_use]
+    pub fn with_storage(mut self, storage: impl HibernationStorage) -> Self {
+        self.storage = Some(Box::new(storage));
+        self
+    }
+
+    /// Returns the number of ticks without clients after which the instance
+    /// hibernates.
+    pub fn after(&self) -> u32 {
+        self.after
+    }
+
+    pub fn set_after(&mut self, ticks: u32) {
+        self.after = ticks;
+    }
+
+    /// Returns the number of ticks the instance has had no clients for.
+    pub fn idle_ticks(&self) -> u32 {
+        self.idle_ticks
+    }
+
+    pub fn is_hibernating(&self) -> bool {
+        self.state.is_some()
+    }
+
+    /// Wakes the instance up at the end of this tick without waiting for a
+    /// client, for instance to prepare an arena before a match starts. The
+    /// instance hibernates again if it stays without clients.
+    pub fn wake(&mut self) {
+        self.wake_requested = true;
+    }
+}
+
+impl fmt::Debug for Hibernation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Hibernation")
+            .field("after", &self.after)
+            .field("storage", &self.storage.is_some())
+            .field("idle_ticks", &self.idle_ticks)
+            .field("wake_requested", &self.wake_requested)
+            .field("state", &self.state)
+            .finish()
+    }
+}
+
+/// An event sent when an instance with a [`Hibernation`] component falls
+/// asleep.
+#[derive(Clone, Debug)]
+pub struct InstanceHibernated {
+    pub instance: Entity,
+}
+
+/// An event sent when a hibernating instance wakes up.
+#[derive(Clone, Debug)]
+pub struct InstanceWokeUp {
+    pub instance: Entity,
+}
+
+type HibernatingInstance<'a> = (
+    Entity,
+    &'a mut Instance,
+    &'a mut Hibernation,
+    Option<&'a mut TickRate>,
+);
+
+/// Counts the ticks instances have been without clients, and puts them to
+/// sleep or wakes them up.
+pub(crate) fn update_hibernation(
+    mut commands: Commands,
+    mut instances: Query<HibernatingInstance>,
+    clients: Query<&Client>,
+    mut hibernated: EventWriter<InstanceHibernated>,
+    mut woke_up: EventWriter<InstanceWokeUp>,
+) {
+    let occupied: FxHashSet<Entity> = clients
+        .iter()
+        .filter(|client| !client.is_disconnected())
+        .map(|client| client.instance())
+        .collect();
+
+    for (entity, mut instance, mut hibernation, tick_rate) in &mut instances {
+        let hibernation = &mut *hibernation;
+
+        if occupied.contains(&entity) || hibernation.wake_requested {
+            hibernation.idle_ticks = 0;
+            hibernation.wake_requested = false;
+
+            let Some(asleep) = hibernation.state.take() else {
+                continue;
+            };
+
+            if let Some(storage) = &mut hibernation.storage {
+                match storage.load() {
+                    Ok(chunks) => {
+                        for (pos, chunk) in chunks {
+                            instance.insert_chunk(pos, chunk);
+                        }
+                    }
+                    Err(e) => warn!("failed to load chunks of hibernating instance: {e:#}"),
+                }
+            }
+
+            match (asleep.was_paused, tick_rate) {
+                (None, _) => {
+                    commands.entity(entity).remove::<TickRate>();
+                }
+                (Some(false), Some(mut tick_rate)) => tick_rate.resume(),
+                _ => {}
+            }
+
+            woke_up.send(InstanceWokeUp { instance: entity });
+            continue;
+        }
+
+        if hibernation.state.is_some() {
+            continue;
+        }
+
+        hibernation.idle_ticks = hibernation.idle_ticks.saturating_add(1);
+
+        if hibernation.idle_ticks < hibernation.after {
+            continue;
+        }
+
+        if let Some(storage) = &mut hibernation.storage {
+            let positions: Vec<_> = instance.chunks().map(|(pos, _)| pos).collect();
+            let chunks: Vec<_> = positions
+                .into_iter()
+                .filter_map(|pos| Some((pos, instance.remove_chunk(pos)?)))
+                .collect();
+
+            if let Err((chunks, e)) = storage.store(chunks) {
+                warn!("failed to store chunks of hibernating instance: {e:#}");
+
+                for (pos, chunk) in chunks {
+                    instance.insert_chunk(pos, chunk);
+                }
+            }
+        }
+
+        let was_paused = match tick_rate {
+            Some(mut tick_rate) => {
+                let was_paused = tick_rate.is_paused();
+                tick_rate.pause();
+                Some(was_paused)
+            }
+            None => {
+                commands.entity(entity).insert(TickRate::paused());
+                None
+            }
+        };
+
+        hibernation.state = Some(Asleep { was_paused });
+        hibernated.send(InstanceHibernated { instance: entity });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy_app::App;
+
+    use super::*;
+    use crate::dimension::DimensionId;
+    use crate::server::Server;
+    use crate::unit_test::util::scenario_single_client;
+
+    #[test]
+    fn idle_instances_hibernate_and_wake_up() {
+        let mut app = App::new();
+        let (client_ent, _client_helper) = scenario_single_client(&mut app);
+
+        let mut arena = app
+            .world
+            .resource::<Server>()
+            .new_instance(DimensionId::default());
+        arena.insert_chunk([0, 0], Chunk::default());
+        arena.insert_chunk([1, 0], Chunk::default());
+
+        let arena_ent = app
+            .world
+            .spawn((
+                arena,
+                Hibernation::new(2).with_storage(MemoryStorage::new()),
+            ))
+            .id();
+
+        app.update();
+        assert!(!app
+            .world
+            .get::<Hibernation>(arena_ent)
+            .unwrap()
+            .is_hibernating());
+
+        app.update();
+
+        let hibernation = app.world.get::<Hibernation>(arena_ent).unwrap();
+        assert!(hibernation.is_hibernating());
+        assert_eq!(
+            app.world
+                .get::<Instance>(arena_ent)
+                .unwrap()
+                .chunks()
+                .count(),
+            0
+        );
+        assert!(app.world.get::<TickRate>(arena_ent).unwrap().is_paused());
+
+        // Routing a client to the arena wakes it up.
+        app.world
+            .get_mut::<Client>(client_ent)
+            .unwrap()
+            .set_instance(arena_ent);
+
+        app.update();
+
+        assert!(!app
+            .world
+            .get::<Hibernation>(arena_ent)
+            .unwrap()
+            .is_hibernating());
+        assert_eq!(
+            app.world
+                .get::<Instance>(arena_ent)
+                .unwrap()
+                .chunks()
+                .count(),
+            2
+        );
+        assert!(app.world.get::<TickRate>(arena_ent).is_none());
+
+        let events = app.world.resource::<Events<InstanceWokeUp>>();
+        assert_eq!(events.get_reader().iter(events).count(), 1);
+    }
+}
@@ -0,0 +1,363 @@
+//! Animated HUD elements.
+//!
+//! Each element is a [`Component`] which is inserted on a client entity and
+//! updated every tick:
+//!
+//! - [`Countdown`] shows a title counting down the seconds, followed by a final
+//!   title such as "GO!".
+//! - [`BossBarTimer`] shows a boss bar which empties over a fixed amount of
+//!   time.
+//! - [`ActionBarTicker`] cycles through messages in the action bar, which can
+//!   also be used for scrolling text.
+
+use bevy_ecs::prelude::*;
+use uuid::Uuid;
+use valence_protocol::packets::s2c::play::{BossBar, SetTitleAnimationTimes};
+use valence_protocol::types::{BossBarAction, BossBarColor, BossBarDivision, BossBarFlags};
+use valence_protocol::Text;
+
+use crate::client::Client;
+
+/// The number of ticks in a second.
+const SECOND_TICKS: u32 = 20;
+
+/// The number of ticks after which the action bar is sent again. The vanilla
+/// client fades out action bar messages after three seconds.
+const ACTION_BAR_REFRESH_TICKS: u32 = 40;
+
+/// A [`Component`] that shows a countdown as a title to the [`Client`] on the
+/// same entity.
+///
+/// Every second, the number of remaining seconds is shown. When the countdown
+/// reaches zero, the finish title is shown, a [`CountdownFinished`] event is
+/// sent and the component is removed.
+#[derive(Component, Clone, Debug)]
+pub struct Countdown {
+    /// The number of seconds to count down from.
+    pub seconds: u32,
+    /// The title shown when the countdown reaches zero.
+    pub finish_title: Text,
+    /// The subtitle shown below the numbers and the finish title.
+    pub subtitle: Text,
+    elapsed: u32,
+}
+
+impl Countdown {
+    pub fn new(seconds: u32) -> Self {
+        Self {
+            seconds,
+            finish_title: Text::from("GO!"),
+            subtitle: Text::default(),
+            elapsed: 0,
+        }
+    }
+
+    #[must_use]
+    pub fn with_finish_title(mut self, finish_title: impl Into<Text>) -> Self {
+        self.finish_title = finish_title.into();
+        self
+    }
+
+    #[must_use]
+    pub fn with_subtitle(mut self, subtitle: impl Into<Text>) -> Self {
+        self.subtitle = subtitle.into();
+        self
+    }
+
+    /// Returns the number of whole seconds until the countdown finishes.
+    pub fn remaining_seconds(&self) -> u32 {
+        self.seconds - self.elapsed / SECOND_TICKS
+    }
+}
+
+/// An event sent when a [`Countdown`] has reached zero.
+#[derive(Clone, Debug)]
+pub struct CountdownFinished {
+    pub client: Entity,
+}
+
+/// A [`Component`] that shows a boss bar to the [`Client`] on the same entity
+/// which empties over `duration` ticks.
+///
+/// When the boss bar is empty, it is hidden, a [`BossBarTimerFinished`] event
+/// is sent and the component is removed. Use [`BossBarTimer::stop`] to hide
+/// the boss bar early. Removing the component directly leaves the boss bar on
+/// the client's screen.
+#[derive(Component, Clone, Debug)]
+pub struct BossBarTimer {
+    /// The title displayed above the boss bar.
+    pub title: Text,
+    /// The number of ticks until the boss bar is empty.
+    pub duration: u32,
+    pub color: BossBarColor,
+    pub division: BossBarDivision,
+    id: Uuid,
+    elapsed: u32,
+    shown: bool,
+    stopped: bool,
+    title_modified: bool,
+}
+
+impl BossBarTimer {
+    pub fn new(title: impl Into<Text>, duration: u32) -> Self {
+        Self {
+            title: title.into(),
+            duration,
+            color: BossBarColor::White,
+            division: BossBarDivision::NoDivision,
+            id: Uuid::from_u128(rand::random()),
+            elapsed: 0,
+            shown: false,
+            stopped: false,
+            title_modified: false,
+        }
+    }
+
+    #[must_use]
+    pub fn with_color(mut self, color: BossBarColor) -> Self {
+        self.color = color;
+        self
+    }
+
+    #[must_use]
+    pub fn with_division(mut self, division: BossBarDivision) -> Self {
+        self.division = division;
+        self
+    }
+
+    /// Changes the title of the boss bar while it is shown.
+    pub fn set_title(&mut self, title: impl Into<Text>) {
+        self.title = title.into();
+        self.title_modified = true;
+    }
+
+    /// Returns the number of ticks until the boss bar is empty.
+    pub fn remaining_ticks(&self) -> u32 {
+        self.duration.saturating_sub(self.elapsed)
+    }
+
+    /// Returns how full the boss bar is, from `1.0` to `0.0`.
+    pub fn progress(&self) -> f32 {
+        if self.duration == 0 {
+            0.0
+        } else {
+            self.remaining_ticks() as f32 / self.duration as f32
+        }
+    }
+
+    /// Hides the boss bar at the end of the tick as if the timer had finished.
+    pub fn stop(&mut self) {
+        self.stopped = true;
+    }
+}
+
+/// An event sent when a [`BossBarTimer`] has finished.
+#[derive(Clone, Debug)]
+pub struct BossBarTimerFinished {
+    pub client: Entity,
+    /// If the timer was ended early with [`BossBarTimer::stop`].
+    pub stopped: bool,
+}
+
+/// A [`Component`] that cycles through messages in the action bar of the
+/// [`Client`] on the same entity.
+///
+/// The ticker runs until the component is removed, after which the last
+/// message fades out.
+#[derive(Component, Clone, Debug)]
+pub struct ActionBarTicker {
+    /// The messages to cycle through.
+    pub messages: Vec<Text>,
+    /// The number of ticks each message is shown for.
+    pub interval: u32,
+    elapsed: u32,
+}
+
+impl ActionBarTicker {
+    pub fn new<T: Into<Text>>(messages: impl IntoIterator<Item = T>, interval: u32) -> Self {
+        Self {
+            messages: messages.into_iter().map(Into::into).collect(),
+            interval: interval.max(1),
+            elapsed: 0,
+        }
+    }
+
+    /// Creates a ticker which scrolls `text` from right to left through a
+    /// window of `width` characters, moving one character every `interval`
+    /// ticks.
+    pub fn scrolling(text: &str, width: usize, interval: u32) -> Self {
+        // Pad the text so that it scrolls in and out of view.
+        let mut padded = vec![' '; width];
+        padded.extend(text.chars());
+
+        let messages = (0..padded.len()).map(|start| {
+            padded
+                .iter()
+                .chain(std::iter::repeat(&' '))
+                .skip(start)
+                .take(width)
+                .collect::<String>()
+        });
+
+        Self::new(messages, interval)
+    }
+
+    /// Returns the message that is currently shown.
+    pub fn current(&self) -> Option<&Text> {
+        if self.messages.is_empty() {
+            None
+        } else {
+            Some(
+                &self.messages
+                    [(self.elapsed / self.interval.max(1)) as usize % self.messages.len()],
+            )
+        }
+    }
+}
+
+pub(crate) fn update_countdowns(
+    mut commands: Commands,
+    mut clients: Query<(Entity, &mut Client, &mut Countdown)>,
+    mut finished: EventWriter<CountdownFinished>,
+) {
+    for (client_id, mut client, mut countdown) in &mut clients {
+        if countdown.elapsed % SECOND_TICKS == 0 {
+            let remaining = countdown.remaining_seconds();
+
+            let title = if remaining == 0 {
+                countdown.finish_title.clone()
+            } else {
+                Text::from(remaining.to_string())
+            };
+
+            client.set_title(
+                title,
+                countdown.subtitle.clone(),
+                SetTitleAnimationTimes {
+                    fade_in: 0,
+                    stay: SECOND_TICKS as i32,
+                    fade_out: if remaining == 0 { 10 } else { 0 },
+                },
+            );
+
+            if remaining == 0 {
+                commands.entity(client_id).remove::<Countdown>();
+                finished.send(CountdownFinished { client: client_id });
+                continue;
+            }
+        }
+
+        countdown.elapsed += 1;
+    }
+}
+
+pub(crate) fn update_boss_bar_timers(
+    mut commands: Commands,
+    mut clients: Query<(Entity, &mut Client, &mut BossBarTimer)>,
+    mut finished: EventWriter<BossBarTimerFinished>,
+) {
+    for (client_id, mut client, mut timer) in &mut clients {
+        if timer.stopped || timer.elapsed > timer.duration {
+            if timer.shown {
+                client.write_packet(&BossBar {
+                    id: timer.id,
+                    action: BossBarAction::Remove,
+                });
+            }
+
+            commands.entity(client_id).remove::<BossBarTimer>();
+            finished.send(BossBarTimerFinished {
+                client: client_id,
+                stopped: timer.stopped,
+            });
+            continue;
+        }
+
+        if !timer.shown {
+            client.write_packet(&BossBar {
+                id: timer.id,
+                action: BossBarAction::Add {
+                    title: timer.title.clone(),
+                    health: timer.progress(),
+                    color: timer.color,
+                    division: timer.division,
+                    flags: BossBarFlags::new(),
+                },
+            });
+
+            timer.shown = true;
+            timer.title_modified = false;
+        } else {
+            if timer.title_modified {
+                client.write_packet(&BossBar {
+                    id: timer.id,
+                    action: BossBarAction::UpdateTitle(timer.title.clone()),
+                });
+
+                timer.title_modified = false;
+            }
+
+            client.write_packet(&BossBar {
+                id: timer.id,
+                action: BossBarAction::UpdateHealth(timer.progress()),
+            });
+        }
+
+        timer.elapsed += 1;
+    }
+}
+
+pub(crate) fn update_action_bar_tickers(mut clients: Query<(&mut Client, &mut ActionBarTicker)>) {
+    for (mut client, mut ticker) in &mut clients {
+        if ticker.elapsed % ticker.interval.max(1) == 0
+            || ticker.elapsed % ACTION_BAR_REFRESH_TICKS == 0
+        {
+            if let Some(message) = ticker.current() {
+                client.set_action_bar(message.clone());
+            }
+        }
+
+        ticker.elapsed = ticker.elapsed.wrapping_add(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy_app::App;
+    use valence_protocol::packets::S2cPlayPacket;
+
+    use super::*;
+    use crate::assert_packet_count;
+    use crate::unit_test::util::scenario_single_client;
+
+    #[test]
+    fn countdown_shows_each_second() -> anyhow::Result<()> {
+        let mut app = App::new();
+        let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+
+        app.update();
+        client_helper.clear_sent();
+
+        app.world.entity_mut(client_ent).insert(Countdown::new(3));
+
+        for _ in 0..3 * SECOND_TICKS + 1 {
+            app.update();
+        }
+
+        let sent_packets = client_helper.collect_sent()?;
+        // "3", "2", "1" and the finish title.
+        assert_packet_count!(sent_packets, 4, S2cPlayPacket::SetTitleText(_));
+        assert!(app.world.get::<Countdown>(client_ent).is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn scrolling_ticker_windows() {
+        let ticker = ActionBarTicker::scrolling("ab", 3, 1);
+
+        let messages: Vec<_> = ticker.messages.iter().map(|m| m.to_string()).collect();
+
+        assert_eq!(messages, ["   ", "  a", " ab", "ab ", "b  "]);
+    }
+}
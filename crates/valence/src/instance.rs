@@ -6,28 +6,45 @@ use bevy_ecs::prelude::*;
 pub use chunk_entry::*;
 use glam::{DVec3, Vec3};
 use num::integer::div_ceil;
-use rustc_hash::FxHashMap;
+use rustc_hash::{FxHashMap, FxHashSet};
 use valence_protocol::packets::s2c::particle::{Particle, ParticleS2c};
 use valence_protocol::packets::s2c::play::{SetActionBarText, SoundEffect};
 use valence_protocol::types::SoundCategory;
 use valence_protocol::{BlockPos, EncodePacket, LengthPrefixedArray, Sound, Text};
 
+use crate::budget::{TickBudget, TickPhase};
+use crate::client::Client;
 use crate::dimension::DimensionId;
 use crate::entity::McEntity;
+use crate::instance::chunk::SectionInterner;
 pub use crate::instance::chunk::{Block, BlockMut, BlockRef, Chunk};
+pub use crate::instance::collision::{BlockCollision, RaycastHit};
 use crate::packet::{PacketWriter, WritePacket};
 use crate::server::{Server, SharedServer};
-use crate::view::ChunkPos;
+use crate::view::{ChunkPos, ChunkView};
+use crate::weather::Weather;
+use crate::world_border::WorldBorder;
 use crate::Despawned;
 
 mod chunk;
 mod chunk_entry;
+mod collision;
 mod paletted_container;
 
 /// An Instance represents a Minecraft world, which consist of [`Chunk`]s.
 /// It manages updating clients when chunks change, and caches chunk and entity
 /// update packets on a per-chunk basis.
 ///
+/// Instances do not share any state with each other. The chunk and entity
+/// update packets of every instance are cached on a separate thread, so a
+/// large instance does not hold up caching the packets of the others. The
+/// gameplay systems, such as fire, weather and explosions, still go through
+/// all instances one after another. Instances created from the same
+/// [`WorldTemplate`](crate::template::WorldTemplate) share the unmodified
+/// sections of their chunks, which are copied when they are modified, and
+/// instances with [`SharedChunks`](crate::shared_chunks::SharedChunks) only
+/// hold the chunks of the store in view of clients.
+///
 /// To create a new instance, use [`SharedServer::new_instance`].
 /// ```
 /// use bevy_app::prelude::*;
@@ -57,6 +74,23 @@ pub struct Instance {
     pub(crate) packet_buf: Vec<u8>,
     /// Scratch space for writing packets.
     scratch: Vec<u8>,
+    /// More scratch space for writing packets, used while the instance is
+    /// updated in parallel with other instances.
+    update_scratch: Vec<u8>,
+    /// The entities whose update packets were cached this tick, paired with
+    /// the range of the packets in the buffer of their partition cell.
+    entity_update_ranges: Vec<(Entity, std::ops::Range<usize>)>,
+    /// The positions of the chunks which were borrowed mutably or inserted
+    /// this tick, and may have had blocks changed.
+    mutated_chunks: FxHashSet<ChunkPos>,
+    /// Shares the data of identical sections between the chunks of this
+    /// instance.
+    section_interner: SectionInterner,
+    weather: Weather,
+    time_of_day: Option<i64>,
+    /// If the time of day was set this tick.
+    time_of_day_modified: bool,
+    world_border: WorldBorder,
 }
 
 pub(crate) struct InstanceInfo {
@@ -65,6 +99,7 @@ pub(crate) struct InstanceInfo {
     min_y: i32,
     biome_registry_len: usize,
     compression_threshold: Option<u32>,
+    compression_level: u32,
     filler_sky_light_mask: Box<[u64]>,
     /// Sending filler light data causes the vanilla client to lag
     /// less. Hopefully we can remove this in the future.
@@ -110,6 +145,7 @@ impl Instance {
                 min_y: dim.min_y,
                 biome_registry_len: shared.biomes().len(),
                 compression_threshold: shared.compression_threshold(),
+                compression_level: shared.compression_level(),
                 filler_sky_light_mask: sky_light_mask.into(),
                 filler_sky_light_arrays: vec![
                     LengthPrefixedArray([0xff; 2048]);
@@ -119,6 +155,14 @@ impl Instance {
             },
             packet_buf: vec![],
             scratch: vec![],
+            update_scratch: vec![],
+            entity_update_ranges: vec![],
+            mutated_chunks: FxHashSet::default(),
+            section_interner: SectionInterner::default(),
+            weather: Weather::CLEAR,
+            time_of_day: None,
+            time_of_day_modified: false,
+            world_border: WorldBorder::DEFAULT,
         }
     }
 
@@ -130,6 +174,63 @@ impl Instance {
         self.info.section_count
     }
 
+    /// Returns the Y coordinate of the lowest block in this instance.
+    pub fn min_y(&self) -> i32 {
+        self.info.min_y
+    }
+
+    /// Returns the current [`Weather`] in this instance.
+    pub fn weather(&self) -> Weather {
+        self.weather
+    }
+
+    /// Sets the [`Weather`] in this instance. All clients in the instance are
+    /// updated at the end of the tick, except for clients with a weather
+    /// override.
+    pub fn set_weather(&mut self, weather: Weather) {
+        self.weather = weather;
+    }
+
+    /// Returns the time of day last set in this instance in ticks, or `None`
+    /// if it was never set. `6000` is noon and `18000` is midnight.
+    pub fn time_of_day(&self) -> Option<i64> {
+        self.time_of_day
+    }
+
+    /// Sets the time of day of the clients in this instance. Clients keep
+    /// advancing the time on their own afterwards, so the time only needs to
+    /// be set to change it. Clients with a time override are not affected.
+    ///
+    /// Instances have no time of day by default, which leaves clients to run
+    /// their own day-night cycle.
+    pub fn set_time_of_day(&mut self, time_of_day: impl Into<Option<i64>>) {
+        self.time_of_day = time_of_day.into();
+        self.time_of_day_modified = true;
+    }
+
+    pub(crate) fn time_of_day_modified(&self) -> bool {
+        self.time_of_day_modified
+    }
+
+    /// Returns the loaded chunks which may have had blocks changed this tick,
+    /// without going through the chunks which were not modified.
+    pub(crate) fn mutated_chunks(&self) -> impl Iterator<Item = (ChunkPos, &Chunk<true>)> + '_ {
+        self.mutated_chunks
+            .iter()
+            .filter_map(|&pos| Some((pos, self.chunk(pos)?)))
+    }
+
+    /// Returns the [`WorldBorder`] of this instance.
+    pub fn world_border(&self) -> &WorldBorder {
+        &self.world_border
+    }
+
+    /// Sets the [`WorldBorder`] of this instance. Clients with a world border
+    /// override are not affected.
+    pub fn set_world_border(&mut self, world_border: WorldBorder) {
+        self.world_border = world_border;
+    }
+
     /// Get a reference to the chunk at the given position, if it is loaded.
     pub fn chunk(&self, pos: impl Into<ChunkPos>) -> Option<&Chunk<true>> {
         self.partition
@@ -140,9 +241,13 @@ impl Instance {
     /// Get a mutable reference to the chunk at the given position, if it is
     /// loaded.
     pub fn chunk_mut(&mut self, pos: impl Into<ChunkPos>) -> Option<&mut Chunk<true>> {
-        self.partition
-            .get_mut(&pos.into())
-            .and_then(|p| p.chunk.as_mut())
+        let pos = pos.into();
+        let chunk = self
+            .partition
+            .get_mut(&pos)
+            .and_then(|p| p.chunk.as_mut())?;
+        self.mutated_chunks.insert(pos);
+        Some(chunk)
     }
 
     /// Insert a chunk into the instance at the given position. This effectively
@@ -181,6 +286,8 @@ impl Instance {
                 if !f(pos, chunk) {
                     cell.chunk = None;
                     cell.chunk_removed = true;
+                } else if chunk.has_changed_blocks() {
+                    self.mutated_chunks.insert(pos);
                 }
             }
         }
@@ -188,7 +295,9 @@ impl Instance {
 
     /// Get a [`ChunkEntry`] for the given position.
     pub fn chunk_entry(&mut self, pos: impl Into<ChunkPos>) -> ChunkEntry {
-        ChunkEntry::new(self.info.section_count, self.partition.entry(pos.into()))
+        let pos = pos.into();
+        self.mutated_chunks.insert(pos);
+        ChunkEntry::new(self.info.section_count, self.partition.entry(pos))
     }
 
     /// Get an iterator over all loaded chunks in the instance. The order of the
@@ -202,6 +311,8 @@ impl Instance {
     /// Get an iterator over all loaded chunks in the instance, mutably. The
     /// order of the chunks is undefined.
     pub fn chunks_mut(&mut self) -> impl FusedIterator<Item = (ChunkPos, &mut Chunk<true>)> + '_ {
+        self.mutated_chunks.extend(self.partition.keys());
+
         self.partition
             .iter_mut()
             .flat_map(|(&pos, par)| par.chunk.as_mut().map(|c| (pos, c)))
@@ -213,6 +324,8 @@ impl Instance {
             chunk.optimize();
         }
 
+        self.section_interner.purge();
+
         self.partition.shrink_to_fit();
         self.packet_buf.shrink_to_fit();
     }
@@ -315,6 +428,7 @@ impl Instance {
         PacketWriter::new(
             &mut self.packet_buf,
             self.info.compression_threshold,
+            self.info.compression_level,
             &mut self.scratch,
         )
         .write_packet(pkt);
@@ -345,6 +459,7 @@ impl Instance {
                 PacketWriter::new(
                     &mut cell.packet_buf,
                     self.info.compression_threshold,
+                    self.info.compression_level,
                     &mut self.scratch,
                 )
                 .write_packet(pkt);
@@ -432,7 +547,10 @@ pub(crate) fn update_instances_pre_client(
     mut instances: Query<&mut Instance>,
     mut entities: Query<(Entity, &mut McEntity, Option<&Despawned>)>,
     server: Res<Server>,
+    mut budget: ResMut<TickBudget>,
 ) {
+    budget.start_phase();
+
     for (entity_id, entity, despawned) in &entities {
         let pos = ChunkPos::at(entity.position().x, entity.position().z);
         let old_pos = ChunkPos::at(entity.old_position().x, entity.old_position().z);
@@ -522,31 +640,39 @@ pub(crate) fn update_instances_pre_client(
         }
     }
 
-    let mut scratch_1 = vec![];
-    let mut scratch_2 = vec![];
+    let compression_threshold = server.compression_threshold();
+    let compression_level = server.compression_level();
 
-    for instance in &mut instances {
+    // Instances are independent of each other from here on, so the packets of
+    // every instance are cached on a separate thread.
+    instances.par_for_each_mut(1, |instance| {
         let instance = instance.into_inner();
 
+        instance.entity_update_ranges.clear();
+
         for (&pos, cell) in &mut instance.partition {
             // Cache chunk update packets into the packet buffer of this cell.
             if let Some(chunk) = &mut cell.chunk {
+                // Identical sections of new chunks share their data with
+                // other chunks.
+                chunk.intern_sections(&mut instance.section_interner);
+
                 let writer = PacketWriter::new(
                     &mut cell.packet_buf,
-                    server.compression_threshold(),
-                    &mut scratch_2,
+                    compression_threshold,
+                    compression_level,
+                    &mut instance.update_scratch,
                 );
 
-                chunk.write_update_packets(writer, &mut scratch_1, pos, &instance.info);
+                chunk.write_update_packets(writer, &mut instance.scratch, pos, &instance.info);
 
                 chunk.clear_viewed();
             }
 
             // Cache entity update packets into the packet buffer of this cell.
             for &id in &cell.entities {
-                let (_, mut entity, despawned) = entities
-                    .get_mut(id)
-                    .expect("missing entity in partition cell");
+                let (_, entity, despawned) =
+                    entities.get(id).expect("missing entity in partition cell");
 
                 if despawned.is_some() {
                     continue;
@@ -556,22 +682,38 @@ pub(crate) fn update_instances_pre_client(
 
                 let writer = PacketWriter::new(
                     &mut cell.packet_buf,
-                    server.compression_threshold(),
-                    &mut scratch_2,
+                    compression_threshold,
+                    compression_level,
+                    &mut instance.update_scratch,
                 );
 
-                entity.write_update_packets(writer, &mut scratch_1);
+                entity.write_update_packets(writer, &mut instance.scratch);
 
                 let end = cell.packet_buf.len();
 
-                entity.self_update_range = start..end;
+                instance.entity_update_ranges.push((id, start..end));
+            }
+        }
+    });
+
+    for instance in &instances {
+        for (id, range) in &instance.entity_update_ranges {
+            if let Ok((_, mut entity, _)) = entities.get_mut(*id) {
+                entity.self_update_range = range.clone();
             }
         }
     }
+
+    budget.end_phase(TickPhase::Instances);
 }
 
-pub(crate) fn update_instances_post_client(mut instances: Query<&mut Instance>) {
-    for mut instance in &mut instances {
+pub(crate) fn update_instances_post_client(
+    mut instances: Query<&mut Instance>,
+    mut budget: ResMut<TickBudget>,
+) {
+    budget.start_phase();
+
+    instances.par_for_each_mut(1, |mut instance| {
         instance.partition.retain(|_, cell| {
             cell.packet_buf.clear();
             cell.chunk_removed = false;
@@ -586,7 +728,31 @@ pub(crate) fn update_instances_post_client(mut instances: Query<&mut Instance>)
         });
 
         instance.packet_buf.clear();
+        instance.mutated_chunks.clear();
+        instance.time_of_day_modified = false;
+    });
+
+    budget.end_phase(TickPhase::Instances);
+}
+
+/// Groups the views of the given clients by the instance each client is in.
+///
+/// Chunks contained in at least one of the views of their instance are
+/// considered to be simulated. Gameplay systems such as fire spread only run
+/// in simulated chunks.
+pub(crate) fn client_views_by_instance<'a>(
+    clients: impl IntoIterator<Item = &'a Client>,
+) -> FxHashMap<Entity, Vec<ChunkView>> {
+    let mut views = FxHashMap::<Entity, Vec<ChunkView>>::default();
+
+    for client in clients {
+        views
+            .entry(client.instance())
+            .or_default()
+            .push(client.view());
     }
+
+    views
 }
 
 pub(crate) fn check_instance_invariants(instances: Query<&Instance>, entities: Query<&McEntity>) {
@@ -1,10 +1,15 @@
 use std::borrow::Cow;
 use std::collections::btree_map::Entry;
 use std::collections::{BTreeMap, BTreeSet};
+use std::hash::{Hash, Hasher};
+use std::io::Write;
 use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Weak};
 
 // Using nonstandard mutex to avoid poisoning API.
+use anyhow::Context;
 use parking_lot::Mutex;
+use rustc_hash::{FxHashMap, FxHasher};
 use valence_nbt::{compound, Compound};
 use valence_protocol::block::{BlockEntity, BlockState};
 use valence_protocol::packets::s2c::play::{
@@ -43,14 +48,80 @@ pub struct Chunk<const LOADED: bool = false> {
 
 #[derive(Clone, Default, Debug)]
 struct Section {
+    /// The blocks and biomes of the section. Cloning a chunk shares the data
+    /// of its sections, which is only copied when one of the clones modifies
+    /// it. Identical sections of different chunks in an instance also share
+    /// their data through the [`SectionInterner`] of the instance.
+    data: Arc<SectionData>,
+    /// Contains modifications for the update section packet. (Or the regular
+    /// block update packet if len == 1).
+    section_updates: Vec<VarLong>,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Default, Debug)]
+struct SectionData {
     block_states: PalettedContainer<BlockState, SECTION_BLOCK_COUNT, { SECTION_BLOCK_COUNT / 2 }>,
     biomes: PalettedContainer<BiomeId, SECTION_BIOME_COUNT, { SECTION_BIOME_COUNT / 2 }>,
     /// Number of non-air blocks in this section. This invariant is maintained
     /// even if `track_changes` is false.
     non_air_count: u16,
-    /// Contains modifications for the update section packet. (Or the regular
-    /// block update packet if len == 1).
-    section_updates: Vec<VarLong>,
+}
+
+/// Deduplicates identical section data across the chunks of an instance.
+/// Flat and ocean areas consist mostly of the same few sections, which are
+/// then stored once.
+#[derive(Default, Debug)]
+pub(crate) struct SectionInterner {
+    /// The sections interned so far, by the hash of their data. Sections are
+    /// not kept alive by the interner.
+    sections: FxHashMap<u64, Vec<Weak<SectionData>>>,
+    /// The number of entries at which the entries of dropped sections are
+    /// removed next.
+    purge_at: usize,
+    len: usize,
+}
+
+impl SectionInterner {
+    /// The smallest number of entries at which entries are purged.
+    const MIN_PURGE_AT: usize = 1024;
+
+    /// Replaces the data with equal data interned before, or interns it.
+    fn intern(&mut self, data: &mut Arc<SectionData>) {
+        let mut hasher = FxHasher::default();
+        data.hash(&mut hasher);
+
+        let bucket = self.sections.entry(hasher.finish()).or_default();
+
+        for interned in bucket.iter().filter_map(Weak::upgrade) {
+            if Arc::ptr_eq(&interned, data) {
+                return;
+            }
+
+            if interned == *data {
+                *data = interned;
+                return;
+            }
+        }
+
+        bucket.push(Arc::downgrade(data));
+        self.len += 1;
+
+        if self.len >= self.purge_at {
+            self.purge();
+        }
+    }
+
+    /// Removes the entries of sections which have been dropped.
+    pub(crate) fn purge(&mut self) {
+        self.sections.retain(|_, bucket| {
+            bucket.retain(|weak| weak.strong_count() > 0);
+            !bucket.is_empty()
+        });
+
+        self.sections.shrink_to_fit();
+        self.len = self.sections.values().map(Vec::len).sum();
+        self.purge_at = (self.len * 2).max(Self::MIN_PURGE_AT);
+    }
 }
 
 /// Represents a block with an optional block entity
@@ -251,9 +322,7 @@ impl Chunk<true> {
             .iter()
             .map(|sect| {
                 Section {
-                    block_states: sect.block_states.clone(),
-                    biomes: sect.biomes.clone(),
-                    non_air_count: 0,
+                    data: sect.data.clone(),
                     section_updates: vec![], // Don't clone the section updates.
                 }
             })
@@ -269,6 +338,30 @@ impl Chunk<true> {
         }
     }
 
+    /// Returns `true` if this chunk was created from `other` and neither chunk
+    /// has changed its blocks, biomes or block entities since.
+    pub(crate) fn is_unmodified_copy_of(&self, other: &Chunk) -> bool {
+        self.sections.len() == other.sections.len()
+            && self
+                .sections
+                .iter()
+                .zip(&other.sections)
+                .all(|(a, b)| Arc::ptr_eq(&a.data, &b.data) || a.data == b.data)
+            && self.block_entities == other.block_entities
+    }
+
+    /// Interns the sections of this chunk if it was inserted or its biomes
+    /// changed since the last tick.
+    pub(super) fn intern_sections(&mut self, interner: &mut SectionInterner) {
+        if !self.refresh {
+            return;
+        }
+
+        for sect in &mut self.sections {
+            interner.intern(&mut sect.data);
+        }
+    }
+
     pub(super) fn clear_viewed(&mut self) {
         *self.viewed.get_mut() = false;
     }
@@ -378,65 +471,29 @@ impl Chunk<true> {
         let mut lck = self.cached_init_packets.lock();
 
         if lck.is_empty() {
-            scratch.clear();
-
-            for sect in &self.sections {
-                sect.non_air_count.encode(&mut *scratch).unwrap();
-
-                sect.block_states
-                    .encode_mc_format(
-                        &mut *scratch,
-                        |b| b.to_raw().into(),
-                        4,
-                        8,
-                        bit_width(BlockState::max_raw().into()),
-                    )
-                    .expect("failed to encode block paletted container");
-
-                sect.biomes
-                    .encode_mc_format(
-                        &mut *scratch,
-                        |b| b.0.into(),
-                        0,
-                        3,
-                        bit_width(info.biome_registry_len - 1),
-                    )
-                    .expect("failed to encode biome paletted container");
-            }
-
-            let mut compression_scratch = vec![];
-
+            // The sections and block entities are encoded straight into the
+            // cached packet, and `scratch` is only used for compression.
             let mut writer = PacketWriter::new(
                 &mut lck,
                 info.compression_threshold,
-                &mut compression_scratch,
+                info.compression_level,
+                scratch,
             );
 
-            let block_entities: Vec<_> = self
-                .block_entities
-                .iter()
-                .map(|(idx, block_entity)| {
-                    let x = idx % 16;
-                    let z = idx / 16 % 16;
-                    let y = (idx / 16 / 16) as i16 + info.min_y as i16;
-
-                    ChunkDataBlockEntity {
-                        packed_xz: ((x << 4) | z) as i8,
-                        y,
-                        kind: block_entity.kind,
-                        data: Cow::Borrowed(&block_entity.nbt),
-                    }
-                })
-                .collect();
-
             writer.write_packet(&ChunkDataAndUpdateLightEncode {
                 chunk_x: pos.x,
                 chunk_z: pos.z,
                 heightmaps: &compound! {
                     // TODO: MOTION_BLOCKING heightmap
                 },
-                blocks_and_biomes: scratch,
-                block_entities: &block_entities,
+                blocks_and_biomes: SectionsEncode {
+                    sections: &self.sections,
+                    biome_bits: bit_width(info.biome_registry_len - 1),
+                },
+                block_entities: BlockEntitiesEncode {
+                    block_entities: &self.block_entities,
+                    min_y: info.min_y,
+                },
                 trust_edges: true,
                 sky_light_mask: &info.filler_sky_light_mask,
                 block_light_mask: &[],
@@ -450,6 +507,50 @@ impl Chunk<true> {
         writer.write_packet_bytes(&lck);
     }
 
+    /// Returns if any blocks were changed this tick, or if the whole chunk is
+    /// sent to clients again.
+    pub(crate) fn has_changed_blocks(&self) -> bool {
+        self.refresh
+            || self
+                .sections
+                .iter()
+                .any(|sect| !sect.section_updates.is_empty())
+    }
+
+    /// Calls `f` with the offsets of the blocks changed this tick whose state
+    /// matches `filter`. Every matching block is visited if the whole chunk is
+    /// sent to clients again, like after the chunk was inserted.
+    pub(crate) fn for_each_changed_block(
+        &self,
+        mut filter: impl FnMut(BlockState) -> bool,
+        mut f: impl FnMut(usize, usize, usize),
+    ) {
+        for (sect_y, sect) in self.sections.iter().enumerate() {
+            if self.refresh {
+                if !sect.data.block_states.palette_contains(&mut filter) {
+                    continue;
+                }
+
+                for idx in 0..SECTION_BLOCK_COUNT {
+                    if filter(sect.data.block_states.get(idx)) {
+                        f(idx % 16, sect_y * 16 + idx / 256, idx / 16 % 16);
+                    }
+                }
+            } else {
+                for &VarLong(packed) in &sect.section_updates {
+                    let x = ((packed >> 8) & 0b1111) as usize;
+                    let y = sect_y * 16 + (packed & 0b1111) as usize;
+                    let z = ((packed >> 4) & 0b1111) as usize;
+
+                    // A block may have changed several times this tick.
+                    if filter(sect.data.block_states.get(x + z * 16 + y % 16 * 16 * 16)) {
+                        f(x, y, z);
+                    }
+                }
+            }
+        }
+    }
+
     pub(super) fn update_post_client(&mut self) {
         self.refresh = false;
 
@@ -460,6 +561,91 @@ impl Chunk<true> {
     }
 }
 
+/// Encodes the blocks and biomes of the sections of a chunk like the
+/// length-prefixed byte array of the chunk data packet, without collecting
+/// them into a buffer first.
+#[derive(Clone, Copy, Debug)]
+struct SectionsEncode<'a> {
+    sections: &'a [Section],
+    biome_bits: usize,
+}
+
+impl SectionsEncode<'_> {
+    const BLOCK_STATE_BITS: usize = bit_width(BlockState::max_raw() as usize);
+
+    fn section_len(&self, sect: &Section) -> usize {
+        let non_air_count_len = 2;
+
+        let block_states_len = sect.data.block_states.encoded_mc_len(
+            |b| b.to_raw().into(),
+            4,
+            8,
+            Self::BLOCK_STATE_BITS,
+        );
+
+        let biomes_len = sect
+            .data
+            .biomes
+            .encoded_mc_len(|b| b.0.into(), 0, 3, self.biome_bits);
+
+        non_air_count_len + block_states_len + biomes_len
+    }
+}
+
+impl Encode for SectionsEncode<'_> {
+    fn encode(&self, mut w: impl Write) -> anyhow::Result<()> {
+        let len: usize = self.sections.iter().map(|s| self.section_len(s)).sum();
+        VarInt(len as i32).encode(&mut w)?;
+
+        for sect in self.sections {
+            sect.data.non_air_count.encode(&mut w)?;
+
+            sect.data
+                .block_states
+                .encode_mc_format(&mut w, |b| b.to_raw().into(), 4, 8, Self::BLOCK_STATE_BITS)
+                .context("failed to encode block paletted container")?;
+
+            sect.data
+                .biomes
+                .encode_mc_format(&mut w, |b| b.0.into(), 0, 3, self.biome_bits)
+                .context("failed to encode biome paletted container")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Encodes the block entities of a chunk like the array of
+/// [`ChunkDataBlockEntity`] in the chunk data packet, without collecting them
+/// into a buffer first.
+#[derive(Clone, Copy, Debug)]
+struct BlockEntitiesEncode<'a> {
+    block_entities: &'a BTreeMap<u32, BlockEntity>,
+    min_y: i32,
+}
+
+impl Encode for BlockEntitiesEncode<'_> {
+    fn encode(&self, mut w: impl Write) -> anyhow::Result<()> {
+        VarInt(self.block_entities.len() as i32).encode(&mut w)?;
+
+        for (idx, block_entity) in self.block_entities {
+            let x = idx % 16;
+            let z = idx / 16 % 16;
+            let y = (idx / 16 / 16) as i16 + self.min_y as i16;
+
+            ChunkDataBlockEntity {
+                packed_xz: ((x << 4) | z) as i8,
+                y,
+                kind: block_entity.kind,
+                data: Cow::Borrowed(&block_entity.nbt),
+            }
+            .encode(&mut w)?;
+        }
+
+        Ok(())
+    }
+}
+
 impl<const LOADED: bool> Chunk<LOADED> {
     /// Returns the number of sections in this chunk. To get the height of the
     /// chunk in meters, multiply the result by 16.
@@ -484,6 +670,7 @@ impl<const LOADED: bool> Chunk<LOADED> {
         );
 
         self.sections[y / 16]
+            .data
             .block_states
             .get(x + z * 16 + y % 16 * 16 * 16)
     }
@@ -516,13 +703,16 @@ impl<const LOADED: bool> Chunk<LOADED> {
         let sect = &mut self.sections[sect_y];
         let idx = x + z * 16 + y % 16 * 16 * 16;
 
-        let old_block = sect.block_states.set(idx, block);
+        let old_block = sect.data.block_states.get(idx);
 
         if block != old_block {
+            let data = Arc::make_mut(&mut sect.data);
+            data.block_states.set(idx, block);
+
             // Update non-air count.
             match (block.is_air(), old_block.is_air()) {
-                (true, false) => sect.non_air_count -= 1,
-                (false, true) => sect.non_air_count += 1,
+                (true, false) => data.non_air_count -= 1,
+                (false, true) => data.non_air_count += 1,
                 _ => {}
             }
 
@@ -558,7 +748,7 @@ impl<const LOADED: bool> Chunk<LOADED> {
         };
 
         if LOADED && !self.refresh {
-            if let PalettedContainer::Single(single) = &sect.block_states {
+            if let PalettedContainer::Single(single) = &sect.data.block_states {
                 if block != *single {
                     self.cached_init_packets.get_mut().clear();
 
@@ -581,7 +771,7 @@ impl<const LOADED: bool> Chunk<LOADED> {
                 for z in 0..16 {
                     for x in 0..16 {
                         let idx = x + z * 16 + sect_y * (16 * 16);
-                        if block != sect.block_states.get(idx) {
+                        if block != sect.data.block_states.get(idx) {
                             self.cached_init_packets.get_mut().clear();
                             let packed = block_bits | (x << 8 | z << 4 | sect_y) as i64;
                             sect.section_updates.push(VarLong(packed));
@@ -591,13 +781,22 @@ impl<const LOADED: bool> Chunk<LOADED> {
             }
         }
 
+        // Filling a section with the block it is already filled with does not
+        // copy its shared data.
+        if matches!(&sect.data.block_states, PalettedContainer::Single(single) if *single == block)
+        {
+            return;
+        }
+
+        let data = Arc::make_mut(&mut sect.data);
+
         if !block.is_air() {
-            sect.non_air_count = SECTION_BLOCK_COUNT as u16;
+            data.non_air_count = SECTION_BLOCK_COUNT as u16;
         } else {
-            sect.non_air_count = 0;
+            data.non_air_count = 0;
         }
 
-        sect.block_states.fill(block);
+        data.block_states.fill(block);
     }
 
     /// Gets a reference to the block entity at the provided offsets in the
@@ -701,13 +900,16 @@ impl<const LOADED: bool> Chunk<LOADED> {
             let sect = &mut self.sections[sect_y];
             let idx = x + z * 16 + y % 16 * 16 * 16;
 
-            let old_state = sect.block_states.set(idx, state);
+            let old_state = sect.data.block_states.get(idx);
 
             if state != old_state {
+                let data = Arc::make_mut(&mut sect.data);
+                data.block_states.set(idx, state);
+
                 // Update non-air count.
                 match (state.is_air(), old_state.is_air()) {
-                    (true, false) => sect.non_air_count -= 1,
-                    (false, true) => sect.non_air_count += 1,
+                    (true, false) => data.non_air_count -= 1,
+                    (false, true) => data.non_air_count += 1,
                     _ => {}
                 }
 
@@ -757,6 +959,7 @@ impl<const LOADED: bool> Chunk<LOADED> {
         );
 
         let state = self.sections[y / 16]
+            .data
             .block_states
             .get(x + z * 16 + y % 16 * 16 * 16);
 
@@ -788,6 +991,7 @@ impl<const LOADED: bool> Chunk<LOADED> {
         );
 
         let state = self.sections[y / 16]
+            .data
             .block_states
             .get(x + z * 16 + y % 16 * 16 * 16);
 
@@ -818,7 +1022,10 @@ impl<const LOADED: bool> Chunk<LOADED> {
             "chunk biome offsets of ({x}, {y}, {z}) are out of bounds"
         );
 
-        self.sections[y / 4].biomes.get(x + z * 4 + y % 4 * 4 * 4)
+        self.sections[y / 4]
+            .data
+            .biomes
+            .get(x + z * 4 + y % 4 * 4 * 4)
     }
 
     /// Sets the biome at the provided offsets in the chunk. The previous
@@ -838,13 +1045,17 @@ impl<const LOADED: bool> Chunk<LOADED> {
             "chunk biome offsets of ({x}, {y}, {z}) are out of bounds"
         );
 
-        let old_biome = self.sections[y / 4]
-            .biomes
-            .set(x + z * 4 + y % 4 * 4 * 4, biome);
+        let sect = &mut self.sections[y / 4];
+        let idx = x + z * 4 + y % 4 * 4 * 4;
+        let old_biome = sect.data.biomes.get(idx);
 
-        if LOADED && biome != old_biome {
-            self.cached_init_packets.get_mut().clear();
-            self.refresh = true;
+        if biome != old_biome {
+            Arc::make_mut(&mut sect.data).biomes.set(idx, biome);
+
+            if LOADED {
+                self.cached_init_packets.get_mut().clear();
+                self.refresh = true;
+            }
         }
 
         old_biome
@@ -871,7 +1082,7 @@ impl<const LOADED: bool> Chunk<LOADED> {
             )
         };
 
-        sect.biomes.fill(biome);
+        Arc::make_mut(&mut sect.data).biomes.fill(biome);
 
         // TODO: this is set unconditionally, but it doesn't have to be.
         self.cached_init_packets.get_mut().clear();
@@ -890,8 +1101,12 @@ impl<const LOADED: bool> Chunk<LOADED> {
 
         for sect in &mut self.sections {
             sect.section_updates.shrink_to_fit();
-            sect.block_states.optimize();
-            sect.biomes.optimize();
+
+            // Optimizing shared data would copy it instead.
+            if let Some(data) = Arc::get_mut(&mut sect.data) {
+                data.block_states.optimize();
+                data.biomes.optimize();
+            }
         }
     }
 }
@@ -911,9 +1126,9 @@ mod tests {
         for sect in &chunk.sections {
             assert_eq!(
                 (0..SECTION_BLOCK_COUNT)
-                    .filter(|&i| !sect.block_states.get(i).is_air())
+                    .filter(|&i| !sect.data.block_states.get(i).is_air())
                     .count(),
-                sect.non_air_count as usize,
+                sect.data.non_air_count as usize,
                 "number of non-air blocks does not match counter"
             );
 
@@ -944,6 +1159,51 @@ mod tests {
         check(&chunk, 6);
     }
 
+    #[test]
+    fn clones_share_sections_until_modified() {
+        let mut chunk = Chunk::new(2);
+        chunk.set_block_state(0, 0, 0, BlockState::STONE);
+
+        let mut copy = chunk.clone();
+        assert!(Arc::ptr_eq(&chunk.sections[0].data, &copy.sections[0].data));
+
+        copy.set_block_state(0, 0, 0, BlockState::DIRT);
+        assert!(!Arc::ptr_eq(
+            &chunk.sections[0].data,
+            &copy.sections[0].data
+        ));
+        assert!(Arc::ptr_eq(&chunk.sections[1].data, &copy.sections[1].data));
+
+        assert_eq!(chunk.block_state(0, 0, 0), BlockState::STONE);
+        assert_eq!(copy.block_state(0, 0, 0), BlockState::DIRT);
+    }
+
+    #[test]
+    fn identical_sections_are_interned() {
+        let mut interner = SectionInterner::default();
+
+        let mut a = Chunk::new(2);
+        a.set_block_state(0, 0, 0, BlockState::STONE);
+        let mut a = a.into_loaded();
+
+        let mut b = Chunk::new(2);
+        b.set_block_state(0, 0, 0, BlockState::STONE);
+        let mut b = b.into_loaded();
+
+        a.intern_sections(&mut interner);
+        b.intern_sections(&mut interner);
+
+        assert!(Arc::ptr_eq(&a.sections[0].data, &b.sections[0].data));
+        assert!(Arc::ptr_eq(&a.sections[1].data, &b.sections[1].data));
+
+        b.set_block_state(0, 0, 0, BlockState::DIRT);
+        assert_eq!(a.block_state(0, 0, 0), BlockState::STONE);
+
+        drop(a);
+        interner.purge();
+        assert_eq!(interner.len, 1);
+    }
+
     #[test]
     fn block_entity_changes() {
         let mut chunk = Chunk::new(5).into_loaded();
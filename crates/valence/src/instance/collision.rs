@@ -0,0 +1,427 @@
+use glam::DVec3;
+use valence_protocol::block::{BlockFace, BlockState};
+use valence_protocol::BlockPos;
+
+use crate::instance::Instance;
+use crate::math::Aabb;
+
+/// The first block hit by a moving bounding box. Returned by
+/// [`Instance::collide_aabb`].
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct BlockCollision {
+    /// The fraction of the velocity the bounding box can move before touching
+    /// the block, in `0.0..=1.0`.
+    pub t: f64,
+    /// The unit vector pointing away from the face of the block which was
+    /// hit.
+    pub normal: DVec3,
+    pub block_pos: BlockPos,
+    pub block: BlockState,
+}
+
+/// The first block hit by a ray. Returned by [`Instance::raycast`].
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct RaycastHit {
+    pub block_pos: BlockPos,
+    pub block: BlockState,
+    /// The face of the block which was hit.
+    pub face: BlockFace,
+    /// The point where the ray hit the block.
+    pub position: DVec3,
+    /// The distance from the origin of the ray to the hit.
+    pub distance: f64,
+}
+
+impl Instance {
+    /// Returns the collision shape of the block at the given position as
+    /// boxes in world space. The iterator is empty if the block has no
+    /// collision or is not in a loaded chunk.
+    pub fn block_collision_shapes(
+        &self,
+        pos: impl Into<BlockPos>,
+    ) -> impl Iterator<Item = Aabb> + Clone {
+        let pos = pos.into();
+
+        self.block(pos)
+            .map(|block| {
+                let state = block.state();
+                let origin = block_origin(pos, state);
+                state
+                    .collision_shapes()
+                    .map(move |shape| shape_to_aabb(origin, shape))
+            })
+            .into_iter()
+            .flatten()
+    }
+
+    /// Returns the outline shape of the block at the given position as boxes
+    /// in world space. This is the shape players target with their cursor.
+    /// The iterator is empty if the block is not in a loaded chunk.
+    pub fn block_outline_shapes(
+        &self,
+        pos: impl Into<BlockPos>,
+    ) -> impl Iterator<Item = Aabb> + Clone {
+        let pos = pos.into();
+
+        self.block(pos)
+            .map(|block| {
+                let state = block.state();
+                let origin = block_origin(pos, state);
+                state
+                    .outline_shapes()
+                    .map(move |shape| shape_to_aabb(origin, shape))
+            })
+            .into_iter()
+            .flatten()
+    }
+
+    /// Sweeps `aabb` along `velocity` and returns the first block collision
+    /// shape it hits, if any.
+    ///
+    /// The real collision shapes of block states are used, so the bounding
+    /// box can move over carpets, onto the lower half of slabs, and is blocked
+    /// by the upper half of fences. Blocks in unloaded chunks are treated as
+    /// air. Collision shapes which already intersect the bounding box before
+    /// it moves are ignored.
+    pub fn collide_aabb(&self, aabb: Aabb, velocity: impl Into<DVec3>) -> Option<BlockCollision> {
+        let velocity = velocity.into();
+
+        let swept = Aabb {
+            min: aabb.min.min(aabb.min + velocity),
+            max: aabb.max.max(aabb.max + velocity),
+        };
+
+        let min = BlockPos::at(swept.min);
+        let max = BlockPos::at(swept.max);
+
+        let mut res: Option<BlockCollision> = None;
+
+        for x in min.x..=max.x {
+            // Collision shapes such as fences extend into the block above.
+            for y in min.y - 1..=max.y {
+                for z in min.z..=max.z {
+                    let block_pos = BlockPos::new(x, y, z);
+
+                    let Some(block) = self.block(block_pos) else {
+                        continue
+                    };
+
+                    let state = block.state();
+                    let origin = block_origin(block_pos, state);
+
+                    for shape in state.collision_shapes() {
+                        let shape = shape_to_aabb(origin, shape);
+
+                        let Some((t, normal)) = sweep(aabb, velocity, shape) else {
+                            continue
+                        };
+
+                        if res.map_or(true, |res| t < res.t) {
+                            res = Some(BlockCollision {
+                                t,
+                                normal,
+                                block_pos,
+                                block: state,
+                            });
+                        }
+                    }
+                }
+            }
+        }
+
+        res
+    }
+
+    /// Casts a ray from `origin` in `direction` and returns the first block
+    /// it hits within `max_distance`, if any.
+    ///
+    /// Like the cursor of players, the ray hits the outline shapes of blocks,
+    /// so it passes through fluids and hits blocks without collision such as
+    /// flowers. Blocks in unloaded chunks are treated as air.
+    pub fn raycast(
+        &self,
+        origin: impl Into<DVec3>,
+        direction: impl Into<DVec3>,
+        max_distance: f64,
+    ) -> Option<RaycastHit> {
+        let origin = origin.into();
+        let direction = direction.into().normalize_or_zero();
+
+        if direction == DVec3::ZERO {
+            return None;
+        }
+
+        let mut block_pos = BlockPos::at(origin);
+        let mut step = [0; 3];
+        // The distance along the ray to the next block boundary on each axis.
+        let mut next = DVec3::splat(f64::INFINITY);
+        // The distance along the ray between block boundaries on each axis.
+        let mut delta = DVec3::splat(f64::INFINITY);
+
+        for axis in 0..3 {
+            let d = direction[axis];
+
+            if d == 0.0 {
+                continue;
+            }
+
+            let start = origin[axis].floor();
+            let boundary = if d > 0.0 { start + 1.0 } else { start };
+
+            step[axis] = d.signum() as i32;
+            next[axis] = (boundary - origin[axis]) / d;
+            delta[axis] = 1.0 / d.abs();
+        }
+
+        loop {
+            let hit = self
+                .block_outline_shapes(block_pos)
+                .filter_map(|shape| shape.ray_intersection(origin, direction))
+                .min_by(|(a, _), (b, _)| a.total_cmp(b));
+
+            if let Some((distance, normal)) = hit {
+                if distance > max_distance {
+                    return None;
+                }
+
+                let block = self.block(block_pos)?.state();
+
+                return Some(RaycastHit {
+                    block_pos,
+                    block,
+                    face: face_of(normal, direction),
+                    position: origin + direction * distance,
+                    distance,
+                });
+            }
+
+            let axis = if next.x < next.y && next.x < next.z {
+                0
+            } else if next.y < next.z {
+                1
+            } else {
+                2
+            };
+
+            if next[axis] > max_distance {
+                return None;
+            }
+
+            next[axis] += delta[axis];
+
+            match axis {
+                0 => block_pos.x += step[0],
+                1 => block_pos.y += step[1],
+                _ => block_pos.z += step[2],
+            }
+        }
+    }
+}
+
+/// Returns the face of a block with the given normal. A ray starting inside
+/// the block has no normal and hits the face it looks at from the inside.
+fn face_of(normal: DVec3, direction: DVec3) -> BlockFace {
+    let normal = if normal == DVec3::ZERO {
+        -direction
+    } else {
+        normal
+    };
+
+    let abs = normal.abs();
+
+    if abs.x >= abs.y && abs.x >= abs.z {
+        if normal.x > 0.0 {
+            BlockFace::East
+        } else {
+            BlockFace::West
+        }
+    } else if abs.y >= abs.z {
+        if normal.y > 0.0 {
+            BlockFace::Top
+        } else {
+            BlockFace::Bottom
+        }
+    } else if normal.z > 0.0 {
+        BlockFace::South
+    } else {
+        BlockFace::North
+    }
+}
+
+/// Returns the point in world space the shapes of a block are relative to,
+/// which is the corner of the block moved by its model offset.
+fn block_origin(pos: BlockPos, state: BlockState) -> DVec3 {
+    DVec3::new(pos.x as f64, pos.y as f64, pos.z as f64) + model_offset(pos, state)
+}
+
+/// Returns the random offset of blocks like flowers and grass at the given
+/// position, which is the same for the client.
+fn model_offset(pos: BlockPos, state: BlockState) -> DVec3 {
+    let [max_horizontal, max_vertical] = state.to_kind().max_model_offset();
+
+    if max_horizontal == 0.0 && max_vertical == 0.0 {
+        return DVec3::ZERO;
+    }
+
+    let hash = position_hash(pos.x, 0, pos.z);
+    // The client computes the fractions as `f32`.
+    let fraction = |shift: i64| ((hash >> shift & 15) as f32 / 15.0) as f64;
+
+    DVec3::new(
+        ((fraction(0) - 0.5) * 0.5).clamp(-max_horizontal, max_horizontal),
+        (fraction(4) - 1.0) * max_vertical,
+        ((fraction(8) - 0.5) * 0.5).clamp(-max_horizontal, max_horizontal),
+    )
+}
+
+/// The hash the client uses to derive random values from a position.
+fn position_hash(x: i32, y: i32, z: i32) -> i64 {
+    let hash = x.wrapping_mul(3129871) as i64 ^ (z as i64).wrapping_mul(116129781) ^ y as i64;
+    hash.wrapping_mul(hash)
+        .wrapping_mul(42317861)
+        .wrapping_add(hash.wrapping_mul(11))
+        >> 16
+}
+
+fn shape_to_aabb(origin: DVec3, [min_x, min_y, min_z, max_x, max_y, max_z]: [f64; 6]) -> Aabb {
+    Aabb {
+        min: origin + DVec3::new(min_x, min_y, min_z),
+        max: origin + DVec3::new(max_x, max_y, max_z),
+    }
+}
+
+/// Returns the time of impact and the normal of the hit face when the moving
+/// box `a` hits the stationary box `b`.
+fn sweep(a: Aabb, velocity: DVec3, b: Aabb) -> Option<(f64, DVec3)> {
+    let mut entry = f64::NEG_INFINITY;
+    let mut exit = f64::INFINITY;
+    let mut normal = DVec3::ZERO;
+
+    for axis in 0..3 {
+        let v = velocity[axis];
+
+        if v == 0.0 {
+            if a.max[axis] <= b.min[axis] || a.min[axis] >= b.max[axis] {
+                return None;
+            }
+
+            continue;
+        }
+
+        let (axis_entry, axis_exit) = if v > 0.0 {
+            (
+                (b.min[axis] - a.max[axis]) / v,
+                (b.max[axis] - a.min[axis]) / v,
+            )
+        } else {
+            (
+                (b.max[axis] - a.min[axis]) / v,
+                (b.min[axis] - a.max[axis]) / v,
+            )
+        };
+
+        if axis_entry > entry {
+            entry = axis_entry;
+            normal = DVec3::ZERO;
+            normal[axis] = -v.signum();
+        }
+
+        exit = exit.min(axis_exit);
+    }
+
+    if entry > exit || !(0.0..=1.0).contains(&entry) {
+        return None;
+    }
+
+    Some((entry, normal))
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy_app::App;
+
+    use super::*;
+    use crate::client::Client;
+    use crate::instance::Chunk;
+    use crate::unit_test::util::scenario_single_client;
+
+    #[test]
+    fn falling_box_lands_on_slab() {
+        let mut app = App::new();
+        let (client_ent, _) = scenario_single_client(&mut app);
+        let instance_ent = app.world.get::<Client>(client_ent).unwrap().instance();
+
+        let mut instance = app.world.get_mut::<Instance>(instance_ent).unwrap();
+        instance.insert_chunk([0, 0], Chunk::default());
+        instance.set_block([0, 0, 0], BlockState::STONE_SLAB);
+
+        let aabb = Aabb::new([0.2, 2.0, 0.2], [0.8, 3.0, 0.8]);
+
+        let collision = instance.collide_aabb(aabb, [0.0, -4.0, 0.0]).unwrap();
+        assert_eq!(collision.block_pos, BlockPos::new(0, 0, 0));
+        assert_eq!(collision.normal, DVec3::Y);
+        assert_eq!(aabb.min.y - 4.0 * collision.t, 0.5);
+
+        // Moving sideways above the slab doesn't hit it.
+        assert!(instance.collide_aabb(aabb, [4.0, 0.0, 0.0]).is_none());
+    }
+
+    #[test]
+    fn rays_hit_block_outlines() {
+        let mut app = App::new();
+        let (client_ent, _) = scenario_single_client(&mut app);
+        let instance_ent = app.world.get::<Client>(client_ent).unwrap().instance();
+
+        let mut instance = app.world.get_mut::<Instance>(instance_ent).unwrap();
+        instance.insert_chunk([0, 0], Chunk::default());
+        instance.set_block([5, 1, 0], BlockState::STONE_SLAB);
+        instance.set_block([8, 1, 0], BlockState::STONE);
+
+        let hit = instance
+            .raycast([0.5, 1.25, 0.5], [1.0, 0.0, 0.0], 10.0)
+            .unwrap();
+        assert_eq!(hit.block_pos, BlockPos::new(5, 1, 0));
+        assert_eq!(hit.face, BlockFace::West);
+        assert_eq!(hit.distance, 4.5);
+
+        // Above the slab.
+        let hit = instance
+            .raycast([0.5, 1.75, 0.5], [1.0, 0.0, 0.0], 10.0)
+            .unwrap();
+        assert_eq!(hit.block_pos, BlockPos::new(8, 1, 0));
+
+        assert!(instance
+            .raycast([0.5, 1.75, 0.5], [1.0, 0.0, 0.0], 5.0)
+            .is_none());
+        assert!(instance
+            .raycast([0.5, 1.75, 0.5], [-1.0, 0.0, 0.0], 10.0)
+            .is_none());
+    }
+
+    #[test]
+    fn block_shapes_are_in_world_space() {
+        let mut app = App::new();
+        let (client_ent, _) = scenario_single_client(&mut app);
+        let instance_ent = app.world.get::<Client>(client_ent).unwrap().instance();
+
+        let mut instance = app.world.get_mut::<Instance>(instance_ent).unwrap();
+        instance.insert_chunk([0, 0], Chunk::default());
+        instance.set_block([1, 2, 3], BlockState::WHITE_CARPET);
+
+        let shapes: Vec<_> = instance.block_collision_shapes([1, 2, 3]).collect();
+        assert_eq!(
+            shapes,
+            [Aabb::new([1.0, 2.0, 3.0], [2.0, 2.0 + 1.0 / 16.0, 4.0])]
+        );
+
+        assert_eq!(instance.block_collision_shapes([1, 3, 3]).count(), 0);
+        // Unloaded chunk.
+        assert_eq!(instance.block_outline_shapes([100, 2, 3]).count(), 0);
+    }
+
+    #[test]
+    fn model_offsets_are_largest_at_origin() {
+        // The extractor relies on this to find the largest model offsets.
+        assert_eq!(position_hash(0, 0, 0), 0);
+    }
+}
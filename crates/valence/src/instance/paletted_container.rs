@@ -7,14 +7,18 @@ use valence_protocol::{Encode, VarInt};
 use crate::math::bit_width;
 
 /// `HALF_LEN` must be equal to `ceil(LEN / 2)`.
-#[derive(Clone, Debug)]
+///
+/// Containers compare equal when they have the same representation. Equal
+/// elements stored with differently ordered palettes are not considered
+/// equal.
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
 pub enum PalettedContainer<T, const LEN: usize, const HALF_LEN: usize> {
     Single(T),
     Indirect(Box<Indirect<T, LEN, HALF_LEN>>),
     Direct(Box<[T; LEN]>),
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
 pub struct Indirect<T, const LEN: usize, const HALF_LEN: usize> {
     /// Each element is a unique instance of `T`. The length of the palette is
     /// always ≥2.
@@ -37,6 +41,17 @@ impl<T: Copy + Eq + Default, const LEN: usize, const HALF_LEN: usize>
         *self = Self::Single(val)
     }
 
+    /// Returns `true` if the palette has an element matching the predicate.
+    /// The palette can keep elements which are no longer in the container, so
+    /// a match doesn't mean the element is still stored.
+    pub fn palette_contains(&self, mut f: impl FnMut(T) -> bool) -> bool {
+        match self {
+            Self::Single(elem) => f(*elem),
+            Self::Indirect(ind) => ind.palette.iter().any(|&elem| f(elem)),
+            Self::Direct(elems) => elems.iter().any(|&elem| f(elem)),
+        }
+    }
+
     pub fn get(&self, idx: usize) -> T {
         debug_assert!(idx < LEN);
 
@@ -223,6 +238,51 @@ impl<T: Copy + Eq + Default, const LEN: usize, const HALF_LEN: usize>
 
         Ok(())
     }
+
+    /// Returns the number of bytes [`Self::encode_mc_format`] writes with the
+    /// same arguments, without encoding anything.
+    pub fn encoded_mc_len<F>(
+        &self,
+        mut to_bits: F,
+        min_indirect_bits: usize,
+        max_indirect_bits: usize,
+        direct_bits: usize,
+    ) -> usize
+    where
+        F: FnMut(T) -> u64,
+    {
+        let direct_len = || {
+            let longs = compact_u64s_len(LEN, direct_bits);
+            1 + VarInt(longs as i32).written_size() + longs * 8
+        };
+
+        match self {
+            Self::Single(val) => {
+                1 + VarInt(to_bits(*val) as i32).written_size() + VarInt(0).written_size()
+            }
+            Self::Indirect(ind) => {
+                let bits_per_entry = min_indirect_bits.max(bit_width(ind.palette.len() - 1));
+
+                if bits_per_entry > max_indirect_bits {
+                    direct_len()
+                } else {
+                    let palette_len: usize = ind
+                        .palette
+                        .iter()
+                        .map(|val| VarInt(to_bits(*val) as i32).written_size())
+                        .sum();
+
+                    let longs = compact_u64s_len(LEN, bits_per_entry);
+
+                    1 + VarInt(ind.palette.len() as i32).written_size()
+                        + palette_len
+                        + VarInt(longs as i32).written_size()
+                        + longs * 8
+                }
+            }
+            Self::Direct(_) => direct_len(),
+        }
+    }
 }
 
 impl<T: Copy + Eq + Default, const LEN: usize, const HALF_LEN: usize> Default
@@ -300,6 +360,27 @@ mod tests {
         (0..LEN).all(|i| p.get(i) == s[i])
     }
 
+    #[test]
+    fn encoded_len_matches_encoding() {
+        let mut p = PalettedContainer::<u32, 64, 32>::new();
+
+        let check_len = |p: &PalettedContainer<u32, 64, 32>| {
+            let mut buf = vec![];
+            p.encode_mc_format(&mut buf, u64::from, 4, 8, 12).unwrap();
+            assert_eq!(p.encoded_mc_len(u64::from, 4, 8, 12), buf.len());
+        };
+
+        check_len(&p);
+
+        p.set(0, 1000);
+        check_len(&p);
+
+        for i in 0..64 {
+            p.set(i, i as u32 * 50);
+        }
+        check_len(&p);
+    }
+
     #[test]
     fn random_assignments() {
         const LEN: usize = 100;
@@ -1,15 +1,29 @@
+use std::fmt;
 use std::iter::FusedIterator;
 
 use bevy_ecs::prelude::*;
+use rustc_hash::FxHashMap;
 use tracing::{debug, warn};
+use valence_nbt::{List, Value};
 use valence_protocol::packets::s2c::play::{
-    CloseContainerS2c, OpenScreen, SetContainerContentEncode, SetContainerSlotEncode,
+    CloseContainerS2c, OpenHorseScreen, OpenScreen, SetContainerContentEncode,
+    SetContainerSlotEncode,
 };
-use valence_protocol::types::{GameMode, WindowType};
-use valence_protocol::{ItemStack, Text, VarInt};
+use valence_protocol::types::{ClickContainerMode, GameMode, Hand, WindowType};
+use valence_protocol::{ItemKind, ItemStack, Text, VarInt};
 
+use crate::bundle::{insert_into_bundle, remove_from_bundle};
 use crate::client::event::{ClickContainer, CloseContainer, SetCreativeModeSlot, SetHeldItem};
 use crate::client::Client;
+use crate::crafting::{can_stack, has_crafting_grid, take_crafting_result, RecipeRegistry};
+use crate::creative::{CreativeItemFiltered, CreativeRules};
+use crate::entity::equipment::{
+    stack_from_nbt, stack_to_nbt, EquipmentChangeEvent, EquipmentSnapshot, HeldItem,
+};
+use crate::entity::McEntity;
+use crate::menu::OpenMenu;
+use crate::trading::{take_trade_result, MerchantOffers, TradeEvent, MERCHANT_RESULT_SLOT};
+use crate::workstation::{take_anvil_result, Anvil, AnvilUsed, ANVIL_RESULT_SLOT};
 
 #[derive(Debug, Clone, Component)]
 pub struct Inventory {
@@ -104,6 +118,71 @@ impl Inventory {
         std::mem::replace(&mut self.title, title.into())
     }
 
+    /// Converts the items of the inventory to a list like the `Inventory` list
+    /// of vanilla player data, or the `Items` list of containers. Every item
+    /// has a `Slot` byte next to its `id`, `Count` and `tag`, and empty slots
+    /// are left out.
+    ///
+    /// Player inventories use the slot numbers of vanilla player data, where
+    /// the hotbar comes first and armor and the off hand have slots of their
+    /// own. The crafting grid is not saved.
+    pub fn to_nbt(&self) -> List {
+        let items = (0..self.slot_count())
+            .filter_map(|idx| {
+                let stack = self.slot(idx)?;
+                let slot = match self.kind {
+                    InventoryKind::Player => player_slot_to_nbt(idx)?,
+                    _ => i8::try_from(idx).ok()?,
+                };
+
+                let mut item = stack_to_nbt(Some(stack));
+                item.insert("Slot", slot);
+                Some(item)
+            })
+            .collect();
+
+        List::Compound(items)
+    }
+
+    /// Creates an inventory of the given kind with the items of a list written
+    /// by [`Self::to_nbt`] or by vanilla. Items without a slot of the
+    /// inventory and unknown items are ignored.
+    ///
+    /// ```
+    /// use valence::prelude::*;
+    ///
+    /// let mut inventory = Inventory::new(InventoryKind::Player);
+    /// inventory.replace_slot(36, ItemStack::new(ItemKind::Diamond, 3, None));
+    ///
+    /// let nbt = inventory.to_nbt();
+    /// let read = Inventory::from_nbt(InventoryKind::Player, &nbt);
+    /// assert_eq!(read.slot(36), inventory.slot(36));
+    /// ```
+    pub fn from_nbt(kind: InventoryKind, items: &List) -> Self {
+        let mut inventory = Self::new(kind);
+
+        let List::Compound(items) = items else {
+            return inventory;
+        };
+
+        for item in items {
+            let Some(Value::Byte(slot)) = item.get("Slot") else {
+                continue;
+            };
+
+            let idx = match kind {
+                InventoryKind::Player => player_slot_from_nbt(*slot),
+                _ => u16::try_from(*slot).ok(),
+            };
+
+            if let Some(idx) = idx.filter(|&idx| idx < inventory.slot_count()) {
+                inventory.replace_slot(idx, stack_from_nbt(item));
+            }
+        }
+
+        inventory
+    }
+
     fn slot_slice(&self) -> &[Option<ItemStack>] {
         self.slots.as_ref()
     }
@@ -119,7 +198,12 @@ pub(crate) fn update_player_inventories(
         }
 
         if inventory.modified != 0 {
-            if inventory.modified == u64::MAX {
+            // The slots that were NOT modified by this client, and they need to be sent
+            let modified_filtered = inventory.modified & !client.inventory_slots_modified;
+
+            if inventory.modified == u64::MAX
+                || prefers_full_sync(modified_filtered.count_ones(), inventory.slot_count())
+            {
                 // Update the whole inventory.
                 client.inventory_state_id += 1;
                 let cursor_item = client.cursor_item.clone();
@@ -132,23 +216,18 @@ pub(crate) fn update_player_inventories(
                 });
 
                 client.cursor_item_modified = false;
-            } else {
+            } else if modified_filtered != 0 {
                 // send the modified slots
-
-                // The slots that were NOT modified by this client, and they need to be sent
-                let modified_filtered = inventory.modified & !client.inventory_slots_modified;
-                if modified_filtered != 0 {
-                    client.inventory_state_id += 1;
-                    let state_id = client.inventory_state_id.0;
-                    for (i, slot) in inventory.slots.iter().enumerate() {
-                        if ((modified_filtered >> i) & 1) == 1 {
-                            client.write_packet(&SetContainerSlotEncode {
-                                window_id: 0,
-                                state_id: VarInt(state_id),
-                                slot_idx: i as i16,
-                                slot_data: slot.as_ref(),
-                            });
-                        }
+                client.inventory_state_id += 1;
+                let state_id = client.inventory_state_id.0;
+                for (i, slot) in inventory.slots.iter().enumerate() {
+                    if ((modified_filtered >> i) & 1) == 1 {
+                        client.write_packet(&SetContainerSlotEncode {
+                            window_id: 0,
+                            state_id: VarInt(state_id),
+                            slot_idx: i as i16,
+                            slot_data: slot.as_ref(),
+                        });
                     }
                 }
             }
@@ -201,6 +280,14 @@ pub enum InventoryKind {
     Cartography,
     Stonecutter,
     Player,
+    /// The inventory of a horse, donkey or mule, which is shown with the horse
+    /// screen. The inventory must be on the same entity as the animal's
+    /// [`McEntity`].
+    Horse {
+        /// If the animal carries a chest, which adds 15 slots after the saddle
+        /// and armor slots.
+        chest: bool,
+    },
 }
 
 impl InventoryKind {
@@ -215,7 +302,7 @@ impl InventoryKind {
             InventoryKind::Generic9x5 => 9 * 5,
             InventoryKind::Generic9x6 => 9 * 6,
             InventoryKind::Generic3x3 => 3 * 3,
-            InventoryKind::Anvil => 4,
+            InventoryKind::Anvil => 3,
             InventoryKind::Beacon => 1,
             InventoryKind::BlastFurnace => 3,
             InventoryKind::BrewingStand => 5,
@@ -233,6 +320,8 @@ impl InventoryKind {
             InventoryKind::Cartography => 3,
             InventoryKind::Stonecutter => 2,
             InventoryKind::Player => 46,
+            InventoryKind::Horse { chest: false } => 2,
+            InventoryKind::Horse { chest: true } => 2 + 15,
         }
     }
 }
@@ -267,6 +356,8 @@ impl From<InventoryKind> for WindowType {
             // arbitrarily chosen, because a player inventory technically does not have a window
             // type
             InventoryKind::Player => WindowType::Generic9x4,
+            // Horse inventories are opened with a different packet.
+            InventoryKind::Horse { .. } => WindowType::Generic9x1,
         }
     }
 }
@@ -325,6 +416,146 @@ impl OpenInventory {
     }
 }
 
+/// Decides where the items clients shift-click in an open inventory go.
+///
+/// Slots are numbered like in the container screen: the slots of the open
+/// inventory come first, followed by the 27 slots of the main inventory and
+/// the 9 slots of the hotbar of the player.
+pub trait QuickMove: Send + Sync + 'static {
+    /// Returns the slots the stack shift-clicked in `slot` is moved to. The
+    /// stack is first added to the stacks of the same item in these slots,
+    /// and the rest is put into the empty ones, both in the order of the
+    /// slots. Items which don't fit stay in the clicked slot.
+    fn destinations(&self, inventory: &Inventory, slot: u16, stack: &ItemStack) -> Vec<u16>;
+}
+
+impl<F> QuickMove for F
+where
+    F: Fn(&Inventory, u16, &ItemStack) -> Vec<u16> + Send + Sync + 'static,
+{
+    fn destinations(&self, inventory: &Inventory, slot: u16, stack: &ItemStack) -> Vec<u16> {
+        self(inventory, slot, stack)
+    }
+}
+
+/// A [`Component`] which overrides where shift-clicked items go while the
+/// [`Inventory`] on the same entity is open. Without it, shift-clicking
+/// follows the rules of vanilla, which are available with
+/// [`default_quick_move`].
+///
+/// ```
+/// use valence::inventory::{default_quick_move, QuickMoveRules};
+/// use valence::prelude::*;
+///
+/// // Shift-clicking never moves items into the first slot, which holds the
+/// // prize of a game.
+/// let rules = QuickMoveRules::new(|inventory: &Inventory, slot: u16, stack: &ItemStack| {
+///     let mut slots = default_quick_move(Some(inventory), slot, stack);
+///     slots.retain(|&s| s != 0);
+///     slots
+/// });
+/// # let _ = rules;
+/// ```
+#[derive(Component)]
+pub struct QuickMoveRules(Box<dyn QuickMove>);
+
+impl QuickMoveRules {
+    pub fn new(rules: impl QuickMove) -> Self {
+        Self(Box::new(rules))
+    }
+}
+
+impl fmt::Debug for QuickMoveRules {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("QuickMoveRules").finish_non_exhaustive()
+    }
+}
+
+/// Returns the slots vanilla moves a stack shift-clicked in `slot` to, with
+/// the slots numbered like in [`QuickMove`]. `open` is the open inventory, or
+/// `None` if the client is shift-clicking in its own inventory.
+pub fn default_quick_move(open: Option<&Inventory>, slot: u16, stack: &ItemStack) -> Vec<u16> {
+    let Some(open) = open else {
+        let other_part: Vec<u16> = match slot {
+            // the crafting grid and the armor
+            1..=8 => return (9..45).collect(),
+            9..=35 => (36..45).collect(),
+            36..=44 => (9..36).collect(),
+            _ => (9..45).collect(),
+        };
+
+        // armor and shields are worn if their slot is empty, and moved like
+        // other items otherwise
+        let worn_slot = if stack.item == ItemKind::Shield {
+            Some(OFF_HAND_SLOT)
+        } else {
+            armor_slot(stack.item)
+        };
+
+        return worn_slot
+            .filter(|&worn| worn != slot)
+            .into_iter()
+            .chain(other_part)
+            .collect();
+    };
+
+    let count = open.slot_count();
+
+    if slot < count {
+        // the last slot of the hotbar is filled first
+        return (count..count + 36).rev().collect();
+    }
+
+    match open.kind() {
+        InventoryKind::ShulkerBox if stack.item.to_str().ends_with("shulker_box") => vec![],
+        InventoryKind::Generic9x1
+        | InventoryKind::Generic9x2
+        | InventoryKind::Generic9x3
+        | InventoryKind::Generic9x4
+        | InventoryKind::Generic9x5
+        | InventoryKind::Generic9x6
+        | InventoryKind::Generic3x3
+        | InventoryKind::Hopper
+        | InventoryKind::ShulkerBox => (0..count).collect(),
+        // workstations fall back to moving items between the main inventory
+        // and the hotbar
+        kind => {
+            let other_part = if slot < count + 27 {
+                count + 27..count + 36
+            } else {
+                count..count + 27
+            };
+
+            (0..count)
+                .filter(|&s| !is_output_slot(kind, s))
+                .chain(other_part)
+                .collect()
+        }
+    }
+}
+
+/// Returns the armor slot of the player inventory an item is worn in, if it
+/// is armor.
+pub(crate) fn armor_slot(item: ItemKind) -> Option<u16> {
+    let name = item.to_str();
+
+    if name.ends_with("_helmet")
+        || name.ends_with("_head")
+        || name.ends_with("_skull")
+        || item == ItemKind::CarvedPumpkin
+    {
+        Some(HELMET_SLOT)
+    } else if name.ends_with("_chestplate") || item == ItemKind::Elytra {
+        Some(CHESTPLATE_SLOT)
+    } else if name.ends_with("_leggings") {
+        Some(LEGGINGS_SLOT)
+    } else if name.ends_with("_boots") {
+        Some(BOOTS_SLOT)
+    } else {
+        None
+    }
+}
+
 /// Handles the `OpenInventory` component being added to a client, which
 /// indicates that the client is now viewing an inventory, and sends inventory
 /// updates to the client when the inventory is modified.
@@ -332,6 +563,7 @@ pub(crate) fn update_open_inventories(
     mut commands: Commands,
     mut clients: Query<(Entity, &mut Client, &mut OpenInventory)>,
     mut inventories: Query<&mut Inventory>,
+    entities: Query<&McEntity>,
 ) {
     // These operations need to happen in this order.
 
@@ -342,57 +574,110 @@ pub(crate) fn update_open_inventories(
             // the inventory no longer exists, so close the inventory
             commands.entity(client_entity).remove::<OpenInventory>();
             let window_id = client.window_id;
-            client.write_packet(&CloseContainerS2c {
-                window_id,
-            });
+            client.write_packet(&CloseContainerS2c { window_id });
             continue;
         };
 
+        // the inventory of the player is shown below the open inventory
+        let player_inventory = inventories
+            .get(client_entity)
+            .ok()
+            .filter(|_| client_entity != open_inventory.entity);
+
         if open_inventory.is_added() {
             // send the inventory to the client if the client just opened the inventory
             client.window_id = client.window_id % 100 + 1;
             open_inventory.client_modified = 0;
 
-            let packet = OpenScreen {
-                window_id: VarInt(client.window_id.into()),
-                window_type: WindowType::from(inventory.kind),
-                window_title: (&inventory.title).into(),
-            };
-            client.write_packet(&packet);
+            if let InventoryKind::Horse { .. } = inventory.kind {
+                let Ok(entity) = entities.get(open_inventory.entity) else {
+                    commands.entity(client_entity).remove::<OpenInventory>();
+                    continue;
+                };
 
-            let packet = SetContainerContentEncode {
-                window_id: client.window_id,
-                state_id: VarInt(client.inventory_state_id.0),
-                slots: inventory.slot_slice(),
-                carried_item: &client.cursor_item.clone(),
-            };
-            client.write_packet(&packet);
-        } else {
-            // the client is already viewing the inventory
-            if inventory.modified == u64::MAX {
-                // send the entire inventory
-                client.inventory_state_id += 1;
-                let packet = SetContainerContentEncode {
-                    window_id: client.window_id,
-                    state_id: VarInt(client.inventory_state_id.0),
-                    slots: inventory.slot_slice(),
-                    carried_item: &client.cursor_item.clone(),
+                let window_id = client.window_id;
+                client.write_packet(&OpenHorseScreen {
+                    window_id,
+                    slot_count: VarInt(inventory.slot_count().into()),
+                    entity_id: entity.protocol_id(),
+                });
+            } else {
+                let packet = OpenScreen {
+                    window_id: VarInt(client.window_id.into()),
+                    window_type: WindowType::from(inventory.kind),
+                    window_title: (&inventory.title).into(),
                 };
                 client.write_packet(&packet);
-            } else {
+            }
+
+            match player_inventory {
+                Some(player_inventory) => {
+                    resync_open_inventory(&mut client, inventory, player_inventory);
+                }
+                None => {
+                    let packet = SetContainerContentEncode {
+                        window_id: client.window_id,
+                        state_id: VarInt(client.inventory_state_id.0),
+                        slots: inventory.slot_slice(),
+                        carried_item: &client.cursor_item.clone(),
+                    };
+                    client.write_packet(&packet);
+                }
+            }
+        } else {
+            // the client is already viewing the inventory
+
+            // The slots that were NOT modified by this client, and they need to be sent
+            let modified_filtered = inventory.modified & !open_inventory.client_modified;
+            // The same for the slots of the player shown below the inventory
+            let player_modified_filtered = player_inventory.map_or(0, |player_inventory| {
+                player_inventory.modified & !client.inventory_slots_modified & PLAYER_WINDOW_SLOTS
+            });
+
+            let changed = modified_filtered.count_ones() + player_modified_filtered.count_ones();
+            let window_slot_count = inventory.slot_count() + 36;
+
+            if inventory.modified == u64::MAX || prefers_full_sync(changed, window_slot_count) {
+                // send the entire window
+                match player_inventory {
+                    Some(player_inventory) => {
+                        resync_open_inventory(&mut client, inventory, player_inventory);
+                    }
+                    None => {
+                        client.inventory_state_id += 1;
+                        let packet = SetContainerContentEncode {
+                            window_id: client.window_id,
+                            state_id: VarInt(client.inventory_state_id.0),
+                            slots: inventory.slot_slice(),
+                            carried_item: &client.cursor_item.clone(),
+                        };
+                        client.write_packet(&packet);
+                    }
+                }
+            } else if changed != 0 {
                 // send the modified slots
                 let window_id = client.window_id as i8;
-                // The slots that were NOT modified by this client, and they need to be sent
-                let modified_filtered = inventory.modified & !open_inventory.client_modified;
-                if modified_filtered != 0 {
-                    client.inventory_state_id += 1;
-                    let state_id = client.inventory_state_id.0;
-                    for (i, slot) in inventory.slots.iter().enumerate() {
-                        if (modified_filtered >> i) & 1 == 1 {
+                client.inventory_state_id += 1;
+                let state_id = client.inventory_state_id.0;
+                for (i, slot) in inventory.slots.iter().enumerate() {
+                    if (modified_filtered >> i) & 1 == 1 {
+                        client.write_packet(&SetContainerSlotEncode {
+                            window_id,
+                            state_id: VarInt(state_id),
+                            slot_idx: i as i16,
+                            slot_data: slot.as_ref(),
+                        });
+                    }
+                }
+
+                if let Some(player_inventory) = player_inventory {
+                    for (i, slot) in player_inventory.slots.iter().enumerate() {
+                        if (player_modified_filtered >> i) & 1 == 1 {
+                            let slot_idx = inventory.slot_count() as usize + i - 9;
                             client.write_packet(&SetContainerSlotEncode {
                                 window_id,
                                 state_id: VarInt(state_id),
-                                slot_idx: i as i16,
+                                slot_idx: slot_idx as i16,
                                 slot_data: slot.as_ref(),
                             });
                         }
@@ -406,15 +691,33 @@ pub(crate) fn update_open_inventories(
     }
 
     // reset the modified flag
-    for (_, _, open_inventory) in clients.iter_mut() {
+    for (client_entity, _, open_inventory) in clients.iter_mut() {
         // validate that the inventory exists
         if let Ok(mut inventory) = inventories.get_component_mut::<Inventory>(open_inventory.entity)
         {
             inventory.modified = 0;
         }
+
+        if client_entity != open_inventory.entity {
+            if let Ok(mut player_inventory) = inventories.get_mut(client_entity) {
+                // the slots which are not shown in the window are sent once the window is
+                // closed
+                player_inventory.modified &= !PLAYER_WINDOW_SLOTS;
+            }
+        }
     }
 }
 
+/// The slots of a player inventory which are shown below open inventories,
+/// which are the main inventory and the hotbar.
+const PLAYER_WINDOW_SLOTS: u64 = ((1 << 36) - 1) << 9;
+
+/// Returns `true` if so many slots of a window changed that sending the whole
+/// window takes less bandwidth than sending each changed slot.
+fn prefers_full_sync(changed_slots: u32, slot_count: u16) -> bool {
+    changed_slots * 2 > slot_count as u32
+}
+
 /// Handles clients telling the server that they are closing an inventory.
 pub(crate) fn handle_close_container(
     mut commands: Commands,
@@ -442,14 +745,30 @@ pub(crate) fn update_client_on_close_inventory(
 pub(crate) fn handle_click_container(
     mut clients: Query<(&mut Client, &mut Inventory, Option<&mut OpenInventory>)>,
     mut inventories: Query<&mut Inventory, Without<Client>>,
+    menus: Query<(), With<OpenMenu>>,
+    quick_move_rules: Query<&QuickMoveRules>,
+    recipes: Option<Res<RecipeRegistry>>,
+    mut anvils: Query<&mut Anvil>,
+    mut merchants: Query<&mut MerchantOffers>,
     mut events: EventReader<ClickContainer>,
+    mut anvil_used: EventWriter<AnvilUsed>,
+    mut trades: EventWriter<TradeEvent>,
+    mut equipment_changes: EventWriter<EquipmentChangeEvent>,
 ) {
+    // The equipment of every clicking client from before its first click.
+    let mut snapshots = FxHashMap::default();
+
     for event in events.iter() {
         let Ok((mut client, mut client_inventory, mut open_inventory)) =
-            clients.get_mut(event.client) else {
-                // the client does not exist, ignore
-                continue;
-            };
+            clients.get_mut(event.client)
+        else {
+            // the client does not exist, ignore
+            continue;
+        };
+
+        snapshots.entry(event.client).or_insert_with(|| {
+            EquipmentSnapshot::take(event.client, &client_inventory, client.held_item_slot())
+        });
 
         // validate the window id
         if (event.window_id == 0) != open_inventory.is_none() {
@@ -464,10 +783,19 @@ pub(crate) fn handle_click_container(
 
         if let Some(open_inventory) = open_inventory.as_mut() {
             // the player is interacting with an inventory that is open
-            let Ok(mut target_inventory) = inventories.get_component_mut::<Inventory>(open_inventory.entity) else {
+            let Ok(mut target_inventory) =
+                inventories.get_component_mut::<Inventory>(open_inventory.entity)
+            else {
                 // the inventory does not exist, ignore
                 continue;
             };
+
+            if menus.contains(open_inventory.entity) {
+                // clicks can't move the items of menus, so the click is undone
+                resync_open_inventory(&mut client, &target_inventory, &client_inventory);
+                continue;
+            }
+
             if client.inventory_state_id.0 != event.state_id {
                 // client is out of sync, resync, ignore click
                 debug!("Client state id mismatch, resyncing");
@@ -482,10 +810,110 @@ pub(crate) fn handle_click_container(
                 continue;
             }
 
+            if event.slot_id == ANVIL_RESULT_SLOT as i16 {
+                if let Ok(mut anvil) = anvils.get_mut(open_inventory.entity) {
+                    // the result is made on the server like crafting results
+                    let result = take_anvil_result(
+                        &mut client,
+                        &mut anvil,
+                        &mut target_inventory,
+                        &mut client_inventory,
+                        event.mode,
+                    );
+
+                    if let Some(result) = result {
+                        anvil_used.send(AnvilUsed {
+                            client: event.client,
+                            anvil: open_inventory.entity,
+                            item: result.item,
+                            cost: result.cost,
+                        });
+                    }
+
+                    resync_open_inventory(&mut client, &target_inventory, &client_inventory);
+                    continue;
+                }
+            }
+
+            if event.slot_id == MERCHANT_RESULT_SLOT as i16 {
+                if let Ok(mut merchant) = merchants.get_mut(open_inventory.entity) {
+                    // trades are carried out on the server like anvil uses
+                    let used = take_trade_result(
+                        &mut client,
+                        &mut merchant,
+                        &mut target_inventory,
+                        &mut client_inventory,
+                        event.mode,
+                    );
+
+                    for offer in used {
+                        trades.send(TradeEvent {
+                            client: event.client,
+                            merchant: open_inventory.entity,
+                            offer,
+                            output: merchant.offers[offer].output.clone(),
+                        });
+                    }
+
+                    resync_open_inventory(&mut client, &target_inventory, &client_inventory);
+                    continue;
+                }
+            }
+
+            let crafting = recipes
+                .as_deref()
+                .filter(|_| has_crafting_grid(&target_inventory));
+
+            if let Some(recipes) = crafting {
+                if event.slot_id == 0 {
+                    // the result is crafted on the server, and the client is sent the
+                    // outcome instead of its prediction
+                    take_crafting_result(
+                        recipes,
+                        &mut client,
+                        &mut target_inventory,
+                        Some(&mut *client_inventory),
+                        event.mode,
+                    );
+                    resync_open_inventory(&mut client, &target_inventory, &client_inventory);
+                    continue;
+                }
+            }
+
+            let mut window = ClickWindow {
+                open: Some(&mut *target_inventory),
+                player: &mut *client_inventory,
+                rules: quick_move_rules.get(open_inventory.entity).ok(),
+            };
+
+            if is_server_side_click(&client, &window, event) {
+                if click_on_server(&mut client, &mut window, event) {
+                    resync_open_inventory(&mut client, &target_inventory, &client_inventory);
+                } else {
+                    // the prediction of the client was right, so the changes are not sent back
+                    let open_slot_count = target_inventory.slot_count() as i16;
+                    for &(slot_id, _) in &event.slot_changes {
+                        if (0..open_slot_count).contains(&slot_id) {
+                            open_inventory.client_modified |= 1 << slot_id;
+                        } else if (open_slot_count..open_slot_count + 36).contains(&slot_id) {
+                            let slot_id =
+                                convert_to_player_slot_id(target_inventory.kind, slot_id as u16);
+                            client.inventory_slots_modified |= 1 << slot_id;
+                        }
+                    }
+                }
+                continue;
+            }
+
+            client.inventory_drag = None;
             client.cursor_item = event.carried_item.clone();
 
             for (slot_id, item) in event.slot_changes.clone() {
-                if (0i16..target_inventory.slot_count() as i16).contains(&slot_id) {
+                if slot_id == 0 && crafting.is_some() {
+                    // the result slot is computed by the server, so the prediction of the
+                    // client is replaced
+                    target_inventory.modified |= 1;
+                } else if (0i16..target_inventory.slot_count() as i16).contains(&slot_id) {
                     // the client is interacting with a slot in the target inventory
                     target_inventory.replace_slot(slot_id as u16, item);
                     open_inventory.client_modified |= 1 << slot_id;
@@ -513,10 +941,52 @@ pub(crate) fn handle_click_container(
                 continue;
             }
 
+            let crafting = recipes
+                .as_deref()
+                .filter(|_| has_crafting_grid(&client_inventory));
+
+            if let Some(recipes) = crafting {
+                if event.slot_id == 0 {
+                    take_crafting_result(
+                        recipes,
+                        &mut client,
+                        &mut client_inventory,
+                        None,
+                        event.mode,
+                    );
+                    // send the whole inventory and the cursor
+                    client_inventory.modified = u64::MAX;
+                    continue;
+                }
+            }
+
+            let mut window = ClickWindow {
+                open: None,
+                player: &mut *client_inventory,
+                rules: None,
+            };
+
+            if is_server_side_click(&client, &window, event) {
+                if click_on_server(&mut client, &mut window, event) {
+                    // send the whole inventory and the cursor
+                    client_inventory.modified = u64::MAX;
+                } else {
+                    for &(slot_id, _) in &event.slot_changes {
+                        if (0i16..client_inventory.slot_count() as i16).contains(&slot_id) {
+                            client.inventory_slots_modified |= 1 << slot_id;
+                        }
+                    }
+                }
+                continue;
+            }
+
             // TODO: do more validation on the click
+            client.inventory_drag = None;
             client.cursor_item = event.carried_item.clone();
             for (slot_id, item) in event.slot_changes.clone() {
-                if (0i16..client_inventory.slot_count() as i16).contains(&slot_id) {
+                if slot_id == 0 && crafting.is_some() {
+                    client_inventory.modified |= 1;
+                } else if (0i16..client_inventory.slot_count() as i16).contains(&slot_id) {
                     client_inventory.replace_slot(slot_id as u16, item);
                     client.inventory_slots_modified |= 1 << slot_id;
                 } else {
@@ -528,13 +998,499 @@ pub(crate) fn handle_click_container(
                     );
                 }
             }
-        }
+        }
+    }
+
+    for (client_ent, snapshot) in snapshots {
+        if let Ok((client, client_inventory, _)) = clients.get(client_ent) {
+            snapshot.send_changes(
+                client_inventory,
+                client.held_item_slot(),
+                &mut equipment_changes,
+            );
+        }
+    }
+}
+
+/// Returns `true` for the clicks which are carried out by the server instead of
+/// trusting the slots changed by the client.
+fn is_server_side_click(client: &Client, window: &ClickWindow, event: &ClickContainer) -> bool {
+    match event.mode {
+        ClickContainerMode::Drag
+        | ClickContainerMode::DoubleClick
+        | ClickContainerMode::ShiftClick => true,
+        // right clicks with or on bundles
+        ClickContainerMode::Click => {
+            let is_bundle = |stack: Option<&ItemStack>| {
+                stack.map_or(false, |stack| stack.item == ItemKind::Bundle)
+            };
+
+            event.button == 1
+                && (0..window.slot_count() as i16).contains(&event.slot_id)
+                && (is_bundle(client.cursor_item.as_ref())
+                    || is_bundle(window.slot(event.slot_id as u16)))
+        }
+        _ => false,
+    }
+}
+
+/// The mouse button a client drags the item on its cursor with.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub(crate) enum DragButton {
+    /// Splits the cursor evenly between the slots.
+    Left,
+    /// Puts one item in each slot.
+    Right,
+    /// Puts a full stack in each slot. Only clients in creative mode can
+    /// middle drag.
+    Middle,
+}
+
+/// The slots a client has dragged the item on its cursor across so far.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub(crate) struct InventoryDrag {
+    button: DragButton,
+    slots: Vec<u16>,
+}
+
+/// The slots of the window a client clicks in, which are the slots of the
+/// open inventory followed by the main inventory and the hotbar of the
+/// player, or the slots of the player inventory if no inventory is open.
+struct ClickWindow<'a> {
+    open: Option<&'a mut Inventory>,
+    player: &'a mut Inventory,
+    /// The rules for shift-clicking in the open inventory.
+    rules: Option<&'a QuickMoveRules>,
+}
+
+impl ClickWindow<'_> {
+    fn slot_count(&self) -> u16 {
+        match &self.open {
+            Some(open) => open.slot_count() + 36,
+            None => self.player.slot_count(),
+        }
+    }
+
+    fn slot(&self, idx: u16) -> Option<&ItemStack> {
+        match &self.open {
+            Some(open) if idx < open.slot_count() => open.slot(idx),
+            Some(open) => self.player.slot(convert_to_player_slot_id(open.kind, idx)),
+            None => self.player.slot(idx),
+        }
+    }
+
+    fn replace_slot(&mut self, idx: u16, item: Option<ItemStack>) {
+        match &mut self.open {
+            Some(open) if idx < open.slot_count() => {
+                open.replace_slot(idx, item);
+            }
+            Some(open) => {
+                let idx = convert_to_player_slot_id(open.kind, idx);
+                self.player.replace_slot(idx, item);
+            }
+            None => {
+                self.player.replace_slot(idx, item);
+            }
+        }
+    }
+
+    /// Returns `true` if the slot holds the output of the inventory, which
+    /// items can't be dragged into or gathered from.
+    fn is_output_slot(&self, idx: u16) -> bool {
+        let kind = self
+            .open
+            .as_ref()
+            .map_or(InventoryKind::Player, |open| open.kind);
+
+        is_output_slot(kind, idx)
+    }
+
+    fn accepts_drag(&self, idx: u16) -> bool {
+        // only armor can be put in the armor slots of the player
+        let is_armor_slot = self.open.is_none() && (HELMET_SLOT..=BOOTS_SLOT).contains(&idx);
+        !self.is_output_slot(idx) && !is_armor_slot
+    }
+
+    fn quick_move_destinations(&self, idx: u16, stack: &ItemStack) -> Vec<u16> {
+        match (&self.open, self.rules) {
+            (Some(open), Some(rules)) => rules.0.destinations(open, idx, stack),
+            (open, _) => default_quick_move(open.as_deref(), idx, stack),
+        }
+    }
+}
+
+/// Returns `true` if the slot of an inventory of the given kind holds the
+/// output of the inventory, which items can't be dragged, gathered, or
+/// shift-clicked into.
+fn is_output_slot(kind: InventoryKind, idx: u16) -> bool {
+    if idx >= kind.slot_count() as u16 {
+        return false;
+    }
+
+    match kind {
+        InventoryKind::Player | InventoryKind::Crafting => idx == 0,
+        InventoryKind::Anvil
+        | InventoryKind::Furnace
+        | InventoryKind::BlastFurnace
+        | InventoryKind::Smoker
+        | InventoryKind::Grindstone
+        | InventoryKind::Merchant
+        | InventoryKind::Cartography
+        | InventoryKind::Smithing => idx == 2,
+        InventoryKind::Stonecutter => idx == 1,
+        InventoryKind::Loom => idx == 3,
+        _ => false,
+    }
+}
+
+/// Carries out a click for which [`is_server_side_click`] returns `true`.
+/// Returns `true` if the outcome differs from what the client predicted, in which case the client
+/// must be resynced.
+fn click_on_server(client: &mut Client, window: &mut ClickWindow, event: &ClickContainer) -> bool {
+    let before: Vec<_> = (0..window.slot_count())
+        .map(|idx| window.slot(idx).cloned())
+        .collect();
+
+    match event.mode {
+        ClickContainerMode::Drag => drag(client, window, event.button, event.slot_id),
+        ClickContainerMode::DoubleClick => {
+            client.inventory_drag = None;
+            gather(client, window, event.slot_id);
+        }
+        ClickContainerMode::Click => {
+            client.inventory_drag = None;
+            click_bundle(client, window, event.slot_id as u16);
+        }
+        ClickContainerMode::ShiftClick => {
+            client.inventory_drag = None;
+            quick_move(window, event.slot_id);
+        }
+        _ => {}
+    }
+
+    if client.cursor_item != event.carried_item {
+        return true;
+    }
+
+    before.iter().enumerate().any(|(idx, old)| {
+        let new = window.slot(idx as u16);
+        let predicted = event
+            .slot_changes
+            .iter()
+            .rev()
+            .find(|(slot_id, _)| *slot_id == idx as i16);
+
+        match predicted {
+            Some((_, item)) => item.as_ref() != new,
+            None => old.as_ref() != new,
+        }
+    })
+}
+
+/// Handles a drag click. Dragging starts, adds a slot, or ends depending on
+/// the lowest two bits of the button, and the mouse button is in the
+/// remaining bits. The cursor is distributed when the drag ends.
+fn drag(client: &mut Client, window: &mut ClickWindow, button: i8, slot_id: i16) {
+    let drag_button = match button >> 2 {
+        0 => DragButton::Left,
+        1 => DragButton::Right,
+        2 => DragButton::Middle,
+        _ => {
+            warn!("Client sent a drag with an invalid button: {button}");
+            client.inventory_drag = None;
+            return;
+        }
+    };
+
+    let creative = client.game_mode() == GameMode::Creative;
+
+    match button & 3 {
+        // start
+        0 => {
+            client.inventory_drag = (client.cursor_item.is_some()
+                && (drag_button != DragButton::Middle || creative))
+                .then(|| InventoryDrag {
+                    button: drag_button,
+                    slots: vec![],
+                });
+        }
+        // add slot
+        1 => {
+            let (Some(drag), Some(cursor)) = (&mut client.inventory_drag, &client.cursor_item)
+            else {
+                return;
+            };
+
+            if drag.button != drag_button
+                || !(0..window.slot_count() as i16).contains(&slot_id)
+                || !window.accepts_drag(slot_id as u16)
+                || drag.slots.contains(&(slot_id as u16))
+            {
+                return;
+            }
+
+            let fits = match window.slot(slot_id as u16) {
+                None => true,
+                Some(stack) => can_stack(stack, cursor),
+            };
+
+            // every slot gets at least one item, except when middle dragging
+            if fits
+                && (drag.button == DragButton::Middle || drag.slots.len() < cursor.count() as usize)
+            {
+                drag.slots.push(slot_id as u16);
+            }
+        }
+        // end
+        2 => {
+            let Some(drag) = client.inventory_drag.take() else {
+                return;
+            };
+
+            if drag.button != drag_button || drag.slots.is_empty() {
+                return;
+            }
+
+            let Some(cursor) = client.cursor_item.clone() else {
+                return;
+            };
+
+            let max = cursor.item.max_stack();
+            let per_slot = match drag.button {
+                DragButton::Left => cursor.count() / drag.slots.len() as u8,
+                DragButton::Right => 1,
+                DragButton::Middle => max,
+            };
+
+            let mut remaining = cursor.count();
+
+            for slot in drag.slots {
+                let count = match window.slot(slot) {
+                    None => 0,
+                    Some(stack) if can_stack(stack, &cursor) => stack.count(),
+                    Some(_) => continue,
+                };
+
+                let mut added = per_slot.min(max.saturating_sub(count));
+                if drag.button != DragButton::Middle {
+                    added = added.min(remaining);
+                    remaining -= added;
+                }
+
+                if added > 0 {
+                    let mut stack = cursor.clone();
+                    stack.set_count(count + added);
+                    window.replace_slot(slot, Some(stack));
+                }
+            }
+
+            if drag.button != DragButton::Middle {
+                client.cursor_item = (remaining > 0).then(|| {
+                    let mut cursor = cursor;
+                    cursor.set_count(remaining);
+                    cursor
+                });
+            }
+        }
+        _ => {
+            warn!("Client sent a drag with an invalid button: {button}");
+            client.inventory_drag = None;
+        }
+    }
+}
+
+/// Handles a double click, which gathers the items matching the cursor into
+/// the cursor. Stacks which are not full are taken first.
+fn gather(client: &mut Client, window: &mut ClickWindow, slot_id: i16) {
+    let Some(mut cursor) = client.cursor_item.clone() else {
+        return;
+    };
+
+    // the first click of the double click picked up the item in the slot
+    if !(0..window.slot_count() as i16).contains(&slot_id)
+        || window.slot(slot_id as u16).is_some() && !window.is_output_slot(slot_id as u16)
+    {
+        return;
+    }
+
+    let max = cursor.item.max_stack();
+
+    for full_stacks in [false, true] {
+        for slot in 0..window.slot_count() {
+            if cursor.count() >= max {
+                break;
+            }
+
+            if window.is_output_slot(slot) {
+                continue;
+            }
+
+            let Some(stack) = window.slot(slot) else {
+                continue;
+            };
+
+            if !can_stack(stack, &cursor) || (stack.count() >= max) != full_stacks {
+                continue;
+            }
+
+            let taken = stack.count().min(max - cursor.count());
+            let left = stack.count() - taken;
+
+            let new = (left > 0).then(|| {
+                let mut stack = stack.clone();
+                stack.set_count(left);
+                stack
+            });
+
+            window.replace_slot(slot, new);
+            cursor.set_count(cursor.count() + taken);
+        }
+    }
+
+    client.cursor_item = Some(cursor);
+}
+
+/// Handles a shift click, which moves the stack in the slot to the slots
+/// given by the quick move rules of the window.
+fn quick_move(window: &mut ClickWindow, slot_id: i16) {
+    if !(0..window.slot_count() as i16).contains(&slot_id) {
+        return;
+    }
+
+    let slot = slot_id as u16;
+
+    let Some(mut stack) = window.slot(slot).cloned() else {
+        return;
+    };
+
+    let destinations = window.quick_move_destinations(slot, &stack);
+    let slot_count = window.slot_count();
+    let max = stack.item.max_stack();
+    let mut remaining = stack.count();
+
+    // stacks of the same item are filled before empty slots
+    for fill_empty in [false, true] {
+        for &dest in &destinations {
+            if remaining == 0 {
+                break;
+            }
+
+            if dest == slot || dest >= slot_count || window.is_output_slot(dest) {
+                continue;
+            }
+
+            match window.slot(dest) {
+                Some(existing)
+                    if !fill_empty && can_stack(existing, &stack) && existing.count() < max =>
+                {
+                    let added = remaining.min(max - existing.count());
+                    let mut existing = existing.clone();
+                    existing.set_count(existing.count() + added);
+                    window.replace_slot(dest, Some(existing));
+                    remaining -= added;
+                }
+                None if fill_empty => {
+                    let added = remaining.min(max);
+                    let mut new = stack.clone();
+                    new.set_count(added);
+                    window.replace_slot(dest, Some(new));
+                    remaining -= added;
+                }
+                _ => {}
+            }
+        }
+    }
+
+    if remaining != stack.count() {
+        stack.set_count(remaining);
+        window.replace_slot(slot, (remaining > 0).then_some(stack));
+    }
+}
+
+/// Handles a right click with a bundle on the cursor or on a bundle in a slot.
+/// The item in the slot or on the cursor is put into the bundle, or if there
+/// is none, the newest item in the bundle is taken out.
+fn click_bundle(client: &mut Client, window: &mut ClickWindow, slot_id: u16) {
+    if window.is_output_slot(slot_id) {
+        return;
+    }
+
+    match (client.cursor_item.clone(), window.slot(slot_id).cloned()) {
+        (Some(mut bundle), slot) if bundle.item == ItemKind::Bundle => match slot {
+            None => {
+                if !window.accepts_drag(slot_id) {
+                    return;
+                }
+
+                if let Some(removed) = remove_from_bundle(&mut bundle) {
+                    window.replace_slot(slot_id, Some(removed));
+                    client.cursor_item = Some(bundle);
+                }
+            }
+            Some(mut stack) => {
+                let added = insert_into_bundle(&mut bundle, &stack);
+
+                if added > 0 {
+                    let left = stack.count() - added;
+                    stack.set_count(left);
+                    window.replace_slot(slot_id, (left > 0).then_some(stack));
+                    client.cursor_item = Some(bundle);
+                }
+            }
+        },
+        (cursor, Some(mut bundle)) if bundle.item == ItemKind::Bundle => match cursor {
+            None => {
+                if let Some(removed) = remove_from_bundle(&mut bundle) {
+                    window.replace_slot(slot_id, Some(bundle));
+                    client.cursor_item = Some(removed);
+                }
+            }
+            Some(mut cursor) => {
+                let added = insert_into_bundle(&mut bundle, &cursor);
+
+                if added > 0 {
+                    let left = cursor.count() - added;
+                    cursor.set_count(left);
+                    window.replace_slot(slot_id, Some(bundle));
+                    client.cursor_item = (left > 0).then_some(cursor);
+                }
+            }
+        },
+        _ => {}
     }
 }
 
+/// Sends the contents of the open inventory and the inventory of the player
+/// to the client, undoing any changes the client made on its side.
+pub(crate) fn resync_open_inventory(
+    client: &mut Client,
+    open_inventory: &Inventory,
+    player_inventory: &Inventory,
+) {
+    // The window contains the slots of the open inventory followed by the main
+    // inventory and the hotbar of the player.
+    let slots: Vec<_> = open_inventory
+        .slots()
+        .chain(player_inventory.slots().skip(9).take(36))
+        .map(|slot| slot.cloned())
+        .collect();
+
+    client.inventory_state_id += 1;
+
+    client.write_packet(&SetContainerContentEncode {
+        window_id: client.window_id,
+        state_id: VarInt(client.inventory_state_id.0),
+        slots: &slots,
+        carried_item: &client.cursor_item.clone(),
+    });
+}
+
 pub(crate) fn handle_set_slot_creative(
     mut clients: Query<(&mut Client, &mut Inventory)>,
+    rules: Option<Res<CreativeRules>>,
     mut events: EventReader<SetCreativeModeSlot>,
+    mut filtered: EventWriter<CreativeItemFiltered>,
+    mut equipment_changes: EventWriter<EquipmentChangeEvent>,
 ) {
     for event in events.iter() {
         if let Ok((mut client, mut inventory)) = clients.get_mut(event.client) {
@@ -546,7 +1502,33 @@ pub(crate) fn handle_set_slot_creative(
                 // the client is trying to interact with a slot that does not exist, ignore
                 continue;
             }
-            inventory.replace_slot(event.slot as u16, event.clicked_item.clone());
+
+            let item = match (&rules, &event.clicked_item) {
+                (Some(rules), Some(original)) => {
+                    let result = rules.apply(event.client, original);
+
+                    if result.as_ref() != Some(original) {
+                        filtered.send(CreativeItemFiltered {
+                            client: event.client,
+                            slot: event.slot as u16,
+                            original: original.clone(),
+                            filtered: result.clone(),
+                        });
+                    }
+
+                    // a rejected item leaves the slot as it is
+                    match result {
+                        Some(item) => Some(item),
+                        None => inventory.slot(event.slot as u16).cloned(),
+                    }
+                }
+                _ => event.clicked_item.clone(),
+            };
+
+            let snapshot =
+                EquipmentSnapshot::take(event.client, &inventory, client.held_item_slot());
+            inventory.replace_slot(event.slot as u16, item.clone());
+            snapshot.send_changes(&inventory, client.held_item_slot(), &mut equipment_changes);
             inventory.modified &= !(1 << event.slot); // clear the modified bit, since we are about to send the update
             client.inventory_state_id += 1;
             let state_id = client.inventory_state_id.0;
@@ -558,19 +1540,40 @@ pub(crate) fn handle_set_slot_creative(
                 window_id: 0,
                 state_id: VarInt(state_id),
                 slot_idx: event.slot,
-                slot_data: event.clicked_item.as_ref(),
+                slot_data: item.as_ref(),
             });
         }
     }
 }
 
 pub(crate) fn handle_set_held_item(
-    mut clients: Query<&mut Client>,
+    mut clients: Query<(&mut Client, Option<&Inventory>, Option<&mut HeldItem>)>,
     mut events: EventReader<SetHeldItem>,
+    mut equipment_changes: EventWriter<EquipmentChangeEvent>,
 ) {
     for event in events.iter() {
-        if let Ok(mut client) = clients.get_mut(event.client) {
-            client.held_item_slot = convert_hotbar_slot_id(event.slot as u16);
+        if let Ok((mut client, inventory, held)) = clients.get_mut(event.client) {
+            if !(0..=8).contains(&event.slot) {
+                warn!(
+                    "Client {} selected invalid hotbar slot {}",
+                    client.username(),
+                    event.slot
+                );
+                continue;
+            }
+
+            let new_slot = convert_hotbar_slot_id(event.slot as u16);
+
+            if let Some(inventory) = inventory {
+                EquipmentSnapshot::take(event.client, inventory, client.held_item_slot())
+                    .send_changes(inventory, new_slot, &mut equipment_changes);
+            }
+
+            client.held_item_slot = new_slot;
+
+            if let Some(mut held) = held {
+                held.set_hotbar_slot(event.slot as u8);
+            }
         }
     }
 }
@@ -587,6 +1590,80 @@ fn convert_hotbar_slot_id(slot_id: u16) -> u16 {
     slot_id + 36
 }
 
+/// Converts a slot of the player inventory to the slot vanilla player data
+/// saves it in. Returns `None` for the crafting grid.
+fn player_slot_to_nbt(idx: u16) -> Option<i8> {
+    match idx {
+        HELMET_SLOT..=BOOTS_SLOT => Some(103 - (idx - HELMET_SLOT) as i8),
+        9..=35 => Some(idx as i8),
+        36..=44 => Some(idx as i8 - 36),
+        OFF_HAND_SLOT => Some(-106),
+        _ => None,
+    }
+}
+
+/// The inverse of [`player_slot_to_nbt`].
+fn player_slot_from_nbt(slot: i8) -> Option<u16> {
+    match slot {
+        0..=8 => Some(slot as u16 + 36),
+        9..=35 => Some(slot as u16),
+        100..=103 => Some(HELMET_SLOT + (103 - slot) as u16),
+        -106 => Some(OFF_HAND_SLOT),
+        _ => None,
+    }
+}
+
+/// The slot in the player's inventory holding the helmet.
+pub(crate) const HELMET_SLOT: u16 = 5;
+/// The slot in the player's inventory holding the chestplate.
+pub(crate) const CHESTPLATE_SLOT: u16 = 6;
+/// The slot in the player's inventory holding the leggings.
+pub(crate) const LEGGINGS_SLOT: u16 = 7;
+/// The slot in the player's inventory holding the boots.
+pub(crate) const BOOTS_SLOT: u16 = 8;
+/// The slot in the player's inventory holding the item in the off hand.
+pub(crate) const OFF_HAND_SLOT: u16 = 45;
+
+/// Returns the slot in the client's player inventory holding the item in the
+/// given hand.
+pub(crate) fn hand_slot(client: &Client, hand: Hand) -> u16 {
+    match hand {
+        Hand::Main => client.held_item_slot(),
+        Hand::Off => OFF_HAND_SLOT,
+    }
+}
+
+/// Returns the item a client is holding in the given hand, according to the
+/// client's player inventory.
+pub(crate) fn held_item<'a>(
+    client: &Client,
+    inventory: &'a Inventory,
+    hand: Hand,
+) -> Option<&'a ItemStack> {
+    inventory.slot(hand_slot(client, hand))
+}
+
+/// Removes one item from the stack the client is holding in the given hand.
+/// Clients in creative mode don't use up their items.
+pub(crate) fn consume_held_item(client: &Client, inventory: &mut Inventory, hand: Hand) {
+    if client.game_mode() == GameMode::Creative {
+        return;
+    }
+
+    let slot = hand_slot(client, hand);
+
+    let Some(mut stack) = inventory.slot(slot).cloned() else {
+        return
+    };
+
+    if stack.count() > 1 {
+        stack.set_count(stack.count() - 1);
+        inventory.replace_slot(slot, stack);
+    } else {
+        inventory.replace_slot(slot, None);
+    }
+}
+
 #[cfg(test)]
 mod test {
     use bevy_app::App;
@@ -1001,6 +2078,47 @@ mod test {
         );
     }
 
+    #[test]
+    fn test_set_creative_mode_slot_with_rules() {
+        let mut app = App::new();
+        let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+        app.insert_resource(CreativeRules::new().with_denied_items([ItemKind::Bedrock]));
+        let mut client = app
+            .world
+            .get_mut::<Client>(client_ent)
+            .expect("could not find client");
+        client.set_game_mode(GameMode::Creative);
+
+        // Process a tick to get past the "on join" logic.
+        app.update();
+        client_helper.clear_sent();
+
+        client_helper.send(&valence_protocol::packets::c2s::play::SetCreativeModeSlot {
+            slot: 36,
+            clicked_item: Some(ItemStack::new(ItemKind::Bedrock, 1, None)),
+        });
+        client_helper.send(&valence_protocol::packets::c2s::play::SetCreativeModeSlot {
+            slot: 37,
+            clicked_item: Some(ItemStack::new(ItemKind::Diamond, 127, None)),
+        });
+
+        app.update();
+
+        // Make assertions
+        let inventory = app
+            .world
+            .get::<Inventory>(client_ent)
+            .expect("could not find inventory for client");
+        assert_eq!(inventory.slot(36), None);
+        assert_eq!(
+            inventory.slot(37),
+            Some(&ItemStack::new(ItemKind::Diamond, 64, None))
+        );
+
+        let events = app.world.resource::<Events<CreativeItemFiltered>>();
+        assert_eq!(events.get_reader().iter(events).count(), 2);
+    }
+
     #[test]
     fn test_ignore_set_creative_mode_slot_if_not_creative() {
         let mut app = App::new();
@@ -1088,4 +2206,324 @@ mod test {
 
         Ok(())
     }
+
+    #[test]
+    fn test_should_distribute_left_drag() -> anyhow::Result<()> {
+        let mut app = App::new();
+        let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+
+        // Process a tick to get past the "on join" logic.
+        app.update();
+        client_helper.clear_sent();
+
+        let mut client = app.world.get_mut::<Client>(client_ent).unwrap();
+        client.cursor_item = Some(ItemStack::new(ItemKind::Diamond, 5, None));
+        let state_id = client.inventory_state_id.0;
+
+        let diamonds = |count| Some(ItemStack::new(ItemKind::Diamond, count, None));
+
+        // Drag the cursor across two slots with the left button.
+        for (button, slot_idx, slots, carried_item) in [
+            (0, -999, vec![], diamonds(5)),
+            (1, 36, vec![], diamonds(5)),
+            (1, 37, vec![], diamonds(5)),
+            (
+                2,
+                -999,
+                vec![(36, diamonds(2)), (37, diamonds(2))],
+                diamonds(1),
+            ),
+        ] {
+            client_helper.send(&valence_protocol::packets::c2s::play::ClickContainer {
+                window_id: 0,
+                state_id: VarInt(state_id),
+                slot_idx,
+                button,
+                mode: valence_protocol::types::ClickContainerMode::Drag,
+                slots,
+                carried_item,
+            });
+        }
+
+        app.update();
+
+        // The client predicted the outcome, so nothing is sent back.
+        let sent_packets = client_helper.collect_sent()?;
+        assert_packet_count!(
+            sent_packets,
+            0,
+            S2cPlayPacket::SetContainerContent(_) | S2cPlayPacket::SetContainerSlot(_)
+        );
+
+        let inventory = app.world.get::<Inventory>(client_ent).unwrap();
+        assert_eq!(inventory.slot(36), diamonds(2).as_ref());
+        assert_eq!(inventory.slot(37), diamonds(2).as_ref());
+
+        let client = app.world.get::<Client>(client_ent).unwrap();
+        assert_eq!(client.cursor_item, diamonds(1));
+        assert_eq!(client.inventory_drag, None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_should_gather_on_double_click() -> anyhow::Result<()> {
+        let mut app = App::new();
+        let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+
+        let diamonds = |count| Some(ItemStack::new(ItemKind::Diamond, count, None));
+
+        let mut inventory = app.world.get_mut::<Inventory>(client_ent).unwrap();
+        inventory.replace_slot(20, diamonds(64));
+        inventory.replace_slot(21, diamonds(3));
+
+        // Process a tick to get past the "on join" logic.
+        app.update();
+        client_helper.clear_sent();
+
+        let mut client = app.world.get_mut::<Client>(client_ent).unwrap();
+        client.cursor_item = diamonds(10);
+        let state_id = client.inventory_state_id.0;
+
+        // The client wrongly predicts that nothing is gathered.
+        client_helper.send(&valence_protocol::packets::c2s::play::ClickContainer {
+            window_id: 0,
+            state_id: VarInt(state_id),
+            slot_idx: 22,
+            button: 0,
+            mode: valence_protocol::types::ClickContainerMode::DoubleClick,
+            slots: vec![],
+            carried_item: diamonds(10),
+        });
+
+        app.update();
+
+        let sent_packets = client_helper.collect_sent()?;
+        assert_packet_count!(sent_packets, 1, S2cPlayPacket::SetContainerContent(_));
+
+        // The stack which is not full is taken first.
+        let inventory = app.world.get::<Inventory>(client_ent).unwrap();
+        assert_eq!(inventory.slot(20), diamonds(13).as_ref());
+        assert_eq!(inventory.slot(21), None);
+
+        let client = app.world.get::<Client>(client_ent).unwrap();
+        assert_eq!(client.cursor_item, diamonds(64));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_should_put_clicked_item_into_bundle() -> anyhow::Result<()> {
+        let mut app = App::new();
+        let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+
+        let diamonds = ItemStack::new(ItemKind::Diamond, 10, None);
+
+        let mut inventory = app.world.get_mut::<Inventory>(client_ent).unwrap();
+        inventory.replace_slot(20, diamonds.clone());
+
+        // Process a tick to get past the "on join" logic.
+        app.update();
+        client_helper.clear_sent();
+
+        let mut client = app.world.get_mut::<Client>(client_ent).unwrap();
+        client.cursor_item = Some(ItemStack::new(ItemKind::Bundle, 1, None));
+        let state_id = client.inventory_state_id.0;
+
+        let mut filled = ItemStack::new(ItemKind::Bundle, 1, None);
+        insert_into_bundle(&mut filled, &diamonds);
+
+        // The client rightly predicts that the diamonds are put into the bundle.
+        client_helper.send(&valence_protocol::packets::c2s::play::ClickContainer {
+            window_id: 0,
+            state_id: VarInt(state_id),
+            slot_idx: 20,
+            button: 1,
+            mode: ClickContainerMode::Click,
+            slots: vec![(20, None)],
+            carried_item: Some(filled.clone()),
+        });
+
+        app.update();
+
+        let sent_packets = client_helper.collect_sent()?;
+        assert_packet_count!(sent_packets, 0, S2cPlayPacket::SetContainerContent(_));
+
+        let inventory = app.world.get::<Inventory>(client_ent).unwrap();
+        assert_eq!(inventory.slot(20), None);
+
+        let client = app.world.get::<Client>(client_ent).unwrap();
+        assert_eq!(client.cursor_item, Some(filled));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_should_convert_player_inventory_to_vanilla_nbt() {
+        let sword = ItemStack::new(ItemKind::DiamondSword, 1, None);
+        let helmet = ItemStack::new(ItemKind::IronHelmet, 1, None);
+        let shield = ItemStack::new(ItemKind::Shield, 1, None);
+
+        let mut inventory = Inventory::new(InventoryKind::Player);
+        inventory.replace_slot(36, sword.clone());
+        inventory.replace_slot(HELMET_SLOT, helmet.clone());
+        inventory.replace_slot(OFF_HAND_SLOT, shield.clone());
+        // The crafting grid is not saved.
+        inventory.replace_slot(1, ItemStack::new(ItemKind::Stick, 1, None));
+
+        let List::Compound(items) = inventory.to_nbt() else {
+            panic!("expected a list of compounds");
+        };
+
+        let slots: Vec<_> = items.iter().map(|item| item.get("Slot")).collect();
+        assert_eq!(
+            slots,
+            [
+                Some(&Value::Byte(103)),
+                Some(&Value::Byte(0)),
+                Some(&Value::Byte(-106))
+            ]
+        );
+
+        let read = Inventory::from_nbt(InventoryKind::Player, &List::Compound(items));
+        assert_eq!(read.slot(36), Some(&sword));
+        assert_eq!(read.slot(HELMET_SLOT), Some(&helmet));
+        assert_eq!(read.slot(OFF_HAND_SLOT), Some(&shield));
+        assert_eq!(read.slot(1), None);
+    }
+
+    #[test]
+    fn test_should_equip_shift_clicked_armor() -> anyhow::Result<()> {
+        let mut app = App::new();
+        let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+
+        let helmet = ItemStack::new(ItemKind::IronHelmet, 1, None);
+
+        let mut inventory = app.world.get_mut::<Inventory>(client_ent).unwrap();
+        inventory.replace_slot(20, helmet.clone());
+
+        // Process a tick to get past the "on join" logic.
+        app.update();
+        client_helper.clear_sent();
+
+        let state_id = app
+            .world
+            .get::<Client>(client_ent)
+            .unwrap()
+            .inventory_state_id
+            .0;
+
+        // The client wrongly predicts that the helmet goes to the hotbar.
+        client_helper.send(&valence_protocol::packets::c2s::play::ClickContainer {
+            window_id: 0,
+            state_id: VarInt(state_id),
+            slot_idx: 20,
+            button: 0,
+            mode: ClickContainerMode::ShiftClick,
+            slots: vec![(20, None), (36, Some(helmet.clone()))],
+            carried_item: None,
+        });
+
+        app.update();
+
+        let sent_packets = client_helper.collect_sent()?;
+        assert_packet_count!(sent_packets, 1, S2cPlayPacket::SetContainerContent(_));
+
+        let inventory = app.world.get::<Inventory>(client_ent).unwrap();
+        assert_eq!(inventory.slot(20), None);
+        assert_eq!(inventory.slot(36), None);
+        assert_eq!(inventory.slot(HELMET_SLOT), Some(&helmet));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_should_stack_shift_clicked_items_before_filling_empty_slots() {
+        let mut open = Inventory::new(InventoryKind::Generic9x1);
+        open.replace_slot(4, ItemStack::new(ItemKind::Diamond, 60, None));
+
+        let mut player = Inventory::new(InventoryKind::Player);
+        player.replace_slot(20, ItemStack::new(ItemKind::Diamond, 10, None));
+
+        let mut window = ClickWindow {
+            open: Some(&mut open),
+            player: &mut player,
+            rules: None,
+        };
+
+        // The chest has 9 slots, so slot 20 of the window is slot 20 of the
+        // player inventory.
+        quick_move(&mut window, 20);
+
+        assert_eq!(
+            open.slot(4),
+            Some(&ItemStack::new(ItemKind::Diamond, 64, None))
+        );
+        assert_eq!(
+            open.slot(0),
+            Some(&ItemStack::new(ItemKind::Diamond, 6, None))
+        );
+        assert_eq!(player.slot(20), None);
+    }
+
+    #[test]
+    fn test_should_sync_entire_player_inventory_above_threshold() -> anyhow::Result<()> {
+        let mut app = App::new();
+        let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+
+        // Process a tick to get past the "on join" logic.
+        app.update();
+        client_helper.clear_sent();
+
+        // Modify most of the inventory.
+        let mut inventory = app.world.get_mut::<Inventory>(client_ent).unwrap();
+        for slot in 9..45 {
+            inventory.replace_slot(slot, ItemStack::new(ItemKind::Diamond, 1, None));
+        }
+
+        app.update();
+
+        let sent_packets = client_helper.collect_sent()?;
+        assert_packet_count!(sent_packets, 1, S2cPlayPacket::SetContainerContent(_));
+        assert_packet_count!(sent_packets, 0, S2cPlayPacket::SetContainerSlot(_));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_should_send_player_slots_in_open_inventory() -> anyhow::Result<()> {
+        let mut app = App::new();
+        let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+        set_up_open_inventory(&mut app, client_ent);
+
+        // Process a tick to get past the "on join" logic.
+        app.update();
+        client_helper.clear_sent();
+
+        // Modify a slot of the hotbar and the helmet slot.
+        let mut inventory = app.world.get_mut::<Inventory>(client_ent).unwrap();
+        inventory.replace_slot(36, ItemStack::new(ItemKind::Diamond, 1, None));
+        inventory.replace_slot(HELMET_SLOT, ItemStack::new(ItemKind::IronHelmet, 1, None));
+
+        app.update();
+
+        // Only the hotbar slot is shown in the window, after the 27 slots of the
+        // open inventory and the 27 slots of the main inventory.
+        let sent_packets = client_helper.collect_sent()?;
+        assert_packet_count!(sent_packets, 1, S2cPlayPacket::SetContainerSlot(_));
+        let slot_idx = sent_packets.iter().find_map(|packet| match packet {
+            S2cPlayPacket::SetContainerSlot(packet) => Some(packet.slot_idx),
+            _ => None,
+        });
+        assert_eq!(slot_idx, Some(54));
+
+        // The helmet is sent once the inventory is closed.
+        app.world.entity_mut(client_ent).remove::<OpenInventory>();
+        app.update();
+
+        let sent_packets = client_helper.collect_sent()?;
+        assert_packet_count!(sent_packets, 1, S2cPlayPacket::SetContainerSlot(_));
+
+        Ok(())
+    }
 }
@@ -0,0 +1,257 @@
+//! Allowing and denying connections by IP address.
+//!
+//! An [`IpFilter`] is checked for every new connection before the handshake is
+//! read, so filtered connections cost nothing more than accepting the socket.
+//! A server behind a proxy can use it to only accept connections from the
+//! proxy:
+//!
+//! ```
+//! use valence::ip_filter::{IpFilter, IpRange};
+//!
+//! let filter = IpFilter::new().allow("10.0.0.0/8".parse::<IpRange>().unwrap());
+//!
+//! assert!(filter.is_allowed("10.1.2.3".parse().unwrap()));
+//! assert!(!filter.is_allowed("203.0.113.7".parse().unwrap()));
+//! ```
+//!
+//! [`AsyncCallbacks::filter_connection`] can be used for checks which can not
+//! be expressed as a list of ranges.
+//!
+//! [`AsyncCallbacks::filter_connection`]: crate::config::AsyncCallbacks::filter_connection
+
+use std::fmt;
+use std::net::{IpAddr, Ipv6Addr};
+use std::str::FromStr;
+
+use thiserror::Error;
+
+/// A range of IP addresses in CIDR notation, such as `192.168.0.0/16`.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub struct IpRange {
+    addr: IpAddr,
+    prefix_len: u8,
+}
+
+impl IpRange {
+    /// Creates a range of the addresses which share the first `prefix_len`
+    /// bits with `addr`. Returns `None` if the prefix length is longer than
+    /// the address.
+    pub fn new(addr: IpAddr, prefix_len: u8) -> Option<Self> {
+        let max_len = match addr {
+            IpAddr::V4(_) => 32,
+            IpAddr::V6(_) => 128,
+        };
+
+        if prefix_len > max_len {
+            return None;
+        }
+
+        // Ranges of IPv4-mapped addresses are stored as IPv4 ranges.
+        if let IpAddr::V6(v6) = addr {
+            if let (Some(v4), true) = (v6.to_ipv4_mapped(), prefix_len >= 96) {
+                return Some(Self {
+                    addr: v4.into(),
+                    prefix_len: prefix_len - 96,
+                });
+            }
+        }
+
+        Some(Self { addr, prefix_len })
+    }
+
+    /// Creates a range containing only the given address.
+    pub fn single(addr: IpAddr) -> Self {
+        let addr = canonical(addr);
+
+        Self {
+            addr,
+            prefix_len: match addr {
+                IpAddr::V4(_) => 32,
+                IpAddr::V6(_) => 128,
+            },
+        }
+    }
+
+    pub fn addr(&self) -> IpAddr {
+        self.addr
+    }
+
+    pub fn prefix_len(&self) -> u8 {
+        self.prefix_len
+    }
+
+    /// Returns `true` if the address is in this range. IPv4 addresses mapped
+    /// to IPv6 addresses are treated as IPv4 addresses.
+    pub fn contains(&self, addr: IpAddr) -> bool {
+        match (self.addr, canonical(addr)) {
+            (IpAddr::V4(range), IpAddr::V4(addr)) => prefix_eq(
+                u32::from(range).into(),
+                u32::from(addr).into(),
+                32,
+                self.prefix_len,
+            ),
+            (IpAddr::V6(range), IpAddr::V6(addr)) => {
+                prefix_eq(range.into(), addr.into(), 128, self.prefix_len)
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Returns `true` if the first `prefix_len` bits of the `bits` long numbers
+/// are equal.
+fn prefix_eq(a: u128, b: u128, bits: u8, prefix_len: u8) -> bool {
+    if prefix_len == 0 {
+        return true;
+    }
+
+    let shift = bits - prefix_len;
+    a >> shift == b >> shift
+}
+
+fn canonical(addr: IpAddr) -> IpAddr {
+    match addr {
+        IpAddr::V6(v6) => v6.to_ipv4_mapped().map_or(IpAddr::V6(v6), IpAddr::V4),
+        v4 => v4,
+    }
+}
+
+impl From<IpAddr> for IpRange {
+    fn from(addr: IpAddr) -> Self {
+        Self::single(addr)
+    }
+}
+
+impl fmt::Display for IpRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.addr, self.prefix_len)
+    }
+}
+
+/// An error returned when parsing an [`IpRange`] fails.
+#[derive(Clone, PartialEq, Eq, Debug, Error)]
+#[error("invalid IP range \"{0}\"")]
+pub struct ParseIpRangeError(String);
+
+impl FromStr for IpRange {
+    type Err = ParseIpRangeError;
+
+    /// Parses a range in CIDR notation or a single address.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = || ParseIpRangeError(s.to_owned());
+
+        match s.split_once('/') {
+            Some((addr, prefix_len)) => Self::new(
+                addr.parse().map_err(|_| err())?,
+                prefix_len.parse().map_err(|_| err())?,
+            )
+            .ok_or_else(err),
+            None => Ok(Self::single(s.parse().map_err(|_| err())?)),
+        }
+    }
+}
+
+/// Lists of IP ranges connections are allowed or denied from.
+///
+/// An address is allowed if it is not in any denied range, and either the
+/// list of allowed ranges is empty or the address is in one of them.
+#[derive(Clone, PartialEq, Eq, Default, Debug)]
+pub struct IpFilter {
+    allowed: Vec<IpRange>,
+    denied: Vec<IpRange>,
+}
+
+impl IpFilter {
+    /// Creates a filter which allows every address.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Only allows addresses in the given range and the other allowed ranges.
+    #[must_use]
+    pub fn allow(mut self, range: impl Into<IpRange>) -> Self {
+        self.allowed.push(range.into());
+        self
+    }
+
+    /// Denies addresses in the given range, even if they are allowed.
+    #[must_use]
+    pub fn deny(mut self, range: impl Into<IpRange>) -> Self {
+        self.denied.push(range.into());
+        self
+    }
+
+    /// Only allows connections from the loopback addresses, which is useful
+    /// when the server is behind a proxy on the same machine.
+    #[must_use]
+    pub fn allow_loopback(self) -> Self {
+        self.allow(IpRange::new([127, 0, 0, 0].into(), 8).unwrap())
+            .allow(IpRange::single(Ipv6Addr::LOCALHOST.into()))
+    }
+
+    pub fn allowed(&self) -> &[IpRange] {
+        &self.allowed
+    }
+
+    pub fn denied(&self) -> &[IpRange] {
+        &self.denied
+    }
+
+    /// Returns `true` if connections from the address are allowed.
+    pub fn is_allowed(&self, addr: IpAddr) -> bool {
+        !self.denied.iter().any(|r| r.contains(addr))
+            && (self.allowed.is_empty() || self.allowed.iter().any(|r| r.contains(addr)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ip(s: &str) -> IpAddr {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn ranges() {
+        let range: IpRange = "192.168.0.0/16".parse().unwrap();
+        assert!(range.contains(ip("192.168.4.2")));
+        assert!(range.contains(ip("::ffff:192.168.4.2")));
+        assert!(!range.contains(ip("192.169.0.1")));
+        assert!(!range.contains(ip("fe80::1")));
+
+        let range: IpRange = "2001:db8::/32".parse().unwrap();
+        assert!(range.contains(ip("2001:db8::1")));
+        assert!(!range.contains(ip("2001:db9::1")));
+
+        assert!("0.0.0.0/0"
+            .parse::<IpRange>()
+            .unwrap()
+            .contains(ip("1.2.3.4")));
+        assert_eq!("10.0.0.1".parse::<IpRange>().unwrap().prefix_len(), 32);
+        assert_eq!(
+            "::ffff:10.0.0.0/104".parse::<IpRange>().unwrap(),
+            "10.0.0.0/8".parse().unwrap()
+        );
+        assert!("10.0.0.0/33".parse::<IpRange>().is_err());
+        assert!("10.0.0/8".parse::<IpRange>().is_err());
+    }
+
+    #[test]
+    fn deny_overrides_allow() {
+        let filter = IpFilter::new()
+            .allow("10.0.0.0/8".parse::<IpRange>().unwrap())
+            .deny(ip("10.0.0.13"));
+
+        assert!(filter.is_allowed(ip("10.0.0.12")));
+        assert!(!filter.is_allowed(ip("10.0.0.13")));
+        assert!(!filter.is_allowed(ip("11.0.0.1")));
+
+        let filter = IpFilter::new().allow_loopback();
+        assert!(filter.is_allowed(ip("127.0.0.1")));
+        assert!(filter.is_allowed(ip("::1")));
+        assert!(!filter.is_allowed(ip("192.168.0.2")));
+
+        assert!(IpFilter::new().is_allowed(ip("203.0.113.7")));
+    }
+}
@@ -0,0 +1,338 @@
+//! Building item stacks with NBT data.
+//!
+//! The NBT of items holds their custom name, lore, enchantments and more.
+//! [`ItemBuilder`] writes these tags in the format clients expect, so they
+//! don't have to be written by hand:
+//!
+//! ```
+//! use valence::item::ItemBuilder;
+//! use valence::prelude::*;
+//! use valence::protocol::enchant::EnchantmentKind;
+//!
+//! let sword = ItemBuilder::new(ItemKind::DiamondSword)
+//!     .with_name("Excalibur".color(Color::GOLD))
+//!     .with_lore_line("Pulled from the stone")
+//!     .with_enchantment(EnchantmentKind::Sharpness, 5)
+//!     .with_unbreakable(true)
+//!     .build();
+//! # let _ = sword;
+//! ```
+
+use uuid::Uuid;
+use valence_nbt::{compound, Compound, List, Value};
+use valence_protocol::enchant::EnchantmentKind;
+use valence_protocol::{ItemKind, ItemStack, Text};
+
+use crate::enchantment::set_enchantment;
+use crate::entity::equipment::EquipmentSlot;
+use crate::player_head::HeadOwner;
+
+/// How the amount of an [`AttributeModifier`] changes the value of the
+/// attribute.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum AttributeOperation {
+    /// Adds the amount to the base value.
+    Add,
+    /// Multiplies the base value by one plus the amount.
+    MultiplyBase,
+    /// Multiplies the value after the other modifiers by one plus the amount.
+    MultiplyTotal,
+}
+
+/// A change of an attribute of the entity holding or wearing an item.
+#[derive(Clone, PartialEq, Debug)]
+pub struct AttributeModifier {
+    /// The resource location of the attribute, such as
+    /// `minecraft:generic.attack_damage`.
+    pub attribute: String,
+    pub amount: f64,
+    pub operation: AttributeOperation,
+    /// The slot the item must be in for the modifier to apply, or `None` for
+    /// every slot. [`EquipmentSlot::Body`] can't be used by items and is the
+    /// same as `None`.
+    pub slot: Option<EquipmentSlot>,
+    /// Identifies the modifier. Modifiers of the same attribute with the same
+    /// UUID don't stack.
+    pub uuid: Uuid,
+}
+
+impl AttributeModifier {
+    /// Creates a modifier with a random UUID which applies in every slot.
+    pub fn new(attribute: impl Into<String>, amount: f64, operation: AttributeOperation) -> Self {
+        Self {
+            attribute: attribute.into(),
+            amount,
+            operation,
+            slot: None,
+            uuid: Uuid::from_u128(rand::random()),
+        }
+    }
+
+    #[must_use]
+    pub fn with_slot(mut self, slot: EquipmentSlot) -> Self {
+        self.slot = Some(slot);
+        self
+    }
+
+    #[must_use]
+    pub fn with_uuid(mut self, uuid: Uuid) -> Self {
+        self.uuid = uuid;
+        self
+    }
+
+    /// Converts the modifier into an entry of the `AttributeModifiers` list.
+    pub fn to_nbt(&self) -> Compound {
+        let operation = match self.operation {
+            AttributeOperation::Add => 0,
+            AttributeOperation::MultiplyBase => 1,
+            AttributeOperation::MultiplyTotal => 2,
+        };
+
+        let mut nbt = compound! {
+            "AttributeName" => self.attribute.clone(),
+            "Name" => self.attribute.clone(),
+            "Amount" => self.amount,
+            "Operation" => operation,
+            "UUID" => self.uuid,
+        };
+
+        let slot = match self.slot {
+            Some(EquipmentSlot::MainHand) => Some("mainhand"),
+            Some(EquipmentSlot::OffHand) => Some("offhand"),
+            Some(EquipmentSlot::Feet) => Some("feet"),
+            Some(EquipmentSlot::Legs) => Some("legs"),
+            Some(EquipmentSlot::Chest) => Some("chest"),
+            Some(EquipmentSlot::Head) => Some("head"),
+            Some(EquipmentSlot::Body) | None => None,
+        };
+
+        if let Some(slot) = slot {
+            nbt.insert("Slot", slot);
+        }
+
+        nbt
+    }
+}
+
+/// Builds an [`ItemStack`] and its NBT data.
+#[derive(Clone, PartialEq, Debug)]
+pub struct ItemBuilder {
+    stack: ItemStack,
+}
+
+impl ItemBuilder {
+    /// Creates a builder of a single item without NBT data.
+    pub fn new(item: ItemKind) -> Self {
+        Self {
+            stack: ItemStack::new(item, 1, None),
+        }
+    }
+
+    #[must_use]
+    pub fn with_count(mut self, count: u8) -> Self {
+        self.stack.set_count(count);
+        self
+    }
+
+    /// Sets the custom name of the item. Custom names are italic unless the
+    /// text sets otherwise.
+    #[must_use]
+    pub fn with_name(mut self, name: impl Into<Text>) -> Self {
+        let name = to_json(&name.into());
+        self.display().insert("Name", name);
+        self
+    }
+
+    /// Replaces the lines of text displayed below the name of the item.
+    #[must_use]
+    pub fn with_lore<I>(mut self, lines: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Into<Text>,
+    {
+        let lines = lines.into_iter().map(|line| to_json(&line.into()));
+        self.display().insert("Lore", List::String(lines.collect()));
+        self
+    }
+
+    /// Adds a line of text below the name of the item.
+    #[must_use]
+    pub fn with_lore_line(mut self, line: impl Into<Text>) -> Self {
+        let line = to_json(&line.into());
+        let display = self.display();
+
+        match display.get_mut("Lore") {
+            Some(Value::List(List::String(lines))) => lines.push(line),
+            _ => {
+                display.insert("Lore", List::String(vec![line]));
+            }
+        }
+
+        self
+    }
+
+    /// Sets the level of an enchantment, replacing its current level.
+    /// Enchanted books store the enchantment instead.
+    #[must_use]
+    pub fn with_enchantment(mut self, kind: EnchantmentKind, level: i16) -> Self {
+        set_enchantment(&mut self.stack, kind, level);
+        self
+    }
+
+    #[must_use]
+    pub fn with_attribute_modifier(mut self, modifier: AttributeModifier) -> Self {
+        let modifier = modifier.to_nbt();
+        let nbt = self.nbt();
+
+        match nbt.get_mut("AttributeModifiers") {
+            Some(Value::List(List::Compound(modifiers))) => modifiers.push(modifier),
+            _ => {
+                nbt.insert("AttributeModifiers", List::Compound(vec![modifier]));
+            }
+        }
+
+        self
+    }
+
+    /// Makes the item lose no durability when it is used.
+    #[must_use]
+    pub fn with_unbreakable(mut self, unbreakable: bool) -> Self {
+        if unbreakable {
+            self.nbt().insert("Unbreakable", true);
+        } else {
+            self.remove("Unbreakable");
+        }
+        self
+    }
+
+    /// Sets the number resource packs use to give the item another model.
+    #[must_use]
+    pub fn with_custom_model_data(mut self, data: i32) -> Self {
+        self.nbt().insert("CustomModelData", data);
+        self
+    }
+
+    /// Sets the player a player head belongs to, which includes the skin
+    /// displayed on the head.
+    #[must_use]
+    pub fn with_skull_owner(mut self, owner: &HeadOwner) -> Self {
+        self.nbt().insert("SkullOwner", owner.to_nbt());
+        self
+    }
+
+    /// Sets a tag of the NBT data which has no method of its own.
+    #[must_use]
+    pub fn with_tag(mut self, key: impl Into<String>, value: impl Into<Value>) -> Self {
+        self.nbt().insert(key, value);
+        self
+    }
+
+    pub fn build(self) -> ItemStack {
+        self.stack
+    }
+
+    fn nbt(&mut self) -> &mut Compound {
+        self.stack.nbt.get_or_insert_with(Compound::new)
+    }
+
+    fn display(&mut self) -> &mut Compound {
+        let nbt = self.nbt();
+
+        if !matches!(nbt.get("display"), Some(Value::Compound(_))) {
+            nbt.insert("display", Compound::new());
+        }
+
+        match nbt.get_mut("display") {
+            Some(Value::Compound(display)) => display,
+            _ => unreachable!(),
+        }
+    }
+
+    fn remove(&mut self, key: &str) {
+        if let Some(nbt) = &mut self.stack.nbt {
+            nbt.remove(key);
+
+            if nbt.is_empty() {
+                self.stack.nbt = None;
+            }
+        }
+    }
+}
+
+/// Continues building an existing stack, keeping its NBT data.
+impl From<ItemStack> for ItemBuilder {
+    fn from(stack: ItemStack) -> Self {
+        Self { stack }
+    }
+}
+
+impl From<ItemBuilder> for ItemStack {
+    fn from(builder: ItemBuilder) -> Self {
+        builder.build()
+    }
+}
+
+fn to_json(text: &Text) -> String {
+    serde_json::to_string(text).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::enchantment::enchantment_level;
+
+    #[test]
+    fn builder_writes_nbt() {
+        let modifier = AttributeModifier::new(
+            "minecraft:generic.attack_damage",
+            4.0,
+            AttributeOperation::Add,
+        )
+        .with_slot(EquipmentSlot::MainHand)
+        .with_uuid(Uuid::from_u128(1));
+
+        let stack = ItemBuilder::new(ItemKind::IronSword)
+            .with_name("Sword")
+            .with_lore(["First"])
+            .with_lore_line("Second")
+            .with_enchantment(EnchantmentKind::Sharpness, 2)
+            .with_attribute_modifier(modifier)
+            .with_custom_model_data(7)
+            .build();
+
+        assert_eq!(enchantment_level(&stack, EnchantmentKind::Sharpness), 2);
+
+        let nbt = stack.nbt.unwrap();
+        let Some(Value::Compound(display)) = nbt.get("display") else {
+            panic!("missing display compound");
+        };
+
+        assert_eq!(
+            display.get("Name"),
+            Some(&Value::String(to_json(&"Sword".into())))
+        );
+        assert!(matches!(
+            display.get("Lore"),
+            Some(Value::List(List::String(lines))) if lines.len() == 2
+        ));
+        assert_eq!(nbt.get("CustomModelData"), Some(&Value::Int(7)));
+
+        let Some(Value::List(List::Compound(modifiers))) = nbt.get("AttributeModifiers") else {
+            panic!("missing attribute modifiers");
+        };
+
+        assert_eq!(
+            modifiers[0].get("Slot"),
+            Some(&Value::String("mainhand".into()))
+        );
+        assert_eq!(modifiers[0].get("Amount"), Some(&Value::Double(4.0)));
+
+        // Removing the last tag removes the NBT data.
+        let stack = ItemBuilder::new(ItemKind::Stick)
+            .with_unbreakable(true)
+            .with_unbreakable(false)
+            .build();
+
+        assert_eq!(stack.nbt, None);
+    }
+}
@@ -0,0 +1,136 @@
+//! Cooldowns of items, such as ender pearls and shields.
+//!
+//! The [`ItemCooldowns`] component tracks the cooldowns of the items of the
+//! client on the same entity. Setting a cooldown shows the cooldown overlay
+//! on the item to the client, and gameplay code can check the cooldown before
+//! letting the client use the item:
+//!
+//! ```
+//! use valence::item_cooldown::ItemCooldowns;
+//! use valence::protocol::ItemKind;
+//!
+//! let mut cooldowns = ItemCooldowns::new();
+//!
+//! if !cooldowns.is_cooling_down(ItemKind::EnderPearl) {
+//!     // Throw the ender pearl.
+//!     cooldowns.set(ItemKind::EnderPearl, 20);
+//! }
+//! ```
+
+use std::collections::HashMap;
+
+use bevy_ecs::prelude::*;
+use valence_protocol::ItemKind;
+
+use crate::client::Client;
+
+/// A [`Component`] with the item cooldowns of the [`Client`] on the same
+/// entity.
+#[derive(Component, Clone, PartialEq, Eq, Default, Debug)]
+pub struct ItemCooldowns {
+    remaining: HashMap<ItemKind, u32>,
+    /// The items whose cooldown changed since it was last sent to the client.
+    modified: Vec<ItemKind>,
+}
+
+impl ItemCooldowns {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the cooldown of the item to the given number of ticks, replacing
+    /// its current cooldown. Zero ticks removes the cooldown.
+    pub fn set(&mut self, item: ItemKind, ticks: u32) {
+        if ticks == 0 {
+            if self.remaining.remove(&item).is_none() {
+                return;
+            }
+        } else {
+            self.remaining.insert(item, ticks);
+        }
+
+        if !self.modified.contains(&item) {
+            self.modified.push(item);
+        }
+    }
+
+    /// Removes the cooldown of the item.
+    pub fn clear(&mut self, item: ItemKind) {
+        self.set(item, 0);
+    }
+
+    /// Returns the number of ticks left until the item can be used again, or
+    /// zero if it can be used.
+    pub fn remaining(&self, item: ItemKind) -> u32 {
+        self.remaining.get(&item).copied().unwrap_or(0)
+    }
+
+    pub fn is_cooling_down(&self, item: ItemKind) -> bool {
+        self.remaining(item) > 0
+    }
+
+    /// Returns the items which are cooling down and their remaining ticks.
+    pub fn iter(&self) -> impl Iterator<Item = (ItemKind, u32)> + '_ {
+        self.remaining.iter().map(|(&item, &ticks)| (item, ticks))
+    }
+}
+
+/// Sends changed cooldowns to clients and counts down the cooldowns.
+pub(crate) fn update_item_cooldowns(mut clients: Query<(&mut Client, &mut ItemCooldowns)>) {
+    for (mut client, mut cooldowns) in &mut clients {
+        if cooldowns.remaining.is_empty() && cooldowns.modified.is_empty() {
+            continue;
+        }
+
+        let cooldowns = cooldowns.as_mut();
+
+        for item in cooldowns.modified.drain(..) {
+            let ticks = cooldowns.remaining.get(&item).copied().unwrap_or(0);
+            client.set_item_cooldown(item, ticks);
+        }
+
+        cooldowns.remaining.retain(|_, ticks| {
+            *ticks -= 1;
+            *ticks > 0
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy_app::App;
+    use valence_protocol::packets::S2cPlayPacket;
+
+    use super::*;
+    use crate::assert_packet_count;
+    use crate::unit_test::util::scenario_single_client;
+
+    #[test]
+    fn cooldowns_are_sent_and_count_down() -> anyhow::Result<()> {
+        let mut app = App::new();
+        let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+
+        let mut cooldowns = ItemCooldowns::new();
+        cooldowns.set(ItemKind::EnderPearl, 2);
+        app.world.entity_mut(client_ent).insert(cooldowns);
+
+        app.update();
+
+        let sent_packets = client_helper.collect_sent()?;
+        assert_packet_count!(sent_packets, 1, S2cPlayPacket::SetCooldown(_));
+
+        let cooldowns = app.world.get::<ItemCooldowns>(client_ent).unwrap();
+        assert_eq!(cooldowns.remaining(ItemKind::EnderPearl), 1);
+
+        app.update();
+
+        let cooldowns = app.world.get::<ItemCooldowns>(client_ent).unwrap();
+        assert!(!cooldowns.is_cooling_down(ItemKind::EnderPearl));
+
+        // The client counts down its cooldown on its own.
+        let sent_packets = client_helper.collect_sent()?;
+        assert_packet_count!(sent_packets, 0, S2cPlayPacket::SetCooldown(_));
+
+        Ok(())
+    }
+}
@@ -31,21 +31,69 @@ pub use {
     anyhow, async_trait, bevy_app, bevy_ecs, uuid, valence_nbt as nbt, valence_protocol as protocol,
 };
 
+pub mod banner;
 pub mod biome;
+pub mod budget;
+pub mod bundle;
+pub mod chat;
 pub mod client;
+pub mod command;
 pub mod config;
+pub mod crafting;
+pub mod creative;
+pub mod custom_item;
+pub mod cutscene;
+pub mod damage;
 pub mod dimension;
+pub mod durability;
+pub mod effect;
+pub mod enchantment;
+pub mod ender;
+pub mod ender_chest;
 pub mod entity;
+pub mod explosion;
+pub mod fall;
+pub mod fire;
+pub mod function;
+pub mod game_rules;
+pub mod hand_swing;
+pub mod hibernation;
+pub mod hud;
 pub mod instance;
 pub mod inventory;
+pub mod ip_filter;
+pub mod item;
+pub mod item_cooldown;
 pub mod math;
+pub mod menu;
+pub mod music;
+pub mod nickname;
+pub mod note_block;
 mod packet;
+pub mod parkour;
+pub mod pick_block;
+pub mod player_head;
 pub mod player_list;
 pub mod player_textures;
+pub mod random;
 pub mod server;
+pub mod shared_chunks;
+pub mod swap_hands;
+pub mod target;
+pub mod template;
+pub mod tick_rate;
+pub mod tick_stats;
+pub mod trading;
+pub mod trigger;
 #[cfg(any(test, doctest))]
 mod unit_test;
 pub mod view;
+pub mod void;
+pub mod water;
+pub mod weather;
+pub mod workstation;
+pub mod world_border;
+pub mod worldgen;
 
 pub mod prelude {
     pub use async_trait::async_trait;
@@ -54,7 +102,8 @@ pub mod prelude {
     pub use biome::{Biome, BiomeId};
     pub use client::Client;
     pub use config::{
-        AsyncCallbacks, ConnectionMode, PlayerSampleEntry, ServerListPing, ServerPlugin,
+        AsyncCallbacks, ConnectionMode, OfflineUuid, PlayerSampleEntry, ServerListPing,
+        ServerPlugin,
     };
     pub use dimension::{Dimension, DimensionId};
     pub use entity::{
@@ -63,6 +112,7 @@ pub mod prelude {
     pub use glam::DVec3;
     pub use instance::{Block, BlockMut, BlockRef, Chunk, Instance};
     pub use inventory::{Inventory, InventoryKind, OpenInventory};
+    pub use menu::{Menu, MenuType};
     pub use player_list::{PlayerList, PlayerListEntry};
     pub use protocol::block::{BlockState, PropName, PropValue};
     pub use protocol::ident::Ident;
@@ -70,7 +120,7 @@ pub mod prelude {
     pub use protocol::types::GameMode;
     pub use protocol::username::Username;
     pub use protocol::{ident, ItemKind, ItemStack};
-    pub use server::{EventLoop, NewClientInfo, Server, SharedServer};
+    pub use server::{EventLoop, NewClientInfo, Server, SharedServer, SpawnPoint};
     pub use uuid::Uuid;
     pub use valence_nbt::Compound;
     pub use valence_protocol::{BlockKind, BlockPos};
@@ -18,6 +18,60 @@ impl Aabb {
         }
     }
 
+    /// Returns if this box and `other` overlap. Boxes which only touch do
+    /// not overlap.
+    pub fn intersects(&self, other: &Aabb) -> bool {
+        self.min.cmplt(other.max).all() && self.max.cmpgt(other.min).all()
+    }
+
+    /// Returns the distance along the ray from `origin` in the normalized
+    /// `direction` to the point where it enters this box, and the unit vector
+    /// pointing away from the face it enters through. A ray starting inside
+    /// the box hits it at a distance of zero with a zero normal.
+    pub fn ray_intersection(&self, origin: DVec3, direction: DVec3) -> Option<(f64, DVec3)> {
+        let mut entry = f64::NEG_INFINITY;
+        let mut exit = f64::INFINITY;
+        let mut normal = DVec3::ZERO;
+
+        for axis in 0..3 {
+            let d = direction[axis];
+
+            if d == 0.0 {
+                if origin[axis] < self.min[axis] || origin[axis] > self.max[axis] {
+                    return None;
+                }
+
+                continue;
+            }
+
+            let (near, far) = if d > 0.0 {
+                (self.min[axis], self.max[axis])
+            } else {
+                (self.max[axis], self.min[axis])
+            };
+
+            let axis_entry = (near - origin[axis]) / d;
+
+            if axis_entry > entry {
+                entry = axis_entry;
+                normal = DVec3::ZERO;
+                normal[axis] = -d.signum();
+            }
+
+            exit = exit.min((far - origin[axis]) / d);
+        }
+
+        if entry > exit || exit < 0.0 {
+            return None;
+        }
+
+        if entry < 0.0 {
+            return Some((0.0, DVec3::ZERO));
+        }
+
+        Some((entry, normal))
+    }
+
     pub(crate) fn from_bottom_size(bottom: impl Into<DVec3>, size: impl Into<DVec3>) -> Self {
         let bottom = bottom.into();
         let size = size.into();
@@ -62,6 +116,18 @@ pub fn from_yaw_and_pitch(yaw: f32, pitch: f32) -> Vec3 {
     Vec3::new(yaw_cos * pitch_cos, pitch_sin, yaw_sin * pitch_cos)
 }
 
+/// Returns the `(yaw, pitch)` in degrees of something at `from` looking at
+/// `to`, or `None` if the positions are the same.
+pub fn yaw_and_pitch_toward(from: DVec3, to: DVec3) -> Option<(f32, f32)> {
+    let d = (to - from).as_vec3();
+
+    if d.length_squared() < f32::EPSILON {
+        return None;
+    }
+
+    Some(to_yaw_and_pitch(d.normalize()))
+}
+
 /// Returns the minimum number of bits needed to represent the integer `n`.
 pub(crate) const fn bit_width(n: usize) -> usize {
     (usize::BITS - n.leading_zeros()) as _
@@ -85,4 +151,39 @@ mod tests {
             assert_relative_eq!(d, d_new, epsilon = f32::EPSILON * 100.0);
         }
     }
+
+    #[test]
+    fn rays_hit_boxes() {
+        let aabb = Aabb::new([1.0, 0.0, 0.0], [2.0, 1.0, 1.0]);
+
+        assert_eq!(
+            aabb.ray_intersection(DVec3::new(0.0, 0.5, 0.5), DVec3::X),
+            Some((1.0, DVec3::NEG_X))
+        );
+        assert_eq!(
+            aabb.ray_intersection(DVec3::new(1.5, 0.5, 0.5), DVec3::X),
+            Some((0.0, DVec3::ZERO))
+        );
+        assert_eq!(
+            aabb.ray_intersection(DVec3::new(0.0, 0.5, 0.5), DVec3::NEG_X),
+            None
+        );
+        assert_eq!(
+            aabb.ray_intersection(DVec3::new(0.0, 2.0, 0.5), DVec3::X),
+            None
+        );
+    }
+
+    #[test]
+    fn yaw_pitch_toward_positions() {
+        let (yaw, pitch) = yaw_and_pitch_toward(DVec3::ZERO, DVec3::new(0.0, 0.0, 10.0)).unwrap();
+        assert_relative_eq!(yaw, 0.0);
+        assert_relative_eq!(pitch, 0.0);
+
+        let (yaw, pitch) = yaw_and_pitch_toward(DVec3::ZERO, DVec3::new(-5.0, 5.0, 0.0)).unwrap();
+        assert_relative_eq!(yaw, 90.0);
+        assert_relative_eq!(pitch, -45.0, epsilon = 1e-4);
+
+        assert_eq!(yaw_and_pitch_toward(DVec3::ONE, DVec3::ONE), None);
+    }
 }
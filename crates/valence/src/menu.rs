@@ -0,0 +1,346 @@
+//! Server-side GUIs shown in container screens.
+//!
+//! A [`Menu`] is a container screen, such as a chest, filled with items that
+//! act as buttons. Menus are opened with [`Client::open_menu`]:
+//!
+//! ```
+//! use valence::menu::{Menu, MenuType};
+//! use valence::protocol::{ItemKind, ItemStack};
+//!
+//! let menu = Menu::new(MenuType::Chest { rows: 1 }, "Warps")
+//!     .with_item(4, ItemStack::new(ItemKind::Compass, 1, None))
+//!     .with_click_handler(|click, _| {
+//!         if click.slot == 4 {
+//!             // Teleport the player.
+//!         }
+//!     });
+//! # let _ = menu;
+//! ```
+//!
+//! Clicks never move the items of a menu. Instead, the click handler of the
+//! menu is run and a [`MenuClick`] event is sent, and the client is sent the
+//! contents of the menu again to undo the click on its side. The menu is
+//! despawned when it is closed, which sends a [`MenuClosed`] event.
+
+use std::fmt;
+
+use bevy_ecs::prelude::*;
+use valence_protocol::types::ClickContainerMode;
+use valence_protocol::{ItemStack, Text};
+
+use crate::client::event::ClickContainer;
+use crate::client::Client;
+use crate::inventory::{Inventory, InventoryKind, OpenInventory};
+
+/// The container screens menus can be shown in.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum MenuType {
+    /// A chest with 1 to 6 rows of 9 slots.
+    Chest { rows: u8 },
+    /// A hopper with 5 slots.
+    Hopper,
+    /// A dispenser or dropper with 3 by 3 slots.
+    Dispenser,
+}
+
+impl MenuType {
+    /// The kind of the inventory of the menu. The number of rows of chests is
+    /// clamped to 1 to 6.
+    pub fn inventory_kind(self) -> InventoryKind {
+        match self {
+            MenuType::Chest { rows: 0..=1 } => InventoryKind::Generic9x1,
+            MenuType::Chest { rows: 2 } => InventoryKind::Generic9x2,
+            MenuType::Chest { rows: 3 } => InventoryKind::Generic9x3,
+            MenuType::Chest { rows: 4 } => InventoryKind::Generic9x4,
+            MenuType::Chest { rows: 5 } => InventoryKind::Generic9x5,
+            MenuType::Chest { .. } => InventoryKind::Generic9x6,
+            MenuType::Hopper => InventoryKind::Hopper,
+            MenuType::Dispenser => InventoryKind::Generic3x3,
+        }
+    }
+
+    pub fn slot_count(self) -> u16 {
+        self.inventory_kind().slot_count() as u16
+    }
+}
+
+type ClickHandler = Box<dyn Fn(&MenuClick, &mut Inventory) + Send + Sync>;
+
+/// A menu which can be opened for a client with [`Client::open_menu`].
+pub struct Menu {
+    inventory: Inventory,
+    on_click: Option<ClickHandler>,
+}
+
+impl Menu {
+    pub fn new(menu_type: MenuType, title: impl Into<Text>) -> Self {
+        Self {
+            inventory: Inventory::with_title(menu_type.inventory_kind(), title),
+            on_click: None,
+        }
+    }
+
+    /// Puts the item in the slot of the menu.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the slot is out of range.
+    #[track_caller]
+    #[must_use]
+    pub fn with_item(mut self, slot: u16, item: ItemStack) -> Self {
+        self.inventory.replace_slot(slot, item);
+        self
+    }
+
+    /// Sets the function run when the client clicks a slot of the menu. The
+    /// function is given the click and the inventory of the menu, which it can
+    /// modify to update the menu.
+    #[must_use]
+    pub fn with_click_handler(
+        mut self,
+        handler: impl Fn(&MenuClick, &mut Inventory) + Send + Sync + 'static,
+    ) -> Self {
+        self.on_click = Some(Box::new(handler));
+        self
+    }
+
+    pub fn inventory(&self) -> &Inventory {
+        &self.inventory
+    }
+}
+
+impl fmt::Debug for Menu {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Menu")
+            .field("inventory", &self.inventory)
+            .field("on_click", &self.on_click.is_some())
+            .finish()
+    }
+}
+
+/// A [`Component`] on the entity of an open menu, next to the [`Inventory`]
+/// of the menu.
+#[derive(Component)]
+pub struct OpenMenu {
+    viewer: Entity,
+    on_click: Option<ClickHandler>,
+}
+
+impl OpenMenu {
+    /// The client the menu is open for.
+    pub fn viewer(&self) -> Entity {
+        self.viewer
+    }
+}
+
+/// An event sent when a client clicks a slot of a menu.
+#[derive(Clone, Debug)]
+pub struct MenuClick {
+    pub client: Entity,
+    /// The entity of the menu.
+    pub menu: Entity,
+    pub slot: u16,
+    pub button: i8,
+    pub mode: ClickContainerMode,
+}
+
+/// An event sent when a menu is closed, either by the client or because
+/// another menu was opened. The entity of the menu is despawned afterwards.
+#[derive(Clone, Debug)]
+pub struct MenuClosed {
+    pub client: Entity,
+    pub menu: Entity,
+}
+
+/// Opens the menus from [`Client::open_menu`]. A menu opened while another
+/// inventory is open is opened on the next tick, after the other inventory
+/// was closed.
+pub(crate) fn open_pending_menus(
+    mut commands: Commands,
+    mut clients: Query<(Entity, &mut Client, Option<&OpenInventory>)>,
+) {
+    for (entity, mut client, open_inventory) in &mut clients {
+        if client.pending_menu.is_none() {
+            continue;
+        }
+
+        if open_inventory.is_some() {
+            commands.entity(entity).remove::<OpenInventory>();
+            continue;
+        }
+
+        let Some(menu) = client.pending_menu.take() else {
+            continue;
+        };
+
+        let menu_entity = commands
+            .spawn((
+                menu.inventory,
+                OpenMenu {
+                    viewer: entity,
+                    on_click: menu.on_click,
+                },
+            ))
+            .id();
+
+        commands
+            .entity(entity)
+            .insert(OpenInventory::new(menu_entity));
+    }
+}
+
+pub(crate) fn handle_menu_clicks(
+    mut events: EventReader<ClickContainer>,
+    clients: Query<&OpenInventory, With<Client>>,
+    mut menus: Query<(&mut Inventory, &OpenMenu), Without<Client>>,
+    mut clicks: EventWriter<MenuClick>,
+) {
+    for event in events.iter() {
+        let Ok(open_inventory) = clients.get(event.client) else {
+            continue;
+        };
+
+        let Ok((mut inventory, menu)) = menus.get_mut(open_inventory.entity()) else {
+            continue;
+        };
+
+        // Clicks outside of the menu and in the inventory of the player are
+        // not reported.
+        if !(0..inventory.slot_count() as i16).contains(&event.slot_id) {
+            continue;
+        }
+
+        let click = MenuClick {
+            client: event.client,
+            menu: open_inventory.entity(),
+            slot: event.slot_id as u16,
+            button: event.button,
+            mode: event.mode,
+        };
+
+        if let Some(on_click) = &menu.on_click {
+            on_click(&click, &mut inventory);
+        }
+
+        clicks.send(click);
+    }
+}
+
+/// Despawns menus which are no longer open for their client.
+pub(crate) fn close_menus(
+    mut commands: Commands,
+    menus: Query<(Entity, &OpenMenu)>,
+    clients: Query<&OpenInventory>,
+    mut closed: EventWriter<MenuClosed>,
+) {
+    for (entity, menu) in &menus {
+        let is_open = clients
+            .get(menu.viewer)
+            .map_or(false, |open| open.entity() == entity);
+
+        if !is_open {
+            closed.send(MenuClosed {
+                client: menu.viewer,
+                menu: entity,
+            });
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    use bevy_app::App;
+    use valence_protocol::packets::c2s::play::{
+        ClickContainer as ClickContainerC2s, CloseContainerC2s,
+    };
+    use valence_protocol::packets::S2cPlayPacket;
+    use valence_protocol::{ItemKind, VarInt};
+
+    use super::*;
+    use crate::assert_packet_count;
+    use crate::unit_test::util::scenario_single_client;
+
+    #[test]
+    fn menu_clicks_are_handled_and_undone() -> anyhow::Result<()> {
+        let mut app = App::new();
+        let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+
+        app.update();
+        client_helper.clear_sent();
+
+        let clicked = Arc::new(AtomicUsize::new(0));
+        let clicked_in_handler = clicked.clone();
+
+        let menu = Menu::new(MenuType::Chest { rows: 3 }, "Warps")
+            .with_item(13, ItemStack::new(ItemKind::Compass, 1, None))
+            .with_click_handler(move |click, inventory| {
+                clicked_in_handler.fetch_add(1, Ordering::SeqCst);
+                inventory.replace_slot(click.slot, ItemStack::new(ItemKind::Barrier, 1, None));
+            });
+
+        app.world
+            .get_mut::<Client>(client_ent)
+            .unwrap()
+            .open_menu(menu);
+
+        // The menu is spawned on the first tick and opened on the second.
+        app.update();
+        app.update();
+
+        let sent_packets = client_helper.collect_sent()?;
+        assert_packet_count!(sent_packets, 1, S2cPlayPacket::OpenScreen(_));
+
+        let menu_ent = app.world.get::<OpenInventory>(client_ent).unwrap().entity();
+        let window_id = app.world.get::<Client>(client_ent).unwrap().window_id;
+        let state_id = app
+            .world
+            .get::<Client>(client_ent)
+            .unwrap()
+            .inventory_state_id
+            .0;
+
+        // Take the compass out of the menu.
+        client_helper.clear_sent();
+        client_helper.send(&ClickContainerC2s {
+            window_id,
+            state_id: VarInt(state_id),
+            slot_idx: 13,
+            button: 0,
+            mode: ClickContainerMode::Click,
+            slots: vec![(13, None)],
+            carried_item: Some(ItemStack::new(ItemKind::Compass, 1, None)),
+        });
+
+        app.update();
+
+        assert_eq!(clicked.load(Ordering::SeqCst), 1);
+        let inventory = app.world.get::<Inventory>(menu_ent).unwrap();
+        assert_eq!(inventory.slot(13).unwrap().item, ItemKind::Barrier);
+        assert_eq!(
+            app.world.get::<Client>(client_ent).unwrap().cursor_item(),
+            None
+        );
+
+        let sent_packets = client_helper.collect_sent()?;
+        assert_packet_count!(sent_packets, 1, S2cPlayPacket::SetContainerContent(_));
+
+        // Closing the menu despawns it.
+        client_helper.send(&CloseContainerC2s {
+            window_id: window_id as i8,
+        });
+
+        app.update();
+        app.update();
+
+        assert!(app.world.get_entity(menu_ent).is_none());
+
+        let events = app.world.resource::<Events<MenuClosed>>();
+        assert_eq!(events.get_reader().iter(events).count(), 1);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,617 @@
+//! Playing Note Block Studio songs.
+//!
+//! Songs are loaded from `.nbs` files with [`Song::from_nbs`] and played to a
+//! group of clients by spawning an entity with a [`SongPlayer`] component.
+//! Each song player has its own listeners and playback controls, so the same
+//! song can be played to different audiences independently.
+
+use std::path::Path;
+use std::sync::Arc;
+
+use anyhow::{bail, ensure, Context};
+use bevy_ecs::prelude::*;
+use glam::DVec3;
+use valence_protocol::types::SoundCategory;
+
+use crate::client::Client;
+use crate::note_block::Instrument;
+use crate::server::Server;
+
+/// The instruments used by Note Block Studio, in the order of their IDs.
+const NBS_INSTRUMENTS: [Instrument; 16] = [
+    Instrument::Harp,
+    Instrument::Bass,
+    Instrument::Basedrum,
+    Instrument::Snare,
+    Instrument::Hat,
+    Instrument::Guitar,
+    Instrument::Flute,
+    Instrument::Bell,
+    Instrument::Chime,
+    Instrument::Xylophone,
+    Instrument::IronXylophone,
+    Instrument::CowBell,
+    Instrument::Didgeridoo,
+    Instrument::Bit,
+    Instrument::Banjo,
+    Instrument::Pling,
+];
+
+/// The Note Block Studio key which plays at the default pitch.
+const NBS_BASE_KEY: f32 = 45.0;
+
+/// A single note of a [`Song`].
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct SongNote {
+    /// The tick of the song the note is played at.
+    pub tick: u32,
+    pub instrument: Instrument,
+    pub volume: f32,
+    pub pitch: f32,
+}
+
+/// A song which can be played with a [`SongPlayer`].
+#[derive(Clone, PartialEq, Debug)]
+pub struct Song {
+    pub name: String,
+    pub author: String,
+    pub original_author: String,
+    pub description: String,
+    /// The speed of the song in song ticks per second.
+    pub tempo: f32,
+    /// The tick the song jumps back to when it is played on a loop.
+    pub loop_start: u32,
+    /// The notes of the song, sorted by tick.
+    pub notes: Vec<SongNote>,
+}
+
+/// Reads the little endian values of an NBS file.
+struct NbsReader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> NbsReader<'a> {
+    fn take<const N: usize>(&mut self) -> anyhow::Result<[u8; N]> {
+        ensure!(self.bytes.len() >= N, "unexpected end of NBS file");
+
+        let (value, rest) = self.bytes.split_at(N);
+        self.bytes = rest;
+
+        Ok(value.try_into().unwrap())
+    }
+
+    fn u8(&mut self) -> anyhow::Result<u8> {
+        Ok(self.take::<1>()?[0])
+    }
+
+    fn i16(&mut self) -> anyhow::Result<i16> {
+        Ok(i16::from_le_bytes(self.take()?))
+    }
+
+    fn i32(&mut self) -> anyhow::Result<i32> {
+        Ok(i32::from_le_bytes(self.take()?))
+    }
+
+    fn string(&mut self) -> anyhow::Result<String> {
+        let len = usize::try_from(self.i32()?).context("negative string length")?;
+
+        ensure!(self.bytes.len() >= len, "unexpected end of NBS file");
+
+        let (string, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+
+        Ok(String::from_utf8_lossy(string).into_owned())
+    }
+}
+
+impl Song {
+    /// Parses a song from the contents of a Note Block Studio file. Both the
+    /// original format and versions 1 through 5 of the new format are
+    /// supported.
+    ///
+    /// Notes played on custom instruments are skipped, since they require a
+    /// resource pack.
+    pub fn from_nbs(bytes: &[u8]) -> anyhow::Result<Self> {
+        let mut r = NbsReader { bytes };
+
+        // Files in the new format start with zero where the old format stores
+        // the length of the song.
+        let version = match r.i16()? {
+            0 => {
+                let version = r.u8()?;
+                ensure!(
+                    (1..=5).contains(&version),
+                    "unsupported NBS version {version}"
+                );
+
+                // Vanilla instrument count.
+                r.u8()?;
+
+                if version >= 3 {
+                    // Song length.
+                    r.i16()?;
+                }
+
+                version
+            }
+            _ => 0,
+        };
+
+        let layer_count = r.i16()?;
+        let name = r.string()?;
+        let author = r.string()?;
+        let original_author = r.string()?;
+        let description = r.string()?;
+        let tempo = r.i16()? as f32 / 100.0;
+
+        ensure!(tempo > 0.0, "invalid song tempo {tempo}");
+
+        // Auto-saving, auto-saving duration and time signature.
+        r.take::<3>()?;
+        // Minutes spent and click and note block counts.
+        r.take::<20>()?;
+        // Imported file name.
+        r.string()?;
+
+        let mut loop_start = 0;
+
+        if version >= 4 {
+            let looping = r.u8()?;
+            // Maximum loop count.
+            r.u8()?;
+            let start = r.i16()?;
+
+            if looping != 0 {
+                loop_start = start.max(0) as u32;
+            }
+        }
+
+        // The notes are stored as jumps between ticks and layers. The volume of
+        // their layers comes after them.
+        let mut raw_notes = vec![];
+        let mut tick = -1_i32;
+
+        loop {
+            let jump = r.i16()?;
+            if jump == 0 {
+                break;
+            }
+            tick += jump as i32;
+
+            let mut layer = -1_i32;
+
+            loop {
+                let jump = r.i16()?;
+                if jump == 0 {
+                    break;
+                }
+                layer += jump as i32;
+
+                let instrument = r.u8()?;
+                let key = r.u8()?;
+
+                let (velocity, pitch) = if version >= 4 {
+                    let velocity = r.u8()?;
+                    // Panning.
+                    r.u8()?;
+                    (velocity, r.i16()?)
+                } else {
+                    (100, 0)
+                };
+
+                raw_notes.push((tick, layer, instrument, key, velocity, pitch));
+            }
+        }
+
+        let mut layer_volumes = vec![100_u8; layer_count.max(0) as usize];
+
+        for volume in &mut layer_volumes {
+            // Layer name.
+            r.string()?;
+
+            if version >= 4 {
+                // Layer lock.
+                r.u8()?;
+            }
+
+            *volume = r.u8()?;
+
+            if version >= 2 {
+                // Layer stereo.
+                r.u8()?;
+            }
+        }
+
+        let mut notes = vec![];
+
+        for (tick, layer, instrument, key, velocity, pitch) in raw_notes {
+            let Some(&instrument) = NBS_INSTRUMENTS.get(instrument as usize) else {
+                continue
+            };
+
+            let layer_volume = layer_volumes.get(layer as usize).copied().unwrap_or(100);
+
+            notes.push(SongNote {
+                tick: tick as u32,
+                instrument,
+                volume: velocity as f32 / 100.0 * layer_volume as f32 / 100.0,
+                pitch: 2_f32.powf((key as f32 - NBS_BASE_KEY + pitch as f32 / 100.0) / 12.0),
+            });
+        }
+
+        Ok(Self {
+            name,
+            author,
+            original_author,
+            description,
+            tempo,
+            loop_start,
+            notes,
+        })
+    }
+
+    /// Reads and parses a Note Block Studio file.
+    pub fn open(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+
+        let bytes =
+            std::fs::read(path).with_context(|| format!("failed to read {}", path.display()))?;
+
+        match Self::from_nbs(&bytes) {
+            Ok(song) => Ok(song),
+            Err(e) => bail!("failed to parse {}: {e:#}", path.display()),
+        }
+    }
+
+    /// Returns the number of ticks in the song, which is one more than the
+    /// tick of the last note.
+    pub fn length(&self) -> u32 {
+        self.notes.last().map_or(0, |note| note.tick + 1)
+    }
+
+    /// Returns the duration of the song in seconds.
+    pub fn duration_secs(&self) -> f32 {
+        self.length() as f32 / self.tempo
+    }
+}
+
+/// The playback state of a [`SongPlayer`].
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum PlaybackState {
+    Playing,
+    Paused,
+    Stopped,
+}
+
+/// A [`Component`] which plays a [`Song`] to its listeners.
+///
+/// Spawn an entity with this component for every group of clients that should
+/// hear the song together. When the song ends without looping, the player is
+/// stopped and a [`SongFinished`] event is sent.
+#[derive(Component, Clone, Debug)]
+pub struct SongPlayer {
+    song: Arc<Song>,
+    listeners: Vec<Entity>,
+    state: PlaybackState,
+    /// The current position in the song, in song ticks.
+    position: f64,
+    looping: bool,
+    volume: f32,
+    /// The position the song is played from. If this is `None`, it is played
+    /// at the position of each listener.
+    origin: Option<DVec3>,
+}
+
+impl SongPlayer {
+    /// Creates a stopped song player without listeners.
+    pub fn new(song: Arc<Song>) -> Self {
+        Self {
+            song,
+            listeners: vec![],
+            state: PlaybackState::Stopped,
+            position: 0.0,
+            looping: false,
+            volume: 1.0,
+            origin: None,
+        }
+    }
+
+    pub fn song(&self) -> &Arc<Song> {
+        &self.song
+    }
+
+    /// Replaces the song and stops playback.
+    pub fn set_song(&mut self, song: Arc<Song>) {
+        self.song = song;
+        self.stop();
+    }
+
+    pub fn listeners(&self) -> &[Entity] {
+        &self.listeners
+    }
+
+    /// Adds a client to the listeners. Returns `false` if it was already
+    /// listening.
+    pub fn add_listener(&mut self, client: Entity) -> bool {
+        if self.listeners.contains(&client) {
+            false
+        } else {
+            self.listeners.push(client);
+            true
+        }
+    }
+
+    /// Removes a client from the listeners. Returns `false` if it was not
+    /// listening.
+    pub fn remove_listener(&mut self, client: Entity) -> bool {
+        let len = self.listeners.len();
+        self.listeners.retain(|&listener| listener != client);
+        self.listeners.len() != len
+    }
+
+    pub fn state(&self) -> PlaybackState {
+        self.state
+    }
+
+    /// Starts or resumes playback.
+    pub fn play(&mut self) {
+        self.state = PlaybackState::Playing;
+    }
+
+    /// Pauses playback at the current position.
+    pub fn pause(&mut self) {
+        if self.state == PlaybackState::Playing {
+            self.state = PlaybackState::Paused;
+        }
+    }
+
+    /// Stops playback and rewinds to the start of the song.
+    pub fn stop(&mut self) {
+        self.state = PlaybackState::Stopped;
+        self.position = 0.0;
+    }
+
+    /// Returns the current song tick.
+    pub fn tick(&self) -> u32 {
+        self.position as u32
+    }
+
+    /// Jumps to the given song tick.
+    pub fn seek(&mut self, tick: u32) {
+        self.position = tick as f64;
+    }
+
+    pub fn is_looping(&self) -> bool {
+        self.looping
+    }
+
+    /// Sets if the song restarts from its loop start when it ends.
+    ///
+    /// # Default Value
+    ///
+    /// `false`
+    pub fn set_looping(&mut self, looping: bool) {
+        self.looping = looping;
+    }
+
+    pub fn volume(&self) -> f32 {
+        self.volume
+    }
+
+    /// Sets the volume the notes are played at, which is multiplied with the
+    /// volume of each note.
+    ///
+    /// # Default Value
+    ///
+    /// `1.0`
+    pub fn set_volume(&mut self, volume: f32) {
+        self.volume = volume;
+    }
+
+    pub fn origin(&self) -> Option<DVec3> {
+        self.origin
+    }
+
+    /// Sets the position the song is played from. If this is `None`, the song
+    /// is played at the position of each listener so it is heard at the same
+    /// volume everywhere.
+    ///
+    /// # Default Value
+    ///
+    /// `None`
+    pub fn set_origin(&mut self, origin: Option<DVec3>) {
+        self.origin = origin;
+    }
+}
+
+/// An event sent when a [`SongPlayer`] reaches the end of its song without
+/// looping.
+#[derive(Clone, Debug)]
+pub struct SongFinished {
+    pub player: Entity,
+}
+
+pub(crate) fn play_songs(
+    server: Res<Server>,
+    mut players: Query<(Entity, &mut SongPlayer)>,
+    mut clients: Query<&mut Client>,
+    mut finished: EventWriter<SongFinished>,
+) {
+    for (entity, mut player) in &mut players {
+        if player.state != PlaybackState::Playing {
+            continue;
+        }
+
+        let player = &mut *player;
+        let song = &player.song;
+        let length = song.length() as f64;
+
+        let start = player.position;
+        let end = start + song.tempo as f64 / server.tps() as f64;
+
+        // Notes are played when the position passes their tick.
+        let first = song
+            .notes
+            .partition_point(|note| (note.tick as f64) < start);
+
+        for note in song.notes[first..]
+            .iter()
+            .take_while(|note| (note.tick as f64) < end)
+        {
+            let Some(sound) = note.instrument.sound() else {
+                continue
+            };
+
+            for &listener in &player.listeners {
+                let Ok(mut client) = clients.get_mut(listener) else {
+                    continue
+                };
+
+                let position = player.origin.unwrap_or_else(|| client.position());
+
+                client.play_sound(
+                    sound,
+                    SoundCategory::Record,
+                    position,
+                    note.volume * player.volume,
+                    note.pitch,
+                );
+            }
+        }
+
+        player.position = end;
+
+        if end >= length {
+            if player.looping && (song.loop_start as f64) < length {
+                player.position = song.loop_start as f64 + (end - length);
+            } else {
+                player.stop();
+                finished.send(SongFinished { player: entity });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy_app::App;
+    use valence_protocol::packets::S2cPlayPacket;
+
+    use super::*;
+    use crate::assert_packet_count;
+    use crate::unit_test::util::scenario_single_client;
+
+    /// Builds a version 5 NBS file with two layers and notes at ticks 0 and 2.
+    fn test_nbs() -> Vec<u8> {
+        let mut bytes = vec![];
+
+        let string = |bytes: &mut Vec<u8>, s: &str| {
+            bytes.extend((s.len() as i32).to_le_bytes());
+            bytes.extend(s.as_bytes());
+        };
+
+        bytes.extend(0_i16.to_le_bytes());
+        bytes.extend([5, 16]);
+        bytes.extend(3_i16.to_le_bytes());
+        bytes.extend(2_i16.to_le_bytes());
+        string(&mut bytes, "Test Song");
+        string(&mut bytes, "Author");
+        string(&mut bytes, "");
+        string(&mut bytes, "");
+        bytes.extend(2000_i16.to_le_bytes());
+        bytes.extend([0, 0, 4]);
+        bytes.extend([0; 20]);
+        string(&mut bytes, "");
+        bytes.extend([1, 0]);
+        bytes.extend(1_i16.to_le_bytes());
+
+        // Tick 0, layer 0: harp at the base key.
+        bytes.extend(1_i16.to_le_bytes());
+        bytes.extend(1_i16.to_le_bytes());
+        bytes.extend([0, 45, 100, 100]);
+        bytes.extend(0_i16.to_le_bytes());
+        bytes.extend(0_i16.to_le_bytes());
+
+        // Tick 2, layers 0 and 1: bass an octave up and a custom instrument.
+        bytes.extend(2_i16.to_le_bytes());
+        bytes.extend(1_i16.to_le_bytes());
+        bytes.extend([1, 57, 50, 100]);
+        bytes.extend(0_i16.to_le_bytes());
+        bytes.extend(1_i16.to_le_bytes());
+        bytes.extend([16, 45, 100, 100]);
+        bytes.extend(0_i16.to_le_bytes());
+        bytes.extend(0_i16.to_le_bytes());
+        bytes.extend(0_i16.to_le_bytes());
+
+        for volume in [100, 50] {
+            string(&mut bytes, "");
+            bytes.extend([0, volume, 100]);
+        }
+
+        // No custom instruments.
+        bytes.push(0);
+
+        bytes
+    }
+
+    #[test]
+    fn parse_nbs() {
+        let song = Song::from_nbs(&test_nbs()).unwrap();
+
+        assert_eq!(song.name, "Test Song");
+        assert_eq!(song.author, "Author");
+        assert_eq!(song.tempo, 20.0);
+        assert_eq!(song.loop_start, 1);
+        assert_eq!(song.length(), 3);
+        assert_eq!(
+            song.notes,
+            [
+                SongNote {
+                    tick: 0,
+                    instrument: Instrument::Harp,
+                    volume: 1.0,
+                    pitch: 1.0,
+                },
+                SongNote {
+                    tick: 2,
+                    instrument: Instrument::Bass,
+                    volume: 0.5,
+                    pitch: 2.0,
+                },
+            ]
+        );
+
+        assert!(Song::from_nbs(&test_nbs()[..40]).is_err());
+    }
+
+    #[test]
+    fn song_player_plays_notes() -> anyhow::Result<()> {
+        let mut app = App::new();
+        let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+
+        let mut player = SongPlayer::new(Arc::new(Song::from_nbs(&test_nbs())?));
+        player.add_listener(client_ent);
+        player.play();
+
+        let player_ent = app.world.spawn(player).id();
+
+        app.update();
+        client_helper.clear_sent();
+
+        // The first note was played on the first tick.
+        app.update();
+        app.update();
+
+        let sent_packets = client_helper.collect_sent()?;
+        assert_packet_count!(sent_packets, 1, S2cPlayPacket::SoundEffect(_));
+
+        let player = app.world.get::<SongPlayer>(player_ent).unwrap();
+        assert_eq!(player.state(), PlaybackState::Stopped);
+        assert_eq!(player.tick(), 0);
+
+        let finished = app.world.resource::<Events<SongFinished>>();
+        assert_eq!(finished.get_reader().iter(finished).count(), 1);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,331 @@
+//! Nicknames displayed in place of player usernames.
+//!
+//! A [`Nickname`] changes the name of a player in the player list and above
+//! the player's head without changing the player's UUID. Chat messages are
+//! formatted by the application, which should use [`display_name`] so that
+//! players are named the same everywhere.
+
+use std::collections::HashMap;
+
+use bevy_ecs::prelude::*;
+use uuid::Uuid;
+use valence_protocol::Text;
+
+use crate::client::Client;
+use crate::entity::McEntity;
+use crate::player_list::{Entry, PlayerList};
+
+/// The maximum length of the name displayed above a player's head.
+const MAX_NAME_TAG_LEN: usize = 16;
+
+/// A [`Component`] that changes the name of the [`Client`] on the same entity.
+///
+/// The nickname is displayed with its formatting in the player list and as
+/// plain text above the player's head, truncated to 16 characters. This is
+/// done by modifying the [`PlayerListEntry`] with the UUID of the client, so
+/// the display name of the entry is overwritten.
+///
+/// Nicknames are unique. If another client uses the same name as its
+/// nickname or username, ignoring case and formatting, a [`NicknameConflict`]
+/// event is sent and the previous nickname of the client is restored.
+///
+/// Removing the component restores the username of the client.
+///
+/// [`PlayerListEntry`]: crate::player_list::PlayerListEntry
+#[derive(Component, Clone, PartialEq, Debug)]
+pub struct Nickname {
+    pub name: Text,
+}
+
+impl Nickname {
+    pub fn new(name: impl Into<Text>) -> Self {
+        Self { name: name.into() }
+    }
+
+    /// Returns the nickname without formatting.
+    pub fn plain(&self) -> String {
+        self.name.to_string()
+    }
+}
+
+/// Returns the name of a client as it should be displayed to other players.
+pub fn display_name(client: &Client, nickname: Option<&Nickname>) -> Text {
+    match nickname {
+        Some(nickname) => nickname.name.clone(),
+        None => client.username().as_str().to_owned().into(),
+    }
+}
+
+/// A [`Resource`] containing the nicknames which are in effect.
+#[derive(Resource, Default, Debug)]
+pub struct Nicknames {
+    /// Maps lowercase plain nicknames to the clients using them.
+    owners: HashMap<String, Entity>,
+    applied: HashMap<Entity, AppliedNickname>,
+}
+
+#[derive(Debug)]
+struct AppliedNickname {
+    key: String,
+    name: Text,
+    uuid: Uuid,
+    /// The username of the client, which is restored when the nickname is
+    /// removed.
+    username: String,
+}
+
+impl Nicknames {
+    /// Returns the client using the given nickname, ignoring case.
+    pub fn owner(&self, nickname: &str) -> Option<Entity> {
+        self.owners.get(&nickname.to_lowercase()).copied()
+    }
+
+    /// Returns the nickname which is in effect for the given client.
+    pub fn get(&self, client: Entity) -> Option<&Text> {
+        self.applied.get(&client).map(|applied| &applied.name)
+    }
+}
+
+/// An event sent when the nickname of a client has changed.
+#[derive(Clone, Debug)]
+pub struct NicknameChanged {
+    pub client: Entity,
+    /// The previous nickname, or `None` if the client had no nickname.
+    pub old: Option<Text>,
+    /// The new nickname, or `None` if the nickname was removed.
+    pub new: Option<Text>,
+}
+
+/// An event sent when a [`Nickname`] was rejected because the name is already
+/// in use.
+#[derive(Clone, Debug)]
+pub struct NicknameConflict {
+    pub client: Entity,
+    /// The rejected nickname.
+    pub nickname: Text,
+    /// The client using the name as its nickname or username.
+    pub owner: Entity,
+}
+
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn update_nicknames(
+    mut commands: Commands,
+    mut nicknames: ResMut<Nicknames>,
+    mut player_list: ResMut<PlayerList>,
+    changed: Query<(Entity, &Client, &Nickname), Changed<Nickname>>,
+    removed: RemovedComponents<Nickname>,
+    clients: Query<(Entity, &Client)>,
+    mut entities: Query<&mut McEntity>,
+    mut changed_events: EventWriter<NicknameChanged>,
+    mut conflict_events: EventWriter<NicknameConflict>,
+) {
+    for client_id in removed.iter() {
+        let Some(applied) = nicknames.applied.remove(&client_id) else {
+            continue
+        };
+
+        nicknames.owners.remove(&applied.key);
+
+        set_player_name(
+            &mut player_list,
+            &mut entities,
+            client_id,
+            applied.uuid,
+            None,
+            &applied.username,
+        );
+
+        changed_events.send(NicknameChanged {
+            client: client_id,
+            old: Some(applied.name),
+            new: None,
+        });
+    }
+
+    for (client_id, client, nickname) in &changed {
+        let old = nicknames.applied.get(&client_id);
+
+        if old.map_or(false, |old| old.name == nickname.name) {
+            continue;
+        }
+
+        let plain = nickname.plain();
+        let key = plain.to_lowercase();
+
+        let owner = nicknames
+            .owners
+            .get(&key)
+            .copied()
+            .filter(|&owner| owner != client_id)
+            .or_else(|| {
+                clients
+                    .iter()
+                    .find(|(id, c)| *id != client_id && c.username().as_str().to_lowercase() == key)
+                    .map(|(id, _)| id)
+            });
+
+        if let Some(owner) = owner {
+            match old {
+                Some(old) => {
+                    commands
+                        .entity(client_id)
+                        .insert(Nickname::new(old.name.clone()));
+                }
+                None => {
+                    commands.entity(client_id).remove::<Nickname>();
+                }
+            }
+
+            conflict_events.send(NicknameConflict {
+                client: client_id,
+                nickname: nickname.name.clone(),
+                owner,
+            });
+
+            continue;
+        }
+
+        let old = nicknames.applied.insert(
+            client_id,
+            AppliedNickname {
+                key: key.clone(),
+                name: nickname.name.clone(),
+                uuid: client.uuid(),
+                username: client.username().as_str().to_owned(),
+            },
+        );
+
+        if let Some(old) = &old {
+            nicknames.owners.remove(&old.key);
+        }
+
+        nicknames.owners.insert(key, client_id);
+
+        let name_tag: String = plain.chars().take(MAX_NAME_TAG_LEN).collect();
+
+        set_player_name(
+            &mut player_list,
+            &mut entities,
+            client_id,
+            client.uuid(),
+            Some(&nickname.name),
+            &name_tag,
+        );
+
+        changed_events.send(NicknameChanged {
+            client: client_id,
+            old: old.map(|old| old.name),
+            new: Some(nickname.name.clone()),
+        });
+    }
+}
+
+/// Changes the name of the player list entry with the given UUID and
+/// respawns the player entity if the name above its head changed.
+fn set_player_name(
+    player_list: &mut PlayerList,
+    entities: &mut Query<&mut McEntity>,
+    client_id: Entity,
+    uuid: Uuid,
+    display_name: Option<&Text>,
+    username: &str,
+) {
+    let Entry::Occupied(mut oe) = player_list.entry(uuid) else {
+        return
+    };
+
+    let username_changed = oe.get().username() != username;
+
+    let new_entry = oe
+        .get()
+        .clone()
+        .with_username(username)
+        .with_display_name(display_name.cloned());
+    oe.insert(new_entry);
+
+    if username_changed {
+        if let Ok(mut entity) = entities.get_mut(client_id) {
+            if entity.uuid() == uuid {
+                entity.respawn();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy_app::App;
+    use valence_protocol::packets::S2cPlayPacket;
+
+    use super::*;
+    use crate::assert_packet_count;
+    use crate::player_list::PlayerListEntry;
+    use crate::unit_test::util::{create_mock_client, gen_client_info, scenario_single_client};
+
+    #[test]
+    fn nickname_replaces_player_list_entry() -> anyhow::Result<()> {
+        let mut app = App::new();
+        let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+        let uuid = app.world.get::<Client>(client_ent).unwrap().uuid();
+
+        app.world
+            .resource_mut::<PlayerList>()
+            .insert(uuid, PlayerListEntry::new().with_username("test"));
+
+        app.update();
+        client_helper.clear_sent();
+
+        app.world
+            .entity_mut(client_ent)
+            .insert(Nickname::new("Nicholas"));
+
+        app.update();
+
+        let entry = app.world.resource::<PlayerList>().get(uuid).unwrap();
+        assert_eq!(entry.username(), "Nicholas");
+        assert_eq!(entry.display_name(), Some(&Text::from("Nicholas")));
+
+        let sent_packets = client_helper.collect_sent()?;
+        assert_packet_count!(sent_packets, 1, S2cPlayPacket::PlayerInfoRemove(_));
+        assert_packet_count!(sent_packets, 1, S2cPlayPacket::PlayerInfoUpdate(_));
+
+        app.world.entity_mut(client_ent).remove::<Nickname>();
+        app.update();
+
+        let entry = app.world.resource::<PlayerList>().get(uuid).unwrap();
+        assert_eq!(entry.username(), "test");
+        assert_eq!(entry.display_name(), None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn nickname_conflicting_with_username_is_rejected() {
+        let mut app = App::new();
+        let (client_ent, _) = scenario_single_client(&mut app);
+
+        let instance_ent = app.world.get::<Client>(client_ent).unwrap().instance();
+
+        let (mut other, _) = create_mock_client(gen_client_info("other"));
+        other.set_instance(instance_ent);
+        let other_ent = app.world.spawn(other).id();
+
+        app.update();
+
+        app.world
+            .entity_mut(client_ent)
+            .insert(Nickname::new("OTHER"));
+
+        app.update();
+
+        let nicknames = app.world.resource::<Nicknames>();
+        assert_eq!(nicknames.get(client_ent), None);
+        assert!(app.world.get::<Nickname>(client_ent).is_none());
+
+        let conflicts = app.world.resource::<Events<NicknameConflict>>();
+        let mut reader = conflicts.get_reader();
+        assert!(reader
+            .iter(conflicts)
+            .any(|e| e.client == client_ent && e.owner == other_ent));
+    }
+}
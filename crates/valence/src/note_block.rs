@@ -0,0 +1,515 @@
+//! Note blocks and instruments.
+//!
+//! Clients tune note blocks by using them and play them by attacking them.
+//! The instrument of a note block is determined by the block below it, or by
+//! the mob head on top of it, and is updated whenever the note block is played
+//! or tuned. There is no redstone, so powering note blocks is left to
+//! [`Instance::set_note_block_powered`].
+//!
+//! [`Instance::play_note`] plays a note without a note block, which is useful
+//! for playing songs.
+
+use bevy_ecs::prelude::*;
+use glam::Vec3;
+use valence_protocol::block::{PropName, PropValue};
+use valence_protocol::packets::s2c::particle::Particle;
+use valence_protocol::types::{GameMode, SoundCategory};
+use valence_protocol::{BlockFace, BlockKind, BlockPos, BlockState, Sound};
+
+use crate::client::event::{StartDigging, UseItemOnBlock};
+use crate::client::pose::PoseState;
+use crate::client::Client;
+use crate::instance::Instance;
+use crate::inventory::{held_item, Inventory};
+
+/// The number of notes a note block can be tuned to.
+pub const NOTE_COUNT: u8 = 25;
+
+/// The instrument played by a note block.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum Instrument {
+    Harp,
+    Basedrum,
+    Snare,
+    Hat,
+    Bass,
+    Flute,
+    Bell,
+    Guitar,
+    Chime,
+    Xylophone,
+    IronXylophone,
+    CowBell,
+    Didgeridoo,
+    Bit,
+    Banjo,
+    Pling,
+    Zombie,
+    Skeleton,
+    Creeper,
+    Dragon,
+    WitherSkeleton,
+    Piglin,
+    /// The sound of a player head placed on top of the note block. This is
+    /// never played by the server.
+    CustomHead,
+}
+
+impl Instrument {
+    /// Returns the instrument of a note block placed on top of `kind`.
+    pub fn from_block_below(kind: BlockKind) -> Self {
+        let name = kind.to_str();
+
+        match kind {
+            BlockKind::GoldBlock => Self::Bell,
+            BlockKind::Clay => Self::Flute,
+            BlockKind::PackedIce => Self::Chime,
+            BlockKind::BoneBlock => Self::Xylophone,
+            BlockKind::IronBlock => Self::IronXylophone,
+            BlockKind::SoulSand => Self::CowBell,
+            BlockKind::Pumpkin => Self::Didgeridoo,
+            BlockKind::EmeraldBlock => Self::Bit,
+            BlockKind::HayBlock => Self::Banjo,
+            BlockKind::Glowstone => Self::Pling,
+            BlockKind::Sand | BlockKind::RedSand | BlockKind::Gravel => Self::Snare,
+            _ if name.ends_with("_concrete_powder") => Self::Snare,
+            BlockKind::SeaLantern | BlockKind::Beacon | BlockKind::RedstoneLamp => Self::Hat,
+            _ if name.contains("glass") => Self::Hat,
+            _ if name.ends_with("_wool") => Self::Guitar,
+            BlockKind::NoteBlock
+            | BlockKind::Jukebox
+            | BlockKind::Bookshelf
+            | BlockKind::CraftingTable
+            | BlockKind::Chest
+            | BlockKind::TrappedChest
+            | BlockKind::Barrel => Self::Bass,
+            _ if ["_planks", "_log", "_wood", "_stem", "_hyphae"]
+                .iter()
+                .any(|suffix| name.ends_with(suffix)) =>
+            {
+                Self::Bass
+            }
+            BlockKind::Bedrock | BlockKind::Netherrack | BlockKind::Obsidian => Self::Basedrum,
+            _ if (name.contains("stone") && !name.contains("redstone"))
+                || [
+                    "_ore",
+                    "brick",
+                    "deepslate",
+                    "terracotta",
+                    "basalt",
+                    "_concrete",
+                ]
+                .iter()
+                .any(|part| name.contains(part)) =>
+            {
+                Self::Basedrum
+            }
+            _ => Self::Harp,
+        }
+    }
+
+    /// Returns the instrument of a note block with the mob head `kind` on top
+    /// of it, or `None` if `kind` is not a mob head placed on the floor.
+    pub fn from_head_above(kind: BlockKind) -> Option<Self> {
+        Some(match kind {
+            BlockKind::ZombieHead => Self::Zombie,
+            BlockKind::SkeletonSkull => Self::Skeleton,
+            BlockKind::CreeperHead => Self::Creeper,
+            BlockKind::DragonHead => Self::Dragon,
+            BlockKind::WitherSkeletonSkull => Self::WitherSkeleton,
+            BlockKind::PiglinHead => Self::Piglin,
+            BlockKind::PlayerHead => Self::CustomHead,
+            _ => return None,
+        })
+    }
+
+    /// Returns if the instrument is a mob head. Mob heads always play at the
+    /// same pitch and don't need air above the note block.
+    pub const fn is_mob_head(self) -> bool {
+        matches!(
+            self,
+            Self::Zombie
+                | Self::Skeleton
+                | Self::Creeper
+                | Self::Dragon
+                | Self::WitherSkeleton
+                | Self::Piglin
+                | Self::CustomHead
+        )
+    }
+
+    /// Returns the sound played by the instrument, or `None` for
+    /// [`Instrument::CustomHead`].
+    pub const fn sound(self) -> Option<Sound> {
+        Some(match self {
+            Self::Harp => Sound::BlockNoteBlockHarp,
+            Self::Basedrum => Sound::BlockNoteBlockBasedrum,
+            Self::Snare => Sound::BlockNoteBlockSnare,
+            Self::Hat => Sound::BlockNoteBlockHat,
+            Self::Bass => Sound::BlockNoteBlockBass,
+            Self::Flute => Sound::BlockNoteBlockFlute,
+            Self::Bell => Sound::BlockNoteBlockBell,
+            Self::Guitar => Sound::BlockNoteBlockGuitar,
+            Self::Chime => Sound::BlockNoteBlockChime,
+            Self::Xylophone => Sound::BlockNoteBlockXylophone,
+            Self::IronXylophone => Sound::BlockNoteBlockIronXylophone,
+            Self::CowBell => Sound::BlockNoteBlockCowBell,
+            Self::Didgeridoo => Sound::BlockNoteBlockDidgeridoo,
+            Self::Bit => Sound::BlockNoteBlockBit,
+            Self::Banjo => Sound::BlockNoteBlockBanjo,
+            Self::Pling => Sound::BlockNoteBlockPling,
+            Self::Zombie => Sound::BlockNoteBlockImitateZombie,
+            Self::Skeleton => Sound::BlockNoteBlockImitateSkeleton,
+            Self::Creeper => Sound::BlockNoteBlockImitateCreeper,
+            Self::Dragon => Sound::BlockNoteBlockImitateEnderDragon,
+            Self::WitherSkeleton => Sound::BlockNoteBlockImitateWitherSkeleton,
+            Self::Piglin => Sound::BlockNoteBlockImitatePiglin,
+            Self::CustomHead => return None,
+        })
+    }
+
+    pub const fn to_prop_value(self) -> PropValue {
+        match self {
+            Self::Harp => PropValue::Harp,
+            Self::Basedrum => PropValue::Basedrum,
+            Self::Snare => PropValue::Snare,
+            Self::Hat => PropValue::Hat,
+            Self::Bass => PropValue::Bass,
+            Self::Flute => PropValue::Flute,
+            Self::Bell => PropValue::Bell,
+            Self::Guitar => PropValue::Guitar,
+            Self::Chime => PropValue::Chime,
+            Self::Xylophone => PropValue::Xylophone,
+            Self::IronXylophone => PropValue::IronXylophone,
+            Self::CowBell => PropValue::CowBell,
+            Self::Didgeridoo => PropValue::Didgeridoo,
+            Self::Bit => PropValue::Bit,
+            Self::Banjo => PropValue::Banjo,
+            Self::Pling => PropValue::Pling,
+            Self::Zombie => PropValue::Zombie,
+            Self::Skeleton => PropValue::Skeleton,
+            Self::Creeper => PropValue::Creeper,
+            Self::Dragon => PropValue::Dragon,
+            Self::WitherSkeleton => PropValue::WitherSkeleton,
+            Self::Piglin => PropValue::Piglin,
+            Self::CustomHead => PropValue::CustomHead,
+        }
+    }
+
+    pub const fn from_prop_value(value: PropValue) -> Option<Self> {
+        Some(match value {
+            PropValue::Harp => Self::Harp,
+            PropValue::Basedrum => Self::Basedrum,
+            PropValue::Snare => Self::Snare,
+            PropValue::Hat => Self::Hat,
+            PropValue::Bass => Self::Bass,
+            PropValue::Flute => Self::Flute,
+            PropValue::Bell => Self::Bell,
+            PropValue::Guitar => Self::Guitar,
+            PropValue::Chime => Self::Chime,
+            PropValue::Xylophone => Self::Xylophone,
+            PropValue::IronXylophone => Self::IronXylophone,
+            PropValue::CowBell => Self::CowBell,
+            PropValue::Didgeridoo => Self::Didgeridoo,
+            PropValue::Bit => Self::Bit,
+            PropValue::Banjo => Self::Banjo,
+            PropValue::Pling => Self::Pling,
+            PropValue::Zombie => Self::Zombie,
+            PropValue::Skeleton => Self::Skeleton,
+            PropValue::Creeper => Self::Creeper,
+            PropValue::Dragon => Self::Dragon,
+            PropValue::WitherSkeleton => Self::WitherSkeleton,
+            PropValue::Piglin => Self::Piglin,
+            PropValue::CustomHead => Self::CustomHead,
+            _ => return None,
+        })
+    }
+}
+
+/// Returns the pitch of the sound played for `note`, which ranges from `0.5`
+/// to `2.0` over two octaves.
+pub fn note_pitch(note: u8) -> f32 {
+    2_f32.powf((note.min(NOTE_COUNT - 1) as f32 - 12.0) / 12.0)
+}
+
+impl Instance {
+    /// Plays `note` on `instrument` at the note block position `pos`, along
+    /// with the note particle above it. Notes range from `0` to `24`, and are
+    /// ignored by mob head instruments.
+    ///
+    /// This does not require a note block at `pos`.
+    pub fn play_note(&mut self, pos: impl Into<BlockPos>, instrument: Instrument, note: u8) {
+        let pos = pos.into();
+
+        let Some(sound) = instrument.sound() else {
+            return
+        };
+
+        let pitch = if instrument.is_mob_head() {
+            1.0
+        } else {
+            note_pitch(note)
+        };
+
+        self.play_sound(
+            sound,
+            SoundCategory::Record,
+            [pos.x as f64 + 0.5, pos.y as f64 + 0.5, pos.z as f64 + 0.5],
+            3.0,
+            pitch,
+        );
+
+        if !instrument.is_mob_head() {
+            // With a count of zero, the X offset of the note particle selects
+            // its color.
+            self.play_particle(
+                &Particle::Note,
+                false,
+                [pos.x as f64 + 0.5, pos.y as f64 + 1.2, pos.z as f64 + 0.5],
+                Vec3::new(note as f32 / 24.0, 0.0, 0.0),
+                1.0,
+                0,
+            );
+        }
+    }
+
+    /// Updates the instrument of the note block at `pos` from the blocks
+    /// around it and returns the new state, or `None` if there is no note
+    /// block at `pos`.
+    fn update_note_block(&mut self, pos: BlockPos) -> Option<BlockState> {
+        let state = self.block(pos)?.state();
+
+        if state.to_kind() != BlockKind::NoteBlock {
+            return None;
+        }
+
+        let kind_at = |pos| {
+            self.block(pos)
+                .map_or(BlockKind::Air, |block| block.state().to_kind())
+        };
+
+        let instrument = Instrument::from_head_above(kind_at(pos.get_in_direction(BlockFace::Top)))
+            .unwrap_or_else(|| {
+                Instrument::from_block_below(kind_at(pos.get_in_direction(BlockFace::Bottom)))
+            });
+
+        let new_state = state.set(PropName::Instrument, instrument.to_prop_value());
+
+        if new_state != state {
+            self.set_block(pos, new_state);
+        }
+
+        Some(new_state)
+    }
+
+    /// Plays the note block at `pos` with its current note. Note blocks with a
+    /// block on top of them are silent unless the instrument is a mob head.
+    ///
+    /// Returns if a note was played.
+    pub fn play_note_block(&mut self, pos: impl Into<BlockPos>) -> bool {
+        let pos = pos.into();
+
+        let Some(state) = self.update_note_block(pos) else {
+            return false
+        };
+
+        let Some(instrument) = state
+            .get(PropName::Instrument)
+            .and_then(Instrument::from_prop_value)
+        else {
+            return false
+        };
+
+        let above = pos.get_in_direction(BlockFace::Top);
+
+        if !instrument.is_mob_head()
+            && !self
+                .block(above)
+                .map_or(true, |block| block.state().is_air())
+        {
+            return false;
+        }
+
+        let note = state
+            .get(PropName::Note)
+            .and_then(PropValue::to_u16)
+            .unwrap_or(0) as u8;
+
+        self.play_note(pos, instrument, note);
+
+        true
+    }
+
+    /// Sets the `powered` property of the note block at `pos`. The note block
+    /// is played when it goes from unpowered to powered.
+    pub fn set_note_block_powered(&mut self, pos: impl Into<BlockPos>, powered: bool) {
+        let pos = pos.into();
+
+        let Some(state) = self.update_note_block(pos) else {
+            return
+        };
+
+        let value = if powered {
+            PropValue::True
+        } else {
+            PropValue::False
+        };
+
+        if state.get(PropName::Powered) == Some(value) {
+            return;
+        }
+
+        self.set_block(pos, state.set(PropName::Powered, value));
+
+        if powered {
+            self.play_note_block(pos);
+        }
+    }
+
+    /// Raises the note of the note block at `pos` by one, wrapping around to
+    /// the lowest note, and plays it.
+    pub fn tune_note_block(&mut self, pos: impl Into<BlockPos>) {
+        let pos = pos.into();
+
+        let Some(state) = self.update_note_block(pos) else {
+            return
+        };
+
+        let note = state
+            .get(PropName::Note)
+            .and_then(PropValue::to_u16)
+            .unwrap_or(0);
+
+        let Some(next) = PropValue::from_u16((note + 1) % NOTE_COUNT as u16) else {
+            return
+        };
+
+        self.set_block(pos, state.set(PropName::Note, next));
+        self.play_note_block(pos);
+    }
+}
+
+pub(crate) fn tune_note_blocks(
+    clients: Query<(&Client, &Inventory, Option<&PoseState>)>,
+    mut instances: Query<&mut Instance>,
+    mut use_item_on_block: EventReader<UseItemOnBlock>,
+) {
+    for event in use_item_on_block.iter() {
+        let Ok((client, inventory, pose)) = clients.get(event.client) else {
+            continue
+        };
+
+        if client.game_mode() == GameMode::Spectator {
+            continue;
+        }
+
+        // Sneaking clients use their item on the note block instead.
+        if pose.map_or(false, |pose| pose.is_sneaking())
+            && held_item(client, inventory, event.hand).is_some()
+        {
+            continue;
+        }
+
+        let Ok(mut instance) = instances.get_mut(client.instance()) else {
+            continue
+        };
+
+        instance.tune_note_block(event.position);
+    }
+}
+
+pub(crate) fn play_attacked_note_blocks(
+    clients: Query<&Client>,
+    mut instances: Query<&mut Instance>,
+    mut start_digging: EventReader<StartDigging>,
+) {
+    for event in start_digging.iter() {
+        let Ok(client) = clients.get(event.client) else {
+            continue
+        };
+
+        if client.game_mode() == GameMode::Spectator {
+            continue;
+        }
+
+        let Ok(mut instance) = instances.get_mut(client.instance()) else {
+            continue
+        };
+
+        instance.play_note_block(event.position);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy_app::App;
+    use valence_protocol::packets::c2s::play::UseItemOn;
+    use valence_protocol::types::Hand;
+
+    use super::*;
+    use crate::instance::Chunk;
+    use crate::unit_test::util::scenario_single_client;
+
+    #[test]
+    fn instrument_from_surroundings() {
+        assert_eq!(
+            Instrument::from_block_below(BlockKind::OakPlanks),
+            Instrument::Bass
+        );
+        assert_eq!(
+            Instrument::from_block_below(BlockKind::Cobblestone),
+            Instrument::Basedrum
+        );
+        assert_eq!(
+            Instrument::from_block_below(BlockKind::RedstoneBlock),
+            Instrument::Harp
+        );
+        assert_eq!(
+            Instrument::from_block_below(BlockKind::WhiteStainedGlass),
+            Instrument::Hat
+        );
+        assert_eq!(
+            Instrument::from_head_above(BlockKind::CreeperHead),
+            Some(Instrument::Creeper)
+        );
+        assert_eq!(
+            Instrument::from_head_above(BlockKind::CreeperWallHead),
+            None
+        );
+
+        assert_eq!(note_pitch(0), 0.5);
+        assert_eq!(note_pitch(12), 1.0);
+        assert_eq!(note_pitch(24), 2.0);
+    }
+
+    #[test]
+    fn using_note_block_tunes_it() {
+        let mut app = App::new();
+        let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+        let instance_ent = app.world.get::<Client>(client_ent).unwrap().instance();
+
+        let mut instance = app.world.get_mut::<Instance>(instance_ent).unwrap();
+        instance.insert_chunk([0, 0], Chunk::default());
+        instance.set_block([0, 0, 0], BlockState::GOLD_BLOCK);
+        instance.set_block([0, 1, 0], BlockState::NOTE_BLOCK);
+
+        for _ in 0..2 {
+            client_helper.send(&UseItemOn {
+                hand: Hand::Main,
+                position: BlockPos::new(0, 1, 0),
+                face: BlockFace::Top,
+                cursor_pos: [0.5, 1.0, 0.5],
+                head_inside_block: false,
+                sequence: 0.into(),
+            });
+        }
+
+        app.update();
+
+        let instance = app.world.get::<Instance>(instance_ent).unwrap();
+        let state = instance.block([0, 1, 0]).unwrap().state();
+
+        assert_eq!(state.get(PropName::Note), Some(PropValue::_2));
+        assert_eq!(state.get(PropName::Instrument), Some(PropValue::Bell));
+    }
+}
@@ -27,14 +27,21 @@ impl<W: WritePacket> WritePacket for &mut W {
 pub(crate) struct PacketWriter<'a> {
     buf: &'a mut Vec<u8>,
     threshold: Option<u32>,
+    level: u32,
     scratch: &'a mut Vec<u8>,
 }
 
 impl<'a> PacketWriter<'a> {
-    pub fn new(buf: &'a mut Vec<u8>, threshold: Option<u32>, scratch: &'a mut Vec<u8>) -> Self {
+    pub fn new(
+        buf: &'a mut Vec<u8>,
+        threshold: Option<u32>,
+        level: u32,
+        scratch: &'a mut Vec<u8>,
+    ) -> Self {
         Self {
             buf,
             threshold,
+            level,
             scratch,
         }
     }
@@ -46,7 +53,7 @@ impl WritePacket for PacketWriter<'_> {
         P: EncodePacket + ?Sized,
     {
         let res = if let Some(threshold) = self.threshold {
-            encode_packet_compressed(self.buf, pkt, threshold, self.scratch)
+            encode_packet_compressed(self.buf, pkt, threshold, self.level, self.scratch)
         } else {
             encode_packet(self.buf, pkt)
         };
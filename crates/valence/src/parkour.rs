@@ -0,0 +1,234 @@
+//! Checkpoints and timing for parkour courses.
+//!
+//! A course is made of [`TriggerRegion`]s with a [`Checkpoint`] component.
+//! Clients with a [`ParkourRun`] save their progress when they enter a
+//! checkpoint and are teleported back to the last checkpoint when they fall.
+
+use bevy_ecs::prelude::*;
+use glam::DVec3;
+
+use crate::client::Client;
+use crate::server::Server;
+use crate::trigger::{EnterTrigger, TriggerRegion};
+
+/// A [`Component`] which turns the [`TriggerRegion`] on the same entity into
+/// a checkpoint of a parkour course.
+#[derive(Component, Copy, Clone, PartialEq, Debug)]
+pub struct Checkpoint {
+    /// The position of the checkpoint in the course. Checkpoints with an
+    /// index less than or equal to the last checkpoint reached by a client
+    /// are ignored, so clients can't go back to an earlier checkpoint.
+    pub index: u32,
+    /// The position clients are teleported back to. If this is `None`, the
+    /// position at which the client entered the checkpoint is used.
+    pub respawn_position: Option<DVec3>,
+    /// If this is the end of the course.
+    pub finish: bool,
+}
+
+impl Checkpoint {
+    pub fn new(index: u32) -> Self {
+        Self {
+            index,
+            respawn_position: None,
+            finish: false,
+        }
+    }
+
+    /// Creates the checkpoint at the end of a course.
+    pub fn finish(index: u32) -> Self {
+        Self {
+            finish: true,
+            ..Self::new(index)
+        }
+    }
+
+    #[must_use]
+    pub fn with_respawn_position(mut self, respawn_position: impl Into<DVec3>) -> Self {
+        self.respawn_position = Some(respawn_position.into());
+        self
+    }
+}
+
+/// A [`Component`] tracking the progress of the [`Client`] on the same entity
+/// through a parkour course.
+///
+/// The timer starts on the tick the component is added. When the client
+/// enters the finish [`Checkpoint`], a [`ParkourCompleted`] event is sent and
+/// the component is removed.
+#[derive(Component, Clone, PartialEq, Debug)]
+pub struct ParkourRun {
+    /// The position the client is teleported to when falling below
+    /// `reset_y`.
+    pub checkpoint_position: DVec3,
+    /// Clients below this Y coordinate are teleported back to the last
+    /// checkpoint.
+    pub reset_y: f64,
+    /// The index of the last checkpoint reached, if any.
+    checkpoint: Option<u32>,
+    start_tick: Option<i64>,
+    falls: u32,
+}
+
+impl ParkourRun {
+    /// Creates a run which starts at `start_position`.
+    pub fn new(start_position: impl Into<DVec3>, reset_y: f64) -> Self {
+        Self {
+            checkpoint_position: start_position.into(),
+            reset_y,
+            checkpoint: None,
+            start_tick: None,
+            falls: 0,
+        }
+    }
+
+    /// Returns the index of the last checkpoint reached.
+    pub fn checkpoint(&self) -> Option<u32> {
+        self.checkpoint
+    }
+
+    /// Returns the tick the run was started on. This is `None` until the end
+    /// of the tick the component was added on.
+    pub fn start_tick(&self) -> Option<i64> {
+        self.start_tick
+    }
+
+    /// Returns the number of times the client was teleported back to a
+    /// checkpoint.
+    pub fn falls(&self) -> u32 {
+        self.falls
+    }
+}
+
+/// An event sent when a client reaches a new [`Checkpoint`].
+#[derive(Clone, Debug)]
+pub struct CheckpointReached {
+    pub client: Entity,
+    /// The entity of the checkpoint.
+    pub checkpoint: Entity,
+    pub index: u32,
+    /// The number of ticks since the start of the run.
+    pub ticks: i64,
+}
+
+/// An event sent when a client reaches the finish [`Checkpoint`] of a course.
+#[derive(Clone, Debug)]
+pub struct ParkourCompleted {
+    pub client: Entity,
+    /// The number of ticks the run took.
+    pub ticks: i64,
+    /// The number of times the client fell during the run.
+    pub falls: u32,
+}
+
+pub(crate) fn start_parkour_runs(
+    mut runs: Query<&mut ParkourRun, Added<ParkourRun>>,
+    server: Res<Server>,
+) {
+    for mut run in &mut runs {
+        run.start_tick = Some(server.current_tick());
+    }
+}
+
+pub(crate) fn update_parkour_checkpoints(
+    mut commands: Commands,
+    mut clients: Query<(&Client, &mut ParkourRun)>,
+    checkpoints: Query<&Checkpoint, With<TriggerRegion>>,
+    mut enter_events: EventReader<EnterTrigger>,
+    mut reached_events: EventWriter<CheckpointReached>,
+    mut completed_events: EventWriter<ParkourCompleted>,
+    server: Res<Server>,
+) {
+    for event in enter_events.iter() {
+        let Ok(checkpoint) = checkpoints.get(event.region) else {
+            continue
+        };
+
+        let Ok((client, mut run)) = clients.get_mut(event.client) else {
+            continue
+        };
+
+        if run
+            .checkpoint
+            .map_or(false, |index| checkpoint.index <= index)
+        {
+            continue;
+        }
+
+        let ticks = server.current_tick() - run.start_tick.unwrap_or(server.current_tick());
+
+        run.checkpoint = Some(checkpoint.index);
+        run.checkpoint_position = checkpoint
+            .respawn_position
+            .unwrap_or_else(|| client.position());
+
+        reached_events.send(CheckpointReached {
+            client: event.client,
+            checkpoint: event.region,
+            index: checkpoint.index,
+            ticks,
+        });
+
+        if checkpoint.finish {
+            commands.entity(event.client).remove::<ParkourRun>();
+
+            completed_events.send(ParkourCompleted {
+                client: event.client,
+                ticks,
+                falls: run.falls,
+            });
+        }
+    }
+}
+
+pub(crate) fn reset_fallen_parkour_players(mut clients: Query<(&mut Client, &mut ParkourRun)>) {
+    for (mut client, mut run) in &mut clients {
+        if client.position().y < run.reset_y {
+            client.set_position(run.checkpoint_position);
+            run.falls += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy_app::App;
+
+    use super::*;
+    use crate::math::Aabb;
+    use crate::unit_test::util::scenario_single_client;
+
+    #[test]
+    fn falling_resets_to_last_checkpoint() {
+        let mut app = App::new();
+        let (client_ent, _) = scenario_single_client(&mut app);
+        let instance_ent = app.world.get::<Client>(client_ent).unwrap().instance();
+
+        app.world.spawn((
+            TriggerRegion::new(instance_ent, Aabb::new([-1.0, -1.0, -1.0], [1.0, 1.0, 1.0])),
+            Checkpoint::new(0).with_respawn_position([0.0, 5.0, 0.0]),
+        ));
+
+        app.world
+            .entity_mut(client_ent)
+            .insert(ParkourRun::new([0.0, 0.0, 0.0], -10.0));
+
+        app.update();
+
+        let run = app.world.get::<ParkourRun>(client_ent).unwrap();
+        assert_eq!(run.checkpoint(), Some(0));
+
+        app.world
+            .get_mut::<Client>(client_ent)
+            .unwrap()
+            .set_position([0.0, -20.0, 0.0]);
+
+        app.update();
+
+        let client = app.world.get::<Client>(client_ent).unwrap();
+        assert_eq!(client.position(), DVec3::new(0.0, 5.0, 0.0));
+
+        let run = app.world.get::<ParkourRun>(client_ent).unwrap();
+        assert_eq!(run.falls(), 1);
+    }
+}
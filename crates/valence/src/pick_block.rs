@@ -0,0 +1,286 @@
+//! Picking blocks and entities with the middle mouse button.
+//!
+//! Clients pick the block or entity under their crosshair to get the matching
+//! item into their hand. The server casts its own ray to find the target, and
+//! looks for the item in the player [`Inventory`] of the client. An item in
+//! the hotbar is selected, and an item elsewhere in the inventory is swapped
+//! into the hotbar first. Clients in creative mode are given the item if they
+//! don't have it yet.
+//!
+//! Every pick sends a [`PickBlockEvent`], even when the client has no
+//! matching item, so servers with custom items can hand out their own:
+//!
+//! ```
+//! use valence::pick_block::PickBlockEvent;
+//! use valence::prelude::*;
+//! use valence::target::Target;
+//!
+//! // Picking diamond ore gives a diamond instead.
+//! fn pick_diamonds(
+//!     mut clients: Query<(&Client, &mut Inventory)>,
+//!     mut picks: EventReader<PickBlockEvent>,
+//! ) {
+//!     for pick in picks.iter() {
+//!         let Some(Target::Block(hit)) = pick.target else {
+//!             continue;
+//!         };
+//!
+//!         if hit.block.to_kind() == BlockKind::DiamondOre {
+//!             if let Ok((client, mut inventory)) = clients.get_mut(pick.client) {
+//!                 let stack = ItemStack::new(ItemKind::Diamond, 1, None);
+//!                 inventory.replace_slot(client.held_item_slot(), stack);
+//!             }
+//!         }
+//!     }
+//! }
+//! ```
+//!
+//! Vanilla clients in creative mode pick items on their own, and only tell
+//! the server about the new contents of their inventory.
+
+use bevy_ecs::prelude::*;
+use valence_protocol::types::GameMode;
+use valence_protocol::{ItemKind, ItemStack};
+
+use crate::client::event::PickItem;
+use crate::client::pose::PoseState;
+use crate::client::Client;
+use crate::crafting::can_stack;
+use crate::enchantment::enchantments;
+use crate::entity::equipment::HeldItem;
+use crate::entity::{McEntity, TrackedData};
+use crate::instance::Instance;
+use crate::inventory::Inventory;
+use crate::target::{cast_ray, Target, DEFAULT_REACH};
+use crate::Despawned;
+
+/// The distance at which clients in creative mode pick blocks and entities.
+const CREATIVE_REACH: f64 = 5.0;
+
+/// An event sent when a client picks the block or entity under its
+/// crosshair.
+#[derive(Clone, PartialEq, Debug)]
+pub struct PickBlockEvent {
+    pub client: Entity,
+    /// The picked block or entity, or `None` if the server found nothing under
+    /// the crosshair of the client.
+    pub target: Option<Target>,
+    /// The item matching the target, if there is one.
+    pub item: Option<ItemStack>,
+    /// The slot of the player inventory holding the picked item, which is
+    /// now held by the client. `None` if the client had no matching item and
+    /// was not given one.
+    pub slot: Option<u16>,
+}
+
+/// Returns the item matching a picked block or entity, like vanilla.
+fn picked_item(
+    target: &Target,
+    entities: &Query<(Entity, &McEntity), Without<Despawned>>,
+) -> Option<ItemStack> {
+    let item = match target {
+        Target::Block(hit) => ItemKind::from_block_kind(hit.block.to_kind()),
+        Target::Entity(hit) => {
+            let (_, mc_entity) = entities.get(hit.entity).ok()?;
+
+            // Item frames are picked as the item in them.
+            let framed = match mc_entity.data() {
+                TrackedData::ItemFrame(frame) => frame.get_item_stack(),
+                TrackedData::GlowItemFrame(frame) => frame.get_item_stack(),
+                _ => None,
+            };
+
+            if let Some(framed) = framed {
+                let mut stack = framed.clone();
+                stack.set_count(1);
+                return Some(stack);
+            }
+
+            let name = mc_entity.kind().translation_key().rsplit('.').next()?;
+
+            ItemKind::from_str(name).or_else(|| ItemKind::from_str(&format!("{name}_spawn_egg")))?
+        }
+    };
+
+    (item != ItemKind::Air).then(|| ItemStack::new(item, 1, None))
+}
+
+/// Returns the hotbar slot a picked item is moved to: the first empty slot
+/// starting from the selected one, or else the first slot without an
+/// enchanted item, or else the selected slot.
+fn swappable_hotbar_slot(inventory: &Inventory, held: &HeldItem) -> u8 {
+    let selected = held.hotbar_slot();
+    let slots = (0..9).map(|i| (selected + i) % 9);
+    let stack = |slot: u8| inventory.slot(36 + slot as u16);
+
+    slots
+        .clone()
+        .find(|&slot| stack(slot).is_none())
+        .or_else(|| {
+            slots
+                .clone()
+                .find(|&slot| stack(slot).map_or(true, |s| enchantments(s).next().is_none()))
+        })
+        .unwrap_or(selected)
+}
+
+/// Moves the picked item into the hand of the client, and returns the slot
+/// holding it.
+fn pick_item(
+    inventory: &mut Inventory,
+    held: &mut HeldItem,
+    item: &ItemStack,
+    creative: bool,
+) -> Option<u16> {
+    let found = (36..45)
+        .chain(9..36)
+        .find(|&slot| inventory.slot(slot).map_or(false, |s| can_stack(s, item)));
+
+    let hotbar_slot = match found {
+        Some(slot @ 36..=44) => (slot - 36) as u8,
+        Some(slot) => {
+            let hotbar_slot = swappable_hotbar_slot(inventory, held);
+            inventory.swap_slot(slot, 36 + hotbar_slot as u16);
+            hotbar_slot
+        }
+        None if creative => {
+            let hotbar_slot = swappable_hotbar_slot(inventory, held);
+            let slot = 36 + hotbar_slot as u16;
+
+            // Make room for the item without throwing away the held one.
+            if inventory.slot(slot).is_some() {
+                if let Some(empty) = (36..45)
+                    .chain(9..36)
+                    .find(|&slot| inventory.slot(slot).is_none())
+                {
+                    inventory.swap_slot(slot, empty);
+                }
+            }
+
+            inventory.replace_slot(slot, item.clone());
+            hotbar_slot
+        }
+        None => return None,
+    };
+
+    if held.hotbar_slot() != hotbar_slot {
+        held.set_hotbar_slot(hotbar_slot);
+    }
+
+    Some(36 + hotbar_slot as u16)
+}
+
+pub(crate) fn pick_blocks(
+    mut clients: Query<(
+        Entity,
+        &Client,
+        Option<&PoseState>,
+        &mut Inventory,
+        &mut HeldItem,
+    )>,
+    instances: Query<&Instance>,
+    entities: Query<(Entity, &McEntity), Without<Despawned>>,
+    mut pick_item_events: EventReader<PickItem>,
+    mut picks: EventWriter<PickBlockEvent>,
+) {
+    for event in pick_item_events.iter() {
+        let Ok(picker) = clients.get_mut(event.client) else {
+            continue;
+        };
+
+        let (client_entity, client, pose, mut inventory, mut held) = picker;
+
+        let creative = match client.game_mode() {
+            GameMode::Spectator => continue,
+            game_mode => game_mode == GameMode::Creative,
+        };
+
+        let reach = if creative {
+            CREATIVE_REACH
+        } else {
+            DEFAULT_REACH
+        };
+
+        let target = cast_ray(client_entity, client, pose, reach, &instances, &entities);
+        let item = target
+            .as_ref()
+            .and_then(|target| picked_item(target, &entities));
+
+        let slot = item
+            .as_ref()
+            .and_then(|item| pick_item(&mut inventory, &mut held, item, creative));
+
+        picks.send(PickBlockEvent {
+            client: event.client,
+            target,
+            item,
+            slot,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy_app::App;
+    use valence_protocol::block::BlockState;
+    use valence_protocol::packets::c2s::play::PickItem as PickItemPacket;
+    use valence_protocol::packets::S2cPlayPacket;
+    use valence_protocol::VarInt;
+
+    use super::*;
+    use crate::assert_packet_count;
+    use crate::instance::Chunk;
+    use crate::unit_test::util::scenario_single_client;
+
+    #[test]
+    fn picked_blocks_are_swapped_into_the_hotbar() -> anyhow::Result<()> {
+        let mut app = App::new();
+        let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+        let instance_ent = app.world.get::<Client>(client_ent).unwrap().instance();
+
+        let mut instance = app.world.get_mut::<Instance>(instance_ent).unwrap();
+        instance.insert_chunk([0, 0], Chunk::default());
+        instance.set_block([0, 1, 3], BlockState::STONE);
+
+        // Look south at the stone.
+        let mut client = app.world.get_mut::<Client>(client_ent).unwrap();
+        client.set_position([0.5, 0.0, 0.5]);
+        client.set_yaw(0.0);
+        client.set_pitch(0.0);
+
+        let sword = ItemStack::new(ItemKind::DiamondSword, 1, None);
+        let stone = ItemStack::new(ItemKind::Stone, 1, None);
+
+        let mut inventory = app.world.get_mut::<Inventory>(client_ent).unwrap();
+        inventory.replace_slot(36, sword.clone());
+        inventory.replace_slot(20, stone.clone());
+
+        app.update();
+        client_helper.clear_sent();
+
+        client_helper.send(&PickItemPacket {
+            slot_to_use: VarInt(20),
+        });
+
+        app.update();
+
+        // The held sword stays, and the stone goes to the next empty slot.
+        let inventory = app.world.get::<Inventory>(client_ent).unwrap();
+        assert_eq!(inventory.slot(36), Some(&sword));
+        assert_eq!(inventory.slot(37), Some(&stone));
+        assert_eq!(inventory.slot(20), None);
+
+        let held = app.world.get::<HeldItem>(client_ent).unwrap();
+        assert_eq!(held.hotbar_slot(), 1);
+
+        let sent_packets = client_helper.collect_sent()?;
+        assert_packet_count!(sent_packets, 1, S2cPlayPacket::SetHeldItemS2c(_));
+
+        let picks = app.world.resource::<Events<PickBlockEvent>>();
+        let pick = picks.get_reader().iter(picks).next().cloned().unwrap();
+        assert_eq!(pick.item, Some(stone));
+        assert_eq!(pick.slot, Some(37));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,278 @@
+//! Player head items and blocks.
+//!
+//! Player heads display the skin of the player they belong to, which is
+//! stored in the `SkullOwner` NBT of the item or block entity. This makes
+//! them useful as icons in inventory menus and as decorations. Clients placing
+//! a player head item get a block with the same owner, and
+//! [`head_drop`] returns the item a head block should drop.
+
+use bevy_ecs::prelude::*;
+use uuid::Uuid;
+use valence_nbt::{compound, Compound, List, Value};
+use valence_protocol::block::{PropName, PropValue};
+use valence_protocol::{BlockFace, BlockKind, BlockState, ItemKind, ItemStack};
+
+use crate::client::event::UseItemOnBlock;
+use crate::client::Client;
+use crate::instance::{Block, BlockRef, Instance};
+use crate::inventory::{consume_held_item, held_item, Inventory};
+use crate::player_textures::PlayerSkin;
+
+/// The player a player head belongs to.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct HeadOwner {
+    /// The UUID of the player.
+    pub uuid: Uuid,
+    /// The username of the player, which is displayed in the name of the item.
+    pub name: Option<String>,
+    /// The skin displayed on the head. If this is `None`, clients look up the
+    /// skin of the player with `uuid` themselves.
+    pub skin: Option<PlayerSkin>,
+}
+
+impl HeadOwner {
+    pub fn new(uuid: Uuid) -> Self {
+        Self {
+            uuid,
+            name: None,
+            skin: None,
+        }
+    }
+
+    #[must_use]
+    pub fn with_name(mut self, name: impl Into<String>) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+
+    #[must_use]
+    pub fn with_skin(mut self, skin: PlayerSkin) -> Self {
+        self.skin = Some(skin);
+        self
+    }
+
+    /// Converts the owner into a `SkullOwner` compound.
+    pub fn to_nbt(&self) -> Compound {
+        let mut nbt = compound! {
+            "Id" => self.uuid,
+        };
+
+        if let Some(name) = &self.name {
+            nbt.insert("Name", name.clone());
+        }
+
+        if let Some(skin) = &self.skin {
+            let mut texture = compound! {
+                "Value" => skin.value.clone(),
+            };
+
+            if let Some(signature) = &skin.signature {
+                texture.insert("Signature", signature.clone());
+            }
+
+            nbt.insert(
+                "Properties",
+                compound! {
+                    "textures" => List::Compound(vec![texture]),
+                },
+            );
+        }
+
+        nbt
+    }
+
+    /// Reads the owner from a `SkullOwner` compound. Returns `None` if the
+    /// compound does not contain a valid UUID.
+    pub fn from_nbt(nbt: &Compound) -> Option<Self> {
+        let Some(Value::IntArray(id)) = nbt.get("Id") else {
+            return None
+        };
+
+        let [a, b, c, d] = id[..] else { return None };
+
+        let uuid = Uuid::from_u64_pair(
+            (a as u32 as u64) << 32 | b as u32 as u64,
+            (c as u32 as u64) << 32 | d as u32 as u64,
+        );
+
+        let name = match nbt.get("Name") {
+            Some(Value::String(name)) => Some(name.clone()),
+            _ => None,
+        };
+
+        let skin = match nbt.get("Properties") {
+            Some(Value::Compound(props)) => match props.get("textures") {
+                Some(Value::List(List::Compound(textures))) => {
+                    textures
+                        .first()
+                        .and_then(|texture| match texture.get("Value") {
+                            Some(Value::String(value)) => Some(PlayerSkin::new(
+                                value.clone(),
+                                match texture.get("Signature") {
+                                    Some(Value::String(signature)) => Some(signature.clone()),
+                                    _ => None,
+                                },
+                            )),
+                            _ => None,
+                        })
+                }
+                _ => None,
+            },
+            _ => None,
+        };
+
+        Some(Self { uuid, name, skin })
+    }
+
+    /// Creates a stack of player head items with this owner.
+    pub fn to_item(&self, count: u8) -> ItemStack {
+        ItemStack::new(
+            ItemKind::PlayerHead,
+            count,
+            Some(compound! {
+                "SkullOwner" => self.to_nbt(),
+            }),
+        )
+    }
+
+    /// Creates a player head block with this owner. `state` must be a
+    /// [`BlockKind::PlayerHead`] or [`BlockKind::PlayerWallHead`] block state.
+    pub fn to_block(&self, state: BlockState) -> Block {
+        Block::with_nbt(
+            state,
+            compound! {
+                "SkullOwner" => self.to_nbt(),
+            },
+        )
+    }
+}
+
+/// Returns the owner of a player head item, if it has one.
+pub fn item_head_owner(stack: &ItemStack) -> Option<HeadOwner> {
+    match stack.nbt.as_ref()?.get("SkullOwner")? {
+        Value::Compound(owner) => HeadOwner::from_nbt(owner),
+        _ => None,
+    }
+}
+
+/// Returns the item dropped by a head or skull block, or `None` if the block
+/// is not a head. The item of a player head keeps the owner of the block.
+pub fn head_drop(block: BlockRef) -> Option<ItemStack> {
+    let kind = block.state().to_kind();
+
+    if !kind.to_str().ends_with("_head") && !kind.to_str().ends_with("_skull") {
+        return None;
+    }
+
+    let nbt = match kind {
+        BlockKind::PlayerHead | BlockKind::PlayerWallHead => {
+            match block.nbt().and_then(|nbt| nbt.get("SkullOwner")) {
+                Some(Value::Compound(owner)) => Some(compound! {
+                    "SkullOwner" => owner.clone(),
+                }),
+                _ => None,
+            }
+        }
+        _ => None,
+    };
+
+    Some(ItemStack::new(kind.to_item_kind(), 1, nbt))
+}
+
+/// Returns the state of the player head placed against `face` by a client
+/// with the given yaw.
+fn placed_head_state(face: BlockFace, yaw: f32) -> Option<BlockState> {
+    let facing = match face {
+        BlockFace::North => PropValue::North,
+        BlockFace::South => PropValue::South,
+        BlockFace::West => PropValue::West,
+        BlockFace::East => PropValue::East,
+        BlockFace::Top | BlockFace::Bottom => {
+            // Standing heads face the client in one of 16 directions.
+            let rotation = ((yaw + 180.0) * 16.0 / 360.0 + 0.5).floor() as i32 & 15;
+
+            return Some(
+                BlockState::PLAYER_HEAD
+                    .set(PropName::Rotation, PropValue::from_u16(rotation as u16)?),
+            );
+        }
+    };
+
+    Some(BlockState::PLAYER_WALL_HEAD.set(PropName::Facing, facing))
+}
+
+pub(crate) fn place_player_heads(
+    mut clients: Query<(&Client, &mut Inventory)>,
+    mut instances: Query<&mut Instance>,
+    mut use_item_on_block: EventReader<UseItemOnBlock>,
+) {
+    for event in use_item_on_block.iter() {
+        let Ok((client, mut inventory)) = clients.get_mut(event.client) else {
+            continue
+        };
+
+        let Some(stack) = held_item(client, &inventory, event.hand) else {
+            continue
+        };
+
+        if stack.item != ItemKind::PlayerHead {
+            continue;
+        }
+
+        let Ok(mut instance) = instances.get_mut(client.instance()) else {
+            continue
+        };
+
+        let pos = event.position.get_in_direction(event.face);
+
+        if !instance
+            .block(pos)
+            .map_or(false, |block| block.state().is_replaceable())
+        {
+            continue;
+        }
+
+        let Some(state) = placed_head_state(event.face, client.yaw()) else {
+            continue
+        };
+
+        let block = match item_head_owner(stack) {
+            Some(owner) => owner.to_block(state),
+            None => Block::new(state),
+        };
+
+        instance.set_block(pos, block);
+
+        consume_held_item(client, &mut inventory, event.hand);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::instance::Chunk;
+
+    #[test]
+    fn head_owner_round_trip() {
+        let owner = HeadOwner::new(Uuid::from_u128(0x0123456789abcdef_fedcba9876543210))
+            .with_name("Herobrine")
+            .with_skin(PlayerSkin::new("dGV4dHVyZXM=", Some("c2lnbmF0dXJl".into())));
+
+        assert_eq!(HeadOwner::from_nbt(&owner.to_nbt()), Some(owner.clone()));
+
+        let item = owner.to_item(1);
+        assert_eq!(item_head_owner(&item), Some(owner.clone()));
+
+        let mut chunk = Chunk::new(1);
+        chunk.set_block(1, 2, 3, owner.to_block(BlockState::PLAYER_HEAD));
+
+        let drop = head_drop(chunk.block(1, 2, 3)).unwrap();
+        assert_eq!(drop, item);
+
+        chunk.set_block(1, 2, 3, BlockState::ZOMBIE_WALL_HEAD);
+        assert_eq!(
+            head_drop(chunk.block(1, 2, 3)),
+            Some(ItemStack::new(ItemKind::ZombieHead, 1, None))
+        );
+    }
+}
@@ -1,23 +1,31 @@
 use std::borrow::Cow;
 use std::collections::hash_map::{Entry as MapEntry, OccupiedEntry as OccupiedMapEntry};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::iter::FusedIterator;
 use std::mem;
 
 use bevy_ecs::prelude::*;
 use tracing::warn;
 use uuid::Uuid;
-use valence_protocol::packets::s2c::play::{PlayerInfoRemove, SetTabListHeaderAndFooter};
+use valence_protocol::packets::s2c::play::{
+    PlayerInfoRemove, SetTabListHeaderAndFooter, UpdateTeams,
+};
 use valence_protocol::packets::s2c::player_info_update::{
     Actions, Entry as PlayerInfoEntry, PlayerInfoUpdate,
 };
+use valence_protocol::packets::s2c::update_teams::{
+    CollisionRule, NameTagVisibility, TeamColor, TeamFlags, UpdateTeamsMode,
+};
 use valence_protocol::types::{GameMode, Property};
 use valence_protocol::Text;
 
+use crate::client::profile::GameProfile;
 use crate::client::Client;
 use crate::packet::{PacketWriter, WritePacket};
 use crate::server::Server;
 
+pub mod layout;
+
 /// The global list of players on a server visible by pressing the tab key by
 /// default.
 ///
@@ -46,12 +54,18 @@ pub struct PlayerList {
     header: Text,
     footer: Text,
     modified_header_or_footer: bool,
+    /// The sort keys of the teams that clients know about.
+    sort_teams: BTreeSet<u16>,
+    /// The sort key and username of the entries that clients know to be in a
+    /// sorting team.
+    sort_team_members: HashMap<Uuid, (u16, String)>,
 }
 
 impl PlayerList {
     /// Returns a set of systems for maintaining the player list in a reasonable
     /// default way. When clients connect, they are added to the player list.
-    /// When clients disconnect, they are removed from the player list.
+    /// When clients disconnect, they are removed from the player list. When the
+    /// [`GameProfile`] of a client changes, its entry is updated.
     pub fn default_system_set() -> SystemSet {
         fn add_new_clients_to_player_list(
             clients: Query<&Client, Added<Client>>,
@@ -68,6 +82,20 @@ impl PlayerList {
             }
         }
 
+        fn update_player_list_properties(
+            profiles: Query<&GameProfile, Changed<GameProfile>>,
+            mut player_list: ResMut<PlayerList>,
+        ) {
+            for profile in &profiles {
+                if let Entry::Occupied(mut oe) = player_list.entry(profile.uuid()) {
+                    if oe.get().properties() != profile.properties() {
+                        let entry = oe.get().clone().with_properties(profile.properties());
+                        oe.insert(entry);
+                    }
+                }
+            }
+        }
+
         fn remove_disconnected_clients_from_player_list(
             clients: Query<&mut Client>,
             mut player_list: ResMut<PlayerList>,
@@ -81,6 +109,7 @@ impl PlayerList {
 
         SystemSet::new()
             .with_system(add_new_clients_to_player_list)
+            .with_system(update_player_list_properties.after(add_new_clients_to_player_list))
             .with_system(remove_disconnected_clients_from_player_list)
     }
 }
@@ -94,6 +123,8 @@ impl PlayerList {
             header: Text::default(),
             footer: Text::default(),
             modified_header_or_footer: false,
+            sort_teams: BTreeSet::new(),
+            sort_team_members: HashMap::new(),
         }
     }
 
@@ -248,6 +279,20 @@ impl PlayerList {
             });
         }
 
+        for &sort_key in &self.sort_teams {
+            let members = self
+                .sort_team_members
+                .values()
+                .filter(|(key, _)| *key == sort_key)
+                .map(|(_, username)| username.as_str())
+                .collect();
+
+            writer.write_packet(&UpdateTeams {
+                team_name: &sort_team_name(sort_key),
+                mode: create_sort_team(members),
+            });
+        }
+
         if !self.header.is_empty() || !self.footer.is_empty() {
             writer.write_packet(&SetTabListHeaderAndFooter {
                 header: (&self.header).into(),
@@ -276,7 +321,11 @@ pub struct PlayerListEntry {
     display_name: Option<Text>,
     listed: bool,
     old_listed: bool,
+    sort_key: Option<u16>,
     is_new: bool,
+    /// If this entry replaced an entry with the same UUID that clients may
+    /// still know about.
+    is_replacement: bool,
     modified_ping: bool,
     modified_display_name: bool,
 }
@@ -292,7 +341,9 @@ impl Default for PlayerListEntry {
             display_name: None,
             old_listed: true,
             listed: true,
+            sort_key: None,
             is_new: true,
+            is_replacement: false,
             modified_ping: false,
             modified_display_name: false,
         }
@@ -382,6 +433,14 @@ impl PlayerListEntry {
         self
     }
 
+    /// Set the sort key for the player list entry. Returns `Self` to chain
+    /// other options. See [`Self::set_sort_key`].
+    #[must_use]
+    pub fn with_sort_key(mut self, sort_key: impl Into<Option<u16>>) -> Self {
+        self.sort_key = sort_key.into();
+        self
+    }
+
     pub fn username(&self) -> &str {
         &self.username
     }
@@ -447,6 +506,22 @@ impl PlayerListEntry {
         self.listed = listed;
     }
 
+    pub fn sort_key(&self) -> Option<u16> {
+        self.sort_key
+    }
+
+    /// Set the sort key for the player list entry.
+    ///
+    /// Entries are grouped by their sort key in the tab list, with lower keys
+    /// displayed first. Entries without a sort key are displayed before all
+    /// other entries and spectators are always displayed last.
+    ///
+    /// Sorting is implemented by putting the entry in a team named after the
+    /// key, so the entry's player entity is part of that team as well.
+    pub fn set_sort_key(&mut self, sort_key: impl Into<Option<u16>>) {
+        self.sort_key = sort_key.into();
+    }
+
     fn clear_trackers(&mut self) {
         self.old_game_mode = self.game_mode;
         self.old_listed = self.listed;
@@ -503,13 +578,17 @@ impl<'a> OccupiedEntry<'a> {
         if old_entry.username != entry.username || old_entry.properties != entry.properties {
             entry.clear_trackers();
             entry.is_new = true;
+            entry.is_replacement = true;
             self.entry.insert(Some(entry)).unwrap()
         } else {
+            let old_sort_key = mem::replace(&mut old_entry.sort_key, entry.sort_key);
+
             PlayerListEntry::new()
                 .with_game_mode(old_entry.game_mode)
                 .with_ping(old_entry.ping)
                 .with_display_name(old_entry.set_display_name(entry.display_name))
                 .with_listed(old_entry.listed)
+                .with_sort_key(old_sort_key)
         }
     }
 
@@ -538,6 +617,9 @@ impl<'a> VacantEntry<'a> {
 
         match self.entry {
             MapEntry::Occupied(mut oe) => {
+                // The old entry was removed this tick, but clients have not been
+                // told about it yet.
+                entry.is_replacement = true;
                 oe.insert(Some(entry));
                 oe.into_mut().as_mut().unwrap()
             }
@@ -560,9 +642,25 @@ pub(crate) fn update_player_list(
     let mut writer = PacketWriter::new(
         &mut pl.cached_update_packets,
         server.compression_threshold(),
+        server.compression_level(),
         &mut scratch,
     );
 
+    // Clients ignore new entries with the UUID of an existing entry, so replaced
+    // entries need to be removed first.
+    let replaced: Vec<_> = pl
+        .entries
+        .iter()
+        .filter(|(_, entry)| entry.as_ref().map_or(false, |e| e.is_replacement))
+        .map(|(&uuid, _)| uuid)
+        .collect();
+
+    if !replaced.is_empty() {
+        writer.write_packet(&PlayerInfoRemove {
+            uuids: replaced.into(),
+        });
+    }
+
     let mut removed = vec![];
 
     pl.entries.retain(|&uuid, entry| {
@@ -573,6 +671,7 @@ pub(crate) fn update_player_list(
 
         if entry.is_new {
             entry.is_new = false;
+            entry.is_replacement = false;
 
             // Send packets to initialize this entry.
 
@@ -663,6 +762,62 @@ pub(crate) fn update_player_list(
         });
     }
 
+    // Move entries between the teams used for sorting.
+    let mut left: BTreeMap<u16, Vec<String>> = BTreeMap::new();
+    let mut joined: BTreeMap<u16, Vec<&str>> = BTreeMap::new();
+
+    pl.sort_team_members.retain(|uuid, (key, username)| {
+        let current = pl
+            .entries
+            .get(uuid)
+            .and_then(|e| e.as_ref())
+            .and_then(|e| e.sort_key.map(|k| (k, &e.username)));
+
+        if current == Some((*key, &*username)) {
+            true
+        } else {
+            left.entry(*key).or_default().push(mem::take(username));
+            false
+        }
+    });
+
+    for (&uuid, entry) in &pl.entries {
+        let Some(entry) = entry else {
+            continue
+        };
+
+        let Some(key) = entry.sort_key else {
+            continue
+        };
+
+        if let MapEntry::Vacant(ve) = pl.sort_team_members.entry(uuid) {
+            ve.insert((key, entry.username.clone()));
+            joined.entry(key).or_default().push(&entry.username);
+        }
+    }
+
+    for (key, usernames) in &left {
+        writer.write_packet(&UpdateTeams {
+            team_name: &sort_team_name(*key),
+            mode: UpdateTeamsMode::RemoveEntities {
+                entities: usernames.iter().map(String::as_str).collect(),
+            },
+        });
+    }
+
+    for (key, usernames) in joined {
+        writer.write_packet(&UpdateTeams {
+            team_name: &sort_team_name(key),
+            mode: if pl.sort_teams.insert(key) {
+                create_sort_team(usernames)
+            } else {
+                UpdateTeamsMode::AddEntities {
+                    entities: usernames,
+                }
+            },
+        });
+    }
+
     if pl.modified_header_or_footer {
         pl.modified_header_or_footer = false;
 
@@ -680,3 +835,110 @@ pub(crate) fn update_player_list(
         }
     }
 }
+
+fn sort_team_name(sort_key: u16) -> String {
+    // Teams are sorted by name, so the key is padded with zeros.
+    format!("sort_{sort_key:05}")
+}
+
+fn create_sort_team<'a>(entities: Vec<&'a str>) -> UpdateTeamsMode<'a> {
+    UpdateTeamsMode::CreateTeam {
+        team_display_name: Cow::Owned(Text::default()),
+        friendly_flags: TeamFlags::new().with_friendly_fire(true),
+        name_tag_visibility: NameTagVisibility::Always,
+        collision_rule: CollisionRule::Always,
+        team_color: TeamColor::Reset,
+        team_prefix: Cow::Owned(Text::default()),
+        team_suffix: Cow::Owned(Text::default()),
+        entities,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy_app::App;
+    use valence_protocol::packets::S2cPlayPacket;
+
+    use super::*;
+    use crate::unit_test::util::scenario_single_client;
+
+    fn team_updates(packets: &[S2cPlayPacket]) -> Vec<(String, &'static str, usize)> {
+        packets
+            .iter()
+            .filter_map(|packet| match packet {
+                S2cPlayPacket::UpdateTeams(p) => {
+                    let (mode, entities) = match &p.mode {
+                        UpdateTeamsMode::CreateTeam { entities, .. } => ("create", entities.len()),
+                        UpdateTeamsMode::AddEntities { entities } => ("add", entities.len()),
+                        UpdateTeamsMode::RemoveEntities { entities } => ("remove", entities.len()),
+                        _ => ("other", 0),
+                    };
+
+                    Some((p.team_name.to_owned(), mode, entities))
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn entries_are_moved_between_sort_teams() -> anyhow::Result<()> {
+        let mut app = App::new();
+        let (_, mut client_helper) = scenario_single_client(&mut app);
+
+        app.update();
+        client_helper.clear_sent();
+
+        let staff = Uuid::from_u128(1);
+        let player = Uuid::from_u128(2);
+
+        let mut pl = app.world.resource_mut::<PlayerList>();
+        pl.insert(
+            staff,
+            PlayerListEntry::new()
+                .with_username("staff")
+                .with_sort_key(0),
+        );
+        pl.insert(
+            player,
+            PlayerListEntry::new()
+                .with_username("player")
+                .with_sort_key(5),
+        );
+
+        app.update();
+
+        let sent_packets = client_helper.collect_sent()?;
+        let mut updates = team_updates(&sent_packets);
+        updates.sort();
+        assert_eq!(
+            updates,
+            [
+                ("sort_00000".into(), "create", 1),
+                ("sort_00005".into(), "create", 1)
+            ]
+        );
+
+        client_helper.clear_sent();
+
+        // Demote the staff member.
+        app.world
+            .resource_mut::<PlayerList>()
+            .get_mut(staff)
+            .unwrap()
+            .set_sort_key(5);
+
+        app.update();
+
+        let sent_packets = client_helper.collect_sent()?;
+        assert_eq!(
+            team_updates(&sent_packets),
+            [
+                ("sort_00000".into(), "remove", 1),
+                ("sort_00005".into(), "add", 1)
+            ]
+        );
+
+        Ok(())
+    }
+}
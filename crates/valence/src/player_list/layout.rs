@@ -0,0 +1,400 @@
+//! Custom tab list layouts made of synthetic entries.
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+
+use bevy_ecs::prelude::*;
+use uuid::Uuid;
+use valence_protocol::packets::s2c::play::PlayerInfoRemove;
+use valence_protocol::packets::s2c::player_info_update::{
+    Actions, Entry as PlayerInfoEntry, PlayerInfoUpdate,
+};
+use valence_protocol::types::{GameMode, Property};
+use valence_protocol::Text;
+
+use crate::client::Client;
+use crate::player_textures::PlayerSkin;
+
+/// The number of rows in each column of the tab list.
+pub const TAB_LAYOUT_ROWS: usize = 20;
+
+/// The maximum number of columns the vanilla client displays.
+pub const TAB_LAYOUT_MAX_COLUMNS: usize = 4;
+
+/// The UUIDs of the synthetic entries are this value plus the index of the
+/// slot.
+const SLOT_UUID_BASE: u128 = 0x7ab1_a70e_0000_4000_8000_0000_0000_0000;
+
+/// A [`Component`] that replaces the tab list of the [`Client`] on the same
+/// entity with a fixed grid of text slots.
+///
+/// Each slot is a synthetic player list entry whose username sorts it into
+/// its position in the grid. The text of a slot may contain placeholders such
+/// as `{name}` which are resolved for each viewer with the
+/// [`TabPlaceholders`] resource. Only the slots that changed are sent to the
+/// client, batched into a single packet per tick.
+///
+/// Entries of the [`PlayerList`](super::PlayerList) are still displayed
+/// after the layout unless they are unlisted.
+#[derive(Component, Clone, Debug)]
+pub struct TabLayout {
+    columns: usize,
+    slots: Vec<TabSlot>,
+    /// The state of each slot the client currently knows about.
+    sent: Vec<Option<TabSlot>>,
+}
+
+/// A single slot in a [`TabLayout`].
+#[derive(Clone, PartialEq, Eq, Default, Debug)]
+pub struct TabSlot {
+    /// The text of the slot. Placeholders in braces are replaced with the
+    /// values from the [`TabPlaceholders`] resource and legacy `§` formatting
+    /// codes are supported.
+    pub text: String,
+    /// The skin whose face is shown as the icon of the slot. The default skin
+    /// is shown if this is `None`.
+    pub icon: Option<PlayerSkin>,
+    /// The ping shown for this slot, or `-1` to hide it.
+    pub ping: i32,
+}
+
+impl TabSlot {
+    pub fn new(text: impl Into<String>) -> Self {
+        Self {
+            text: text.into(),
+            icon: None,
+            ping: -1,
+        }
+    }
+
+    #[must_use]
+    pub fn with_icon(mut self, icon: PlayerSkin) -> Self {
+        self.icon = Some(icon);
+        self
+    }
+
+    #[must_use]
+    pub fn with_ping(mut self, ping: i32) -> Self {
+        self.ping = ping;
+        self
+    }
+}
+
+impl TabLayout {
+    /// Creates an empty layout with the given number of columns, clamped to
+    /// `1..=4`. Every column has [`TAB_LAYOUT_ROWS`] rows.
+    pub fn new(columns: usize) -> Self {
+        let columns = columns.clamp(1, TAB_LAYOUT_MAX_COLUMNS);
+
+        Self {
+            columns,
+            slots: vec![TabSlot::default(); columns * TAB_LAYOUT_ROWS],
+            sent: vec![None; columns * TAB_LAYOUT_ROWS],
+        }
+    }
+
+    pub fn columns(&self) -> usize {
+        self.columns
+    }
+
+    /// Returns the slot in the given column and row.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the column or row is out of bounds.
+    pub fn slot(&self, column: usize, row: usize) -> &TabSlot {
+        &self.slots[self.index(column, row)]
+    }
+
+    /// Returns a mutable reference to the slot in the given column and row.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the column or row is out of bounds.
+    pub fn slot_mut(&mut self, column: usize, row: usize) -> &mut TabSlot {
+        let idx = self.index(column, row);
+        &mut self.slots[idx]
+    }
+
+    /// Sets the slot in the given column and row.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the column or row is out of bounds.
+    pub fn set_slot(&mut self, column: usize, row: usize, slot: TabSlot) {
+        *self.slot_mut(column, row) = slot;
+    }
+
+    fn index(&self, column: usize, row: usize) -> usize {
+        assert!(column < self.columns, "tab layout column out of bounds");
+        assert!(row < TAB_LAYOUT_ROWS, "tab layout row out of bounds");
+
+        // The client fills the tab list column by column.
+        column * TAB_LAYOUT_ROWS + row
+    }
+}
+
+fn slot_uuid(idx: usize) -> Uuid {
+    Uuid::from_u128(SLOT_UUID_BASE + idx as u128)
+}
+
+/// The client sorts entries without a team by username, so the index is
+/// encoded in the username. `!` sorts before all characters allowed in
+/// regular usernames.
+fn slot_username(idx: usize) -> String {
+    format!("!tab{idx:03}")
+}
+
+type Resolver = Box<dyn Fn(&Client) -> String + Send + Sync>;
+
+/// A [`Resource`] with the placeholders available in the text of
+/// [`TabLayout`] slots.
+///
+/// The `{name}` and `{ping}` placeholders are available by default and
+/// resolve to the username and ping of the viewer.
+#[derive(Resource)]
+pub struct TabPlaceholders {
+    resolvers: HashMap<String, Resolver>,
+}
+
+impl Default for TabPlaceholders {
+    fn default() -> Self {
+        let mut placeholders = Self {
+            resolvers: HashMap::new(),
+        };
+
+        placeholders.insert("name", |client| client.username().to_string());
+        placeholders.insert("ping", |client| client.ping().to_string());
+
+        placeholders
+    }
+}
+
+impl TabPlaceholders {
+    /// Adds a placeholder which is replaced with the output of `resolver` for
+    /// each viewer. An existing placeholder with the same name is replaced.
+    pub fn insert(
+        &mut self,
+        name: impl Into<String>,
+        resolver: impl Fn(&Client) -> String + Send + Sync + 'static,
+    ) {
+        self.resolvers.insert(name.into(), Box::new(resolver));
+    }
+
+    pub fn remove(&mut self, name: &str) -> bool {
+        self.resolvers.remove(name).is_some()
+    }
+
+    /// Replaces the placeholders in `text` with their values for `viewer`.
+    /// Unknown placeholders are left as they are.
+    pub fn resolve(&self, text: &str, viewer: &Client) -> String {
+        let mut res = String::with_capacity(text.len());
+        let mut rest = text;
+
+        while let Some(start) = rest.find('{') {
+            res.push_str(&rest[..start]);
+            rest = &rest[start..];
+
+            let Some(end) = rest.find('}') else {
+                break
+            };
+
+            match self.resolvers.get(&rest[1..end]) {
+                Some(resolver) => res.push_str(&resolver(viewer)),
+                None => res.push_str(&rest[..=end]),
+            }
+
+            rest = &rest[end + 1..];
+        }
+
+        res.push_str(rest);
+        res
+    }
+}
+
+pub(crate) fn update_tab_layouts(
+    mut clients: Query<(&mut Client, &mut TabLayout)>,
+    placeholders: Res<TabPlaceholders>,
+) {
+    for (mut client, mut layout) in &mut clients {
+        let layout = &mut *layout;
+
+        let mut removed = vec![];
+        let mut added = vec![];
+        let mut updated = vec![];
+
+        for (idx, (slot, sent)) in layout.slots.iter().zip(&mut layout.sent).enumerate() {
+            let resolved = TabSlot {
+                text: placeholders.resolve(&slot.text, &client),
+                icon: slot.icon.clone(),
+                ping: slot.ping,
+            };
+
+            match sent {
+                Some(sent) if *sent == resolved => continue,
+                // The properties of an entry cannot be changed, so the entry is added again.
+                Some(sent) if sent.icon != resolved.icon => {
+                    removed.push(slot_uuid(idx));
+                    added.push(idx);
+                }
+                Some(_) => updated.push(idx),
+                None => added.push(idx),
+            }
+
+            *sent = Some(resolved);
+        }
+
+        let usernames: Vec<_> = added.iter().map(|&idx| slot_username(idx)).collect();
+        let properties: Vec<Vec<Property>> = added
+            .iter()
+            .map(|&idx| {
+                let slot = layout.sent[idx].as_ref().unwrap();
+                slot.icon.iter().map(PlayerSkin::to_property).collect()
+            })
+            .collect();
+        let texts: Vec<_> = layout
+            .sent
+            .iter()
+            .map(|slot| slot.as_ref().map(|s| Text::from(s.text.clone())))
+            .collect();
+
+        let entry = |idx: usize, username, properties| PlayerInfoEntry {
+            player_uuid: slot_uuid(idx),
+            username,
+            properties,
+            chat_data: None,
+            listed: true,
+            ping: layout.sent[idx].as_ref().map_or(-1, |slot| slot.ping),
+            game_mode: GameMode::Survival,
+            display_name: texts[idx].as_ref().map(Cow::Borrowed),
+        };
+
+        if !removed.is_empty() {
+            client.write_packet(&PlayerInfoRemove {
+                uuids: removed.into(),
+            });
+        }
+
+        if !added.is_empty() {
+            let entries: Vec<_> = added
+                .iter()
+                .zip(&usernames)
+                .zip(&properties)
+                .map(|((&idx, username), props)| {
+                    entry(idx, username.as_str(), Cow::Borrowed(props))
+                })
+                .collect();
+
+            client.write_packet(&PlayerInfoUpdate {
+                actions: Actions::new()
+                    .with_add_player(true)
+                    .with_update_listed(true)
+                    .with_update_latency(true)
+                    .with_update_display_name(true),
+                entries: entries.into(),
+            });
+        }
+
+        if !updated.is_empty() {
+            let entries: Vec<_> = updated
+                .iter()
+                .map(|&idx| entry(idx, "", Cow::Borrowed(&[])))
+                .collect();
+
+            client.write_packet(&PlayerInfoUpdate {
+                actions: Actions::new()
+                    .with_update_latency(true)
+                    .with_update_display_name(true),
+                entries: entries.into(),
+            });
+        }
+    }
+}
+
+/// Removes the synthetic entries from clients whose [`TabLayout`] was removed.
+pub(crate) fn remove_tab_layouts(
+    removed: RemovedComponents<TabLayout>,
+    mut clients: Query<&mut Client, Without<TabLayout>>,
+) {
+    for entity in removed.iter() {
+        if let Ok(mut client) = clients.get_mut(entity) {
+            let uuids: Vec<_> = (0..TAB_LAYOUT_MAX_COLUMNS * TAB_LAYOUT_ROWS)
+                .map(slot_uuid)
+                .collect();
+
+            client.write_packet(&PlayerInfoRemove {
+                uuids: uuids.into(),
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy_app::App;
+    use valence_protocol::packets::S2cPlayPacket;
+
+    use super::*;
+    use crate::unit_test::util::scenario_single_client;
+
+    #[test]
+    fn slots_are_sorted_by_username() {
+        let mut usernames: Vec<_> = (0..TAB_LAYOUT_MAX_COLUMNS * TAB_LAYOUT_ROWS)
+            .map(slot_username)
+            .collect();
+
+        assert!(usernames.iter().all(|u| u.len() <= 16));
+
+        let unsorted = usernames.clone();
+        usernames.sort_by_key(|u| u.to_lowercase());
+        assert_eq!(usernames, unsorted);
+    }
+
+    #[test]
+    fn only_changed_slots_are_sent() -> anyhow::Result<()> {
+        let mut app = App::new();
+        let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+
+        let mut layout = TabLayout::new(1);
+        layout.set_slot(0, 0, TabSlot::new("Hello, {name}!"));
+        app.world.entity_mut(client_ent).insert(layout);
+
+        app.update();
+
+        let sent_packets = client_helper.collect_sent()?;
+        let added: Vec<_> = sent_packets
+            .iter()
+            .filter_map(|p| match p {
+                S2cPlayPacket::PlayerInfoUpdate(p) if p.actions.add_player() => {
+                    Some(p.entries.len())
+                }
+                _ => None,
+            })
+            .collect();
+
+        assert_eq!(added, [TAB_LAYOUT_ROWS]);
+
+        client_helper.clear_sent();
+
+        app.world
+            .get_mut::<TabLayout>(client_ent)
+            .unwrap()
+            .slot_mut(0, 1)
+            .text = "Changed".into();
+
+        app.update();
+
+        let sent_packets = client_helper.collect_sent()?;
+        let updated: Vec<_> = sent_packets
+            .iter()
+            .filter_map(|p| match p {
+                S2cPlayPacket::PlayerInfoUpdate(p) => Some(p.entries.len()),
+                _ => None,
+            })
+            .collect();
+
+        assert_eq!(updated, [1]);
+
+        Ok(())
+    }
+}
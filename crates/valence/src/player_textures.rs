@@ -1,11 +1,22 @@
 //! Player skins and capes.
 
+use std::collections::{HashMap, HashSet};
+use std::time::{Duration, Instant};
+
 use anyhow::Context;
 use base64::prelude::*;
+use bevy_ecs::prelude::*;
+use flume::{Receiver, Sender};
 use serde::Deserialize;
+use tracing::warn;
 use url::Url;
+use uuid::Uuid;
 use valence_protocol::types::Property;
 
+use crate::entity::McEntity;
+use crate::player_list::{Entry, PlayerList, PlayerListEntry};
+use crate::server::Server;
+
 /// Contains URLs to the skin and cape of a player.
 #[derive(Clone, PartialEq, Eq, Debug)]
 pub struct PlayerTextures {
@@ -51,3 +62,297 @@ impl PlayerTextures {
         })
     }
 }
+
+/// A [`Component`] that sets the skin of the player [`McEntity`] on the same
+/// entity.
+///
+/// Clients look up the skin of a player entity in the [`PlayerList`] entry
+/// with the entity's UUID. When this component is added or changed, the
+/// textures of that entry are replaced (an unlisted entry is created if there
+/// is none) and the entity is respawned for every client viewing it. This
+/// works both for the entities of connected players and for NPCs.
+///
+/// A client does not see the change to its own skin until it is respawned.
+#[derive(Component, Clone, PartialEq, Eq, Debug)]
+pub struct PlayerSkin {
+    /// The base64 encoded textures payload.
+    pub value: String,
+    /// Mojang's signature of `value`, if the textures were signed.
+    pub signature: Option<String>,
+}
+
+impl PlayerSkin {
+    pub fn new(value: impl Into<String>, signature: Option<String>) -> Self {
+        Self {
+            value: value.into(),
+            signature,
+        }
+    }
+
+    /// Gets the skin from the `textures` property in a list of profile
+    /// properties, such as
+    /// [`Client::properties`](crate::client::Client::properties).
+    pub fn from_properties(props: &[Property]) -> Option<Self> {
+        props
+            .iter()
+            .find(|p| p.name == "textures")
+            .map(|p| Self::new(p.value.clone(), p.signature.clone()))
+    }
+
+    /// Converts the skin into a `textures` profile property.
+    pub fn to_property(&self) -> Property {
+        Property {
+            name: "textures".into(),
+            value: self.value.clone(),
+            signature: self.signature.clone(),
+        }
+    }
+
+    /// Decodes the URLs of the skin and cape in this skin's payload.
+    pub fn textures(&self) -> anyhow::Result<PlayerTextures> {
+        PlayerTextures::from_properties(&[self.to_property()])
+    }
+}
+
+/// A [`Component`] that requests the skin of a Mojang account. Once the skin
+/// is fetched, this component is replaced with a [`PlayerSkin`] component.
+///
+/// Skins are fetched from Mojang's API in the background and cached in the
+/// [`SkinCache`] resource. If the request fails, a warning is logged and this
+/// component is removed.
+#[derive(Component, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum FetchSkin {
+    /// Fetch the skin of the account with the given username.
+    Username(String),
+    /// Fetch the skin of the account with the given UUID.
+    Uuid(Uuid),
+}
+
+/// A [`Resource`] containing the skins fetched with [`FetchSkin`].
+#[derive(Resource, Debug)]
+pub struct SkinCache {
+    /// How long a fetched skin is used for before it is requested again.
+    ///
+    /// # Default Value
+    ///
+    /// 10 minutes
+    pub ttl: Duration,
+    entries: HashMap<FetchSkin, (PlayerSkin, Instant)>,
+    pending: HashSet<FetchSkin>,
+    results_send: Sender<(FetchSkin, anyhow::Result<PlayerSkin>)>,
+    results_recv: Receiver<(FetchSkin, anyhow::Result<PlayerSkin>)>,
+}
+
+impl Default for SkinCache {
+    fn default() -> Self {
+        let (results_send, results_recv) = flume::unbounded();
+
+        Self {
+            ttl: Duration::from_secs(10 * 60),
+            entries: HashMap::new(),
+            pending: HashSet::new(),
+            results_send,
+            results_recv,
+        }
+    }
+}
+
+impl SkinCache {
+    /// Gets a cached skin if it has not expired yet.
+    pub fn get(&self, source: &FetchSkin) -> Option<&PlayerSkin> {
+        self.entries
+            .get(source)
+            .filter(|(_, fetched)| fetched.elapsed() < self.ttl)
+            .map(|(skin, _)| skin)
+    }
+
+    /// Inserts a skin into the cache, as if it was just fetched.
+    pub fn insert(&mut self, source: FetchSkin, skin: PlayerSkin) {
+        self.entries.insert(source, (skin, Instant::now()));
+    }
+
+    /// Removes all cached skins.
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
+
+async fn fetch_skin(http: reqwest::Client, source: FetchSkin) -> anyhow::Result<PlayerSkin> {
+    let uuid = match source {
+        FetchSkin::Username(username) => {
+            #[derive(Debug, Deserialize)]
+            struct Profile {
+                id: Uuid,
+            }
+
+            let url = format!("https://api.mojang.com/users/profiles/minecraft/{username}");
+
+            let profile: Profile = http
+                .get(url)
+                .send()
+                .await?
+                .error_for_status()?
+                .json()
+                .await
+                .with_context(|| format!("no account with username \"{username}\""))?;
+
+            profile.id
+        }
+        FetchSkin::Uuid(uuid) => uuid,
+    };
+
+    #[derive(Debug, Deserialize)]
+    struct GameProfile {
+        properties: Vec<Property>,
+    }
+
+    let url = format!(
+        "https://sessionserver.mojang.com/session/minecraft/profile/{}?unsigned=false",
+        uuid.simple()
+    );
+
+    let profile: GameProfile = http
+        .get(url)
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await
+        .context("parsing game profile")?;
+
+    PlayerSkin::from_properties(&profile.properties).context("no textures in game profile")
+}
+
+pub(crate) fn fetch_skins(
+    mut commands: Commands,
+    server: Res<Server>,
+    mut cache: ResMut<SkinCache>,
+    requests: Query<(Entity, &FetchSkin)>,
+) {
+    let mut failed = HashSet::new();
+
+    for (source, result) in cache.results_recv.try_iter().collect::<Vec<_>>() {
+        cache.pending.remove(&source);
+
+        match result {
+            Ok(skin) => cache.insert(source, skin),
+            Err(e) => {
+                warn!("failed to fetch skin for {source:?}: {e:#}");
+                failed.insert(source);
+            }
+        }
+    }
+
+    for (entity, source) in &requests {
+        if let Some(skin) = cache.get(source) {
+            commands
+                .entity(entity)
+                .insert(skin.clone())
+                .remove::<FetchSkin>();
+        } else if failed.contains(source) {
+            commands.entity(entity).remove::<FetchSkin>();
+        } else if cache.pending.insert(source.clone()) {
+            let http = server.http_client().clone();
+            let results_send = cache.results_send.clone();
+            let source = source.clone();
+
+            server.tokio_handle().spawn(async move {
+                let result = fetch_skin(http, source.clone()).await;
+                let _ = results_send.send((source, result));
+            });
+        }
+    }
+}
+
+pub(crate) fn update_player_skins(
+    mut entities: Query<(&PlayerSkin, &mut McEntity), Changed<PlayerSkin>>,
+    mut player_list: ResMut<PlayerList>,
+) {
+    for (skin, mut entity) in &mut entities {
+        match player_list.entry(entity.uuid()) {
+            Entry::Occupied(mut oe) => {
+                let mut properties: Vec<_> = oe
+                    .get()
+                    .properties()
+                    .iter()
+                    .filter(|p| p.name != "textures")
+                    .cloned()
+                    .collect();
+
+                properties.push(skin.to_property());
+
+                let new_entry = oe.get().clone().with_properties(properties);
+                oe.insert(new_entry);
+            }
+            Entry::Vacant(ve) => {
+                ve.insert(
+                    PlayerListEntry::new()
+                        .with_properties(vec![skin.to_property()])
+                        .with_listed(false),
+                );
+            }
+        }
+
+        entity.respawn();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy_app::App;
+    use valence_protocol::packets::S2cPlayPacket;
+
+    use super::*;
+    use crate::client::Client;
+    use crate::entity::EntityKind;
+    use crate::instance::{Chunk, Instance};
+    use crate::unit_test::util::scenario_single_client;
+    use crate::{assert_packet_count, assert_packet_order};
+
+    #[test]
+    fn skin_change_respawns_entity() -> anyhow::Result<()> {
+        let mut app = App::new();
+        let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+        let instance_ent = app.world.get::<Client>(client_ent).unwrap().instance();
+
+        app.world
+            .get_mut::<Instance>(instance_ent)
+            .unwrap()
+            .insert_chunk([0, 0], Chunk::default());
+
+        let npc = app
+            .world
+            .spawn(McEntity::new(EntityKind::Player, instance_ent))
+            .id();
+
+        app.update();
+        client_helper.clear_sent();
+
+        app.world
+            .entity_mut(npc)
+            .insert(PlayerSkin::new("skin", Some("signature".into())));
+
+        app.update();
+
+        let uuid = app.world.get::<McEntity>(npc).unwrap().uuid();
+        let entry = app.world.resource::<PlayerList>().get(uuid).unwrap();
+        assert!(!entry.is_listed());
+        assert_eq!(
+            PlayerSkin::from_properties(entry.properties())
+                .unwrap()
+                .value,
+            "skin"
+        );
+
+        let sent_packets = client_helper.collect_sent()?;
+        assert_packet_count!(sent_packets, 1, S2cPlayPacket::RemoveEntities(_));
+        assert_packet_order!(
+            sent_packets,
+            S2cPlayPacket::PlayerInfoUpdate(_),
+            S2cPlayPacket::RemoveEntities(_),
+            S2cPlayPacket::SpawnPlayer(_)
+        );
+
+        Ok(())
+    }
+}
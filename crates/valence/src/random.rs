@@ -0,0 +1,368 @@
+//! Deterministic random numbers derived from a world seed.
+//!
+//! The generators in this module reproduce the random number generation of
+//! vanilla Minecraft. Randomness used by world generation and loot is derived
+//! from a [`WorldSeed`] instead of the thread-local generator so that a world
+//! generated with the same seed always looks the same and matches the output
+//! of seed-based tooling.
+//!
+//! Independent sources of randomness are split off from the world seed either
+//! by position ([`PositionalRandom::at`]) or by name
+//! ([`PositionalRandom::by_name`]). Because every split only depends on
+//! the seed and its key, chunks and features can be generated in any order
+//! and on any thread.
+
+use bevy_ecs::prelude::*;
+use md5::Md5;
+use rand::{RngCore, SeedableRng};
+use sha2::{Digest, Sha256};
+
+use crate::view::ChunkPos;
+
+const GOLDEN_RATIO_64: u64 = 0x9e37_79b9_7f4a_7c15;
+const SILVER_RATIO_64: u64 = 0x6a09_e667_f3bc_c909;
+
+/// Scrambles the bits of a 64 bit value using Stafford's variant 13 of the
+/// SplitMix64 finalizer.
+fn mix_stafford_13(mut z: u64) -> u64 {
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+/// Expands a 64 bit seed into the two halves of a 128 bit seed the same way
+/// vanilla does.
+fn upgrade_seed(seed: i64) -> (u64, u64) {
+    let lo = seed as u64 ^ SILVER_RATIO_64;
+    let hi = lo.wrapping_add(GOLDEN_RATIO_64);
+    (mix_stafford_13(lo), mix_stafford_13(hi))
+}
+
+/// Hashes a block position the same way as vanilla's `Mth.getSeed`.
+fn position_seed(x: i32, y: i32, z: i32) -> i64 {
+    let mut seed = (x.wrapping_mul(3129871) as i64) ^ (z as i64).wrapping_mul(116129781) ^ y as i64;
+    seed = seed
+        .wrapping_mul(seed)
+        .wrapping_mul(42317861)
+        .wrapping_add(seed.wrapping_mul(11));
+    seed >> 16
+}
+
+/// The seed of a world, from which all of its deterministic randomness is
+/// derived.
+///
+/// Insert this on an [`Instance`](crate::instance::Instance) entity and share
+/// it with the instance's [`ChunkGenerator`](crate::worldgen::ChunkGenerator)
+/// so that generation and loot can be reproduced.
+#[derive(Component, Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub struct WorldSeed(pub i64);
+
+impl WorldSeed {
+    /// Creates a random world seed.
+    pub fn random() -> Self {
+        Self(rand::random())
+    }
+
+    /// Converts text to a seed the way the "Seed for the World Generator"
+    /// field of the vanilla client does. Text which is a number is used as is
+    /// and all other text is hashed like a Java string.
+    pub fn from_text(text: &str) -> Self {
+        match text.trim().parse() {
+            Ok(seed) => Self(seed),
+            Err(_) => Self(text.trim().encode_utf16().fold(0_i32, |hash, c| {
+                hash.wrapping_mul(31).wrapping_add(c as i32)
+            }) as i64),
+        }
+    }
+
+    /// Returns the obfuscated seed sent to clients when they join, which
+    /// the client uses for biome noise.
+    pub fn hashed(self) -> i64 {
+        let digest = Sha256::digest(self.0.to_be_bytes());
+        i64::from_be_bytes(digest[..8].try_into().unwrap())
+    }
+
+    /// Returns a random number generator seeded with the world seed.
+    pub fn random_source(self) -> Xoroshiro {
+        Xoroshiro::from_seed_i64(self.0)
+    }
+
+    /// Returns the factory for positional random number generators of this
+    /// world. This is the root of all world generation randomness.
+    pub fn positional(self) -> PositionalRandom {
+        self.random_source().fork_positional()
+    }
+
+    /// Returns the random number generator used to decorate the chunk at
+    /// `pos`, along with the decoration seed of the chunk.
+    pub fn decoration_random(self, pos: ChunkPos) -> (Xoroshiro, i64) {
+        let mut rng = self.random_source();
+        let a = rng.next_i64() | 1;
+        let b = rng.next_i64() | 1;
+
+        let seed = ((pos.x as i64 * 16).wrapping_mul(a))
+            .wrapping_add((pos.z as i64 * 16).wrapping_mul(b))
+            ^ self.0;
+
+        (Xoroshiro::from_seed_i64(seed), seed)
+    }
+
+    /// Returns the random number generator of the feature with the given
+    /// index in a generation step of a chunk, given the chunk's decoration
+    /// seed from [`Self::decoration_random`].
+    pub fn feature_random(decoration_seed: i64, index: i32, step: i32) -> Xoroshiro {
+        Xoroshiro::from_seed_i64(
+            decoration_seed
+                .wrapping_add(index as i64)
+                .wrapping_add(10000 * step as i64),
+        )
+    }
+
+    /// Returns the random number generator used by large features such as
+    /// caves and ravines that start in the chunk at `pos`.
+    pub fn large_feature_random(self, pos: ChunkPos) -> Xoroshiro {
+        let mut rng = self.random_source();
+        let a = rng.next_i64();
+        let b = rng.next_i64();
+
+        let seed = (pos.x as i64).wrapping_mul(a) ^ (pos.z as i64).wrapping_mul(b) ^ self.0;
+        Xoroshiro::from_seed_i64(seed)
+    }
+
+    /// Returns the random number generator used to roll the loot table with
+    /// the given name for a loot seed, such as the `LootTableSeed` of a
+    /// container.
+    pub fn loot_random(self, table: &str, loot_seed: i64) -> Xoroshiro {
+        let seed = if loot_seed == 0 {
+            self.positional().by_name(table).next_i64()
+        } else {
+            loot_seed
+        };
+
+        Xoroshiro::from_seed_i64(seed)
+    }
+}
+
+/// A Xoroshiro128++ random number generator which produces the same values as
+/// vanilla's `XoroshiroRandomSource`.
+///
+/// Implements [`RngCore`], so it can be used with everything in the `rand`
+/// crate. The inherent methods mirror the vanilla methods of the same names
+/// for when exact compatibility matters.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Xoroshiro {
+    lo: u64,
+    hi: u64,
+}
+
+impl Xoroshiro {
+    /// Creates a generator from the two halves of its state. A state of all
+    /// zeros is replaced with a valid one.
+    pub fn new(lo: u64, hi: u64) -> Self {
+        if lo == 0 && hi == 0 {
+            Self {
+                lo: GOLDEN_RATIO_64,
+                hi: SILVER_RATIO_64,
+            }
+        } else {
+            Self { lo, hi }
+        }
+    }
+
+    /// Creates a generator from a 64 bit seed like vanilla does.
+    pub fn from_seed_i64(seed: i64) -> Self {
+        let (lo, hi) = upgrade_seed(seed);
+        Self::new(lo, hi)
+    }
+
+    pub fn next_i64(&mut self) -> i64 {
+        let lo = self.lo;
+        let mut hi = self.hi;
+        let result = lo.wrapping_add(hi).rotate_left(17).wrapping_add(lo);
+
+        hi ^= lo;
+        self.lo = lo.rotate_left(49) ^ hi ^ (hi << 21);
+        self.hi = hi.rotate_left(28);
+
+        result as i64
+    }
+
+    pub fn next_i32(&mut self) -> i32 {
+        self.next_i64() as i32
+    }
+
+    /// Returns a number in `0..bound`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `bound` is not positive.
+    #[track_caller]
+    pub fn next_i32_bounded(&mut self, bound: i32) -> i32 {
+        assert!(bound > 0, "bound must be positive");
+
+        let bound = bound as u64;
+        let mut product = (self.next_i32() as u32 as u64) * bound;
+        let mut low = product & 0xffff_ffff;
+
+        if low < bound {
+            let threshold = (!bound + 1) as u32 as u64 % bound;
+            while low < threshold {
+                product = (self.next_i32() as u32 as u64) * bound;
+                low = product & 0xffff_ffff;
+            }
+        }
+
+        (product >> 32) as i32
+    }
+
+    /// Returns a number in `min..=max`.
+    pub fn next_i32_between(&mut self, min: i32, max: i32) -> i32 {
+        min + self.next_i32_bounded(max - min + 1)
+    }
+
+    fn next_bits(&mut self, bits: u32) -> u64 {
+        self.next_i64() as u64 >> (64 - bits)
+    }
+
+    pub fn next_bool(&mut self) -> bool {
+        self.next_i64() & 1 != 0
+    }
+
+    /// Returns a number in `0.0..1.0`.
+    pub fn next_f32(&mut self) -> f32 {
+        self.next_bits(24) as f32 * 5.960_464_5e-8
+    }
+
+    /// Returns a number in `0.0..1.0`.
+    pub fn next_f64(&mut self) -> f64 {
+        self.next_bits(53) as f64 * 1.110_223_024_625_156_5e-16
+    }
+
+    /// Skips the next `count` values.
+    pub fn consume(&mut self, count: usize) {
+        for _ in 0..count {
+            self.next_i64();
+        }
+    }
+
+    /// Splits off an independent generator.
+    pub fn fork(&mut self) -> Self {
+        Self::new(self.next_i64() as u64, self.next_i64() as u64)
+    }
+
+    /// Splits off a factory for positional generators.
+    pub fn fork_positional(&mut self) -> PositionalRandom {
+        PositionalRandom {
+            lo: self.next_i64() as u64,
+            hi: self.next_i64() as u64,
+        }
+    }
+}
+
+impl RngCore for Xoroshiro {
+    fn next_u32(&mut self) -> u32 {
+        self.next_i64() as u32
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.next_i64() as u64
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        for chunk in dest.chunks_mut(8) {
+            let bytes = self.next_u64().to_le_bytes();
+            chunk.copy_from_slice(&bytes[..chunk.len()]);
+        }
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+impl SeedableRng for Xoroshiro {
+    type Seed = [u8; 16];
+
+    fn from_seed(seed: Self::Seed) -> Self {
+        Self::new(
+            u64::from_le_bytes(seed[..8].try_into().unwrap()),
+            u64::from_le_bytes(seed[8..].try_into().unwrap()),
+        )
+    }
+
+    fn seed_from_u64(state: u64) -> Self {
+        Self::from_seed_i64(state as i64)
+    }
+}
+
+/// Creates [`Xoroshiro`] generators which only depend on the factory's seed
+/// and a position or name, like vanilla's `PositionalRandomFactory`.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub struct PositionalRandom {
+    lo: u64,
+    hi: u64,
+}
+
+impl PositionalRandom {
+    /// Returns the generator for a block position.
+    pub fn at(&self, x: i32, y: i32, z: i32) -> Xoroshiro {
+        Xoroshiro::new(position_seed(x, y, z) as u64 ^ self.lo, self.hi)
+    }
+
+    /// Returns the generator for the block at the origin of a chunk.
+    pub fn at_chunk(&self, pos: ChunkPos) -> Xoroshiro {
+        self.at(pos.x * 16, 0, pos.z * 16)
+    }
+
+    /// Returns the generator for a name, such as the identifier of a noise or
+    /// a structure. Like in vanilla, the name is hashed with MD5.
+    pub fn by_name(&self, name: &str) -> Xoroshiro {
+        let digest = Md5::digest(name.as_bytes());
+        let lo = u64::from_be_bytes(digest[..8].try_into().unwrap());
+        let hi = u64::from_be_bytes(digest[8..].try_into().unwrap());
+
+        Xoroshiro::new(lo ^ self.lo, hi ^ self.hi)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn same_seed_same_values() {
+        let seed = WorldSeed::from_text("valence");
+        assert_eq!(seed, WorldSeed(231137644));
+
+        let positional = seed.positional();
+
+        let mut a = positional.at(10, 64, -3);
+        let mut b = seed.positional().at(10, 64, -3);
+        let mut c = positional.at(11, 64, -3);
+
+        let a = (0..8).map(|_| a.next_i64()).collect::<Vec<_>>();
+        let b = (0..8).map(|_| b.next_i64()).collect::<Vec<_>>();
+        let c = (0..8).map(|_| c.next_i64()).collect::<Vec<_>>();
+
+        assert_eq!(a, b);
+        assert_ne!(a, c);
+
+        assert_eq!(
+            positional.by_name("minecraft:cave").next_i64(),
+            positional.by_name("minecraft:cave").next_i64()
+        );
+    }
+
+    #[test]
+    fn bounded_values() {
+        let mut rng = Xoroshiro::from_seed_i64(0);
+
+        for _ in 0..1000 {
+            assert!((0..7).contains(&rng.next_i32_bounded(7)));
+            assert!((-3..=3).contains(&rng.next_i32_between(-3, 3)));
+            assert!((0.0..1.0).contains(&rng.next_f32()));
+            assert!((0.0..1.0).contains(&rng.next_f64()));
+        }
+    }
+}
@@ -11,6 +11,7 @@ use bevy_app::AppExit;
 use bevy_ecs::event::ManualEventReader;
 use bevy_ecs::prelude::*;
 use flume::{Receiver, Sender};
+use glam::DVec3;
 use rand::rngs::OsRng;
 use rsa::{PublicKeyParts, RsaPrivateKey};
 use tokio::runtime::{Handle, Runtime};
@@ -21,14 +22,99 @@ use valence_protocol::types::Property;
 use valence_protocol::{ident, Username};
 
 use crate::biome::{validate_biomes, Biome, BiomeId};
+use crate::budget::{start_tick_budget, TickBudget, WorkShed};
+use crate::bundle::drop_bundle_contents;
+use crate::chat::{broadcast_chat_messages, handle_private_message_commands, ChatType};
+use crate::client::disconnect::{kick_clients, send_disconnect_events, DisconnectEvent};
 use crate::client::event::{event_loop_run_criteria, register_client_events};
+use crate::client::pose::{update_pose_states, PoseChanged, PoseState};
+use crate::client::profile::GameProfile;
 use crate::client::{update_clients, Client};
-use crate::config::{AsyncCallbacks, ConnectionMode, ServerPlugin};
+use crate::command::{dispatch_commands, send_command_trees, send_help, CommandExecuted};
+use crate::config::{AsyncCallbacks, ConnectionMode, OfflineUuid, ServerPlugin};
+use crate::crafting::{send_recipes, update_crafting_results};
+use crate::creative::CreativeItemFiltered;
+use crate::custom_item::{
+    consume_custom_items, stamp_custom_items, use_custom_items, CustomItemEvent, CustomItems,
+};
+use crate::cutscene::{
+    despawn_orphaned_cutscene_cameras, handle_cutscene_sneaking, play_cutscenes, start_cutscenes,
+    CutsceneFinished,
+};
+use crate::damage::EntityDamage;
 use crate::dimension::{validate_dimensions, Dimension, DimensionId};
+use crate::durability::{
+    damage_items, wear_attacking_weapons, wear_damaged_armor, wear_digging_tools, DamageItem,
+    ItemBreakEvent,
+};
+use crate::effect::{
+    remove_effect_particles, send_effects_to_clients, tick_status_effects,
+    update_effect_particles, StatusEffectExpired,
+};
+use crate::ender::{
+    eat_chorus_fruit, explode_end_crystals, start_eating_chorus_fruit, throw_ender_pearls,
+    tick_ender_pearls, ItemTeleport,
+};
+use crate::ender_chest::{despawn_ender_chests, open_ender_chests};
+use crate::entity::armor_stand::{
+    manipulate_armor_stands, reset_armor_stand_poses, reset_armor_stand_styles,
+    update_armor_stand_poses, update_armor_stand_styles, ArmorStandManipulateEvent,
+};
+use crate::entity::attributes::{send_attributes_to_clients, update_armor_attributes};
+use crate::entity::breeding::{
+    breed_animals, feed_breedables, tick_breedables, update_baby_data, AnimalBred, EnterLoveMode,
+};
+use crate::entity::disguise::{
+    clear_disguise_modifications, remove_disguises_on_action, respawn_disguised_entities,
+};
+use crate::entity::dropped_item::{
+    merge_item_entities, pick_up_items, tick_item_entities, update_item_entity_data,
+    ItemPickupEvent,
+};
+use crate::entity::equipment::{
+    remove_equipments, send_held_items, update_equipments, update_player_equipments,
+    EquipmentChangeEvent, Equipments, HeldItem,
+};
+use crate::entity::horse::{
+    dismount_horses, handle_horse_jumps, init_horse_inventories, interact_with_horses,
+    open_horse_inventories, show_horse_armor, steer_horses, tick_horses, update_horse_data,
+    update_horse_passengers, HorseJump, HorseTamed,
+};
+use crate::entity::item_frame::{interact_with_item_frames, ItemFrameEvent};
+use crate::entity::item_interaction::{use_items_on_entities, ItemInteractions, ItemUsedOnEntity};
+use crate::entity::name_tag::{
+    despawn_orphaned_name_tag_lines, remove_custom_names, update_custom_names, update_name_tags,
+};
+use crate::entity::passengers::{
+    dismount_passengers, move_passengers, remove_despawned_passengers, seat_clients,
+    update_passenger_ids, DismountEvent,
+};
+use crate::entity::pet::{
+    follow_owners, interact_with_pets, stand_up_hurt_pets, update_pet_data, PetTamed,
+};
+use crate::entity::pushing::push_entities;
+use crate::entity::villager::{restock_villagers, update_villager_data};
 use crate::entity::{
     check_entity_invariants, deinit_despawned_entities, init_entities, update_entities,
     McEntityManager,
 };
+use crate::explosion::{
+    detonate_explosions, handle_flint_and_steel, ignite_tnt, tick_primed_tnt, ExplosionSettings,
+    IgniteTnt, PendingExplosions,
+};
+use crate::fall::{apply_fall_damage, update_fall_distance, FallDistance, PendingFallDamage};
+use crate::fire::{
+    extinguish_entities, extinguish_fire, ignite_blocks, ignite_entities,
+    place_fire_with_flint_and_steel, schedule_placed_fire, tick_burning_entities, tick_fire,
+    IgniteBlock, ScheduledFireTicks,
+};
+use crate::function::run_functions;
+use crate::hand_swing::{broadcast_hand_swings, HandSwingSettings};
+use crate::hibernation::{update_hibernation, InstanceHibernated, InstanceWokeUp};
+use crate::hud::{
+    update_action_bar_tickers, update_boss_bar_timers, update_countdowns, BossBarTimerFinished,
+    CountdownFinished,
+};
 use crate::instance::{
     check_instance_invariants, update_instances_post_client, update_instances_pre_client, Instance,
 };
@@ -37,8 +123,42 @@ use crate::inventory::{
     update_client_on_close_inventory, update_open_inventories, update_player_inventories,
     Inventory, InventoryKind,
 };
+use crate::ip_filter::IpFilter;
+use crate::item_cooldown::update_item_cooldowns;
+use crate::menu::{close_menus, handle_menu_clicks, open_pending_menus, MenuClick, MenuClosed};
+use crate::music::{play_songs, SongFinished};
+use crate::nickname::{update_nicknames, NicknameChanged, NicknameConflict, Nicknames};
+use crate::note_block::{play_attacked_note_blocks, tune_note_blocks};
+use crate::parkour::{
+    reset_fallen_parkour_players, start_parkour_runs, update_parkour_checkpoints,
+    CheckpointReached, ParkourCompleted,
+};
+use crate::pick_block::{pick_blocks, PickBlockEvent};
+use crate::player_head::place_player_heads;
+use crate::player_list::layout::{remove_tab_layouts, update_tab_layouts, TabPlaceholders};
 use crate::player_list::{update_player_list, PlayerList};
+use crate::player_textures::{fetch_skins, update_player_skins, SkinCache};
 use crate::server::connect::do_accept_loop;
+use crate::shared_chunks::update_shared_chunks;
+use crate::swap_hands::{queue_hand_swaps, swap_hands, PendingHandSwaps};
+use crate::target::{update_targets, TargetChanged};
+use crate::tick_rate::{update_tick_rates, SkippedInstances};
+use crate::tick_stats::TickStats;
+use crate::trading::{
+    handle_select_trade, send_merchant_offers, update_merchant_results, TradeEvent,
+};
+use crate::trigger::{update_trigger_regions, EnterTrigger, LeaveTrigger};
+use crate::void::handle_out_of_bounds;
+use crate::water::{tick_air_supply, update_in_water};
+use crate::weather::{
+    despawn_lightning_bolts, strike_lightning, tick_weather, LightningStrike, WeatherSettings,
+};
+use crate::workstation::{
+    handle_enchant_buttons, handle_rename_items, send_container_properties, tick_brewing_stands,
+    update_anvils, update_enchanting_tables, Anvil, AnvilUsed, BrewingRecipes, BrewingStand,
+    EnchantingTable, ItemEnchanted, PotionBrewed,
+};
+use crate::worldgen::{insert_generated_chunks, queue_chunk_generation, WorldGenPool};
 use crate::Despawned;
 
 mod byte_channel;
@@ -83,7 +203,11 @@ struct SharedServerInner {
     address: SocketAddr,
     tps: i64,
     connection_mode: ConnectionMode,
+    offline_encryption: bool,
+    offline_uuid: OfflineUuid,
+    ip_filter: IpFilter,
     compression_threshold: Option<u32>,
+    compression_level: u32,
     max_connections: usize,
     incoming_capacity: usize,
     outgoing_capacity: usize,
@@ -137,12 +261,28 @@ impl SharedServer {
         &self.0.connection_mode
     }
 
+    /// Gets how the UUIDs of players are derived in offline mode.
+    pub fn offline_uuid(&self) -> OfflineUuid {
+        self.0.offline_uuid
+    }
+
+    /// Gets the IP addresses connections are accepted from.
+    pub fn ip_filter(&self) -> &IpFilter {
+        &self.0.ip_filter
+    }
+
     /// Gets the compression threshold for packets. `None` indicates no
     /// compression.
     pub fn compression_threshold(&self) -> Option<u32> {
         self.0.compression_threshold
     }
 
+    /// Gets the zlib compression level for packets above the compression
+    /// threshold.
+    pub fn compression_level(&self) -> u32 {
+        self.0.compression_level
+    }
+
     /// Gets the maximum number of connections allowed to the server at once.
     pub fn max_connections(&self) -> usize {
         self.0.max_connections
@@ -163,6 +303,11 @@ impl SharedServer {
         &self.0.tokio_handle
     }
 
+    /// Gets the HTTP client used for requests to Mojang's APIs.
+    pub(crate) fn http_client(&self) -> &reqwest::Client {
+        &self.0.http_client
+    }
+
     /// Obtains a [`Dimension`] by using its corresponding [`DimensionId`].
     #[track_caller]
     pub fn dimension(&self, id: DimensionId) -> &Dimension {
@@ -223,6 +368,31 @@ pub struct NewClientInfo {
     /// The client's properties from the game profile. Typically contains a
     /// `textures` property with the skin and cape of the player.
     pub properties: Vec<Property>,
+    /// Where the client is placed when it is spawned. This is set by
+    /// [`AsyncCallbacks::pre_spawn`].
+    pub spawn: Option<SpawnPoint>,
+}
+
+/// The initial location of a new client.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct SpawnPoint {
+    /// The [`Instance`](crate::instance::Instance) entity the client is
+    /// spawned in.
+    pub instance: Entity,
+    pub position: DVec3,
+    pub yaw: f32,
+    pub pitch: f32,
+}
+
+impl SpawnPoint {
+    pub fn new(instance: Entity, position: impl Into<DVec3>) -> Self {
+        Self {
+            instance,
+            position: position.into(),
+            yaw: 0.0,
+            pitch: 0.0,
+        }
+    }
 }
 
 pub fn build_plugin(
@@ -233,6 +403,10 @@ pub fn build_plugin(
         plugin.tps > 0,
         "configured tick rate must be greater than zero"
     );
+    ensure!(
+        plugin.compression_level <= 9,
+        "configured compression level must be between 0 and 9"
+    );
     ensure!(
         plugin.incoming_capacity > 0,
         "configured incoming packet capacity must be nonzero"
@@ -270,7 +444,11 @@ pub fn build_plugin(
         address: plugin.address,
         tps: plugin.tps,
         connection_mode: plugin.connection_mode.clone(),
+        offline_encryption: plugin.offline_encryption,
+        offline_uuid: plugin.offline_uuid,
+        ip_filter: plugin.ip_filter.clone(),
         compression_threshold: plugin.compression_threshold,
+        compression_level: plugin.compression_level,
         max_connections: plugin.max_connections,
         incoming_capacity: plugin.incoming_capacity,
         outgoing_capacity: plugin.outgoing_capacity,
@@ -312,7 +490,21 @@ pub fn build_plugin(
                 break
             };
 
-            world.spawn((client, Inventory::new(InventoryKind::Player)));
+            let profile = GameProfile::new(
+                client.username().to_owned_username(),
+                client.uuid(),
+                client.properties().to_vec(),
+            );
+
+            world.spawn((
+                client,
+                profile,
+                Inventory::new(InventoryKind::Player),
+                PoseState::default(),
+                FallDistance::default(),
+                Equipments::default(),
+                HeldItem::default(),
+            ));
         }
     };
 
@@ -325,12 +517,78 @@ pub fn build_plugin(
     // Insert resources.
     app.insert_resource(server)
         .insert_resource(McEntityManager::new())
-        .insert_resource(PlayerList::new());
+        .insert_resource(PlayerList::new())
+        .insert_resource(TabPlaceholders::default())
+        .insert_resource(ItemInteractions::default())
+        .insert_resource(CustomItems::default())
+        .insert_resource(Nicknames::default())
+        .insert_resource(SkinCache::default())
+        .insert_resource(ExplosionSettings::default())
+        .insert_resource(PendingExplosions::default())
+        .insert_resource(ScheduledFireTicks::default())
+        .insert_resource(PendingFallDamage::default())
+        .insert_resource(PendingHandSwaps::default())
+        .insert_resource(HandSwingSettings::default())
+        .insert_resource(WeatherSettings::default())
+        .insert_resource(WorldGenPool::default())
+        .insert_resource(BrewingRecipes::default())
+        .insert_resource(SkippedInstances::default())
+        .insert_resource(TickStats::new(plugin.tps))
+        .insert_resource(TickBudget::new(Duration::from_secs_f64(
+            (plugin.tps as f64).recip(),
+        )))
+        .add_event::<IgniteTnt>()
+        .add_event::<IgniteBlock>()
+        .add_event::<LightningStrike>()
+        .add_event::<EntityDamage>()
+        .add_event::<CutsceneFinished>()
+        .add_event::<CountdownFinished>()
+        .add_event::<BossBarTimerFinished>()
+        .add_event::<NicknameChanged>()
+        .add_event::<NicknameConflict>()
+        .add_event::<EnterTrigger>()
+        .add_event::<LeaveTrigger>()
+        .add_event::<CheckpointReached>()
+        .add_event::<ParkourCompleted>()
+        .add_event::<PoseChanged>()
+        .add_event::<ItemTeleport>()
+        .add_event::<ItemPickupEvent>()
+        .add_event::<InstanceHibernated>()
+        .add_event::<InstanceWokeUp>()
+        .add_event::<SongFinished>()
+        .add_event::<HorseJump>()
+        .add_event::<HorseTamed>()
+        .add_event::<PetTamed>()
+        .add_event::<CommandExecuted>()
+        .add_event::<MenuClick>()
+        .add_event::<MenuClosed>()
+        .add_event::<CreativeItemFiltered>()
+        .add_event::<AnvilUsed>()
+        .add_event::<TradeEvent>()
+        .add_event::<ItemEnchanted>()
+        .add_event::<PotionBrewed>()
+        .add_event::<TargetChanged>()
+        .add_event::<EnterLoveMode>()
+        .add_event::<AnimalBred>()
+        .add_event::<ItemUsedOnEntity>()
+        .add_event::<ItemFrameEvent>()
+        .add_event::<ArmorStandManipulateEvent>()
+        .add_event::<DismountEvent>()
+        .add_event::<PickBlockEvent>()
+        .add_event::<CustomItemEvent>()
+        .add_event::<StatusEffectExpired>()
+        .add_event::<DamageItem>()
+        .add_event::<ItemBreakEvent>()
+        .add_event::<WorkShed>()
+        .add_event::<EquipmentChangeEvent>()
+        .add_event::<DisconnectEvent>();
     register_client_events(&mut app.world);
 
     // Add core systems and stages. User code is expected to run in
     // `CoreStage::Update` and `EventLoop`.
     app.add_system_to_stage(CoreStage::PreUpdate, spawn_new_clients)
+        .add_system_to_stage(CoreStage::PreUpdate, update_tick_rates)
+        .add_system_to_stage(CoreStage::PreUpdate, start_tick_budget)
         .add_stage_before(
             CoreStage::Update,
             EventLoop,
@@ -370,8 +628,499 @@ pub fn build_plugin(
                     handle_set_slot_creative
                         .before(update_open_inventories)
                         .before(update_player_inventories),
+                )
+                .with_system(
+                    update_crafting_results
+                        .after(handle_click_container)
+                        .after(handle_set_slot_creative)
+                        .before(update_open_inventories)
+                        .before(update_player_inventories),
+                ),
+        )
+        .add_system_set_to_stage(
+            CoreStage::PostUpdate,
+            SystemSet::new()
+                .label("workstation")
+                .before("valence_core")
+                .with_system(handle_rename_items.before(update_anvils))
+                .with_system(
+                    update_anvils
+                        .after(handle_click_container)
+                        .before(update_open_inventories),
+                )
+                .with_system(
+                    handle_enchant_buttons
+                        .after(handle_click_container)
+                        .before(update_enchanting_tables),
+                )
+                .with_system(
+                    update_enchanting_tables
+                        .after(handle_click_container)
+                        .before(update_open_inventories),
+                )
+                .with_system(
+                    tick_brewing_stands
+                        .after(handle_click_container)
+                        .before(update_open_inventories),
+                )
+                .with_system(send_container_properties::<Anvil>.after(update_open_inventories))
+                .with_system(
+                    send_container_properties::<EnchantingTable>.after(update_open_inventories),
+                )
+                .with_system(
+                    send_container_properties::<BrewingStand>.after(update_open_inventories),
+                )
+                .with_system(
+                    handle_select_trade
+                        .after(handle_click_container)
+                        .before(update_merchant_results),
+                )
+                .with_system(
+                    update_merchant_results
+                        .after(handle_click_container)
+                        .before(update_open_inventories),
+                )
+                .with_system(send_merchant_offers.after(update_open_inventories)),
+        )
+        .add_system_set_to_stage(
+            CoreStage::PostUpdate,
+            SystemSet::new()
+                .label("item_cooldown")
+                .before("valence_core")
+                .with_system(update_item_cooldowns),
+        )
+        .add_system_set_to_stage(
+            CoreStage::PostUpdate,
+            SystemSet::new()
+                .label("crafting")
+                .before("valence_core")
+                .with_system(send_recipes),
+        )
+        .add_system_set_to_stage(
+            CoreStage::PostUpdate,
+            SystemSet::new()
+                .label("menu")
+                .before("valence_core")
+                .before("inventory")
+                .with_system(open_pending_menus)
+                .with_system(handle_menu_clicks)
+                .with_system(close_menus),
+        )
+        .add_system_set_to_stage(
+            CoreStage::PostUpdate,
+            SystemSet::new()
+                .label("disconnect")
+                .before("valence_core")
+                .with_system(kick_clients)
+                .with_system(send_disconnect_events.after(kick_clients)),
+        )
+        .add_system_set_to_stage(
+            CoreStage::PostUpdate,
+            SystemSet::new()
+                .label("player_skin")
+                .before("valence_core")
+                .with_system(fetch_skins)
+                .with_system(update_player_skins.after(fetch_skins)),
+        )
+        // Disguises are removed in `CoreStage::Update` so that the removal is
+        // visible to `respawn_disguised_entities` in the same tick.
+        .add_system_to_stage(CoreStage::Update, remove_disguises_on_action)
+        .add_system_to_stage(
+            CoreStage::PostUpdate,
+            respawn_disguised_entities.before("valence_core"),
+        )
+        .add_system_to_stage(
+            CoreStage::PostUpdate,
+            clear_disguise_modifications.after("valence_core"),
+        )
+        .add_system_to_stage(
+            CoreStage::PostUpdate,
+            dismount_passengers.before("horse"),
+        )
+        .add_system_to_stage(
+            CoreStage::PostUpdate,
+            remove_despawned_passengers.before(move_passengers),
+        )
+        .add_system_to_stage(
+            CoreStage::PostUpdate,
+            move_passengers.after("horse").before("valence_core"),
+        )
+        .add_system_to_stage(
+            CoreStage::PostUpdate,
+            update_passenger_ids
+                .after(move_passengers)
+                .after(init_entities)
+                .before(update_instances_pre_client),
+        )
+        .add_system_to_stage(
+            CoreStage::PostUpdate,
+            seat_clients.after("valence_core"),
+        )
+        .add_system_to_stage(
+            CoreStage::PostUpdate,
+            update_hibernation.before("valence_core"),
+        )
+        .add_system_to_stage(
+            CoreStage::PostUpdate,
+            update_shared_chunks
+                .after(update_hibernation)
+                .before("valence_core"),
+        )
+        .add_system_set_to_stage(
+            CoreStage::PostUpdate,
+            SystemSet::new()
+                .label("tab_list")
+                .before("valence_core")
+                .with_system(update_tab_layouts)
+                .with_system(remove_tab_layouts),
+        )
+        .add_system_to_stage(
+            CoreStage::PostUpdate,
+            update_nicknames.before("valence_core"),
+        )
+        .add_system_to_stage(CoreStage::PostUpdate, push_entities.before("valence_core"))
+        .add_system_to_stage(
+            CoreStage::PostUpdate,
+            update_villager_data.before("valence_core"),
+        )
+        .add_system_to_stage(
+            CoreStage::PostUpdate,
+            restock_villagers.before("valence_core"),
+        )
+        .add_system_set_to_stage(
+            CoreStage::PostUpdate,
+            SystemSet::new()
+                .label("horse")
+                .before("valence_core")
+                .before("equipment")
+                .with_system(init_horse_inventories)
+                .with_system(interact_with_horses)
+                .with_system(steer_horses.after(interact_with_horses))
+                .with_system(handle_horse_jumps.after(interact_with_horses))
+                .with_system(open_horse_inventories.after(interact_with_horses))
+                .with_system(dismount_horses.after(interact_with_horses))
+                .with_system(tick_horses.after(dismount_horses))
+                .with_system(update_horse_passengers.after(tick_horses))
+                .with_system(show_horse_armor.after(interact_with_horses))
+                .with_system(
+                    update_horse_data
+                        .after(tick_horses)
+                        .after(steer_horses),
+                ),
+        )
+        .add_system_set_to_stage(
+            CoreStage::PostUpdate,
+            SystemSet::new()
+                .label("pet")
+                .before("valence_core")
+                .with_system(interact_with_pets)
+                .with_system(stand_up_hurt_pets.after(interact_with_pets))
+                .with_system(follow_owners.after(stand_up_hurt_pets))
+                .with_system(update_pet_data.after(follow_owners)),
+        )
+        .add_system_set_to_stage(
+            CoreStage::PostUpdate,
+            SystemSet::new()
+                .label("breeding")
+                .before("valence_core")
+                .after("horse")
+                .after("pet")
+                .with_system(breed_animals)
+                .with_system(feed_breedables.after(breed_animals))
+                .with_system(tick_breedables.after(feed_breedables))
+                .with_system(update_baby_data.after(tick_breedables)),
+        )
+        .add_system_to_stage(
+            CoreStage::PostUpdate,
+            use_items_on_entities.before("valence_core"),
+        )
+        .add_system_to_stage(
+            CoreStage::PostUpdate,
+            interact_with_item_frames.before("inventory"),
+        )
+        .add_system_to_stage(
+            CoreStage::PostUpdate,
+            manipulate_armor_stands.before("inventory"),
+        )
+        .add_system_to_stage(CoreStage::PostUpdate, pick_blocks.before("inventory"))
+        .add_system_set_to_stage(
+            CoreStage::PostUpdate,
+            SystemSet::new()
+                .label("custom_items")
+                .before("inventory")
+                .with_system(use_custom_items)
+                .with_system(consume_custom_items.after(use_custom_items))
+                .with_system(stamp_custom_items),
+        )
+        .add_system_set_to_stage(
+            CoreStage::PostUpdate,
+            SystemSet::new()
+                .label("status_effect")
+                .before("valence_core")
+                .with_system(tick_status_effects)
+                .with_system(update_effect_particles.after(tick_status_effects))
+                .with_system(remove_effect_particles)
+                .with_system(send_effects_to_clients.after(tick_status_effects)),
+        )
+        .add_system_set_to_stage(
+            CoreStage::PostUpdate,
+            SystemSet::new()
+                .label("durability")
+                .before("valence_core")
+                .before("equipment")
+                .with_system(wear_attacking_weapons)
+                .with_system(wear_digging_tools)
+                .with_system(wear_damaged_armor)
+                .with_system(
+                    damage_items
+                        .after(wear_attacking_weapons)
+                        .after(wear_digging_tools)
+                        .after(wear_damaged_armor),
                 ),
         )
+        .add_system_set_to_stage(
+            CoreStage::PostUpdate,
+            SystemSet::new()
+                .label("command")
+                .before("valence_core")
+                .with_system(run_functions.before(dispatch_commands))
+                .with_system(dispatch_commands)
+                .with_system(send_command_trees)
+                .with_system(send_help.after(dispatch_commands)),
+        )
+        .add_system_set_to_stage(
+            CoreStage::PostUpdate,
+            SystemSet::new()
+                .label("chat")
+                .before("valence_core")
+                .with_system(broadcast_chat_messages)
+                .with_system(handle_private_message_commands),
+        )
+        .add_system_set_to_stage(
+            CoreStage::PostUpdate,
+            SystemSet::new()
+                .label("equipment")
+                .before("valence_core")
+                .after("inventory")
+                .with_system(send_held_items)
+                .with_system(update_player_equipments)
+                .with_system(update_equipments.after(update_player_equipments))
+                .with_system(remove_equipments),
+        )
+        .add_system_set_to_stage(
+            CoreStage::PostUpdate,
+            SystemSet::new()
+                .label("attributes")
+                .before("valence_core")
+                .after("equipment")
+                .with_system(update_armor_attributes)
+                .with_system(send_attributes_to_clients.after(update_armor_attributes)),
+        )
+        .add_system_set_to_stage(
+            CoreStage::PostUpdate,
+            SystemSet::new()
+                .label("armor_stand")
+                .before("valence_core")
+                .with_system(update_armor_stand_poses)
+                .with_system(update_armor_stand_styles)
+                .with_system(reset_armor_stand_poses)
+                .with_system(reset_armor_stand_styles),
+        )
+        .add_system_to_stage(
+            CoreStage::PostUpdate,
+            update_pose_states.before("valence_core"),
+        )
+        .add_system_to_stage(
+            CoreStage::PostUpdate,
+            update_targets
+                .after(update_pose_states)
+                .before("valence_core"),
+        )
+        .add_system_to_stage(
+            CoreStage::PostUpdate,
+            update_trigger_regions.before("valence_core"),
+        )
+        .add_system_set_to_stage(
+            CoreStage::PostUpdate,
+            SystemSet::new()
+                .label("parkour")
+                .before("valence_core")
+                .with_system(start_parkour_runs)
+                .with_system(
+                    update_parkour_checkpoints
+                        .after(start_parkour_runs)
+                        .after(update_trigger_regions),
+                )
+                .with_system(reset_fallen_parkour_players.after(update_parkour_checkpoints)),
+        )
+        .add_system_set_to_stage(
+            CoreStage::PostUpdate,
+            SystemSet::new()
+                .label("hud")
+                .before("valence_core")
+                .with_system(update_countdowns)
+                .with_system(update_boss_bar_timers)
+                .with_system(update_action_bar_tickers),
+        )
+        .add_system_set_to_stage(
+            CoreStage::PostUpdate,
+            SystemSet::new()
+                .label("cutscene")
+                .before("valence_core")
+                .with_system(start_cutscenes)
+                .with_system(handle_cutscene_sneaking)
+                .with_system(
+                    play_cutscenes
+                        .after(start_cutscenes)
+                        .after(handle_cutscene_sneaking),
+                )
+                .with_system(despawn_orphaned_cutscene_cameras.after(play_cutscenes)),
+        )
+        .add_system_set_to_stage(
+            CoreStage::PostUpdate,
+            SystemSet::new()
+                .label("name_tag")
+                .before("valence_core")
+                .with_system(update_custom_names)
+                .with_system(remove_custom_names)
+                .with_system(update_name_tags)
+                .with_system(despawn_orphaned_name_tag_lines.after(update_name_tags)),
+        )
+        .add_system_set_to_stage(
+            CoreStage::PostUpdate,
+            SystemSet::new()
+                .label("explosion")
+                .before("valence_core")
+                .with_system(detonate_explosions)
+                .with_system(handle_flint_and_steel)
+                .with_system(
+                    ignite_tnt
+                        .after(detonate_explosions)
+                        .after(handle_flint_and_steel),
+                )
+                .with_system(tick_primed_tnt.after(detonate_explosions)),
+        )
+        .add_system_set_to_stage(
+            CoreStage::PostUpdate,
+            SystemSet::new()
+                .label("ender")
+                .before("valence_core")
+                .with_system(throw_ender_pearls)
+                .with_system(tick_ender_pearls.after(throw_ender_pearls))
+                .with_system(start_eating_chorus_fruit)
+                .with_system(eat_chorus_fruit.after(start_eating_chorus_fruit))
+                .with_system(explode_end_crystals.after(detonate_explosions)),
+        )
+        .add_system_set_to_stage(
+            CoreStage::PostUpdate,
+            SystemSet::new()
+                .label("ender_chest")
+                .before("inventory")
+                .with_system(open_ender_chests)
+                .with_system(despawn_ender_chests),
+        )
+        .add_system_set_to_stage(
+            CoreStage::PostUpdate,
+            SystemSet::new()
+                .label("dropped_item")
+                .before("valence_core")
+                .with_system(tick_item_entities)
+                .with_system(merge_item_entities.after(tick_item_entities))
+                .with_system(pick_up_items.after(merge_item_entities))
+                .with_system(update_item_entity_data.after(pick_up_items)),
+        )
+        .add_system_set_to_stage(
+            CoreStage::PostUpdate,
+            SystemSet::new()
+                .label("bundle")
+                .before("valence_core")
+                .before("inventory")
+                .with_system(drop_bundle_contents),
+        )
+        .add_system_set_to_stage(
+            CoreStage::PostUpdate,
+            SystemSet::new()
+                .label("fire")
+                .before("explosion")
+                .with_system(place_fire_with_flint_and_steel)
+                .with_system(extinguish_fire)
+                .with_system(ignite_blocks.after(place_fire_with_flint_and_steel))
+                .with_system(tick_fire.after(ignite_blocks).after(extinguish_fire))
+                .with_system(ignite_entities)
+                .with_system(tick_burning_entities)
+                .with_system(extinguish_entities),
+        )
+        .add_system_to_stage(
+            CoreStage::PostUpdate,
+            schedule_placed_fire
+                .after(update_clients)
+                .before(update_instances_post_client),
+        )
+        .add_system_set_to_stage(
+            CoreStage::PostUpdate,
+            SystemSet::new()
+                .label("weather")
+                .before("fire")
+                .with_system(tick_weather)
+                .with_system(strike_lightning.after(tick_weather))
+                .with_system(despawn_lightning_bolts),
+        )
+        .add_system_set_to_stage(
+            CoreStage::PostUpdate,
+            SystemSet::new()
+                .label("swap_hands")
+                .before("inventory")
+                .with_system(swap_hands)
+                .with_system(queue_hand_swaps.after(swap_hands)),
+        )
+        .add_system_to_stage(
+            CoreStage::PostUpdate,
+            broadcast_hand_swings.before("valence_core"),
+        )
+        .add_system_set_to_stage(
+            CoreStage::PostUpdate,
+            SystemSet::new()
+                .label("fall_damage")
+                .before("valence_core")
+                .with_system(apply_fall_damage)
+                .with_system(update_fall_distance.after(apply_fall_damage)),
+        )
+        .add_system_to_stage(
+            CoreStage::PostUpdate,
+            place_player_heads.before("valence_core"),
+        )
+        .add_system_set_to_stage(
+            CoreStage::PostUpdate,
+            SystemSet::new()
+                .label("note_block")
+                .before("valence_core")
+                .with_system(tune_note_blocks)
+                .with_system(play_attacked_note_blocks),
+        )
+        .add_system_to_stage(CoreStage::PostUpdate, play_songs.before("valence_core"))
+        .add_system_to_stage(
+            CoreStage::PostUpdate,
+            handle_out_of_bounds
+                .before("valence_core")
+                .after("fall_damage"),
+        )
+        .add_system_set_to_stage(
+            CoreStage::PostUpdate,
+            SystemSet::new()
+                .label("water")
+                .before("valence_core")
+                .after(update_pose_states)
+                .with_system(update_in_water)
+                .with_system(tick_air_supply),
+        )
+        .add_system_set_to_stage(
+            CoreStage::PostUpdate,
+            SystemSet::new()
+                .label("worldgen")
+                .before("valence_core")
+                .with_system(insert_generated_chunks)
+                .with_system(queue_chunk_generation.after(insert_generated_chunks)),
+        )
         .add_system_to_stage(CoreStage::Last, inc_current_tick);
 
     let tick_duration = Duration::from_secs_f64((shared.tps() as f64).recip());
@@ -397,6 +1146,12 @@ pub fn build_plugin(
             // Run the scheduled stages.
             app.update();
 
+            let elapsed = tick_start.elapsed();
+
+            app.world
+                .resource_mut::<TickStats>()
+                .record_tick(tick_start, elapsed);
+
             // Sleep until the next tick.
             thread::sleep(tick_duration.saturating_sub(tick_start.elapsed()));
         }
@@ -445,7 +1200,12 @@ fn make_registry_codec(dimensions: &[Dimension], biomes: &[Biome]) -> Compound {
         },
         ident!("chat_type") => compound! {
             "type" => ident!("chat_type"),
-            "value" => List::Compound(vec![]),
+            "value" => List::Compound(
+                ChatType::ALL
+                    .into_iter()
+                    .map(ChatType::to_chat_type_registry_item)
+                    .collect(),
+            ),
         },
     }
 }
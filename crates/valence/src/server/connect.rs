@@ -19,7 +19,7 @@ use sha2::{Digest, Sha256};
 use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
 use tokio::net::{TcpListener, TcpStream};
 use tokio::sync::OwnedSemaphorePermit;
-use tracing::{error, info, instrument, trace, warn};
+use tracing::{debug, error, info, instrument, trace, warn};
 use uuid::Uuid;
 use valence_protocol::packets::c2s::handshake::HandshakeOwned;
 use valence_protocol::packets::c2s::login::{EncryptionResponse, LoginPluginResponse, LoginStart};
@@ -81,6 +81,13 @@ async fn handle_connection(
 ) {
     trace!("handling connection");
 
+    if !shared.ip_filter().is_allowed(remote_addr.ip())
+        || !callbacks.filter_connection(&shared, remote_addr).await
+    {
+        debug!("connection filtered");
+        return;
+    }
+
     if let Err(e) = stream.set_nodelay(true) {
         error!("failed to set TCP_NODELAY: {e}");
     }
@@ -226,11 +233,29 @@ async fn handle_login(
 
     let username = username.to_owned_username();
 
-    let info = match shared.connection_mode() {
+    if let Err(reason) = callbacks
+        .pre_auth(shared, remote_addr, username.as_str_username())
+        .await
+    {
+        info!("disconnect before authentication: \"{reason}\"");
+        conn.send_packet(&DisconnectLogin {
+            reason: reason.into(),
+        })
+        .await?;
+        return Ok(None);
+    }
+
+    let mut info = match shared.connection_mode() {
         ConnectionMode::Online { .. } => {
             login_online(shared, &callbacks, conn, remote_addr, username).await?
         }
-        ConnectionMode::Offline => login_offline(remote_addr, username)?,
+        ConnectionMode::Offline => {
+            if shared.0.offline_encryption {
+                exchange_keys(shared, conn).await?;
+            }
+
+            login_offline(shared, &callbacks, remote_addr, username).await?
+        }
         ConnectionMode::BungeeCord => login_bungeecord(&handshake.server_address, username)?,
         ConnectionMode::Velocity { secret } => login_velocity(conn, username, secret).await?,
     };
@@ -241,7 +266,7 @@ async fn handle_login(
         })
         .await?;
 
-        conn.set_compression(Some(threshold));
+        conn.set_compression(Some(threshold), shared.0.compression_level);
     }
 
     if let Err(reason) = callbacks.login(shared, &info).await {
@@ -253,6 +278,18 @@ async fn handle_login(
         return Ok(None);
     }
 
+    match callbacks.pre_spawn(shared, &info).await {
+        Ok(spawn) => info.spawn = spawn,
+        Err(reason) => {
+            info!("disconnect before spawning: \"{reason}\"");
+            conn.send_packet(&DisconnectLogin {
+                reason: reason.into(),
+            })
+            .await?;
+            return Ok(None);
+        }
+    }
+
     conn.send_packet(&LoginSuccess {
         uuid: info.uuid,
         username: info.username.as_str_username(),
@@ -271,43 +308,7 @@ pub(super) async fn login_online(
     remote_addr: SocketAddr,
     username: Username<String>,
 ) -> anyhow::Result<NewClientInfo> {
-    let my_verify_token: [u8; 16] = rand::random();
-
-    conn.send_packet(&EncryptionRequest {
-        server_id: "", // Always empty
-        public_key: &shared.0.public_key_der,
-        verify_token: &my_verify_token,
-    })
-    .await?;
-
-    let EncryptionResponse {
-        shared_secret,
-        verify_token: encrypted_verify_token,
-    } = conn.recv_packet().await?;
-
-    let shared_secret = shared
-        .0
-        .rsa_key
-        .decrypt(PaddingScheme::PKCS1v15Encrypt, shared_secret)
-        .context("failed to decrypt shared secret")?;
-
-    let verify_token = shared
-        .0
-        .rsa_key
-        .decrypt(PaddingScheme::PKCS1v15Encrypt, encrypted_verify_token)
-        .context("failed to decrypt verify token")?;
-
-    ensure!(
-        my_verify_token.as_slice() == verify_token,
-        "verify tokens do not match"
-    );
-
-    let crypt_key: [u8; 16] = shared_secret
-        .as_slice()
-        .try_into()
-        .context("shared secret has the wrong length")?;
-
-    conn.enable_encryption(&crypt_key);
+    let shared_secret = exchange_keys(shared, conn).await?;
 
     let hash = Sha1::new()
         .chain(&shared_secret)
@@ -359,24 +360,79 @@ pub(super) async fn login_online(
         username,
         ip: remote_addr.ip(),
         properties: profile.properties,
+        spawn: None,
     })
 }
 
+/// Performs the key exchange with the client and enables encryption on the
+/// connection. Returns the shared secret.
+async fn exchange_keys(
+    shared: &SharedServer,
+    conn: &mut InitialConnection<OwnedReadHalf, OwnedWriteHalf>,
+) -> anyhow::Result<Vec<u8>> {
+    let my_verify_token: [u8; 16] = rand::random();
+
+    conn.send_packet(&EncryptionRequest {
+        server_id: "", // Always empty
+        public_key: &shared.0.public_key_der,
+        verify_token: &my_verify_token,
+    })
+    .await?;
+
+    let EncryptionResponse {
+        shared_secret,
+        verify_token: encrypted_verify_token,
+    } = conn.recv_packet().await?;
+
+    let shared_secret = shared
+        .0
+        .rsa_key
+        .decrypt(PaddingScheme::PKCS1v15Encrypt, shared_secret)
+        .context("failed to decrypt shared secret")?;
+
+    let verify_token = shared
+        .0
+        .rsa_key
+        .decrypt(PaddingScheme::PKCS1v15Encrypt, encrypted_verify_token)
+        .context("failed to decrypt verify token")?;
+
+    ensure!(
+        my_verify_token.as_slice() == verify_token,
+        "verify tokens do not match"
+    );
+
+    let crypt_key: [u8; 16] = shared_secret
+        .as_slice()
+        .try_into()
+        .context("shared secret has the wrong length")?;
+
+    conn.enable_encryption(&crypt_key);
+
+    Ok(shared_secret)
+}
+
 fn auth_digest(bytes: &[u8]) -> String {
     BigInt::from_signed_bytes_be(bytes).to_str_radix(16)
 }
 
 /// Login procedure for offline mode.
-pub(super) fn login_offline(
+pub(super) async fn login_offline(
+    shared: &SharedServer,
+    callbacks: &Arc<impl AsyncCallbacks>,
     remote_addr: SocketAddr,
     username: Username<String>,
 ) -> anyhow::Result<NewClientInfo> {
+    let uuid = callbacks
+        .offline_uuid(shared, username.as_str_username())
+        .await
+        .context("failed to get offline UUID")?;
+
     Ok(NewClientInfo {
-        // Derive the client's UUID from a hash of their username.
-        uuid: Uuid::from_slice(&Sha256::digest(username.as_str())[..16])?,
+        uuid,
         username,
         properties: vec![],
         ip: remote_addr.ip(),
+        spawn: None,
     })
 }
 
@@ -402,6 +458,7 @@ pub(super) fn login_bungeecord(
         username,
         properties,
         ip: client_ip.parse()?,
+        spawn: None,
     })
 }
 
@@ -476,6 +533,7 @@ pub(super) async fn login_velocity(
         username,
         properties,
         ip: remote_addr,
+        spawn: None,
     })
 }
 
@@ -109,8 +109,9 @@ where
     }
 
     #[allow(dead_code)]
-    pub fn set_compression(&mut self, threshold: Option<u32>) {
+    pub fn set_compression(&mut self, threshold: Option<u32>, level: u32) {
         self.enc.set_compression(threshold);
+        self.enc.set_compression_level(level);
         self.dec.set_compression(threshold.is_some());
     }
 
@@ -0,0 +1,224 @@
+//! Instances backed by a shared, read-only set of chunks.
+//!
+//! Mirrored lobbies show the same map in many instances, and most of it is
+//! never changed. Adding [`SharedChunks`] to the entity of an [`Instance`]
+//! makes the chunks of the store appear in the instance as clients come into
+//! view of them, and removes them again once no client sees them. Chunks the
+//! instance has modified are kept in the instance as an overlay over the
+//! store, so only the changes of an instance take up memory of their own:
+//!
+//! ```
+//! use bevy_app::prelude::*;
+//! use valence::prelude::*;
+//! use valence::shared_chunks::SharedChunks;
+//!
+//! let mut app = App::new();
+//! app.add_plugin(ServerPlugin::new(()));
+//!
+//! let lobby = SharedChunks::new([([0, 0], Chunk::default())]);
+//!
+//! for _ in 0..10 {
+//!     let instance = app
+//!         .world
+//!         .resource::<Server>()
+//!         .new_instance(DimensionId::default());
+//!
+//!     app.world.spawn((instance, lobby.clone()));
+//! }
+//! ```
+//!
+//! Chunks of the store which no client can see are not in the instance, so
+//! [`Instance::chunk`] returns `None` for them. Code changing the world far
+//! from clients should load the chunk with [`SharedChunks::load_chunk`]
+//! first.
+//!
+//! The chunks of the store should have the section count of the dimension of
+//! the instances. Other chunks are resized when they are loaded and are then
+//! kept like modified chunks.
+
+use std::sync::Arc;
+
+use bevy_ecs::prelude::*;
+use rustc_hash::FxHashMap;
+
+use crate::client::Client;
+use crate::instance::{client_views_by_instance, Chunk, Instance};
+use crate::template::WorldTemplate;
+use crate::view::ChunkPos;
+
+/// A [`Component`] with read-only chunks for the [`Instance`] on the same
+/// entity. Cloning it is cheap, and all clones share the same chunks.
+#[derive(Component, Clone, Default, Debug)]
+pub struct SharedChunks {
+    chunks: Arc<FxHashMap<ChunkPos, Chunk>>,
+}
+
+impl SharedChunks {
+    pub fn new<P: Into<ChunkPos>>(chunks: impl IntoIterator<Item = (P, Chunk)>) -> Self {
+        Self {
+            chunks: Arc::new(
+                chunks
+                    .into_iter()
+                    .map(|(pos, chunk)| (pos.into(), chunk))
+                    .collect(),
+            ),
+        }
+    }
+
+    /// Creates a store with the chunks of a template. The chunk data is
+    /// shared with the template.
+    pub fn from_template(template: &WorldTemplate) -> Self {
+        Self::new(template.chunks().map(|(pos, chunk)| (pos, chunk.clone())))
+    }
+
+    pub fn chunk(&self, pos: impl Into<ChunkPos>) -> Option<&Chunk> {
+        self.chunks.get(&pos.into())
+    }
+
+    pub fn chunks(&self) -> impl ExactSizeIterator<Item = (ChunkPos, &Chunk)> + '_ {
+        self.chunks.iter().map(|(pos, chunk)| (*pos, chunk))
+    }
+
+    pub fn len(&self) -> usize {
+        self.chunks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.chunks.is_empty()
+    }
+
+    /// Returns `true` if the instance has a chunk at the position which is
+    /// not an unmodified copy of the chunk in the store.
+    pub fn is_modified(&self, instance: &Instance, pos: impl Into<ChunkPos>) -> bool {
+        let pos = pos.into();
+
+        match (instance.chunk(pos), self.chunks.get(&pos)) {
+            (Some(chunk), Some(shared)) => !chunk.is_unmodified_copy_of(shared),
+            (Some(_), None) => true,
+            (None, _) => false,
+        }
+    }
+
+    /// Returns the chunk of the instance at the position, copying it from the
+    /// store first if the instance does not have it. Modifying the returned
+    /// chunk keeps it in the instance.
+    pub fn load_chunk<'a>(
+        &self,
+        instance: &'a mut Instance,
+        pos: impl Into<ChunkPos>,
+    ) -> Option<&'a mut Chunk<true>> {
+        let pos = pos.into();
+
+        if instance.chunk(pos).is_none() {
+            instance.insert_chunk(pos, self.chunks.get(&pos)?.clone());
+        }
+
+        instance.chunk_mut(pos)
+    }
+
+    /// Discards the changes the instance made to the chunk at the position,
+    /// and shows clients the chunk of the store again. Does nothing if the
+    /// store has no chunk at the position.
+    pub fn reset_chunk(&self, instance: &mut Instance, pos: impl Into<ChunkPos>) {
+        let pos = pos.into();
+
+        if self.is_modified(instance, pos) {
+            if let Some(shared) = self.chunks.get(&pos) {
+                instance.insert_chunk(pos, shared.clone());
+            }
+        }
+    }
+}
+
+/// Copies the shared chunks clients can see into their instances, and
+/// removes the unmodified chunks no client can see anymore.
+pub(crate) fn update_shared_chunks(
+    mut instances: Query<(Entity, &mut Instance, &SharedChunks)>,
+    clients: Query<&Client>,
+) {
+    let views = client_views_by_instance(clients.iter().filter(|c| !c.is_disconnected()));
+
+    for (entity, mut instance, shared) in &mut instances {
+        let views = views.get(&entity).map_or(&[][..], |v| v.as_slice());
+
+        for view in views {
+            for pos in view.iter() {
+                if instance.chunk(pos).is_none() {
+                    if let Some(chunk) = shared.chunks.get(&pos) {
+                        instance.insert_chunk(pos, chunk.clone());
+                    }
+                }
+            }
+        }
+
+        instance.retain_chunks(|pos, chunk| {
+            views.iter().any(|view| view.contains(pos))
+                || shared
+                    .chunks
+                    .get(&pos)
+                    .map_or(true, |shared| !chunk.is_unmodified_copy_of(shared))
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy_app::App;
+    use valence_protocol::block::BlockState;
+
+    use super::*;
+    use crate::unit_test::util::scenario_single_client;
+
+    #[test]
+    fn modified_chunks_stay_in_the_instance() {
+        let mut app = App::new();
+        let (client_ent, _client_helper) = scenario_single_client(&mut app);
+
+        let instance_ent = app.world.get::<Client>(client_ent).unwrap().instance();
+        let section_count = app
+            .world
+            .get::<Instance>(instance_ent)
+            .unwrap()
+            .section_count();
+
+        let shared = SharedChunks::new([
+            ([0, 0], Chunk::new(section_count)),
+            ([1, 0], Chunk::new(section_count)),
+            ([50, 50], Chunk::new(section_count)),
+        ]);
+
+        app.world.entity_mut(instance_ent).insert(shared.clone());
+
+        app.update();
+
+        let mut instance = app.world.get_mut::<Instance>(instance_ent).unwrap();
+        assert!(instance.chunk([0, 0]).is_some());
+        assert!(instance.chunk([1, 0]).is_some());
+        // Chunks out of view are only in the store.
+        assert!(instance.chunk([50, 50]).is_none());
+
+        instance.set_block([0, 0, 0], BlockState::STONE);
+        assert!(shared.is_modified(&instance, [0, 0]));
+        assert!(!shared.is_modified(&instance, [1, 0]));
+
+        // Move the client away from the chunks.
+        app.world
+            .get_mut::<Client>(client_ent)
+            .unwrap()
+            .set_position([800.0, 0.0, 800.0]);
+
+        app.update();
+
+        let mut instance = app.world.get_mut::<Instance>(instance_ent).unwrap();
+        assert!(instance.chunk([0, 0]).is_some());
+        assert!(instance.chunk([1, 0]).is_none());
+        assert!(instance.chunk([50, 50]).is_some());
+
+        shared.reset_chunk(&mut instance, [0, 0]);
+        assert_eq!(instance.block([0, 0, 0]).unwrap().state(), BlockState::AIR);
+        assert_eq!(
+            shared.chunk([0, 0]).unwrap().block_state(0, 0, 0),
+            BlockState::AIR
+        );
+    }
+}
@@ -0,0 +1,181 @@
+//! Swapping the items in the main hand and the off hand.
+//!
+//! Clients ask to swap the items in their hands with the swap key, F by
+//! default, and wait for the server to do it. The swap is queued in the
+//! [`PendingHandSwaps`] resource and carried out at the end of the following
+//! tick, after which the [`Equipments`](crate::entity::equipment::Equipments)
+//! of the client show the swapped items. Systems that run before then may
+//! inspect or cancel the swap:
+//!
+//! ```
+//! use valence::prelude::*;
+//! use valence::swap_hands::PendingHandSwaps;
+//!
+//! // Keep swords out of the off hand.
+//! fn no_off_hand_swords(mut swaps: ResMut<PendingHandSwaps>) {
+//!     swaps.retain(|swap| {
+//!         swap.main_hand
+//!             .as_ref()
+//!             .map_or(true, |stack| !stack.item.to_str().ends_with("_sword"))
+//!     });
+//! }
+//! ```
+
+use bevy_ecs::prelude::*;
+use valence_protocol::types::GameMode;
+use valence_protocol::ItemStack;
+
+use crate::client::event::SwapItemInHand;
+use crate::client::Client;
+use crate::entity::equipment::{EquipmentChangeEvent, EquipmentSnapshot};
+use crate::inventory::{Inventory, OFF_HAND_SLOT};
+
+/// A swap of the items in the hands of a client which will be carried out at
+/// the end of the tick.
+#[derive(Clone, PartialEq, Debug)]
+pub struct SwapHandsEvent {
+    pub client: Entity,
+    /// The item in the main hand when the client asked for the swap, which
+    /// goes to the off hand.
+    pub main_hand: Option<ItemStack>,
+    /// The item in the off hand when the client asked for the swap, which goes
+    /// to the main hand.
+    pub off_hand: Option<ItemStack>,
+}
+
+/// A [`Resource`] containing the hand swaps that will be carried out at the
+/// end of the current tick.
+///
+/// Removing an entry from this queue cancels the swap. Clients don't swap
+/// their items before the server does, so nothing has to be undone.
+#[derive(Resource, Default, Debug)]
+pub struct PendingHandSwaps {
+    swaps: Vec<SwapHandsEvent>,
+}
+
+impl PendingHandSwaps {
+    pub fn iter(&self) -> impl ExactSizeIterator<Item = &SwapHandsEvent> + '_ {
+        self.swaps.iter()
+    }
+
+    /// Returns `true` if a swap is queued for the client.
+    pub fn contains(&self, client: Entity) -> bool {
+        self.swaps.iter().any(|swap| swap.client == client)
+    }
+
+    /// Retains only the swaps for which the given predicate returns `true`.
+    /// The rest are cancelled.
+    pub fn retain<F>(&mut self, f: F)
+    where
+        F: FnMut(&SwapHandsEvent) -> bool,
+    {
+        self.swaps.retain(f);
+    }
+
+    /// Cancels the swap queued for the client.
+    pub fn cancel(&mut self, client: Entity) {
+        self.swaps.retain(|swap| swap.client != client);
+    }
+
+    /// Cancels all pending swaps.
+    pub fn clear(&mut self) {
+        self.swaps.clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.swaps.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.swaps.is_empty()
+    }
+}
+
+pub(crate) fn swap_hands(
+    mut pending: ResMut<PendingHandSwaps>,
+    mut clients: Query<(&Client, &mut Inventory)>,
+    mut equipment_changes: EventWriter<EquipmentChangeEvent>,
+) {
+    for swap in pending.swaps.drain(..) {
+        let Ok((client, mut inventory)) = clients.get_mut(swap.client) else {
+            continue;
+        };
+
+        let snapshot = EquipmentSnapshot::take(swap.client, &inventory, client.held_item_slot());
+        inventory.swap_slot(client.held_item_slot(), OFF_HAND_SLOT);
+        snapshot.send_changes(&inventory, client.held_item_slot(), &mut equipment_changes);
+    }
+}
+
+pub(crate) fn queue_hand_swaps(
+    clients: Query<(&Client, &Inventory)>,
+    mut pending: ResMut<PendingHandSwaps>,
+    mut swap_item_in_hand: EventReader<SwapItemInHand>,
+) {
+    for event in swap_item_in_hand.iter() {
+        let Ok((client, inventory)) = clients.get(event.client) else {
+            continue;
+        };
+
+        if client.game_mode() == GameMode::Spectator || pending.contains(event.client) {
+            continue;
+        }
+
+        pending.swaps.push(SwapHandsEvent {
+            client: event.client,
+            main_hand: inventory.slot(client.held_item_slot()).cloned(),
+            off_hand: inventory.slot(OFF_HAND_SLOT).cloned(),
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy_app::App;
+    use valence_protocol::packets::c2s::play::PlayerAction;
+    use valence_protocol::types::DiggingStatus;
+    use valence_protocol::{BlockFace, BlockPos, ItemKind};
+
+    use super::*;
+    use crate::entity::equipment::{EquipmentSlot, Equipments};
+    use crate::unit_test::util::scenario_single_client;
+
+    #[test]
+    fn swap_key_swaps_hands() {
+        let mut app = App::new();
+        let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+
+        let sword = ItemStack::new(ItemKind::DiamondSword, 1, None);
+        let held_slot = app
+            .world
+            .get::<Client>(client_ent)
+            .unwrap()
+            .held_item_slot();
+
+        let mut inventory = app.world.get_mut::<Inventory>(client_ent).unwrap();
+        inventory.replace_slot(held_slot, sword.clone());
+
+        app.update();
+
+        client_helper.send(&PlayerAction {
+            status: DiggingStatus::SwapItemInHand,
+            position: BlockPos::new(0, 0, 0),
+            face: BlockFace::Bottom,
+            sequence: 0.into(),
+        });
+
+        app.update();
+
+        // The swap is carried out on the tick after it was requested.
+        assert_eq!(app.world.resource::<PendingHandSwaps>().len(), 1);
+        app.update();
+
+        let inventory = app.world.get::<Inventory>(client_ent).unwrap();
+        assert_eq!(inventory.slot(held_slot), None);
+        assert_eq!(inventory.slot(OFF_HAND_SLOT), Some(&sword));
+
+        let equipments = app.world.get::<Equipments>(client_ent).unwrap();
+        assert_eq!(equipments.get(EquipmentSlot::OffHand), Some(&sword));
+        assert_eq!(equipments.get(EquipmentSlot::MainHand), None);
+    }
+}
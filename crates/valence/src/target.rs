@@ -0,0 +1,325 @@
+//! The block or entity under the crosshair of clients.
+//!
+//! Inserting a [`Targeting`] component on the entity of a client makes the
+//! server cast a ray from the eyes of the client in the direction it looks
+//! every few ticks, and store the block or entity it hits. Systems reacting
+//! to what clients look at read the stored target instead of casting their
+//! own rays:
+//!
+//! ```
+//! use valence::prelude::*;
+//! use valence::target::{Target, Targeting};
+//!
+//! fn show_targets(mut clients: Query<(&mut Client, &Targeting)>) {
+//!     for (mut client, targeting) in &mut clients {
+//!         if let Some(Target::Block(hit)) = targeting.target() {
+//!             client.set_action_bar(format!("Looking at {:?}", hit.block.to_kind()));
+//!         }
+//!     }
+//! }
+//! ```
+//!
+//! A [`TargetChanged`] event is sent whenever a client starts looking at
+//! another block or entity.
+
+use bevy_ecs::prelude::*;
+use glam::DVec3;
+
+use crate::client::pose::PoseState;
+use crate::client::Client;
+use crate::entity::{McEntity, PLAYER_EYE_HEIGHT};
+use crate::instance::{Instance, RaycastHit};
+use crate::math::from_yaw_and_pitch;
+use crate::Despawned;
+
+/// The default distance at which blocks and entities are targeted, which is
+/// the reach of players in survival mode.
+pub const DEFAULT_REACH: f64 = 4.5;
+/// The default number of ticks between updates of the target.
+pub const DEFAULT_INTERVAL: u32 = 2;
+
+/// An entity hit by the ray of a [`Targeting`] component.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct EntityHit {
+    pub entity: Entity,
+    /// The point where the ray hit the hitbox of the entity.
+    pub position: DVec3,
+    /// The distance from the eyes of the client to the hit.
+    pub distance: f64,
+}
+
+/// The block or entity a client is looking at.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Target {
+    Block(RaycastHit),
+    Entity(EntityHit),
+}
+
+impl Target {
+    /// The point where the ray hit the target.
+    pub fn position(&self) -> DVec3 {
+        match self {
+            Target::Block(hit) => hit.position,
+            Target::Entity(hit) => hit.position,
+        }
+    }
+
+    /// The distance from the eyes of the client to the target.
+    pub fn distance(&self) -> f64 {
+        match self {
+            Target::Block(hit) => hit.distance,
+            Target::Entity(hit) => hit.distance,
+        }
+    }
+
+    /// Returns `true` if both targets are the same block or entity, wherever
+    /// they were hit.
+    pub fn is_same(&self, other: &Target) -> bool {
+        match (self, other) {
+            (Target::Block(a), Target::Block(b)) => {
+                a.block_pos == b.block_pos && a.block == b.block
+            }
+            (Target::Entity(a), Target::Entity(b)) => a.entity == b.entity,
+            _ => false,
+        }
+    }
+}
+
+/// A [`Component`] with the block or entity the [`Client`] on the same entity
+/// is looking at.
+#[derive(Component, Clone, PartialEq, Debug)]
+pub struct Targeting {
+    reach: f64,
+    interval: u32,
+    ticks_until_update: u32,
+    target: Option<Target>,
+}
+
+impl Targeting {
+    /// Creates a component which targets blocks and entities within
+    /// [`DEFAULT_REACH`] every [`DEFAULT_INTERVAL`] ticks.
+    pub fn new() -> Self {
+        Self {
+            reach: DEFAULT_REACH,
+            interval: DEFAULT_INTERVAL,
+            ticks_until_update: 0,
+            target: None,
+        }
+    }
+
+    /// Sets the distance at which blocks and entities are targeted.
+    #[must_use]
+    pub fn with_reach(mut self, reach: f64) -> Self {
+        self.reach = reach;
+        self
+    }
+
+    /// Sets the number of ticks between updates of the target. The target is
+    /// updated every tick if the interval is zero or one.
+    #[must_use]
+    pub fn with_interval(mut self, ticks: u32) -> Self {
+        self.interval = ticks.max(1);
+        self
+    }
+
+    pub fn reach(&self) -> f64 {
+        self.reach
+    }
+
+    pub fn interval(&self) -> u32 {
+        self.interval
+    }
+
+    /// The block or entity the client was looking at when the target was last
+    /// updated.
+    pub fn target(&self) -> Option<&Target> {
+        self.target.as_ref()
+    }
+
+    /// The block the client is looking at, if it is looking at a block.
+    pub fn block(&self) -> Option<&RaycastHit> {
+        match &self.target {
+            Some(Target::Block(hit)) => Some(hit),
+            _ => None,
+        }
+    }
+
+    /// The entity the client is looking at, if it is looking at an entity.
+    pub fn entity(&self) -> Option<Entity> {
+        match &self.target {
+            Some(Target::Entity(hit)) => Some(hit.entity),
+            _ => None,
+        }
+    }
+
+    /// Makes the target update at the end of this tick, regardless of the
+    /// interval.
+    pub fn refresh(&mut self) {
+        self.ticks_until_update = 0;
+    }
+}
+
+impl Default for Targeting {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// An event sent when a client with a [`Targeting`] component starts looking
+/// at another block or entity, or stops looking at anything.
+#[derive(Clone, Debug)]
+pub struct TargetChanged {
+    pub client: Entity,
+    pub old: Option<Target>,
+    pub new: Option<Target>,
+}
+
+/// Casts a ray from the eyes of a client in the direction it looks, and
+/// returns the closest block or entity it hits within `reach`.
+pub(crate) fn cast_ray(
+    client_entity: Entity,
+    client: &Client,
+    pose: Option<&PoseState>,
+    reach: f64,
+    instances: &Query<&Instance>,
+    entities: &Query<(Entity, &McEntity), Without<Despawned>>,
+) -> Option<Target> {
+    let eye_height = pose.map_or(PLAYER_EYE_HEIGHT, PoseState::eye_height);
+    let origin = client.position() + DVec3::new(0.0, eye_height, 0.0);
+    let direction = from_yaw_and_pitch(client.yaw(), client.pitch()).as_dvec3();
+
+    let block = instances
+        .get(client.instance())
+        .ok()
+        .and_then(|instance| instance.raycast(origin, direction, reach));
+
+    // Entities behind the block can't be targeted.
+    let max_distance = block.map_or(reach, |hit| hit.distance);
+
+    let entity = entities
+        .iter()
+        .filter(|(entity, mc_entity)| {
+            *entity != client_entity && mc_entity.instance() == client.instance()
+        })
+        .filter_map(|(entity, mc_entity)| {
+            let (distance, _) = mc_entity.hitbox().ray_intersection(origin, direction)?;
+
+            (distance <= max_distance).then(|| EntityHit {
+                entity,
+                position: origin + direction * distance,
+                distance,
+            })
+        })
+        .min_by(|a, b| a.distance.total_cmp(&b.distance));
+
+    match entity {
+        Some(hit) => Some(Target::Entity(hit)),
+        None => block.map(Target::Block),
+    }
+}
+
+type TargetingClient<'a> = (Entity, &'a Client, Option<&'a PoseState>, &'a mut Targeting);
+
+/// Casts the rays of clients whose target is due for an update.
+pub(crate) fn update_targets(
+    mut clients: Query<TargetingClient>,
+    instances: Query<&Instance>,
+    entities: Query<(Entity, &McEntity), Without<Despawned>>,
+    mut changed: EventWriter<TargetChanged>,
+) {
+    for (client_entity, client, pose, mut targeting) in &mut clients {
+        if targeting.ticks_until_update > 1 {
+            targeting.ticks_until_update -= 1;
+            continue;
+        }
+
+        targeting.ticks_until_update = targeting.interval;
+
+        let target = cast_ray(
+            client_entity,
+            client,
+            pose,
+            targeting.reach,
+            &instances,
+            &entities,
+        );
+
+        let is_same = match (&targeting.target, &target) {
+            (Some(old), Some(new)) => old.is_same(new),
+            (None, None) => true,
+            _ => false,
+        };
+
+        if !is_same {
+            changed.send(TargetChanged {
+                client: client_entity,
+                old: targeting.target,
+                new: target,
+            });
+        }
+
+        targeting.target = target;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy_app::App;
+    use valence_protocol::block::{BlockFace, BlockState};
+    use valence_protocol::BlockPos;
+
+    use super::*;
+    use crate::entity::EntityKind;
+    use crate::instance::Chunk;
+    use crate::unit_test::util::scenario_single_client;
+
+    #[test]
+    fn targets_are_cached_and_changes_are_reported() {
+        let mut app = App::new();
+        let (client_ent, _client_helper) = scenario_single_client(&mut app);
+        let instance_ent = app.world.get::<Client>(client_ent).unwrap().instance();
+
+        let mut instance = app.world.get_mut::<Instance>(instance_ent).unwrap();
+        instance.insert_chunk([0, 0], Chunk::default());
+        instance.set_block([0, 1, 3], BlockState::STONE);
+
+        // Look south at the stone.
+        let mut client = app.world.get_mut::<Client>(client_ent).unwrap();
+        client.set_position([0.5, 0.0, 0.5]);
+        client.set_yaw(0.0);
+        client.set_pitch(0.0);
+
+        app.world
+            .entity_mut(client_ent)
+            .insert(Targeting::new().with_interval(2));
+
+        app.update();
+
+        let targeting = app.world.get::<Targeting>(client_ent).unwrap();
+        let hit = targeting.block().unwrap();
+        assert_eq!(hit.block_pos, BlockPos::new(0, 1, 3));
+        assert_eq!(hit.face, BlockFace::North);
+        assert!((hit.distance - 2.5).abs() < 1e-6);
+
+        // An entity in front of the block is targeted instead.
+        let mut zombie = McEntity::new(EntityKind::Zombie, instance_ent);
+        zombie.set_position([0.5, 0.0, 2.0]);
+        let zombie_ent = app.world.spawn(zombie).id();
+
+        // The target is only updated every other tick.
+        app.update();
+        assert_eq!(
+            app.world.get::<Targeting>(client_ent).unwrap().entity(),
+            None
+        );
+
+        app.update();
+        assert_eq!(
+            app.world.get::<Targeting>(client_ent).unwrap().entity(),
+            Some(zombie_ent)
+        );
+
+        let events = app.world.resource::<Events<TargetChanged>>();
+        assert_eq!(events.get_reader().iter(events).count(), 1);
+    }
+}
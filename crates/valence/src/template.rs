@@ -0,0 +1,216 @@
+//! Spawning many instances from the same map.
+//!
+//! A [`WorldTemplate`] is an immutable copy of a world, usually loaded once at
+//! startup. Instances created from a template share the blocks and biomes of
+//! its chunks, and a chunk section is only copied into an instance when the
+//! instance modifies it. Dozens of rounds of a minigame can be played on the
+//! same map at once while only the sections changed in each round take up
+//! extra memory:
+//!
+//! ```
+//! use bevy_app::prelude::*;
+//! use valence::prelude::*;
+//! use valence::template::{TemplateRegistry, WorldTemplate};
+//!
+//! let mut app = App::new();
+//! app.add_plugin(ServerPlugin::new(()));
+//!
+//! let mut template = WorldTemplate::new(DimensionId::default());
+//! template.insert_chunk([0, 0], Chunk::default());
+//!
+//! let mut registry = TemplateRegistry::new();
+//! registry.insert("arena", template);
+//!
+//! let server = app.world.resource::<Server>();
+//! let instance = registry.get("arena").unwrap().instantiate(server);
+//! app.world.spawn(instance);
+//! ```
+//!
+//! Block entities are not shared and are copied into every instance.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use bevy_ecs::prelude::*;
+
+use crate::dimension::DimensionId;
+use crate::instance::{Chunk, Instance};
+use crate::server::Server;
+use crate::view::ChunkPos;
+use crate::weather::Weather;
+use crate::world_border::WorldBorder;
+
+/// An immutable world from which instances are created.
+#[derive(Clone, Debug)]
+pub struct WorldTemplate {
+    dimension: DimensionId,
+    chunks: Vec<(ChunkPos, Chunk)>,
+    weather: Weather,
+    time_of_day: Option<i64>,
+    world_border: WorldBorder,
+}
+
+impl WorldTemplate {
+    /// Creates a template without chunks. The weather, time of day and world
+    /// border are the defaults of new instances.
+    pub fn new(dimension: DimensionId) -> Self {
+        Self {
+            dimension,
+            chunks: vec![],
+            weather: Weather::CLEAR,
+            time_of_day: None,
+            world_border: WorldBorder::DEFAULT,
+        }
+    }
+
+    /// Creates a template from the chunks, weather, time of day and world
+    /// border of an instance. The template shares the chunk data of the
+    /// instance, so this is cheap even for large instances.
+    pub fn from_instance(instance: &Instance) -> Self {
+        Self {
+            dimension: instance.dimension(),
+            chunks: instance
+                .chunks()
+                .map(|(pos, chunk)| (pos, chunk.to_unloaded()))
+                .collect(),
+            weather: instance.weather(),
+            time_of_day: instance.time_of_day(),
+            world_border: *instance.world_border(),
+        }
+    }
+
+    #[must_use]
+    pub fn with_weather(mut self, weather: Weather) -> Self {
+        self.weather = weather;
+        self
+    }
+
+    #[must_use]
+    pub fn with_time_of_day(mut self, time_of_day: i64) -> Self {
+        self.time_of_day = Some(time_of_day);
+        self
+    }
+
+    #[must_use]
+    pub fn with_world_border(mut self, world_border: WorldBorder) -> Self {
+        self.world_border = world_border;
+        self
+    }
+
+    /// Adds a chunk to the template, replacing the chunk at the same position.
+    /// Templates can only be modified before they are added to the
+    /// [`TemplateRegistry`].
+    pub fn insert_chunk(&mut self, pos: impl Into<ChunkPos>, chunk: Chunk) {
+        let pos = pos.into();
+        self.chunks.retain(|(p, _)| *p != pos);
+        self.chunks.push((pos, chunk));
+    }
+
+    pub fn dimension(&self) -> DimensionId {
+        self.dimension
+    }
+
+    pub fn chunk(&self, pos: impl Into<ChunkPos>) -> Option<&Chunk> {
+        let pos = pos.into();
+        self.chunks
+            .iter()
+            .find(|(p, _)| *p == pos)
+            .map(|(_, chunk)| chunk)
+    }
+
+    pub fn chunks(&self) -> impl ExactSizeIterator<Item = (ChunkPos, &Chunk)> + '_ {
+        self.chunks.iter().map(|(pos, chunk)| (*pos, chunk))
+    }
+
+    /// Creates an instance with the chunks of the template. The instance
+    /// shares the chunk data of the template until it modifies it.
+    pub fn instantiate(&self, server: &Server) -> Instance {
+        let mut instance = server.new_instance(self.dimension);
+
+        for (pos, chunk) in &self.chunks {
+            instance.insert_chunk(*pos, chunk.clone());
+        }
+
+        instance.set_weather(self.weather);
+        instance.set_time_of_day(self.time_of_day);
+        instance.set_world_border(self.world_border);
+        instance
+    }
+}
+
+/// A [`Resource`] with named [`WorldTemplate`]s.
+///
+/// This resource is not added by default.
+#[derive(Resource, Clone, Default, Debug)]
+pub struct TemplateRegistry {
+    templates: HashMap<String, Arc<WorldTemplate>>,
+}
+
+impl TemplateRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a template, replacing the template with the same name. Instances
+    /// created from the replaced template are unaffected.
+    pub fn insert(
+        &mut self,
+        name: impl Into<String>,
+        template: WorldTemplate,
+    ) -> Option<Arc<WorldTemplate>> {
+        self.templates.insert(name.into(), Arc::new(template))
+    }
+
+    pub fn remove(&mut self, name: &str) -> Option<Arc<WorldTemplate>> {
+        self.templates.remove(name)
+    }
+
+    /// Returns the template with the given name. The returned [`Arc`] can be
+    /// kept to create instances without access to the registry.
+    pub fn get(&self, name: &str) -> Option<&Arc<WorldTemplate>> {
+        self.templates.get(name)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &Arc<WorldTemplate>)> + '_ {
+        self.templates
+            .iter()
+            .map(|(name, template)| (name.as_str(), template))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy_app::App;
+    use valence_protocol::block::BlockState;
+
+    use super::*;
+    use crate::client::Client;
+    use crate::unit_test::util::scenario_single_client;
+
+    #[test]
+    fn instances_are_independent_copies() {
+        let mut app = App::new();
+        let (client_ent, _client_helper) = scenario_single_client(&mut app);
+        let instance_ent = app.world.get::<Client>(client_ent).unwrap().instance();
+
+        let mut instance = app.world.get_mut::<Instance>(instance_ent).unwrap();
+        instance.insert_chunk([0, 0], Chunk::default());
+        instance.set_block([0, 0, 0], BlockState::STONE);
+
+        let template = WorldTemplate::from_instance(&instance).with_time_of_day(18000);
+
+        let server = app.world.resource::<Server>();
+        let mut a = template.instantiate(server);
+        let b = template.instantiate(server);
+
+        a.set_block([0, 0, 0], BlockState::DIRT);
+
+        assert_eq!(a.block([0, 0, 0]).unwrap().state(), BlockState::DIRT);
+        assert_eq!(b.block([0, 0, 0]).unwrap().state(), BlockState::STONE);
+        assert_eq!(b.time_of_day(), Some(18000));
+        assert_eq!(
+            template.chunk([0, 0]).unwrap().block_state(0, 0, 0),
+            BlockState::STONE
+        );
+    }
+}
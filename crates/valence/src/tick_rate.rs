@@ -0,0 +1,198 @@
+//! Slowing down and pausing the simulation of instances.
+//!
+//! Adding a [`TickRate`] to the entity of an [`Instance`] makes its simulation
+//! run on only some of the server's ticks, or not at all while paused.
+//! Networking is unaffected: clients in the instance still receive packets,
+//! keep-alives and block changes every tick. This makes idle instances such as
+//! lobbies nearly free.
+//!
+//! Systems which simulate instances should skip the instances in the
+//! [`SkippedInstances`] resource. The built-in systems which do are:
+//!
+//! - weather and fire spread,
+//! - primed TNT and thrown ender pearls,
+//! - dropped items falling and merging,
+//! - animals growing up and leaving love mode, and horses being tamed,
+//! - pets following their owners and entities pushing each other,
+//! - status effects running out and the air supply of entities,
+//! - villagers restocking their trades.
+//!
+//! Everything else, including handling the packets of clients, runs every
+//! tick.
+
+use std::collections::HashSet;
+
+use bevy_ecs::prelude::*;
+
+use crate::instance::Instance;
+
+/// A [`Component`] which controls how often the [`Instance`] on the same
+/// entity is simulated. Instances without this component are simulated every
+/// tick.
+#[derive(Component, Clone, PartialEq, Eq, Debug)]
+pub struct TickRate {
+    interval: u32,
+    paused: bool,
+    /// Ticks since the instance was last simulated.
+    elapsed: u32,
+    simulated_ticks: i64,
+}
+
+impl TickRate {
+    /// Simulates the instance on one out of every `interval` ticks. An
+    /// interval of zero is treated as one.
+    pub fn every(interval: u32) -> Self {
+        Self {
+            interval: interval.max(1),
+            paused: false,
+            elapsed: 0,
+            simulated_ticks: 0,
+        }
+    }
+
+    /// Does not simulate the instance until [`Self::resume`] is called.
+    pub fn paused() -> Self {
+        Self {
+            paused: true,
+            ..Self::every(1)
+        }
+    }
+
+    pub fn interval(&self) -> u32 {
+        self.interval
+    }
+
+    /// Sets the number of ticks between simulated ticks. An interval of zero
+    /// is treated as one.
+    pub fn set_interval(&mut self, interval: u32) {
+        self.interval = interval.max(1);
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    pub fn pause(&mut self) {
+        self.paused = true;
+    }
+
+    pub fn resume(&mut self) {
+        self.paused = false;
+    }
+
+    /// Returns the number of ticks the instance has been simulated for since
+    /// this component was added.
+    pub fn simulated_ticks(&self) -> i64 {
+        self.simulated_ticks
+    }
+
+    /// Advances the rate by one server tick and returns if the instance is
+    /// simulated on it.
+    fn advance(&mut self) -> bool {
+        if self.paused {
+            return false;
+        }
+
+        self.elapsed += 1;
+
+        if self.elapsed < self.interval {
+            return false;
+        }
+
+        self.elapsed = 0;
+        self.simulated_ticks += 1;
+        true
+    }
+}
+
+impl Default for TickRate {
+    fn default() -> Self {
+        Self::every(1)
+    }
+}
+
+/// A [`Resource`] containing the instances which are not simulated on the
+/// current tick because of their [`TickRate`].
+#[derive(Resource, Default, Debug)]
+pub struct SkippedInstances(HashSet<Entity>);
+
+impl SkippedInstances {
+    /// Returns `true` if the instance is not simulated on the current tick.
+    pub fn contains(&self, instance: Entity) -> bool {
+        self.0.contains(&instance)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = Entity> + '_ {
+        self.0.iter().copied()
+    }
+}
+
+pub(crate) fn update_tick_rates(
+    mut instances: Query<(Entity, &mut TickRate), With<Instance>>,
+    mut skipped: ResMut<SkippedInstances>,
+) {
+    skipped.0.clear();
+
+    for (entity, mut rate) in &mut instances {
+        // Counting ticks does not need to trigger change detection.
+        if !rate.bypass_change_detection().advance() {
+            skipped.0.insert(entity);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy_app::App;
+
+    use super::*;
+    use crate::client::Client;
+    use crate::unit_test::util::scenario_single_client;
+
+    #[test]
+    fn intervals_skip_ticks() {
+        let mut rate = TickRate::every(3);
+
+        let simulated = (0..9).filter(|_| rate.advance()).count();
+        assert_eq!(simulated, 3);
+        assert_eq!(rate.simulated_ticks(), 3);
+
+        rate.pause();
+        assert!((0..9).all(|_| !rate.advance()));
+
+        rate.resume();
+        rate.set_interval(0);
+        assert!(rate.advance());
+    }
+
+    #[test]
+    fn paused_instances_are_skipped() {
+        let mut app = App::new();
+        let (client_ent, _) = scenario_single_client(&mut app);
+        let instance_ent = app.world.get::<Client>(client_ent).unwrap().instance();
+
+        app.world
+            .entity_mut(instance_ent)
+            .insert(TickRate::paused());
+        app.update();
+
+        let skipped = app.world.resource::<SkippedInstances>();
+        assert!(skipped.contains(instance_ent));
+
+        app.world
+            .get_mut::<TickRate>(instance_ent)
+            .unwrap()
+            .resume();
+        app.update();
+
+        let skipped = app.world.resource::<SkippedInstances>();
+        assert!(!skipped.contains(instance_ent));
+        assert_eq!(
+            app.world
+                .get::<TickRate>(instance_ent)
+                .unwrap()
+                .simulated_ticks(),
+            1
+        );
+    }
+}
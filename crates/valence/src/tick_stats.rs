@@ -0,0 +1,242 @@
+//! Statistics about the duration of ticks.
+//!
+//! The [`TickStats`] resource keeps the ticks of the last fifteen minutes to
+//! compute the ticks per second (TPS) over the usual 1, 5 and 15 minute windows
+//! and percentiles of the milliseconds per tick (MSPT). This is the data shown
+//! by the `/tps` and `/mspt` commands of other servers. The
+//! [`TickBudget`](crate::budget::TickBudget) uses the same statistics to
+//! detect overload.
+
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use bevy_ecs::prelude::*;
+
+/// The number of recent ticks MSPT statistics are computed over.
+const MSPT_SAMPLES: usize = 1200;
+
+/// The weight of the latest tick in the average tick duration.
+const AVERAGE_WEIGHT: f64 = 0.1;
+
+/// A window of time TPS is averaged over.
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub enum TickWindow {
+    OneMinute,
+    FiveMinutes,
+    FifteenMinutes,
+}
+
+impl TickWindow {
+    pub const ALL: [Self; 3] = [Self::OneMinute, Self::FiveMinutes, Self::FifteenMinutes];
+
+    pub fn duration(self) -> Duration {
+        match self {
+            TickWindow::OneMinute => Duration::from_secs(60),
+            TickWindow::FiveMinutes => Duration::from_secs(5 * 60),
+            TickWindow::FifteenMinutes => Duration::from_secs(15 * 60),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+struct TickSample {
+    start: Instant,
+    duration: Duration,
+}
+
+/// A [`Resource`] containing statistics about recent ticks.
+///
+/// The server's runner records every tick. Custom runners can record ticks
+/// with [`Self::record_tick`].
+#[derive(Resource, Debug)]
+pub struct TickStats {
+    /// The configured tick rate.
+    target_tps: f64,
+    samples: VecDeque<TickSample>,
+    average_tick_duration: Duration,
+}
+
+impl TickStats {
+    pub fn new(target_tps: i64) -> Self {
+        Self {
+            target_tps: target_tps as f64,
+            samples: VecDeque::new(),
+            average_tick_duration: Duration::ZERO,
+        }
+    }
+
+    /// Records a tick which started at `start` and took `duration`.
+    pub fn record_tick(&mut self, start: Instant, duration: Duration) {
+        self.samples.push_back(TickSample { start, duration });
+
+        self.average_tick_duration = if self.average_tick_duration.is_zero() {
+            duration
+        } else {
+            self.average_tick_duration.mul_f64(1.0 - AVERAGE_WEIGHT)
+                + duration.mul_f64(AVERAGE_WEIGHT)
+        };
+
+        let longest = TickWindow::FifteenMinutes.duration();
+
+        while let Some(oldest) = self.samples.front() {
+            if start.saturating_duration_since(oldest.start) <= longest {
+                break;
+            }
+            self.samples.pop_front();
+        }
+    }
+
+    /// Returns the average number of ticks per second over the window, which
+    /// is at most the configured tick rate. If the server has been running
+    /// for less than the window, the average since it started is returned.
+    pub fn tps(&self, window: TickWindow) -> f64 {
+        let (Some(first), Some(last)) = (self.samples.front(), self.samples.back()) else {
+            return self.target_tps;
+        };
+
+        let now = last.start + last.duration;
+        let window_start = now.checked_sub(window.duration()).unwrap_or(first.start);
+
+        let mut ticks = 0;
+        let mut oldest = now;
+
+        for sample in self.samples.iter().rev() {
+            if sample.start < window_start {
+                break;
+            }
+            ticks += 1;
+            oldest = sample.start;
+        }
+
+        let elapsed = now.duration_since(oldest).as_secs_f64();
+
+        if elapsed <= 0.0 {
+            return self.target_tps;
+        }
+
+        (ticks as f64 / elapsed).min(self.target_tps)
+    }
+
+    /// Returns the duration of the most recent tick.
+    pub fn last_tick_duration(&self) -> Duration {
+        self.samples.back().map_or(Duration::ZERO, |s| s.duration)
+    }
+
+    /// Returns a moving average of the tick duration. Unlike
+    /// [`Self::mean_mspt`], it follows changes in load within a few ticks.
+    pub fn average_tick_duration(&self) -> Duration {
+        self.average_tick_duration
+    }
+
+    /// Returns the duration of the most recent tick in milliseconds.
+    pub fn last_mspt(&self) -> f64 {
+        millis(self.last_tick_duration())
+    }
+
+    /// Returns the average milliseconds per tick over the recent ticks.
+    pub fn mean_mspt(&self) -> f64 {
+        let recent = self.recent_mspt();
+
+        if recent.is_empty() {
+            return 0.0;
+        }
+
+        recent.iter().sum::<f64>() / recent.len() as f64
+    }
+
+    /// Returns the `p`th percentile of the milliseconds per tick over the
+    /// recent ticks, where `p` is in the range `0.0..=100.0`. The 100th
+    /// percentile is the longest tick.
+    pub fn mspt_percentile(&self, p: f64) -> f64 {
+        let mut recent = self.recent_mspt();
+
+        if recent.is_empty() {
+            return 0.0;
+        }
+
+        recent.sort_unstable_by(f64::total_cmp);
+
+        let rank = (p.clamp(0.0, 100.0) / 100.0 * (recent.len() - 1) as f64).round();
+        recent[rank as usize]
+    }
+
+    /// Returns the number of ticks the statistics are currently based on.
+    pub fn sample_count(&self) -> usize {
+        self.samples.len()
+    }
+
+    fn recent_mspt(&self) -> Vec<f64> {
+        self.samples
+            .iter()
+            .rev()
+            .take(MSPT_SAMPLES)
+            .map(|s| millis(s.duration))
+            .collect()
+    }
+}
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_nanos() as f64 / 1_000_000.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(stats: &mut TickStats, start: Instant, ticks: u32, interval_ms: u64, mspt: u64) {
+        for i in 0..ticks {
+            stats.record_tick(
+                start + Duration::from_millis(interval_ms * i as u64),
+                Duration::from_millis(mspt),
+            );
+        }
+    }
+
+    #[test]
+    fn tps_is_capped_and_windowed() {
+        let mut stats = TickStats::new(20);
+        assert_eq!(stats.tps(TickWindow::OneMinute), 20.0);
+
+        let start = Instant::now();
+
+        // Five minutes at full speed followed by a minute at half speed.
+        run(&mut stats, start, 20 * 300, 50, 10);
+        assert_eq!(stats.tps(TickWindow::FiveMinutes), 20.0);
+
+        run(
+            &mut stats,
+            start + Duration::from_secs(300),
+            10 * 60,
+            100,
+            90,
+        );
+
+        let one = stats.tps(TickWindow::OneMinute);
+        let five = stats.tps(TickWindow::FiveMinutes);
+        assert!((one - 10.0).abs() < 0.1, "{one}");
+        assert!(five > one && five < 20.0, "{five}");
+    }
+
+    #[test]
+    fn mspt_percentiles() {
+        let mut stats = TickStats::new(20);
+        let start = Instant::now();
+
+        run(&mut stats, start, 99, 50, 10);
+        stats.record_tick(start + Duration::from_secs(5), Duration::from_millis(500));
+
+        assert_eq!(stats.mspt_percentile(50.0), 10.0);
+        assert_eq!(stats.mspt_percentile(100.0), 500.0);
+        assert_eq!(stats.last_mspt(), 500.0);
+        assert!((stats.mean_mspt() - 14.9).abs() < 1e-9);
+    }
+
+    #[test]
+    fn old_ticks_are_forgotten() {
+        let mut stats = TickStats::new(20);
+        let start = Instant::now();
+
+        run(&mut stats, start, 20 * 60 * 16, 50, 1);
+        assert!(stats.sample_count() <= 20 * 60 * 15 + 1);
+    }
+}
@@ -0,0 +1,524 @@
+//! Trading screens of villagers and other merchants.
+//!
+//! An [`Inventory`] of the kind [`InventoryKind::Merchant`] with a
+//! [`MerchantOffers`] component on its entity is a trading screen. Clients
+//! opening it are sent the offers, and the server fills in the result slot
+//! when the items in the input slots pay for an offer. Selecting an offer in
+//! the list moves the items it costs from the inventory of the client into the
+//! input slots. Every trade is reported with a [`TradeEvent`]:
+//!
+//! ```
+//! use valence::entity::villager::VillagerOffer;
+//! use valence::prelude::*;
+//! use valence::trading::{merchant_inventory, MerchantOffers};
+//!
+//! fn open_shop(mut commands: Commands, clients: Query<Entity, Added<Client>>) {
+//!     for client in &clients {
+//!         let offer = VillagerOffer::new(
+//!             ItemStack::new(ItemKind::Emerald, 3, None),
+//!             ItemStack::new(ItemKind::Bread, 6, None),
+//!         )
+//!         .with_max_uses(16);
+//!
+//!         let shop = commands
+//!             .spawn((
+//!                 merchant_inventory("Baker"),
+//!                 MerchantOffers::new(vec![offer]),
+//!             ))
+//!             .id();
+//!
+//!         commands.entity(client).insert(OpenInventory::new(shop));
+//!     }
+//! }
+//! ```
+//!
+//! The offers are [`VillagerOffer`]s, so they have the prices, uses and
+//! experience of villager trades. [`MerchantOffers::from_villager`] creates a
+//! screen with the offers a [`Villager`] makes to a player.
+
+use bevy_ecs::prelude::*;
+use valence_protocol::packets::s2c::play::MerchantOffers as MerchantOffersS2c;
+use valence_protocol::types::{ClickContainerMode, MerchantTrade};
+use valence_protocol::{ItemStack, Text, VarInt};
+
+use crate::client::event::SelectTrade;
+use crate::client::Client;
+use crate::crafting::{can_stack, deposit};
+use crate::entity::villager::{Villager, VillagerOffer, VillagerOffers};
+use crate::inventory::{Inventory, InventoryKind, OpenInventory};
+
+/// The slot of a trading screen holding the first item an offer costs.
+pub const MERCHANT_INPUT_ONE_SLOT: u16 = 0;
+/// The slot of a trading screen holding the second item an offer costs.
+pub const MERCHANT_INPUT_TWO_SLOT: u16 = 1;
+/// The slot of a trading screen holding the result.
+pub const MERCHANT_RESULT_SLOT: u16 = 2;
+
+/// A [`Component`] which makes the merchant [`Inventory`] on the same entity
+/// a trading screen with the given offers.
+#[derive(Component, Clone, PartialEq, Debug)]
+pub struct MerchantOffers {
+    pub offers: Vec<VillagerOffer>,
+    /// The level shown at the top of the screen, from 1 to 5. The level and
+    /// the experience bar are hidden for `0`.
+    pub villager_level: i32,
+    /// The trading experience shown by the experience bar.
+    pub xp: i32,
+    /// If the screen shows the level and experience bar of a villager.
+    pub is_regular_villager: bool,
+    /// If out of stock offers tell the player that the merchant restocks.
+    pub can_restock: bool,
+    /// The reputation of the trading player, which changes the prices.
+    pub reputation: i32,
+    selected: Option<usize>,
+}
+
+impl MerchantOffers {
+    pub fn new(offers: Vec<VillagerOffer>) -> Self {
+        Self {
+            offers,
+            villager_level: 0,
+            xp: 0,
+            is_regular_villager: false,
+            can_restock: false,
+            reputation: 0,
+            selected: None,
+        }
+    }
+
+    /// Creates a screen with the offers available from a villager, at the
+    /// prices for a player with the given reputation.
+    pub fn from_villager(villager: &Villager, offers: &VillagerOffers, reputation: i32) -> Self {
+        Self {
+            villager_level: villager.level(),
+            xp: villager.xp(),
+            is_regular_villager: true,
+            can_restock: true,
+            reputation,
+            ..Self::new(offers.available(villager).cloned().collect())
+        }
+    }
+
+    #[must_use]
+    pub fn with_reputation(mut self, reputation: i32) -> Self {
+        self.reputation = reputation;
+        self
+    }
+
+    /// Returns the index of the offer the client selected last.
+    pub fn selected(&self) -> Option<usize> {
+        self.selected
+    }
+
+    /// Returns the trades sent to clients.
+    pub fn trades(&self) -> Vec<MerchantTrade> {
+        self.offers
+            .iter()
+            .map(|offer| offer.to_merchant_trade(self.reputation))
+            .collect()
+    }
+
+    /// Returns the index of the offer the given input items pay for, and
+    /// whether the inputs are swapped. The selected offer is preferred.
+    fn matching_offer(
+        &self,
+        input_one: Option<&ItemStack>,
+        input_two: Option<&ItemStack>,
+    ) -> Option<(usize, bool)> {
+        let pays = |idx: usize| {
+            let offer = &self.offers[idx];
+
+            if offer.is_out_of_stock() {
+                None
+            } else if pays_for(offer, self.reputation, input_one, input_two) {
+                Some((idx, false))
+            } else if pays_for(offer, self.reputation, input_two, input_one) {
+                Some((idx, true))
+            } else {
+                None
+            }
+        };
+
+        self.selected
+            .filter(|&idx| idx < self.offers.len())
+            .and_then(pays)
+            .or_else(|| (0..self.offers.len()).find_map(pays))
+    }
+
+    /// Returns the result of the trade the items in the input slots of the
+    /// inventory pay for.
+    pub fn result(&self, inventory: &Inventory) -> Option<ItemStack> {
+        let (idx, _) = self.matching_offer(
+            inventory.slot(MERCHANT_INPUT_ONE_SLOT),
+            inventory.slot(MERCHANT_INPUT_TWO_SLOT),
+        )?;
+
+        Some(self.offers[idx].output.clone())
+    }
+
+    /// Carries out the trade paid for by the items in the input slots, taking
+    /// the price out of them. Returns the index of the offer used.
+    fn trade(&mut self, inventory: &mut Inventory) -> Option<usize> {
+        let (idx, swapped) = self.matching_offer(
+            inventory.slot(MERCHANT_INPUT_ONE_SLOT),
+            inventory.slot(MERCHANT_INPUT_TWO_SLOT),
+        )?;
+
+        let (first_slot, second_slot) = if swapped {
+            (MERCHANT_INPUT_TWO_SLOT, MERCHANT_INPUT_ONE_SLOT)
+        } else {
+            (MERCHANT_INPUT_ONE_SLOT, MERCHANT_INPUT_TWO_SLOT)
+        };
+
+        let offer = &mut self.offers[idx];
+
+        shrink_slot(inventory, first_slot, offer.price(self.reputation) as u8);
+
+        if let Some(input_two) = &offer.input_two {
+            shrink_slot(inventory, second_slot, input_two.count());
+        }
+
+        offer.uses += 1;
+
+        Some(idx)
+    }
+}
+
+/// Returns `true` if `stack` is the item `cost` asks for. Costs without NBT
+/// accept items with any NBT.
+fn is_cost_item(stack: &ItemStack, cost: &ItemStack) -> bool {
+    stack.item == cost.item && (cost.nbt.is_none() || cost.nbt == stack.nbt)
+}
+
+fn pays_for(
+    offer: &VillagerOffer,
+    reputation: i32,
+    first: Option<&ItemStack>,
+    second: Option<&ItemStack>,
+) -> bool {
+    let first_pays = first.map_or(false, |stack| {
+        is_cost_item(stack, &offer.input_one) && stack.count() as i32 >= offer.price(reputation)
+    });
+
+    let second_pays = match (second, &offer.input_two) {
+        (None, None) => true,
+        (Some(stack), Some(cost)) => is_cost_item(stack, cost) && stack.count() >= cost.count(),
+        _ => false,
+    };
+
+    first_pays && second_pays
+}
+
+fn shrink_slot(inventory: &mut Inventory, slot: u16, count: u8) {
+    let Some(mut stack) = inventory.slot(slot).cloned() else {
+        return;
+    };
+
+    if stack.count() > count {
+        stack.set_count(stack.count() - count);
+        inventory.replace_slot(slot, stack);
+    } else {
+        inventory.replace_slot(slot, None);
+    }
+}
+
+/// Creates an empty trading screen inventory with the given title.
+pub fn merchant_inventory(title: impl Into<Text>) -> Inventory {
+    Inventory::with_title(InventoryKind::Merchant, title)
+}
+
+/// An event sent when a client trades with a merchant. Shift-clicking the
+/// result can carry out several trades, which are reported separately.
+#[derive(Clone, PartialEq, Debug)]
+pub struct TradeEvent {
+    pub client: Entity,
+    /// The entity of the trading screen [`Inventory`].
+    pub merchant: Entity,
+    /// The index of the offer used in [`MerchantOffers::offers`].
+    pub offer: usize,
+    /// The item the client received.
+    pub output: ItemStack,
+}
+
+/// Handles a click on the result slot of a trading screen. Clicking puts the
+/// result on the cursor and shift-clicking trades as often as possible into
+/// the inventory of the player. Returns the indices of the offers used.
+pub(crate) fn take_trade_result(
+    client: &mut Client,
+    merchant: &mut MerchantOffers,
+    inventory: &mut Inventory,
+    player: &mut Inventory,
+    mode: ClickContainerMode,
+) -> Vec<usize> {
+    let mut used = vec![];
+
+    match mode {
+        ClickContainerMode::Click => {
+            let Some(result) = merchant.result(inventory) else {
+                return used;
+            };
+
+            let cursor = match client.cursor_item() {
+                None => result,
+                Some(cursor)
+                    if can_stack(cursor, &result)
+                        && cursor.count() as u16 + result.count() as u16
+                            <= cursor.item.max_stack() as u16 =>
+                {
+                    let mut cursor = cursor.clone();
+                    cursor.set_count(cursor.count() + result.count());
+                    cursor
+                }
+                Some(_) => return used,
+            };
+
+            used.extend(merchant.trade(inventory));
+            client.replace_cursor_item(cursor);
+        }
+        ClickContainerMode::ShiftClick => {
+            // A stack of the result is at most 64 trades.
+            for _ in 0..64 {
+                let Some(result) = merchant.result(inventory) else {
+                    break;
+                };
+
+                if !deposit(player, &result) {
+                    break;
+                }
+
+                used.extend(merchant.trade(inventory));
+            }
+        }
+        _ => {}
+    }
+
+    inventory.replace_slot(MERCHANT_RESULT_SLOT, merchant.result(inventory));
+    used
+}
+
+/// Selects the offers clients click in the list of a trading screen, and
+/// moves the items the offer costs from the inventory of the client into the
+/// input slots.
+pub(crate) fn handle_select_trade(
+    mut events: EventReader<SelectTrade>,
+    mut clients: Query<(&mut Inventory, &OpenInventory), With<Client>>,
+    mut merchants: Query<(&mut Inventory, &mut MerchantOffers), Without<Client>>,
+) {
+    for event in events.iter() {
+        let Ok((mut player, open_inventory)) = clients.get_mut(event.client) else {
+            continue;
+        };
+
+        let Ok((mut inventory, mut merchant)) = merchants.get_mut(open_inventory.entity()) else {
+            continue;
+        };
+
+        let Ok(idx) = usize::try_from(event.slot) else {
+            continue;
+        };
+
+        let Some(offer) = merchant.offers.get(idx).cloned() else {
+            continue;
+        };
+
+        merchant.selected = Some(idx);
+
+        let inputs = [
+            (MERCHANT_INPUT_ONE_SLOT, Some(&offer.input_one)),
+            (MERCHANT_INPUT_TWO_SLOT, offer.input_two.as_ref()),
+        ];
+
+        for (slot, cost) in inputs {
+            // Items which aren't part of the price go back to the player.
+            if let Some(stack) = inventory.slot(slot).cloned() {
+                if cost.map_or(true, |cost| !is_cost_item(&stack, cost))
+                    && deposit(&mut player, &stack)
+                {
+                    inventory.replace_slot(slot, None);
+                }
+            }
+
+            if let Some(cost) = cost {
+                fill_input(&mut inventory, slot, &mut player, cost);
+            }
+        }
+    }
+}
+
+/// Fills the input slot with items for the cost from the main inventory and
+/// the hotbar of the player, up to a full stack.
+fn fill_input(inventory: &mut Inventory, slot: u16, player: &mut Inventory, cost: &ItemStack) {
+    for player_slot in 9..45 {
+        let Some(stack) = player.slot(player_slot).cloned() else {
+            continue;
+        };
+
+        if !is_cost_item(&stack, cost) {
+            continue;
+        }
+
+        let max = stack.item.max_stack();
+
+        let moved = match inventory.slot(slot) {
+            None => {
+                let moved = stack.count().min(max);
+                let mut new = stack.clone();
+                new.set_count(moved);
+                inventory.replace_slot(slot, new);
+                moved
+            }
+            Some(current) if can_stack(current, &stack) => {
+                let moved = stack.count().min(max.saturating_sub(current.count()));
+
+                if moved == 0 {
+                    return;
+                }
+
+                let mut current = current.clone();
+                current.set_count(current.count() + moved);
+                inventory.replace_slot(slot, current);
+                moved
+            }
+            Some(_) => continue,
+        };
+
+        shrink_slot(player, player_slot, moved);
+    }
+}
+
+/// Updates the result slot of changed trading screens.
+pub(crate) fn update_merchant_results(
+    mut merchants: Query<
+        (&mut Inventory, &MerchantOffers),
+        Or<(Changed<Inventory>, Changed<MerchantOffers>)>,
+    >,
+) {
+    for (mut inventory, merchant) in &mut merchants {
+        if inventory.kind() != InventoryKind::Merchant {
+            continue;
+        }
+
+        let result = merchant.result(&inventory);
+
+        // Avoid marking the inventory as changed again.
+        if inventory.slot(MERCHANT_RESULT_SLOT) != result.as_ref() {
+            inventory.replace_slot(MERCHANT_RESULT_SLOT, result);
+        }
+    }
+}
+
+/// Sends the offers of trading screens to the clients which just opened
+/// them, and to every viewer when they change.
+pub(crate) fn send_merchant_offers(
+    mut clients: Query<(&mut Client, &OpenInventory, ChangeTrackers<OpenInventory>)>,
+    merchants: Query<(&MerchantOffers, ChangeTrackers<MerchantOffers>)>,
+) {
+    for (mut client, open_inventory, open_tracker) in &mut clients {
+        let Ok((merchant, merchant_tracker)) = merchants.get(open_inventory.entity()) else {
+            continue;
+        };
+
+        if open_tracker.is_added() || merchant_tracker.is_changed() {
+            let window_id = client.window_id;
+
+            client.write_packet(&MerchantOffersS2c {
+                window_id: VarInt(window_id.into()),
+                trades: merchant.trades(),
+                villager_level: VarInt(merchant.villager_level),
+                experience: VarInt(merchant.xp),
+                is_regular_villager: merchant.is_regular_villager,
+                can_restock: merchant.can_restock,
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy_app::App;
+    use valence_protocol::packets::c2s::play::{
+        ClickContainer as ClickContainerC2s, SelectTrade as SelectTradeC2s,
+    };
+    use valence_protocol::packets::S2cPlayPacket;
+    use valence_protocol::ItemKind;
+
+    use super::*;
+    use crate::assert_packet_count;
+    use crate::unit_test::util::scenario_single_client;
+
+    #[test]
+    fn trading_takes_the_price_and_gives_the_output() -> anyhow::Result<()> {
+        let mut app = App::new();
+        let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+
+        let bread = ItemStack::new(ItemKind::Bread, 6, None);
+        let offer = VillagerOffer::new(ItemStack::new(ItemKind::Emerald, 3, None), bread.clone());
+
+        let merchant_ent = app
+            .world
+            .spawn((
+                merchant_inventory("Baker"),
+                MerchantOffers::new(vec![offer]),
+            ))
+            .id();
+
+        let mut player = app.world.get_mut::<Inventory>(client_ent).unwrap();
+        player.replace_slot(20, ItemStack::new(ItemKind::Emerald, 7, None));
+
+        app.world
+            .entity_mut(client_ent)
+            .insert(OpenInventory::new(merchant_ent));
+
+        app.update();
+
+        let sent_packets = client_helper.collect_sent()?;
+        assert_packet_count!(sent_packets, 1, S2cPlayPacket::MerchantOffers(_));
+
+        client_helper.send(&SelectTradeC2s {
+            selected_slot: VarInt(0),
+        });
+
+        app.update();
+
+        let inventory = app.world.get::<Inventory>(merchant_ent).unwrap();
+        assert_eq!(
+            inventory.slot(MERCHANT_INPUT_ONE_SLOT),
+            Some(&ItemStack::new(ItemKind::Emerald, 7, None))
+        );
+        assert_eq!(inventory.slot(MERCHANT_RESULT_SLOT), Some(&bread));
+
+        let player = app.world.get::<Inventory>(client_ent).unwrap();
+        assert_eq!(player.slot(20), None);
+
+        let client = app.world.get::<Client>(client_ent).unwrap();
+        let (window_id, state_id) = (client.window_id, client.inventory_state_id.0);
+
+        client_helper.send(&ClickContainerC2s {
+            window_id,
+            state_id: VarInt(state_id),
+            slot_idx: MERCHANT_RESULT_SLOT as i16,
+            button: 0,
+            mode: ClickContainerMode::ShiftClick,
+            slots: vec![],
+            carried_item: None,
+        });
+
+        app.update();
+
+        // 7 emeralds pay for two trades.
+        let inventory = app.world.get::<Inventory>(merchant_ent).unwrap();
+        assert_eq!(
+            inventory.slot(MERCHANT_INPUT_ONE_SLOT),
+            Some(&ItemStack::new(ItemKind::Emerald, 1, None))
+        );
+        assert_eq!(inventory.slot(MERCHANT_RESULT_SLOT), None);
+
+        let merchant = app.world.get::<MerchantOffers>(merchant_ent).unwrap();
+        assert_eq!(merchant.offers[0].uses, 2);
+
+        let trades = app.world.resource::<Events<TradeEvent>>();
+        assert_eq!(trades.get_reader().iter(trades).count(), 2);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,230 @@
+//! Regions which react to clients entering and leaving them.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use bevy_ecs::prelude::*;
+use glam::DVec3;
+use rustc_hash::FxHashSet;
+
+use crate::client::event::ChatCommand;
+use crate::client::Client;
+use crate::math::Aabb;
+
+/// A [`Component`] for a region of an [`Instance`] which sends
+/// [`EnterTrigger`] and [`LeaveTrigger`] events when clients enter or leave
+/// it.
+///
+/// A client is inside of the region if the position of its feet is. Regions
+/// are checked once per tick, so a client moving quickly can pass through a
+/// small region without triggering it.
+///
+/// [`Instance`]: crate::instance::Instance
+#[derive(Component, Clone, Debug)]
+pub struct TriggerRegion {
+    /// The instance the region is located in.
+    pub instance: Entity,
+    pub shape: TriggerShape,
+    /// What happens to clients when they enter the region.
+    pub action: Option<TriggerAction>,
+    occupants: FxHashSet<Entity>,
+}
+
+impl TriggerRegion {
+    pub fn new(instance: Entity, shape: impl Into<TriggerShape>) -> Self {
+        Self {
+            instance,
+            shape: shape.into(),
+            action: None,
+            occupants: FxHashSet::default(),
+        }
+    }
+
+    #[must_use]
+    pub fn with_action(mut self, action: TriggerAction) -> Self {
+        self.action = Some(action);
+        self
+    }
+
+    /// Returns if the client was inside of the region at the end of the
+    /// previous tick.
+    pub fn contains(&self, client: Entity) -> bool {
+        self.occupants.contains(&client)
+    }
+
+    /// Returns the clients which were inside of the region at the end of the
+    /// previous tick.
+    pub fn occupants(&self) -> impl Iterator<Item = Entity> + '_ {
+        self.occupants.iter().copied()
+    }
+}
+
+/// The shape of a [`TriggerRegion`].
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum TriggerShape {
+    Cuboid(Aabb),
+    Sphere { center: DVec3, radius: f64 },
+}
+
+impl TriggerShape {
+    pub fn contains_point(&self, point: DVec3) -> bool {
+        match self {
+            TriggerShape::Cuboid(aabb) => {
+                point.cmpge(aabb.min).all() && point.cmple(aabb.max).all()
+            }
+            TriggerShape::Sphere { center, radius } => {
+                center.distance_squared(point) <= radius * radius
+            }
+        }
+    }
+}
+
+impl From<Aabb> for TriggerShape {
+    fn from(aabb: Aabb) -> Self {
+        Self::Cuboid(aabb)
+    }
+}
+
+/// An action performed on clients entering a [`TriggerRegion`].
+#[derive(Clone, PartialEq, Debug)]
+pub enum TriggerAction {
+    /// Teleports the client to the position, optionally in another instance.
+    Teleport {
+        instance: Option<Entity>,
+        position: DVec3,
+    },
+    /// Runs a command as if the client had sent it. The command is sent as a
+    /// [`ChatCommand`] event and should not include the leading slash.
+    Command(String),
+}
+
+/// An event sent when a client enters a [`TriggerRegion`].
+#[derive(Clone, Debug)]
+pub struct EnterTrigger {
+    pub client: Entity,
+    pub region: Entity,
+}
+
+/// An event sent when a client leaves a [`TriggerRegion`]. This is also sent
+/// when the client is despawned while inside of the region.
+#[derive(Clone, Debug)]
+pub struct LeaveTrigger {
+    pub client: Entity,
+    pub region: Entity,
+}
+
+pub(crate) fn update_trigger_regions(
+    mut regions: Query<(Entity, &mut TriggerRegion)>,
+    mut clients: Query<(Entity, &mut Client)>,
+    mut enter_events: EventWriter<EnterTrigger>,
+    mut leave_events: EventWriter<LeaveTrigger>,
+    mut chat_commands: EventWriter<ChatCommand>,
+) {
+    for (region_id, mut region) in &mut regions {
+        let region = &mut *region;
+
+        region.occupants.retain(|&client_id| {
+            let inside = clients.get(client_id).map_or(false, |(_, client)| {
+                client.instance() == region.instance
+                    && region.shape.contains_point(client.position())
+            });
+
+            if !inside {
+                leave_events.send(LeaveTrigger {
+                    client: client_id,
+                    region: region_id,
+                });
+            }
+
+            inside
+        });
+
+        for (client_id, mut client) in &mut clients {
+            if client.instance() != region.instance
+                || region.occupants.contains(&client_id)
+                || !region.shape.contains_point(client.position())
+            {
+                continue;
+            }
+
+            region.occupants.insert(client_id);
+
+            enter_events.send(EnterTrigger {
+                client: client_id,
+                region: region_id,
+            });
+
+            match &region.action {
+                Some(TriggerAction::Teleport { instance, position }) => {
+                    if let Some(instance) = *instance {
+                        client.set_instance(instance);
+                    }
+
+                    client.set_position(*position);
+                }
+                Some(TriggerAction::Command(command)) => {
+                    let timestamp = SystemTime::now()
+                        .duration_since(UNIX_EPOCH)
+                        .map_or(0, |d| d.as_millis() as u64);
+
+                    chat_commands.send(ChatCommand {
+                        client: client_id,
+                        command: command.as_str().into(),
+                        timestamp,
+                    });
+                }
+                None => {}
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy_app::App;
+
+    use super::*;
+    use crate::unit_test::util::scenario_single_client;
+
+    #[test]
+    fn teleport_region_sends_enter_and_leave() {
+        let mut app = App::new();
+        let (client_ent, _) = scenario_single_client(&mut app);
+        let instance_ent = app.world.get::<Client>(client_ent).unwrap().instance();
+
+        let region_ent = app
+            .world
+            .spawn(
+                TriggerRegion::new(
+                    instance_ent,
+                    TriggerShape::Sphere {
+                        center: DVec3::ZERO,
+                        radius: 2.0,
+                    },
+                )
+                .with_action(TriggerAction::Teleport {
+                    instance: None,
+                    position: DVec3::new(10.0, 0.0, 0.0),
+                }),
+            )
+            .id();
+
+        app.update();
+
+        let client = app.world.get::<Client>(client_ent).unwrap();
+        assert_eq!(client.position(), DVec3::new(10.0, 0.0, 0.0));
+
+        let region = app.world.get::<TriggerRegion>(region_ent).unwrap();
+        assert!(region.contains(client_ent));
+
+        app.update();
+
+        let region = app.world.get::<TriggerRegion>(region_ent).unwrap();
+        assert!(!region.contains(client_ent));
+
+        let leave_events = app.world.resource::<Events<LeaveTrigger>>();
+        let mut reader = leave_events.get_reader();
+        assert!(reader
+            .iter(leave_events)
+            .any(|e| e.client == client_ent && e.region == region_ent));
+    }
+}
@@ -6,9 +6,13 @@ use bytes::BytesMut;
 use valence_protocol::packets::S2cPlayPacket;
 use valence_protocol::{EncodePacket, PacketDecoder, PacketEncoder, Username};
 
+use crate::client::pose::PoseState;
+use crate::client::profile::GameProfile;
 use crate::client::{Client, ClientConnection};
 use crate::config::{ConnectionMode, ServerPlugin};
 use crate::dimension::DimensionId;
+use crate::entity::equipment::{Equipments, HeldItem};
+use crate::fall::FallDistance;
 use crate::inventory::{Inventory, InventoryKind};
 use crate::server::{NewClientInfo, Server};
 
@@ -32,6 +36,7 @@ pub fn gen_client_info(username: &str) -> NewClientInfo {
         uuid: uuid::Uuid::new_v4(),
         ip: std::net::IpAddr::V4(std::net::Ipv4Addr::new(127, 0, 0, 1)),
         properties: vec![],
+        spawn: None,
     }
 }
 
@@ -169,9 +174,22 @@ pub fn scenario_single_client(app: &mut App) -> (Entity, MockClientHelper) {
     let (mut client, client_helper) = create_mock_client(info);
     // HACK: needed so client does not get disconnected on first update
     client.set_instance(instance_ent);
+    let profile = GameProfile::new(
+        client.username().to_owned_username(),
+        client.uuid(),
+        client.properties().to_vec(),
+    );
     let client_ent = app
         .world
-        .spawn((client, Inventory::new(InventoryKind::Player)))
+        .spawn((
+            client,
+            profile,
+            Inventory::new(InventoryKind::Player),
+            PoseState::default(),
+            FallDistance::default(),
+            Equipments::default(),
+            HeldItem::default(),
+        ))
         .id();
     (client_ent, client_helper)
 }
@@ -0,0 +1,240 @@
+//! Handling entities which fall into the void or leave the world border.
+
+use bevy_ecs::prelude::*;
+use glam::DVec3;
+
+use crate::client::Client;
+use crate::damage::{DamageKind, EntityDamage};
+use crate::entity::McEntity;
+use crate::fall::FallDistance;
+use crate::instance::Instance;
+use crate::server::Server;
+use crate::world_border::WorldBorder;
+use crate::Despawned;
+
+/// A [`Component`] controlling what happens to entities and clients which are
+/// out of bounds in an [`Instance`].
+///
+/// Insert this component on the same entity as the instance to change its
+/// policy. Instances without this component use the default policy, which
+/// damages entities that have fallen far enough into the void like in
+/// vanilla.
+#[derive(Component, Clone, PartialEq, Debug)]
+pub struct OutOfBoundsPolicy {
+    /// The distance below the minimum Y coordinate of the instance at which
+    /// entities are out of bounds.
+    ///
+    /// # Default Value
+    ///
+    /// `64.0`
+    pub void_depth: f64,
+    /// If entities outside of the [`WorldBorder`] of the instance are also out
+    /// of bounds. The world border displayed to a client is used for clients.
+    ///
+    /// # Default Value
+    ///
+    /// `false`
+    pub world_border: bool,
+    /// What happens to the entities which are out of bounds.
+    ///
+    /// # Default Value
+    ///
+    /// `OutOfBoundsAction::Damage(4.0)`
+    pub action: OutOfBoundsAction,
+}
+
+impl Default for OutOfBoundsPolicy {
+    fn default() -> Self {
+        Self {
+            void_depth: 64.0,
+            world_border: false,
+            action: OutOfBoundsAction::Damage(4.0),
+        }
+    }
+}
+
+/// What happens to an entity which is out of bounds.
+#[derive(Clone, PartialEq, Debug)]
+pub enum OutOfBoundsAction {
+    /// Deals the given amount of damage every half second until the entity
+    /// is back in bounds. The damage is reported with the [`EntityDamage`]
+    /// event.
+    Damage(f32),
+    /// Despawns entities and deals an infinite amount of damage to clients.
+    Kill,
+    /// Teleports the entity to the position, optionally in another instance.
+    /// This can be used to respawn clients or send them back to a lobby.
+    Teleport {
+        instance: Option<Entity>,
+        position: DVec3,
+    },
+}
+
+/// Returns if `position` is outside of the world border.
+fn outside_border(border: &WorldBorder, position: DVec3) -> bool {
+    let radius = border.diameter / 2.0;
+
+    (position.x - border.center[0]).abs() > radius || (position.z - border.center[1]).abs() > radius
+}
+
+type NonClientEntity = (Without<Client>, Without<Despawned>);
+
+pub(crate) fn handle_out_of_bounds(
+    mut commands: Commands,
+    server: Res<Server>,
+    instances: Query<(&Instance, Option<&OutOfBoundsPolicy>)>,
+    mut entities: Query<(Entity, &mut McEntity), NonClientEntity>,
+    mut clients: Query<(Entity, &mut Client, Option<&mut FallDistance>)>,
+    mut damage: EventWriter<EntityDamage>,
+) {
+    let default_policy = OutOfBoundsPolicy::default();
+
+    let check = |instance: Entity, position: DVec3, border: Option<&WorldBorder>| {
+        let (instance, policy) = instances.get(instance).ok()?;
+        let policy = policy.unwrap_or(&default_policy);
+
+        if position.y < instance.min_y() as f64 - policy.void_depth {
+            Some((DamageKind::Void, policy.action.clone()))
+        } else if policy.world_border
+            && outside_border(border.unwrap_or(instance.world_border()), position)
+        {
+            Some((DamageKind::OutsideBorder, policy.action.clone()))
+        } else {
+            None
+        }
+    };
+
+    let mut send_damage = |entity, kind, amount| {
+        damage.send(EntityDamage {
+            entity,
+            source: None,
+            kind,
+            amount,
+        });
+    };
+
+    let damage_tick = server.current_tick() % 10 == 0;
+
+    for (entity, mut mc_entity) in &mut entities {
+        let Some((kind, action)) = check(mc_entity.instance(), mc_entity.position(), None) else {
+            continue
+        };
+
+        match action {
+            OutOfBoundsAction::Damage(amount) => {
+                if damage_tick {
+                    send_damage(entity, kind, amount);
+                }
+            }
+            OutOfBoundsAction::Kill => {
+                commands.entity(entity).insert(Despawned);
+            }
+            OutOfBoundsAction::Teleport { instance, position } => {
+                if let Some(instance) = instance {
+                    mc_entity.set_instance(instance);
+                }
+
+                mc_entity.set_position(position);
+            }
+        }
+    }
+
+    for (entity, mut client, fall) in &mut clients {
+        let Some((kind, action)) = check(
+            client.instance(),
+            client.position(),
+            client.world_border_override(),
+        ) else {
+            continue
+        };
+
+        match action {
+            OutOfBoundsAction::Damage(amount) => {
+                if damage_tick {
+                    send_damage(entity, kind, amount);
+                }
+            }
+            OutOfBoundsAction::Kill => send_damage(entity, kind, f32::INFINITY),
+            OutOfBoundsAction::Teleport { instance, position } => {
+                if let Some(instance) = instance {
+                    client.set_instance(instance);
+                }
+
+                client.set_position(position);
+
+                if let Some(mut fall) = fall {
+                    fall.distance = 0.0;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy_app::App;
+
+    use super::*;
+    use crate::entity::EntityKind;
+    use crate::unit_test::util::scenario_single_client;
+
+    #[test]
+    fn out_of_bounds_entities_are_handled() {
+        let mut app = App::new();
+        let (client_ent, _) = scenario_single_client(&mut app);
+        let instance_ent = app.world.get::<Client>(client_ent).unwrap().instance();
+
+        let mut instance = app.world.get_mut::<Instance>(instance_ent).unwrap();
+        instance.set_world_border(WorldBorder::new([0.0, 0.0], 100.0));
+
+        app.world
+            .entity_mut(instance_ent)
+            .insert(OutOfBoundsPolicy {
+                void_depth: 0.0,
+                world_border: true,
+                action: OutOfBoundsAction::Teleport {
+                    instance: None,
+                    position: DVec3::new(0.0, 100.0, 0.0),
+                },
+            });
+
+        let mut pig = McEntity::new(EntityKind::Pig, instance_ent);
+        pig.set_position([60.0, 0.0, 0.0]);
+        let pig_ent = app.world.spawn(pig).id();
+
+        let mut client = app.world.get_mut::<Client>(client_ent).unwrap();
+        client.set_position([0.0, -1000.0, 0.0]);
+
+        app.world
+            .get_mut::<FallDistance>(client_ent)
+            .unwrap()
+            .distance = 500.0;
+
+        app.update();
+
+        let pig = app.world.get::<McEntity>(pig_ent).unwrap();
+        assert_eq!(pig.position(), DVec3::new(0.0, 100.0, 0.0));
+
+        let client = app.world.get::<Client>(client_ent).unwrap();
+        assert_eq!(client.position(), DVec3::new(0.0, 100.0, 0.0));
+        assert_eq!(
+            app.world.get::<FallDistance>(client_ent).unwrap().distance,
+            0.0
+        );
+
+        // Killing despawns entities.
+        app.world
+            .get_mut::<OutOfBoundsPolicy>(instance_ent)
+            .unwrap()
+            .action = OutOfBoundsAction::Kill;
+
+        app.world
+            .get_mut::<McEntity>(pig_ent)
+            .unwrap()
+            .set_position([0.0, -1000.0, 0.0]);
+
+        app.update();
+
+        assert!(app.world.get::<Despawned>(pig_ent).is_some());
+    }
+}
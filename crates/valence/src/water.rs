@@ -0,0 +1,263 @@
+//! Detecting entities in water and drowning.
+//!
+//! Entities and clients whose feet are in water are given the [`InWater`]
+//! component, which also records whether their eyes are under water.
+//! Entities with an [`AirSupply`] lose air while their eyes are submerged and
+//! start drowning once it runs out, which is reported with the
+//! [`EntityDamage`] event.
+
+use bevy_ecs::prelude::*;
+use valence_protocol::block::{BlockKind, BlockState, PropName, PropValue};
+use valence_protocol::BlockPos;
+
+use crate::client::pose::PoseState;
+use crate::client::Client;
+use crate::damage::{DamageKind, EntityDamage};
+use crate::entity::{McEntity, PLAYER_EYE_HEIGHT};
+use crate::instance::Instance;
+use crate::tick_rate::SkippedInstances;
+use crate::Despawned;
+
+/// The amount of air entities have when they are not under water.
+pub const MAX_AIR: i32 = 300;
+
+/// The air at which an entity takes drowning damage. The air is then reset to
+/// zero.
+const DROWNING_AIR: i32 = -20;
+
+/// The amount of air regained per tick while the eyes are out of water.
+const AIR_REFILL_RATE: i32 = 4;
+
+/// A [`Component`] for entities and clients whose feet are in water. The
+/// component is added and removed automatically.
+#[derive(Component, Copy, Clone, PartialEq, Eq, Debug)]
+pub struct InWater {
+    /// If the eyes of the entity are also under water.
+    pub eyes: bool,
+}
+
+/// A [`Component`] which makes the entity lose air while its eyes are under
+/// water.
+///
+/// The air is displayed as bubbles for clients and is synchronized with the
+/// tracked data of the [`McEntity`] on the same entity, if any. Once the air
+/// runs out, the entity takes [`DamageKind::Drown`] damage every second.
+#[derive(Component, Copy, Clone, PartialEq, Eq, Debug)]
+pub struct AirSupply {
+    /// The remaining air in ticks.
+    ///
+    /// # Default Value
+    ///
+    /// [`MAX_AIR`]
+    pub air: i32,
+}
+
+impl AirSupply {
+    pub fn new() -> Self {
+        Self { air: MAX_AIR }
+    }
+}
+
+impl Default for AirSupply {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Returns if the block state is water or contains water.
+pub fn is_water(state: BlockState) -> bool {
+    matches!(
+        state.to_kind(),
+        BlockKind::Water
+            | BlockKind::BubbleColumn
+            | BlockKind::Kelp
+            | BlockKind::KelpPlant
+            | BlockKind::Seagrass
+            | BlockKind::TallSeagrass
+    ) || state.get(PropName::Waterlogged) == Some(PropValue::True)
+}
+
+impl Instance {
+    /// Returns if the block containing `pos` is water or contains water.
+    /// Blocks in unloaded chunks are never water.
+    pub fn is_water_at(&self, pos: impl Into<BlockPos>) -> bool {
+        self.block(pos)
+            .map_or(false, |block| is_water(block.state()))
+    }
+}
+
+type WaterEntity<'a> = (Entity, &'a McEntity, Option<&'a InWater>);
+
+pub(crate) fn update_in_water(
+    mut commands: Commands,
+    instances: Query<&Instance>,
+    entities: Query<WaterEntity, (Without<Client>, Without<Despawned>)>,
+    clients: Query<(Entity, &Client, Option<&PoseState>, Option<&InWater>)>,
+) {
+    let positions = entities
+        .iter()
+        .map(|(entity, mc_entity, in_water)| {
+            (
+                entity,
+                mc_entity.instance(),
+                mc_entity.position(),
+                mc_entity.eye_height(),
+                in_water,
+            )
+        })
+        .chain(clients.iter().map(|(entity, client, pose, in_water)| {
+            (
+                entity,
+                client.instance(),
+                client.position(),
+                pose.map_or(PLAYER_EYE_HEIGHT, PoseState::eye_height),
+                in_water,
+            )
+        }));
+
+    for (entity, instance, position, eye_height, in_water) in positions {
+        let Ok(instance) = instances.get(instance) else {
+            continue
+        };
+
+        let new = instance
+            .is_water_at(BlockPos::at(position))
+            .then(|| InWater {
+                eyes: instance.is_water_at(BlockPos::at([
+                    position.x,
+                    position.y + eye_height,
+                    position.z,
+                ])),
+            });
+
+        match (in_water, new) {
+            (Some(old), Some(new)) if *old == new => {}
+            (_, Some(new)) => {
+                commands.entity(entity).insert(new);
+            }
+            (Some(_), None) => {
+                commands.entity(entity).remove::<InWater>();
+            }
+            (None, None) => {}
+        }
+    }
+}
+
+type BreathingEntity<'a> = (
+    Entity,
+    &'a mut AirSupply,
+    Option<&'a InWater>,
+    Option<&'a mut McEntity>,
+    Option<&'a mut Client>,
+);
+
+pub(crate) fn tick_air_supply(
+    mut entities: Query<BreathingEntity>,
+    mut damage: EventWriter<EntityDamage>,
+    skipped: Res<SkippedInstances>,
+) {
+    for (entity, mut supply, in_water, mc_entity, client) in &mut entities {
+        let instance = mc_entity
+            .as_ref()
+            .map(|mc_entity| mc_entity.instance())
+            .or_else(|| client.as_ref().map(|client| client.instance()));
+
+        if instance.map_or(false, |instance| skipped.contains(instance)) {
+            continue;
+        }
+
+        if in_water.map_or(false, |in_water| in_water.eyes) {
+            supply.air -= 1;
+
+            if supply.air <= DROWNING_AIR {
+                supply.air = 0;
+
+                damage.send(EntityDamage {
+                    entity,
+                    source: None,
+                    kind: DamageKind::Drown,
+                    amount: 2.0,
+                });
+            }
+        } else if supply.air < MAX_AIR {
+            supply.air = (supply.air + AIR_REFILL_RATE).min(MAX_AIR);
+        }
+
+        // Negative air is only used for timing the drowning damage.
+        let air = supply.air.max(0);
+
+        if let Some(mut mc_entity) = mc_entity {
+            if mc_entity.data().get_air() != air {
+                mc_entity.data_mut().set_air(air);
+            }
+        }
+
+        if let Some(mut client) = client {
+            if client.player().get_air() != air {
+                client.player_mut().set_air(air);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy_app::App;
+
+    use super::*;
+    use crate::instance::Chunk;
+    use crate::unit_test::util::scenario_single_client;
+
+    #[test]
+    fn submerged_client_drowns() {
+        let mut app = App::new();
+        let (client_ent, _) = scenario_single_client(&mut app);
+        let instance_ent = app.world.get::<Client>(client_ent).unwrap().instance();
+
+        let mut instance = app.world.get_mut::<Instance>(instance_ent).unwrap();
+        instance.insert_chunk([0, 0], Chunk::default());
+        instance.set_block([0, 0, 0], BlockState::WATER);
+        instance.set_block([0, 1, 0], BlockState::WATER);
+
+        app.world
+            .entity_mut(client_ent)
+            .insert(AirSupply { air: 0 });
+
+        // The air refills on the first tick since the client is not known to be
+        // in water yet.
+        for _ in 0..25 {
+            app.update();
+        }
+
+        assert_eq!(
+            app.world.get::<InWater>(client_ent),
+            Some(&InWater { eyes: true })
+        );
+        assert_eq!(app.world.get::<AirSupply>(client_ent).unwrap().air, 0);
+        assert_eq!(
+            app.world
+                .get::<Client>(client_ent)
+                .unwrap()
+                .player()
+                .get_air(),
+            0
+        );
+
+        let damage = app.world.resource::<Events<EntityDamage>>();
+        let mut reader = damage.get_reader();
+        assert!(reader
+            .iter(damage)
+            .any(|e| e.entity == client_ent && e.kind == DamageKind::Drown));
+
+        // Leaving the water refills the air.
+        let mut instance = app.world.get_mut::<Instance>(instance_ent).unwrap();
+        instance.set_block([0, 0, 0], BlockState::AIR);
+        instance.set_block([0, 1, 0], BlockState::AIR);
+
+        app.update();
+        app.update();
+
+        assert!(app.world.get::<InWater>(client_ent).is_none());
+        assert!(app.world.get::<AirSupply>(client_ent).unwrap().air > 0);
+    }
+}
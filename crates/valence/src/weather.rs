@@ -0,0 +1,463 @@
+//! Weather and the effects it has on instances.
+//!
+//! Every [`Instance`] has its own [`Weather`] which is changed with
+//! [`Instance::set_weather`]. Thunderstorms strike lightning at random in
+//! simulated chunks (chunks in view of at least one client). Rain can
+//! additionally fill cauldrons and snowfall can cover the ground in snow. These
+//! effects are configured with the [`WeatherSettings`] resource.
+//!
+//! Lightning can also be summoned manually with the [`LightningStrike`] event.
+
+use bevy_ecs::prelude::*;
+use glam::DVec3;
+use rand::Rng;
+use valence_protocol::block::{PropName, PropValue};
+use valence_protocol::packets::s2c::play::GameEvent;
+use valence_protocol::types::{GameEventKind, SoundCategory};
+use valence_protocol::{BlockKind, BlockPos, BlockState, Sound};
+
+use crate::biome::BiomePrecipitation;
+use crate::budget::{SheddableWork, TickBudget};
+use crate::client::Client;
+use crate::damage::{DamageKind, EntityDamage};
+use crate::entity::{EntityKind, McEntity};
+use crate::fire::{IgniteBlock, OnFire, FIRE_BURN_TICKS};
+use crate::game_rules::GameRules;
+use crate::instance::{client_views_by_instance, Instance};
+use crate::packet::WritePacket;
+use crate::server::Server;
+use crate::tick_rate::SkippedInstances;
+use crate::view::ChunkPos;
+use crate::Despawned;
+
+/// The number of ticks a lightning bolt entity exists for.
+pub const LIGHTNING_TICKS: u32 = 8;
+
+/// The weather in an [`Instance`].
+#[derive(Copy, Clone, PartialEq, Default, Debug)]
+pub struct Weather {
+    /// The intensity of the rain from `0.0` to `1.0`. It is raining while this
+    /// is greater than zero.
+    pub rain: f32,
+    /// The intensity of the thunderstorm from `0.0` to `1.0`. Thunderstorms
+    /// only occur while it is also raining.
+    pub thunder: f32,
+}
+
+impl Weather {
+    /// Clear skies.
+    pub const CLEAR: Self = Self {
+        rain: 0.0,
+        thunder: 0.0,
+    };
+
+    /// Full intensity rain without thunder.
+    pub const RAIN: Self = Self {
+        rain: 1.0,
+        thunder: 0.0,
+    };
+
+    /// A full intensity thunderstorm.
+    pub const THUNDER: Self = Self {
+        rain: 1.0,
+        thunder: 1.0,
+    };
+
+    pub fn is_raining(self) -> bool {
+        self.rain > 0.0
+    }
+
+    pub fn is_thundering(self) -> bool {
+        self.is_raining() && self.thunder > 0.0
+    }
+}
+
+/// Writes the packets needed to change a client's weather from `old` to `new`.
+pub(crate) fn write_weather_change(mut writer: impl WritePacket, old: Weather, new: Weather) {
+    if old.is_raining() != new.is_raining() {
+        writer.write_packet(&GameEvent {
+            kind: if new.is_raining() {
+                GameEventKind::BeginRaining
+            } else {
+                GameEventKind::EndRaining
+            },
+            value: 0.0,
+        });
+    }
+
+    if old.rain != new.rain {
+        writer.write_packet(&GameEvent {
+            kind: GameEventKind::RainLevelChange,
+            value: new.rain.clamp(0.0, 1.0),
+        });
+    }
+
+    if old.thunder != new.thunder {
+        writer.write_packet(&GameEvent {
+            kind: GameEventKind::ThunderLevelChange,
+            value: new.thunder.clamp(0.0, 1.0),
+        });
+    }
+}
+
+/// A [`Resource`] containing global settings for weather effects.
+#[derive(Resource, Clone, Debug)]
+pub struct WeatherSettings {
+    /// Whether or not lightning strikes at random during thunderstorms.
+    ///
+    /// # Default Value
+    ///
+    /// `true`
+    pub natural_lightning: bool,
+    /// Whether or not snow layers form on the ground in snowy biomes while it
+    /// is raining.
+    ///
+    /// # Default Value
+    ///
+    /// `false`
+    pub snow_accumulation: bool,
+    /// Whether or not cauldrons exposed to the sky are filled with water or
+    /// powder snow while it is raining.
+    ///
+    /// # Default Value
+    ///
+    /// `false`
+    pub cauldron_filling: bool,
+}
+
+impl Default for WeatherSettings {
+    fn default() -> Self {
+        Self {
+            natural_lightning: true,
+            snow_accumulation: false,
+            cauldron_filling: false,
+        }
+    }
+}
+
+/// An event that summons a lightning bolt.
+#[derive(Clone, Debug)]
+pub struct LightningStrike {
+    /// The [`Entity`] with the [`Instance`] component the lightning strikes
+    /// in.
+    pub instance: Entity,
+    /// The position the lightning strikes.
+    pub position: DVec3,
+    /// If `true`, the lightning bolt does not start fires or damage entities.
+    pub cosmetic: bool,
+    /// The entity responsible for the strike, if any.
+    pub source: Option<Entity>,
+}
+
+/// A [`Component`] for lightning bolt entities. The entity is despawned once
+/// [`LightningBolt::ticks`] reaches zero.
+#[derive(Component, Copy, Clone, PartialEq, Eq, Debug)]
+pub struct LightningBolt {
+    /// The number of ticks until the lightning bolt is despawned.
+    pub ticks: u32,
+}
+
+/// Returns the position of the highest non-air block in the column at `x` and
+/// `z`.
+fn top_block(instance: &Instance, x: i32, z: i32) -> Option<BlockPos> {
+    let max_y = instance.min_y() + instance.section_count() as i32 * 16;
+
+    (instance.min_y()..max_y)
+        .rev()
+        .map(|y| BlockPos::new(x, y, z))
+        .find(|&pos| instance.block(pos).map_or(false, |b| !b.state().is_air()))
+}
+
+fn precipitation_at(instance: &Instance, server: &Server, pos: BlockPos) -> BiomePrecipitation {
+    let Some(chunk) = instance.chunk(ChunkPos::from_block_pos(pos)) else {
+        return BiomePrecipitation::None
+    };
+
+    let y = (pos.y - instance.min_y()).clamp(0, chunk.section_count() as i32 * 16 - 1) as usize;
+
+    let biome = chunk.biome(
+        pos.x.rem_euclid(16) as usize / 4,
+        y / 4,
+        pos.z.rem_euclid(16) as usize / 4,
+    );
+
+    server.biome(biome).precipitation
+}
+
+/// Returns the cauldron with an extra level of precipitation in it, or `None`
+/// if `state` is not a cauldron that can be filled by the precipitation.
+fn fill_cauldron(state: BlockState, precipitation: BiomePrecipitation) -> Option<BlockState> {
+    let level = |state: BlockState| {
+        state
+            .get(PropName::Level)
+            .and_then(|level| level.to_u16())
+            .unwrap_or(0)
+    };
+
+    let (filled, level) = match (state.to_kind(), precipitation) {
+        (BlockKind::Cauldron, BiomePrecipitation::Rain) => (BlockState::WATER_CAULDRON, 1),
+        (BlockKind::Cauldron, BiomePrecipitation::Snow) => (BlockState::POWDER_SNOW_CAULDRON, 1),
+        (BlockKind::WaterCauldron, BiomePrecipitation::Rain)
+        | (BlockKind::PowderSnowCauldron, BiomePrecipitation::Snow)
+            if level(state) < 3 =>
+        {
+            (state, level(state) + 1)
+        }
+        _ => return None,
+    };
+
+    Some(filled.set(PropName::Level, PropValue::from_u16(level)?))
+}
+
+pub(crate) fn tick_weather(
+    server: Res<Server>,
+    settings: Res<WeatherSettings>,
+    skipped: Res<SkippedInstances>,
+    budget: Res<TickBudget>,
+    mut instances: Query<(Entity, &mut Instance)>,
+    clients: Query<&Client>,
+    mut lightning: EventWriter<LightningStrike>,
+) {
+    if budget.is_shedding(SheddableWork::RandomTicks) {
+        return;
+    }
+
+    let views = client_views_by_instance(&clients);
+    let mut rng = rand::thread_rng();
+
+    for (instance_entity, mut instance) in &mut instances {
+        let weather = instance.weather();
+
+        if !weather.is_raining() || skipped.contains(instance_entity) {
+            continue;
+        }
+
+        let Some(views) = views.get(&instance_entity) else {
+            continue
+        };
+
+        let simulated: Vec<_> = instance
+            .chunks()
+            .map(|(pos, _)| pos)
+            .filter(|&pos| views.iter().any(|view| view.contains(pos)))
+            .collect();
+
+        for chunk_pos in simulated {
+            if settings.natural_lightning
+                && weather.is_thundering()
+                && rng.gen_range(0..100_000) == 0
+            {
+                let x = chunk_pos.x * 16 + rng.gen_range(0..16);
+                let z = chunk_pos.z * 16 + rng.gen_range(0..16);
+
+                if let Some(top) = top_block(&instance, x, z) {
+                    if precipitation_at(&instance, &server, top) == BiomePrecipitation::Rain {
+                        lightning.send(LightningStrike {
+                            instance: instance_entity,
+                            position: DVec3::new(
+                                x as f64 + 0.5,
+                                top.y as f64 + 1.0,
+                                z as f64 + 0.5,
+                            ),
+                            cosmetic: false,
+                            source: None,
+                        });
+                    }
+                }
+            }
+
+            if !(settings.snow_accumulation || settings.cauldron_filling)
+                || rng.gen_range(0..16) != 0
+            {
+                continue;
+            }
+
+            let x = chunk_pos.x * 16 + rng.gen_range(0..16);
+            let z = chunk_pos.z * 16 + rng.gen_range(0..16);
+
+            let Some(top) = top_block(&instance, x, z) else {
+                continue
+            };
+
+            let state = instance.block(top).map_or(BlockState::AIR, |b| b.state());
+            let precipitation = precipitation_at(&instance, &server, top);
+
+            if settings.snow_accumulation
+                && precipitation == BiomePrecipitation::Snow
+                && state.is_opaque()
+            {
+                instance.set_block(BlockPos::new(x, top.y + 1, z), BlockState::SNOW);
+            } else if settings.cauldron_filling {
+                let chance = match precipitation {
+                    BiomePrecipitation::Rain => 0.05,
+                    BiomePrecipitation::Snow => 0.1,
+                    BiomePrecipitation::None => continue,
+                };
+
+                if rng.gen_bool(chance) {
+                    if let Some(filled) = fill_cauldron(state, precipitation) {
+                        instance.set_block(top, filled);
+                    }
+                }
+            }
+        }
+    }
+}
+
+pub(crate) fn strike_lightning(
+    mut commands: Commands,
+    mut instances: Query<(&mut Instance, Option<&GameRules>)>,
+    entities: Query<(Entity, &McEntity, Option<&OnFire>), Without<Despawned>>,
+    clients: Query<(Entity, &Client, Option<&OnFire>), Without<McEntity>>,
+    mut events: EventReader<LightningStrike>,
+    mut ignite_block: EventWriter<IgniteBlock>,
+    mut damage: EventWriter<EntityDamage>,
+) {
+    let mut rng = rand::thread_rng();
+
+    for event in events.iter() {
+        let Ok((mut instance, rules)) = instances.get_mut(event.instance) else {
+            continue
+        };
+
+        let mut bolt = McEntity::new(EntityKind::Lightning, event.instance);
+        bolt.set_position(event.position);
+
+        commands.spawn((
+            bolt,
+            LightningBolt {
+                ticks: LIGHTNING_TICKS,
+            },
+        ));
+
+        instance.play_sound(
+            Sound::EntityLightningBoltThunder,
+            SoundCategory::Weather,
+            event.position,
+            10000.0,
+            0.8 + rng.gen::<f32>() * 0.2,
+        );
+
+        instance.play_sound(
+            Sound::EntityLightningBoltImpact,
+            SoundCategory::Weather,
+            event.position,
+            2.0,
+            0.5 + rng.gen::<f32>() * 0.2,
+        );
+
+        if event.cosmetic {
+            continue;
+        }
+
+        if rules.map_or(true, |rules| rules.do_fire_tick) {
+            ignite_block.send(IgniteBlock {
+                instance: event.instance,
+                position: BlockPos::at(event.position),
+                igniter: event.source,
+            });
+        }
+
+        // Vanilla damages the entities in a box around the bolt that extends
+        // higher up than down.
+        let min = event.position - DVec3::new(3.0, 3.0, 3.0);
+        let max = event.position + DVec3::new(3.0, 9.0, 3.0);
+
+        let struck = entities
+            .iter()
+            .map(|(entity, mc_entity, on_fire)| {
+                (entity, mc_entity.instance(), mc_entity.position(), on_fire)
+            })
+            .chain(clients.iter().map(|(entity, client, on_fire)| {
+                (entity, client.instance(), client.position(), on_fire)
+            }))
+            .filter(|&(_, instance, pos, _)| {
+                instance == event.instance && pos.cmpge(min).all() && pos.cmple(max).all()
+            });
+
+        for (entity, _, _, on_fire) in struck {
+            damage.send(EntityDamage {
+                entity,
+                source: event.source,
+                kind: DamageKind::Lightning,
+                amount: 5.0,
+            });
+
+            if on_fire.map_or(true, |on_fire| on_fire.ticks < FIRE_BURN_TICKS) {
+                commands.entity(entity).insert(OnFire::new(FIRE_BURN_TICKS));
+            }
+        }
+    }
+}
+
+pub(crate) fn despawn_lightning_bolts(
+    mut commands: Commands,
+    mut bolts: Query<(Entity, &mut LightningBolt), Without<Despawned>>,
+) {
+    for (entity, mut bolt) in &mut bolts {
+        bolt.ticks = bolt.ticks.saturating_sub(1);
+
+        if bolt.ticks == 0 {
+            commands.entity(entity).insert(Despawned);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy_app::App;
+    use valence_protocol::packets::S2cPlayPacket;
+
+    use super::*;
+    use crate::assert_packet_count;
+    use crate::unit_test::util::scenario_single_client;
+
+    #[test]
+    fn weather_change_is_sent() -> anyhow::Result<()> {
+        let mut app = App::new();
+        let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+        let instance_ent = app.world.get::<Client>(client_ent).unwrap().instance();
+
+        app.update();
+        client_helper.clear_sent();
+
+        app.world
+            .get_mut::<Instance>(instance_ent)
+            .unwrap()
+            .set_weather(Weather::RAIN);
+
+        app.update();
+
+        let sent_packets = client_helper.collect_sent()?;
+        assert_packet_count!(sent_packets, 2, S2cPlayPacket::GameEvent(_));
+
+        Ok(())
+    }
+
+    #[test]
+    fn lightning_damages_nearby_entities() {
+        let mut app = App::new();
+        let (client_ent, _) = scenario_single_client(&mut app);
+        let instance_ent = app.world.get::<Client>(client_ent).unwrap().instance();
+
+        app.world.send_event(LightningStrike {
+            instance: instance_ent,
+            position: DVec3::ZERO,
+            cosmetic: false,
+            source: None,
+        });
+
+        app.update();
+
+        let damage = app.world.resource::<Events<EntityDamage>>();
+        let mut reader = damage.get_reader();
+        assert!(reader
+            .iter(damage)
+            .any(|e| e.entity == client_ent && e.kind == DamageKind::Lightning));
+
+        assert!(app.world.get::<OnFire>(client_ent).is_some());
+
+        let mut bolts = app.world.query::<&LightningBolt>();
+        assert_eq!(bolts.iter(&app.world).count(), 1);
+    }
+}
@@ -0,0 +1,1421 @@
+//! Anvils, enchanting tables and brewing stands.
+//!
+//! Inventories of the kinds [`InventoryKind::Anvil`],
+//! [`InventoryKind::Enchantment`] and [`InventoryKind::BrewingStand`] are
+//! ordinary containers on their own. Adding an [`Anvil`], [`EnchantingTable`]
+//! or [`BrewingStand`] component to the entity of the inventory makes the
+//! server run the workstation: the results and the properties shown by the
+//! screen, such as the cost of an anvil or the progress of a brewing stand,
+//! are computed by the server and sent to the clients viewing the inventory.
+//!
+//! ```
+//! use valence::prelude::*;
+//! use valence::workstation::EnchantingTable;
+//!
+//! fn spawn_enchanting_table(mut commands: Commands) {
+//!     commands.spawn((
+//!         Inventory::new(InventoryKind::Enchantment),
+//!         EnchantingTable::new().with_bookshelves(15),
+//!     ));
+//! }
+//! ```
+//!
+//! The results can be changed with [`Anvil::with_combiner`],
+//! [`EnchantingTable::with_offers`] and the [`BrewingRecipes`] resource.
+//! Every use of a workstation is reported with an [`AnvilUsed`],
+//! [`ItemEnchanted`] or [`PotionBrewed`] event.
+//!
+//! Valence does not track the experience of players, so the level costs of
+//! anvils and enchanting tables are only shown to clients and reported in the
+//! events. Clients in survival mode only let players pay costs covered by the
+//! experience level they were sent with the `SetExperience` packet.
+
+use std::fmt;
+
+use bevy_ecs::prelude::*;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use valence_nbt::{Compound, Value};
+use valence_protocol::enchant::EnchantmentKind;
+use valence_protocol::packets::s2c::play::SetContainerProperty;
+use valence_protocol::types::{ClickContainerMode, GameMode};
+use valence_protocol::{ItemKind, ItemStack, Text};
+
+use crate::client::event::{ClickContainerButton, RenameItem};
+use crate::client::Client;
+use crate::crafting::deposit;
+use crate::durability::item_damage;
+use crate::enchantment::{
+    can_enchant, enchantment_level, enchantments, set_enchantment, stored_enchantments,
+};
+use crate::inventory::{Inventory, InventoryKind, OpenInventory};
+
+/// The slot of an anvil holding the item which is repaired or renamed.
+pub const ANVIL_LEFT_SLOT: u16 = 0;
+/// The slot of an anvil holding the item combined with the left item.
+pub const ANVIL_RIGHT_SLOT: u16 = 1;
+/// The slot of an anvil holding the result.
+pub const ANVIL_RESULT_SLOT: u16 = 2;
+/// Anvil uses costing this many levels or more are "Too Expensive!" for
+/// clients in survival mode.
+pub const ANVIL_MAX_COST: u32 = 40;
+/// The longest name clients can give items in an anvil.
+pub const MAX_ITEM_NAME_LENGTH: usize = 50;
+
+/// The slot of an enchanting table holding the enchanted item.
+pub const ENCHANTING_ITEM_SLOT: u16 = 0;
+/// The slot of an enchanting table holding lapis lazuli.
+pub const ENCHANTING_LAPIS_SLOT: u16 = 1;
+/// The most bookshelves around an enchanting table which are counted.
+pub const MAX_BOOKSHELVES: u8 = 15;
+
+/// The slot of a brewing stand holding the ingredient.
+pub const BREWING_INGREDIENT_SLOT: u16 = 3;
+/// The slot of a brewing stand holding the blaze powder used as fuel.
+pub const BREWING_FUEL_SLOT: u16 = 4;
+/// The number of ticks it takes to brew potions.
+pub const BREW_TIME: u16 = 400;
+/// The number of brews a blaze powder is good for.
+pub const FUEL_PER_BLAZE_POWDER: u8 = 20;
+
+/// Workstations whose screens show container properties.
+pub(crate) trait ContainerProperties: Component {
+    /// The properties of the screen and their values.
+    fn properties(&self) -> Vec<(i16, i16)>;
+
+    /// Returns `true` if the properties changed since they were last sent.
+    fn properties_modified(&self) -> bool;
+
+    fn clear_properties_modified(&mut self);
+}
+
+/// The items in an anvil.
+#[derive(Copy, Clone, Debug)]
+pub struct AnvilInput<'a> {
+    pub left: &'a ItemStack,
+    pub right: Option<&'a ItemStack>,
+    /// The name typed in the text field of the anvil, if the client sent one.
+    pub name: Option<&'a str>,
+}
+
+/// The result of using an anvil.
+#[derive(Clone, PartialEq, Debug)]
+pub struct AnvilResult {
+    pub item: ItemStack,
+    /// The number of experience levels the result costs.
+    pub cost: u32,
+    /// The number of items used up from the right slot.
+    pub right_consumed: u8,
+}
+
+type Combiner = Box<dyn Fn(&AnvilInput, Option<AnvilResult>) -> Option<AnvilResult> + Send + Sync>;
+
+/// A [`Component`] which makes the anvil [`Inventory`] on the same entity
+/// repair, combine and rename items.
+#[derive(Component, Default)]
+pub struct Anvil {
+    name: Option<String>,
+    cost: u32,
+    properties_modified: bool,
+    combiner: Option<Combiner>,
+}
+
+impl Anvil {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the function computing the result of the anvil. The function is
+    /// given the items in the anvil and the result computed by [`combine`],
+    /// and returns the result to use instead.
+    #[must_use]
+    pub fn with_combiner(
+        mut self,
+        combiner: impl Fn(&AnvilInput, Option<AnvilResult>) -> Option<AnvilResult>
+            + Send
+            + Sync
+            + 'static,
+    ) -> Self {
+        self.combiner = Some(Box::new(combiner));
+        self
+    }
+
+    /// The name typed in the text field of the anvil.
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    /// The number of experience levels the current result costs.
+    pub fn cost(&self) -> u32 {
+        self.cost
+    }
+
+    fn result(&self, inventory: &Inventory) -> Option<AnvilResult> {
+        let input = AnvilInput {
+            left: inventory.slot(ANVIL_LEFT_SLOT)?,
+            right: inventory.slot(ANVIL_RIGHT_SLOT),
+            name: self.name.as_deref(),
+        };
+
+        let result = combine(&input);
+
+        match &self.combiner {
+            Some(combiner) => combiner(&input, result),
+            None => result,
+        }
+    }
+}
+
+impl fmt::Debug for Anvil {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Anvil")
+            .field("name", &self.name)
+            .field("cost", &self.cost)
+            .field("combiner", &self.combiner.is_some())
+            .finish()
+    }
+}
+
+impl ContainerProperties for Anvil {
+    fn properties(&self) -> Vec<(i16, i16)> {
+        vec![(0, self.cost.min(i16::MAX as u32) as i16)]
+    }
+
+    fn properties_modified(&self) -> bool {
+        self.properties_modified
+    }
+
+    fn clear_properties_modified(&mut self) {
+        self.properties_modified = false;
+    }
+}
+
+/// Returns the level of the enchantment on the item, or stored in the item if
+/// it is an enchanted book.
+fn level_on(stack: &ItemStack, kind: EnchantmentKind) -> i16 {
+    if stack.item == ItemKind::EnchantedBook {
+        stored_enchantments(stack)
+            .find(|(k, _)| *k == kind)
+            .map_or(0, |(_, level)| level.max(0))
+    } else {
+        enchantment_level(stack, kind)
+    }
+}
+
+fn repair_cost(stack: &ItemStack) -> u32 {
+    match stack.nbt.as_ref().and_then(|nbt| nbt.get("RepairCost")) {
+        Some(Value::Int(cost)) => (*cost).max(0) as u32,
+        _ => 0,
+    }
+}
+
+/// The `display.Name` tag of items named `name`.
+fn name_tag(name: &str) -> String {
+    serde_json::to_string(&Text::text(name.to_owned())).unwrap_or_default()
+}
+
+fn custom_name(stack: &ItemStack) -> Option<&str> {
+    let Some(Value::Compound(display)) = stack.nbt.as_ref()?.get("display") else {
+        return None;
+    };
+
+    match display.get("Name") {
+        Some(Value::String(name)) => Some(name),
+        _ => None,
+    }
+}
+
+fn set_custom_name(stack: &mut ItemStack, name: Option<&str>) {
+    let nbt = stack.nbt.get_or_insert_with(Compound::new);
+
+    if !matches!(nbt.get("display"), Some(Value::Compound(_))) {
+        nbt.insert("display", Compound::new());
+    }
+
+    if let Some(Value::Compound(display)) = nbt.get_mut("display") {
+        match name {
+            Some(name) => {
+                display.insert("Name", name_tag(name));
+            }
+            None => {
+                display.remove("Name");
+            }
+        }
+
+        if display.is_empty() {
+            nbt.remove("display");
+        }
+    }
+
+    if nbt.is_empty() {
+        stack.nbt = None;
+    }
+}
+
+/// Computes the result of an anvil like vanilla anvils do, without the
+/// repairs with raw materials.
+///
+/// Two items of the same kind are combined into one with the durability of
+/// both and their enchantments merged, and enchanted books add their
+/// enchantments to the left item. Equal levels of an enchantment are raised
+/// by one up to the maximum level. The left item is renamed to the given
+/// name, and an empty name removes its custom name. Returns `None` if the
+/// anvil does nothing with the items.
+pub fn combine(input: &AnvilInput) -> Option<AnvilResult> {
+    let left = input.left;
+    let mut item = left.clone();
+    let mut cost = 0;
+    let mut right_consumed = 0;
+
+    if let Some(right) = input.right {
+        let is_book = right.item == ItemKind::EnchantedBook;
+        let max_durability = left.item.max_durability() as i32;
+        let mut changed = false;
+
+        if right.item == left.item && max_durability > 0 {
+            // The durability of both items and a bonus of 12%.
+            let remaining = (max_durability - item_damage(left))
+                + (max_durability - item_damage(right))
+                + max_durability * 12 / 100;
+            let damage = (max_durability - remaining).max(0);
+
+            if damage < item_damage(left) {
+                item.nbt
+                    .get_or_insert_with(Compound::new)
+                    .insert("Damage", damage);
+                cost += 2;
+                changed = true;
+            }
+        } else if !is_book && right.item != left.item {
+            return None;
+        }
+
+        let added: Vec<_> = if is_book {
+            stored_enchantments(right).collect()
+        } else {
+            enchantments(right).collect()
+        };
+
+        for (kind, level) in added {
+            if !can_enchant(kind, item.item) {
+                continue;
+            }
+
+            let current = level_on(&item, kind);
+            let level = if current == level {
+                (level + 1).min(kind.max_level())
+            } else {
+                current.max(level)
+            };
+
+            if level != current {
+                set_enchantment(&mut item, kind, level);
+                changed = true;
+            }
+
+            let multiplier = match kind.rarity_weight() {
+                10 => 1,
+                5 => 2,
+                2 => 4,
+                _ => 8,
+            };
+
+            // Enchantments from books cost half as much.
+            let multiplier = if is_book {
+                (multiplier / 2).max(1)
+            } else {
+                multiplier
+            };
+
+            cost += level.max(0) as u32 * multiplier;
+        }
+
+        if !changed {
+            return None;
+        }
+
+        right_consumed = 1;
+    }
+
+    if let Some(name) = input.name {
+        let current = custom_name(&item);
+
+        if name.is_empty() {
+            if current.is_some() {
+                set_custom_name(&mut item, None);
+                cost += 1;
+            }
+        } else if current != Some(name_tag(name).as_str()) {
+            set_custom_name(&mut item, Some(name));
+            cost += 1;
+        }
+    }
+
+    if cost == 0 {
+        return None;
+    }
+
+    cost += repair_cost(left) + input.right.map_or(0, repair_cost);
+
+    match input.right {
+        Some(right) => {
+            let penalty = repair_cost(left).max(repair_cost(right)) * 2 + 1;
+            item.nbt
+                .get_or_insert_with(Compound::new)
+                .insert("RepairCost", penalty as i32);
+        }
+        // Renaming alone is never too expensive.
+        None => cost = cost.min(ANVIL_MAX_COST - 1),
+    }
+
+    Some(AnvilResult {
+        item,
+        cost,
+        right_consumed,
+    })
+}
+
+/// An event sent when a client takes the result of an anvil.
+#[derive(Clone, Debug)]
+pub struct AnvilUsed {
+    pub client: Entity,
+    /// The entity of the anvil.
+    pub anvil: Entity,
+    pub item: ItemStack,
+    /// The number of experience levels the result cost.
+    pub cost: u32,
+}
+
+/// An enchantment offered by an enchanting table.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct EnchantmentOffer {
+    /// The experience level needed to pick the offer.
+    pub cost: u32,
+    pub enchantment: EnchantmentKind,
+    pub level: i16,
+}
+
+/// The three offers of an enchanting table, from top to bottom.
+pub type EnchantmentOffers = [Option<EnchantmentOffer>; 3];
+
+type OfferFn = Box<dyn Fn(&ItemStack, EnchantmentOffers) -> EnchantmentOffers + Send + Sync>;
+
+/// A [`Component`] which makes the enchanting table [`Inventory`] on the same
+/// entity offer enchantments for its item.
+#[derive(Component)]
+pub struct EnchantingTable {
+    bookshelves: u8,
+    seed: u32,
+    offers: EnchantmentOffers,
+    properties_modified: bool,
+    offer_fn: Option<OfferFn>,
+}
+
+impl EnchantingTable {
+    /// Creates an enchanting table without bookshelves and with a random
+    /// seed.
+    pub fn new() -> Self {
+        Self {
+            bookshelves: 0,
+            seed: rand::random(),
+            offers: [None; 3],
+            properties_modified: false,
+            offer_fn: None,
+        }
+    }
+
+    /// Sets the number of bookshelves around the table, which is clamped to
+    /// [`MAX_BOOKSHELVES`].
+    #[must_use]
+    pub fn with_bookshelves(mut self, bookshelves: u8) -> Self {
+        self.set_bookshelves(bookshelves);
+        self
+    }
+
+    /// Sets the function computing the offers of the table. The function is
+    /// given the item in the table and the offers computed by
+    /// [`enchantment_offers`], and returns the offers to use instead.
+    #[must_use]
+    pub fn with_offers(
+        mut self,
+        offers: impl Fn(&ItemStack, EnchantmentOffers) -> EnchantmentOffers + Send + Sync + 'static,
+    ) -> Self {
+        self.offer_fn = Some(Box::new(offers));
+        self
+    }
+
+    pub fn bookshelves(&self) -> u8 {
+        self.bookshelves
+    }
+
+    pub fn set_bookshelves(&mut self, bookshelves: u8) {
+        self.bookshelves = bookshelves.min(MAX_BOOKSHELVES);
+    }
+
+    /// The seed the offers are rolled with. The seed changes whenever an item
+    /// is enchanted.
+    pub fn seed(&self) -> u32 {
+        self.seed
+    }
+
+    pub fn set_seed(&mut self, seed: u32) {
+        self.seed = seed;
+    }
+
+    /// The offers for the item in the table.
+    pub fn offers(&self) -> &EnchantmentOffers {
+        &self.offers
+    }
+
+    fn compute_offers(&self, inventory: &Inventory) -> EnchantmentOffers {
+        let Some(item) = inventory.slot(ENCHANTING_ITEM_SLOT) else {
+            return [None; 3];
+        };
+
+        let offers = enchantment_offers(item, self.bookshelves, self.seed);
+
+        match &self.offer_fn {
+            Some(offer_fn) => offer_fn(item, offers),
+            None => offers,
+        }
+    }
+}
+
+impl Default for EnchantingTable {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for EnchantingTable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EnchantingTable")
+            .field("bookshelves", &self.bookshelves)
+            .field("seed", &self.seed)
+            .field("offers", &self.offers)
+            .field("offer_fn", &self.offer_fn.is_some())
+            .finish()
+    }
+}
+
+impl ContainerProperties for EnchantingTable {
+    fn properties(&self) -> Vec<(i16, i16)> {
+        let mut properties = vec![(3, (self.seed & !0xf) as i16)];
+
+        for (i, offer) in self.offers.iter().enumerate() {
+            let i = i as i16;
+
+            properties.extend([
+                (i, offer.map_or(0, |o| o.cost.min(i16::MAX as u32) as i16)),
+                (4 + i, offer.map_or(-1, |o| o.enchantment.to_raw() as i16)),
+                (7 + i, offer.map_or(-1, |o| o.level)),
+            ]);
+        }
+
+        properties
+    }
+
+    fn properties_modified(&self) -> bool {
+        self.properties_modified
+    }
+
+    fn clear_properties_modified(&mut self) {
+        self.properties_modified = false;
+    }
+}
+
+/// Rolls the offers of an enchanting table for an item.
+///
+/// The costs are rolled like in vanilla, with more bookshelves giving higher
+/// costs. Each offer is an enchantment found in enchanting tables which
+/// applies to the item, picked by its rarity, with a level growing with the
+/// cost. Items which are already enchanted and items which can't be enchanted
+/// get no offers.
+pub fn enchantment_offers(item: &ItemStack, bookshelves: u8, seed: u32) -> EnchantmentOffers {
+    let enchantability = match item.item {
+        ItemKind::Book => 1,
+        kind => kind.enchantability() as u32,
+    };
+
+    if enchantability == 0 || item.count() != 1 || enchantments(item).next().is_some() {
+        return [None; 3];
+    }
+
+    let candidates: Vec<_> = (0..)
+        .map_while(EnchantmentKind::from_raw)
+        .filter(|kind| {
+            let sources = kind.sources();
+            sources.enchantment_table && !sources.treasure && can_enchant(*kind, item.item)
+        })
+        .collect();
+
+    if candidates.is_empty() {
+        return [None; 3];
+    }
+
+    let total_weight: i32 = candidates.iter().map(|kind| kind.rarity_weight()).sum();
+    let bookshelves = bookshelves.min(MAX_BOOKSHELVES) as u32;
+    let mut rng = StdRng::seed_from_u64(seed.into());
+    let mut offers = [None; 3];
+
+    for (i, offer) in offers.iter_mut().enumerate() {
+        let base = rng.gen_range(1..=8) + bookshelves / 2 + rng.gen_range(0..=bookshelves);
+
+        let cost = match i {
+            0 => (base / 3).max(1),
+            1 => base * 2 / 3 + 1,
+            _ => base.max(bookshelves * 2),
+        };
+
+        let power = cost
+            + 1
+            + rng.gen_range(0..=enchantability / 4)
+            + rng.gen_range(0..=enchantability / 4);
+
+        let mut n = rng.gen_range(0..total_weight);
+        let enchantment = *candidates
+            .iter()
+            .find(|kind| {
+                n -= kind.rarity_weight();
+                n < 0
+            })
+            .unwrap_or(&candidates[0]);
+
+        let max_level = enchantment.max_level().max(1);
+        let level = (power as i16 * max_level / 30).clamp(1, max_level);
+
+        *offer = Some(EnchantmentOffer {
+            cost,
+            enchantment,
+            level,
+        });
+    }
+
+    offers
+}
+
+/// An event sent when a client enchants the item in an enchanting table.
+#[derive(Clone, Debug)]
+pub struct ItemEnchanted {
+    pub client: Entity,
+    /// The entity of the enchanting table.
+    pub table: Entity,
+    /// The enchanted item.
+    pub item: ItemStack,
+    pub offer: EnchantmentOffer,
+    /// The number of experience levels the enchantment costs, which is one
+    /// level for every line of the offer and less than the required
+    /// [`cost`](EnchantmentOffer::cost).
+    pub levels: u32,
+}
+
+/// A potion made by brewing a potion with an ingredient.
+#[derive(Clone, PartialEq, Eq, Debug)]
+struct PotionMix {
+    input: String,
+    ingredient: ItemKind,
+    output: String,
+}
+
+/// A [`Resource`] with the mixes brewing stands brew. The default recipes are
+/// those of vanilla.
+#[derive(Resource, Clone, PartialEq, Eq, Debug)]
+pub struct BrewingRecipes {
+    potions: Vec<PotionMix>,
+    /// Mixes changing the item of the bottle, such as splash potions.
+    containers: Vec<(ItemKind, ItemKind, ItemKind)>,
+}
+
+impl BrewingRecipes {
+    /// Creates recipes without any mixes.
+    pub fn new() -> Self {
+        Self {
+            potions: vec![],
+            containers: vec![],
+        }
+    }
+
+    /// Adds a mix turning the potion `input` into the potion `output`, such
+    /// as `"water"` and `"awkward"`. Mixes added first take precedence.
+    pub fn add_potion_mix(
+        &mut self,
+        input: impl Into<String>,
+        ingredient: ItemKind,
+        output: impl Into<String>,
+    ) {
+        self.potions.push(PotionMix {
+            input: input.into(),
+            ingredient,
+            output: output.into(),
+        });
+    }
+
+    /// Adds a mix turning bottles of the item `input` into bottles of the item
+    /// `output`, keeping their potion.
+    pub fn add_container_mix(&mut self, input: ItemKind, ingredient: ItemKind, output: ItemKind) {
+        self.containers.push((input, ingredient, output));
+    }
+
+    /// Removes every mix using the ingredient.
+    pub fn remove_ingredient(&mut self, ingredient: ItemKind) {
+        self.potions.retain(|mix| mix.ingredient != ingredient);
+        self.containers.retain(|(_, i, _)| *i != ingredient);
+    }
+
+    /// Returns the bottle brewed from the bottle and the ingredient, if any.
+    pub fn brew(&self, bottle: &ItemStack, ingredient: ItemKind) -> Option<ItemStack> {
+        let container = self
+            .containers
+            .iter()
+            .find(|(input, i, _)| *input == bottle.item && *i == ingredient);
+
+        if let Some(&(_, _, output)) = container {
+            let mut bottle = bottle.clone();
+            bottle.item = output;
+            return Some(bottle);
+        }
+
+        if !matches!(
+            bottle.item,
+            ItemKind::Potion | ItemKind::SplashPotion | ItemKind::LingeringPotion
+        ) {
+            return None;
+        }
+
+        let Some(Value::String(potion)) = bottle.nbt.as_ref()?.get("Potion") else {
+            return None;
+        };
+
+        let potion = potion.strip_prefix("minecraft:").unwrap_or(potion);
+
+        let mix = self
+            .potions
+            .iter()
+            .find(|mix| mix.input == potion && mix.ingredient == ingredient)?;
+
+        let mut bottle = bottle.clone();
+        bottle
+            .nbt
+            .get_or_insert_with(Compound::new)
+            .insert("Potion", format!("minecraft:{}", mix.output));
+
+        Some(bottle)
+    }
+}
+
+impl Default for BrewingRecipes {
+    fn default() -> Self {
+        let mut recipes = Self::new();
+
+        for (ingredient, output) in [
+            (ItemKind::NetherWart, "awkward"),
+            (ItemKind::Redstone, "mundane"),
+            (ItemKind::GlowstoneDust, "thick"),
+            (ItemKind::FermentedSpiderEye, "weakness"),
+        ] {
+            recipes.add_potion_mix("water", ingredient, output);
+        }
+
+        for (ingredient, output) in [
+            (ItemKind::Sugar, "swiftness"),
+            (ItemKind::RabbitFoot, "leaping"),
+            (ItemKind::BlazePowder, "strength"),
+            (ItemKind::GlisteringMelonSlice, "healing"),
+            (ItemKind::SpiderEye, "poison"),
+            (ItemKind::GhastTear, "regeneration"),
+            (ItemKind::MagmaCream, "fire_resistance"),
+            (ItemKind::Pufferfish, "water_breathing"),
+            (ItemKind::GoldenCarrot, "night_vision"),
+            (ItemKind::TurtleHelmet, "turtle_master"),
+            (ItemKind::PhantomMembrane, "slow_falling"),
+        ] {
+            recipes.add_potion_mix("awkward", ingredient, output);
+        }
+
+        for (input, output) in [
+            ("swiftness", "slowness"),
+            ("long_swiftness", "long_slowness"),
+            ("leaping", "slowness"),
+            ("long_leaping", "long_slowness"),
+            ("healing", "harming"),
+            ("strong_healing", "strong_harming"),
+            ("poison", "harming"),
+            ("long_poison", "harming"),
+            ("strong_poison", "strong_harming"),
+            ("night_vision", "invisibility"),
+            ("long_night_vision", "long_invisibility"),
+        ] {
+            recipes.add_potion_mix(input, ItemKind::FermentedSpiderEye, output);
+        }
+
+        for potion in [
+            "swiftness",
+            "leaping",
+            "strength",
+            "poison",
+            "regeneration",
+            "fire_resistance",
+            "water_breathing",
+            "night_vision",
+            "invisibility",
+            "slowness",
+            "weakness",
+            "slow_falling",
+            "turtle_master",
+        ] {
+            recipes.add_potion_mix(potion, ItemKind::Redstone, format!("long_{potion}"));
+        }
+
+        for potion in [
+            "swiftness",
+            "leaping",
+            "strength",
+            "healing",
+            "harming",
+            "poison",
+            "regeneration",
+            "slowness",
+            "turtle_master",
+        ] {
+            recipes.add_potion_mix(potion, ItemKind::GlowstoneDust, format!("strong_{potion}"));
+        }
+
+        recipes.add_container_mix(
+            ItemKind::Potion,
+            ItemKind::Gunpowder,
+            ItemKind::SplashPotion,
+        );
+        recipes.add_container_mix(
+            ItemKind::SplashPotion,
+            ItemKind::DragonBreath,
+            ItemKind::LingeringPotion,
+        );
+
+        recipes
+    }
+}
+
+/// A [`Component`] which makes the brewing stand [`Inventory`] on the same
+/// entity brew potions with the [`BrewingRecipes`].
+#[derive(Component, Clone, PartialEq, Eq, Default, Debug)]
+pub struct BrewingStand {
+    brew_time: u16,
+    fuel: u8,
+    /// The ingredient being brewed.
+    ingredient: Option<ItemKind>,
+    properties_modified: bool,
+}
+
+impl BrewingStand {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The number of ticks left until the potions are brewed, or zero if the
+    /// brewing stand is not brewing.
+    pub fn brew_time(&self) -> u16 {
+        self.brew_time
+    }
+
+    /// The number of brews left before the next blaze powder is used.
+    pub fn fuel(&self) -> u8 {
+        self.fuel
+    }
+
+    pub fn set_fuel(&mut self, fuel: u8) {
+        self.fuel = fuel.min(FUEL_PER_BLAZE_POWDER);
+        self.properties_modified = true;
+    }
+}
+
+impl ContainerProperties for BrewingStand {
+    fn properties(&self) -> Vec<(i16, i16)> {
+        vec![(0, self.brew_time as i16), (1, self.fuel as i16)]
+    }
+
+    fn properties_modified(&self) -> bool {
+        self.properties_modified
+    }
+
+    fn clear_properties_modified(&mut self) {
+        self.properties_modified = false;
+    }
+}
+
+/// An event sent when a brewing stand finishes brewing.
+#[derive(Clone, Debug)]
+pub struct PotionBrewed {
+    /// The entity of the brewing stand.
+    pub brewing_stand: Entity,
+    pub ingredient: ItemKind,
+    /// The brewed bottles.
+    pub potions: Vec<ItemStack>,
+}
+
+/// Removes `count` items from the stack in the slot.
+fn shrink_slot(inventory: &mut Inventory, slot: u16, count: u8) {
+    let Some(mut stack) = inventory.slot(slot).cloned() else {
+        return;
+    };
+
+    if stack.count() > count {
+        stack.set_count(stack.count() - count);
+        inventory.replace_slot(slot, stack);
+    } else {
+        inventory.replace_slot(slot, None);
+    }
+}
+
+/// Handles a click on the result slot of an anvil. Clicking puts the result
+/// on the cursor and shift-clicking puts it in the inventory of the player.
+/// Returns the result if it was taken.
+pub(crate) fn take_anvil_result(
+    client: &mut Client,
+    anvil: &mut Anvil,
+    inventory: &mut Inventory,
+    player: &mut Inventory,
+    mode: ClickContainerMode,
+) -> Option<AnvilResult> {
+    let result = anvil.result(inventory)?;
+
+    if result.cost >= ANVIL_MAX_COST && client.game_mode() != GameMode::Creative {
+        return None;
+    }
+
+    match mode {
+        ClickContainerMode::Click if client.cursor_item().is_none() => {
+            client.replace_cursor_item(result.item.clone());
+        }
+        ClickContainerMode::ShiftClick if deposit(player, &result.item) => {}
+        _ => return None,
+    }
+
+    inventory.replace_slot(ANVIL_LEFT_SLOT, None);
+    shrink_slot(inventory, ANVIL_RIGHT_SLOT, result.right_consumed);
+    inventory.replace_slot(ANVIL_RESULT_SLOT, None);
+    anvil.name = None;
+
+    Some(result)
+}
+
+/// Stores the names clients type in the text field of anvils.
+pub(crate) fn handle_rename_items(
+    mut events: EventReader<RenameItem>,
+    clients: Query<&OpenInventory>,
+    mut anvils: Query<&mut Anvil>,
+) {
+    for event in events.iter() {
+        let Ok(open_inventory) = clients.get(event.client) else {
+            continue;
+        };
+
+        let Ok(mut anvil) = anvils.get_mut(open_inventory.entity()) else {
+            continue;
+        };
+
+        let name: String = event.name.chars().take(MAX_ITEM_NAME_LENGTH).collect();
+
+        if anvil.name.as_deref() != Some(name.as_str()) {
+            anvil.name = Some(name);
+        }
+    }
+}
+
+/// Updates the result slot and the cost of changed anvils.
+pub(crate) fn update_anvils(
+    mut anvils: Query<(&mut Inventory, &mut Anvil), Or<(Changed<Inventory>, Changed<Anvil>)>>,
+) {
+    for (mut inventory, mut anvil) in &mut anvils {
+        if inventory.kind() != InventoryKind::Anvil {
+            continue;
+        }
+
+        let (item, cost) = match anvil.result(&inventory) {
+            Some(result) => (Some(result.item), result.cost),
+            None => (None, 0),
+        };
+
+        // Avoid marking the inventory as changed again.
+        if inventory.slot(ANVIL_RESULT_SLOT) != item.as_ref() {
+            inventory.replace_slot(ANVIL_RESULT_SLOT, item);
+        }
+
+        if anvil.cost != cost {
+            anvil.cost = cost;
+            anvil.properties_modified = true;
+        }
+    }
+}
+
+/// Enchants the item in an enchanting table when a client clicks one of the
+/// offers.
+pub(crate) fn handle_enchant_buttons(
+    mut events: EventReader<ClickContainerButton>,
+    clients: Query<(&Client, &OpenInventory)>,
+    mut tables: Query<(&mut Inventory, &mut EnchantingTable)>,
+    mut enchanted: EventWriter<ItemEnchanted>,
+) {
+    for event in events.iter() {
+        let Ok((client, open_inventory)) = clients.get(event.client) else {
+            continue;
+        };
+
+        if event.window_id as u8 != client.window_id {
+            continue;
+        }
+
+        let Ok((mut inventory, mut table)) = tables.get_mut(open_inventory.entity()) else {
+            continue;
+        };
+
+        if inventory.kind() != InventoryKind::Enchantment {
+            continue;
+        }
+
+        let Some(Some(offer)) = usize::try_from(event.button_id)
+            .ok()
+            .and_then(|i| table.offers.get(i).copied())
+        else {
+            continue;
+        };
+
+        let Some(mut item) = inventory.slot(ENCHANTING_ITEM_SLOT).cloned() else {
+            continue;
+        };
+
+        let lapis = event.button_id as u8 + 1;
+        let creative = client.game_mode() == GameMode::Creative;
+
+        if !creative {
+            let available = inventory
+                .slot(ENCHANTING_LAPIS_SLOT)
+                .filter(|stack| stack.item == ItemKind::LapisLazuli)
+                .map_or(0, |stack| stack.count());
+
+            if available < lapis {
+                continue;
+            }
+
+            shrink_slot(&mut inventory, ENCHANTING_LAPIS_SLOT, lapis);
+        }
+
+        if item.item == ItemKind::Book {
+            item.item = ItemKind::EnchantedBook;
+        }
+
+        set_enchantment(&mut item, offer.enchantment, offer.level);
+        inventory.replace_slot(ENCHANTING_ITEM_SLOT, item.clone());
+
+        // New offers are rolled for the next item.
+        table.seed = rand::random();
+
+        enchanted.send(ItemEnchanted {
+            client: event.client,
+            table: open_inventory.entity(),
+            item,
+            offer,
+            levels: lapis as u32,
+        });
+    }
+}
+
+/// Updates the offers of changed enchanting tables.
+pub(crate) fn update_enchanting_tables(
+    mut tables: Query<
+        (&Inventory, &mut EnchantingTable),
+        Or<(Changed<Inventory>, Changed<EnchantingTable>)>,
+    >,
+) {
+    for (inventory, mut table) in &mut tables {
+        if inventory.kind() != InventoryKind::Enchantment {
+            continue;
+        }
+
+        let offers = table.compute_offers(inventory);
+
+        if table.offers != offers {
+            table.offers = offers;
+            table.properties_modified = true;
+        }
+    }
+}
+
+/// Refuels brewing stands, counts down their brew time, and brews the potions
+/// when the time is up.
+pub(crate) fn tick_brewing_stands(
+    recipes: Res<BrewingRecipes>,
+    mut stands: Query<(Entity, &mut Inventory, &mut BrewingStand)>,
+    mut brewed: EventWriter<PotionBrewed>,
+) {
+    for (entity, mut inventory, mut stand) in &mut stands {
+        if inventory.kind() != InventoryKind::BrewingStand {
+            continue;
+        }
+
+        let has_blaze_powder = inventory
+            .slot(BREWING_FUEL_SLOT)
+            .map_or(false, |stack| stack.item == ItemKind::BlazePowder);
+
+        if stand.fuel == 0 && has_blaze_powder {
+            shrink_slot(&mut inventory, BREWING_FUEL_SLOT, 1);
+            stand.fuel = FUEL_PER_BLAZE_POWDER;
+            stand.properties_modified = true;
+        }
+
+        let ingredient = inventory
+            .slot(BREWING_INGREDIENT_SLOT)
+            .map(|stack| stack.item);
+        let can_brew = ingredient.map_or(false, |ingredient| {
+            (0..3).any(|slot| {
+                inventory
+                    .slot(slot)
+                    .map_or(false, |bottle| recipes.brew(bottle, ingredient).is_some())
+            })
+        });
+
+        if stand.brew_time > 0 {
+            stand.properties_modified = true;
+
+            // Brewing stops if the ingredient or the bottles are taken out.
+            if !can_brew || stand.ingredient != ingredient {
+                stand.brew_time = 0;
+                stand.ingredient = None;
+                continue;
+            }
+
+            stand.brew_time -= 1;
+
+            if stand.brew_time > 0 {
+                continue;
+            }
+
+            let Some(ingredient) = stand.ingredient.take() else {
+                continue;
+            };
+
+            let mut potions = vec![];
+
+            for slot in 0..3 {
+                let brewed = inventory
+                    .slot(slot)
+                    .and_then(|bottle| recipes.brew(bottle, ingredient));
+
+                if let Some(potion) = brewed {
+                    inventory.replace_slot(slot, potion.clone());
+                    potions.push(potion);
+                }
+            }
+
+            shrink_slot(&mut inventory, BREWING_INGREDIENT_SLOT, 1);
+
+            brewed.send(PotionBrewed {
+                brewing_stand: entity,
+                ingredient,
+                potions,
+            });
+        } else if can_brew && stand.fuel > 0 {
+            stand.fuel -= 1;
+            stand.brew_time = BREW_TIME;
+            stand.ingredient = ingredient;
+            stand.properties_modified = true;
+        }
+    }
+}
+
+/// Sends the container properties of workstations to the clients which just
+/// opened them, and to every viewer when they change.
+pub(crate) fn send_container_properties<T: ContainerProperties>(
+    mut clients: Query<(&mut Client, &OpenInventory, ChangeTrackers<OpenInventory>)>,
+    mut workstations: Query<&mut T>,
+) {
+    for (mut client, open_inventory, open_tracker) in &mut clients {
+        let Ok(workstation) = workstations.get(open_inventory.entity()) else {
+            continue;
+        };
+
+        if open_tracker.is_added() || workstation.properties_modified() {
+            let window_id = client.window_id;
+
+            for (property, value) in workstation.properties() {
+                client.write_packet(&SetContainerProperty {
+                    window_id,
+                    property,
+                    value,
+                });
+            }
+        }
+    }
+
+    for mut workstation in &mut workstations {
+        if workstation.properties_modified() {
+            workstation.clear_properties_modified();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy_app::App;
+    use valence_nbt::compound;
+    use valence_protocol::packets::c2s::play::{
+        ClickContainer as ClickContainerC2s, ClickContainerButton as ClickContainerButtonC2s,
+    };
+    use valence_protocol::packets::S2cPlayPacket;
+    use valence_protocol::VarInt;
+
+    use super::*;
+    use crate::assert_packet_count;
+    use crate::unit_test::util::scenario_single_client;
+
+    fn book(kind: EnchantmentKind, level: i16) -> ItemStack {
+        let mut book = ItemStack::new(ItemKind::EnchantedBook, 1, None);
+        set_enchantment(&mut book, kind, level);
+        book
+    }
+
+    #[test]
+    fn anvils_combine_and_rename() {
+        let mut sword = ItemStack::new(ItemKind::DiamondSword, 1, None);
+        set_enchantment(&mut sword, EnchantmentKind::Sharpness, 3);
+
+        let right = book(EnchantmentKind::Sharpness, 3);
+        let result = combine(&AnvilInput {
+            left: &sword,
+            right: Some(&right),
+            name: None,
+        })
+        .unwrap();
+
+        assert_eq!(
+            enchantment_level(&result.item, EnchantmentKind::Sharpness),
+            4
+        );
+        assert_eq!(result.cost, 4);
+        assert_eq!(result.right_consumed, 1);
+        assert_eq!(repair_cost(&result.item), 1);
+
+        // Books with enchantments which don't apply do nothing.
+        let right = book(EnchantmentKind::Power, 1);
+        assert_eq!(
+            combine(&AnvilInput {
+                left: &sword,
+                right: Some(&right),
+                name: None,
+            }),
+            None
+        );
+
+        let renamed = combine(&AnvilInput {
+            left: &result.item,
+            right: None,
+            name: Some("Excalibur"),
+        })
+        .unwrap();
+
+        assert_eq!(
+            custom_name(&renamed.item),
+            Some(name_tag("Excalibur").as_str())
+        );
+        // One level for the name and one for the prior work.
+        assert_eq!(renamed.cost, 2);
+
+        let unchanged = combine(&AnvilInput {
+            left: &renamed.item,
+            right: None,
+            name: Some("Excalibur"),
+        });
+        assert_eq!(unchanged, None);
+
+        let damaged = |damage: i32| {
+            ItemStack::new(
+                ItemKind::DiamondPickaxe,
+                1,
+                Some(compound! { "Damage" => damage }),
+            )
+        };
+
+        let (left, right) = (damaged(1000), damaged(1500));
+        let repaired = combine(&AnvilInput {
+            left: &left,
+            right: Some(&right),
+            name: None,
+        })
+        .unwrap();
+
+        // 1561 - (561 + 61 + 187)
+        assert_eq!(item_damage(&repaired.item), 752);
+    }
+
+    #[test]
+    fn anvil_results_are_taken_on_the_server() -> anyhow::Result<()> {
+        let mut app = App::new();
+        let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+
+        let mut inventory = Inventory::new(InventoryKind::Anvil);
+        inventory.replace_slot(
+            ANVIL_LEFT_SLOT,
+            ItemStack::new(ItemKind::DiamondSword, 1, None),
+        );
+        inventory.replace_slot(ANVIL_RIGHT_SLOT, book(EnchantmentKind::Sharpness, 1));
+        let anvil_ent = app.world.spawn((inventory, Anvil::new())).id();
+
+        app.world
+            .entity_mut(client_ent)
+            .insert(OpenInventory::new(anvil_ent));
+
+        app.update();
+
+        let sent_packets = client_helper.collect_sent()?;
+        assert_packet_count!(sent_packets, 1, S2cPlayPacket::SetContainerProperty(_));
+
+        let anvil = app.world.get::<Anvil>(anvil_ent).unwrap();
+        assert_eq!(anvil.cost(), 1);
+
+        let client = app.world.get::<Client>(client_ent).unwrap();
+        let window_id = client.window_id;
+        let state_id = client.inventory_state_id.0;
+
+        client_helper.send(&ClickContainerC2s {
+            window_id,
+            state_id: VarInt(state_id),
+            slot_idx: ANVIL_RESULT_SLOT as i16,
+            button: 0,
+            mode: ClickContainerMode::Click,
+            slots: vec![(ANVIL_RESULT_SLOT as i16, None)],
+            carried_item: None,
+        });
+
+        app.update();
+
+        let inventory = app.world.get::<Inventory>(anvil_ent).unwrap();
+        assert_eq!(inventory.slots().flatten().count(), 0);
+
+        let cursor = app
+            .world
+            .get::<Client>(client_ent)
+            .unwrap()
+            .cursor_item()
+            .cloned()
+            .unwrap();
+        assert_eq!(enchantment_level(&cursor, EnchantmentKind::Sharpness), 1);
+
+        let events = app.world.resource::<Events<AnvilUsed>>();
+        assert_eq!(events.get_reader().iter(events).count(), 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn enchanting_tables_offer_and_apply_enchantments() -> anyhow::Result<()> {
+        let mut app = App::new();
+        let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+
+        let mut inventory = Inventory::new(InventoryKind::Enchantment);
+        inventory.replace_slot(
+            ENCHANTING_ITEM_SLOT,
+            ItemStack::new(ItemKind::IronPickaxe, 1, None),
+        );
+        inventory.replace_slot(
+            ENCHANTING_LAPIS_SLOT,
+            ItemStack::new(ItemKind::LapisLazuli, 3, None),
+        );
+        let table_ent = app
+            .world
+            .spawn((inventory, EnchantingTable::new().with_bookshelves(15)))
+            .id();
+
+        app.world
+            .entity_mut(client_ent)
+            .insert(OpenInventory::new(table_ent));
+
+        app.update();
+
+        let sent_packets = client_helper.collect_sent()?;
+        assert_packet_count!(sent_packets, 10, S2cPlayPacket::SetContainerProperty(_));
+
+        let offer = app
+            .world
+            .get::<EnchantingTable>(table_ent)
+            .unwrap()
+            .offers()[2]
+            .unwrap();
+        assert!(offer.cost >= 30);
+        assert!(can_enchant(offer.enchantment, ItemKind::IronPickaxe));
+
+        let window_id = app.world.get::<Client>(client_ent).unwrap().window_id;
+        client_helper.send(&ClickContainerButtonC2s {
+            window_id: window_id as i8,
+            button_id: 2,
+        });
+
+        app.update();
+
+        let inventory = app.world.get::<Inventory>(table_ent).unwrap();
+        let item = inventory.slot(ENCHANTING_ITEM_SLOT).unwrap();
+        assert_eq!(enchantment_level(item, offer.enchantment), offer.level);
+        assert_eq!(inventory.slot(ENCHANTING_LAPIS_SLOT), None);
+
+        // Enchanted items get no more offers.
+        let table = app.world.get::<EnchantingTable>(table_ent).unwrap();
+        assert_eq!(table.offers(), &[None; 3]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn brewing_stands_brew_potions() {
+        let mut app = App::new();
+        let _ = scenario_single_client(&mut app);
+
+        let water = ItemStack::new(
+            ItemKind::Potion,
+            1,
+            Some(compound! { "Potion" => "minecraft:water" }),
+        );
+
+        let mut inventory = Inventory::new(InventoryKind::BrewingStand);
+        inventory.replace_slot(0, water.clone());
+        inventory.replace_slot(2, water);
+        inventory.replace_slot(
+            BREWING_INGREDIENT_SLOT,
+            ItemStack::new(ItemKind::NetherWart, 2, None),
+        );
+        inventory.replace_slot(
+            BREWING_FUEL_SLOT,
+            ItemStack::new(ItemKind::BlazePowder, 1, None),
+        );
+        let stand_ent = app.world.spawn((inventory, BrewingStand::new())).id();
+
+        app.update();
+
+        let stand = app.world.get::<BrewingStand>(stand_ent).unwrap();
+        assert_eq!(stand.brew_time(), BREW_TIME);
+        assert_eq!(stand.fuel(), FUEL_PER_BLAZE_POWDER - 1);
+
+        for _ in 0..BREW_TIME {
+            app.update();
+        }
+
+        let inventory = app.world.get::<Inventory>(stand_ent).unwrap();
+        let awkward = ItemStack::new(
+            ItemKind::Potion,
+            1,
+            Some(compound! { "Potion" => "minecraft:awkward" }),
+        );
+        assert_eq!(inventory.slot(0), Some(&awkward));
+        assert_eq!(inventory.slot(1), None);
+        assert_eq!(inventory.slot(2), Some(&awkward));
+        assert_eq!(
+            inventory.slot(BREWING_INGREDIENT_SLOT),
+            Some(&ItemStack::new(ItemKind::NetherWart, 1, None))
+        );
+        assert_eq!(inventory.slot(BREWING_FUEL_SLOT), None);
+
+        let events = app.world.resource::<Events<PotionBrewed>>();
+        assert_eq!(events.get_reader().iter(events).count(), 1);
+
+        // Awkward potions can't be brewed with nether wart again.
+        let stand = app.world.get::<BrewingStand>(stand_ent).unwrap();
+        assert_eq!(stand.brew_time(), 0);
+    }
+}
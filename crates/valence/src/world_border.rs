@@ -0,0 +1,107 @@
+//! The world border displayed to clients.
+
+use valence_protocol::packets::s2c::play::{
+    SetBorderCenter, SetBorderSize, SetBorderWarningDelay, SetBorderWarningDistance,
+    WorldBorderInitialize,
+};
+use valence_protocol::{VarInt, VarLong};
+
+use crate::packet::WritePacket;
+
+/// The world border of an [`Instance`](crate::instance::Instance) or of a
+/// single client.
+///
+/// Valence only renders the world border. Clients are not prevented from
+/// crossing it.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct WorldBorder {
+    /// The X and Z coordinates of the center of the border.
+    pub center: [f64; 2],
+    /// The length of the sides of the border in blocks.
+    pub diameter: f64,
+    /// The distance from the border in blocks at which the client's screen
+    /// starts turning red.
+    pub warning_blocks: i32,
+    /// The time in seconds at which the client's screen starts turning red
+    /// while the border is shrinking.
+    pub warning_time: i32,
+    /// The maximum distance from the origin at which nether portals teleport
+    /// players.
+    pub portal_teleport_boundary: i32,
+}
+
+impl WorldBorder {
+    /// The world border used by vanilla worlds.
+    pub const DEFAULT: Self = Self {
+        center: [0.0, 0.0],
+        diameter: 59_999_968.0,
+        warning_blocks: 5,
+        warning_time: 15,
+        portal_teleport_boundary: 29_999_984,
+    };
+
+    pub fn new(center: [f64; 2], diameter: f64) -> Self {
+        Self {
+            center,
+            diameter,
+            ..Self::DEFAULT
+        }
+    }
+}
+
+impl Default for WorldBorder {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+/// Writes the packets needed to change a client's world border from `old` to
+/// `new`. If `old` is `None`, the whole border is sent.
+pub(crate) fn write_world_border_change(
+    mut writer: impl WritePacket,
+    old: Option<&WorldBorder>,
+    new: &WorldBorder,
+) {
+    let Some(old) = old else {
+        writer.write_packet(&WorldBorderInitialize {
+            x: new.center[0],
+            z: new.center[1],
+            old_diameter: new.diameter,
+            new_diameter: new.diameter,
+            speed: VarLong(0),
+            portal_teleport_boundary: VarInt(new.portal_teleport_boundary),
+            warning_blocks: VarInt(new.warning_blocks),
+            warning_time: VarInt(new.warning_time),
+        });
+        return;
+    };
+
+    if old.portal_teleport_boundary != new.portal_teleport_boundary {
+        // There is no separate packet for the portal teleport boundary.
+        return write_world_border_change(writer, None, new);
+    }
+
+    if old.center != new.center {
+        writer.write_packet(&SetBorderCenter {
+            xz_position: new.center,
+        });
+    }
+
+    if old.diameter != new.diameter {
+        writer.write_packet(&SetBorderSize {
+            diameter: new.diameter,
+        });
+    }
+
+    if old.warning_blocks != new.warning_blocks {
+        writer.write_packet(&SetBorderWarningDistance {
+            warning_blocks: VarInt(new.warning_blocks),
+        });
+    }
+
+    if old.warning_time != new.warning_time {
+        writer.write_packet(&SetBorderWarningDelay {
+            warning_time: VarInt(new.warning_time),
+        });
+    }
+}
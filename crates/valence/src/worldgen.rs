@@ -0,0 +1,521 @@
+//! Procedural generation of chunks.
+//!
+//! Instances with a [`WorldGen`] component have the chunks in view of their
+//! clients generated by a [`ChunkGenerator`]. Generation happens on the
+//! threads of the shared [`WorldGenPool`] so that exploring clients do not
+//! stall the tick. Chunks closer to the clients are generated first, and only
+//! a limited number of finished chunks are inserted into the instance every
+//! tick.
+//!
+//! [`DefaultGenerator`] is a ready-made generator which produces survival
+//! terrain with caves and ores from a [`WorldSeed`](crate::random::WorldSeed).
+//!
+//! Instances can mix generated chunks with chunks from elsewhere, such as an
+//! imported Anvil world. Setting [`WorldGen::blending`] reshapes the edges of
+//! generated chunks so that they meet the terrain of such neighbors.
+
+use std::collections::HashSet;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::Arc;
+use std::thread;
+
+use bevy_ecs::prelude::*;
+use flume::{Receiver, Sender};
+use parking_lot::{Condvar, Mutex};
+use tracing::error;
+
+use self::blend::{BlendData, Blending};
+use crate::budget::{SheddableWork, TickBudget};
+use crate::client::Client;
+use crate::instance::{Chunk, Instance};
+use crate::view::ChunkPos;
+
+pub mod blend;
+pub mod carver;
+pub mod noise;
+pub mod ore;
+pub mod structure;
+pub mod surface;
+mod terrain;
+
+pub use terrain::DefaultGenerator;
+
+/// Generates the contents of chunks.
+///
+/// Generators are called from multiple threads at once and must produce the
+/// same chunk for the same position so that chunks can be generated in any
+/// order. If a generator panics, the panic is logged and the chunk is
+/// generated again once a client's view moves over it.
+pub trait ChunkGenerator: Send + Sync + 'static {
+    /// Fills in the blocks and biomes of the empty chunk at `pos`. The chunk
+    /// has the section count of the instance it is generated for.
+    fn generate(&self, pos: ChunkPos, chunk: &mut Chunk);
+}
+
+impl<F> ChunkGenerator for F
+where
+    F: Fn(ChunkPos, &mut Chunk) + Send + Sync + 'static,
+{
+    fn generate(&self, pos: ChunkPos, chunk: &mut Chunk) {
+        self(pos, chunk)
+    }
+}
+
+/// The default value of [`WorldGen::insertions_per_tick`].
+pub const DEFAULT_INSERTIONS_PER_TICK: usize = 32;
+
+/// A [`Component`] which generates the chunks of the [`Instance`] on the same
+/// entity.
+///
+/// Every chunk in view of a client in the instance which is not loaded is
+/// queued for generation. Queued chunks which are no longer in view of any
+/// client are dropped from the queue before they are generated.
+#[derive(Component)]
+pub struct WorldGen {
+    generator: Arc<dyn ChunkGenerator>,
+    /// Chunks which are queued or being generated.
+    pending: HashSet<ChunkPos>,
+    /// Chunks which are generated before the chunks in view of clients,
+    /// regardless of distance.
+    preload: HashSet<ChunkPos>,
+    /// Chunks which were inserted into the instance by this component.
+    generated: HashSet<ChunkPos>,
+    /// Generated chunks, or `None` if the generator panicked.
+    finished_send: Sender<(ChunkPos, Option<Chunk>)>,
+    finished_recv: Receiver<(ChunkPos, Option<Chunk>)>,
+    /// The maximum number of generated chunks inserted into the instance every
+    /// tick. Finished chunks beyond the budget are inserted on the following
+    /// ticks.
+    ///
+    /// # Default Value
+    ///
+    /// [`DEFAULT_INSERTIONS_PER_TICK`]
+    pub insertions_per_tick: usize,
+    /// If chunks which are not in view of any client are removed from the
+    /// instance.
+    ///
+    /// # Default Value
+    ///
+    /// `false`
+    pub unload_unviewed: bool,
+    /// If set, generated chunks are blended into the neighboring chunks which
+    /// were not generated, so that there is no cliff at the seam between
+    /// them. Only neighbors which are loaded when a chunk is queued are
+    /// taken into account.
+    ///
+    /// # Default Value
+    ///
+    /// `None`
+    pub blending: Option<Blending>,
+}
+
+impl WorldGen {
+    pub fn new(generator: impl ChunkGenerator) -> Self {
+        Self::from_arc(Arc::new(generator))
+    }
+
+    /// Like [`Self::new`], but allows sharing a generator between instances.
+    pub fn from_arc(generator: Arc<dyn ChunkGenerator>) -> Self {
+        let (finished_send, finished_recv) = flume::unbounded();
+
+        Self {
+            generator,
+            pending: HashSet::new(),
+            preload: HashSet::new(),
+            generated: HashSet::new(),
+            finished_send,
+            finished_recv,
+            insertions_per_tick: DEFAULT_INSERTIONS_PER_TICK,
+            unload_unviewed: false,
+            blending: None,
+        }
+    }
+
+    pub fn generator(&self) -> &Arc<dyn ChunkGenerator> {
+        &self.generator
+    }
+
+    /// Generates the chunks within `radius` of `center` before any chunk in
+    /// view of a client, even if no client can see them.
+    ///
+    /// This is useful to generate the chunks around a teleport target before
+    /// the client arrives.
+    pub fn preload(&mut self, center: impl Into<ChunkPos>, radius: u8) {
+        let center = center.into();
+        let radius = radius as i32;
+
+        for z in center.z - radius..=center.z + radius {
+            for x in center.x - radius..=center.x + radius {
+                self.preload.insert(ChunkPos::new(x, z));
+            }
+        }
+    }
+
+    /// Returns the number of chunks which are queued or being generated.
+    pub fn pending_count(&self) -> usize {
+        self.pending.len()
+    }
+}
+
+/// A chunk waiting to be generated by the [`WorldGenPool`].
+struct Job {
+    instance: Entity,
+    pos: ChunkPos,
+    /// Smaller values are generated first.
+    priority: u64,
+    section_count: usize,
+    generator: Arc<dyn ChunkGenerator>,
+    /// The edges of the neighbors to blend the generated chunk into.
+    blend: Option<BlendData>,
+    finished: Sender<(ChunkPos, Option<Chunk>)>,
+}
+
+#[derive(Default)]
+struct PoolShared {
+    /// Queued jobs sorted by descending priority, so the next job is at the
+    /// end.
+    queue: Mutex<Vec<Job>>,
+    job_added: Condvar,
+}
+
+/// A [`Resource`] containing the threads which generate chunks for every
+/// [`WorldGen`].
+///
+/// The threads are started when the first chunk is queued.
+#[derive(Resource)]
+pub struct WorldGenPool {
+    shared: Arc<PoolShared>,
+    thread_count: usize,
+    started: bool,
+}
+
+impl WorldGenPool {
+    /// Creates a pool with the given number of worker threads.
+    pub fn new(thread_count: usize) -> Self {
+        Self {
+            shared: Arc::new(PoolShared::default()),
+            thread_count: thread_count.max(1),
+            started: false,
+        }
+    }
+
+    pub fn thread_count(&self) -> usize {
+        self.thread_count
+    }
+
+    /// Returns the number of chunks waiting for a worker thread.
+    pub fn queued_count(&self) -> usize {
+        self.shared.queue.lock().len()
+    }
+
+    fn start(&mut self) {
+        if self.started {
+            return;
+        }
+
+        self.started = true;
+
+        for i in 0..self.thread_count {
+            let shared = self.shared.clone();
+
+            thread::Builder::new()
+                .name(format!("valence-worldgen-{i}"))
+                .spawn(move || worldgen_worker(&shared))
+                .expect("failed to spawn world generation thread");
+        }
+    }
+}
+
+impl Default for WorldGenPool {
+    fn default() -> Self {
+        Self::new(thread::available_parallelism().map_or(1, |n| n.get()))
+    }
+}
+
+fn worldgen_worker(shared: &PoolShared) {
+    loop {
+        let job = {
+            let mut queue = shared.queue.lock();
+            loop {
+                if let Some(job) = queue.pop() {
+                    break job;
+                }
+                shared.job_added.wait(&mut queue);
+            }
+        };
+
+        // A panicking generator must not take the thread down with it, or the
+        // pool would eventually run out of threads.
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            let mut chunk = Chunk::new(job.section_count);
+            job.generator.generate(job.pos, &mut chunk);
+
+            if let Some(data) = &job.blend {
+                blend::blend(&mut chunk, data);
+            }
+
+            chunk
+        }));
+
+        let chunk = match result {
+            Ok(chunk) => Some(chunk),
+            Err(payload) => {
+                let msg = payload
+                    .downcast_ref::<&str>()
+                    .copied()
+                    .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+                    .unwrap_or("<non-string payload>");
+
+                error!("generating the chunk at {:?} panicked: {msg}", job.pos);
+                None
+            }
+        };
+
+        // The instance might have been despawned in the meantime.
+        let _ = job.finished.send((job.pos, chunk));
+    }
+}
+
+/// Returns the priority of generating the chunk at `pos`, which is its
+/// distance to the closest view center.
+fn chunk_priority(pos: ChunkPos, preload: &HashSet<ChunkPos>, centers: &[ChunkPos]) -> u64 {
+    if preload.contains(&pos) {
+        return 0;
+    }
+
+    centers
+        .iter()
+        .map(|center| center.distance_squared(pos) + 1)
+        .min()
+        .unwrap_or(u64::MAX)
+}
+
+pub(crate) fn insert_generated_chunks(
+    mut instances: Query<(&mut Instance, &mut WorldGen)>,
+    budget: Res<TickBudget>,
+) {
+    for (mut instance, mut worldgen) in &mut instances {
+        let worldgen = &mut *worldgen;
+
+        let insertions = if budget.is_shedding(SheddableWork::ChunkInsertions) {
+            (worldgen.insertions_per_tick / 4).max(1)
+        } else {
+            worldgen.insertions_per_tick
+        };
+
+        for (pos, chunk) in worldgen.finished_recv.try_iter().take(insertions) {
+            if worldgen.pending.remove(&pos) {
+                // Failed chunks are not preloaded again either, so that a
+                // broken generator doesn't panic on every tick.
+                worldgen.preload.remove(&pos);
+
+                if let Some(chunk) = chunk {
+                    if instance.chunk(pos).is_none() {
+                        instance.insert_chunk(pos, chunk);
+                        worldgen.generated.insert(pos);
+                    }
+                }
+            }
+        }
+    }
+}
+
+pub(crate) fn queue_chunk_generation(
+    mut instances: Query<(Entity, &mut Instance, &mut WorldGen)>,
+    clients: Query<&Client>,
+    mut pool: ResMut<WorldGenPool>,
+) {
+    let mut queue = pool.shared.queue.lock();
+    let mut queued_any = false;
+
+    for (instance_id, mut instance, mut worldgen) in &mut instances {
+        let worldgen = &mut *worldgen;
+
+        let views = clients
+            .iter()
+            .filter(|client| client.instance() == instance_id)
+            .collect::<Vec<_>>();
+
+        let centers = views
+            .iter()
+            .map(|client| client.view().pos)
+            .collect::<Vec<_>>();
+
+        if worldgen.unload_unviewed {
+            instance.retain_chunks(|pos, _| views.iter().any(|c| c.view().contains(pos)));
+            worldgen
+                .generated
+                .retain(|&pos| instance.chunk(pos).is_some());
+        }
+
+        // Reprioritize the queued chunks of this instance and drop the ones
+        // nobody can see anymore.
+        queue.retain_mut(|job| {
+            if job.instance != instance_id {
+                return true;
+            }
+
+            let needed = worldgen.preload.contains(&job.pos)
+                || views.iter().any(|c| c.view().contains(job.pos));
+
+            if !needed {
+                worldgen.pending.remove(&job.pos);
+                return false;
+            }
+
+            job.priority = chunk_priority(job.pos, &worldgen.preload, &centers);
+            true
+        });
+
+        let mut new_positions = worldgen.preload.iter().copied().collect::<Vec<_>>();
+
+        // Only look at the views of clients which changed to avoid scanning
+        // every view on every tick.
+        for client in &views {
+            if client.is_new()
+                || client.view() != client.old_view()
+                || client.instance() != client.old_instance()
+            {
+                new_positions.extend(client.view().iter());
+            }
+        }
+
+        for pos in new_positions {
+            if instance.chunk(pos).is_some() || !worldgen.pending.insert(pos) {
+                continue;
+            }
+
+            let blend = worldgen.blending.and_then(|settings| {
+                blend::collect_edges(&instance, pos, settings, |p| {
+                    !worldgen.generated.contains(&p)
+                })
+            });
+
+            queue.push(Job {
+                instance: instance_id,
+                pos,
+                priority: chunk_priority(pos, &worldgen.preload, &centers),
+                section_count: instance.section_count(),
+                generator: worldgen.generator.clone(),
+                blend,
+                finished: worldgen.finished_send.clone(),
+            });
+
+            queued_any = true;
+        }
+    }
+
+    // Sort by descending priority so workers pop the closest chunk first.
+    queue.sort_unstable_by(|a, b| b.priority.cmp(&a.priority));
+
+    drop(queue);
+
+    if queued_any {
+        pool.start();
+        pool.shared.job_added.notify_all();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy_app::App;
+    use valence_protocol::BlockState;
+
+    use super::*;
+    use crate::unit_test::util::scenario_single_client;
+
+    #[test]
+    fn chunks_near_clients_come_first() {
+        let preload = HashSet::from([ChunkPos::new(50, 50)]);
+        let centers = [ChunkPos::new(0, 0), ChunkPos::new(10, 0)];
+
+        assert_eq!(chunk_priority(ChunkPos::new(50, 50), &preload, &centers), 0);
+        assert_eq!(chunk_priority(ChunkPos::new(9, 0), &preload, &centers), 2);
+        assert_eq!(chunk_priority(ChunkPos::new(1, 1), &preload, &[]), u64::MAX);
+    }
+
+    #[test]
+    fn chunks_in_view_are_generated() {
+        let mut app = App::new();
+        let (client_ent, _) = scenario_single_client(&mut app);
+        let instance_ent = app.world.get::<Client>(client_ent).unwrap().instance();
+
+        app.world.entity_mut(instance_ent).insert(WorldGen::new(
+            |_: ChunkPos, chunk: &mut Chunk| {
+                chunk.set_block_state(0, 0, 0, BlockState::STONE);
+            },
+        ));
+
+        let view = app.world.get::<Client>(client_ent).unwrap().view();
+
+        for _ in 0..1000 {
+            app.update();
+
+            let instance = app.world.get::<Instance>(instance_ent).unwrap();
+            if view.iter().all(|pos| instance.chunk(pos).is_some()) {
+                break;
+            }
+
+            thread::sleep(std::time::Duration::from_millis(1));
+        }
+
+        let instance = app.world.get::<Instance>(instance_ent).unwrap();
+        for pos in view.iter() {
+            assert_eq!(
+                instance.chunk(pos).unwrap().block_state(0, 0, 0),
+                BlockState::STONE
+            );
+        }
+
+        assert_eq!(
+            app.world
+                .get::<WorldGen>(instance_ent)
+                .unwrap()
+                .pending_count(),
+            0
+        );
+    }
+
+    #[test]
+    fn panicking_generators_do_not_stop_the_pool() {
+        let mut app = App::new();
+        let (client_ent, _) = scenario_single_client(&mut app);
+        let instance_ent = app.world.get::<Client>(client_ent).unwrap().instance();
+
+        // With a single thread, nothing else is generated if the thread dies.
+        app.insert_resource(WorldGenPool::new(1));
+
+        app.world.entity_mut(instance_ent).insert(WorldGen::new(
+            |pos: ChunkPos, chunk: &mut Chunk| {
+                assert_ne!(pos, ChunkPos::new(0, 0), "broken generator");
+                chunk.set_block_state(0, 0, 0, BlockState::STONE);
+            },
+        ));
+
+        for _ in 0..1000 {
+            app.update();
+
+            let worldgen = app.world.get::<WorldGen>(instance_ent).unwrap();
+            if worldgen.pending_count() == 0 {
+                break;
+            }
+
+            thread::sleep(std::time::Duration::from_millis(1));
+        }
+
+        assert_eq!(
+            app.world
+                .get::<WorldGen>(instance_ent)
+                .unwrap()
+                .pending_count(),
+            0
+        );
+
+        let instance = app.world.get::<Instance>(instance_ent).unwrap();
+        let view = app.world.get::<Client>(client_ent).unwrap().view();
+
+        for pos in view.iter() {
+            assert_eq!(instance.chunk(pos).is_some(), pos != ChunkPos::new(0, 0));
+        }
+    }
+}
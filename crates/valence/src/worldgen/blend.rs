@@ -0,0 +1,269 @@
+//! Blending generated chunks into neighboring chunks which were not
+//! generated, such as chunks imported from an Anvil world.
+
+use valence_protocol::BlockState;
+
+use crate::biome::BiomeId;
+use crate::instance::{Chunk, Instance};
+use crate::view::ChunkPos;
+
+/// Settings for blending generated chunks into their neighbors. See
+/// [`WorldGen::blending`](super::WorldGen::blending).
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct Blending {
+    /// How many blocks away from a neighboring chunk the terrain height is
+    /// interpolated. At most 16.
+    ///
+    /// # Default Value
+    ///
+    /// `12`
+    pub distance: u32,
+    /// If the biomes of neighboring chunks are spread into the generated
+    /// chunk.
+    ///
+    /// # Default Value
+    ///
+    /// `true`
+    pub biomes: bool,
+}
+
+impl Default for Blending {
+    fn default() -> Self {
+        Self {
+            distance: 12,
+            biomes: true,
+        }
+    }
+}
+
+/// A column at the edge of a neighboring chunk.
+#[derive(Copy, Clone, Debug)]
+struct EdgeColumn {
+    /// The position of the column relative to the origin of the generated
+    /// chunk. At least one component is outside of `0..16`.
+    x: i32,
+    z: i32,
+    /// The height of the highest terrain block, in chunk coordinates.
+    height: Option<usize>,
+    biome: BiomeId,
+}
+
+/// The edges of the neighbors of a chunk which is being generated.
+#[derive(Clone, Debug)]
+pub(super) struct BlendData {
+    settings: Blending,
+    edges: Vec<EdgeColumn>,
+}
+
+/// Returns `true` if the block is part of the ground rather than vegetation.
+fn is_terrain(state: BlockState) -> bool {
+    if state.is_air() || state.is_liquid() || !state.is_opaque() {
+        return false;
+    }
+
+    let name = state.to_kind().to_str();
+    !name.ends_with("_leaves") && !name.ends_with("_log")
+}
+
+fn terrain_height<const LOADED: bool>(chunk: &Chunk<LOADED>, x: usize, z: usize) -> Option<usize> {
+    (0..chunk.section_count() * 16)
+        .rev()
+        .find(|&y| is_terrain(chunk.block_state(x, y, z)))
+}
+
+/// Collects the edges of the loaded neighbors of `pos` which `is_foreign`
+/// returns `true` for. Returns `None` if there are no such neighbors.
+pub(super) fn collect_edges(
+    instance: &Instance,
+    pos: ChunkPos,
+    settings: Blending,
+    is_foreign: impl Fn(ChunkPos) -> bool,
+) -> Option<BlendData> {
+    let mut edges = vec![];
+
+    for dz in -1..=1 {
+        for dx in -1..=1 {
+            let neighbor_pos = ChunkPos::new(pos.x + dx, pos.z + dz);
+
+            if (dx, dz) == (0, 0) || !is_foreign(neighbor_pos) {
+                continue;
+            }
+
+            let Some(neighbor) = instance.chunk(neighbor_pos) else {
+                continue;
+            };
+
+            // The columns of the neighbor which touch the generated chunk.
+            let xs = match dx {
+                -1 => 15..=15,
+                1 => 0..=0,
+                _ => 0..=15,
+            };
+            let zs = match dz {
+                -1 => 15..=15,
+                1 => 0..=0,
+                _ => 0..=15,
+            };
+
+            for z in zs {
+                for x in xs.clone() {
+                    let height = terrain_height(neighbor, x, z);
+                    let biome_y = height.unwrap_or(0) / 4;
+
+                    edges.push(EdgeColumn {
+                        x: dx * 16 + x as i32,
+                        z: dz * 16 + z as i32,
+                        height,
+                        biome: neighbor.biome(x / 4, biome_y, z / 4),
+                    });
+                }
+            }
+        }
+    }
+
+    (!edges.is_empty()).then_some(BlendData { settings, edges })
+}
+
+/// Reshapes the columns of a generated chunk near its foreign neighbors so
+/// that the terrain meets theirs.
+pub(super) fn blend(chunk: &mut Chunk, data: &BlendData) {
+    let distance = data.settings.distance.clamp(1, 16) as f64;
+    let height = chunk.section_count() * 16;
+
+    for z in 0..16 {
+        for x in 0..16 {
+            let Some((edge, dist)) = data
+                .edges
+                .iter()
+                .map(|edge| {
+                    let dx = (edge.x - x as i32) as f64;
+                    let dz = (edge.z - z as i32) as f64;
+                    (edge, (dx * dx + dz * dz).sqrt())
+                })
+                .min_by(|(_, a), (_, b)| a.total_cmp(b))
+            else {
+                continue;
+            };
+
+            if dist > distance {
+                continue;
+            }
+
+            // Smoothly decrease the influence of the neighbor with distance.
+            let t = 1.0 - (dist - 1.0).max(0.0) / distance;
+            let t = t * t * (3.0 - 2.0 * t);
+
+            if data.settings.biomes && x % 4 == 2 && z % 4 == 2 && t >= 0.5 {
+                for biome_y in 0..chunk.section_count() * 4 {
+                    chunk.set_biome(x / 4, biome_y, z / 4, edge.biome);
+                }
+            }
+
+            let (Some(generated), Some(edge_height)) = (terrain_height(chunk, x, z), edge.height)
+            else {
+                continue;
+            };
+
+            let target = generated as f64 + (edge_height as f64 - generated as f64) * t;
+            let target = (target.round() as usize).min(height - 1);
+
+            reshape_column(chunk, x, z, generated, target);
+        }
+    }
+}
+
+/// Moves the surface of a column from `from` to `to`, keeping its top
+/// blocks.
+fn reshape_column(chunk: &mut Chunk, x: usize, z: usize, from: usize, to: usize) {
+    let height = chunk.section_count() * 16;
+    let top = chunk.block_state(x, from, z);
+    let under = if from > 0 {
+        chunk.block_state(x, from - 1, z)
+    } else {
+        top
+    };
+
+    if to < from {
+        // Keep oceans and lakes filled.
+        let fill = match chunk.block_state(x, (from + 1).min(height - 1), z) {
+            state if state.is_liquid() => state,
+            _ => BlockState::AIR,
+        };
+
+        for y in to + 1..=from {
+            chunk.set_block_state(x, y, z, fill);
+        }
+
+        // Remove the vegetation which would otherwise float.
+        for y in from + 1..height {
+            let state = chunk.block_state(x, y, z);
+            if state.is_air() || state.is_liquid() {
+                break;
+            }
+            chunk.set_block_state(x, y, z, fill);
+        }
+    } else if to > from {
+        for y in from..to {
+            let block = if to - y <= 3 {
+                under
+            } else {
+                BlockState::STONE
+            };
+            chunk.set_block_state(x, y, z, block);
+        }
+    } else {
+        return;
+    }
+
+    chunk.set_block_state(x, to, z, top);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn flat_chunk(height: usize) -> Chunk {
+        let mut chunk = Chunk::new(2);
+
+        for z in 0..16 {
+            for x in 0..16 {
+                for y in 0..height {
+                    chunk.set_block_state(x, y, z, BlockState::STONE);
+                }
+                chunk.set_block_state(x, height, z, BlockState::GRASS_BLOCK);
+            }
+        }
+
+        chunk
+    }
+
+    #[test]
+    fn terrain_meets_the_neighbor() {
+        let mut chunk = flat_chunk(5);
+
+        let data = BlendData {
+            settings: Blending::default(),
+            edges: (0..16)
+                .map(|z| EdgeColumn {
+                    x: -1,
+                    z,
+                    height: Some(20),
+                    biome: BiomeId::default(),
+                })
+                .collect(),
+        };
+
+        blend(&mut chunk, &data);
+
+        // Right next to the neighbor the terrain has its height.
+        assert_eq!(terrain_height(&chunk, 0, 8), Some(20));
+        assert_eq!(chunk.block_state(0, 20, 8), BlockState::GRASS_BLOCK);
+
+        // The height decreases towards the generated terrain.
+        let heights = (0..16)
+            .map(|x| terrain_height(&chunk, x, 8).unwrap())
+            .collect::<Vec<_>>();
+        assert!(heights.windows(2).all(|w| w[0] >= w[1]));
+        assert_eq!(heights[15], 5);
+    }
+}
@@ -0,0 +1,292 @@
+//! Carving of caves and ravines into generated terrain.
+
+use std::f64::consts::PI;
+
+use valence_protocol::BlockState;
+
+use super::noise::OctaveNoise;
+use super::ore::HeightDistribution;
+use crate::instance::Chunk;
+use crate::random::{WorldSeed, Xoroshiro};
+use crate::view::ChunkPos;
+
+/// How far away in chunks ravines can start and still reach a chunk.
+const RAVINE_RANGE: i32 = 8;
+
+/// The configuration of the caves and ravines carved by the
+/// [`DefaultGenerator`](super::DefaultGenerator).
+#[derive(Clone, PartialEq, Debug)]
+pub struct CarverConfig {
+    /// If winding tunnels and large caverns are carved using 3D noise.
+    ///
+    /// # Default Value
+    ///
+    /// `true`
+    pub noise_caves: bool,
+    /// How much of the underground is carved into caverns, in the range
+    /// `0.0..=1.0`.
+    ///
+    /// # Default Value
+    ///
+    /// `0.1`
+    pub cavern_density: f64,
+    /// The width of noise tunnels. Larger values make wider and more frequent
+    /// tunnels.
+    ///
+    /// # Default Value
+    ///
+    /// `0.08`
+    pub tunnel_width: f64,
+    /// The chance that a ravine starts in a chunk.
+    ///
+    /// # Default Value
+    ///
+    /// `0.01`
+    pub ravine_chance: f32,
+    /// The heights ravines start at.
+    ///
+    /// # Default Value
+    ///
+    /// Uniform between `10` and `67`.
+    pub ravine_height: HeightDistribution,
+    /// Carved blocks at or below this height are filled with lava instead of
+    /// air.
+    ///
+    /// # Default Value
+    ///
+    /// `-56`
+    pub lava_level: i32,
+}
+
+impl Default for CarverConfig {
+    fn default() -> Self {
+        Self {
+            noise_caves: true,
+            cavern_density: 0.1,
+            tunnel_width: 0.08,
+            ravine_chance: 0.01,
+            ravine_height: HeightDistribution::Uniform { min: 10, max: 67 },
+            lava_level: -56,
+        }
+    }
+}
+
+/// The noises used to carve caves.
+#[derive(Clone, Debug)]
+pub(super) struct CaveNoise {
+    cavern: OctaveNoise,
+    tunnel_a: OctaveNoise,
+    tunnel_b: OctaveNoise,
+}
+
+impl CaveNoise {
+    pub(super) fn new(seed: WorldSeed) -> Self {
+        let positional = seed.positional();
+
+        Self {
+            cavern: OctaveNoise::new(&mut positional.by_name("valence:cave_cavern"), 3, 96.0),
+            tunnel_a: OctaveNoise::new(&mut positional.by_name("valence:cave_tunnel_a"), 2, 64.0),
+            tunnel_b: OctaveNoise::new(&mut positional.by_name("valence:cave_tunnel_b"), 2, 64.0),
+        }
+    }
+}
+
+/// Replaces a block of the chunk with air or lava unless it is something
+/// which should not be carved.
+fn carve_block(chunk: &mut Chunk, x: usize, y: usize, z: usize, min_y: i32, lava_level: i32) {
+    let block = chunk.block_state(x, y, z);
+
+    if block.is_air() || block == BlockState::BEDROCK || block.is_liquid() {
+        return;
+    }
+
+    // Avoid draining oceans and lakes into the caves below.
+    if y + 1 < chunk.section_count() * 16 && chunk.block_state(x, y + 1, z).is_liquid() {
+        return;
+    }
+
+    let carved = if y as i32 + min_y <= lava_level {
+        BlockState::LAVA
+    } else {
+        BlockState::CAVE_AIR
+    };
+
+    chunk.set_block_state(x, y, z, carved);
+}
+
+/// Carves noise caves into the chunk. `surface` contains the surface height of
+/// every column of the chunk, indexed by `x + z * 16`.
+pub(super) fn carve_noise_caves(
+    chunk: &mut Chunk,
+    pos: ChunkPos,
+    min_y: i32,
+    surface: &[i32; 256],
+    config: &CarverConfig,
+    noise: &CaveNoise,
+) {
+    if !config.noise_caves {
+        return;
+    }
+
+    let height = chunk.section_count() as i32 * 16;
+    let cavern_threshold = 1.0 - config.cavern_density.clamp(0.0, 1.0) * 2.0;
+
+    for z in 0..16 {
+        for x in 0..16 {
+            let wx = (pos.x * 16 + x as i32) as f64;
+            let wz = (pos.z * 16 + z as i32) as f64;
+            let top = surface[x + z * 16] - min_y;
+
+            // Stay above the bedrock floor.
+            for y in 5..top.min(height) {
+                let wy = (y + min_y) as f64;
+
+                // Caverns stay below the surface, but tunnels may break
+                // through to form cave entrances.
+                let in_cavern =
+                    y < top - 8 && noise.cavern.sample(wx, wy * 2.0, wz) * 2.0 > cavern_threshold;
+
+                let in_tunnel = noise.tunnel_a.sample(wx, wy, wz).abs() < config.tunnel_width / 2.0
+                    && noise.tunnel_b.sample(wx, wy, wz).abs() < config.tunnel_width / 2.0;
+
+                if in_cavern || in_tunnel {
+                    carve_block(chunk, x, y as usize, z, min_y, config.lava_level);
+                }
+            }
+        }
+    }
+}
+
+/// Carves the parts of ravines starting in nearby chunks which pass through
+/// the chunk at `pos`.
+pub(super) fn carve_ravines(
+    chunk: &mut Chunk,
+    pos: ChunkPos,
+    min_y: i32,
+    seed: WorldSeed,
+    config: &CarverConfig,
+) {
+    for start_z in pos.z - RAVINE_RANGE..=pos.z + RAVINE_RANGE {
+        for start_x in pos.x - RAVINE_RANGE..=pos.x + RAVINE_RANGE {
+            let start = ChunkPos::new(start_x, start_z);
+            let mut rng = seed.large_feature_random(start);
+
+            if rng.next_f32() >= config.ravine_chance {
+                continue;
+            }
+
+            carve_ravine(chunk, pos, start, min_y, config, &mut rng);
+        }
+    }
+}
+
+fn carve_ravine(
+    chunk: &mut Chunk,
+    pos: ChunkPos,
+    start: ChunkPos,
+    min_y: i32,
+    config: &CarverConfig,
+    rng: &mut Xoroshiro,
+) {
+    let mut x = (start.x * 16 + rng.next_i32_bounded(16)) as f64;
+    let mut y = config.ravine_height.sample(rng) as f64;
+    let mut z = (start.z * 16 + rng.next_i32_bounded(16)) as f64;
+
+    let mut yaw = rng.next_f64() * PI * 2.0;
+    let mut pitch = (rng.next_f64() - 0.5) / 4.0;
+    let mut yaw_change = 0.0;
+    let mut pitch_change = 0.0;
+
+    let width = (rng.next_f64() * 2.0 + rng.next_f64()) * 2.0;
+    let length = 112 - rng.next_i32_bounded(28);
+
+    let chunk_center_x = (pos.x * 16 + 8) as f64;
+    let chunk_center_z = (pos.z * 16 + 8) as f64;
+
+    for step in 0..length {
+        let radius = 1.5 + (PI * step as f64 / length as f64).sin() * width;
+        let vertical_radius = radius * 3.0;
+
+        x += yaw.cos() * pitch.cos();
+        y += pitch.sin();
+        z += yaw.sin() * pitch.cos();
+
+        pitch *= 0.7;
+        pitch += pitch_change * 0.05;
+        yaw += yaw_change * 0.05;
+        pitch_change =
+            pitch_change * 0.8 + (rng.next_f64() - rng.next_f64()) * rng.next_f64() * 2.0;
+        yaw_change = yaw_change * 0.5 + (rng.next_f64() - rng.next_f64()) * rng.next_f64() * 4.0;
+
+        // Occasionally skip a step to roughen the walls.
+        if rng.next_i32_bounded(4) == 0 {
+            continue;
+        }
+
+        if (x - chunk_center_x).abs() > 16.0 + radius * 2.0
+            || (z - chunk_center_z).abs() > 16.0 + radius * 2.0
+        {
+            continue;
+        }
+
+        carve_ellipsoid(
+            chunk,
+            pos,
+            min_y,
+            [x, y, z],
+            radius,
+            vertical_radius,
+            config,
+        );
+    }
+}
+
+fn carve_ellipsoid(
+    chunk: &mut Chunk,
+    pos: ChunkPos,
+    min_y: i32,
+    center: [f64; 3],
+    radius: f64,
+    vertical_radius: f64,
+    config: &CarverConfig,
+) {
+    let height = chunk.section_count() as i32 * 16;
+
+    let [cx, cy, cz] = center;
+    let local_x = cx - (pos.x * 16) as f64;
+    let local_y = cy - min_y as f64;
+    let local_z = cz - (pos.z * 16) as f64;
+
+    let min_x = ((local_x - radius).floor() as i32).max(0);
+    let max_x = ((local_x + radius).ceil() as i32).min(15);
+    let min_z = ((local_z - radius).floor() as i32).max(0);
+    let max_z = ((local_z + radius).ceil() as i32).min(15);
+    let min_cy = ((local_y - vertical_radius).floor() as i32).max(5);
+    let max_cy = ((local_y + vertical_radius).ceil() as i32).min(height - 1);
+
+    for z in min_z..=max_z {
+        for x in min_x..=max_x {
+            let dx = (x as f64 + 0.5 - local_x) / radius;
+            let dz = (z as f64 + 0.5 - local_z) / radius;
+
+            if dx * dx + dz * dz >= 1.0 {
+                continue;
+            }
+
+            for y in min_cy..=max_cy {
+                let dy = (y as f64 + 0.5 - local_y) / vertical_radius;
+
+                if dx * dx + dy * dy + dz * dz < 1.0 {
+                    carve_block(
+                        chunk,
+                        x as usize,
+                        y as usize,
+                        z as usize,
+                        min_y,
+                        config.lava_level,
+                    );
+                }
+            }
+        }
+    }
+}
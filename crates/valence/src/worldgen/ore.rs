@@ -0,0 +1,216 @@
+//! Placement of ore veins.
+
+use valence_protocol::BlockState;
+
+use crate::instance::Chunk;
+use crate::random::Xoroshiro;
+
+/// How the heights of ore veins are distributed.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum HeightDistribution {
+    /// Every height in `min..=max` is equally likely.
+    Uniform { min: i32, max: i32 },
+    /// Heights in the middle of `min..=max` are the most likely, and the
+    /// likelihood decreases linearly towards both ends.
+    Triangular { min: i32, max: i32 },
+}
+
+impl HeightDistribution {
+    /// Picks a height from the distribution.
+    pub fn sample(self, rng: &mut Xoroshiro) -> i32 {
+        match self {
+            Self::Uniform { min, max } => {
+                if max <= min {
+                    min
+                } else {
+                    rng.next_i32_between(min, max)
+                }
+            }
+            Self::Triangular { min, max } => {
+                if max <= min {
+                    return min;
+                }
+
+                let range = max - min;
+                let half = range / 2;
+
+                min + rng.next_i32_between(0, range - half) + rng.next_i32_between(0, half)
+            }
+        }
+    }
+}
+
+/// The configuration of an ore placed by the
+/// [`DefaultGenerator`](super::DefaultGenerator).
+#[derive(Clone, PartialEq, Debug)]
+pub struct OreConfig {
+    /// The ore placed in place of stone.
+    pub ore: BlockState,
+    /// The ore placed in place of deepslate.
+    pub deepslate_ore: BlockState,
+    /// The maximum number of blocks in a vein.
+    pub size: u32,
+    /// The number of veins attempted per chunk.
+    pub veins_per_chunk: u32,
+    pub height: HeightDistribution,
+}
+
+impl OreConfig {
+    pub fn new(
+        ore: BlockState,
+        deepslate_ore: BlockState,
+        size: u32,
+        veins_per_chunk: u32,
+        height: HeightDistribution,
+    ) -> Self {
+        Self {
+            ore,
+            deepslate_ore,
+            size,
+            veins_per_chunk,
+            height,
+        }
+    }
+
+    /// Returns ores similar to the ones in vanilla's overworld.
+    pub fn overworld() -> Vec<Self> {
+        use HeightDistribution::*;
+
+        vec![
+            Self::new(
+                BlockState::COAL_ORE,
+                BlockState::DEEPSLATE_COAL_ORE,
+                17,
+                20,
+                Triangular { min: 0, max: 192 },
+            ),
+            Self::new(
+                BlockState::IRON_ORE,
+                BlockState::DEEPSLATE_IRON_ORE,
+                9,
+                10,
+                Triangular { min: -24, max: 56 },
+            ),
+            Self::new(
+                BlockState::COPPER_ORE,
+                BlockState::DEEPSLATE_COPPER_ORE,
+                10,
+                16,
+                Triangular { min: -16, max: 112 },
+            ),
+            Self::new(
+                BlockState::GOLD_ORE,
+                BlockState::DEEPSLATE_GOLD_ORE,
+                9,
+                4,
+                Triangular { min: -64, max: 32 },
+            ),
+            Self::new(
+                BlockState::REDSTONE_ORE,
+                BlockState::DEEPSLATE_REDSTONE_ORE,
+                8,
+                4,
+                Uniform { min: -64, max: 15 },
+            ),
+            Self::new(
+                BlockState::LAPIS_ORE,
+                BlockState::DEEPSLATE_LAPIS_ORE,
+                7,
+                2,
+                Triangular { min: -32, max: 32 },
+            ),
+            Self::new(
+                BlockState::DIAMOND_ORE,
+                BlockState::DEEPSLATE_DIAMOND_ORE,
+                8,
+                7,
+                Triangular { min: -144, max: 16 },
+            ),
+            Self::new(
+                BlockState::EMERALD_ORE,
+                BlockState::DEEPSLATE_EMERALD_ORE,
+                3,
+                3,
+                Triangular { min: -16, max: 480 },
+            ),
+        ]
+    }
+}
+
+/// Places the veins of the ores in the chunk. `min_y` is the world height of
+/// the bottom of the chunk.
+pub(super) fn place_ores(chunk: &mut Chunk, min_y: i32, ores: &[OreConfig], rng: &mut Xoroshiro) {
+    let height = chunk.section_count() as i32 * 16;
+
+    for ore in ores {
+        for _ in 0..ore.veins_per_chunk {
+            let x = rng.next_i32_bounded(16);
+            let z = rng.next_i32_bounded(16);
+            let y = ore.height.sample(rng) - min_y;
+
+            if !(0..height).contains(&y) {
+                continue;
+            }
+
+            place_vein(chunk, [x, y, z], ore, rng);
+        }
+    }
+}
+
+/// Places a vein as a random walk of blobs starting at `start`. Only stone
+/// and deepslate inside the chunk are replaced.
+fn place_vein(chunk: &mut Chunk, start: [i32; 3], ore: &OreConfig, rng: &mut Xoroshiro) {
+    let height = chunk.section_count() as i32 * 16;
+    let [mut x, mut y, mut z] = start;
+
+    for _ in 0..ore.size {
+        if (0..16).contains(&x) && (0..height).contains(&y) && (0..16).contains(&z) {
+            let (bx, by, bz) = (x as usize, y as usize, z as usize);
+
+            let replacement = match chunk.block_state(bx, by, bz) {
+                BlockState::STONE => Some(ore.ore),
+                BlockState::DEEPSLATE => Some(ore.deepslate_ore),
+                _ => None,
+            };
+
+            if let Some(replacement) = replacement {
+                chunk.set_block_state(bx, by, bz, replacement);
+            }
+        }
+
+        match rng.next_i32_bounded(6) {
+            0 => x += 1,
+            1 => x -= 1,
+            2 => y += 1,
+            3 => y -= 1,
+            4 => z += 1,
+            _ => z -= 1,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn distributions_stay_in_range() {
+        let mut rng = Xoroshiro::from_seed_i64(1);
+
+        let triangular = HeightDistribution::Triangular { min: -16, max: 112 };
+        let uniform = HeightDistribution::Uniform { min: -64, max: 15 };
+
+        let mut sum = 0;
+        for _ in 0..10_000 {
+            let y = triangular.sample(&mut rng);
+            assert!((-16..=112).contains(&y));
+            sum += y;
+
+            assert!((-64..=15).contains(&uniform.sample(&mut rng)));
+        }
+
+        // Triangular distributions are centered on the middle of the range.
+        let mean = sum / 10_000;
+        assert!((40..=56).contains(&mean), "mean was {mean}");
+    }
+}
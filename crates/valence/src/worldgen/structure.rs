@@ -0,0 +1,888 @@
+//! Jigsaw structures such as villages and dungeons.
+//!
+//! A structure is assembled from [`StructureTemplate`]s. Templates contain
+//! [`Connector`]s (jigsaw blocks in vanilla) which name the [`TemplatePool`]
+//! the next piece is taken from. Starting with a piece from the structure's
+//! start pool, pieces are attached to open connectors until
+//! [`JigsawStructure::max_depth`] is reached or no piece fits without
+//! overlapping the others.
+//!
+//! Where structures start is derived from the [`WorldSeed`], so the bounding
+//! boxes of structures can be computed without generating any chunks. See
+//! [`DefaultGenerator::locate`](super::DefaultGenerator::locate).
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use anyhow::{bail, ensure, Context};
+use valence_nbt::{Compound, List, Value};
+use valence_protocol::block::{BlockKind, PropName, PropValue};
+use valence_protocol::{BlockFace, BlockPos, BlockState};
+
+use crate::random::{WorldSeed, Xoroshiro};
+use crate::view::ChunkPos;
+
+/// An axis-aligned box of blocks. Both corners are inclusive.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub struct BoundingBox {
+    pub min: BlockPos,
+    pub max: BlockPos,
+}
+
+impl BoundingBox {
+    /// Creates the smallest box containing both corners.
+    pub fn new(a: impl Into<BlockPos>, b: impl Into<BlockPos>) -> Self {
+        let (a, b) = (a.into(), b.into());
+
+        Self {
+            min: BlockPos::new(a.x.min(b.x), a.y.min(b.y), a.z.min(b.z)),
+            max: BlockPos::new(a.x.max(b.x), a.y.max(b.y), a.z.max(b.z)),
+        }
+    }
+
+    pub fn contains(&self, pos: BlockPos) -> bool {
+        (self.min.x..=self.max.x).contains(&pos.x)
+            && (self.min.y..=self.max.y).contains(&pos.y)
+            && (self.min.z..=self.max.z).contains(&pos.z)
+    }
+
+    pub fn intersects(&self, other: &Self) -> bool {
+        self.min.x <= other.max.x
+            && self.max.x >= other.min.x
+            && self.min.y <= other.max.y
+            && self.max.y >= other.min.y
+            && self.min.z <= other.max.z
+            && self.max.z >= other.min.z
+    }
+
+    /// Returns `true` if the box overlaps the columns of the chunk.
+    pub fn intersects_chunk(&self, pos: ChunkPos) -> bool {
+        self.min.x <= pos.x * 16 + 15
+            && self.max.x >= pos.x * 16
+            && self.min.z <= pos.z * 16 + 15
+            && self.max.z >= pos.z * 16
+    }
+
+    /// Returns the smallest box containing both boxes.
+    pub fn union(&self, other: &Self) -> Self {
+        Self::new(
+            [
+                self.min.x.min(other.min.x),
+                self.min.y.min(other.min.y),
+                self.min.z.min(other.min.z),
+            ],
+            [
+                self.max.x.max(other.max.x),
+                self.max.y.max(other.max.y),
+                self.max.z.max(other.max.z),
+            ],
+        )
+    }
+
+    pub fn center(&self) -> BlockPos {
+        BlockPos::new(
+            (self.min.x + self.max.x).div_euclid(2),
+            (self.min.y + self.max.y).div_euclid(2),
+            (self.min.z + self.max.z).div_euclid(2),
+        )
+    }
+}
+
+/// A rotation of a piece around the vertical axis.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum Rotation {
+    None,
+    Clockwise90,
+    Clockwise180,
+    Counterclockwise90,
+}
+
+impl Rotation {
+    pub const ALL: [Self; 4] = [
+        Self::None,
+        Self::Clockwise90,
+        Self::Clockwise180,
+        Self::Counterclockwise90,
+    ];
+
+    pub fn rotate_pos(self, pos: BlockPos) -> BlockPos {
+        let BlockPos { x, y, z } = pos;
+
+        match self {
+            Self::None => BlockPos::new(x, y, z),
+            Self::Clockwise90 => BlockPos::new(-z, y, x),
+            Self::Clockwise180 => BlockPos::new(-x, y, -z),
+            Self::Counterclockwise90 => BlockPos::new(z, y, -x),
+        }
+    }
+
+    pub fn rotate_face(self, face: BlockFace) -> BlockFace {
+        let turns = match self {
+            Self::None => 0,
+            Self::Clockwise90 => 1,
+            Self::Clockwise180 => 2,
+            Self::Counterclockwise90 => 3,
+        };
+
+        (0..turns).fold(face, |face, _| match face {
+            BlockFace::North => BlockFace::East,
+            BlockFace::East => BlockFace::South,
+            BlockFace::South => BlockFace::West,
+            BlockFace::West => BlockFace::North,
+            vertical => vertical,
+        })
+    }
+
+    /// Rotates the `facing` and `axis` properties of a block state.
+    pub fn rotate_block(self, state: BlockState) -> BlockState {
+        let mut state = state;
+
+        if let Some(facing) = state.get(PropName::Facing) {
+            let face = match facing {
+                PropValue::North => Some(BlockFace::North),
+                PropValue::East => Some(BlockFace::East),
+                PropValue::South => Some(BlockFace::South),
+                PropValue::West => Some(BlockFace::West),
+                _ => None,
+            };
+
+            if let Some(face) = face {
+                let value = match self.rotate_face(face) {
+                    BlockFace::North => PropValue::North,
+                    BlockFace::East => PropValue::East,
+                    BlockFace::South => PropValue::South,
+                    _ => PropValue::West,
+                };
+
+                state = state.set(PropName::Facing, value);
+            }
+        }
+
+        if matches!(self, Self::Clockwise90 | Self::Counterclockwise90) {
+            match state.get(PropName::Axis) {
+                Some(PropValue::X) => state = state.set(PropName::Axis, PropValue::Z),
+                Some(PropValue::Z) => state = state.set(PropName::Axis, PropValue::X),
+                _ => {}
+            }
+        }
+
+        state
+    }
+}
+
+fn opposite(face: BlockFace) -> BlockFace {
+    match face {
+        BlockFace::Bottom => BlockFace::Top,
+        BlockFace::Top => BlockFace::Bottom,
+        BlockFace::North => BlockFace::South,
+        BlockFace::South => BlockFace::North,
+        BlockFace::West => BlockFace::East,
+        BlockFace::East => BlockFace::West,
+    }
+}
+
+/// A point on a template where another piece can be attached.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Connector {
+    /// The position of the connector in the template.
+    pub pos: BlockPos,
+    /// The direction attached pieces are placed in.
+    pub facing: BlockFace,
+    /// The name other connectors target to attach to this connector.
+    pub name: String,
+    /// The name of the connectors this connector attaches to.
+    pub target: String,
+    /// The pool the pieces attached to this connector are taken from.
+    pub pool: String,
+}
+
+/// A piece of a structure.
+#[derive(Clone, PartialEq, Debug)]
+pub struct StructureTemplate {
+    size: [i32; 3],
+    blocks: Vec<(BlockPos, BlockState)>,
+    connectors: Vec<Connector>,
+}
+
+impl StructureTemplate {
+    /// Creates an empty template. Blocks and connectors must be within
+    /// `size`.
+    pub fn new(size: [i32; 3]) -> Self {
+        Self {
+            size,
+            blocks: vec![],
+            connectors: vec![],
+        }
+    }
+
+    #[must_use]
+    pub fn with_block(mut self, pos: impl Into<BlockPos>, state: BlockState) -> Self {
+        self.blocks.push((pos.into(), state));
+        self
+    }
+
+    #[must_use]
+    pub fn with_connector(mut self, connector: Connector) -> Self {
+        self.connectors.push(connector);
+        self
+    }
+
+    /// Loads a template from the NBT of a vanilla structure file, such as
+    /// the ones saved by structure blocks. Jigsaw blocks become connectors
+    /// and are replaced by their final state, and structure voids are left
+    /// out.
+    pub fn from_nbt(nbt: &Compound) -> anyhow::Result<Self> {
+        let Some(Value::List(List::Int(size))) = nbt.get("size") else {
+            bail!("missing structure size")
+        };
+        ensure!(size.len() == 3, "structure size must have three components");
+
+        let Some(Value::List(List::Compound(palette))) = nbt.get("palette") else {
+            bail!("missing structure palette")
+        };
+
+        let palette = palette
+            .iter()
+            .map(|entry| {
+                let Some(Value::String(name)) = entry.get("Name") else {
+                    bail!("palette entry has no name")
+                };
+
+                let mut props = vec![];
+                if let Some(Value::Compound(properties)) = entry.get("Properties") {
+                    for (key, value) in properties.iter() {
+                        if let Value::String(value) = value {
+                            props.push((key.as_str(), value.as_str()));
+                        }
+                    }
+                }
+
+                Ok((name.as_str(), props))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        let mut template = Self::new([size[0], size[1], size[2]]);
+
+        let blocks = match nbt.get("blocks") {
+            Some(Value::List(List::Compound(blocks))) => blocks.as_slice(),
+            _ => &[],
+        };
+
+        for block in blocks {
+            let (Some(Value::List(List::Int(pos))), Some(Value::Int(state))) =
+                (block.get("pos"), block.get("state"))
+            else {
+                bail!("invalid structure block")
+            };
+            ensure!(pos.len() == 3, "block position must have three components");
+
+            let pos = BlockPos::new(pos[0], pos[1], pos[2]);
+            let (name, props) = palette
+                .get(*state as usize)
+                .context("block state is not in the palette")?;
+
+            let name = name.strip_prefix("minecraft:").unwrap_or(name);
+
+            match name {
+                "structure_void" => continue,
+                "jigsaw" => {
+                    let orientation = props
+                        .iter()
+                        .find(|(key, _)| *key == "orientation")
+                        .map_or("north_up", |(_, value)| value);
+
+                    let facing = match orientation.split('_').next() {
+                        Some("down") => BlockFace::Bottom,
+                        Some("up") => BlockFace::Top,
+                        Some("south") => BlockFace::South,
+                        Some("west") => BlockFace::West,
+                        Some("east") => BlockFace::East,
+                        _ => BlockFace::North,
+                    };
+
+                    let jigsaw = match block.get("nbt") {
+                        Some(Value::Compound(nbt)) => nbt.clone(),
+                        _ => Compound::new(),
+                    };
+
+                    let string = |key: &str| match jigsaw.get(key) {
+                        Some(Value::String(s)) => s.clone(),
+                        _ => String::new(),
+                    };
+
+                    let final_state = match jigsaw.get("final_state") {
+                        Some(Value::String(s)) => parse_block_state(s)?,
+                        _ => BlockState::AIR,
+                    };
+
+                    template.connectors.push(Connector {
+                        pos,
+                        facing,
+                        name: string("name"),
+                        target: string("target"),
+                        pool: string("pool"),
+                    });
+                    template.blocks.push((pos, final_state));
+                }
+                _ => {
+                    let mut state = BlockKind::from_str(name)
+                        .with_context(|| format!("unknown block \"{name}\""))?
+                        .to_state();
+
+                    for (key, value) in props {
+                        if let (Some(key), Some(value)) =
+                            (PropName::from_str(key), PropValue::from_str(value))
+                        {
+                            state = state.set(key, value);
+                        }
+                    }
+
+                    template.blocks.push((pos, state));
+                }
+            }
+        }
+
+        Ok(template)
+    }
+
+    pub fn size(&self) -> [i32; 3] {
+        self.size
+    }
+
+    pub fn blocks(&self) -> &[(BlockPos, BlockState)] {
+        &self.blocks
+    }
+
+    pub fn connectors(&self) -> &[Connector] {
+        &self.connectors
+    }
+
+    /// Returns the bounding box of the template when placed at `origin` with
+    /// the given rotation.
+    fn bounds(&self, origin: BlockPos, rotation: Rotation) -> BoundingBox {
+        let [sx, sy, sz] = self.size.map(|s| (s - 1).max(0));
+        let a = rotation.rotate_pos(BlockPos::new(0, 0, 0));
+        let b = rotation.rotate_pos(BlockPos::new(sx, sy, sz));
+
+        BoundingBox::new(
+            [origin.x + a.x, origin.y + a.y, origin.z + a.z],
+            [origin.x + b.x, origin.y + b.y, origin.z + b.z],
+        )
+    }
+}
+
+/// Parses a block state like `minecraft:chest[facing=north]`.
+fn parse_block_state(s: &str) -> anyhow::Result<BlockState> {
+    let (name, props) = match s.split_once('[') {
+        Some((name, props)) => (name, props.trim_end_matches(']')),
+        None => (s, ""),
+    };
+
+    let name = name.strip_prefix("minecraft:").unwrap_or(name);
+
+    let mut state = BlockKind::from_str(name)
+        .with_context(|| format!("unknown block \"{name}\""))?
+        .to_state();
+
+    for prop in props.split(',').filter(|p| !p.is_empty()) {
+        if let Some((key, value)) = prop.split_once('=') {
+            if let (Some(key), Some(value)) = (
+                PropName::from_str(key.trim()),
+                PropValue::from_str(value.trim()),
+            ) {
+                state = state.set(key, value);
+            }
+        }
+    }
+
+    Ok(state)
+}
+
+/// Weighted templates which pieces are picked from.
+#[derive(Clone, PartialEq, Default, Debug)]
+pub struct TemplatePool {
+    templates: Vec<(Arc<StructureTemplate>, u32)>,
+}
+
+impl TemplatePool {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[must_use]
+    pub fn with_template(
+        mut self,
+        template: impl Into<Arc<StructureTemplate>>,
+        weight: u32,
+    ) -> Self {
+        self.templates.push((template.into(), weight));
+        self
+    }
+
+    /// Returns the templates in a random order, where templates with a
+    /// higher weight tend to come first.
+    fn shuffled(&self, rng: &mut Xoroshiro) -> Vec<&Arc<StructureTemplate>> {
+        let mut templates = self
+            .templates
+            .iter()
+            .flat_map(|(template, weight)| (0..*weight).map(move |_| template))
+            .collect::<Vec<_>>();
+
+        shuffle(&mut templates, rng);
+
+        let mut seen = vec![];
+        templates.retain(|t| {
+            if seen.iter().any(|s| Arc::ptr_eq(s, t)) {
+                false
+            } else {
+                seen.push(*t);
+                true
+            }
+        });
+
+        templates
+    }
+}
+
+fn shuffle<T>(items: &mut [T], rng: &mut Xoroshiro) {
+    for i in (1..items.len()).rev() {
+        let j = rng.next_i32_bounded(i as i32 + 1) as usize;
+        items.swap(i, j);
+    }
+}
+
+/// A structure assembled from the templates of a [`StructureSet`].
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct JigsawStructure {
+    /// The name of the structure, used to locate it.
+    pub name: String,
+    /// The pool the first piece is taken from.
+    pub start_pool: String,
+    /// How many pieces away from the start piece pieces can be attached.
+    pub max_depth: u32,
+    /// The size in chunks of the square regions in which one structure of
+    /// this kind is attempted.
+    pub spacing: i32,
+    /// The minimum distance in chunks between two structures of this kind.
+    /// Must be less than `spacing`.
+    pub separation: i32,
+    /// Distinguishes the placement of this structure from the placement of
+    /// others with the same spacing.
+    pub salt: i32,
+    /// The maximum horizontal distance in blocks between pieces and the
+    /// start of the structure.
+    pub max_distance: i32,
+}
+
+impl JigsawStructure {
+    /// Creates a structure with vanilla village-like spacing.
+    pub fn new(name: impl Into<String>, start_pool: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            start_pool: start_pool.into(),
+            max_depth: 6,
+            spacing: 34,
+            separation: 8,
+            salt: 10387312,
+            max_distance: 80,
+        }
+    }
+
+    fn region_of(&self, pos: ChunkPos) -> (i32, i32) {
+        let spacing = self.spacing.max(1);
+        (pos.x.div_euclid(spacing), pos.z.div_euclid(spacing))
+    }
+
+    /// Returns the chunk the structure starts in within the given region.
+    pub fn start_chunk(&self, seed: WorldSeed, region_x: i32, region_z: i32) -> ChunkPos {
+        let spacing = self.spacing.max(1);
+        let range = (spacing - self.separation).max(1);
+
+        let mut rng = Xoroshiro::from_seed_i64(
+            (region_x as i64)
+                .wrapping_mul(341873128712)
+                .wrapping_add((region_z as i64).wrapping_mul(132897987541))
+                .wrapping_add(seed.0)
+                .wrapping_add(self.salt as i64),
+        );
+
+        ChunkPos::new(
+            region_x * spacing + rng.next_i32_bounded(range),
+            region_z * spacing + rng.next_i32_bounded(range),
+        )
+    }
+
+    /// Returns the distance in chunks around the start chunk which pieces can
+    /// reach.
+    pub(super) fn chunk_reach(&self) -> i32 {
+        self.max_distance / 16 + 1
+    }
+}
+
+/// A template placed in the world.
+#[derive(Clone, PartialEq, Debug)]
+pub struct PlacedPiece {
+    pub template: Arc<StructureTemplate>,
+    /// The world position of the template's origin.
+    pub origin: BlockPos,
+    pub rotation: Rotation,
+    pub bounds: BoundingBox,
+    /// The number of pieces between this piece and the start piece.
+    pub depth: u32,
+}
+
+impl PlacedPiece {
+    /// Returns the world position of a position in the template.
+    pub fn world_pos(&self, pos: BlockPos) -> BlockPos {
+        let p = self.rotation.rotate_pos(pos);
+        BlockPos::new(
+            self.origin.x + p.x,
+            self.origin.y + p.y,
+            self.origin.z + p.z,
+        )
+    }
+
+    /// Returns an iterator over the blocks of the piece in world
+    /// coordinates.
+    pub fn blocks(&self) -> impl Iterator<Item = (BlockPos, BlockState)> + '_ {
+        self.template
+            .blocks
+            .iter()
+            .map(|(pos, state)| (self.world_pos(*pos), self.rotation.rotate_block(*state)))
+    }
+}
+
+/// An assembled structure.
+#[derive(Clone, PartialEq, Debug)]
+pub struct StructureStart {
+    /// The name of the [`JigsawStructure`].
+    pub structure: String,
+    pub chunk: ChunkPos,
+    pub pieces: Vec<PlacedPiece>,
+    /// The bounding box containing all pieces.
+    pub bounds: BoundingBox,
+}
+
+/// The structures a generator places and the template pools they are built
+/// from.
+#[derive(Clone, PartialEq, Default, Debug)]
+pub struct StructureSet {
+    pub pools: HashMap<String, TemplatePool>,
+    pub structures: Vec<JigsawStructure>,
+}
+
+impl StructureSet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[must_use]
+    pub fn with_pool(mut self, name: impl Into<String>, pool: TemplatePool) -> Self {
+        self.pools.insert(name.into(), pool);
+        self
+    }
+
+    #[must_use]
+    pub fn with_structure(mut self, structure: JigsawStructure) -> Self {
+        self.structures.push(structure);
+        self
+    }
+
+    /// Returns the distance in chunks around their start chunk which the
+    /// structures in this set can reach.
+    pub(super) fn chunk_reach(&self) -> i32 {
+        self.structures
+            .iter()
+            .map(JigsawStructure::chunk_reach)
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Assembles the structures which start in the chunk. `height` returns
+    /// the surface height of a column, which the start piece is placed on.
+    pub fn starts_in(
+        &self,
+        seed: WorldSeed,
+        chunk: ChunkPos,
+        height: impl Fn(i32, i32) -> i32,
+    ) -> Vec<StructureStart> {
+        self.structures
+            .iter()
+            .filter(|s| {
+                let (rx, rz) = s.region_of(chunk);
+                s.start_chunk(seed, rx, rz) == chunk
+            })
+            .filter_map(|s| self.assemble(s, seed, chunk, &height))
+            .collect()
+    }
+
+    fn assemble(
+        &self,
+        structure: &JigsawStructure,
+        seed: WorldSeed,
+        chunk: ChunkPos,
+        height: &impl Fn(i32, i32) -> i32,
+    ) -> Option<StructureStart> {
+        let mut rng = seed
+            .positional()
+            .by_name(&structure.name)
+            .fork_positional()
+            .at_chunk(chunk);
+
+        let start_pool = self.pools.get(&structure.start_pool)?;
+        let template = start_pool.shuffled(&mut rng).first().copied()?.clone();
+
+        let rotation = Rotation::ALL[rng.next_i32_bounded(4) as usize];
+        let x = chunk.x * 16 + 8;
+        let z = chunk.z * 16 + 8;
+        let origin = BlockPos::new(x, height(x, z) + 1, z);
+
+        let start = PlacedPiece {
+            bounds: template.bounds(origin, rotation),
+            template,
+            origin,
+            rotation,
+            depth: 0,
+        };
+
+        let center = start.bounds.center();
+        let limit = BoundingBox::new(
+            [
+                center.x - structure.max_distance,
+                i32::MIN,
+                center.z - structure.max_distance,
+            ],
+            [
+                center.x + structure.max_distance,
+                i32::MAX,
+                center.z + structure.max_distance,
+            ],
+        );
+
+        let mut pieces = vec![start];
+        let mut open = (0..pieces[0].template.connectors.len())
+            .map(|i| (0, i))
+            .collect::<Vec<_>>();
+
+        while !open.is_empty() {
+            let (piece_idx, connector_idx) = open.remove(0);
+            let piece = &pieces[piece_idx];
+
+            if piece.depth >= structure.max_depth {
+                continue;
+            }
+
+            let connector = &piece.template.connectors[connector_idx];
+            let facing = piece.rotation.rotate_face(connector.facing);
+            let target = piece.world_pos(connector.pos).get_in_direction(facing);
+            let depth = piece.depth + 1;
+
+            let Some(pool) = self.pools.get(&connector.pool) else {
+                continue;
+            };
+
+            let target_name = connector.target.clone();
+
+            let placed = pool.shuffled(&mut rng).into_iter().find_map(|template| {
+                let mut rotations = Rotation::ALL;
+                shuffle(&mut rotations, &mut rng);
+
+                rotations.into_iter().find_map(|rotation| {
+                    template
+                        .connectors
+                        .iter()
+                        .enumerate()
+                        .filter(|(_, c)| {
+                            c.name == target_name
+                                && rotation.rotate_face(c.facing) == opposite(facing)
+                        })
+                        .find_map(|(idx, c)| {
+                            let p = rotation.rotate_pos(c.pos);
+                            let origin =
+                                BlockPos::new(target.x - p.x, target.y - p.y, target.z - p.z);
+                            let bounds = template.bounds(origin, rotation);
+
+                            let fits = limit.contains(bounds.min)
+                                && limit.contains(bounds.max)
+                                && pieces.iter().all(|other| !other.bounds.intersects(&bounds));
+
+                            fits.then(|| {
+                                (
+                                    PlacedPiece {
+                                        template: template.clone(),
+                                        origin,
+                                        rotation,
+                                        bounds,
+                                        depth,
+                                    },
+                                    idx,
+                                )
+                            })
+                        })
+                })
+            });
+
+            if let Some((piece, used_connector)) = placed {
+                let idx = pieces.len();
+
+                open.extend(
+                    (0..piece.template.connectors.len())
+                        .filter(|&i| i != used_connector)
+                        .map(|i| (idx, i)),
+                );
+
+                pieces.push(piece);
+            }
+        }
+
+        let bounds = pieces
+            .iter()
+            .skip(1)
+            .fold(pieces[0].bounds, |acc, piece| acc.union(&piece.bounds));
+
+        Some(StructureStart {
+            structure: structure.name.clone(),
+            chunk,
+            pieces,
+            bounds,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn corridor() -> StructureTemplate {
+        let mut template = StructureTemplate::new([5, 3, 5]);
+
+        for x in 0..5 {
+            for z in 0..5 {
+                template = template.with_block([x, 0, z], BlockState::COBBLESTONE);
+            }
+        }
+
+        for (pos, facing) in [
+            ([2, 1, 0], BlockFace::North),
+            ([2, 1, 4], BlockFace::South),
+            ([0, 1, 2], BlockFace::West),
+            ([4, 1, 2], BlockFace::East),
+        ] {
+            template = template.with_connector(Connector {
+                pos: pos.into(),
+                facing,
+                name: "corridor".into(),
+                target: "corridor".into(),
+                pool: "corridors".into(),
+            });
+        }
+
+        template
+    }
+
+    #[test]
+    fn rotations() {
+        let pos = BlockPos::new(1, 2, 3);
+
+        for rotation in Rotation::ALL {
+            assert_eq!(
+                rotation.rotate_face(BlockFace::Top),
+                BlockFace::Top,
+                "{rotation:?}"
+            );
+        }
+
+        assert_eq!(
+            Rotation::Clockwise90.rotate_pos(pos),
+            BlockPos::new(-3, 2, 1)
+        );
+        assert_eq!(
+            Rotation::Clockwise90.rotate_face(BlockFace::North),
+            BlockFace::East
+        );
+        assert_eq!(
+            Rotation::Counterclockwise90.rotate_pos(Rotation::Clockwise90.rotate_pos(pos)),
+            pos
+        );
+    }
+
+    #[test]
+    fn pieces_do_not_overlap() {
+        let set = StructureSet::new()
+            .with_pool(
+                "corridors",
+                TemplatePool::new().with_template(corridor(), 1),
+            )
+            .with_structure(JigsawStructure {
+                spacing: 1,
+                separation: 0,
+                ..JigsawStructure::new("dungeon", "corridors")
+            });
+
+        let seed = WorldSeed(99);
+        let starts = set.starts_in(seed, ChunkPos::new(0, 0), |_, _| 40);
+
+        assert_eq!(starts.len(), 1);
+        let start = &starts[0];
+
+        assert!(start.pieces.len() > 1);
+        assert!(start.pieces.iter().all(|p| p.depth <= 6));
+
+        for (i, a) in start.pieces.iter().enumerate() {
+            assert!(start.bounds.contains(a.bounds.min));
+            assert!(start.bounds.contains(a.bounds.max));
+
+            for b in &start.pieces[i + 1..] {
+                assert!(!a.bounds.intersects(&b.bounds));
+            }
+        }
+
+        assert_eq!(
+            set.starts_in(seed, ChunkPos::new(0, 0), |_, _| 40),
+            starts,
+            "assembly must be deterministic"
+        );
+    }
+
+    #[test]
+    fn template_from_nbt() {
+        use valence_nbt::compound;
+
+        let nbt = compound! {
+            "size" => List::Int(vec![2, 1, 1]),
+            "palette" => List::Compound(vec![
+                compound! { "Name" => "minecraft:stone" },
+                compound! {
+                    "Name" => "minecraft:jigsaw",
+                    "Properties" => compound! { "orientation" => "east_up" },
+                },
+            ]),
+            "blocks" => List::Compound(vec![
+                compound! { "pos" => List::Int(vec![0, 0, 0]), "state" => 0 },
+                compound! {
+                    "pos" => List::Int(vec![1, 0, 0]),
+                    "state" => 1,
+                    "nbt" => compound! {
+                        "name" => "a",
+                        "target" => "b",
+                        "pool" => "c",
+                        "final_state" => "minecraft:oak_log[axis=x]",
+                    },
+                },
+            ]),
+        };
+
+        let template = StructureTemplate::from_nbt(&nbt).unwrap();
+
+        assert_eq!(template.size(), [2, 1, 1]);
+        assert_eq!(
+            template.blocks()[1],
+            (
+                BlockPos::new(1, 0, 0),
+                BlockState::OAK_LOG.set(PropName::Axis, PropValue::X)
+            )
+        );
+        assert_eq!(template.connectors()[0].facing, BlockFace::East);
+        assert_eq!(template.connectors()[0].pool, "c");
+    }
+}
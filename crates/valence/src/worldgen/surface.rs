@@ -0,0 +1,564 @@
+//! Biome-driven surface blocks and vegetation.
+//!
+//! The [`DefaultGenerator`](super::DefaultGenerator) picks a biome for every
+//! column from temperature and humidity noise and then builds the surface
+//! and places plants and trees according to that biome's [`BiomeSurface`].
+//!
+//! Surface configurations can be loaded from JSON with
+//! [`SurfaceConfig::from_json`]. Blocks are referred to by name and biomes by
+//! the name they were registered with in the
+//! [`ServerPlugin`](crate::config::ServerPlugin):
+//!
+//! ```json
+//! {
+//!   "biomes": [
+//!     {
+//!       "biome": "minecraft:badlands",
+//!       "temperature": 0.9,
+//!       "humidity": -0.8,
+//!       "top": "red_sand",
+//!       "under": "terracotta",
+//!       "under_depth": 12,
+//!       "banded": true,
+//!       "vegetation_per_chunk": 2,
+//!       "vegetation": [
+//!         { "weight": 3, "plant": "dead_bush" },
+//!         { "weight": 1, "column": "cactus", "height": 3 }
+//!       ]
+//!     }
+//!   ]
+//! }
+//! ```
+
+use anyhow::{bail, Context};
+use serde::Deserialize;
+use valence_protocol::block::{BlockKind, PropName, PropValue};
+use valence_protocol::BlockState;
+
+use crate::biome::BiomeId;
+use crate::instance::Chunk;
+use crate::random::{WorldSeed, Xoroshiro};
+use crate::server::SharedServer;
+
+/// The number of layers in the terracotta band pattern of banded biomes.
+const BAND_COUNT: usize = 64;
+
+/// The surfaces of all biomes placed by the generator.
+#[derive(Clone, PartialEq, Debug)]
+pub struct SurfaceConfig {
+    /// Must contain at least one biome.
+    pub biomes: Vec<BiomeSurface>,
+}
+
+impl SurfaceConfig {
+    /// Parses a surface configuration from JSON, resolving biome names against
+    /// the biomes of the server.
+    pub fn from_json(json: &str, server: &SharedServer) -> anyhow::Result<Self> {
+        let raw: RawSurfaceConfig = serde_json::from_str(json)?;
+
+        let biomes = raw
+            .biomes
+            .into_iter()
+            .map(|raw| raw.resolve(server))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        if biomes.is_empty() {
+            bail!("surface configuration contains no biomes");
+        }
+
+        Ok(Self { biomes })
+    }
+
+    /// Returns the index of the biome whose climate is closest to the given
+    /// temperature and humidity.
+    pub(super) fn closest_biome(&self, temperature: f64, humidity: f64) -> usize {
+        self.biomes
+            .iter()
+            .enumerate()
+            .min_by(|(_, a), (_, b)| {
+                a.climate_distance(temperature, humidity)
+                    .total_cmp(&b.climate_distance(temperature, humidity))
+            })
+            .map_or(0, |(i, _)| i)
+    }
+}
+
+impl Default for SurfaceConfig {
+    fn default() -> Self {
+        Self {
+            biomes: vec![BiomeSurface::new(BiomeId::default())],
+        }
+    }
+}
+
+/// The surface and vegetation of a biome.
+#[derive(Clone, PartialEq, Debug)]
+pub struct BiomeSurface {
+    pub biome: BiomeId,
+    /// The temperature at which the biome is placed, in `-1.0..=1.0`. Columns
+    /// get the biome whose temperature and humidity are closest to theirs.
+    pub temperature: f64,
+    /// The humidity at which the biome is placed, in `-1.0..=1.0`.
+    pub humidity: f64,
+    /// The block at the top of columns above sea level.
+    pub top: BlockState,
+    /// The block below the top block.
+    pub under: BlockState,
+    /// The number of `under` blocks below the top block.
+    pub under_depth: u32,
+    /// The top and under block of columns at or below sea level.
+    pub underwater: BlockState,
+    /// If the `under` layers are replaced by bands of colored terracotta, like
+    /// in badlands.
+    pub banded: bool,
+    /// The number of attempts to place vegetation in each chunk.
+    pub vegetation_per_chunk: u32,
+    /// The vegetation which is randomly chosen for every attempt.
+    pub vegetation: Vec<WeightedVegetation>,
+}
+
+impl BiomeSurface {
+    /// Creates a grassy biome with plants, flowers, and oak trees.
+    pub fn new(biome: BiomeId) -> Self {
+        Self {
+            biome,
+            temperature: 0.0,
+            humidity: 0.0,
+            top: BlockState::GRASS_BLOCK,
+            under: BlockState::DIRT,
+            under_depth: 3,
+            underwater: BlockState::SAND,
+            banded: false,
+            vegetation_per_chunk: 12,
+            vegetation: vec![
+                WeightedVegetation::new(16, Vegetation::Plant(BlockState::GRASS)),
+                WeightedVegetation::new(2, Vegetation::TallPlant(BlockState::TALL_GRASS)),
+                WeightedVegetation::new(1, Vegetation::Plant(BlockState::DANDELION)),
+                WeightedVegetation::new(1, Vegetation::Plant(BlockState::POPPY)),
+                WeightedVegetation::new(
+                    1,
+                    Vegetation::Tree {
+                        log: BlockState::OAK_LOG,
+                        leaves: BlockState::OAK_LEAVES,
+                        min_height: 4,
+                        max_height: 6,
+                    },
+                ),
+            ],
+        }
+    }
+
+    fn climate_distance(&self, temperature: f64, humidity: f64) -> f64 {
+        (self.temperature - temperature).powi(2) + (self.humidity - humidity).powi(2)
+    }
+
+    /// Chooses a random entry from the vegetation of the biome.
+    fn pick_vegetation(&self, rng: &mut Xoroshiro) -> Option<&Vegetation> {
+        let total: u32 = self.vegetation.iter().map(|v| v.weight).sum();
+
+        if total == 0 {
+            return None;
+        }
+
+        let mut roll = rng.next_i32_bounded(total.min(i32::MAX as u32) as i32) as u32;
+
+        for entry in &self.vegetation {
+            if roll < entry.weight {
+                return Some(&entry.vegetation);
+            }
+            roll -= entry.weight;
+        }
+
+        None
+    }
+}
+
+/// A [`Vegetation`] and how likely it is to be chosen.
+#[derive(Clone, PartialEq, Debug)]
+pub struct WeightedVegetation {
+    pub weight: u32,
+    pub vegetation: Vegetation,
+}
+
+impl WeightedVegetation {
+    pub fn new(weight: u32, vegetation: Vegetation) -> Self {
+        Self { weight, vegetation }
+    }
+}
+
+/// Something that grows on the surface of a biome.
+#[derive(Clone, PartialEq, Debug)]
+pub enum Vegetation {
+    /// A single block such as grass or a flower.
+    Plant(BlockState),
+    /// A block with an upper and lower half such as tall grass.
+    TallPlant(BlockState),
+    /// A column of the same block such as cactus or sugar cane.
+    Column { block: BlockState, height: u32 },
+    /// A tree with a straight trunk and a round canopy.
+    Tree {
+        log: BlockState,
+        leaves: BlockState,
+        min_height: u32,
+        max_height: u32,
+    },
+}
+
+/// The terracotta band pattern of banded biomes in a world.
+#[derive(Clone, Debug)]
+pub(super) struct Bands([BlockState; BAND_COUNT]);
+
+impl Bands {
+    pub(super) fn new(seed: WorldSeed) -> Self {
+        let mut rng = seed.positional().by_name("valence:terracotta_bands");
+
+        let mut bands = [BlockState::TERRACOTTA; BAND_COUNT];
+
+        for color in [
+            BlockState::ORANGE_TERRACOTTA,
+            BlockState::YELLOW_TERRACOTTA,
+            BlockState::BROWN_TERRACOTTA,
+            BlockState::RED_TERRACOTTA,
+            BlockState::WHITE_TERRACOTTA,
+            BlockState::LIGHT_GRAY_TERRACOTTA,
+        ] {
+            for _ in 0..rng.next_i32_between(2, 5) {
+                let start = rng.next_i32_bounded(BAND_COUNT as i32) as usize;
+                let width = rng.next_i32_between(1, 3) as usize;
+
+                for band in bands.iter_mut().skip(start).take(width) {
+                    *band = color;
+                }
+            }
+        }
+
+        Self(bands)
+    }
+
+    fn at(&self, y: i32) -> BlockState {
+        self.0[y.rem_euclid(BAND_COUNT as i32) as usize]
+    }
+}
+
+/// Replaces the top stone blocks of a column with the surface of its biome.
+/// `top` is the world height of the highest terrain block.
+pub(super) fn build_surface(
+    chunk: &mut Chunk,
+    [x, z]: [usize; 2],
+    top: i32,
+    min_y: i32,
+    sea_level: i32,
+    biome: &BiomeSurface,
+    bands: &Bands,
+) {
+    let height = chunk.section_count() as i32 * 16;
+    let underwater = top < sea_level + 2;
+
+    for depth in 0..=biome.under_depth as i32 {
+        let world_y = top - depth;
+        let y = world_y - min_y;
+
+        if !(0..height).contains(&y) {
+            continue;
+        }
+
+        let y = y as usize;
+
+        if chunk.block_state(x, y, z) != BlockState::STONE {
+            continue;
+        }
+
+        let block = if underwater {
+            biome.underwater
+        } else if depth == 0 {
+            biome.top
+        } else if biome.banded {
+            bands.at(world_y)
+        } else {
+            biome.under
+        };
+
+        chunk.set_block_state(x, y, z, block);
+    }
+}
+
+/// Places the vegetation of `biome` in the chunk. `surface` contains the
+/// height of the highest terrain block of every column, indexed by
+/// `x + z * 16`.
+pub(super) fn place_vegetation(
+    chunk: &mut Chunk,
+    min_y: i32,
+    surface: &[i32; 256],
+    biome_at: impl Fn(usize, usize) -> usize,
+    biomes: &[BiomeSurface],
+    rng: &mut Xoroshiro,
+) {
+    let height = chunk.section_count() as i32 * 16;
+    let attempts = biomes[biome_at(8, 8)].vegetation_per_chunk;
+
+    for _ in 0..attempts {
+        let x = rng.next_i32_bounded(16) as usize;
+        let z = rng.next_i32_bounded(16) as usize;
+        let biome = &biomes[biome_at(x, z)];
+
+        let Some(vegetation) = biome.pick_vegetation(rng) else {
+            continue;
+        };
+
+        let ground = surface[x + z * 16] - min_y;
+
+        if !(0..height - 1).contains(&ground)
+            || chunk.block_state(x, ground as usize, z) != biome.top
+            || !chunk.block_state(x, ground as usize + 1, z).is_air()
+        {
+            continue;
+        }
+
+        place(chunk, [x, ground as usize + 1, z], vegetation, rng);
+    }
+}
+
+fn place(chunk: &mut Chunk, [x, y, z]: [usize; 3], vegetation: &Vegetation, rng: &mut Xoroshiro) {
+    let height = chunk.section_count() * 16;
+    let is_free = |chunk: &Chunk, y: usize| y < height && chunk.block_state(x, y, z).is_air();
+
+    match *vegetation {
+        Vegetation::Plant(block) => {
+            chunk.set_block_state(x, y, z, block);
+        }
+        Vegetation::TallPlant(block) => {
+            if is_free(chunk, y + 1) {
+                chunk.set_block_state(x, y, z, block.set(PropName::Half, PropValue::Lower));
+                chunk.set_block_state(x, y + 1, z, block.set(PropName::Half, PropValue::Upper));
+            }
+        }
+        Vegetation::Column { block, height } => {
+            for y in y..y + rng.next_i32_between(1, height.max(1) as i32) as usize {
+                if !is_free(chunk, y) {
+                    break;
+                }
+                chunk.set_block_state(x, y, z, block);
+            }
+        }
+        Vegetation::Tree {
+            log,
+            leaves,
+            min_height,
+            max_height,
+        } => {
+            // Keep the canopy inside the chunk.
+            if !(2..14).contains(&x) || !(2..14).contains(&z) {
+                return;
+            }
+
+            let trunk =
+                rng.next_i32_between(min_height as i32, max_height.max(min_height) as i32) as usize;
+
+            if y + trunk + 2 >= height {
+                return;
+            }
+
+            let leaves = leaves.set(PropName::Persistent, PropValue::True);
+            let canopy_bottom = y + trunk - 2;
+
+            for ly in canopy_bottom..=y + trunk + 1 {
+                let radius = if ly > y + trunk - 1 { 1 } else { 2 };
+
+                for lz in z - radius..=z + radius {
+                    for lx in x - radius..=x + radius {
+                        let corner = lx.abs_diff(x) == radius && lz.abs_diff(z) == radius;
+
+                        if (!corner || rng.next_bool()) && chunk.block_state(lx, ly, lz).is_air() {
+                            chunk.set_block_state(lx, ly, lz, leaves);
+                        }
+                    }
+                }
+            }
+
+            for ty in y..y + trunk {
+                chunk.set_block_state(x, ty, z, log);
+            }
+
+            chunk.set_block_state(x, y - 1, z, BlockState::DIRT);
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct RawSurfaceConfig {
+    biomes: Vec<RawBiomeSurface>,
+}
+
+#[derive(Deserialize)]
+struct RawBiomeSurface {
+    biome: String,
+    #[serde(default)]
+    temperature: f64,
+    #[serde(default)]
+    humidity: f64,
+    #[serde(default = "default_top")]
+    top: String,
+    #[serde(default = "default_under")]
+    under: String,
+    #[serde(default = "default_under_depth")]
+    under_depth: u32,
+    #[serde(default = "default_underwater")]
+    underwater: String,
+    #[serde(default)]
+    banded: bool,
+    #[serde(default)]
+    vegetation_per_chunk: u32,
+    #[serde(default)]
+    vegetation: Vec<RawVegetation>,
+}
+
+fn default_top() -> String {
+    "grass_block".into()
+}
+
+fn default_under() -> String {
+    "dirt".into()
+}
+
+fn default_under_depth() -> u32 {
+    3
+}
+
+fn default_underwater() -> String {
+    "sand".into()
+}
+
+#[derive(Deserialize)]
+struct RawVegetation {
+    #[serde(default = "default_weight")]
+    weight: u32,
+    plant: Option<String>,
+    tall_plant: Option<String>,
+    column: Option<String>,
+    #[serde(default = "default_column_height")]
+    height: u32,
+    log: Option<String>,
+    leaves: Option<String>,
+    #[serde(default = "default_min_height")]
+    min_height: u32,
+    #[serde(default = "default_max_height")]
+    max_height: u32,
+}
+
+fn default_weight() -> u32 {
+    1
+}
+
+fn default_column_height() -> u32 {
+    3
+}
+
+fn default_min_height() -> u32 {
+    4
+}
+
+fn default_max_height() -> u32 {
+    6
+}
+
+fn parse_block(name: &str) -> anyhow::Result<BlockState> {
+    let name = name.strip_prefix("minecraft:").unwrap_or(name);
+
+    BlockKind::from_str(name)
+        .map(BlockKind::to_state)
+        .with_context(|| format!("unknown block \"{name}\""))
+}
+
+impl RawBiomeSurface {
+    fn resolve(self, server: &SharedServer) -> anyhow::Result<BiomeSurface> {
+        let (biome, _) = server
+            .biomes()
+            .find(|(_, b)| b.name.as_str() == self.biome || b.name.path() == self.biome)
+            .with_context(|| format!("unknown biome \"{}\"", self.biome))?;
+
+        let vegetation = self
+            .vegetation
+            .into_iter()
+            .map(|raw| {
+                let vegetation = if let Some(plant) = raw.plant {
+                    Vegetation::Plant(parse_block(&plant)?)
+                } else if let Some(plant) = raw.tall_plant {
+                    Vegetation::TallPlant(parse_block(&plant)?)
+                } else if let Some(block) = raw.column {
+                    Vegetation::Column {
+                        block: parse_block(&block)?,
+                        height: raw.height,
+                    }
+                } else if let (Some(log), Some(leaves)) = (raw.log, raw.leaves) {
+                    Vegetation::Tree {
+                        log: parse_block(&log)?,
+                        leaves: parse_block(&leaves)?,
+                        min_height: raw.min_height,
+                        max_height: raw.max_height,
+                    }
+                } else {
+                    bail!("vegetation entry has no plant, tall_plant, column, or log and leaves")
+                };
+
+                Ok(WeightedVegetation::new(raw.weight, vegetation))
+            })
+            .collect::<anyhow::Result<_>>()?;
+
+        Ok(BiomeSurface {
+            biome,
+            temperature: self.temperature,
+            humidity: self.humidity,
+            top: parse_block(&self.top)?,
+            under: parse_block(&self.under)?,
+            under_depth: self.under_depth,
+            underwater: parse_block(&self.underwater)?,
+            banded: self.banded,
+            vegetation_per_chunk: self.vegetation_per_chunk,
+            vegetation,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn weighted_vegetation() {
+        let mut biome = BiomeSurface::new(BiomeId::default());
+        biome.vegetation = vec![
+            WeightedVegetation::new(0, Vegetation::Plant(BlockState::POPPY)),
+            WeightedVegetation::new(1, Vegetation::Plant(BlockState::GRASS)),
+        ];
+
+        let mut rng = Xoroshiro::from_seed_i64(3);
+        for _ in 0..100 {
+            assert_eq!(
+                biome.pick_vegetation(&mut rng),
+                Some(&Vegetation::Plant(BlockState::GRASS))
+            );
+        }
+
+        biome.vegetation.clear();
+        assert_eq!(biome.pick_vegetation(&mut rng), None);
+    }
+
+    #[test]
+    fn banded_surface() {
+        let bands = Bands::new(WorldSeed(7));
+        let mut biome = BiomeSurface::new(BiomeId::default());
+        biome.top = BlockState::RED_SAND;
+        biome.banded = true;
+
+        let mut chunk = Chunk::new(1);
+        for y in 0..16 {
+            chunk.set_block_state(0, y, 0, BlockState::STONE);
+        }
+
+        build_surface(&mut chunk, [0, 0], 80, 70, 63, &biome, &bands);
+
+        assert_eq!(chunk.block_state(0, 10, 0), BlockState::RED_SAND);
+        assert_eq!(chunk.block_state(0, 9, 0), bands.at(79));
+        assert_eq!(chunk.block_state(0, 6, 0), BlockState::STONE);
+    }
+}
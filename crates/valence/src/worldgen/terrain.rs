@@ -0,0 +1,408 @@
+//! The [`DefaultGenerator`].
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use parking_lot::Mutex;
+use valence_protocol::BlockState;
+
+use super::carver::{carve_noise_caves, carve_ravines, CarverConfig, CaveNoise};
+use super::noise::OctaveNoise;
+use super::ore::{place_ores, OreConfig};
+use super::structure::{BoundingBox, StructureSet, StructureStart};
+use super::surface::{build_surface, place_vegetation, Bands, SurfaceConfig};
+use super::ChunkGenerator;
+use crate::biome::BiomeId;
+use crate::instance::Chunk;
+use crate::random::WorldSeed;
+use crate::view::ChunkPos;
+
+/// The generation step of ores, used to derive their random number generator.
+const ORE_STEP: i32 = 6;
+/// The generation step of vegetation.
+const VEGETATION_STEP: i32 = 9;
+/// The number of chunks whose structure starts are cached before the cache is
+/// cleared.
+const STRUCTURE_CACHE_SIZE: usize = 4096;
+
+/// A [`ChunkGenerator`] which produces rolling hills and oceans with caves,
+/// ravines, ores, and vegetation, suitable for survival gameplay out of the
+/// box.
+///
+/// The surface of the terrain depends on the biomes in its
+/// [`SurfaceConfig`].
+///
+/// Everything generated is derived from the [`WorldSeed`], so the same seed
+/// always produces the same terrain.
+#[derive(Clone, Debug)]
+pub struct DefaultGenerator {
+    seed: WorldSeed,
+    min_y: i32,
+    sea_level: i32,
+    ores: Vec<OreConfig>,
+    carvers: CarverConfig,
+    surface: SurfaceConfig,
+    structures: StructureSet,
+    /// Structures starting in chunks, since every chunk a structure reaches
+    /// needs to assemble it.
+    structure_cache: Arc<Mutex<HashMap<ChunkPos, Arc<[StructureStart]>>>>,
+    continents: OctaveNoise,
+    hills: OctaveNoise,
+    temperature: OctaveNoise,
+    humidity: OctaveNoise,
+    cave_noise: CaveNoise,
+    bands: Bands,
+}
+
+impl DefaultGenerator {
+    /// Creates a generator for a dimension with the default height of the
+    /// overworld.
+    pub fn new(seed: WorldSeed) -> Self {
+        let positional = seed.positional();
+
+        Self {
+            seed,
+            min_y: -64,
+            sea_level: 63,
+            ores: OreConfig::overworld(),
+            carvers: CarverConfig::default(),
+            surface: SurfaceConfig::default(),
+            structures: StructureSet::default(),
+            structure_cache: Default::default(),
+            continents: OctaveNoise::new(&mut positional.by_name("valence:continents"), 4, 512.0),
+            hills: OctaveNoise::new(&mut positional.by_name("valence:hills"), 4, 96.0),
+            temperature: OctaveNoise::new(&mut positional.by_name("valence:temperature"), 3, 384.0),
+            humidity: OctaveNoise::new(&mut positional.by_name("valence:humidity"), 3, 384.0),
+            cave_noise: CaveNoise::new(seed),
+            bands: Bands::new(seed),
+        }
+    }
+
+    /// Sets the height of the bottom of the dimension the chunks are generated
+    /// for. This must match [`Dimension::min_y`].
+    ///
+    /// [`Dimension::min_y`]: crate::dimension::Dimension::min_y
+    #[must_use]
+    pub fn with_min_y(mut self, min_y: i32) -> Self {
+        self.min_y = min_y;
+        self
+    }
+
+    /// Sets the height up to which oceans are filled with water.
+    #[must_use]
+    pub fn with_sea_level(mut self, sea_level: i32) -> Self {
+        self.sea_level = sea_level;
+        self
+    }
+
+    /// Replaces the ores which are placed. See [`OreConfig::overworld`] for
+    /// the defaults.
+    #[must_use]
+    pub fn with_ores(mut self, ores: Vec<OreConfig>) -> Self {
+        self.ores = ores;
+        self
+    }
+
+    #[must_use]
+    pub fn with_carvers(mut self, carvers: CarverConfig) -> Self {
+        self.carvers = carvers;
+        self
+    }
+
+    /// Sets the biomes placed by the generator and their surfaces.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the configuration contains no biomes.
+    #[must_use]
+    #[track_caller]
+    pub fn with_surface(mut self, surface: SurfaceConfig) -> Self {
+        assert!(
+            !surface.biomes.is_empty(),
+            "surface configuration contains no biomes"
+        );
+        self.surface = surface;
+        self
+    }
+
+    /// Sets the jigsaw structures placed by the generator.
+    #[must_use]
+    pub fn with_structures(mut self, structures: StructureSet) -> Self {
+        self.structures = structures;
+        self.structure_cache = Default::default();
+        self
+    }
+
+    pub fn seed(&self) -> WorldSeed {
+        self.seed
+    }
+
+    pub fn min_y(&self) -> i32 {
+        self.min_y
+    }
+
+    pub fn sea_level(&self) -> i32 {
+        self.sea_level
+    }
+
+    /// Returns the height of the highest terrain block in the column at the
+    /// given block position, before caves are carved.
+    pub fn surface_height(&self, x: i32, z: i32) -> i32 {
+        let (x, z) = (x as f64, z as f64);
+
+        let continentalness = self.continents.sample_2d(x, z);
+        let hills = (self.hills.sample_2d(x, z) + 1.0) / 2.0;
+
+        // Land rises out of the oceans and gets hillier further inland.
+        let base = self.sea_level as f64 + continentalness * 48.0;
+        let hilliness = continentalness.clamp(0.0, 1.0) * 48.0 + 6.0;
+
+        (base + hills * hilliness) as i32
+    }
+
+    /// Returns the biome at the given block position.
+    pub fn biome_at(&self, x: i32, z: i32) -> BiomeId {
+        self.surface.biomes[self.biome_index(x, z)].biome
+    }
+
+    /// Returns the index of the biome of the 4x4 biome cell containing the
+    /// block position.
+    fn biome_index(&self, x: i32, z: i32) -> usize {
+        let x = (x.div_euclid(4) * 4 + 2) as f64;
+        let z = (z.div_euclid(4) * 4 + 2) as f64;
+
+        let temperature = self.temperature.sample_2d(x, z) * 2.0;
+        let humidity = self.humidity.sample_2d(x, z) * 2.0;
+
+        self.surface.closest_biome(temperature, humidity)
+    }
+
+    /// Returns the structures which start in the chunk at `pos`.
+    pub fn structure_starts(&self, pos: ChunkPos) -> Arc<[StructureStart]> {
+        if let Some(starts) = self.structure_cache.lock().get(&pos) {
+            return starts.clone();
+        }
+
+        let starts: Arc<[StructureStart]> = self
+            .structures
+            .starts_in(self.seed, pos, |x, z| self.surface_height(x, z))
+            .into();
+
+        let mut cache = self.structure_cache.lock();
+        if cache.len() >= STRUCTURE_CACHE_SIZE {
+            cache.clear();
+        }
+        cache.insert(pos, starts.clone());
+
+        starts
+    }
+
+    /// Finds the structure with the given name whose start is closest to
+    /// `near`, searching up to `radius` placement regions away. Returns the
+    /// bounding box of the structure.
+    ///
+    /// This only assembles the structures in question and does not generate
+    /// any chunks.
+    pub fn locate(&self, structure: &str, near: ChunkPos, radius: i32) -> Option<BoundingBox> {
+        let structure = self
+            .structures
+            .structures
+            .iter()
+            .find(|s| s.name == structure)?;
+        let spacing = structure.spacing.max(1);
+        let (center_x, center_z) = (near.x.div_euclid(spacing), near.z.div_euclid(spacing));
+
+        let mut candidates = vec![];
+        for rz in center_z - radius..=center_z + radius {
+            for rx in center_x - radius..=center_x + radius {
+                let chunk = structure.start_chunk(self.seed, rx, rz);
+                candidates.push((chunk.distance_squared(near), chunk));
+            }
+        }
+
+        candidates.sort_unstable_by_key(|(dist, _)| *dist);
+
+        candidates.into_iter().find_map(|(_, chunk)| {
+            self.structure_starts(chunk)
+                .iter()
+                .find(|start| start.structure == structure.name)
+                .map(|start| start.bounds)
+        })
+    }
+
+    /// Places the blocks of the structures which reach into the chunk.
+    fn place_structures(&self, pos: ChunkPos, chunk: &mut Chunk) {
+        let height = chunk.section_count() as i32 * 16;
+        let reach = self.structures.chunk_reach();
+
+        for z in pos.z - reach..=pos.z + reach {
+            for x in pos.x - reach..=pos.x + reach {
+                let start_pos = ChunkPos::new(x, z);
+
+                // Only assemble structures in chunks they can start in.
+                let may_start = self.structures.structures.iter().any(|s| {
+                    let spacing = s.spacing.max(1);
+                    s.start_chunk(self.seed, x.div_euclid(spacing), z.div_euclid(spacing))
+                        == start_pos
+                });
+
+                if !may_start {
+                    continue;
+                }
+
+                for start in self.structure_starts(start_pos).iter() {
+                    for piece in &start.pieces {
+                        if !piece.bounds.intersects_chunk(pos) {
+                            continue;
+                        }
+
+                        for (block_pos, state) in piece.blocks() {
+                            let bx = block_pos.x - pos.x * 16;
+                            let by = block_pos.y - self.min_y;
+                            let bz = block_pos.z - pos.z * 16;
+
+                            if (0..16).contains(&bx)
+                                && (0..height).contains(&by)
+                                && (0..16).contains(&bz)
+                            {
+                                chunk.set_block_state(bx as usize, by as usize, bz as usize, state);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    fn fill_terrain(&self, pos: ChunkPos, chunk: &mut Chunk, surface: &[i32; 256]) {
+        let height = chunk.section_count() as i32 * 16;
+        let mut rng = self.seed.positional().at_chunk(pos);
+
+        for z in 0..16 {
+            for x in 0..16 {
+                let top = surface[x + z * 16];
+                let bedrock_top = rng.next_i32_bounded(5);
+                let deepslate_top = rng.next_i32_bounded(8);
+
+                for y in 0..height {
+                    let world_y = y + self.min_y;
+
+                    let block = if y <= bedrock_top {
+                        BlockState::BEDROCK
+                    } else if world_y > top {
+                        if world_y <= self.sea_level {
+                            BlockState::WATER
+                        } else {
+                            break;
+                        }
+                    } else if world_y < deepslate_top {
+                        BlockState::DEEPSLATE
+                    } else {
+                        BlockState::STONE
+                    };
+
+                    chunk.set_block_state(x, y as usize, z, block);
+                }
+            }
+        }
+    }
+}
+
+impl ChunkGenerator for DefaultGenerator {
+    fn generate(&self, pos: ChunkPos, chunk: &mut Chunk) {
+        let surface: [i32; 256] = std::array::from_fn(|i| {
+            self.surface_height(pos.x * 16 + (i % 16) as i32, pos.z * 16 + (i / 16) as i32)
+        });
+
+        let biomes: [usize; 16] = std::array::from_fn(|i| {
+            self.biome_index(
+                pos.x * 16 + (i % 4) as i32 * 4,
+                pos.z * 16 + (i / 4) as i32 * 4,
+            )
+        });
+        let biome_at = |x: usize, z: usize| biomes[x / 4 + z / 4 * 4];
+
+        self.fill_terrain(pos, chunk, &surface);
+
+        for (i, &biome) in biomes.iter().enumerate() {
+            for y in 0..chunk.section_count() * 4 {
+                chunk.set_biome(i % 4, y, i / 4, self.surface.biomes[biome].biome);
+            }
+        }
+
+        for z in 0..16 {
+            for x in 0..16 {
+                build_surface(
+                    chunk,
+                    [x, z],
+                    surface[x + z * 16],
+                    self.min_y,
+                    self.sea_level,
+                    &self.surface.biomes[biome_at(x, z)],
+                    &self.bands,
+                );
+            }
+        }
+
+        carve_noise_caves(
+            chunk,
+            pos,
+            self.min_y,
+            &surface,
+            &self.carvers,
+            &self.cave_noise,
+        );
+        carve_ravines(chunk, pos, self.min_y, self.seed, &self.carvers);
+
+        let (_, decoration_seed) = self.seed.decoration_random(pos);
+        let mut rng = WorldSeed::feature_random(decoration_seed, 0, ORE_STEP);
+        place_ores(chunk, self.min_y, &self.ores, &mut rng);
+
+        self.place_structures(pos, chunk);
+
+        let mut rng = WorldSeed::feature_random(decoration_seed, 0, VEGETATION_STEP);
+        place_vegetation(
+            chunk,
+            self.min_y,
+            &surface,
+            biome_at,
+            &self.surface.biomes,
+            &mut rng,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn same_seed_same_chunk() {
+        let generator = DefaultGenerator::new(WorldSeed(42));
+        let pos = ChunkPos::new(3, -7);
+
+        let mut a = Chunk::new(24);
+        let mut b = Chunk::new(24);
+
+        generator.generate(pos, &mut a);
+        DefaultGenerator::new(WorldSeed(42)).generate(pos, &mut b);
+
+        let mut ores = 0;
+
+        for y in 0..24 * 16 {
+            for z in 0..16 {
+                for x in 0..16 {
+                    let block = a.block_state(x, y, z);
+                    assert_eq!(block, b.block_state(x, y, z));
+
+                    if block.to_kind().to_str().ends_with("_ore") {
+                        ores += 1;
+                    }
+                }
+            }
+        }
+
+        assert!(ores > 0);
+        assert_eq!(a.block_state(0, 0, 0), BlockState::BEDROCK);
+    }
+}
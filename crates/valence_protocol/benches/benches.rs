@@ -11,6 +11,7 @@ use valence_protocol::text::Color;
 use valence_protocol::{
     encode_packet, encode_packet_compressed, ByteAngle, Decode, Encode, ItemKind,
     LengthPrefixedArray, PacketDecoder, PacketEncoder, TextFormat, VarInt, VarLong,
+    DEFAULT_COMPRESSION_LEVEL,
 };
 
 criterion_group! {
@@ -112,7 +113,7 @@ fn packets(c: &mut Criterion) {
     const SKY_LIGHT_ARRAYS: [LengthPrefixedArray<u8, 2048>; 26] =
         [LengthPrefixedArray([0xff; 2048]); 26];
 
-    let chunk_data_packet = ChunkDataAndUpdateLightEncode {
+    let chunk_data_packet: ChunkDataAndUpdateLightEncode = ChunkDataAndUpdateLightEncode {
         chunk_x: 123,
         chunk_z: 456,
         heightmaps: &compound! {
@@ -276,7 +277,14 @@ fn packets(c: &mut Criterion) {
     let mut scratch = vec![];
 
     packet_buf.clear();
-    encode_packet_compressed(&mut packet_buf, &chunk_data_packet, 256, &mut scratch).unwrap();
+    encode_packet_compressed(
+        &mut packet_buf,
+        &chunk_data_packet,
+        256,
+        DEFAULT_COMPRESSION_LEVEL,
+        &mut scratch,
+    )
+    .unwrap();
 
     c.bench_function("decode_chunk_data_compressed", |b| {
         b.iter(|| {
@@ -296,6 +304,7 @@ fn packets(c: &mut Criterion) {
         &mut packet_buf,
         &tab_list_header_footer_packet,
         256,
+        DEFAULT_COMPRESSION_LEVEL,
         &mut scratch,
     )
     .unwrap();
@@ -314,7 +323,14 @@ fn packets(c: &mut Criterion) {
     });
 
     packet_buf.clear();
-    encode_packet_compressed(&mut packet_buf, &spawn_entity_packet, 256, &mut scratch).unwrap();
+    encode_packet_compressed(
+        &mut packet_buf,
+        &spawn_entity_packet,
+        256,
+        DEFAULT_COMPRESSION_LEVEL,
+        &mut scratch,
+    )
+    .unwrap();
 
     c.bench_function("decode_spawn_entity_compressed", |b| {
         b.iter(|| {
@@ -23,6 +23,12 @@ struct Block {
     properties: Vec<Property>,
     default_state_id: u16,
     states: Vec<State>,
+    /// Missing from data extracted before model offsets were added, in which
+    /// case the block is not offset.
+    #[serde(default)]
+    max_horizontal_offset: f64,
+    #[serde(default)]
+    max_vertical_offset: f64,
 }
 
 impl Block {
@@ -55,6 +61,10 @@ struct State {
     opaque: bool,
     replaceable: bool,
     collision_shapes: Vec<u16>,
+    /// Missing from data extracted before outline shapes were added, in which
+    /// case the collision shapes are used.
+    #[serde(default)]
+    outline_shapes: Option<Vec<u16>>,
     block_entity_type: Option<u32>,
 }
 
@@ -88,6 +98,19 @@ pub fn build() -> anyhow::Result<TokenStream> {
         })
         .collect::<TokenStream>();
 
+    let kind_to_max_model_offset_arms = blocks
+        .iter()
+        .filter(|b| b.max_horizontal_offset != 0.0 || b.max_vertical_offset != 0.0)
+        .map(|b| {
+            let kind = ident(b.name.to_pascal_case());
+            let max_horizontal_offset = b.max_horizontal_offset;
+            let max_vertical_offset = b.max_vertical_offset;
+            quote! {
+                Self::#kind => [#max_horizontal_offset, #max_vertical_offset],
+            }
+        })
+        .collect::<TokenStream>();
+
     let state_to_kind_arms = blocks
         .iter()
         .map(|b| {
@@ -181,6 +204,19 @@ pub fn build() -> anyhow::Result<TokenStream> {
         })
         .collect::<TokenStream>();
 
+    let state_to_outline_shapes_arms = blocks
+        .iter()
+        .flat_map(|b| {
+            b.states.iter().map(|s| {
+                let id = s.id;
+                let outline_shapes = s.outline_shapes.as_ref().unwrap_or(&s.collision_shapes);
+                quote! {
+                    #id => &[#(#outline_shapes),*],
+                }
+            })
+        })
+        .collect::<TokenStream>();
+
     let get_arms = blocks
         .iter()
         .filter(|&b| !b.properties.is_empty())
@@ -650,6 +686,11 @@ pub fn build() -> anyhow::Result<TokenStream> {
                 #(#shapes,)*
             ];
 
+            /// Returns the boxes making up the collision shape of this block
+            /// state. The boxes are `[min_x, min_y, min_z, max_x, max_y, max_z]`
+            /// relative to the block's position and may extend past the block,
+            /// as is the case for fences. The boxes do not include the
+            /// [model offset](BlockKind::max_model_offset) of the block.
             pub fn collision_shapes(self) -> impl ExactSizeIterator<Item = [f64; 6]> + FusedIterator + Clone {
                 let shape_idxs: &'static [u16] = match self.0 {
                     #state_to_collision_shapes_arms
@@ -659,6 +700,19 @@ pub fn build() -> anyhow::Result<TokenStream> {
                 shape_idxs.into_iter().map(|idx| Self::SHAPES[*idx as usize])
             }
 
+            /// Returns the boxes making up the outline of this block state,
+            /// which is the shape players target with their cursor. Unlike
+            /// the collision shape, this is not empty for blocks such as
+            /// flowers.
+            pub fn outline_shapes(self) -> impl ExactSizeIterator<Item = [f64; 6]> + FusedIterator + Clone {
+                let shape_idxs: &'static [u16] = match self.0 {
+                    #state_to_outline_shapes_arms
+                    _ => &[],
+                };
+
+                shape_idxs.into_iter().map(|idx| Self::SHAPES[*idx as usize])
+            }
+
             pub const fn luminance(self) -> u8 {
                 match self.0 {
                     #state_to_luminance_arms
@@ -724,6 +778,17 @@ pub fn build() -> anyhow::Result<TokenStream> {
                 }
             }
 
+            /// Returns the largest horizontal and vertical distances the model
+            /// and shapes of this block kind are moved by. Blocks like flowers
+            /// and grass are moved by a random offset which depends on their
+            /// position.
+            pub const fn max_model_offset(self) -> [f64; 2] {
+                match self {
+                    #kind_to_max_model_offset_arms
+                    _ => [0.0, 0.0],
+                }
+            }
+
             /// Converts a block kind to its corresponding item kind.
             ///
             /// [`ItemKind::Air`] is used to indicate the absence of an item.
@@ -12,17 +12,73 @@ use crate::{DecodePacket, Encode, EncodePacket, Result, MAX_PACKET_SIZE};
 #[cfg(feature = "encryption")]
 type Cipher = cfb8::Cfb8<aes::Aes128>;
 
-#[derive(Default)]
+/// The zlib compression level packets are compressed with unless configured
+/// otherwise. This is a good tradeoff between speed and size.
+#[cfg(feature = "compression")]
+pub const DEFAULT_COMPRESSION_LEVEL: u32 = 4;
+
+/// Statistics about the packets compressed by a [`PacketEncoder`].
+#[cfg(feature = "compression")]
+#[derive(Copy, Clone, PartialEq, Eq, Default, Debug)]
+pub struct CompressionStats {
+    /// The number of packets which exceeded the threshold and were
+    /// compressed.
+    pub compressed_packets: u64,
+    /// The number of packets which were below the threshold and sent
+    /// uncompressed.
+    pub uncompressed_packets: u64,
+    /// The total size of the compressed packets before compression.
+    pub bytes_before: u64,
+    /// The total size of the compressed packets after compression.
+    pub bytes_after: u64,
+}
+
+#[cfg(feature = "compression")]
+impl CompressionStats {
+    /// Returns the size of the compressed packets after compression relative
+    /// to their size before compression, or `1.0` if no packets were
+    /// compressed.
+    pub fn ratio(&self) -> f64 {
+        if self.bytes_before == 0 {
+            1.0
+        } else {
+            self.bytes_after as f64 / self.bytes_before as f64
+        }
+    }
+}
+
 pub struct PacketEncoder {
     buf: BytesMut,
     #[cfg(feature = "compression")]
     compress_buf: Vec<u8>,
     #[cfg(feature = "compression")]
     compression_threshold: Option<u32>,
+    #[cfg(feature = "compression")]
+    compression_level: u32,
+    #[cfg(feature = "compression")]
+    compression_stats: CompressionStats,
     #[cfg(feature = "encryption")]
     cipher: Option<Cipher>,
 }
 
+impl Default for PacketEncoder {
+    fn default() -> Self {
+        Self {
+            buf: BytesMut::new(),
+            #[cfg(feature = "compression")]
+            compress_buf: vec![],
+            #[cfg(feature = "compression")]
+            compression_threshold: None,
+            #[cfg(feature = "compression")]
+            compression_level: DEFAULT_COMPRESSION_LEVEL,
+            #[cfg(feature = "compression")]
+            compression_stats: CompressionStats::default(),
+            #[cfg(feature = "encryption")]
+            cipher: None,
+        }
+    }
+}
+
 impl PacketEncoder {
     pub fn new() -> Self {
         Self::default()
@@ -72,7 +128,10 @@ impl PacketEncoder {
             use flate2::Compression;
 
             if data_len > threshold as usize {
-                let mut z = ZlibEncoder::new(&self.buf[start_len..], Compression::new(4));
+                let mut z = ZlibEncoder::new(
+                    &self.buf[start_len..],
+                    Compression::new(self.compression_level),
+                );
 
                 self.compress_buf.clear();
 
@@ -94,6 +153,10 @@ impl PacketEncoder {
                 VarInt(packet_len as i32).encode(&mut writer)?;
                 VarInt(data_len as i32).encode(&mut writer)?;
                 self.buf.extend_from_slice(&self.compress_buf);
+
+                self.compression_stats.compressed_packets += 1;
+                self.compression_stats.bytes_before += data_len as u64;
+                self.compression_stats.bytes_after += self.compress_buf.len() as u64;
             } else {
                 let data_len_size = 1;
                 let packet_len = data_len_size + data_len;
@@ -116,6 +179,8 @@ impl PacketEncoder {
                 VarInt(packet_len as i32).encode(&mut front)?;
                 // Zero for no compression on this packet.
                 VarInt(0).encode(front)?;
+
+                self.compression_stats.uncompressed_packets += 1;
             }
 
             return Ok(());
@@ -160,6 +225,24 @@ impl PacketEncoder {
         self.compression_threshold = threshold;
     }
 
+    /// Sets the zlib compression level from 0 (fastest) to 9 (smallest) used
+    /// for packets above the compression threshold.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the level is greater than 9.
+    #[cfg(feature = "compression")]
+    pub fn set_compression_level(&mut self, level: u32) {
+        assert!(level <= 9, "invalid compression level of {level}");
+        self.compression_level = level;
+    }
+
+    /// Returns statistics about the packets compressed by this encoder.
+    #[cfg(feature = "compression")]
+    pub fn compression_stats(&self) -> CompressionStats {
+        self.compression_stats
+    }
+
     /// Encrypts all future packets **and any packets that have
     /// not been [taken] yet.**
     ///
@@ -205,6 +288,7 @@ pub fn encode_packet_compressed<P>(
     buf: &mut Vec<u8>,
     pkt: &P,
     threshold: u32,
+    level: u32,
     scratch: &mut Vec<u8>,
 ) -> Result<()>
 where
@@ -222,7 +306,7 @@ where
     let data_len = buf.len() - start_len;
 
     if data_len > threshold as usize {
-        let mut z = ZlibEncoder::new(&buf[start_len..], Compression::new(4));
+        let mut z = ZlibEncoder::new(&buf[start_len..], Compression::new(level));
 
         scratch.clear();
 
@@ -598,6 +682,42 @@ mod tests {
             .check("third");
     }
 
+    #[cfg(feature = "compression")]
+    #[test]
+    fn compression_levels_and_stats() {
+        for level in [0, DEFAULT_COMPRESSION_LEVEL, 9] {
+            let mut enc = PacketEncoder::new();
+            enc.set_compression(Some(512));
+            enc.set_compression_level(level);
+
+            enc.append_packet(&TestPacket::new(&"a".repeat(1000)))
+                .unwrap();
+            enc.append_packet(&TestPacket::new("")).unwrap();
+
+            let stats = enc.compression_stats();
+            assert_eq!(stats.compressed_packets, 1);
+            assert_eq!(stats.uncompressed_packets, 1);
+            assert!(stats.bytes_before > 1000);
+
+            if level > 0 {
+                assert!(stats.ratio() < 1.0);
+            }
+
+            let mut dec = PacketDecoder::new();
+            dec.set_compression(true);
+            dec.queue_bytes(enc.take());
+
+            dec.try_next_packet::<TestPacket>()
+                .unwrap()
+                .unwrap()
+                .check(&"a".repeat(1000));
+            dec.try_next_packet::<TestPacket>()
+                .unwrap()
+                .unwrap()
+                .check("");
+        }
+    }
+
     #[test]
     fn collect_packets_into_vec() {
         let packets = vec![
@@ -89,6 +89,9 @@ pub use var_int::VarInt;
 pub use var_long::VarLong;
 pub use {uuid, valence_nbt as nbt};
 
+// TODO: 1.19.4 adds the `interaction` entity, which makes invisible regions
+// clickable without covering them in armor stands. Support it once the
+// protocol and the extracted data are updated.
 /// The Minecraft protocol version this library currently targets.
 pub const PROTOCOL_VERSION: i32 = 761;
 
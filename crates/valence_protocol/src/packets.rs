@@ -67,6 +67,17 @@ macro_rules! packet_enum {
             }
         }
 
+        impl<$enum_life> $enum_name<$enum_life> {
+            /// Returns the name of the packet in this enum.
+            pub fn packet_name(&self) -> &'static str {
+                match self {
+                    $(
+                        Self::$packet(_) => stringify!($packet),
+                    )*
+                }
+            }
+        }
+
         impl<$enum_life> std::fmt::Debug for $enum_name<$enum_life> {
             fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
                 match self {
@@ -131,6 +142,17 @@ macro_rules! packet_enum {
             }
         }
 
+        impl $enum_name {
+            /// Returns the name of the packet in this enum.
+            pub fn packet_name(&self) -> &'static str {
+                match self {
+                    $(
+                        Self::$packet(_) => stringify!($packet),
+                    )*
+                }
+            }
+        }
+
         impl std::fmt::Debug for $enum_name {
             fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
                 match self {
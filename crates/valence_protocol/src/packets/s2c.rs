@@ -414,14 +414,20 @@ pub mod play {
         pub block_light_arrays: Vec<LengthPrefixedArray<u8, 2048>>,
     }
 
+    /// The encoding half of [`ChunkDataAndUpdateLight`].
+    ///
+    /// The section data and the block entities can be any type encoding
+    /// like a length-prefixed byte array and a length-prefixed array of
+    /// [`ChunkDataBlockEntity`] respectively, so they can be written straight
+    /// into the packet instead of being collected into buffers first.
     #[derive(Clone, Debug, Encode, EncodePacket)]
     #[packet_id = 0x20]
-    pub struct ChunkDataAndUpdateLightEncode<'a> {
+    pub struct ChunkDataAndUpdateLightEncode<'a, B = &'a [u8], E = &'a [ChunkDataBlockEntity<'a>]> {
         pub chunk_x: i32,
         pub chunk_z: i32,
         pub heightmaps: &'a Compound,
-        pub blocks_and_biomes: &'a [u8],
-        pub block_entities: &'a [ChunkDataBlockEntity<'a>],
+        pub blocks_and_biomes: B,
+        pub block_entities: E,
         pub trust_edges: bool,
         pub sky_light_mask: &'a [u64],
         pub block_light_mask: &'a [u64],
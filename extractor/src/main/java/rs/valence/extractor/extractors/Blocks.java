@@ -37,6 +37,13 @@ public class Blocks implements Main.Extractor {
             blockJson.addProperty("translation_key", block.getTranslationKey());
             blockJson.addProperty("item_id", Registries.ITEM.getRawId(block.asItem()));
 
+            // Blocks like flowers and grass are moved by a random offset which depends on their
+            // position. At the origin, the offset is the largest possible offset on every axis
+            // towards negative coordinates.
+            var modelOffset = block.getDefaultState().getModelOffset(EmptyBlockView.INSTANCE, BlockPos.ORIGIN);
+            blockJson.addProperty("max_horizontal_offset", -modelOffset.x);
+            blockJson.addProperty("max_vertical_offset", -modelOffset.y);
+
             var propsJson = new JsonArray();
             for (var prop : block.getStateManager().getProperties()) {
                 var propJson = new JsonObject();
@@ -67,7 +74,10 @@ public class Blocks implements Main.Extractor {
                 }
 
                 var collisionShapeIdxsJson = new JsonArray();
-                for (var box : state.getCollisionShape(EmptyBlockView.INSTANCE, BlockPos.ORIGIN).getBoundingBoxes()) {
+                // The shapes are written without the model offset, which is applied per position.
+                var collisionVoxelShape = state.getCollisionShape(EmptyBlockView.INSTANCE, BlockPos.ORIGIN)
+                        .offset(-modelOffset.x, -modelOffset.y, -modelOffset.z);
+                for (var box : collisionVoxelShape.getBoundingBoxes()) {
                     var collisionShape = new Shape(box.minX, box.minY, box.minZ, box.maxX, box.maxY, box.maxZ);
 
                     var idx = shapes.putIfAbsent(collisionShape, shapes.size());
@@ -76,6 +86,18 @@ public class Blocks implements Main.Extractor {
 
                 stateJson.add("collision_shapes", collisionShapeIdxsJson);
 
+                var outlineShapeIdxsJson = new JsonArray();
+                var outlineVoxelShape = state.getOutlineShape(EmptyBlockView.INSTANCE, BlockPos.ORIGIN)
+                        .offset(-modelOffset.x, -modelOffset.y, -modelOffset.z);
+                for (var box : outlineVoxelShape.getBoundingBoxes()) {
+                    var outlineShape = new Shape(box.minX, box.minY, box.minZ, box.maxX, box.maxY, box.maxZ);
+
+                    var idx = shapes.putIfAbsent(outlineShape, shapes.size());
+                    outlineShapeIdxsJson.add(Objects.requireNonNullElseGet(idx, () -> shapes.size() - 1));
+                }
+
+                stateJson.add("outline_shapes", outlineShapeIdxsJson);
+
                 for (var blockEntity : Registries.BLOCK_ENTITY_TYPE) {
                     if (blockEntity.supports(state)) {
                         stateJson.addProperty("block_entity_type", Registries.BLOCK_ENTITY_TYPE.getRawId(blockEntity));